@@ -3,9 +3,10 @@
 #![feature(extract_if)]
 #![feature(div_duration)]
 #![feature(option_take_if)]
+#![cfg_attr(test, feature(test))]
 
 mod modules;
-use std::{env::current_exe, fs, io::ErrorKind, time::{Duration, Instant}};
+use std::{collections::VecDeque, env::current_exe, fs, io::ErrorKind, time::{Duration, Instant}};
 use eframe::App;
 use egui::{widgets, Id, RichText, Ui, Widget, WidgetText};
 use egui_dock::{DockArea, DockState, TabViewer};
@@ -13,7 +14,7 @@ use lazy_static::lazy_static;
 use log::{debug, info, trace};
 use serde::{Deserialize, Serialize};
 use modules::tabs;
-use modules::{database, gui::TabVariant, map, types};
+use modules::{alerts, cw, database, dxcluster, fldigi, gui::TabVariant, js8call, map, n1mm, rbn, rigctld, solar, types, wsjtx};
 use strum::IntoEnumIterator;
 use modules::gui::Tab;
 
@@ -100,8 +101,33 @@ impl App for Gui {
         // Get a mutable reference to the gui config
         let config = &mut self.tab_viewer.config;
 
+        // Process any messages from the background services
+        wsjtx::tick(config);
+        js8call::tick(config);
+        fldigi::tick(config);
+        n1mm::tick(config);
+        cw::tick(config);
+        rigctld::tick(config);
+        dxcluster::tick(config);
+        rbn::tick(config);
+        alerts::tick(config);
+        solar::tick(config);
+
         // Check the events queue and send out the necessary events
-        while let Some((task_tab_id, event)) = config.events.pop() {
+        while let Some((task_tab_id, event)) = config.events.pop_front() {
+
+            // Broadcast contacts logged by QLog to the other loggers on the network
+            if let types::Event::ContactLogged(contact) = &event {
+                n1mm::broadcast(config, contact);
+            }
+
+            // Tune the radio
+            if let types::Event::Tune(frequency) = &event {
+                rigctld::tune(config, *frequency);
+            }
+
+            // Check the spots for alerts
+            alerts::process_event(config, &event);
 
             // The task is bound to a specific tab
             if let Some(task_tab_id) = task_tab_id {
@@ -144,7 +170,14 @@ impl App for Gui {
                             3 => "Callsign Lookup",
                             4 => "PSKReporter",
                             5 => "Settings",
-                            6.. => "Band Allocations",
+                            6 => "Band Allocations",
+                            7 => "WSJT-X",
+                            8 => "JS8Call",
+                            9 => "DX Cluster",
+                            10 => "Reverse Beacon",
+                            11 => "Solar Conditions",
+                            12 => "MUF Map",
+                            13.. => "Contacts Map",
                         };
 
                         if ui.selectable_label(false, text).clicked() {
@@ -203,6 +236,9 @@ impl App for Gui {
             });
         });
 
+        // Remember which tab has focus, so only that tab handles keyboard shortcuts
+        self.tab_viewer.config.focused_tab = self.dock_state.find_active_focused().map(|(_, tab)| tab.id());
+
         // Render the dockable area
         DockArea::new(&mut self.dock_state)
         .show(ctx, &mut self.tab_viewer);
@@ -342,7 +378,10 @@ pub struct GuiConfig {
     /// They are usually used to synchronize multiple tabs. For example, if you insert a contact into the database,
     /// the contact table tab should also be made aware of the change so it can update itself.
     #[serde(skip)]
-    pub events: Vec<(Option<Id>, types::Event)>,
+    pub events: VecDeque<(Option<Id>, types::Event)>,
+    /// The ID of the tab that has focus. Global keyboard shortcuts should only be handled by this tab.
+    #[serde(skip)]
+    pub focused_tab: Option<Id>,
     /// The FPS counter
     #[serde(skip)]
     fps_counter: FpsCounter,
@@ -356,7 +395,59 @@ pub struct GuiConfig {
     /// The map widget config
     map_config: map::Config,
     /// The callsign lookup config
-    callsign_lookup_config: tabs::callsign_lookup::Config
+    callsign_lookup_config: tabs::callsign_lookup::Config,
+    /// The WSJT-X listener. This is `None` if the listener is disabled.
+    #[serde(skip)]
+    wsjtx: Option<wsjtx::Listener>,
+    /// The WSJT-X module config
+    wsjtx_config: wsjtx::Config,
+    /// The JS8Call API client. This is `None` if the client is disabled.
+    #[serde(skip)]
+    js8call: Option<js8call::Client>,
+    /// The JS8Call module config
+    js8call_config: js8call::Config,
+    /// The fldigi client. This is `None` if the client is disabled.
+    #[serde(skip)]
+    fldigi: Option<fldigi::Client>,
+    /// The fldigi module config
+    fldigi_config: fldigi::Config,
+    /// The contact broadcast listener and sender. This is `None` if both are disabled.
+    #[serde(skip)]
+    n1mm: Option<n1mm::Node>,
+    /// The contact broadcast module config
+    n1mm_config: n1mm::Config,
+    /// The operator's own station
+    station: types::StationProfile,
+    /// The CW keyer. This is `None` if the keyer is disabled.
+    #[serde(skip)]
+    cw: Option<cw::Keyer>,
+    /// The CW keyer module config
+    cw_config: cw::Config,
+    /// The one-off commands that are being sent to the radio
+    #[serde(skip)]
+    rig: rigctld::Rig,
+    /// The rig control config
+    rig_config: rigctld::Config,
+    /// The DX cluster client. This is `None` if the client is disabled.
+    #[serde(skip)]
+    dxcluster: Option<dxcluster::Client>,
+    /// The DX cluster module config
+    dxcluster_config: dxcluster::Config,
+    /// The Reverse Beacon Network client. This is `None` if the client is disabled.
+    #[serde(skip)]
+    rbn: Option<dxcluster::Client>,
+    /// The Reverse Beacon Network module config
+    rbn_config: rbn::Config,
+    /// The alert engine, which checks spots against the log
+    #[serde(skip)]
+    alerts: alerts::Engine,
+    /// The alerts module config
+    alerts_config: alerts::Config,
+    /// The solar conditions monitor
+    #[serde(skip)]
+    solar: solar::Monitor,
+    /// The solar conditions module config
+    solar_config: solar::Config
 }
 impl Default for GuiConfig {
     fn default() -> Self {
@@ -371,12 +462,34 @@ impl Default for GuiConfig {
             notifications: Default::default(),
             notification_read: Default::default(),
             events: Default::default(),
+            focused_tab: None,
             fps_counter: Default::default(),
             add_tab_idx: Default::default(),
             distance_unit: types::DistanceUnit::Miles,
             pskreporter_config: Default::default(),
             map_config: Default::default(),
-            callsign_lookup_config: Default::default()
+            callsign_lookup_config: Default::default(),
+            wsjtx: Default::default(),
+            wsjtx_config: Default::default(),
+            js8call: Default::default(),
+            js8call_config: Default::default(),
+            fldigi: Default::default(),
+            fldigi_config: Default::default(),
+            n1mm: Default::default(),
+            n1mm_config: Default::default(),
+            station: Default::default(),
+            cw: Default::default(),
+            cw_config: Default::default(),
+            rig: Default::default(),
+            rig_config: Default::default(),
+            dxcluster: Default::default(),
+            dxcluster_config: Default::default(),
+            rbn: Default::default(),
+            rbn_config: Default::default(),
+            alerts: Default::default(),
+            alerts_config: Default::default(),
+            solar: Default::default(),
+            solar_config: Default::default()
         }
     }
 }
@@ -0,0 +1,259 @@
+//
+// Contains functions to parse ADIF (Amateur Data Interchange Format) records into contacts.
+//
+// An ADIF record is a list of fields formatted as `<NAME:LENGTH>VALUE`, terminated by `<EOR>`.
+// A file may start with a header which is terminated by `<EOH>`.
+//
+
+use std::collections::HashMap;
+use chrono::{NaiveDate, NaiveTime};
+use super::{gui, types};
+
+
+/// An ADIF record. The field names are stored in uppercase.
+pub type Record = HashMap<String, String>;
+
+/// Parses ADIF text into a list of records, skipping the header if one exists.
+///
+/// Malformed fields are skipped. A trailing record without an `<EOR>` tag is still returned.
+pub fn parse(text: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut record = Record::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        // Get the contents of the tag (e.g. `CALL:5` or `EOR`)
+        let Some(end) = rest.find('>') else { break };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        // Split the tag into the name, length, and an optional data type (e.g. `QSO_DATE:8:D`)
+        let mut parts = tag.split(':');
+        let name = parts.next().unwrap_or_default().trim().to_ascii_uppercase();
+        let length = parts.next().and_then(|l| l.trim().parse::<usize>().ok());
+
+        match (name.as_str(), length) {
+            // The end of the header; anything before this wasn't a record
+            ("EOH", _) => record.clear(),
+            // The end of a record
+            ("EOR", _) => records.push(std::mem::take(&mut record)),
+            // A field with a value
+            (_, Some(length)) => {
+                // The length is in characters, but we have to be careful not to split a UTF-8 character
+                let value: String = rest.chars().take(length).collect();
+                rest = &rest[value.len()..];
+                record.insert(name, value);
+            },
+            // A tag without a length is malformed, so it's skipped
+            _ => {}
+        }
+    }
+
+    // Keep a trailing record that wasn't terminated
+    if !record.is_empty() {
+        records.push(record);
+    }
+
+    records
+}
+
+/// Converts an ADIF record into a [types::Contact]
+pub fn to_contact(record: &Record) -> types::Contact {
+    let get = |name: &str| record.get(name).map(|s| s.trim()).unwrap_or_default();
+
+    // Parse the start date and time
+    let date = NaiveDate::parse_from_str(get("QSO_DATE"), "%Y%m%d").unwrap_or_default();
+    let time = parse_time(get("TIME_ON"));
+
+    // Calculate the duration using the end date and time, if they exist
+    let duration = match parse_time(get("TIME_OFF")) {
+        time_off if !get("TIME_OFF").is_empty() => {
+            let date_off = NaiveDate::parse_from_str(get("QSO_DATE_OFF"), "%Y%m%d").unwrap_or(date);
+            date_off.and_time(time_off).signed_duration_since(date.and_time(time)).num_seconds().max(0) as u64
+        },
+        _ => 0
+    };
+
+    // The frequency is in MHz
+    let frequency = get("FREQ").parse::<f64>().map(|f| (f * 1_000_000.0).round() as u64).unwrap_or_default();
+
+    // Use the submode (e.g. FT4) if it exists, since it's more specific than the mode (e.g. MFSK)
+    let mode = match get("SUBMODE") {
+        "" => types::Mode::from_name(get("MODE")),
+        submode => types::Mode::from_name(submode)
+    };
+
+    // Combine the name, QTH, and comment into the note
+    let note = [get("NAME"), get("QTH"), get("COMMENT"), get("NOTES")].into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" - ");
+
+    types::Contact {
+        id: None,
+        callsign: get("CALL").to_ascii_uppercase(),
+        grid: get("GRIDSQUARE").to_string(),
+        date,
+        time,
+        duration,
+        frequency,
+        mode,
+        // The power is in watts, which is what the power parser assumes when there isn't a unit
+        tx_power: parse_power(get("TX_PWR")),
+        rx_power: parse_power(get("RX_PWR")),
+        tx_rst: get("RST_SENT").to_string(),
+        rx_rst: get("RST_RCVD").to_string(),
+        note
+    }
+}
+
+/// Parses an ADIF power (in watts) into milliwatts, returning 0 if it's empty or invalid
+fn parse_power(s: &str) -> u64 {
+    match s {
+        "" => 0,
+        s => gui::power_parser(s).unwrap_or_default() as u64
+    }
+}
+
+/// Parses an ADIF time (`HHMM` or `HHMMSS`), returning midnight if it's invalid
+fn parse_time(s: &str) -> NaiveTime {
+    let format = match s.len() {
+        4 => "%H%M",
+        6 => "%H%M%S",
+        _ => return NaiveTime::default()
+    };
+    NaiveTime::parse_from_str(s, format).unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_are_parsed() {
+        let text = "Exported by fldigi <ADIF_VER:5>3.1.4 <PROGRAMID:6>fldigi <EOH>\n\
+            <CALL:5>K1ABC <QSO_DATE:8:D>20240316 <TIME_ON:4>1234 <EOR>\n\
+            <CALL:5>W1AW <EOR>";
+        let records = parse(text);
+        assert_eq!(records.len(), 2);
+
+        // The header fields aren't part of the first record, and the data type is ignored
+        assert_eq!(records[0].len(), 3);
+        assert_eq!(records[0]["CALL"], "K1ABC");
+        assert_eq!(records[0]["QSO_DATE"], "20240316");
+
+        // The length is used, even if the value is followed by other text
+        assert_eq!(records[1]["CALL"], "W1AW ");
+    }
+
+    #[test]
+    fn tags_are_case_insensitive() {
+        let records = parse("<call:5>K1ABC<Gridsquare:4>FN42<eor><Call:4>W1AW<Eor>");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["CALL"], "K1ABC");
+        assert_eq!(records[0]["GRIDSQUARE"], "FN42");
+        assert_eq!(records[1]["CALL"], "W1AW");
+
+        let records = parse("<adif_ver:5>3.1.4<eoh><call:5>K1ABC<eor>");
+        assert_eq!(records.len(), 1);
+        assert!(!records[0].contains_key("ADIF_VER"));
+    }
+
+    #[test]
+    fn missing_eor_is_a_record() {
+        let records = parse("<CALL:5>K1ABC<EOR><CALL:4>W1AW<MODE:2>CW");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["CALL"], "W1AW");
+        assert_eq!(records[1]["MODE"], "CW");
+
+        // Empty text, a header without records, and an empty record don't have any records
+        assert!(parse("").is_empty());
+        assert!(parse("<EOH>").is_empty());
+        assert!(parse("just text").is_empty());
+        assert_eq!(parse("<EOR>"), [Record::new()]);
+    }
+
+    #[test]
+    fn field_lengths_are_in_characters() {
+        // Multibyte characters count as one character, and aren't split
+        let records = parse("<NAME:6>Jürgen<QTH:9>Göteborg!<CALL:5>SM5ÅÖ<EOR>");
+        assert_eq!(records[0]["NAME"], "Jürgen");
+        assert_eq!(records[0]["QTH"], "Göteborg!");
+        assert_eq!(records[0]["CALL"], "SM5ÅÖ");
+
+        // Values longer than their length are cut short, and the rest is skipped
+        let records = parse("<CALL:3>K1ABC<MODE:2>CW<EOR>");
+        assert_eq!(records[0]["CALL"], "K1A");
+        assert_eq!(records[0]["MODE"], "CW");
+
+        // Lengths past the end of the text take the rest of it
+        let records = parse("<CALL:4>W1AW<NAME:50>Hiram");
+        assert_eq!(records[0]["NAME"], "Hiram");
+        let records = parse("<NAME:50>Jürgen");
+        assert_eq!(records[0]["NAME"], "Jürgen");
+
+        // A zero length is an empty value
+        assert_eq!(parse("<NAME:0><EOR>")[0]["NAME"], "");
+    }
+
+    #[test]
+    fn malformed_tags_are_skipped() {
+        let records = parse("<CALL>K1ABC<MODE:x>CW<:3>abc<GRIDSQUARE:4>FN42<EOR><CALL:5");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].len(), 2);
+        assert_eq!(records[0]["GRIDSQUARE"], "FN42");
+        assert_eq!(records[0][""], "abc");
+    }
+
+    #[test]
+    fn records_are_converted_to_contacts() {
+        let record = &parse("<CALL:5>k1abc<GRIDSQUARE:6>FN42ab<QSO_DATE:8>20240316<TIME_ON:4>2358\
+            <QSO_DATE_OFF:8>20240317<TIME_OFF:6>000130<FREQ:8>14.07015<MODE:4>MFSK<SUBMODE:3>FT4\
+            <TX_PWR:3>100<RX_PWR:2>5W<RST_SENT:3>-10<RST_RCVD:3>+05<NAME:3>Joe<QTH:6>Boston<EOR>")[0];
+        let contact = to_contact(record);
+
+        assert_eq!(contact.callsign, "K1ABC");
+        assert_eq!(contact.grid, "FN42ab");
+        assert_eq!(contact.date, NaiveDate::from_ymd_opt(2024, 3, 16).unwrap());
+        assert_eq!(contact.time, NaiveTime::from_hms_opt(23, 58, 0).unwrap());
+        assert_eq!(contact.duration, 210);
+        assert_eq!(contact.frequency, 14_070_150);
+        assert_eq!(contact.mode, types::Mode::FT4);
+        assert_eq!((contact.tx_power, contact.rx_power), (100_000, 5_000));
+        assert_eq!((contact.tx_rst.as_str(), contact.rx_rst.as_str()), ("-10", "+05"));
+        assert_eq!(contact.note, "Joe - Boston");
+    }
+
+    #[test]
+    fn frequencies_are_converted_to_hz() {
+        let frequency = |freq: &str| to_contact(&parse(&format!("<FREQ:{}>{freq}", freq.len()))[0]).frequency;
+        assert_eq!(frequency("14.074"), 14_074_000);
+        assert_eq!(frequency("7.0255"), 7_025_500);
+        assert_eq!(frequency("0.475"), 475_000);
+        assert_eq!(frequency("144.174000"), 144_174_000);
+        assert_eq!(frequency(" 3.573 "), 3_573_000);
+        assert_eq!(frequency("20m"), 0);
+    }
+
+    #[test]
+    fn times_are_parsed() {
+        let time = |time_on: &str| to_contact(&parse(&format!("<QSO_DATE:8>20240316<TIME_ON:{}>{time_on}", time_on.len()))[0]).time;
+        assert_eq!(time("1234"), NaiveTime::from_hms_opt(12, 34, 0).unwrap());
+        assert_eq!(time("123456"), NaiveTime::from_hms_opt(12, 34, 56).unwrap());
+        assert_eq!(time("0000"), NaiveTime::MIN);
+
+        // Invalid times are midnight
+        assert_eq!(time("2460"), NaiveTime::MIN);
+        assert_eq!(time("12345"), NaiveTime::MIN);
+        assert_eq!(time(""), NaiveTime::MIN);
+
+        // Without an end time, the duration is 0, and it's never negative
+        let duration = |text: &str| to_contact(&parse(text)[0]).duration;
+        assert_eq!(duration("<QSO_DATE:8>20240316<TIME_ON:4>1234"), 0);
+        assert_eq!(duration("<QSO_DATE:8>20240316<TIME_ON:4>1234<TIME_OFF:4>1240"), 360);
+        assert_eq!(duration("<QSO_DATE:8>20240316<TIME_ON:4>1234<TIME_OFF:4>1200"), 0);
+    }
+}
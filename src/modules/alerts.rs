@@ -0,0 +1,769 @@
+//
+// The alert engine. This checks every incoming spot (DX cluster, RBN, PSKReporter, and WSJT-X decodes) against the log,
+// and alerts the user when a spot is something they still need (e.g. a new DXCC entity or grid square).
+//
+// Rules are checked in order, and the first matching rule decides how the spot is highlighted and whether a notification or sound is raised.
+//
+
+use std::{collections::{HashMap, HashSet}, path::PathBuf, time::{Duration, Instant}};
+use anyhow::Result;
+use egui::{Color32, Ui};
+use log::{debug, error, info};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+use crate::{GuiConfig, RT};
+use super::{database::WorkedRecord, dxcc, dxcluster, types};
+
+
+/// Where a spot came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum Source {
+    #[strum(to_string = "DX Cluster")]
+    Cluster,
+    #[strum(to_string = "RBN")]
+    Rbn,
+    #[strum(to_string = "PSKReporter")]
+    PskReporter,
+    #[strum(to_string = "WSJT-X")]
+    Wsjtx
+}
+
+/// The type of station that a rule alerts for
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum AlertKind {
+    /// A DXCC entity that has never been worked
+    #[strum(to_string = "New DXCC")]
+    NewDxcc,
+    /// A DXCC entity that has never been worked on this band with this type of mode (CW, phone, or digital)
+    #[strum(to_string = "New band slot")]
+    NewBandSlot,
+    /// A grid square that has never been worked
+    #[strum(to_string = "New grid")]
+    NewGrid,
+    /// A callsign that has never been worked
+    #[strum(to_string = "New call")]
+    NewCall
+}
+
+/// A broad type of mode, used for band slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ModeClass {
+    Cw,
+    Phone,
+    Digital
+}
+impl From<&types::Mode> for ModeClass {
+    fn from(mode: &types::Mode) -> Self {
+        match mode {
+            types::Mode::CW => Self::Cw,
+            types::Mode::SSB | types::Mode::AM | types::Mode::FM => Self::Phone,
+            _ => Self::Digital
+        }
+    }
+}
+
+/// The rule that a spot matched, stored with the spot so the rules don't have to be checked every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alert {
+    /// The type of alert
+    pub kind: AlertKind,
+    /// The highlight color of the rule
+    pub color: [u8; 4]
+}
+impl Alert {
+    /// The highlight color of the alert
+    pub fn color32(&self) -> Color32 {
+        let [r, g, b, a] = self.color;
+        Color32::from_rgba_unmultiplied(r, g, b, a)
+    }
+}
+
+/// A spot that should be checked against the log
+#[derive(Debug, Clone)]
+pub struct Candidate<'a> {
+    /// Where the spot came from
+    pub source: Source,
+    /// The callsign of the spotted station
+    pub callsign: &'a str,
+    /// The frequency of the spotted station, in Hz
+    pub frequency: Option<u64>,
+    /// The mode of the spotted station
+    pub mode: Option<types::Mode>,
+    /// The grid square of the spotted station, if it's known
+    pub grid: Option<&'a str>
+}
+impl<'a> Candidate<'a> {
+    /// Creates a candidate from a DX cluster spot
+    pub fn from_cluster_spot(spot: &'a dxcluster::Spot) -> Self {
+        Self {
+            source: Source::Cluster,
+            callsign: &spot.callsign,
+            frequency: Some(spot.frequency),
+            mode: Some(spot.mode()),
+            grid: None
+        }
+    }
+
+    /// The band of the spotted station
+    fn band(&self) -> types::Band {
+        self.frequency.map_or(types::Band::All, types::Band::from_frequency)
+    }
+
+    /// The mode of the spotted station, using the band plan if it wasn't provided
+    fn mode_class(&self) -> Option<ModeClass> {
+        match (&self.mode, self.frequency) {
+            (Some(mode), _) => Some(mode.into()),
+            (None, Some(frequency)) => Some((&dxcluster::guess_mode(frequency, "")).into()),
+            (None, None) => None
+        }
+    }
+}
+
+/// What has been worked before, built from the contacts table
+#[derive(Debug, Default)]
+struct Worked {
+    /// Every callsign that has been worked
+    callsigns: HashSet<String>,
+    /// The primary prefix of every entity that has been worked
+    entities: HashSet<String>,
+    /// Every entity, band, and type of mode that has been worked
+    slots: HashSet<(String, types::Band, ModeClass)>,
+    /// Every 4 character grid square that has been worked
+    grids: HashSet<String>
+}
+impl Worked {
+    /// Builds the worked summary from the contacts, using the country file to find the entity of each callsign
+    fn new(records: &[WorkedRecord], dxcc: Option<&dxcc::Database>) -> Self {
+        let mut worked = Self::default();
+
+        for record in records {
+            let callsign = record.callsign.trim().to_ascii_uppercase();
+
+            if let Some(entity) = dxcc.and_then(|d| d.lookup(&callsign)) {
+                let band = types::Band::from_frequency(record.frequency);
+                worked.entities.insert(entity.prefix.clone());
+                worked.slots.insert((entity.prefix.clone(), band, (&record.mode).into()));
+            }
+
+            if let Some(grid) = record.grid.trim().get(..4) {
+                worked.grids.insert(grid.to_ascii_uppercase());
+            }
+
+            worked.callsigns.insert(callsign);
+        }
+
+        worked
+    }
+}
+
+/// Checks spots against the log. This lives in the GUI config so every tab can use it to highlight spots.
+#[derive(Default)]
+pub struct Engine {
+    /// The contacts that have been worked
+    records: Vec<WorkedRecord>,
+    /// What has been worked before, built from `records`
+    worked: Worked,
+    /// The country file, used to find the entity of a callsign
+    dxcc: Option<dxcc::Database>,
+    /// The task that is querying the database for the worked contacts
+    records_task: Option<Promise<Result<Vec<WorkedRecord>>>>,
+    /// Should the worked contacts be queried again once the current query finishes? The log can change while it's running.
+    reload_records: bool,
+    /// When the worked contacts last failed to load, if they failed since they last loaded. They're loaded again after a delay.
+    records_failed: Option<Instant>,
+    /// The task that is loading or downloading the country file
+    dxcc_task: Option<Promise<Result<dxcc::Database>>>,
+    /// Has the engine loaded the log and country file yet?
+    initialized: bool,
+    /// The dial frequency and mode of each WSJT-X client, used to find the frequency of decodes
+    wsjtx_status: HashMap<String, (u64, types::Mode)>,
+    /// When each station was last alerted for, so the same spot doesn't raise an alert every time it's posted
+    recent: HashMap<(String, types::Band), Instant>,
+    /// The rules the stored alerts were checked with, used to notice when the rules are edited
+    checked_rules: Option<(bool, Vec<Rule>)>,
+    /// Incremented whenever the log or the rules change, so tabs know to check their stored spots again
+    generation: u64,
+    /// Incremented whenever the worked contacts are loaded, so layers know when the log changed
+    records_version: u64
+}
+impl Engine {
+    /// How long to wait before loading the worked contacts again if they failed to load
+    const RECORDS_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+    /// Returns the first enabled rule that matches the spot, if any
+    pub fn check<'a>(&self, config: &'a Config, candidate: &Candidate) -> Option<&'a Rule> {
+        if !config.enabled || !self.initialized {
+            return None;
+        }
+        config.rules.iter().find(|rule| rule.applies_to(candidate) && self.needed(rule.kind, candidate))
+    }
+
+    /// Checks a spot without raising an alert, returning the alert that should be stored with the spot
+    pub fn alert(&self, config: &Config, candidate: &Candidate) -> Option<Alert> {
+        self.check(config, candidate).map(Rule::alert)
+    }
+
+    /// Changes whenever the stored alerts may be out of date
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns true if the spot is needed for the provided type of alert
+    fn needed(&self, kind: AlertKind, candidate: &Candidate) -> bool {
+        let callsign = candidate.callsign.to_ascii_uppercase();
+        let entity = self.dxcc.as_ref().and_then(|d| d.lookup(&callsign));
+
+        match kind {
+            AlertKind::NewDxcc => entity.is_some_and(|e| !self.worked.entities.contains(&e.prefix)),
+            AlertKind::NewBandSlot => {
+                let band = candidate.band();
+                match (entity, candidate.mode_class()) {
+                    (Some(entity), Some(mode)) if band != types::Band::All => !self.worked.slots.contains(&(entity.prefix.clone(), band, mode)),
+                    _ => false
+                }
+            },
+            AlertKind::NewGrid => candidate.grid
+                .and_then(|g| g.get(..4))
+                .is_some_and(|g| !self.worked.grids.contains(&g.to_ascii_uppercase())),
+            AlertKind::NewCall => !self.worked.callsigns.contains(&callsign)
+        }
+    }
+
+    /// The callsign, grid, frequency, and mode of every logged contact
+    pub fn records(&self) -> &[WorkedRecord] {
+        &self.records
+    }
+
+    /// Changes whenever the worked contacts are loaded, including when a contact is edited or deleted
+    pub fn records_version(&self) -> u64 {
+        self.records_version
+    }
+
+    /// The loaded country file, if any
+    pub fn dxcc(&self) -> Option<&dxcc::Database> {
+        self.dxcc.as_ref()
+    }
+
+    /// Is the country file currently being loaded or downloaded?
+    pub fn dxcc_loading(&self) -> bool {
+        self.dxcc_task.is_some()
+    }
+
+    /// Loads the country file from disk
+    pub fn load_dxcc(&mut self, path: PathBuf) {
+        let _eg = RT.enter();
+        self.dxcc_task = Some(Promise::spawn_blocking(move || dxcc::Database::load(&path)));
+    }
+
+    /// Downloads the latest country file, saving it to the provided path
+    pub fn download_dxcc(&mut self, url: String, path: PathBuf) {
+        let _eg = RT.enter();
+        self.dxcc_task = Some(Promise::spawn_async(dxcc::Database::download(url, path)));
+    }
+
+    /// Queries the database for the worked contacts, or queues another query if one is already running
+    fn load_records(&mut self, db: &super::database::DatabaseInterface) {
+        if self.records_task.is_none() {
+            self.reload_records = false;
+            self.records_task = Some(db.get_worked_promise());
+        } else {
+            self.reload_records = true;
+        }
+    }
+
+    /// Stores the result of loading the worked contacts. If they failed to load, the previous contacts are kept.
+    fn set_records(&mut self, result: Result<Vec<WorkedRecord>>) -> Result<()> {
+        match result {
+            Ok(records) => {
+                self.records = records;
+                self.records_version += 1;
+                self.records_failed = None;
+                Ok(())
+            },
+            Err(err) => {
+                self.records_failed = Some(Instant::now());
+                Err(err)
+            }
+        }
+    }
+
+    /// Rebuilds the worked summary from the worked contacts and the country file.
+    ///
+    /// Nothing is checked until the worked contacts have loaded, since every spot would look new without them.
+    fn rebuild(&mut self) {
+        if self.records_version == 0 {
+            return;
+        }
+        self.worked = Worked::new(&self.records, self.dxcc.as_ref());
+        self.initialized = true;
+        self.generation += 1;
+    }
+}
+impl std::fmt::Debug for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Engine")
+        .field("records", &self.records.len())
+        .field("dxcc", &self.dxcc.as_ref().map(|d| d.entity_count()))
+        .field("initialized", &self.initialized)
+        .field("recent", &self.recent.len())
+        .finish()
+    }
+}
+
+/// Loads the log and country file, and processes any finished loading tasks.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+    let engine = &mut config.alerts;
+
+    // Load the log and country file the first time this is called
+    if !engine.initialized && engine.records_task.is_none() && engine.dxcc_task.is_none() && engine.records_failed.is_none() {
+        engine.load_records(&config.db_api);
+        engine.load_dxcc(config.alerts_config.cty_path());
+    }
+
+    let mut changed = false;
+
+    // Process the worked contacts
+    if let Some(task) = engine.records_task.take_if(|t| t.ready().is_some()) {
+        let first_failure = engine.records_failed.is_none();
+        match engine.set_records(task.block_and_take()) {
+            Ok(()) => changed = true,
+            Err(err) => {
+                // Loading is retried, so only the first failure is shown
+                error!("Failed to get worked contacts: {err}");
+                if first_failure {
+                    config.notification_read = false;
+                    config.notifications.push(types::Notification::Error(format!("Failed to load the log for alerts: {err}")));
+                }
+            }
+        }
+    }
+
+    // Process the country file
+    if let Some(task) = engine.dxcc_task.take_if(|t| t.ready().is_some()) {
+        match task.block_and_take() {
+            Ok(dxcc) => {
+                info!("Loaded {} DXCC entities", dxcc.entity_count());
+                engine.dxcc = Some(dxcc);
+            },
+            Err(err) => {
+                // The country file doesn't exist until it's downloaded, so this isn't worth notifying the user about on startup
+                debug!("Failed to load the country file: {err}");
+                if engine.initialized {
+                    config.notification_read = false;
+                    config.notifications.push(types::Notification::Error(format!("Failed to load the country file: {err}")));
+                }
+            }
+        }
+        changed = true;
+    }
+
+    // Rebuild the worked summary once everything has loaded
+    if changed && engine.records_task.is_none() && engine.dxcc_task.is_none() {
+        engine.rebuild();
+    }
+
+    // The log changed while the worked contacts were being queried, or they failed to load a while ago, so query them again
+    let retry = engine.records_failed.is_some_and(|t| t.elapsed() >= Engine::RECORDS_RETRY_DELAY);
+    if (engine.reload_records || retry) && engine.records_task.is_none() {
+        engine.load_records(&config.db_api);
+    }
+
+    // The rules were edited, so the stored alerts need to be checked again
+    let rules_changed = !engine.checked_rules.as_ref().is_some_and(|(enabled, rules)| *enabled == config.alerts_config.enabled && *rules == config.alerts_config.rules);
+    if rules_changed {
+        engine.checked_rules = Some((config.alerts_config.enabled, config.alerts_config.rules.clone()));
+        engine.generation += 1;
+    }
+
+    // Forget the old alerts
+    let cooldown = Duration::from_secs(config.alerts_config.cooldown * 60);
+    engine.recent.retain(|_, t| t.elapsed() < cooldown);
+}
+
+/// Checks the spots in an event, raising any alerts
+pub fn process_event(config: &mut GuiConfig, event: &types::Event) {
+    match event {
+        // The log changed, so what has been worked may have changed too
+        types::Event::RefreshContacts => config.alerts.load_records(&config.db_api),
+        types::Event::DxSpot(spot) => {
+            raise(config, &Candidate::from_cluster_spot(spot));
+        },
+        types::Event::RbnSpot(spot) => {
+            raise(config, &Candidate {
+                source: Source::Rbn,
+                callsign: &spot.callsign,
+                frequency: Some(spot.frequency),
+                mode: Some(spot.mode.clone()),
+                grid: None
+            });
+        },
+        types::Event::WsjtxStatus(status) => {
+            config.alerts.wsjtx_status.insert(status.id.clone(), (status.dial_frequency, types::Mode::from_name(&status.mode)));
+        },
+        // Replayed decodes were already checked when they were first decoded
+        types::Event::WsjtxDecode(decode) if decode.new => {
+            let Some(callsign) = decode.de_callsign() else {
+                return;
+            };
+            let status = config.alerts.wsjtx_status.get(&decode.id).cloned();
+            raise(config, &Candidate {
+                source: Source::Wsjtx,
+                callsign,
+                frequency: status.as_ref().map(|(dial, _)| dial + decode.delta_frequency as u64),
+                mode: status.map(|(_, mode)| mode),
+                grid: decode.grid()
+            });
+        },
+        _ => {}
+    }
+}
+
+/// Checks a spot against the rules, showing a notification and playing a sound if the matching rule asks for it.
+///
+/// Returns the alert that matched, so it can be stored with the spot.
+pub fn raise(config: &mut GuiConfig, candidate: &Candidate) -> Option<Alert> {
+    let rule = config.alerts.check(&config.alerts_config, candidate)?;
+    let alert = rule.alert();
+
+    // Only alert once per station and band until the cooldown has passed
+    let key = (candidate.callsign.to_ascii_uppercase(), candidate.band());
+    if config.alerts.recent.contains_key(&key) {
+        return Some(alert);
+    }
+    config.alerts.recent.insert(key, Instant::now());
+
+    debug!("{} alert for {} from {}", rule.kind, candidate.callsign, candidate.source);
+
+    if rule.notify {
+        let frequency = candidate.frequency.map(|f| format!(" on {}", super::gui::frequency_formatter(f as f64, 0..=0))).unwrap_or_default();
+        config.notification_read = false;
+        config.notifications.push(types::Notification::Info(format!("{}: {}{frequency} ({})", rule.kind, candidate.callsign, candidate.source)));
+    }
+
+    if rule.sound {
+        play_sound(config.alerts_config.sound_path.trim());
+    }
+
+    Some(alert)
+}
+
+/// Plays the alert sound on a background thread. If no sound file is provided, a short beep is played instead.
+fn play_sound(path: &str) {
+    let path = path.to_string();
+    std::thread::spawn(move || {
+        let result = (|| -> Result<()> {
+            use rodio::Source;
+
+            let (_stream, handle) = rodio::OutputStream::try_default()?;
+            let sink = rodio::Sink::try_new(&handle)?;
+
+            if path.is_empty() {
+                sink.append(rodio::source::SineWave::new(880.0).take_duration(Duration::from_millis(250)).amplify(0.2));
+            } else {
+                let file = std::io::BufReader::new(std::fs::File::open(&path)?);
+                sink.append(rodio::Decoder::new(file)?);
+            }
+
+            sink.sleep_until_end();
+            Ok(())
+        })();
+
+        if let Err(err) = result {
+            error!("Failed to play the alert sound: {err}");
+        }
+    });
+}
+
+/// Highlights the background of a table cell with the color of an alert
+pub fn highlight(ui: &mut Ui, alert: &Alert) {
+    let rect = ui.max_rect().expand2(ui.spacing().item_spacing * 0.5);
+    ui.painter().rect_filled(rect, 0.0, alert.color32().gamma_multiply(0.35));
+}
+
+
+/// A rule that decides which spots raise an alert
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    /// Is the rule enabled?
+    pub enabled: bool,
+    /// The type of station to alert for
+    pub kind: AlertKind,
+    /// Only alert for spots on this band, unless it's `All`
+    pub band: types::Band,
+    /// Check spots from the DX cluster
+    pub cluster: bool,
+    /// Check spots from the RBN
+    pub rbn: bool,
+    /// Check reception reports from PSKReporter
+    pub pskreporter: bool,
+    /// Check messages decoded by WSJT-X
+    pub wsjtx: bool,
+    /// Should a notification be shown?
+    pub notify: bool,
+    /// Should a sound be played?
+    pub sound: bool,
+    /// The color used to highlight the spots
+    pub color: [u8; 4]
+}
+impl Rule {
+    /// Creates an enabled rule that checks every source
+    pub fn new(kind: AlertKind, color: [u8; 4]) -> Self {
+        Self {
+            enabled: true,
+            kind,
+            band: types::Band::All,
+            cluster: true,
+            rbn: true,
+            pskreporter: true,
+            wsjtx: true,
+            notify: true,
+            sound: false,
+            color
+        }
+    }
+
+    /// Should the rule be checked for this spot?
+    fn applies_to(&self, candidate: &Candidate) -> bool {
+        let source = match candidate.source {
+            Source::Cluster => self.cluster,
+            Source::Rbn => self.rbn,
+            Source::PskReporter => self.pskreporter,
+            Source::Wsjtx => self.wsjtx
+        };
+        self.enabled && source && (self.band == types::Band::All || self.band == candidate.band())
+    }
+
+    /// The alert that is stored with the spots that match this rule
+    pub fn alert(&self) -> Alert {
+        Alert { kind: self.kind, color: self.color }
+    }
+}
+impl Default for Rule {
+    fn default() -> Self {
+        Self::new(AlertKind::NewDxcc, [255, 80, 80, 255])
+    }
+}
+
+
+/// The alerts module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should spots be checked for alerts?
+    pub enabled: bool,
+    /// The rules, in order of priority
+    pub rules: Vec<Rule>,
+    /// How long to wait before alerting for the same station on the same band again, in minutes
+    pub cooldown: u64,
+    /// The sound file to play. A beep is played if this is empty.
+    pub sound_path: String,
+    /// The path of the country file. The file next to the exe is used if this is empty.
+    pub cty_path: String,
+    /// The URL to download the country file from
+    pub cty_url: String
+}
+impl Config {
+    /// The path of the country file
+    pub fn cty_path(&self) -> PathBuf {
+        match self.cty_path.trim() {
+            "" => dxcc::default_path(),
+            path => PathBuf::from(path)
+        }
+    }
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: vec![
+                Rule::new(AlertKind::NewDxcc, [255, 80, 80, 255]),
+                Rule::new(AlertKind::NewBandSlot, [255, 170, 0, 255]),
+                Rule::new(AlertKind::NewGrid, [80, 160, 255, 255]),
+                Rule { enabled: false, notify: false, ..Rule::new(AlertKind::NewCall, [80, 200, 120, 255]) }
+            ],
+            cooldown: 30,
+            sound_path: Default::default(),
+            cty_path: Default::default(),
+            cty_url: dxcc::CTY_URL.into()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A few entities from a real cty.dat file
+    const CTY: &str = "\
+Spratly Islands:          26:  50:  OC:    9.88:  -114.23:    -8.0:  1S:
+    1S,9M0,BM9S,BN9S;
+United States:            05:  08:  NA:   37.53:    91.67:     5.0:  K:
+    AA,AB,AC,AD,AE,AF,AG,AI,AJ,AK,K,N,W;
+Hawaii:                   31:  61:  OC:   21.12:   157.48:    10.0:  KH6:
+    AH6,AH7,KH6,KH7,NH6,NH7,WH6,WH7;
+";
+
+    /// Creates a worked contact
+    fn record(callsign: &str, grid: &str, frequency: u64, mode: types::Mode) -> WorkedRecord {
+        WorkedRecord { callsign: callsign.to_string(), grid: grid.to_string(), frequency, mode }
+    }
+
+    /// Creates an engine that has loaded the contacts and the country file
+    fn engine(records: Vec<WorkedRecord>) -> Engine {
+        let mut engine = Engine { dxcc: Some(dxcc::Database::parse(CTY).unwrap()), ..Default::default() };
+        engine.set_records(Ok(records)).unwrap();
+        engine.rebuild();
+        engine
+    }
+
+    /// Creates a DX cluster spot
+    fn spot<'a>(callsign: &'a str, frequency: Option<u64>, mode: Option<types::Mode>) -> Candidate<'a> {
+        Candidate { source: Source::Cluster, callsign, frequency, mode, grid: None }
+    }
+
+    /// Creates a WSJT-X decode with a grid square
+    fn decode(grid: Option<&str>) -> Candidate {
+        Candidate { source: Source::Wsjtx, callsign: "K1ABC", frequency: Some(14_074_000), mode: Some(types::Mode::FT8), grid }
+    }
+
+    #[test]
+    fn band_slots() {
+        let engine = engine(vec![
+            record("W1AW", "", 14_074_000, types::Mode::FT8),
+            record("K1ABC", "", 7_030_000, types::Mode::CW)
+        ]);
+        let needed = |candidate: &Candidate| engine.needed(AlertKind::NewBandSlot, candidate);
+
+        // The same entity, band, and type of mode
+        assert!(!needed(&spot("N1XYZ", Some(14_074_000), Some(types::Mode::FT8))));
+        // FT4 is digital, like FT8
+        assert!(!needed(&spot("N1XYZ", Some(14_080_000), Some(types::Mode::FT4))));
+        // Another type of mode on the same band
+        assert!(needed(&spot("N1XYZ", Some(14_025_000), Some(types::Mode::CW))));
+        assert!(needed(&spot("N1XYZ", Some(14_250_000), Some(types::Mode::SSB))));
+        // The same type of mode on another band
+        assert!(needed(&spot("N1XYZ", Some(7_074_000), Some(types::Mode::FT8))));
+        assert!(!needed(&spot("N1XYZ", Some(7_025_000), Some(types::Mode::CW))));
+        // The mode is guessed from the band plan if it isn't known
+        assert!(!needed(&spot("N1XYZ", Some(7_025_000), None)));
+        // Another entity
+        assert!(needed(&spot("KH6ABC", Some(14_074_000), Some(types::Mode::FT8))));
+        // Spots without a band, or from an unknown entity, can't be checked
+        assert!(!needed(&spot("N1XYZ", None, Some(types::Mode::CW))));
+        assert!(!needed(&spot("N1XYZ", Some(12_000_000), Some(types::Mode::CW))));
+        assert!(!needed(&spot("9M2ABC", Some(14_074_000), Some(types::Mode::FT8))));
+    }
+
+    #[test]
+    fn entities() {
+        let engine = engine(vec![record("W1AW", "", 14_074_000, types::Mode::FT8)]);
+        let needed = |callsign: &str| engine.needed(AlertKind::NewDxcc, &spot(callsign, Some(7_030_000), Some(types::Mode::CW)));
+
+        assert!(!needed("N1XYZ"));
+        assert!(!needed("W1AW/P"));
+        assert!(needed("KH6ABC"));
+        assert!(needed("W1AW/KH6"));
+        assert!(needed("9M0X"));
+        // Unknown entities can't be checked
+        assert!(!needed("9M2ABC"));
+    }
+
+    #[test]
+    fn grids() {
+        let engine = engine(vec![
+            record("W1AW", "fn31pr", 14_074_000, types::Mode::FT8),
+            record("K1ABC", " FN42 ", 7_030_000, types::Mode::CW),
+            record("N1XYZ", "", 14_074_000, types::Mode::FT8),
+            record("N1XYZ", "FN", 14_074_000, types::Mode::FT8)
+        ]);
+
+        // Grid squares are truncated to 4 characters and case folded, and incomplete ones are skipped
+        let grids: HashSet<&str> = engine.worked.grids.iter().map(String::as_str).collect();
+        assert_eq!(grids, HashSet::from(["FN31", "FN42"]));
+
+        let needed = |grid: Option<&str>| engine.needed(AlertKind::NewGrid, &decode(grid));
+        assert!(!needed(Some("FN31")));
+        assert!(!needed(Some("fn31ab")));
+        assert!(!needed(Some("FN42")));
+        assert!(needed(Some("FN32")));
+        assert!(needed(Some("fn20xa")));
+        // Spots without a grid square can't be checked
+        assert!(!needed(None));
+        assert!(!needed(Some("FN")));
+    }
+
+    #[test]
+    fn callsigns() {
+        let engine = engine(vec![record("w1aw ", "", 14_074_000, types::Mode::FT8)]);
+        let needed = |callsign: &str| engine.needed(AlertKind::NewCall, &spot(callsign, Some(14_074_000), Some(types::Mode::FT8)));
+
+        assert!(!needed("W1AW"));
+        assert!(!needed("w1aw"));
+        assert!(needed("W1AX"));
+        assert!(needed("W1AW/P"));
+    }
+
+    #[test]
+    fn rules() {
+        let engine = engine(vec![record("W1AW", "FN31", 14_074_000, types::Mode::FT8)]);
+        let config = Config {
+            rules: vec![
+                Rule { band: types::Band::B40m, ..Rule::new(AlertKind::NewCall, [255, 0, 0, 255]) },
+                Rule { cluster: false, ..Rule::new(AlertKind::NewGrid, [0, 255, 0, 255]) },
+                Rule::new(AlertKind::NewDxcc, [0, 0, 255, 255])
+            ],
+            ..Default::default()
+        };
+        let kind = |candidate: &Candidate| engine.check(&config, candidate).map(|r| r.kind);
+
+        // The first matching rule wins, and rules only apply to their band and sources
+        assert_eq!(kind(&spot("N1XYZ", Some(7_030_000), Some(types::Mode::CW))), Some(AlertKind::NewCall));
+        assert_eq!(kind(&spot("N1XYZ", Some(14_030_000), Some(types::Mode::CW))), None);
+        assert_eq!(kind(&decode(Some("FN32"))), Some(AlertKind::NewGrid));
+        assert_eq!(kind(&spot("KH6ABC", Some(14_030_000), Some(types::Mode::CW))), Some(AlertKind::NewDxcc));
+
+        // Nothing is alerted for when alerts are disabled
+        let disabled = Config { enabled: false, ..Config::default() };
+        assert_eq!(engine.check(&disabled, &spot("KH6ABC", Some(14_030_000), Some(types::Mode::CW))), None);
+    }
+
+    #[test]
+    fn records_failed_to_load() {
+        let config = Config::default();
+        let new_dxcc = spot("KH6ABC", Some(14_030_000), Some(types::Mode::CW));
+        let worked_dxcc = spot("W1AW", Some(14_030_000), Some(types::Mode::CW));
+
+        // Nothing is checked until the contacts load, since every spot would look new
+        let mut engine = Engine { dxcc: Some(dxcc::Database::parse(CTY).unwrap()), ..Default::default() };
+        assert!(engine.set_records(Err(anyhow::anyhow!("the database is locked"))).is_err());
+        engine.rebuild();
+        assert!(!engine.initialized);
+        assert!(engine.records_failed.is_some());
+        assert_eq!(engine.records_version(), 0);
+        assert_eq!(engine.check(&config, &new_dxcc), None);
+        assert_eq!(engine.check(&config, &worked_dxcc), None);
+
+        // The contacts loaded when they were retried
+        engine.set_records(Ok(vec![record("K1ABC", "", 7_030_000, types::Mode::CW)])).unwrap();
+        engine.rebuild();
+        assert!(engine.initialized);
+        assert!(engine.records_failed.is_none());
+        assert_eq!(engine.check(&config, &new_dxcc).map(|r| r.kind), Some(AlertKind::NewDxcc));
+        assert_eq!(engine.check(&config, &worked_dxcc).map(|r| r.kind), Some(AlertKind::NewBandSlot));
+
+        // The previous contacts are kept if they fail to load again
+        let generation = engine.generation();
+        assert!(engine.set_records(Err(anyhow::anyhow!("the database is locked"))).is_err());
+        assert!(engine.records_failed.is_some());
+        assert_eq!(engine.records().len(), 1);
+        assert_eq!(engine.records_version(), 1);
+        assert_eq!(engine.generation(), generation);
+        assert_eq!(engine.check(&config, &worked_dxcc).map(|r| r.kind), Some(AlertKind::NewBandSlot));
+    }
+}
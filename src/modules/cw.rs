@@ -0,0 +1,751 @@
+//
+// CW (morse code) macros and keyer outputs.
+//
+// Macros are templates such as `CQ TEST {MYCALL}` that are filled with values from the contact logger and the station profile,
+// then sent through hamlib rigctld (`send_morse`), a K1EL WinKeyer, or a test output that logs the keyed text with its timing.
+//
+
+use std::{collections::VecDeque, io::{Read, Write}, time::{Duration, Instant}};
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+use thiserror::Error;
+use tokio::{sync::mpsc, task::JoinHandle};
+use crate::{GuiConfig, RT};
+use super::{rigctld, types};
+
+
+/// The values that can be used in a macro
+#[derive(Debug, Default)]
+pub struct Variables<'a> {
+    /// `{MYCALL}`: the callsign of this station
+    pub my_call: &'a str,
+    /// `{MYNAME}`: the name of the operator
+    pub my_name: &'a str,
+    /// `{MYGRID}`: the grid square of this station
+    pub my_grid: &'a str,
+    /// `{CALL}`: the callsign of the other station
+    pub call: &'a str,
+    /// `{GRID}`: the grid square of the other station
+    pub grid: &'a str,
+    /// `{RST}`: the signal report sent to the other station
+    pub rst: &'a str,
+    /// `{NR}`: the serial number of the contact
+    pub nr: u32,
+    /// Should the digits in `{NR}` be sent as cut numbers (e.g. `T` for 0 and `N` for 9)?
+    pub cut_numbers: bool
+}
+
+/// Fills a macro template with the provided values. Unknown variables are left as-is.
+pub fn expand(template: &str, variables: &Variables) -> String {
+    let mut nr = format!("{:03}", variables.nr);
+    if variables.cut_numbers {
+        nr = nr.replace('0', "T").replace('9', "N");
+    }
+
+    template
+        .replace("{MYCALL}", variables.my_call)
+        .replace("{MYNAME}", variables.my_name)
+        .replace("{MYGRID}", variables.my_grid)
+        .replace("{CALL}", variables.call)
+        .replace("{GRID}", variables.grid)
+        .replace("{RST}", variables.rst)
+        .replace("{NR}", &nr)
+        .to_ascii_uppercase()
+}
+
+/// Gets the morse code pattern of a character, or `None` if the character can't be sent
+pub fn morse(c: char) -> Option<&'static str> {
+    Some(match c.to_ascii_uppercase() {
+        'A' => ".-", 'B' => "-...", 'C' => "-.-.", 'D' => "-..", 'E' => ".", 'F' => "..-.",
+        'G' => "--.", 'H' => "....", 'I' => "..", 'J' => ".---", 'K' => "-.-", 'L' => ".-..",
+        'M' => "--", 'N' => "-.", 'O' => "---", 'P' => ".--.", 'Q' => "--.-", 'R' => ".-.",
+        'S' => "...", 'T' => "-", 'U' => "..-", 'V' => "...-", 'W' => ".--", 'X' => "-..-",
+        'Y' => "-.--", 'Z' => "--..",
+        '0' => "-----", '1' => ".----", '2' => "..---", '3' => "...--", '4' => "....-",
+        '5' => ".....", '6' => "-....", '7' => "--...", '8' => "---..", '9' => "----.",
+        '.' => ".-.-.-", ',' => "--..--", '?' => "..--..", '/' => "-..-.", '=' => "-...-",
+        '+' => ".-.-.", '-' => "-....-", '@' => ".--.-.",
+        _ => return None
+    })
+}
+
+/// The length of a dit at the provided speed, using the PARIS standard (50 dits per word)
+pub fn dit_length(wpm: u8) -> Duration {
+    Duration::from_millis(1200 / wpm.max(1) as u64)
+}
+
+/// The number of dits that a morse code pattern takes to send, not including the space after it.
+///
+/// A dit is 1 unit, a dah is 3 units, and the space between each element is 1 unit.
+pub fn pattern_units(pattern: &str) -> u32 {
+    let elements: u32 = pattern.chars().map(|e| if e == '-' { 3 } else { 1 }).sum();
+    elements + pattern.len().saturating_sub(1) as u32
+}
+
+/// How long it takes to send some text at the provided speed
+pub fn send_duration(text: &str, wpm: u8) -> Duration {
+    let words: Vec<Vec<&str>> = text.split_whitespace()
+        .map(|w| w.chars().filter_map(morse).collect::<Vec<_>>())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut units = 0;
+    for (i, word) in words.iter().enumerate() {
+        for (j, pattern) in word.iter().enumerate() {
+            units += pattern_units(pattern);
+            // Characters are separated by a 3 unit space
+            if j + 1 < word.len() {
+                units += 3;
+            }
+        }
+        // Words are separated by a 7 unit space
+        if i + 1 < words.len() {
+            units += 7;
+        }
+    }
+
+    dit_length(wpm) * units
+}
+
+
+/// The output that the keyer sends morse code through
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, EnumIter, Display, PartialEq, Eq)]
+pub enum Output {
+    /// The radio's internal keyer, through hamlib rigctld
+    #[default]
+    #[strum(to_string = "rigctld")]
+    Rigctld,
+    /// A K1EL WinKeyer connected to a serial port
+    WinKeyer,
+    /// No hardware. The keyed text is logged with its timing instead.
+    Test
+}
+
+/// A command sent to the keyer task
+#[derive(Debug)]
+enum Command {
+    /// Send text
+    Send(String),
+    /// Change the speed, in words per minute
+    Speed(u8),
+    /// Stop sending immediately
+    Abort
+}
+
+/// A status message sent by the keyer task
+#[derive(Debug)]
+enum Status {
+    /// A line that should be shown in the keyer log
+    Log(String),
+    /// An error occurred
+    Error(String)
+}
+
+/// A CW keyer. Text is sent by a background task, so sending never blocks the GUI.
+pub struct Keyer {
+    /// The output that is being used
+    output: Output,
+    /// The address of rigctld, or the serial port of the WinKeyer
+    address: String,
+    /// The current speed, in words per minute
+    speed: u8,
+    /// The sending end of the commands for the keyer task
+    tx: mpsc::UnboundedSender<Command>,
+    /// The receiving end of the status messages from the keyer task
+    rx: mpsc::UnboundedReceiver<Status>,
+    /// The keyer task, if it runs on the tokio runtime. The WinKeyer uses a thread instead, since the serial port is blocking.
+    task: Option<JoinHandle<()>>,
+    /// The most recent keyer log lines, oldest first
+    log: VecDeque<String>
+}
+impl Keyer {
+    /// The maximum number of lines to keep in the keyer log
+    const MAX_LOG_LINES: usize = 200;
+
+    /// Starts a keyer using the provided output and address
+    pub fn new(output: Output, address: String, speed: u8) -> Self {
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (status_tx, status_rx) = mpsc::unbounded_channel();
+
+        let task = match output {
+            Output::Rigctld => Some(RT.spawn(run_rigctld(address.clone(), speed, command_rx, status_tx))),
+            Output::Test => Some(RT.spawn(run_test(speed, command_rx, status_tx))),
+            Output::WinKeyer => {
+                let port = address.clone();
+                std::thread::spawn(move || run_winkeyer(port, speed, command_rx, status_tx));
+                None
+            }
+        };
+
+        Self {
+            output,
+            address,
+            speed,
+            tx: command_tx,
+            rx: status_rx,
+            task,
+            log: Default::default()
+        }
+    }
+
+    /// Sends text as morse code
+    pub fn send(&self, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            let _ = self.tx.send(Command::Send(text.to_string()));
+        }
+    }
+
+    /// Stops sending immediately
+    pub fn abort(&self) {
+        let _ = self.tx.send(Command::Abort);
+    }
+
+    /// Changes the speed, in words per minute
+    pub fn set_speed(&mut self, wpm: u8) {
+        if wpm != self.speed {
+            self.speed = wpm;
+            let _ = self.tx.send(Command::Speed(wpm));
+        }
+    }
+
+    /// The most recent keyer log lines, oldest first
+    pub fn log(&self) -> &VecDeque<String> {
+        &self.log
+    }
+}
+impl Drop for Keyer {
+    fn drop(&mut self) {
+        // The WinKeyer thread stops by itself once the command channel is closed
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+impl std::fmt::Debug for Keyer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keyer")
+        .field("output", &self.output)
+        .field("address", &self.address)
+        .field("speed", &self.speed)
+        .field("log", &self.log.len())
+        .finish()
+    }
+}
+
+/// The rigctld keyer task. This connects to rigctld when there's something to send, and reconnects if the connection is lost.
+///
+/// rigctld only replies to `send_morse` once the text has been sent, so commands are still handled while sending.
+/// An abort stops the radio through a second connection, since the first one is waiting for that reply.
+async fn run_rigctld(address: String, mut speed: u8, mut rx: mpsc::UnboundedReceiver<Command>, tx: mpsc::UnboundedSender<Status>) {
+    let mut connection: Option<rigctld::Connection> = None;
+    let mut speed_changed = true;
+    let mut queue: VecDeque<String> = VecDeque::new();
+
+    loop {
+        // Send any queued text before waiting for the next command
+        let command = match queue.pop_front() {
+            Some(text) => Command::Send(text),
+            None => match rx.recv().await {
+                Some(command) => command,
+                None => return
+            }
+        };
+
+        // Connect to rigctld if we aren't connected
+        if connection.is_none() {
+            match rigctld::Connection::connect(&address).await {
+                Ok(c) => {
+                    connection = Some(c);
+                    speed_changed = true;
+                },
+                Err(err) => {
+                    let _ = tx.send(Status::Error(format!("Failed to connect to rigctld at '{address}': {err}")));
+                    continue;
+                }
+            }
+        }
+        let Some(conn) = connection.as_mut() else { continue };
+
+        let mut aborted = false;
+        let result = match command {
+            Command::Send(text) => {
+                let _ = tx.send(Status::Log(format!("Sending '{text}' at {speed} WPM")));
+
+                // Make sure the radio is using the right speed before sending
+                let result = match speed_changed {
+                    true => conn.set_keyer_speed(speed).await,
+                    false => Ok(())
+                };
+                speed_changed = false;
+
+                match result {
+                    Ok(()) => {
+                        let send = conn.send_morse(&text);
+                        tokio::pin!(send);
+                        loop {
+                            tokio::select! {
+                                result = &mut send => break result,
+                                command = rx.recv() => match command {
+                                    Some(Command::Send(text)) => queue.push_back(text),
+                                    Some(Command::Speed(wpm)) => {
+                                        speed = wpm;
+                                        speed_changed = true;
+                                    },
+                                    Some(Command::Abort) => {
+                                        aborted = true;
+                                        break Ok(());
+                                    },
+                                    None => return
+                                }
+                            }
+                        }
+                    },
+                    Err(err) => Err(err)
+                }
+            },
+            Command::Speed(wpm) => {
+                speed = wpm;
+                conn.set_keyer_speed(wpm).await
+            },
+            Command::Abort => {
+                queue.clear();
+                let _ = tx.send(Status::Log("Aborted".into()));
+                conn.stop_morse().await
+            }
+        };
+
+        if let Err(err) = result {
+            let _ = tx.send(Status::Error(format!("rigctld command failed: {err}")));
+            connection = None;
+        }
+
+        // The abandoned send leaves a reply on the first connection, so stop the radio through a second one and reconnect afterwards
+        if aborted {
+            queue.clear();
+            connection = None;
+            let _ = tx.send(Status::Log("Aborted".into()));
+
+            let result = match rigctld::Connection::connect(&address).await {
+                Ok(mut c) => c.stop_morse().await,
+                Err(err) => Err(err)
+            };
+            if let Err(err) = result {
+                let _ = tx.send(Status::Error(format!("Failed to stop sending through rigctld: {err}")));
+            }
+        }
+    }
+}
+
+/// The WinKeyer keyer thread. This opens the serial port and sends commands until the command channel is closed.
+fn run_winkeyer(port: String, speed: u8, mut rx: mpsc::UnboundedReceiver<Command>, tx: mpsc::UnboundedSender<Status>) {
+    let mut serial = match open_winkeyer(&port, speed) {
+        Ok((serial, version)) => {
+            info!("Opened WinKeyer (version {version}) on '{port}'");
+            let _ = tx.send(Status::Log(format!("Opened WinKeyer (version {version}) on '{port}'")));
+            serial
+        },
+        Err(err) => {
+            let _ = tx.send(Status::Error(format!("Failed to open WinKeyer on '{port}': {err}")));
+            return;
+        }
+    };
+
+    while let Some(command) = rx.blocking_recv() {
+        let bytes = match command {
+            Command::Send(text) => {
+                let _ = tx.send(Status::Log(format!("Sending '{text}'")));
+                // The WinKeyer sends any ASCII character it receives, and ignores characters it can't send
+                text.chars().filter(|c| c.is_ascii()).map(|c| c.to_ascii_uppercase() as u8).collect()
+            },
+            Command::Speed(wpm) => vec![WinKeyerCommand::SET_SPEED, wpm],
+            Command::Abort => {
+                let _ = tx.send(Status::Log("Aborted".into()));
+                vec![WinKeyerCommand::CLEAR_BUFFER]
+            }
+        };
+
+        if let Err(err) = serial.write_all(&bytes) {
+            let _ = tx.send(Status::Error(format!("Failed to write to WinKeyer: {err}")));
+            return;
+        }
+    }
+
+    // Close the host mode so the WinKeyer goes back to standalone mode
+    let _ = serial.write_all(&[WinKeyerCommand::ADMIN, WinKeyerCommand::ADMIN_HOST_CLOSE]);
+    debug!("Closed WinKeyer on '{port}'");
+}
+
+/// Opens a WinKeyer in host mode and sets the speed, returning the serial port and the firmware version
+fn open_winkeyer(port: &str, speed: u8) -> Result<(Box<dyn serialport::SerialPort>, u8)> {
+    // The WinKeyer uses 1200 baud, 8 data bits, no parity, and 2 stop bits
+    let mut serial = serialport::new(port, 1200)
+        .stop_bits(serialport::StopBits::Two)
+        .timeout(Duration::from_secs(2))
+        .open()
+        .map_err(Error::Serial)?;
+
+    // Open the host mode. The WinKeyer responds with its firmware version.
+    serial.write_all(&[WinKeyerCommand::ADMIN, WinKeyerCommand::ADMIN_HOST_OPEN]).map_err(Error::Io)?;
+    let mut version = [0];
+    serial.read_exact(&mut version).map_err(Error::Io)?;
+
+    serial.write_all(&[WinKeyerCommand::SET_SPEED, speed]).map_err(Error::Io)?;
+
+    Ok((serial, version[0]))
+}
+
+/// The WinKeyer host mode command bytes
+struct WinKeyerCommand;
+impl WinKeyerCommand {
+    const ADMIN: u8 = 0x00;
+    const ADMIN_HOST_OPEN: u8 = 0x02;
+    const ADMIN_HOST_CLOSE: u8 = 0x03;
+    const SET_SPEED: u8 = 0x02;
+    const CLEAR_BUFFER: u8 = 0x0A;
+}
+
+/// The test keyer task. This "sends" each character in real time and logs it with its timing, so macros can be checked without a radio.
+async fn run_test(mut speed: u8, mut rx: mpsc::UnboundedReceiver<Command>, tx: mpsc::UnboundedSender<Status>) {
+    let mut queue: VecDeque<String> = VecDeque::new();
+
+    loop {
+        // Wait for something to send, handling any other commands in the meantime
+        let text = match queue.pop_front() {
+            Some(text) => text,
+            None => match rx.recv().await {
+                Some(Command::Send(text)) => text,
+                Some(Command::Speed(wpm)) => { speed = wpm; continue },
+                Some(Command::Abort) => continue,
+                None => return
+            }
+        };
+
+        let dit = dit_length(speed);
+        let start = Instant::now();
+        let _ = tx.send(Status::Log(format!("Sending '{text}' at {speed} WPM (dit {} ms, {:.2} s)", dit.as_millis(), send_duration(&text, speed).as_secs_f64())));
+
+        let words: Vec<&str> = text.split_whitespace().collect();
+        'send: for (i, word) in words.iter().enumerate() {
+            for (j, c) in word.chars().enumerate() {
+                let Some(pattern) = morse(c) else {
+                    let _ = tx.send(Status::Log(format!("{:>7.2} s  {c}  (skipped, no morse code)", start.elapsed().as_secs_f64())));
+                    continue;
+                };
+
+                // The character, followed by a character space, or a word space after the last character of a word
+                let last_in_word = j == word.chars().count() - 1;
+                let space = match (last_in_word, i == words.len() - 1) {
+                    (true, true) => 0,
+                    (true, false) => 7,
+                    (false, _) => 3
+                };
+                let units = pattern_units(pattern);
+
+                let _ = tx.send(Status::Log(format!("{:>7.2} s  {c}  {pattern:<6}  {units} + {space} units", start.elapsed().as_secs_f64())));
+
+                // Wait for the character to be "sent", stopping if the keyer was aborted
+                let sleep = tokio::time::sleep(dit * (units + space));
+                tokio::pin!(sleep);
+                loop {
+                    tokio::select! {
+                        _ = &mut sleep => break,
+                        command = rx.recv() => match command {
+                            Some(Command::Send(text)) => queue.push_back(text),
+                            Some(Command::Speed(wpm)) => speed = wpm,
+                            Some(Command::Abort) => {
+                                queue.clear();
+                                let _ = tx.send(Status::Log(format!("{:>7.2} s  Aborted", start.elapsed().as_secs_f64())));
+                                break 'send;
+                            },
+                            None => return
+                        }
+                    }
+                }
+            }
+        }
+
+        let _ = tx.send(Status::Log(format!("Finished in {:.2} s", start.elapsed().as_secs_f64())));
+    }
+}
+
+/// Processes any status messages from the keyer, starting or stopping it depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the keyer out of the config so we can mutate the rest of the config while processing messages
+    let mut keyer = config.cw.take();
+
+    // Start, stop, or restart the keyer if the config changed
+    let address = match config.cw_config.output {
+        Output::Rigctld => config.rig_config.address.clone(),
+        Output::WinKeyer => config.cw_config.winkeyer_port.clone(),
+        Output::Test => String::new()
+    };
+    if !config.cw_config.enabled {
+        keyer = None;
+    } else if keyer.as_ref().map_or(true, |k| k.output != config.cw_config.output || k.address != address) {
+        keyer = Some(Keyer::new(config.cw_config.output, address, config.cw_config.speed));
+    }
+
+    let Some(mut keyer) = keyer else {
+        return;
+    };
+
+    // Keep the keyer speed in sync with the config
+    keyer.set_speed(config.cw_config.speed);
+
+    // Process the status messages
+    while let Ok(status) = keyer.rx.try_recv() {
+        match status {
+            Status::Log(line) => {
+                info!("CW keyer: {line}");
+                keyer.log.push_back(line);
+                while keyer.log.len() > Keyer::MAX_LOG_LINES {
+                    keyer.log.pop_front();
+                }
+            },
+            Status::Error(err) => {
+                error!("CW keyer: {err}");
+                keyer.log.push_back(err.clone());
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(err));
+            }
+        }
+    }
+
+    // Put the keyer back into the config
+    config.cw = Some(keyer);
+
+}
+
+/// Fills a macro using the contact in the logger and the station profile, then sends it through the keyer
+pub fn send_macro(config: &GuiConfig, template: &str, contact: &types::Contact) {
+    let Some(keyer) = &config.cw else {
+        warn!("Tried to send a CW macro, but the keyer is disabled");
+        return;
+    };
+
+    let text = expand(template, &Variables {
+        my_call: &config.station.callsign,
+        my_name: &config.station.name,
+        my_grid: &config.station.grid,
+        call: &contact.callsign,
+        grid: &contact.grid,
+        rst: &contact.tx_rst,
+        nr: config.cw_config.serial,
+        cut_numbers: config.cw_config.cut_numbers
+    });
+
+    keyer.send(&text);
+}
+
+
+/// A CW macro
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Macro {
+    /// The label shown on the macro button
+    pub label: String,
+    /// The macro template (e.g. `CQ TEST {MYCALL}`)
+    pub text: String
+}
+impl Macro {
+    fn new(label: &str, text: &str) -> Self {
+        Self { label: label.into(), text: text.into() }
+    }
+}
+
+/// The CW keyer module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should the keyer be enabled?
+    pub enabled: bool,
+    /// The output that the keyer sends morse code through
+    pub output: Output,
+    /// The serial port of the WinKeyer (e.g. `/dev/ttyUSB0` or `COM3`)
+    pub winkeyer_port: String,
+    /// The speed, in words per minute
+    pub speed: u8,
+    /// The serial number of the next contact, used by `{NR}`
+    pub serial: u32,
+    /// Should the digits in `{NR}` be sent as cut numbers?
+    pub cut_numbers: bool,
+    /// The macros, in F-key order
+    pub macros: Vec<Macro>
+}
+impl Config {
+    /// The slowest speed supported by the keyer
+    pub const MIN_SPEED: u8 = 5;
+    /// The fastest speed supported by the keyer
+    pub const MAX_SPEED: u8 = 60;
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            output: Default::default(),
+            winkeyer_port: Default::default(),
+            speed: 25,
+            serial: 1,
+            cut_numbers: false,
+            macros: vec![
+                Macro::new("CQ", "CQ TEST {MYCALL} {MYCALL} TEST"),
+                Macro::new("Exch", "{CALL} 5NN {NR}"),
+                Macro::new("TU", "TU {MYCALL}"),
+                Macro::new("My call", "{MYCALL}"),
+                Macro::new("His call", "{CALL}"),
+                Macro::new("Repeat", "{NR} {NR}"),
+                Macro::new("?", "?"),
+                Macro::new("AGN", "AGN")
+            ]
+        }
+    }
+}
+
+/// Errors regarding the CW keyer module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to open serial port: {0}")]
+    Serial(serialport::Error),
+    #[error("Serial port error: {0}")]
+    Io(std::io::Error)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::TcpListener};
+    use super::*;
+
+    #[test]
+    fn variables_are_expanded() {
+        let variables = Variables {
+            my_call: "n0call",
+            my_name: "Alex",
+            call: "JA1XYZ",
+            rst: "599",
+            nr: 7,
+            ..Default::default()
+        };
+        assert_eq!(expand("cq test {MYCALL} {MYCALL}", &variables), "CQ TEST N0CALL N0CALL");
+        assert_eq!(expand("{CALL} {RST} {NR} {MYNAME}", &variables), "JA1XYZ 599 007 ALEX");
+
+        // Unknown variables are left as-is, and empty ones are removed
+        assert_eq!(expand("{CALL} {QTH} {GRID}", &variables), "JA1XYZ {QTH} ");
+
+        // Serial numbers with more than 3 digits aren't cut short
+        assert_eq!(expand("{NR}", &Variables { nr: 1234, ..Default::default() }), "1234");
+    }
+
+    #[test]
+    fn cut_numbers_are_only_used_for_the_serial() {
+        let variables = Variables { my_call: "N0CALL", nr: 90, cut_numbers: true, ..Default::default() };
+        assert_eq!(expand("{MYCALL} 5NN {NR}", &variables), "N0CALL 5NN TNT");
+    }
+
+    #[test]
+    fn characters_are_converted_to_morse() {
+        assert_eq!(morse('A'), Some(".-"));
+        assert_eq!(morse('a'), Some(".-"));
+        assert_eq!(morse('0'), Some("-----"));
+        assert_eq!(morse('/'), Some("-..-."));
+        assert_eq!(morse('?'), Some("..--.."));
+
+        // Prosigns are sent as their characters (e.g. `=` for BT and `+` for AR), not with a special syntax
+        assert_eq!(morse('='), Some("-...-"));
+        assert_eq!(morse('+'), Some(".-.-."));
+        for c in ['<', '>', '#', '!', 'Ä', ' '] {
+            assert_eq!(morse(c), None, "{c:?}");
+        }
+    }
+
+    #[test]
+    fn patterns_are_timed() {
+        assert_eq!(pattern_units("."), 1);
+        assert_eq!(pattern_units("-"), 3);
+        assert_eq!(pattern_units(".-"), 5);
+        assert_eq!(pattern_units("-----"), 19);
+        assert_eq!(pattern_units(""), 0);
+
+        assert_eq!(dit_length(20), Duration::from_millis(60));
+        assert_eq!(dit_length(12), Duration::from_millis(100));
+        assert_eq!(dit_length(0), Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn paris_is_50_units() {
+        // PARIS is 43 units, plus the 7 unit space before the next word
+        for wpm in [5, 20, 25, 60] {
+            assert_eq!(send_duration("PARIS", wpm), dit_length(wpm) * 43);
+            assert_eq!(send_duration("PARIS PARIS", wpm), dit_length(wpm) * (50 + 43));
+        }
+        assert_eq!(send_duration("PARIS", 20) + dit_length(20) * 7, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn unknown_characters_are_not_timed() {
+        assert_eq!(send_duration("A#B", 20), send_duration("AB", 20));
+        assert_eq!(send_duration("A ## B", 20), send_duration("A B", 20));
+        assert_eq!(send_duration("  A   B ", 20), send_duration("A B", 20));
+        assert_eq!(send_duration("", 20), Duration::ZERO);
+    }
+
+    #[test]
+    fn rigctld_abort_interrupts_a_send() {
+        // A fake rigctld that never finishes sending morse code, and reports every command it receives
+        let listener = RT.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (received_tx, mut received_rx) = mpsc::unbounded_channel();
+        let rigctld = RT.spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let received_tx = received_tx.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let _ = received_tx.send(line.clone());
+                        if !line.starts_with("b ") {
+                            writer.write_all(b"RPRT 0\n").await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (status_tx, mut status_rx) = mpsc::unbounded_channel();
+        let keyer = RT.spawn(run_rigctld(address, 25, command_rx, status_tx));
+
+        let mut receive = || RT.block_on(async {
+            tokio::time::timeout(Duration::from_secs(1), received_rx.recv()).await.unwrap().unwrap()
+        });
+        command_tx.send(Command::Send("CQ TEST N0CALL".into())).unwrap();
+        command_tx.send(Command::Send("CQ TEST N0CALL".into())).unwrap();
+        assert_eq!(receive(), "L KEYSPD 25");
+        assert_eq!(receive(), "b CQ TEST N0CALL");
+
+        // The radio is stopped while the send is still waiting for its reply, and the queued text is dropped
+        command_tx.send(Command::Abort).unwrap();
+        assert_eq!(receive(), "\\stop_morse");
+
+        // The keyer reconnects for the next send
+        command_tx.send(Command::Send("TU".into())).unwrap();
+        assert_eq!(receive(), "L KEYSPD 25");
+        assert_eq!(receive(), "b TU");
+
+        let mut statuses = Vec::new();
+        while let Ok(status) = status_rx.try_recv() {
+            statuses.push(status);
+        }
+        assert!(statuses.iter().any(|s| matches!(s, Status::Log(line) if line == "Aborted")));
+        assert!(!statuses.iter().any(|s| matches!(s, Status::Error(_))), "{statuses:?}");
+
+        keyer.abort();
+        rigctld.abort();
+    }
+}
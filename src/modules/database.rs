@@ -3,7 +3,8 @@
 //
 
 
-use std::{env::current_exe, future::IntoFuture, sync::{atomic::{AtomicBool, Ordering::SeqCst}, Arc}, time::Duration};
+use std::{collections::HashSet, env::current_exe, future::IntoFuture, sync::{atomic::{AtomicBool, Ordering::SeqCst}, Arc}, time::Duration};
+use chrono::{NaiveDate, NaiveTime};
 use lazy_static::lazy_static;
 use log::{debug, error, info};
 use poll_promise::Promise;
@@ -11,7 +12,7 @@ use serde::{Deserialize, Serialize};
 use surrealdb::{engine::any::Any, opt::{auth::Root, IntoQuery}, sql::{self, statements, Field, Thing, Value}, Surreal};
 use tokio::runtime::Handle;
 use crate::RT;
-use super::types::{self, Event};
+use super::{pskreporter::{hash_reception_report, ReceptionReport}, types::{self, Event}};
 use thiserror::Error;
 use anyhow::{Context, Result};
 
@@ -26,7 +27,9 @@ const DB_NAME: &str = "primary";
 /// The name for the metadata table
 const TABLE_METADATA: &str = "metadata";
 /// The name for the table that contains all of the logged radio contacts
-const TABLE_CONTACT: &str = "contact";
+pub const TABLE_CONTACT: &str = "contact";
+/// The name for the table that contains the PSKReporter reception reports that were received
+pub const TABLE_RECEPTION_REPORT: &str = "reception_report";
 
 lazy_static! {
     /// The metadata for the contact table
@@ -42,22 +45,14 @@ lazy_static! {
         )])),
         ..Default::default()
     });
-
-    /// The statement that decrements the number of contacts in the metadata table
-    static ref STATEMENT_DECREMENT_N_CONTACTS: sql::Statement = sql::Statement::Update(statements::UpdateStatement {
-        what: sql::Values(vec![sql::Value::Thing(METADATA_CONTACT.clone())]),
-        data: Some(sql::Data::SetExpression(vec![(
-            sql::idiom("n_contacts").unwrap(),
-            sql::Operator::Dec,
-            sql::Value::Number(sql::Number::Int(1))
-        )])),
-        ..Default::default()
-    });
 }
 
 /// The default record limit to be returned from the database.
 /// 1k is a very generous limit and I advise that you avoid reaching it in the first place.
 const DEFAULT_RECORD_LIMIT: usize = 1_000;
+/// The maximum number of reception reports to be returned from the database.
+/// Busy stations can be heard tens of thousands of times a day, so this is much higher than the default limit.
+const RECEPTION_REPORT_LIMIT: usize = 200_000;
 
 
 /// The interface to the database. This should be created only once, and shared with every tab in the GUI.
@@ -196,6 +191,36 @@ impl DatabaseInterface {
                 ]),
                 index: sql::Index::Idx,
                 comment: Some(sql::Strand("Contact Table Time Index".into()))
+            })),
+            // Create the reception_report->time index
+            sql::Statement::Define(DefineStatement::Index(DefineIndexStatement {
+                name: sql::Ident("reception_report_time_index".into()),
+                what: sql::Ident(TABLE_RECEPTION_REPORT.into()),
+                cols: sql::Idioms(vec![
+                    sql::idiom("time").unwrap()
+                ]),
+                index: sql::Index::Idx,
+                comment: Some(sql::Strand("Reception Report Table Time Index".into()))
+            })),
+            // Create the reception_report->tx_callsign index
+            sql::Statement::Define(DefineStatement::Index(DefineIndexStatement {
+                name: sql::Ident("reception_report_tx_callsign_index".into()),
+                what: sql::Ident(TABLE_RECEPTION_REPORT.into()),
+                cols: sql::Idioms(vec![
+                    sql::idiom("tx_callsign").unwrap()
+                ]),
+                index: sql::Index::Idx,
+                comment: Some(sql::Strand("Reception Report Table TX Callsign Index".into()))
+            })),
+            // Create the reception_report->rx_callsign index
+            sql::Statement::Define(DefineStatement::Index(DefineIndexStatement {
+                name: sql::Ident("reception_report_rx_callsign_index".into()),
+                what: sql::Ident(TABLE_RECEPTION_REPORT.into()),
+                cols: sql::Idioms(vec![
+                    sql::idiom("rx_callsign").unwrap()
+                ]),
+                index: sql::Index::Idx,
+                comment: Some(sql::Strand("Reception Report Table RX Callsign Index".into()))
             }))
         ]));
 
@@ -235,6 +260,8 @@ impl DatabaseInterface {
 
     /// Inserts a contact into the contacts table
     /// 
+    /// If the contact already has an ID, the contact is inserted with that ID. Otherwise, the database generates one.
+    /// 
     /// If the insert was successful, this function returns the contact that was just inserted.
     pub fn insert_contact_promise(&self, contact: types::Contact) -> Promise<Result<types::Contact>> {
         let db = self.db.clone();
@@ -242,13 +269,19 @@ impl DatabaseInterface {
         let _eg = RT.enter();
         Promise::spawn_async(async move {
 
+            // Use the ID in the contact if one was provided (e.g. a contact received from another logger), otherwise let the database generate one
+            let what: sql::Value = match &contact.id {
+                Some(thing) => sql::Thing { tb: TABLE_CONTACT.into(), id: thing.id.clone() }.into(),
+                None => sql::Table(TABLE_CONTACT.into()).into()
+            };
+
             // Create the query
             // This is a transaction that inserts the contact into the database, and then increments the number of contacts in the metadata table.
             // If anything fails, everything is rolled back.
             let query = sql::Query(sql::Statements(vec![
                 sql::Statement::Begin(Default::default()),
                 sql::Statement::Create(sql::statements::CreateStatement {
-                    what: sql::Values(vec![sql::Table(TABLE_CONTACT.into()).into()]),
+                    what: sql::Values(vec![what]),
                     data: Some(sql::Data::ContentExpression(sql::to_value(&contact).unwrap())),
                     ..Default::default()
                 }),
@@ -271,6 +304,46 @@ impl DatabaseInterface {
         })
     }
 
+    /// Inserts a contact into the contacts table with the ID in the provided contact, replacing the contact with that ID if it already exists
+    ///
+    /// This is used for contacts received from other loggers, which can be sent more than once. The number of contacts is only incremented if the contact didn't exist.
+    ///
+    /// If the upsert was successful, this function returns the contact after it was inserted or replaced.
+    pub fn upsert_contact_promise(&self, mut contact: types::Contact) -> Promise<Result<types::Contact>> {
+        let db = self.db.clone();
+        let contacts_metadata_changed = self.contacts_metadata_changed.clone();
+        let _eg = RT.enter();
+        Promise::spawn_async(async move {
+
+            let id = contact.id.as_ref().ok_or(Error::DoesNotExist)?.id.clone();
+            let record = sql::Thing { tb: TABLE_CONTACT.into(), id };
+            contact.id = Some(record.clone());
+
+            // Create the query
+            // This is a transaction that increments the number of contacts in the metadata table if the contact doesn't exist yet, and then inserts or replaces the contact.
+            // Updating a record that doesn't exist creates it. If anything fails, everything is rolled back.
+            let query = db.query("
+                BEGIN TRANSACTION;
+                UPDATE $metadata SET n_contacts += 1 WHERE array::len((SELECT id FROM $record)) = 0 RETURN NONE;
+                UPDATE $record CONTENT $contact RETURN AFTER;
+                COMMIT TRANSACTION;
+            ")
+            .bind(("metadata", METADATA_CONTACT.clone()))
+            .bind(("record", record))
+            .bind(("contact", contact));
+
+            // Execute the query, and get the result of the contact update
+            let response: Option<types::Contact> = execute_query_single_at(query, 1, Self::QUERY_TIMEOUT).await?;
+            let contact = response.ok_or(Error::EmptyResponse)?;
+
+            // Mark the metadata as changed
+            contacts_metadata_changed.store(true, SeqCst);
+
+            Ok(contact)
+
+        })
+    }
+
     /// Updates a contact in the contacts table using the ID in the provided contact
     /// 
     /// If the update was successful, this function returns the contact after it was updated
@@ -312,22 +385,19 @@ impl DatabaseInterface {
         Promise::spawn_async(async move {
 
             // Create the delete query
-            // This is a transaction that deletes the contact from the database, and then decrements the number of contacts in the metadata table.
+            // This is a transaction that decrements the number of contacts in the metadata table if the contact exists, and then deletes the contact from the database.
             // If anything fails, everything is rolled back.
-            let query = sql::Query(sql::Statements(vec![
-                sql::Statement::Begin(Default::default()),
-                sql::Statement::Delete(statements::DeleteStatement {
-                    what: sql::Values(vec![sql::Thing { tb: TABLE_CONTACT.into(), id: id.clone() }.into()]),
-                    only: true,
-                    output: Some(sql::Output::Before),
-                    ..Default::default()
-                }),
-                STATEMENT_DECREMENT_N_CONTACTS.clone(),
-                sql::Statement::Commit(Default::default()),
-            ]));
-
-            // Execute the query
-            let response: Option<types::Contact> = execute_query_single(db.query(query), Self::QUERY_TIMEOUT).await?;
+            let query = db.query("
+                BEGIN TRANSACTION;
+                UPDATE $metadata SET n_contacts -= array::len((SELECT id FROM $record)) RETURN NONE;
+                DELETE $record RETURN BEFORE;
+                COMMIT TRANSACTION;
+            ")
+            .bind(("metadata", METADATA_CONTACT.clone()))
+            .bind(("record", sql::Thing { tb: TABLE_CONTACT.into(), id }));
+
+            // Execute the query, and get the result of the delete
+            let response: Option<types::Contact> = execute_query_single_at(query, 1, Self::QUERY_TIMEOUT).await?;
 
             // Get the deleted contact and ensure the database response wasn't empty
             let contact = response.ok_or(Error::DoesNotExist)?;
@@ -446,6 +516,165 @@ impl DatabaseInterface {
         })
     }
 
+    /// Get the row of a contact in the contacts table when it's sorted in the default order, or `None` if the contact doesn't exist
+    ///
+    /// This counts the contacts that come before it, so the contacts don't have to be queried to find it.
+    pub fn get_contact_row_promise(&self, callsign: String, date: NaiveDate, time: NaiveTime) -> Promise<Result<Option<usize>>> {
+        let db = self.db.clone();
+        let _eg = RT.enter();
+        Promise::spawn_async(async move {
+
+            // Make sure that the contact exists
+            // The sql statement should be something like; SELECT count() FROM contact WHERE date = '2024-03-16' AND time = '12:34:56' AND callsign = 'W1AW' GROUP ALL
+            let query = db.query(format!("SELECT count() FROM {TABLE_CONTACT} WHERE date = $date AND time = $time AND callsign = $callsign GROUP ALL"))
+                .bind(("date", date))
+                .bind(("time", time))
+                .bind(("callsign", callsign.clone()));
+            let matches: Option<CountRecord> = execute_query_single(query, Self::QUERY_TIMEOUT).await?;
+            if matches.map_or(0, |r| r.count) == 0 {
+                return Ok(None);
+            }
+
+            // Count the contacts that are sorted before it. The default order is by date, time, and callsign, in descending order.
+            let query = db.query(format!(
+                "SELECT count() FROM {TABLE_CONTACT} WHERE date > $date OR (date = $date AND time > $time) OR (date = $date AND time = $time AND callsign > $callsign) GROUP ALL"
+            ))
+            .bind(("date", date))
+            .bind(("time", time))
+            .bind(("callsign", callsign));
+            let before: Option<CountRecord> = execute_query_single(query, Self::QUERY_TIMEOUT).await?;
+
+            Ok(Some(before.map_or(0, |r| r.count)))
+
+        })
+    }
+
+    /// Get every unique callsign in the contacts table
+    ///
+    /// This is used to determine if a station has been worked before (e.g. when highlighting decoded messages).
+    pub fn get_worked_callsigns_promise(&self) -> Promise<Result<HashSet<String>>> {
+        let db = self.db.clone();
+        let _eg = RT.enter();
+        Promise::spawn_async(async move {
+
+            // Create the sql statement
+            // The sql statement should be something like; SELECT callsign FROM contact GROUP BY callsign
+            let stmt = statements::SelectStatement {
+                expr: sql::Fields(vec![sql::Field::Single {
+                    expr: Value::Idiom(ContactTableColumn::Callsign.as_idiom()),
+                    alias: None
+                }], false),
+                what: sql::Values(vec![sql::Table(TABLE_CONTACT.into()).into()]),
+                group: Some(sql::Groups(vec![sql::Group(ContactTableColumn::Callsign.as_idiom())])),
+                ..Default::default()
+            };
+
+            // Execute the query
+            let response: Vec<CallsignRecord> = execute_query(db.query(stmt), Self::QUERY_TIMEOUT).await?;
+
+            // Collect the callsigns into a set, normalized to uppercase
+            Ok(response.into_iter().map(|r| r.callsign.to_ascii_uppercase()).collect())
+
+        })
+    }
+
+    /// Get the callsign, grid, frequency, and mode of every contact in the contacts table
+    ///
+    /// This is used to determine what has been worked before (e.g. when checking spots for new entities or grids).
+    pub fn get_worked_promise(&self) -> Promise<Result<Vec<WorkedRecord>>> {
+        let db = self.db.clone();
+        let _eg = RT.enter();
+        Promise::spawn_async(async move {
+
+            // Create the sql statement
+            // The sql statement should be something like; SELECT callsign, grid, frequency, mode FROM contact
+            let columns = [ContactTableColumn::Callsign, ContactTableColumn::Grid, ContactTableColumn::Frequency, ContactTableColumn::Mode];
+            let stmt = statements::SelectStatement {
+                expr: sql::Fields(columns.iter().map(|c| sql::Field::Single {
+                    expr: Value::Idiom(c.as_idiom()),
+                    alias: None
+                }).collect(), false),
+                what: sql::Values(vec![sql::Table(TABLE_CONTACT.into()).into()]),
+                ..Default::default()
+            };
+
+            // Execute the query
+            execute_query(db.query(stmt), Self::QUERY_TIMEOUT).await
+
+        })
+    }
+
+    /// Inserts PSKReporter reception reports into the reception report table, then deletes the reports that are older than `cutoff`
+    ///
+    /// The reports are keyed by [hash_reception_report], so a report that was already inserted (e.g. one that was returned by multiple API queries) is skipped.
+    /// `cutoff` is in seconds since the epoch.
+    pub fn insert_reception_reports_promise(&self, reports: Vec<ReceptionReport>, cutoff: u64) -> Promise<Result<()>> {
+        let db = self.db.clone();
+        let _eg = RT.enter();
+        Promise::spawn_async(async move {
+
+            // Key each report by its hash, so duplicates are ignored
+            let records: Vec<ReceptionReportRecord> = reports.into_iter().map(|report| ReceptionReportRecord {
+                id: format!("{:016x}", hash_reception_report(&report)),
+                report
+            }).collect();
+
+            // Create and execute the insert statement
+            // The sql statement should be something like; INSERT IGNORE INTO reception_report [{ id: '...', tx_callsign: '...', ... }] RETURN NONE
+            let insert = db.query(format!("INSERT IGNORE INTO {TABLE_RECEPTION_REPORT} $records RETURN NONE"))
+                .bind(("records", records));
+            let _: Vec<Value> = execute_query(insert, Self::QUERY_TIMEOUT).await?;
+
+            // Create and execute the statement that deletes the old reports
+            // The sql statement should be something like; DELETE reception_report WHERE time < 1700000000 RETURN NONE
+            let delete = db.query(format!("DELETE {TABLE_RECEPTION_REPORT} WHERE time < $cutoff RETURN NONE"))
+                .bind(("cutoff", cutoff));
+            let _: Vec<Value> = execute_query(delete, Self::QUERY_TIMEOUT).await?;
+
+            Ok(())
+
+        })
+    }
+
+    /// Get the PSKReporter reception reports that were generated since `since`, oldest first
+    ///
+    /// If there are more than [RECEPTION_REPORT_LIMIT] reports, the newest reports are returned.
+    ///
+    /// 1. `tx_callsign` only returns the reports of signals sent by the callsign, if it's provided.
+    /// 2. `rx_callsign` only returns the reports of signals received by the callsign, if it's provided.
+    /// 3. `since` is in seconds since the epoch.
+    pub fn get_reception_reports_promise(&self, tx_callsign: Option<String>, rx_callsign: Option<String>, since: u64) -> Promise<Result<Vec<ReceptionReport>>> {
+        let db = self.db.clone();
+        let _eg = RT.enter();
+        Promise::spawn_async(async move {
+
+            // Only filter by the callsigns that were provided
+            let mut conditions = vec!["time >= $since"];
+            if tx_callsign.is_some() {
+                conditions.push("tx_callsign = $tx_callsign");
+            }
+            if rx_callsign.is_some() {
+                conditions.push("rx_callsign = $rx_callsign");
+            }
+
+            // Create the query. The newest reports are selected, so the end of the timeline is kept if the limit is reached.
+            // The sql statement should be something like; SELECT * FROM reception_report WHERE time >= 1700000000 AND tx_callsign = 'W1AW' ORDER BY time DESC LIMIT 200000
+            let query = db.query(format!(
+                "SELECT * FROM {TABLE_RECEPTION_REPORT} WHERE {} ORDER BY time DESC LIMIT {RECEPTION_REPORT_LIMIT}",
+                conditions.join(" AND ")
+            ))
+            .bind(("since", since))
+            .bind(("tx_callsign", tx_callsign))
+            .bind(("rx_callsign", rx_callsign));
+
+            // Execute the query, and put the reports back in chronological order
+            let mut reports: Vec<ReceptionReport> = execute_query(query, Self::QUERY_TIMEOUT).await?;
+            reports.reverse();
+            Ok(reports)
+
+        })
+    }
+
     /// Returns the metadata about the contacts table
     pub fn get_contacts_metadata(&mut self) -> Result<&ContactsTableMetadata> {
         // If the metadata has changed, query the database for the new metadata
@@ -534,6 +763,26 @@ where
 
 }
 
+/// Executes a database query with multiple statements, returning the single object that's returned by the statement at `index`.
+/// 
+/// `BEGIN` and `COMMIT` statements don't count towards the index.
+async fn execute_query_single_at<T>(
+    fut: impl IntoFuture<Output = surrealdb::Result<surrealdb::Response>>,
+    index: usize,
+    timeout: Duration
+) -> Result<Option<T>>
+where
+    T: for<'a> Deserialize<'a>
+{
+    // Execute the query with the provided timeout
+    let response = tokio::time::timeout(timeout, fut.into_future()).await
+        .map_err(|_e| Error::Timeout)?
+        .map_err(Error::QueryFailed)?
+        .take::<Option<T>>(index).map_err(Error::QueryFailed)?;
+
+    Ok(response)
+}
+
 
 /// The direction in which a table column should be sorted
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
@@ -550,6 +799,7 @@ pub enum ColumnSortDirection {
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, strum_macros::Display, strum_macros::EnumIter, strum_macros::EnumIs)]
 pub enum ContactTableColumn {
     Callsign,
+    Grid,
     Frequency,
     Mode,
     #[strum(to_string = "TX RST")]
@@ -570,6 +820,7 @@ impl ContactTableColumn {
     fn as_idiom(&self) -> sql::Idiom {
         sql::idiom(match self {
             ContactTableColumn::Callsign => "callsign",
+            ContactTableColumn::Grid => "grid",
             ContactTableColumn::Frequency => "frequency",
             ContactTableColumn::Mode => "mode",
             ContactTableColumn::TxRst => "tx_rst",
@@ -586,6 +837,7 @@ impl ContactTableColumn {
     pub fn is_sortable(&self) -> bool {
         match self {
             ContactTableColumn::Callsign => true,
+            ContactTableColumn::Grid => false,
             ContactTableColumn::Frequency => false,
             ContactTableColumn::Mode => false,
             ContactTableColumn::TxRst => false,
@@ -608,6 +860,44 @@ pub struct ContactsTableMetadata {
     pub n_contacts: usize
 }
 
+/// A record containing only a callsign. Used when querying for unique callsigns in the contacts table.
+#[derive(Debug, Deserialize)]
+struct CallsignRecord {
+    callsign: String
+}
+
+/// The number of rows that matched a query. Used when counting the rows of a table with `SELECT count() ... GROUP ALL`.
+#[derive(Debug, Deserialize)]
+struct CountRecord {
+    count: usize
+}
+
+/// A reception report as it's stored in the reception report table
+#[derive(Debug, Serialize)]
+struct ReceptionReportRecord {
+    /// The ID of the record, which is the hash of the report
+    id: String,
+    /// The report
+    #[serde(flatten)]
+    report: ReceptionReport
+}
+
+/// A record containing the fields of a contact that are needed to determine what has been worked before.
+///
+/// Contacts logged before the grid square was added don't have one, so missing fields are defaulted.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WorkedRecord {
+    /// The callsign of the station
+    pub callsign: String,
+    /// The grid square of the station, if known
+    pub grid: String,
+    /// The frequency of the contact, in Hz
+    pub frequency: u64,
+    /// The mode of the contact
+    pub mode: types::Mode
+}
+
 /// Errors regarding the database module
 #[derive(Debug, Error)]
 pub enum Error {
@@ -0,0 +1,315 @@
+//
+// Resolves callsigns into DXCC entities using a cty.dat country file (https://www.country-files.com).
+//
+// Each entity starts with a header line, followed by its prefixes, separated by commas and terminated with a semicolon:
+// `Spratly Islands:          26:  50:  OC:    9.88:  -114.23:    -8.0:  1S:`
+// `    1S,9M0,BM9S,BN9S,=9M6AAC(26),...;`
+// Prefixes starting with `=` are exact callsigns, and anything in brackets overrides the zones, location, or continent.
+//
+
+use std::{collections::HashMap, env::current_exe, path::{Path, PathBuf}};
+use anyhow::Result;
+use geo::Coord;
+use thiserror::Error;
+
+
+/// The URL of the latest cty.dat file
+pub const CTY_URL: &str = "https://www.country-files.com/cty/cty.dat";
+/// The name of the cty.dat file, relative to the exe file
+const CTY_FILE: &str = "cty.dat";
+
+
+/// A DXCC entity
+#[derive(Debug, Clone)]
+pub struct Entity {
+    /// The name of the entity (e.g. `Spratly Islands`)
+    pub name: String,
+    /// The primary prefix of the entity (e.g. `1S`)
+    pub prefix: String,
+    /// The continent of the entity (e.g. `OC`)
+    pub continent: String,
+    /// The CQ zone of the entity
+    pub cq_zone: u8,
+    /// The ITU zone of the entity
+    pub itu_zone: u8,
+    /// The approximate location of the entity
+    pub location: Coord
+}
+
+/// The entities and prefixes of a cty.dat file
+#[derive(Debug, Default)]
+pub struct Database {
+    /// Every entity in the file
+    entities: Vec<Entity>,
+    /// The index of the entity of each prefix
+    prefixes: HashMap<String, usize>,
+    /// The index of the entity of each callsign that doesn't match its prefix
+    callsigns: HashMap<String, usize>,
+    /// The length of the longest prefix
+    max_prefix_len: usize
+}
+impl Database {
+    /// Parses the contents of a cty.dat file
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut database = Self::default();
+        let mut pending = String::new();
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            // Header lines aren't indented, and prefix lines are
+            if !line.starts_with(char::is_whitespace) {
+                let fields: Vec<&str> = trimmed.split(':').map(str::trim).collect();
+                if fields.len() < 8 {
+                    return Err(Error::InvalidLine(trimmed.to_string()))?;
+                }
+
+                // Longitude is positive to the west in cty.dat
+                let lat = fields[4].parse::<f64>().unwrap_or_default();
+                let lon = fields[5].parse::<f64>().unwrap_or_default();
+
+                database.entities.push(Entity {
+                    name: fields[0].to_string(),
+                    // WAE-only entities are marked with an asterisk
+                    prefix: fields[7].trim_start_matches('*').to_string(),
+                    continent: fields[3].to_string(),
+                    cq_zone: fields[1].parse().unwrap_or_default(),
+                    itu_zone: fields[2].parse().unwrap_or_default(),
+                    location: Coord { x: -lon, y: lat }
+                });
+                pending.clear();
+                continue;
+            }
+
+            // Collect the prefixes until the terminating semicolon
+            pending.push_str(trimmed);
+            let Some(list) = pending.strip_suffix(';') else {
+                continue;
+            };
+            let Some(index) = database.entities.len().checked_sub(1) else {
+                return Err(Error::InvalidLine(trimmed.to_string()))?;
+            };
+
+            for prefix in list.split(',') {
+                // Remove the overrides (e.g. `(26)`, `[50]`, `<9.88/-114.23>`, `{OC}`, or `~-8.0~`)
+                let prefix = prefix.trim();
+                let end = prefix.find(['(', '[', '<', '{', '~']).unwrap_or(prefix.len());
+                let prefix = &prefix[..end];
+
+                match prefix.strip_prefix('=') {
+                    Some(callsign) => { database.callsigns.insert(callsign.to_string(), index); },
+                    None if !prefix.is_empty() => {
+                        database.max_prefix_len = database.max_prefix_len.max(prefix.len());
+                        database.prefixes.insert(prefix.to_string(), index);
+                    },
+                    None => {}
+                }
+            }
+            pending.clear();
+        }
+
+        if database.entities.is_empty() {
+            return Err(Error::Empty)?;
+        }
+
+        Ok(database)
+    }
+
+    /// Reads and parses a cty.dat file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(Error::Io)?;
+        Self::parse(&text)
+    }
+
+    /// Downloads the latest cty.dat file, saving it to the provided path
+    pub async fn download(url: String, path: PathBuf) -> Result<Self> {
+        let text = reqwest::get(url).await.map_err(Error::Request)?
+            .error_for_status().map_err(Error::Request)?
+            .text().await.map_err(Error::Request)?;
+
+        // Make sure the file is valid before overwriting the old one
+        let database = Self::parse(&text)?;
+        tokio::fs::write(path, text).await.map_err(Error::Io)?;
+
+        Ok(database)
+    }
+
+    /// Gets the entity of a callsign
+    pub fn lookup(&self, callsign: &str) -> Option<&Entity> {
+        let callsign = callsign.trim().to_ascii_uppercase();
+
+        // Check the exact callsigns first, including any portable designator
+        if let Some(index) = self.callsigns.get(&callsign) {
+            return self.entities.get(*index);
+        }
+        let base = base_callsign(&callsign);
+        if let Some(index) = self.callsigns.get(base) {
+            return self.entities.get(*index);
+        }
+
+        // Find the longest matching prefix
+        (1..=base.len().min(self.max_prefix_len)).rev()
+            .filter_map(|len| base.get(..len))
+            .find_map(|prefix| self.prefixes.get(prefix))
+            .and_then(|index| self.entities.get(*index))
+    }
+
+    /// The number of entities
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+}
+
+/// Gets the part of a callsign that determines its entity.
+///
+/// Examples:
+/// - `W1AW/P` -> `W1AW`
+/// - `KH6/W1AW` -> `KH6`
+/// - `W1AW/KH6` -> `KH6`
+fn base_callsign(callsign: &str) -> &str {
+    // These don't change the entity
+    const SUFFIXES: [&str; 8] = ["P", "M", "MM", "AM", "QRP", "A", "B", "LH"];
+
+    let parts: Vec<&str> = callsign.split('/')
+        .filter(|p| !p.is_empty() && !SUFFIXES.contains(p) && !p.chars().all(|c| c.is_ascii_digit()))
+        .collect();
+
+    // If there are two parts, the shorter one is the prefix
+    parts.into_iter().min_by_key(|p| p.len()).unwrap_or(callsign)
+}
+
+/// The default path of the cty.dat file, which is next to the exe file
+pub fn default_path() -> PathBuf {
+    let exe_path = current_exe().expect("Failed to get path of exe file");
+    let exe_dir = exe_path.parent().expect("Failed to get parent directory of exe file");
+    exe_dir.join(CTY_FILE)
+}
+
+
+/// Errors regarding the DXCC module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read the country file: {0}")]
+    Io(std::io::Error),
+    #[error("Failed to download the country file: {0}")]
+    Request(reqwest::Error),
+    #[error("Invalid line in the country file: {0}")]
+    InvalidLine(String),
+    #[error("The country file doesn't contain any entities")]
+    Empty
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A few entities from a real cty.dat file
+    const CTY: &str = "\
+Spratly Islands:          26:  50:  OC:    9.88:  -114.23:    -8.0:  1S:
+    1S,9M0,BM9S,BN9S,=9M6AAC(26),=9M6NA;
+United States:            05:  08:  NA:   37.53:    91.67:     5.0:  K:
+    AA,AB,AC,AD,AE,AF,AG,AI,AJ,AK,K,N,W,
+    =KH6AB(3)[6]<19.58/155.50>{OC}~-10.0~;
+Hawaii:                   31:  61:  OC:   21.12:   157.48:    10.0:  KH6:
+    AH6,AH7,KH6,KH7,NH6,NH7,WH6,WH7;
+Sicily:                   15:  28:  EU:   37.50:   -14.00:    -1.0:  *IT9:
+    IT9,IW9;
+";
+
+    #[test]
+    fn base_callsign_removes_suffixes() {
+        assert_eq!(base_callsign("W1AW"), "W1AW");
+        assert_eq!(base_callsign("W1AW/P"), "W1AW");
+        assert_eq!(base_callsign("W1AW/MM"), "W1AW");
+        assert_eq!(base_callsign("W1AW/QRP"), "W1AW");
+        assert_eq!(base_callsign("W1AW/7"), "W1AW");
+        assert_eq!(base_callsign("W1AW/KH6/P"), "KH6");
+    }
+
+    #[test]
+    fn base_callsign_finds_prefix() {
+        assert_eq!(base_callsign("KH6/W1AW"), "KH6");
+        assert_eq!(base_callsign("W1AW/KH6"), "KH6");
+        // The first part is used if both parts are the same length
+        assert_eq!(base_callsign("K1AB/W1AW"), "K1AB");
+        // Nothing left after removing the suffixes
+        assert_eq!(base_callsign("P"), "P");
+        assert_eq!(base_callsign(""), "");
+    }
+
+    #[test]
+    fn parse_entities() {
+        let database = Database::parse(CTY).unwrap();
+        assert_eq!(database.entity_count(), 4);
+
+        let spratly = &database.entities[0];
+        assert_eq!(spratly.name, "Spratly Islands");
+        assert_eq!(spratly.prefix, "1S");
+        assert_eq!(spratly.continent, "OC");
+        assert_eq!(spratly.cq_zone, 26);
+        assert_eq!(spratly.itu_zone, 50);
+        // Longitude is positive to the west in the file
+        assert_eq!(spratly.location, Coord { x: 114.23, y: 9.88 });
+
+        let us = &database.entities[1];
+        assert_eq!(us.location, Coord { x: -91.67, y: 37.53 });
+
+        // The WAE marker is removed from the prefix
+        assert_eq!(database.entities[3].prefix, "IT9");
+
+        // The longest prefix is `BM9S`
+        assert_eq!(database.max_prefix_len, 4);
+    }
+
+    #[test]
+    fn parse_prefixes_and_callsigns() {
+        let database = Database::parse(CTY).unwrap();
+
+        // Prefixes spanning multiple lines
+        assert_eq!(database.prefixes.get("AA"), Some(&1));
+        assert_eq!(database.prefixes.get("W"), Some(&1));
+
+        // Exact callsigns, with their overrides removed
+        assert_eq!(database.callsigns.get("9M6AAC"), Some(&0));
+        assert_eq!(database.callsigns.get("9M6NA"), Some(&0));
+        assert_eq!(database.callsigns.get("KH6AB"), Some(&1));
+        assert!(!database.prefixes.contains_key("=9M6NA"));
+    }
+
+    #[test]
+    fn lookup() {
+        let database = Database::parse(CTY).unwrap();
+        let name = |callsign: &str| database.lookup(callsign).map(|e| e.name.as_str());
+
+        assert_eq!(name("W1AW"), Some("United States"));
+        assert_eq!(name(" w1aw "), Some("United States"));
+        // The longest prefix wins
+        assert_eq!(name("KH6ABC"), Some("Hawaii"));
+        assert_eq!(name("IT9ABC"), Some("Sicily"));
+        // Exact callsigns override the prefix
+        assert_eq!(name("9M6NA"), Some("Spratly Islands"));
+        assert_eq!(name("KH6AB"), Some("United States"));
+        assert_eq!(name("KH6AB/P"), Some("United States"));
+        // Portable prefixes
+        assert_eq!(name("KH6/W1AW"), Some("Hawaii"));
+        assert_eq!(name("W1AW/KH6"), Some("Hawaii"));
+        assert_eq!(name("W1AW/P"), Some("United States"));
+        // Unknown prefixes
+        assert_eq!(name("9M2ABC"), None);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        // Not enough fields in the header
+        assert!(Database::parse("Spratly Islands: 26: 50: OC:\n    1S;\n").is_err());
+        // Prefixes without an entity
+        assert!(Database::parse("    1S,9M0;\n").is_err());
+        // No entities
+        assert!(Database::parse("").is_err());
+        assert!(Database::parse("\n\n").is_err());
+    }
+}
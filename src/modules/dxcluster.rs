@@ -0,0 +1,658 @@
+//
+// A telnet client for DX cluster nodes (DX Spider, AR-Cluster, and CC-Cluster).
+//
+// After connecting, the node asks for a callsign (e.g. `login:`), then sends spots as lines formatted like:
+// `DX de W3LPL:     14025.0  JA1XYZ       CQ up 2                        1234Z`
+// Any node that speaks this format can be used, including a local test node (e.g. `nc -l 7300` with spot lines pasted in).
+//
+
+use std::{collections::VecDeque, time::Duration};
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use log::{debug, info, trace};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream, sync::mpsc, task::JoinHandle};
+use crate::{GuiConfig, RT};
+use super::types;
+
+
+/// A spot received from a DX cluster
+#[derive(Debug, Clone)]
+pub struct Spot {
+    /// The callsign of the station that posted the spot
+    pub spotter: String,
+    /// The frequency of the spotted station, in Hz
+    pub frequency: u64,
+    /// The callsign of the spotted station
+    pub callsign: String,
+    /// The comment attached to the spot
+    pub comment: String,
+    /// The time of the spot, in UTC
+    pub time: NaiveDateTime
+}
+impl Spot {
+    /// Parses a spot line, returning `None` if the line isn't a spot.
+    ///
+    /// Both live spots (`DX de SPOTTER: ...`) and the output of `SH/DX` (`14025.0 JA1XYZ 17-Oct-2026 1234Z comment <SPOTTER>`) are supported.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        match line.strip_prefix("DX de ") {
+            Some(rest) => Self::parse_live(rest),
+            None => Self::parse_show(line)
+        }
+    }
+
+    /// Parses a live spot, not including the `DX de ` prefix
+    fn parse_live(line: &str) -> Option<Self> {
+        let (spotter, rest) = line.split_once(':')?;
+        let mut tokens = rest.split_whitespace();
+        let frequency = parse_khz(tokens.next()?)?;
+        let callsign = tokens.next()?;
+        let remaining: Vec<&str> = tokens.collect();
+
+        // The time is usually the last token, but some nodes add the spotter's locator after it
+        let (comment, time) = match remaining.iter().rposition(|t| parse_hhmm(t).is_some()) {
+            Some(idx) => (&remaining[..idx], parse_hhmm(remaining[idx])),
+            None => (&remaining[..], None)
+        };
+
+        Some(Self {
+            spotter: spotter.trim().to_ascii_uppercase(),
+            frequency,
+            callsign: callsign.to_ascii_uppercase(),
+            comment: comment.join(" "),
+            time: spot_time(None, time)
+        })
+    }
+
+    /// Parses a line of `SH/DX` output
+    fn parse_show(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        let frequency = parse_khz(tokens.next()?)?;
+        let callsign = tokens.next()?;
+        let date = NaiveDate::parse_from_str(tokens.next()?, "%d-%b-%Y").ok()?;
+        let time = parse_hhmm(tokens.next()?)?;
+        let mut remaining: Vec<&str> = tokens.collect();
+
+        // The spotter is the last token, wrapped in angle brackets
+        let spotter = remaining.pop()?.strip_prefix('<')?.strip_suffix('>')?;
+
+        Some(Self {
+            spotter: spotter.to_ascii_uppercase(),
+            frequency,
+            callsign: callsign.to_ascii_uppercase(),
+            comment: remaining.join(" "),
+            time: spot_time(Some(date), Some(time))
+        })
+    }
+
+    /// The band of the spot
+    pub fn band(&self) -> types::Band {
+        types::Band::from_frequency(self.frequency)
+    }
+
+    /// The mode of the spot, using the comment if it mentions a mode, or the band plan otherwise
+    pub fn mode(&self) -> types::Mode {
+        guess_mode(self.frequency, &self.comment)
+    }
+}
+
+/// Parses a frequency in kHz (e.g. `14025.0`) into Hz
+fn parse_khz(s: &str) -> Option<u64> {
+    let khz = s.parse::<f64>().ok()?;
+    (khz > 0.0).then(|| (khz * 1000.0).round() as u64)
+}
+
+/// Parses a spot time formatted as `HHMMZ`
+fn parse_hhmm(s: &str) -> Option<NaiveTime> {
+    let s = s.strip_suffix('Z')?;
+    if s.len() != 4 {
+        return None;
+    }
+    NaiveTime::parse_from_str(s, "%H%M").ok()
+}
+
+/// Gets the date and time of a spot. Live spots only include the time, so the current date is used, unless that would put the spot in the future.
+fn spot_time(date: Option<NaiveDate>, time: Option<NaiveTime>) -> NaiveDateTime {
+    let now = Utc::now().naive_utc();
+    match (date, time) {
+        (Some(date), Some(time)) => date.and_time(time),
+        (None, Some(time)) => {
+            let dt = now.date().and_time(time);
+            // The spot was posted just before midnight
+            if dt > now + chrono::Duration::minutes(5) { dt - chrono::Duration::days(1) } else { dt }
+        },
+        _ => now
+    }
+}
+
+/// Guesses the mode of a signal, using the comment if it mentions a mode, or the band plan otherwise
+pub fn guess_mode(frequency: u64, comment: &str) -> types::Mode {
+
+    // Check the comment for a mode (e.g. `FT8 -12dB` or `CW 25 WPM`)
+    let comment = comment.to_ascii_uppercase();
+    for word in comment.split(|c: char| !c.is_ascii_alphanumeric()) {
+        match word {
+            "CW" => return types::Mode::CW,
+            "SSB" | "USB" | "LSB" => return types::Mode::SSB,
+            "AM" => return types::Mode::AM,
+            "FM" => return types::Mode::FM,
+            "FT8" => return types::Mode::FT8,
+            "FT4" => return types::Mode::FT4,
+            "RTTY" => return types::Mode::RTTY,
+            "PSK" | "PSK31" | "BPSK" | "BPSK31" => return types::Mode::PSK31,
+            "JS8" => return types::Mode::JS8CALL,
+            "OLIVIA" => return types::Mode::OLIVIA,
+            "DOMINO" | "DOMINOEX" => return types::Mode::DOMINOEX,
+            _ => {}
+        }
+    }
+
+    // Check the FT8 frequencies, in kHz
+    const FT8_FREQUENCIES: [u64; 11] = [1_840, 3_573, 5_357, 7_074, 10_136, 14_074, 18_100, 21_074, 24_915, 28_074, 50_313];
+    if FT8_FREQUENCIES.iter().any(|f| (f * 1000..=f * 1000 + 3000).contains(&frequency)) {
+        return types::Mode::FT8;
+    }
+
+    // The bottom of most bands is used for CW, and the rest is used for phone
+    use types::Band;
+    let band = Band::from_frequency(frequency);
+    let cw_width = match band {
+        Band::B2200m | Band::B630m | Band::B30m => u64::MAX,
+        Band::B160m => 40_000,
+        Band::B17m => 32_000,
+        Band::B12m => 25_000,
+        Band::B80m | Band::B40m | Band::B20m | Band::B15m | Band::B10m => 70_000,
+        Band::B6m | Band::B2m => 100_000,
+        _ => 0
+    };
+    match band.freq_range() {
+        Some((min, _)) if frequency - min < cw_width => types::Mode::CW,
+        _ => types::Mode::SSB
+    }
+}
+
+/// The state of the telnet command that's being received
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum TelnetState {
+    /// Text is being received
+    #[default]
+    Text,
+    /// An IAC byte was received, so the next byte is a command
+    Command,
+    /// A WILL, WONT, DO, or DONT command was received, so the next byte is an option
+    Option,
+    /// A subnegotiation is being received, which ends with IAC SE
+    Subnegotiation,
+    /// An IAC byte was received during a subnegotiation
+    SubnegotiationCommand
+}
+
+/// Splits the bytes received from a telnet connection into lines, removing telnet commands (e.g. option negotiation).
+///
+/// Telnet commands and UTF-8 characters can be split across reads, so the incomplete line and command are kept between them.
+#[derive(Debug, Default)]
+struct LineDecoder {
+    /// The bytes of the incomplete line
+    pending: Vec<u8>,
+    /// The state of the telnet command that's being received
+    state: TelnetState
+}
+impl LineDecoder {
+    /// The maximum length of a line, in bytes. A longer line is split, so a node that never sends a newline can't use up our memory.
+    const MAX_LINE_LENGTH: usize = 4096;
+
+    /// Adds the received bytes, returning the lines that were completed without their line endings
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        const IAC: u8 = 255;
+        const SB: u8 = 250;
+        const SE: u8 = 240;
+
+        let mut lines = Vec::new();
+        for &byte in bytes {
+            self.state = match (self.state, byte) {
+                (TelnetState::Text, IAC) => TelnetState::Command,
+                (TelnetState::Text, byte) => {
+                    self.pending.push(byte);
+                    if byte == b'\n' || self.pending.len() >= Self::MAX_LINE_LENGTH {
+                        lines.push(self.take_line());
+                    }
+                    TelnetState::Text
+                },
+                // An escaped 255 byte
+                (TelnetState::Command, IAC) => {
+                    self.pending.push(IAC);
+                    TelnetState::Text
+                },
+                // WILL, WONT, DO, and DONT are followed by an option byte
+                (TelnetState::Command, 251..=254) => TelnetState::Option,
+                (TelnetState::Command, SB) => TelnetState::Subnegotiation,
+                // Any other command is 2 bytes
+                (TelnetState::Command, _) | (TelnetState::Option, _) => TelnetState::Text,
+                (TelnetState::Subnegotiation, IAC) => TelnetState::SubnegotiationCommand,
+                (TelnetState::Subnegotiation, _) => TelnetState::Subnegotiation,
+                (TelnetState::SubnegotiationCommand, SE) => TelnetState::Text,
+                (TelnetState::SubnegotiationCommand, _) => TelnetState::Subnegotiation
+            };
+        }
+        lines
+    }
+
+    /// The incomplete line, such as a login prompt that isn't followed by a newline
+    fn pending(&self) -> String {
+        String::from_utf8_lossy(&self.pending).into_owned()
+    }
+
+    /// Discards the incomplete line
+    fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Decodes and removes the incomplete line
+    fn take_line(&mut self) -> String {
+        let line = String::from_utf8_lossy(&self.pending).trim_end().to_string();
+        self.pending.clear();
+        line
+    }
+}
+
+
+/// A message received from the DX cluster
+#[derive(Debug, Clone)]
+enum Message {
+    /// The connection was established
+    Connected,
+    /// The connection was lost
+    Disconnected(String),
+    /// A line of text was received
+    Line(String),
+    /// A spot was received
+    Spot(Spot)
+}
+
+/// A connection to a DX cluster node. This reconnects automatically if the connection is lost.
+pub struct Client {
+    /// The address of the node
+    address: String,
+    /// The callsign that we log in with
+    callsign: String,
+    /// The receiving end of the messages that were received from the node
+    rx: mpsc::UnboundedReceiver<Message>,
+    /// The sending end of the commands that should be sent to the node
+    tx: mpsc::UnboundedSender<String>,
+    /// The connection task
+    task: JoinHandle<()>,
+    /// Are we currently connected?
+    connected: bool,
+    /// The most recent lines received from the node, oldest first
+    console: VecDeque<String>
+}
+impl Client {
+    /// How long to wait before reconnecting if the connection failed
+    const RETRY_DELAY: Duration = Duration::from_secs(10);
+    /// The maximum number of lines to keep in the console
+    const MAX_CONSOLE_LINES: usize = 500;
+
+    /// Connects to the node at the provided address (e.g. `dxc.ve7cc.net:23`), logging in with the provided callsign
+    pub fn new(address: String, callsign: String) -> Self {
+        let (message_tx, message_rx) = mpsc::unbounded_channel();
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+
+        let task = RT.spawn(Self::run(address.clone(), callsign.clone(), message_tx, command_rx));
+
+        Self {
+            address,
+            callsign,
+            rx: message_rx,
+            tx: command_tx,
+            task,
+            connected: false,
+            console: Default::default()
+        }
+    }
+
+    /// Sends a command to the node (e.g. `SH/DX 30`)
+    pub fn send(&self, command: &str) {
+        let _ = self.tx.send(command.trim().to_string());
+    }
+
+    /// The address of the node
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Are we currently connected?
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The most recent lines received from the node, oldest first
+    pub fn console(&self) -> &VecDeque<String> {
+        &self.console
+    }
+
+    /// Starts, stops, or restarts a client so that it matches the provided settings. We can't log in without a callsign, so the client is stopped if it's empty.
+    pub fn reconcile(client: Option<Self>, enabled: bool, address: &str, callsign: &str) -> Option<Self> {
+        if !enabled || callsign.is_empty() {
+            None
+        } else if client.as_ref().map_or(true, |c| c.address != address || c.callsign != callsign) {
+            Some(Self::new(address.to_string(), callsign.to_string()))
+        } else {
+            client
+        }
+    }
+
+    /// Processes the messages received from the node, returning the spots
+    pub fn process(&mut self) -> Vec<Spot> {
+        let mut spots = Vec::new();
+        while let Ok(message) = self.rx.try_recv() {
+            let line = match message {
+                Message::Connected => {
+                    self.connected = true;
+                    format!("*** Connected to {}", self.address)
+                },
+                Message::Disconnected(reason) => {
+                    self.connected = false;
+                    format!("*** Disconnected: {reason}")
+                },
+                Message::Line(line) => line,
+                Message::Spot(spot) => {
+                    spots.push(spot);
+                    continue;
+                }
+            };
+
+            self.console.push_back(line);
+            while self.console.len() > Self::MAX_CONSOLE_LINES {
+                self.console.pop_front();
+            }
+        }
+        spots
+    }
+
+    /// The connection task. This connects to the node and forwards messages in both directions until the client is dropped.
+    async fn run(address: String, callsign: String, tx: mpsc::UnboundedSender<Message>, mut rx: mpsc::UnboundedReceiver<String>) {
+        loop {
+            match Self::connection(&address, &callsign, &tx, &mut rx).await {
+                Ok(()) => return,
+                Err(err) => {
+                    debug!("DX cluster connection to '{address}' failed: {err}");
+                    if tx.send(Message::Disconnected(err.to_string())).is_err() {
+                        return;
+                    }
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// A single connection to the node. This returns `Ok(())` if the client was dropped, or an error if the connection failed.
+    async fn connection(address: &str, callsign: &str, tx: &mpsc::UnboundedSender<Message>, rx: &mut mpsc::UnboundedReceiver<String>) -> Result<()> {
+        let mut stream = TcpStream::connect(address).await.map_err(Error::Io)?;
+        info!("Connected to DX cluster at '{address}'");
+        if tx.send(Message::Connected).is_err() {
+            return Ok(());
+        }
+
+        let mut buf = [0; 4096];
+        let mut decoder = LineDecoder::default();
+        let mut logged_in = false;
+
+        loop {
+            tokio::select! {
+                // Data was received from the node
+                read = stream.read(&mut buf) => {
+                    let read = read.map_err(Error::Io)?;
+                    if read == 0 {
+                        return Err(Error::Disconnected)?;
+                    }
+
+                    // Process each complete line
+                    for line in decoder.push(&buf[..read]) {
+                        trace!("DX cluster: {line}");
+
+                        let spot = Spot::parse(&line);
+                        if tx.send(Message::Line(line)).is_err() {
+                            return Ok(());
+                        }
+                        if let Some(spot) = spot {
+                            if tx.send(Message::Spot(spot)).is_err() {
+                                return Ok(());
+                            }
+                        }
+                    }
+
+                    // The login prompt isn't followed by a newline, so check the incomplete line
+                    let prompt = decoder.pending().trim().to_ascii_lowercase();
+                    if !logged_in && (prompt.ends_with("login:") || prompt.ends_with("call:") || prompt.ends_with("callsign:")) {
+                        stream.write_all(format!("{callsign}\r\n").as_bytes()).await.map_err(Error::Io)?;
+                        logged_in = true;
+                        decoder.clear();
+                    }
+                },
+                // A command should be sent to the node
+                command = rx.recv() => {
+                    let Some(command) = command else {
+                        return Ok(());
+                    };
+                    stream.write_all(format!("{command}\r\n").as_bytes()).await.map_err(Error::Io)?;
+                }
+            }
+        }
+    }
+}
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+        .field("address", &self.address)
+        .field("callsign", &self.callsign)
+        .field("connected", &self.connected)
+        .field("console", &self.console.len())
+        .finish()
+    }
+}
+
+/// Processes any messages received from the DX cluster, connecting or disconnecting depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the client out of the config so we can mutate the rest of the config while processing messages, connecting, disconnecting, or reconnecting if the config changed
+    let callsign = config.station.callsign.trim().to_ascii_uppercase();
+    let client = Client::reconcile(config.dxcluster.take(), config.dxcluster_config.enabled, &config.dxcluster_config.address, &callsign);
+
+    let Some(mut client) = client else {
+        return;
+    };
+
+    // Send the spots to the tabs
+    for spot in client.process() {
+        config.events.push_back((None, types::Event::DxSpot(spot)));
+    }
+
+    // Put the client back into the config
+    config.dxcluster = Some(client);
+
+}
+
+
+/// The DX cluster module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should we connect to the DX cluster?
+    pub enabled: bool,
+    /// The address of the DX cluster node
+    pub address: String
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "dxc.ve7cc.net:23".into()
+        }
+    }
+}
+
+/// Errors regarding the DX cluster module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Connection error: {0}")]
+    Io(std::io::Error),
+    #[error("The node closed the connection")]
+    Disconnected
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::{AsyncBufReadExt, BufReader}, net::TcpListener};
+
+    #[test]
+    fn live_spot_is_parsed() {
+        let spot = Spot::parse("DX de W3LPL:     14025.0  JA1XYZ       CQ up 2                        1234Z\r\n").unwrap();
+        assert_eq!(spot.spotter, "W3LPL");
+        assert_eq!(spot.frequency, 14_025_000);
+        assert_eq!(spot.callsign, "JA1XYZ");
+        assert_eq!(spot.comment, "CQ up 2");
+        assert_eq!(spot.time.time(), NaiveTime::from_hms_opt(12, 34, 0).unwrap());
+        assert_eq!(spot.band(), types::Band::B20m);
+        assert_eq!(spot.mode(), types::Mode::CW);
+
+        // Skimmer spots with the spotter's locator after the time
+        let spot = Spot::parse("DX de EA5WU-#:    7074.5  dl1abc       FT8 -12 dB  CQ               2259Z IM98").unwrap();
+        assert_eq!((spot.spotter.as_str(), spot.callsign.as_str()), ("EA5WU-#", "DL1ABC"));
+        assert_eq!(spot.frequency, 7_074_500);
+        assert_eq!(spot.comment, "FT8 -12 dB CQ");
+        assert_eq!(spot.time.time(), NaiveTime::from_hms_opt(22, 59, 0).unwrap());
+        assert_eq!(spot.mode(), types::Mode::FT8);
+    }
+
+    #[test]
+    fn show_dx_spot_is_parsed() {
+        let spot = Spot::parse("14025.0 JA1XYZ      17-Oct-2026 1234Z  CQ up 2                      <W3LPL>").unwrap();
+        assert_eq!(spot.spotter, "W3LPL");
+        assert_eq!(spot.frequency, 14_025_000);
+        assert_eq!(spot.callsign, "JA1XYZ");
+        assert_eq!(spot.comment, "CQ up 2");
+        assert_eq!(spot.time, NaiveDate::from_ymd_opt(2026, 10, 17).unwrap().and_hms_opt(12, 34, 0).unwrap());
+    }
+
+    #[test]
+    fn other_lines_are_ignored() {
+        let lines = [
+            "",
+            "WWV de W0MU <18>:   SFI=68, A=4, K=1, No Storms -> No Storms",
+            "WCY de DK0WCY-1 <08> : K=2 expK=0 A=7 R=17 SFI=70 SA=qui GMF=qui Au=no",
+            "To ALL de K1ABC: anyone hear the 6m opening?",
+            "N0CALL de DXC-NODE  17-Oct-2026 1234Z dxspider >",
+            // Malformed spots
+            "DX de W3LPL 14025.0 JA1XYZ 1234Z",
+            "DX de W3LPL: fourteen JA1XYZ 1234Z",
+            "DX de W3LPL: 14025.0",
+            "14025.0 JA1XYZ 17-Oct-2026 1234Z CQ up 2 W3LPL"
+        ];
+        for line in lines {
+            assert!(Spot::parse(line).is_none(), "'{line}' shouldn't be a spot");
+        }
+    }
+
+    #[test]
+    fn mode_is_guessed() {
+        // The comment is checked first, by whole words
+        assert_eq!(guess_mode(14_200_000, "FT8 -12dB"), types::Mode::FT8);
+        assert_eq!(guess_mode(14_200_000, "cq 25 wpm cw"), types::Mode::CW);
+        assert_eq!(guess_mode(7_010_000, "USB?"), types::Mode::SSB);
+        assert_eq!(guess_mode(14_200_000, "CWT contest"), types::Mode::SSB);
+
+        // Then the FT8 frequencies and the band plan
+        assert_eq!(guess_mode(14_075_500, ""), types::Mode::FT8);
+        assert_eq!(guess_mode(14_020_000, ""), types::Mode::CW);
+        assert_eq!(guess_mode(14_250_000, ""), types::Mode::SSB);
+        assert_eq!(guess_mode(10_140_000, ""), types::Mode::CW);
+        assert_eq!(guess_mode(15_000_000, ""), types::Mode::SSB);
+    }
+
+    #[test]
+    fn telnet_commands_are_removed_across_reads() {
+        let mut decoder = LineDecoder::default();
+
+        // IAC WILL ECHO split between reads, an escaped 255 byte, and a subnegotiation
+        assert!(decoder.push(&[255]).is_empty());
+        assert!(decoder.push(&[251, 1, b'a', 255, 255, b'b', 255]).is_empty());
+        assert!(decoder.push(&[250, 24, 1, 255, 255, 255]).is_empty());
+        assert_eq!(decoder.push(&[240, b'c', b'\r', b'\n']), [String::from_utf8_lossy(&[b'a', 255, b'b', b'c']).into_owned()]);
+        assert!(decoder.pending().is_empty());
+
+        // A command that isn't followed by an option
+        assert_eq!(decoder.push(b"x\xff\xf1y\n"), ["xy"]);
+    }
+
+    #[test]
+    fn utf8_is_decoded_across_reads() {
+        let mut decoder = LineDecoder::default();
+        let text = "DX de SM5ÅÖ: 14025.0 JA1XYZ CQ 1234Z\n".as_bytes();
+        let split = text.iter().position(|b| *b >= 0x80).unwrap() + 1;
+
+        assert!(decoder.push(&text[..split]).is_empty());
+        assert_eq!(decoder.push(&text[split..]), ["DX de SM5ÅÖ: 14025.0 JA1XYZ CQ 1234Z"]);
+    }
+
+    #[test]
+    fn long_lines_are_split() {
+        let mut decoder = LineDecoder::default();
+        let lines = decoder.push(&vec![b'a'; LineDecoder::MAX_LINE_LENGTH * 2 + 10]);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l.len() == LineDecoder::MAX_LINE_LENGTH));
+        assert_eq!(decoder.pending().len(), 10);
+    }
+
+    #[test]
+    fn client_logs_in_and_receives_spots() {
+        // A fake node that asks for a callsign, and sends a spot split across two writes if we logged in with the right one
+        let listener = RT.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let node = RT.spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            writer.write_all(b"\xff\xfb\x01Welcome to the test node\r\nPlease enter your call\r\nlogin: ").await.unwrap();
+
+            let mut callsign = String::new();
+            BufReader::new(reader).read_line(&mut callsign).await.unwrap();
+            if callsign != "N0CALL\r\n" {
+                return;
+            }
+
+            writer.write_all(b"Hello N0CALL\r\nDX de W3LPL:     14025.0  JA1XYZ       CQ up 2   ").await.unwrap();
+            writer.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            writer.write_all(b"                     1234Z\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let mut client = Client::new(address, "N0CALL".into());
+        let mut spots = Vec::new();
+        for _ in 0..100 {
+            spots.extend(client.process());
+            if !spots.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        assert!(client.connected());
+        assert_eq!(spots.len(), 1);
+        assert_eq!((spots[0].spotter.as_str(), spots[0].callsign.as_str(), spots[0].frequency), ("W3LPL", "JA1XYZ", 14_025_000));
+        assert!(client.console().iter().any(|l| l == "Welcome to the test node"));
+
+        drop(client);
+        node.abort();
+    }
+}
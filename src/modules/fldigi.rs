@@ -0,0 +1,783 @@
+//
+// A client for the fldigi XML-RPC API, and a server that accepts QSOs logged by fldigi.
+//
+// fldigi's XML-RPC server listens on port 7362 by default. It's polled for the current modem, frequency, and the log fields that the operator fills in.
+// When the operator saves a QSO, fldigi sends it to an fllog-compatible logbook server (Configure -> Logging -> Logbook -> "Connect to server").
+// We act as that logbook server, which listens on port 8421 by default.
+//
+
+use std::time::Duration;
+use anyhow::Result;
+use log::{debug, error, info, trace, warn};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader}, net::{tcp::OwnedWriteHalf, TcpListener, TcpStream}, sync::mpsc, task::JoinHandle};
+use crate::{GuiConfig, RT};
+use super::{adif, types::{self, escape_xml}};
+
+
+/// A message received from fldigi
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// The state of fldigi was polled
+    State(State),
+    /// fldigi couldn't be reached
+    Disconnected,
+    /// The operator saved a QSO in fldigi
+    QsoLogged(types::Contact)
+}
+
+/// The state of fldigi, including the log fields that the operator has filled in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct State {
+    /// The name of the current modem (e.g. `BPSK31` or `OLIVIA-8-500`)
+    pub modem: String,
+    /// The dial frequency of the radio, in Hz
+    pub dial_frequency: u64,
+    /// The audio offset of the carrier, in Hz
+    pub carrier: u64,
+    /// The callsign of the other station
+    pub callsign: String,
+    /// The name of the other operator
+    pub name: String,
+    /// The QTH of the other station
+    pub qth: String,
+    /// The grid square of the other station
+    pub locator: String,
+    /// The signal report received from the other station
+    pub rst_in: String,
+    /// The signal report sent to the other station
+    pub rst_out: String
+}
+impl State {
+    /// The frequency of the signal (dial + carrier), in Hz
+    pub fn frequency(&self) -> u64 {
+        self.dial_frequency + self.carrier
+    }
+
+    /// The current modem as a [types::Mode]
+    pub fn mode(&self) -> types::Mode {
+        modem_to_mode(&self.modem)
+    }
+
+    /// The note that should be filled into the logger, made from the name and QTH of the other station
+    pub fn note(&self) -> String {
+        [self.name.trim(), self.qth.trim()].into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" - ")
+    }
+}
+
+/// Converts the name of a fldigi modem into a [types::Mode].
+///
+/// fldigi includes the speed or bandwidth in the modem name (e.g. `BPSK31`, `OLIVIA-8-500`, or `DOMX16`), so the prefix is used where possible.
+pub fn modem_to_mode(modem: &str) -> types::Mode {
+    let modem = modem.trim().to_ascii_uppercase();
+
+    match modem.as_str() {
+        "BPSK31" => types::Mode::PSK31,
+        m if m.starts_with("RTTY") => types::Mode::RTTY,
+        m if m.starts_with("OLIVIA") => types::Mode::OLIVIA,
+        m if m.starts_with("DOMX") || m.starts_with("DOMINOEX") => types::Mode::DOMINOEX,
+        m => types::Mode::from_name(m)
+    }
+}
+
+
+/// The methods that are called on the fldigi XML-RPC server each time it's polled, in the same order as the fields in [State].
+///
+/// They're sent in a single `system.multicall`, so a poll is one request.
+const POLL_METHODS: [&str; 9] = [
+    "modem.get_name",
+    "main.get_frequency",
+    "modem.get_carrier",
+    "log.get_call",
+    "log.get_name",
+    "log.get_qth",
+    "log.get_locator",
+    "log.get_rst_in",
+    "log.get_rst_out"
+];
+
+/// Builds an XML-RPC method call with string parameters
+fn method_call(method: &str, params: &[&str]) -> String {
+    let params: String = params.iter()
+        .map(|p| format!("<param><value><string>{}</string></value></param>", escape_xml(p)))
+        .collect();
+
+    format!("<?xml version=\"1.0\"?><methodCall><methodName>{method}</methodName><params>{params}</params></methodCall>")
+}
+
+/// Builds an XML-RPC `system.multicall` that calls each of the methods without parameters
+fn multicall(methods: &[&str]) -> String {
+    let calls: String = methods.iter()
+        .map(|m| format!("<value><struct>\
+            <member><name>methodName</name><value><string>{}</string></value></member>\
+            <member><name>params</name><value><array><data></data></array></value></member>\
+            </struct></value>", escape_xml(m)))
+        .collect();
+
+    format!("<?xml version=\"1.0\"?><methodCall><methodName>system.multicall</methodName><params><param><value><array><data>{calls}</data></array></value></param></params></methodCall>")
+}
+
+/// Builds an XML-RPC method response with a single string value
+fn method_response(value: &str) -> String {
+    format!("<?xml version=\"1.0\"?><methodResponse><params><param><value><string>{}</string></value></param></params></methodResponse>", escape_xml(value))
+}
+
+/// Parses an XML-RPC method response, returning the value as a string.
+///
+/// fldigi only returns scalar values (strings, integers, doubles, and booleans), so we don't need to handle arrays or structs.
+fn parse_response(xml: &str) -> Result<String, Error> {
+    let document = roxmltree::Document::parse(xml).map_err(Error::InvalidXml)?;
+    let root = document.root_element();
+
+    // A fault is a struct with a `faultCode` and `faultString` member, and the string is the last value
+    if let Some(fault) = root.children().find(|n| n.has_tag_name("fault")) {
+        let message = values(fault).pop().unwrap_or_default();
+        return Err(Error::Fault(message));
+    }
+
+    // A method without a return value (e.g. a setter) has an empty response
+    Ok(values(root).into_iter().next().unwrap_or_default())
+}
+
+/// Parses the response to a `system.multicall`, returning the value of each call as a string.
+///
+/// The response is an array with a result for each call, which is either an array with the value, or a fault struct if the call failed.
+fn parse_multicall_response(xml: &str) -> Result<Vec<String>, Error> {
+    let document = roxmltree::Document::parse(xml).map_err(Error::InvalidXml)?;
+    let root = document.root_element();
+
+    if let Some(fault) = root.children().find(|n| n.has_tag_name("fault")) {
+        let message = values(fault).pop().unwrap_or_default();
+        return Err(Error::Fault(message));
+    }
+
+    let results = descend(root, &["params", "param", "value", "array", "data"]).ok_or(Error::InvalidMulticall)?;
+    results.children()
+        .filter(|n| n.has_tag_name("value"))
+        .map(|result| {
+            if let Some(fault) = descend(result, &["struct"]) {
+                return Err(Error::Fault(values(fault).pop().unwrap_or_default()));
+            }
+            let value = descend(result, &["array", "data", "value"]).ok_or(Error::InvalidMulticall)?;
+            Ok(values(value).into_iter().next().unwrap_or_default())
+        })
+        .collect()
+}
+
+/// Parses an XML-RPC method call, returning the name of the method and its parameters as strings
+fn parse_call(xml: &str) -> Result<(String, Vec<String>), Error> {
+    let document = roxmltree::Document::parse(xml).map_err(Error::InvalidXml)?;
+    let root = document.root_element();
+    if !root.has_tag_name("methodCall") {
+        return Err(Error::NotMethodCall);
+    }
+
+    let method = root.children()
+        .find(|n| n.has_tag_name("methodName"))
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .ok_or(Error::NotMethodCall)?;
+
+    Ok((method, values(root)))
+}
+
+/// Follows the first child element with each of the tag names, returning `None` if one doesn't exist
+fn descend<'a, 'input>(node: roxmltree::Node<'a, 'input>, tags: &[&str]) -> Option<roxmltree::Node<'a, 'input>> {
+    tags.iter().try_fold(node, |node, tag| node.children().find(|n| n.has_tag_name(*tag)))
+}
+
+/// Gets the text of every `<value>` below a node, removing the type tag (e.g. `<string>`) if one exists
+fn values(node: roxmltree::Node) -> Vec<String> {
+    node.descendants()
+        .filter(|n| n.has_tag_name("value"))
+        .map(|value| {
+            // Use the text of the type tag, or the raw text since XML-RPC treats a value without a type as a string
+            let text = match value.children().find(|n| n.is_element()) {
+                Some(typed) => typed.text(),
+                None => value.text()
+            };
+            text.unwrap_or_default().trim().to_string()
+        })
+        .collect()
+}
+
+
+/// A connection to fldigi. This polls the fldigi XML-RPC server, and optionally runs a logbook server that fldigi sends QSOs to.
+pub struct Client {
+    /// The URL of the fldigi XML-RPC server
+    url: String,
+    /// The address of the logbook server, if it's enabled
+    log_address: Option<String>,
+    /// The receiving end of the messages that were received from fldigi
+    rx: mpsc::UnboundedReceiver<Message>,
+    /// The polling task
+    poll_task: JoinHandle<()>,
+    /// The logbook server task
+    log_task: Option<JoinHandle<()>>,
+    /// The tasks that are currently inserting logged QSOs into the database
+    insert_tasks: Vec<Promise<Result<types::Contact>>>,
+    /// The last state reported by fldigi
+    state: State,
+    /// Is fldigi currently reachable?
+    connected: bool
+}
+impl Client {
+    /// How often to poll fldigi
+    const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+    /// How long to wait before polling again if fldigi couldn't be reached
+    const RETRY_DELAY: Duration = Duration::from_secs(5);
+    /// How long to wait for fldigi to accept a connection
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+    /// How long to wait for fldigi to answer a poll, including connecting. The next poll waits for this one, so a hung fldigi can't pile up requests.
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+    /// The maximum length of the request line and each header sent to the logbook server, in bytes
+    const MAX_LINE_LENGTH: usize = 8 * 1024;
+    /// The maximum number of headers in a request sent to the logbook server
+    const MAX_HEADERS: usize = 64;
+    /// The maximum length of a request body sent to the logbook server, in bytes. A logged QSO is usually well under 1 KiB.
+    const MAX_BODY_LENGTH: usize = 64 * 1024;
+
+    /// Starts polling the fldigi XML-RPC server at the provided URL (e.g. `http://127.0.0.1:7362/RPC2`).
+    ///
+    /// If `log_address` is provided, a logbook server is started at that address (e.g. `127.0.0.1:8421`).
+    pub fn new(url: String, log_address: Option<String>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let poll_task = RT.spawn(Self::poll(url.clone(), tx.clone()));
+        let log_task = log_address.clone().map(|address| RT.spawn(Self::serve(address, tx)));
+
+        Self {
+            url,
+            log_address,
+            rx,
+            poll_task,
+            log_task,
+            insert_tasks: Default::default(),
+            state: Default::default(),
+            connected: false
+        }
+    }
+
+    /// The last state reported by fldigi
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Is fldigi currently reachable?
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Calls the methods on the fldigi XML-RPC server in a single request, returning their values as strings
+    async fn call(client: &reqwest::Client, url: &str, methods: &[&str]) -> Result<Vec<String>> {
+        let body = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, "text/xml")
+            .body(multicall(methods))
+            .send().await.map_err(Error::Request)?
+            .error_for_status().map_err(Error::Request)?
+            .text().await.map_err(Error::Request)?;
+
+        Ok(parse_multicall_response(&body)?)
+    }
+
+    /// Polls the state of fldigi
+    async fn get_state(client: &reqwest::Client, url: &str) -> Result<State> {
+        let values = Self::call(client, url, &POLL_METHODS).await?;
+
+        // The frequency is a double, and the carrier is an integer
+        let number = |s: &str| s.trim().parse::<f64>().unwrap_or_default().max(0.0).round() as u64;
+        let [modem, frequency, carrier, callsign, name, qth, locator, rst_in, rst_out] = values.try_into().map_err(|_| Error::InvalidState)?;
+
+        Ok(State {
+            modem,
+            dial_frequency: number(&frequency),
+            carrier: number(&carrier),
+            callsign: callsign.trim().to_ascii_uppercase(),
+            name,
+            qth,
+            locator: locator.trim().to_string(),
+            rst_in,
+            rst_out
+        })
+    }
+
+    /// The polling task. This polls fldigi until the client is dropped.
+    async fn poll(url: String, tx: mpsc::UnboundedSender<Message>) {
+        let client = match reqwest::Client::builder().connect_timeout(Self::CONNECT_TIMEOUT).timeout(Self::REQUEST_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Failed to create the fldigi client: {err}");
+                let _ = tx.send(Message::Disconnected);
+                return;
+            }
+        };
+        let mut connected = false;
+
+        loop {
+            let delay = match Self::get_state(&client, &url).await {
+                Ok(state) => {
+                    if !connected {
+                        info!("Connected to fldigi at '{url}'");
+                        connected = true;
+                    }
+                    if tx.send(Message::State(state)).is_err() {
+                        return;
+                    }
+                    Self::POLL_INTERVAL
+                },
+                Err(err) => {
+                    if connected {
+                        debug!("Lost connection to fldigi at '{url}': {err}");
+                        connected = false;
+                    }
+                    if tx.send(Message::Disconnected).is_err() {
+                        return;
+                    }
+                    Self::RETRY_DELAY
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// The logbook server task. This accepts connections from fldigi until the client is dropped.
+    async fn serve(address: String, tx: mpsc::UnboundedSender<Message>) {
+        let listener = loop {
+            match TcpListener::bind(&address).await {
+                Ok(listener) => break listener,
+                Err(err) => {
+                    error!("Failed to start fldigi logbook server on '{address}': {err}");
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                }
+            }
+        };
+
+        info!("fldigi logbook server listening on '{address}'");
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = Self::handle_connection(stream, &tx).await {
+                            debug!("fldigi logbook connection from '{peer}' failed: {err}");
+                        }
+                    });
+                },
+                Err(err) => warn!("Failed to accept fldigi logbook connection: {err}")
+            }
+
+            if tx.is_closed() {
+                return;
+            }
+        }
+    }
+
+    /// Handles the HTTP requests on a single logbook server connection
+    async fn handle_connection(stream: TcpStream, tx: &mpsc::UnboundedSender<Message>) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        loop {
+            // Read the request line and headers, keeping the content length
+            let mut content_length = 0;
+            let mut line = String::new();
+            for n_lines in 0.. {
+                line.clear();
+                let length = (&mut reader).take(Self::MAX_LINE_LENGTH as u64 + 1).read_line(&mut line).await.map_err(Error::Io)?;
+                if length == 0 {
+                    // The connection was closed
+                    return Ok(());
+                }
+
+                // Reject lines that are too long, and requests with too many headers
+                if length > Self::MAX_LINE_LENGTH || n_lines > Self::MAX_HEADERS {
+                    Self::reject(&mut writer, "400 Bad Request").await?;
+                    return Err(Error::RequestTooLarge.into());
+                }
+
+                let line = line.trim();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("content-length") {
+                        let Ok(length) = value.trim().parse() else {
+                            Self::reject(&mut writer, "400 Bad Request").await?;
+                            return Err(Error::InvalidContentLength.into());
+                        };
+                        content_length = length;
+                    }
+                }
+            }
+
+            // Reject bodies that are too large before allocating anything for them
+            if content_length > Self::MAX_BODY_LENGTH {
+                Self::reject(&mut writer, "413 Payload Too Large").await?;
+                return Err(Error::RequestTooLarge.into());
+            }
+
+            // Read the body
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await.map_err(Error::Io)?;
+            let body = String::from_utf8_lossy(&body);
+
+            let response = match parse_call(&body) {
+                Ok((method, params)) => Self::handle_call(&method, &params, tx),
+                Err(err) => {
+                    warn!("Failed to parse fldigi logbook request: {err}");
+                    method_response("")
+                }
+            };
+
+            let http = format!("HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{response}", response.len());
+            writer.write_all(http.as_bytes()).await.map_err(Error::Io)?;
+        }
+    }
+
+    /// Sends an error response with the provided status, before the connection is closed
+    async fn reject(writer: &mut OwnedWriteHalf, status: &str) -> Result<()> {
+        let http = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        writer.write_all(http.as_bytes()).await.map_err(Error::Io)?;
+        Ok(())
+    }
+
+    /// Handles a logbook method call from fldigi, returning the XML-RPC response
+    fn handle_call(method: &str, params: &[String], tx: &mpsc::UnboundedSender<Message>) -> String {
+        match method {
+            // fldigi sends the saved QSO as an ADIF record
+            "log.add_record" => {
+                for record in params.iter().flat_map(|p| adif::parse(p)) {
+                    let _ = tx.send(Message::QsoLogged(adif::to_contact(&record)));
+                }
+                method_response("")
+            },
+            // We don't check for duplicates, so always report that the contact isn't a duplicate
+            "log.check_dup" => method_response("false"),
+            // We don't provide previous contacts to fldigi
+            "log.get_record" => method_response(""),
+            other => {
+                trace!("Ignoring fldigi logbook method {other}");
+                method_response("")
+            }
+        }
+    }
+}
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.poll_task.abort();
+        if let Some(task) = &self.log_task {
+            task.abort();
+        }
+    }
+}
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+        .field("url", &self.url)
+        .field("log_address", &self.log_address)
+        .field("state", &self.state)
+        .field("connected", &self.connected)
+        .finish()
+    }
+}
+
+/// Processes any messages received from fldigi, starting or stopping the client depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the client out of the config so we can mutate the rest of the config while processing messages
+    let mut client = config.fldigi.take();
+
+    // Start, stop, or restart the client if the config changed
+    let log_address = config.fldigi_config.log_server.then(|| config.fldigi_config.log_address.clone());
+    if !config.fldigi_config.enabled {
+        client = None;
+    } else if client.as_ref().map_or(true, |c| c.url != config.fldigi_config.url || c.log_address != log_address) {
+        client = Some(Client::new(config.fldigi_config.url.clone(), log_address));
+    }
+
+    let Some(mut client) = client else {
+        return;
+    };
+
+    // Process the received messages
+    while let Ok(message) = client.rx.try_recv() {
+        match message {
+            Message::State(state) => {
+                client.connected = true;
+
+                // Push anything that changed into the contact logger
+                let old = &client.state;
+                let mut prefill = types::LoggerPrefill::default();
+                if config.fldigi_config.follow_radio {
+                    prefill.frequency = (state.frequency() != old.frequency()).then(|| state.frequency());
+                    prefill.mode = (state.modem != old.modem).then(|| state.mode());
+                }
+                if config.fldigi_config.prefill_fields {
+                    prefill.callsign = (state.callsign != old.callsign).then(|| state.callsign.clone());
+                    prefill.grid = (state.locator != old.locator).then(|| state.locator.clone());
+                    prefill.rx_rst = (state.rst_in != old.rst_in).then(|| state.rst_in.clone());
+                    prefill.tx_rst = (state.rst_out != old.rst_out).then(|| state.rst_out.clone());
+                    prefill.note = (state.note() != old.note()).then(|| state.note());
+                }
+
+                if prefill != types::LoggerPrefill::default() {
+                    config.events.push_back((None, types::Event::PrefillLogger(prefill)));
+                }
+
+                client.state = state;
+            },
+            Message::Disconnected => client.connected = false,
+            Message::QsoLogged(contact) => {
+                debug!("fldigi logged a QSO with {}", contact.callsign);
+
+                // Insert the contact into the database if automatic logging is enabled
+                if config.fldigi_config.auto_log {
+                    client.insert_tasks.push(config.db_api.insert_contact_promise(contact));
+                }
+            }
+        }
+    }
+
+    // Process any finished insert tasks
+    for task in client.insert_tasks.extract_if(|t| t.ready().is_some()) {
+        match task.block_and_take() {
+            Ok(contact) => {
+                config.events.push_back((None, types::Event::RefreshContacts));
+                config.events.push_back((None, types::Event::ContactLogged(contact.clone())));
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Info(format!("Logged {} from fldigi", contact.callsign)));
+            },
+            Err(err) => {
+                error!("Failed to insert contact from fldigi: {err}");
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to log contact from fldigi: {err}")));
+            }
+        }
+    }
+
+    // Put the client back into the config
+    config.fldigi = Some(client);
+
+}
+
+
+/// The fldigi module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should we connect to fldigi?
+    pub enabled: bool,
+    /// The URL of the fldigi XML-RPC server
+    pub url: String,
+    /// Should the frequency and mode in fldigi be pushed into the contact logger?
+    pub follow_radio: bool,
+    /// Should the log fields in fldigi (call, name, QTH, RST) be pushed into the contact logger?
+    pub prefill_fields: bool,
+    /// Should we run a logbook server that fldigi can send saved QSOs to?
+    pub log_server: bool,
+    /// The address that the logbook server listens on
+    pub log_address: String,
+    /// Should QSOs saved in fldigi automatically be inserted into the database?
+    pub auto_log: bool
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://127.0.0.1:7362/RPC2".into(),
+            follow_radio: true,
+            prefill_fields: true,
+            log_server: true,
+            log_address: "127.0.0.1:8421".into(),
+            auto_log: true
+        }
+    }
+}
+
+/// Errors regarding the fldigi module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Request failed: {0}")]
+    Request(reqwest::Error),
+    #[error("Connection error: {0}")]
+    Io(std::io::Error),
+    #[error("fldigi returned a fault: {0}")]
+    Fault(String),
+    #[error("Invalid XML-RPC document: {0}")]
+    InvalidXml(roxmltree::Error),
+    #[error("The XML-RPC document isn't a method call")]
+    NotMethodCall,
+    #[error("The XML-RPC multicall response doesn't have a result for each call")]
+    InvalidMulticall,
+    #[error("The fldigi state has the wrong number of values")]
+    InvalidState,
+    #[error("The logbook request is too large")]
+    RequestTooLarge,
+    #[error("The logbook request has an invalid content length")]
+    InvalidContentLength
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A QSO with K1ABC saved by fldigi 4.1, sent as an untyped ADIF value
+    const ADD_RECORD: &str = "<?xml version=\"1.0\"?>\n<methodCall><methodName>log.add_record</methodName>\n<params><param><value>\
+        &lt;CALL:5&gt;K1ABC&lt;QSO_DATE:8&gt;20240316&lt;TIME_ON:4&gt;1234&lt;FREQ:9&gt;14.070000&lt;MODE:5&gt;BPSK31\
+        &lt;RST_SENT:3&gt;599&lt;RST_RCVD:3&gt;579&lt;GRIDSQUARE:4&gt;FN42&lt;EOR&gt;\
+        </value></param></params></methodCall>\n";
+
+    #[test]
+    fn add_record_is_parsed() {
+        let (method, params) = parse_call(ADD_RECORD).unwrap();
+        assert_eq!(method, "log.add_record");
+        assert_eq!(params.len(), 1);
+        assert!(params[0].starts_with("<CALL:5>K1ABC<QSO_DATE:8>20240316"));
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        assert_eq!(Client::handle_call(&method, &params, &tx), method_response(""));
+        let Ok(Message::QsoLogged(contact)) = rx.try_recv() else { panic!("A QSO should've been logged") };
+        assert_eq!(contact.callsign, "K1ABC");
+        assert_eq!(contact.grid, "FN42");
+        assert_eq!(contact.frequency, 14_070_000);
+    }
+
+    #[test]
+    fn typed_and_empty_values_are_parsed() {
+        let xml = "<?xml version=\"1.0\"?><methodCall><methodName> log.check_dup </methodName><params>\
+            <param><value><string>W1AW</string></value></param>\
+            <param><value><int>5</int></value></param>\
+            <param><value><string/></value></param>\
+            <param><value></value></param>\
+            </params></methodCall>";
+        let (method, params) = parse_call(xml).unwrap();
+        assert_eq!(method, "log.check_dup");
+        assert_eq!(params, ["W1AW", "5", "", ""]);
+    }
+
+    #[test]
+    fn invalid_calls_are_rejected() {
+        assert!(matches!(parse_call(""), Err(Error::InvalidXml(_))));
+        assert!(matches!(parse_call("<methodCall><methodName>log.add_record</methodCall>"), Err(Error::InvalidXml(_))));
+        assert!(matches!(parse_call("<methodResponse><params/></methodResponse>"), Err(Error::NotMethodCall)));
+        assert!(matches!(parse_call("<methodCall><params/></methodCall>"), Err(Error::NotMethodCall)));
+    }
+
+    #[test]
+    fn responses_and_faults_are_parsed() {
+        let response = "<?xml version=\"1.0\"?><methodResponse><params><param><value><double>14070000.0</double></value></param></params></methodResponse>";
+        assert_eq!(parse_response(response).unwrap(), "14070000.0");
+        assert_eq!(parse_response("<methodResponse><params/></methodResponse>").unwrap(), "");
+
+        let fault = "<?xml version=\"1.0\"?><methodResponse><fault><value><struct>\
+            <member><name>faultCode</name><value><int>-1</int></value></member>\
+            <member><name>faultString</name><value><string>No such method</string></value></member>\
+            </struct></value></fault></methodResponse>";
+        assert!(matches!(parse_response(fault), Err(Error::Fault(message)) if message == "No such method"));
+    }
+
+    #[test]
+    fn values_are_escaped() {
+        let text = "<CALL:5>K1ABC & <EOR>";
+        assert_eq!(escape_xml(text), "&lt;CALL:5&gt;K1ABC &amp; &lt;EOR&gt;");
+
+        // Escaped values round trip through a method call and response
+        let (method, params) = parse_call(&method_call("log.add_record", &[text])).unwrap();
+        assert_eq!(method, "log.add_record");
+        assert_eq!(params, [text]);
+        assert_eq!(parse_response(&method_response(text)).unwrap(), text);
+    }
+
+    #[test]
+    fn multicall_responses_are_parsed() {
+        let response = "<?xml version=\"1.0\"?><methodResponse><params><param><value><array><data>\
+            <value><array><data><value><string>BPSK31</string></value></data></array></value>\
+            <value><array><data><value><double>14070000.0</double></value></data></array></value>\
+            <value><array><data><value>untyped</value></data></array></value>\
+            </data></array></value></param></params></methodResponse>";
+        assert_eq!(parse_multicall_response(response).unwrap(), ["BPSK31", "14070000.0", "untyped"]);
+
+        // A failed call is a fault struct in place of its result
+        let response = "<methodResponse><params><param><value><array><data>\
+            <value><array><data><value><string>BPSK31</string></value></data></array></value>\
+            <value><struct>\
+            <member><name>faultCode</name><value><int>-506</int></value></member>\
+            <member><name>faultString</name><value><string>Method 'log.get_foo' not defined</string></value></member>\
+            </struct></value>\
+            </data></array></value></param></params></methodResponse>";
+        assert!(matches!(parse_multicall_response(response), Err(Error::Fault(message)) if message == "Method 'log.get_foo' not defined"));
+
+        // A fault for the whole multicall, and responses that aren't arrays of results
+        let fault = "<methodResponse><fault><value><struct>\
+            <member><name>faultCode</name><value><int>-1</int></value></member>\
+            <member><name>faultString</name><value><string>No such method</string></value></member>\
+            </struct></value></fault></methodResponse>";
+        assert!(matches!(parse_multicall_response(fault), Err(Error::Fault(message)) if message == "No such method"));
+        assert!(matches!(parse_multicall_response("<methodResponse><params><param><value><string>BPSK31</string></value></param></params></methodResponse>"), Err(Error::InvalidMulticall)));
+        assert!(matches!(parse_multicall_response("<methodResponse><params><param><value><array><data><value>BPSK31</value></data></array></value></param></params></methodResponse>"), Err(Error::InvalidMulticall)));
+    }
+
+    #[test]
+    fn state_is_polled_in_one_request() {
+        // A fake fldigi that answers a single multicall with the state, then closes the connection
+        let listener = RT.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("http://{}/RPC2", listener.local_addr().unwrap());
+        let fldigi = RT.spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+
+            let mut content_length = 0;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await.unwrap();
+            let (method, params) = parse_call(&String::from_utf8(body).unwrap()).unwrap();
+
+            let results: String = ["BPSK31", "14070000.0", "1500", "k1abc", "Joe", "Boston", "FN42", "599", "579"].iter()
+                .map(|v| format!("<value><array><data><value><string>{v}</string></value></data></array></value>"))
+                .collect();
+            let response = format!("<methodResponse><params><param><value><array><data>{results}</data></array></value></param></params></methodResponse>");
+            let http = format!("HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}", response.len());
+            writer.write_all(http.as_bytes()).await.unwrap();
+
+            (method, params)
+        });
+
+        let client = reqwest::Client::new();
+        let state = RT.block_on(Client::get_state(&client, &url)).unwrap();
+        assert_eq!(state.modem, "BPSK31");
+        assert_eq!(state.frequency(), 14_071_500);
+        assert_eq!(state.callsign, "K1ABC");
+        assert_eq!(state.note(), "Joe - Boston");
+        assert_eq!((state.rst_in.as_str(), state.rst_out.as_str()), ("599", "579"));
+
+        let (method, params) = RT.block_on(fldigi).unwrap();
+        assert_eq!(method, "system.multicall");
+        for method in POLL_METHODS {
+            assert!(params.iter().any(|p| p == method), "{method}");
+        }
+    }
+}
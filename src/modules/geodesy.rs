@@ -0,0 +1,322 @@
+//
+// Distance, bearing, and great-circle utilities.
+//
+// Locations are `geo::Coord`s in degrees, with the longitude as `x` and the latitude as `y`. Distances are in meters, and bearings are in degrees clockwise from true north.
+// Vincenty's formulae are used for distances on the WGS84 ellipsoid, falling back to the haversine formula for nearly antipodal points where they don't converge.
+// Bearings and paths use a sphere, which is plenty for pointing an antenna or drawing a line on the map.
+//
+
+use std::f64::consts::PI;
+use geo::Coord;
+
+
+/// The mean radius of the earth, in meters
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The semi-major axis of the WGS84 ellipsoid, in meters
+const WGS84_A: f64 = 6_378_137.0;
+/// The flattening of the WGS84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+/// The semi-minor axis of the WGS84 ellipsoid, in meters
+const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+
+
+/// The short and long paths between two locations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Path {
+    /// The short path distance, in meters
+    pub distance: f64,
+    /// The initial short path bearing
+    pub bearing: f64,
+    /// The long path distance, in meters
+    pub long_distance: f64,
+    /// The initial long path bearing
+    pub long_bearing: f64
+}
+impl Path {
+    /// Calculates the short and long paths from one location to another
+    pub fn new(from: &Coord<f64>, to: &Coord<f64>) -> Self {
+        let distance = distance(from, to);
+        let bearing = bearing(from, to);
+
+        Self {
+            distance,
+            bearing,
+            long_distance: long_path_distance(distance),
+            long_bearing: long_path_bearing(bearing)
+        }
+    }
+}
+
+/// Calculates the great-circle distance between two locations on a sphere, in meters
+pub fn haversine_distance(from: &Coord<f64>, to: &Coord<f64>) -> f64 {
+    let (lat1, lat2) = (from.y.to_radians(), to.y.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.x - from.x).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+}
+
+/// Calculates the distance between two locations on the WGS84 ellipsoid, in meters.
+///
+/// Returns None if the formula doesn't converge, which only happens for nearly antipodal points.
+pub fn vincenty_distance(from: &Coord<f64>, to: &Coord<f64>) -> Option<f64> {
+    const MAX_ITERATIONS: usize = 200;
+    const TOLERANCE: f64 = 1e-12;
+
+    let l = (to.x - from.x).to_radians();
+    let u1 = ((1.0 - WGS84_F) * from.y.to_radians().tan()).atan();
+    let u2 = ((1.0 - WGS84_F) * to.y.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..MAX_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2) + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2)).sqrt();
+
+        // The points are the same
+        if sin_sigma == 0.0 {
+            return Some(0.0);
+        }
+
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos_sq_alpha = 1.0 - sin_alpha.powi(2);
+
+        // Both points are on the equator
+        let cos_2sigma_m = if cos_sq_alpha == 0.0 { 0.0 } else { cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha };
+
+        let c = WGS84_F / 16.0 * cos_sq_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos_sq_alpha));
+        let previous_lambda = lambda;
+        lambda = l + (1.0 - c) * WGS84_F * sin_alpha * (sigma + c * sin_sigma * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+
+        if (lambda - previous_lambda).abs() < TOLERANCE {
+            let u_sq = cos_sq_alpha * (WGS84_A.powi(2) - WGS84_B.powi(2)) / WGS84_B.powi(2);
+            let a = 1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = b * sin_sigma * (cos_2sigma_m + b / 4.0 * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                - b / 6.0 * cos_2sigma_m * (-3.0 + 4.0 * sin_sigma.powi(2)) * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+
+            return Some(WGS84_B * a * (sigma - delta_sigma));
+        }
+    }
+
+    None
+}
+
+/// Calculates the distance between two locations, in meters.
+///
+/// This uses Vincenty's formulae, falling back to the haversine formula if they don't converge.
+pub fn distance(from: &Coord<f64>, to: &Coord<f64>) -> f64 {
+    vincenty_distance(from, to).unwrap_or_else(|| haversine_distance(from, to))
+}
+
+/// Calculates the long path distance from the short path distance, in meters
+pub fn long_path_distance(distance: f64) -> f64 {
+    (2.0 * PI * EARTH_RADIUS - distance).max(0.0)
+}
+
+/// Calculates the initial bearing of the short path from one location to another, from 0 to 360 degrees
+pub fn bearing(from: &Coord<f64>, to: &Coord<f64>) -> f64 {
+    let (lat1, lat2) = (from.y.to_radians(), to.y.to_radians());
+    let d_lon = (to.x - from.x).to_radians();
+
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Calculates the initial bearing of the long path from the initial bearing of the short path
+pub fn long_path_bearing(bearing: f64) -> f64 {
+    (bearing + 180.0).rem_euclid(360.0)
+}
+
+/// Calculates the location at a distance (in meters) and initial bearing from another location
+pub fn destination(from: &Coord<f64>, bearing: f64, distance: f64) -> Coord<f64> {
+    let lat1 = from.y.to_radians();
+    let bearing = bearing.to_radians();
+    let angle = distance / EARTH_RADIUS;
+
+    let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+    let d_lon = (bearing.sin() * angle.sin() * lat1.cos()).atan2(angle.cos() - lat1.sin() * lat2.sin());
+
+    Coord {
+        x: (from.x + d_lon.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+        y: lat2.to_degrees()
+    }
+}
+
+/// Calculates the location that is a fraction of the way along the great circle between two locations
+pub fn interpolate(from: &Coord<f64>, to: &Coord<f64>, fraction: f64) -> Coord<f64> {
+    let a = unit_vector(from);
+    let b = unit_vector(to);
+
+    // The angle between the locations
+    let dot = (a[0] * b[0] + a[1] * b[1] + a[2] * b[2]).clamp(-1.0, 1.0);
+    let angle = dot.acos();
+
+    // The locations are the same, or antipodal, where every great circle is a shortest path so there's nothing sensible to interpolate
+    if angle.sin().abs() < 1e-12 {
+        return *from;
+    }
+
+    // Spherical linear interpolation
+    let wa = ((1.0 - fraction) * angle).sin() / angle.sin();
+    let wb = (fraction * angle).sin() / angle.sin();
+    let v = [0, 1, 2].map(|i| wa * a[i] + wb * b[i]);
+
+    Coord {
+        x: v[1].atan2(v[0]).to_degrees(),
+        y: v[2].atan2((v[0].powi(2) + v[1].powi(2)).sqrt()).to_degrees()
+    }
+}
+
+/// Calculates the locations along the great circle between two locations, split into separate lines where the path crosses the antimeridian.
+///
+/// `step` is the approximate distance between each location, in meters.
+pub fn great_circle(from: &Coord<f64>, to: &Coord<f64>, step: f64) -> Vec<Vec<Coord<f64>>> {
+    const MAX_SEGMENTS: usize = 512;
+
+    let segments = ((haversine_distance(from, to) / step.max(1.0)).ceil() as usize).clamp(1, MAX_SEGMENTS);
+    let points = (0..=segments).map(|i| interpolate(from, to, i as f64 / segments as f64));
+
+    let mut lines: Vec<Vec<Coord<f64>>> = vec![Vec::with_capacity(segments + 1)];
+    let mut previous: Option<Coord<f64>> = None;
+    for point in points {
+        if let Some(previous) = previous.filter(|p| (point.x - p.x).abs() > 180.0) {
+
+            // Find the latitude where the path crosses the antimeridian, by unwrapping the longitude of the next point
+            let sign = previous.x.signum();
+            let unwrapped = point.x + 360.0 * sign;
+            let fraction = (180.0 * sign - previous.x) / (unwrapped - previous.x);
+            let latitude = previous.y + (point.y - previous.y) * fraction;
+
+            // End the current line at the antimeridian and start a new one on the other side
+            if let Some(line) = lines.last_mut() {
+                line.push(Coord { x: 180.0 * sign, y: latitude });
+            }
+            lines.push(vec![Coord { x: -180.0 * sign, y: latitude }]);
+        }
+
+        if let Some(line) = lines.last_mut() {
+            line.push(point);
+        }
+        previous = Some(point);
+    }
+
+    lines
+}
+
+/// Converts a location into a unit vector
+pub fn unit_vector(location: &Coord<f64>) -> [f64; 3] {
+    let (lon, lat) = (location.x.to_radians(), location.y.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts degrees, minutes, and seconds into decimal degrees
+    fn dms(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        degrees.signum() * (degrees.abs() + minutes / 60.0 + seconds / 3600.0)
+    }
+
+    #[test]
+    fn vincenty_distance_matches_known_values() {
+        // Flinders Peak to Buninyong, the example from Vincenty's paper
+        let flinders_peak = Coord { x: dms(144.0, 25.0, 29.5244), y: dms(-37.0, 57.0, 3.7203) };
+        let buninyong = Coord { x: dms(143.0, 55.0, 35.3839), y: dms(-37.0, 39.0, 10.1561) };
+        assert!((vincenty_distance(&flinders_peak, &buninyong).unwrap() - 54_972.271).abs() < 0.01);
+
+        // A quarter of the equator, and a quarter of a meridian
+        let origin = Coord { x: 0.0, y: 0.0 };
+        assert!((vincenty_distance(&origin, &Coord { x: 90.0, y: 0.0 }).unwrap() - WGS84_A * PI / 2.0).abs() < 0.001);
+        assert!((vincenty_distance(&origin, &Coord { x: 0.0, y: 90.0 }).unwrap() - 10_001_965.729).abs() < 0.001);
+
+        assert_eq!(vincenty_distance(&flinders_peak, &flinders_peak), Some(0.0));
+    }
+
+    #[test]
+    fn nearly_antipodal_distance_falls_back() {
+        // Vincenty's formulae don't converge for points on the equator that are this close to antipodal
+        let from = Coord { x: 0.0, y: 0.0 };
+        let to = Coord { x: 179.7, y: 0.0 };
+        assert_eq!(vincenty_distance(&from, &to), None);
+
+        let distance = distance(&from, &to);
+        assert_eq!(distance, haversine_distance(&from, &to));
+        assert!((distance - EARTH_RADIUS * 179.7f64.to_radians()).abs() < 1.0);
+
+        // Slightly off the equator, the formulae still converge
+        let to = Coord { x: 179.5, y: 0.5 };
+        assert!((vincenty_distance(&from, &to).unwrap() - 19_936_288.579).abs() < 0.01);
+    }
+
+    #[test]
+    fn bearings_match_known_values() {
+        let origin = Coord { x: 0.0, y: 0.0 };
+        assert_eq!(bearing(&origin, &Coord { x: 0.0, y: 10.0 }), 0.0);
+        assert!((bearing(&origin, &Coord { x: 10.0, y: 0.0 }) - 90.0).abs() < 1e-9);
+        assert!((bearing(&Coord { x: 0.0, y: 10.0 }, &origin) - 180.0).abs() < 1e-9);
+        assert!((bearing(&origin, &Coord { x: -10.0, y: 0.0 }) - 270.0).abs() < 1e-9);
+
+        // New York (JFK) to London (LHR) heads northeast, and the return heads northwest
+        let jfk = Coord { x: -73.7781, y: 40.6413 };
+        let lhr = Coord { x: -0.4543, y: 51.4700 };
+        assert!((bearing(&jfk, &lhr) - 51.35).abs() < 0.01);
+        assert!((bearing(&lhr, &jfk) - 287.94).abs() < 0.01);
+
+        let path = Path::new(&jfk, &lhr);
+        assert!((path.long_bearing - (path.bearing + 180.0)).abs() < 1e-9);
+        assert!((path.distance + path.long_distance - 2.0 * PI * EARTH_RADIUS).abs() < 1e-6);
+        assert_eq!(long_path_bearing(300.0), 120.0);
+    }
+
+    #[test]
+    fn destination_follows_the_bearing() {
+        // A quarter of the way around the world to the east along the equator
+        let quarter = PI / 2.0 * EARTH_RADIUS;
+        let east = destination(&Coord { x: 0.0, y: 0.0 }, 90.0, quarter);
+        assert!((east.x - 90.0).abs() < 1e-9 && east.y.abs() < 1e-9);
+
+        // Crossing the antimeridian wraps the longitude
+        let west = destination(&Coord { x: 170.0, y: 0.0 }, 90.0, 20f64.to_radians() * EARTH_RADIUS);
+        assert!((west.x - -170.0).abs() < 1e-9);
+
+        // Going to another location by its bearing and distance ends up there
+        let from = Coord { x: -73.7781, y: 40.6413 };
+        let to = Coord { x: 139.7798, y: 35.5494 };
+        let arrived = destination(&from, bearing(&from, &to), haversine_distance(&from, &to));
+        assert!((arrived.x - to.x).abs() < 1e-6 && (arrived.y - to.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn great_circle_splits_at_the_antimeridian() {
+        // Tokyo to San Francisco crosses the antimeridian once
+        let tokyo = Coord { x: 139.6917, y: 35.6895 };
+        let san_francisco = Coord { x: -122.4194, y: 37.7749 };
+        let lines = great_circle(&tokyo, &san_francisco, 100_000.0);
+        assert_eq!(lines.len(), 2);
+
+        let (west, east) = (&lines[0], &lines[1]);
+        assert!((west[0].x - tokyo.x).abs() < 1e-9 && (west[0].y - tokyo.y).abs() < 1e-9);
+        assert!((east.last().unwrap().x - san_francisco.x).abs() < 1e-9);
+        assert_eq!(west.last().unwrap().x, 180.0);
+        assert_eq!(east[0].x, -180.0);
+        assert_eq!(west.last().unwrap().y, east[0].y);
+
+        // Neither line jumps across the map
+        for line in &lines {
+            assert!(line.windows(2).all(|w| (w[1].x - w[0].x).abs() < 180.0));
+        }
+
+        // A path that doesn't cross the antimeridian isn't split
+        let london = Coord { x: -0.1276, y: 51.5072 };
+        assert_eq!(great_circle(&tokyo, &london, 100_000.0).len(), 1);
+    }
+}
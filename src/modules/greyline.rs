@@ -0,0 +1,228 @@
+//
+// Calculates the solar terminator (greyline) and draws it as a map layer.
+//
+// This is pure astronomy, so it works offline. The subsolar point uses the low precision formulas from the Astronomical Almanac,
+// which are accurate to about 0.01 degrees, far better than the map can show.
+//
+
+use std::time::Duration;
+use chrono::{DateTime, Timelike, Utc};
+use egui::Ui;
+use geo::Coord;
+use serde::{Deserialize, Serialize};
+use crate::GuiConfig;
+use super::map::{self, MapLayer, MapTransform, OverlayImage};
+
+
+/// Calculates the subsolar point (the location where the sun is directly overhead) at a time
+pub fn subsolar_point(time: DateTime<Utc>) -> Coord<f64> {
+
+    // Days since the J2000 epoch
+    let n = time.timestamp_millis() as f64 / 86_400_000.0 - 10_957.5;
+
+    // The mean longitude and mean anomaly of the sun
+    let mean_longitude = (280.460 + 0.985_647_4 * n).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.985_600_3 * n).rem_euclid(360.0).to_radians();
+
+    // The ecliptic longitude of the sun and the obliquity of the ecliptic
+    let ecliptic_longitude = (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin()).to_radians();
+    let obliquity = (23.439 - 0.000_000_4 * n).to_radians();
+
+    // The declination and right ascension of the sun
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+
+    // The sun is overhead where the local sidereal time matches its right ascension
+    let sidereal_time = (280.460_618_37 + 360.985_647_366_29 * n).rem_euclid(360.0);
+    let longitude = (right_ascension.to_degrees() - sidereal_time + 540.0).rem_euclid(360.0) - 180.0;
+
+    Coord { x: longitude, y: declination.to_degrees() }
+}
+
+/// Calculates the elevation of the sun above the horizon at a location, in degrees
+pub fn solar_elevation(location: &Coord<f64>, subsolar_point: &Coord<f64>) -> f64 {
+    let (lat, sun_lat) = (location.y.to_radians(), subsolar_point.y.to_radians());
+    let hour_angle = (location.x - subsolar_point.x).to_radians();
+    (lat.sin() * sun_lat.sin() + lat.cos() * sun_lat.cos() * hour_angle.cos()).asin().to_degrees()
+}
+
+/// The lighting at a location, based on the elevation of the sun
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lighting {
+    /// The sun is above the horizon
+    Day,
+    /// The sun is less than 6 degrees below the horizon
+    CivilTwilight,
+    /// The sun is 6 to 12 degrees below the horizon
+    NauticalTwilight,
+    /// The sun is 12 to 18 degrees below the horizon
+    AstronomicalTwilight,
+    /// The sun is more than 18 degrees below the horizon
+    Night
+}
+impl Lighting {
+    /// Gets the lighting from the elevation of the sun, in degrees
+    pub fn from_elevation(elevation: f64) -> Self {
+        match elevation {
+            e if e >= 0.0 => Self::Day,
+            e if e >= -6.0 => Self::CivilTwilight,
+            e if e >= -12.0 => Self::NauticalTwilight,
+            e if e >= -18.0 => Self::AstronomicalTwilight,
+            _ => Self::Night
+        }
+    }
+
+    /// How dark the lighting is, from 0 (day) to 1 (night)
+    fn darkness(&self) -> f64 {
+        match self {
+            Self::Day => 0.0,
+            Self::CivilTwilight => 0.25,
+            Self::NauticalTwilight => 0.5,
+            Self::AstronomicalTwilight => 0.75,
+            Self::Night => 1.0
+        }
+    }
+}
+
+
+/// A map layer that shades the night side of the earth, with bands for civil, nautical, and astronomical twilight.
+///
+/// The layer is redrawn each minute.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layer {
+    /// Should the layer be shown?
+    pub enabled: bool,
+    /// The opacity of the night side
+    pub opacity: u8,
+    /// The time the layer was last drawn at, in minutes since the UNIX epoch
+    #[serde(skip)]
+    drawn_minute: i64
+}
+impl Layer {
+    /// The size of each shaded cell, in pixels
+    const CELL_SIZE: u32 = 4;
+    /// The greyline is drawn over area layers like the MUF, but under the grid
+    const Z_ORDER: i32 = 10;
+}
+impl MapLayer for Layer {
+    fn name(&self) -> &str {
+        "Greyline"
+    }
+
+    fn is_visible(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.enabled = visible;
+    }
+
+    fn z_order(&self) -> i32 {
+        Self::Z_ORDER
+    }
+
+    fn draw(&mut self, image: &mut OverlayImage, transform: &MapTransform, _config: &mut GuiConfig) {
+        let now = Utc::now();
+        self.drawn_minute = now.timestamp() / 60;
+        let sun = subsolar_point(now);
+
+        for (cell, location) in map::world_cells(transform, Self::CELL_SIZE) {
+            let elevation = solar_elevation(&location, &sun);
+            let alpha = (Lighting::from_elevation(elevation).darkness() * self.opacity as f64) as u8;
+            if alpha == 0 {
+                continue;
+            }
+
+            map::blend_rect(image, cell, image::Rgba([0, 0, 20, alpha]));
+        }
+    }
+
+    fn ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) -> bool {
+        let now = Utc::now();
+        ui.label(format!("Greyline at {}", now.format("%H:%M UTC")));
+
+        // Repaint at the start of the next minute, so the layer is redrawn even if nothing else happens
+        ui.ctx().request_repaint_after(Duration::from_secs(60 - now.second() as u64));
+
+        // Redraw the layer each minute
+        now.timestamp() / 60 != self.drawn_minute
+    }
+}
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            opacity: 140,
+            drawn_minute: 0
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use super::*;
+
+    /// The tilt of the earth's axis, which is the declination of the sun at the solstices
+    const OBLIQUITY: f64 = 23.44;
+
+    fn time(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn subsolar_point_matches_the_equinoxes_and_solstices() {
+        // The sun is over the equator at the equinoxes, and over the tropics at the solstices
+        assert!(subsolar_point(time(3, 20, 3, 6)).y.abs() < 0.01);
+        assert!(subsolar_point(time(9, 22, 12, 44)).y.abs() < 0.01);
+        assert!((subsolar_point(time(6, 20, 20, 51)).y - OBLIQUITY).abs() < 0.01);
+        assert!((subsolar_point(time(12, 21, 9, 21)).y + OBLIQUITY).abs() < 0.01);
+    }
+
+    #[test]
+    fn subsolar_point_follows_the_equation_of_time() {
+        // The sun crosses the prime meridian at about 12:07 UTC on the March equinox, so it's still east of it at noon
+        let march = subsolar_point(time(3, 20, 12, 0));
+        assert!((march.x - 1.83).abs() < 0.05);
+
+        // The sun crosses the prime meridian at about 11:44 UTC in early November, so it's already west of it at noon
+        let november = subsolar_point(time(11, 3, 12, 0));
+        assert!((november.x + 4.11).abs() < 0.05);
+        assert!((november.y + 15.3).abs() < 0.05);
+
+        // The sun moves west by 15 degrees an hour
+        let later = subsolar_point(time(11, 3, 13, 0));
+        assert!((november.x - later.x - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn solar_elevation_matches_the_subsolar_point() {
+        let sun = subsolar_point(time(6, 20, 20, 51));
+
+        // The sun is overhead at the subsolar point, and directly below at its antipode
+        assert!((solar_elevation(&sun, &sun) - 90.0).abs() < 1e-6);
+        let antipode = Coord { x: sun.x + 180.0, y: -sun.y };
+        assert!((solar_elevation(&antipode, &sun) + 90.0).abs() < 1e-6);
+
+        // At the June solstice, the sun circles the north pole at the height of the tilt of the earth's axis, and never rises at the south pole
+        assert!((solar_elevation(&Coord { x: 0.0, y: 90.0 }, &sun) - OBLIQUITY).abs() < 0.01);
+        assert!((solar_elevation(&Coord { x: 0.0, y: -90.0 }, &sun) + OBLIQUITY).abs() < 0.01);
+
+        // The sun is on the horizon 90 degrees away from the subsolar point
+        let terminator = Coord { x: sun.x + 90.0, y: 0.0 };
+        assert!(solar_elevation(&terminator, &sun).abs() < 1e-6);
+    }
+
+    #[test]
+    fn lighting_uses_the_twilight_elevations() {
+        assert_eq!(Lighting::from_elevation(10.0), Lighting::Day);
+        assert_eq!(Lighting::from_elevation(0.0), Lighting::Day);
+        assert_eq!(Lighting::from_elevation(-3.0), Lighting::CivilTwilight);
+        assert_eq!(Lighting::from_elevation(-6.0), Lighting::CivilTwilight);
+        assert_eq!(Lighting::from_elevation(-9.0), Lighting::NauticalTwilight);
+        assert_eq!(Lighting::from_elevation(-15.0), Lighting::AstronomicalTwilight);
+        assert_eq!(Lighting::from_elevation(-18.5), Lighting::Night);
+    }
+}
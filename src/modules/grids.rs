@@ -0,0 +1,418 @@
+//
+// A map layer that draws the Maidenhead grid, and shades the grid squares that have been worked.
+//
+// Fields (e.g. `DM`) are always drawn, and squares (e.g. `DM79`) are drawn once the map is zoomed in far enough to tell them apart.
+// The worked squares come from the contact log, and the progress towards VUCC and the FFMA is shown in the legend.
+// The log doesn't record QSL confirmations, so squares are shaded once they've been worked.
+//
+
+use std::collections::HashSet;
+use egui::{Color32, Rect, Ui};
+use geo::Coord;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use crate::GuiConfig;
+use super::{database::WorkedRecord, maidenhead, map::{self, MapLayer, MapTransform, OverlayImage, Projection, MAX_LATITUDE}, types::Band};
+
+/// The squares that count towards the Fred Fish Memorial Award: the 488 squares that contain part of the contiguous US, worked on 6M.
+///
+/// This is the official list from the ARRL, which includes squares that only contain a small island or a sliver of the border.
+const FFMA: &[&str] = &[
+    "CM79", "CM86", "CM87", "CM88", "CM89", "CM93", "CM94", "CM95", "CM96", "CM97", "CM98", "CM99",
+    "CN70", "CN71", "CN72", "CN73", "CN74", "CN75", "CN76", "CN77", "CN78", "CN80", "CN81", "CN82", "CN83", "CN84", "CN85", "CN86",
+    "CN87", "CN88", "CN90", "CN91", "CN92", "CN93", "CN94", "CN95", "CN96", "CN97", "CN98",
+    "DL79", "DL88", "DL89", "DL98", "DL99",
+    "DM02", "DM03", "DM04", "DM05", "DM06", "DM07", "DM08", "DM09", "DM12", "DM13", "DM14", "DM15", "DM16", "DM17", "DM18", "DM19",
+    "DM22", "DM23", "DM24", "DM25", "DM26", "DM27", "DM28", "DM29", "DM31", "DM32", "DM33", "DM34", "DM35", "DM36", "DM37", "DM38",
+    "DM39", "DM41", "DM42", "DM43", "DM44", "DM45", "DM46", "DM47", "DM48", "DM49", "DM51", "DM52", "DM53", "DM54", "DM55", "DM56",
+    "DM57", "DM58", "DM59", "DM61", "DM62", "DM63", "DM64", "DM65", "DM66", "DM67", "DM68", "DM69", "DM70", "DM71", "DM72", "DM73",
+    "DM74", "DM75", "DM76", "DM77", "DM78", "DM79", "DM80", "DM81", "DM82", "DM83", "DM84", "DM85", "DM86", "DM87", "DM88", "DM89",
+    "DM90", "DM91", "DM92", "DM93", "DM94", "DM95", "DM96", "DM97", "DM98", "DM99",
+    "DN00", "DN01", "DN02", "DN03", "DN04", "DN05", "DN06", "DN07", "DN08", "DN10", "DN11", "DN12", "DN13", "DN14", "DN15", "DN16",
+    "DN17", "DN18", "DN20", "DN21", "DN22", "DN23", "DN24", "DN25", "DN26", "DN27", "DN28", "DN30", "DN31", "DN32", "DN33", "DN34",
+    "DN35", "DN36", "DN37", "DN38", "DN40", "DN41", "DN42", "DN43", "DN44", "DN45", "DN46", "DN47", "DN48", "DN50", "DN51", "DN52",
+    "DN53", "DN54", "DN55", "DN56", "DN57", "DN58", "DN60", "DN61", "DN62", "DN63", "DN64", "DN65", "DN66", "DN67", "DN68", "DN70",
+    "DN71", "DN72", "DN73", "DN74", "DN75", "DN76", "DN77", "DN78", "DN80", "DN81", "DN82", "DN83", "DN84", "DN85", "DN86", "DN87",
+    "DN88", "DN90", "DN91", "DN92", "DN93", "DN94", "DN95", "DN96", "DN97", "DN98",
+    "EL06", "EL07", "EL08", "EL09", "EL15", "EL16", "EL17", "EL18", "EL19", "EL28", "EL29", "EL39", "EL49", "EL58", "EL59", "EL79",
+    "EL84", "EL86", "EL87", "EL88", "EL89", "EL94", "EL95", "EL96", "EL97", "EL98", "EL99",
+    "EM00", "EM01", "EM02", "EM03", "EM04", "EM05", "EM06", "EM07", "EM08", "EM09", "EM10", "EM11", "EM12", "EM13", "EM14", "EM15",
+    "EM16", "EM17", "EM18", "EM19", "EM20", "EM21", "EM22", "EM23", "EM24", "EM25", "EM26", "EM27", "EM28", "EM29", "EM30", "EM31",
+    "EM32", "EM33", "EM34", "EM35", "EM36", "EM37", "EM38", "EM39", "EM40", "EM41", "EM42", "EM43", "EM44", "EM45", "EM46", "EM47",
+    "EM48", "EM49", "EM50", "EM51", "EM52", "EM53", "EM54", "EM55", "EM56", "EM57", "EM58", "EM59", "EM60", "EM61", "EM62", "EM63",
+    "EM64", "EM65", "EM66", "EM67", "EM68", "EM69", "EM70", "EM71", "EM72", "EM73", "EM74", "EM75", "EM76", "EM77", "EM78", "EM79",
+    "EM80", "EM81", "EM82", "EM83", "EM84", "EM85", "EM86", "EM87", "EM88", "EM89", "EM90", "EM91", "EM92", "EM93", "EM94", "EM95",
+    "EM96", "EM97", "EM98", "EM99",
+    "EN00", "EN01", "EN02", "EN03", "EN04", "EN05", "EN06", "EN07", "EN08", "EN10", "EN11", "EN12", "EN13", "EN14", "EN15", "EN16",
+    "EN17", "EN18", "EN20", "EN21", "EN22", "EN23", "EN24", "EN25", "EN26", "EN27", "EN28", "EN29", "EN30", "EN31", "EN32", "EN33",
+    "EN34", "EN35", "EN36", "EN37", "EN38", "EN40", "EN41", "EN42", "EN43", "EN44", "EN45", "EN46", "EN47", "EN48", "EN50", "EN51",
+    "EN52", "EN53", "EN54", "EN55", "EN56", "EN57", "EN58", "EN60", "EN61", "EN62", "EN63", "EN64", "EN65", "EN66", "EN67", "EN70",
+    "EN71", "EN72", "EN73", "EN74", "EN75", "EN76", "EN80", "EN81", "EN82", "EN83", "EN84", "EN85", "EN86", "EN90", "EN91", "EN92",
+    "FM02", "FM03", "FM04", "FM05", "FM06", "FM07", "FM08", "FM09", "FM13", "FM14", "FM15", "FM16", "FM17", "FM18", "FM19", "FM25",
+    "FM26", "FM27", "FM28", "FM29",
+    "FN00", "FN01", "FN02", "FN03", "FN10", "FN11", "FN12", "FN13", "FN14", "FN20", "FN21", "FN22", "FN23", "FN24", "FN25", "FN30",
+    "FN31", "FN32", "FN33", "FN34", "FN35", "FN41", "FN42", "FN43", "FN44", "FN45", "FN46", "FN51", "FN53", "FN54", "FN55", "FN56",
+    "FN57", "FN64", "FN65", "FN66", "FN67"
+];
+
+lazy_static! {
+    /// The indices of the FFMA squares
+    static ref FFMA_SQUARES: HashSet<u16> = FFMA.iter().filter_map(|s| parse_square(s)).collect();
+}
+
+/// The number of squares in the world (180 columns of 2 degrees by 180 rows of 1 degree)
+const SQUARES: u16 = 180 * 180;
+
+
+/// Gets the index of the square that contains a location
+pub fn square_index(location: &Coord<f64>) -> Option<u16> {
+    if !(-180.0..=180.0).contains(&location.x) || !(-90.0..=90.0).contains(&location.y) {
+        return None;
+    }
+
+    let column = (((location.x + 180.0) / 2.0) as u16).min(179);
+    let row = ((location.y + 90.0) as u16).min(179);
+    Some(column * 180 + row)
+}
+
+/// Gets the index of the square (the first 4 characters) of a grid square. Returns None if the grid is invalid or shorter than 4 characters.
+pub fn parse_square(grid: &str) -> Option<u16> {
+    let locator = maidenhead::Locator::parse(grid).ok().filter(|l| l.precision() >= 4)?;
+    square_index(&locator.center())
+}
+
+/// Finds the squares of the contacts on a band. `All` finds the squares worked on any band.
+fn worked_squares(records: &[WorkedRecord], band: Band) -> HashSet<u16> {
+    records.iter()
+        .filter(|r| band == Band::All || Band::from_frequency(r.frequency) == band)
+        .filter_map(|r| parse_square(&r.grid))
+        .collect()
+}
+
+/// Gets the name of a square (e.g. `DM79`) from its index
+pub fn square_name(index: u16) -> String {
+    let (column, row) = (index / 180, index % 180);
+    [
+        (b'A' + (column / 10) as u8) as char,
+        (b'A' + (row / 10) as u8) as char,
+        (b'0' + (column % 10) as u8) as char,
+        (b'0' + (row % 10) as u8) as char
+    ].iter().collect()
+}
+
+/// Gets the area covered by a square
+fn square_rect(index: u16) -> geo::Rect<f64> {
+    let (column, row) = (index / 180, index % 180);
+    let min = geo::coord! { x: column as f64 * 2.0 - 180.0, y: row as f64 - 90.0 };
+    geo::Rect::new(min, min + geo::coord! { x: 2.0, y: 1.0 })
+}
+
+/// The number of squares needed for VUCC on a band, or None if there's no VUCC award for the band
+pub fn vucc_requirement(band: Band) -> Option<usize> {
+    match band {
+        Band::B6m | Band::B2m => Some(100),
+        Band::B1_25M | Band::B70CM => Some(50),
+        Band::B33CM | Band::B23CM => Some(25),
+        Band::F2_4GHZ | Band::F3_4GHZ | Band::F5_8GHZ | Band::F10GHZ => Some(10),
+        Band::F24GHZ | Band::F47GHZ | Band::F76GHZ => Some(5),
+        _ => None
+    }
+}
+
+
+/// A map layer that draws the Maidenhead grid, and shades the squares that have been worked on a band
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layer {
+    /// Should the layer be shown?
+    pub enabled: bool,
+    /// The band to shade the worked squares of. `All` shades the squares worked on any band.
+    band: Band,
+    /// The opacity of the worked squares
+    opacity: u8,
+    /// The squares that have been worked on the band
+    #[serde(skip)]
+    worked: HashSet<u16>,
+    /// The version of the worked contacts when the worked squares were found. The layer is redrawn when this changes.
+    /// This is reset when the band changes, so the worked squares are only found again when they could have changed.
+    #[serde(skip)]
+    records_version: Option<u64>,
+    /// The labels of the visible fields or squares, with their position on the overlay
+    #[serde(skip)]
+    labels: Vec<(egui::Pos2, String)>
+}
+impl Layer {
+    /// The size of each shaded cell, in pixels
+    const CELL_SIZE: u32 = 4;
+    /// The grid is drawn over the other area layers, so its lines and labels aren't covered
+    const Z_ORDER: i32 = 20;
+    /// The scale (in pixels per degree) at which the squares are drawn
+    const SQUARE_SCALE: f64 = 6.0;
+    /// The scale (in pixels per degree) at which the squares are labelled instead of the fields
+    const SQUARE_LABEL_SCALE: f64 = 20.0;
+    /// The color of the field lines
+    const FIELD_COLOR: image::Rgba<u8> = image::Rgba([150, 150, 150, 150]);
+    /// The color of the square lines
+    const SQUARE_COLOR: image::Rgba<u8> = image::Rgba([70, 70, 70, 70]);
+
+    /// Finds the squares that have been worked on the band
+    fn update_worked(&mut self, config: &GuiConfig) {
+        self.worked = worked_squares(config.alerts.records(), self.band);
+        self.records_version = Some(config.alerts.records_version());
+    }
+
+    /// Shades the worked squares, and the squares that are still needed for the FFMA when showing 6M
+    fn draw_worked(&self, image: &mut OverlayImage, transform: &MapTransform) {
+        let ffma = self.band == Band::B6m;
+
+        for (cell, location) in map::world_cells(transform, Self::CELL_SIZE) {
+            let Some(index) = square_index(&location) else { continue };
+
+            let color = match self.worked.contains(&index) {
+                true => image::Rgba([40, 200, 80, self.opacity]),
+                false if ffma && FFMA_SQUARES.contains(&index) => image::Rgba([230, 140, 20, self.opacity / 3]),
+                false => continue
+            };
+            map::blend_rect(image, cell, color);
+        }
+    }
+
+    /// Draws the lines of the grid, every `lon_step` degrees of longitude and `lat_step` degrees of latitude
+    fn draw_lines(image: &mut OverlayImage, transform: &MapTransform, lon_step: f64, lat_step: f64, color: image::Rgba<u8>) {
+        let (lon_range, lat_range, sample_step) = match transform.projection() {
+            // The lines are straight, so they only need to be drawn across the visible area
+            Projection::Mercator => {
+                let rect = transform.geo_rect();
+                (
+                    [rect.min().x.max(-180.0), rect.max().x.min(180.0)],
+                    [rect.min().y.max(-MAX_LATITUDE), rect.max().y.min(MAX_LATITUDE)],
+                    f64::INFINITY
+                )
+            },
+            // The lines are curved, so they're split into one degree segments
+            Projection::AzimuthalEquidistant => ([-180.0, 180.0], [-90.0, 90.0], 1.0)
+        };
+
+        // Segments that are longer than this are wrapping around the antipode, so they're skipped
+        let max_length = transform.azimuthal_radius().map_or(f64::INFINITY, |r| r * 0.25);
+
+        let mut draw = |from: Coord<f64>, to: Coord<f64>| {
+            let (from, to) = (transform.to_screen(&from), transform.to_screen(&to));
+            if (to.0 - from.0).hypot(to.1 - from.1) <= max_length {
+                map::draw_line(image, from, to, color);
+            }
+        };
+
+        // The lines of longitude
+        let mut lon = (lon_range[0] / lon_step).ceil() * lon_step;
+        while lon <= lon_range[1] {
+            let mut lat = lat_range[0];
+            while lat < lat_range[1] {
+                let next = (lat + sample_step).min(lat_range[1]);
+                draw(geo::coord! { x: lon, y: lat }, geo::coord! { x: lon, y: next });
+                lat = next;
+            }
+            lon += lon_step;
+        }
+
+        // The lines of latitude
+        let mut lat = (lat_range[0] / lat_step).ceil() * lat_step;
+        while lat <= lat_range[1] {
+            let mut lon = lon_range[0];
+            while lon < lon_range[1] {
+                let next = (lon + sample_step).min(lon_range[1]);
+                draw(geo::coord! { x: lon, y: lat }, geo::coord! { x: next, y: lat });
+                lon = next;
+            }
+            lat += lat_step;
+        }
+    }
+
+    /// Finds the labels of the fields or squares that are visible on the overlay
+    fn update_labels(&mut self, transform: &MapTransform) {
+        let (width, height) = (transform.width() as f32, transform.height() as f32);
+        let squares = transform.pixels_per_degree() >= Self::SQUARE_LABEL_SCALE;
+
+        let centers: Vec<(Coord<f64>, String)> = match squares {
+            true => (0..SQUARES).map(|i| (square_rect(i).center(), square_name(i))).collect(),
+            false => (0..18).flat_map(|column| (0..18).map(move |row| {
+                let center = geo::coord! { x: column as f64 * 20.0 - 170.0, y: row as f64 * 10.0 - 85.0 };
+                (center, [(b'A' + column) as char, (b'A' + row) as char].iter().collect())
+            })).collect()
+        };
+
+        self.labels = centers.into_iter()
+            .filter(|(center, _)| transform.projection() != Projection::Mercator || transform.is_visible(center))
+            .filter_map(|(center, name)| {
+                let (x, y) = transform.to_screen(&center);
+                let position = egui::Pos2::new(x as f32, y as f32);
+                (position.x >= 0.0 && position.y >= 0.0 && position.x <= width && position.y <= height).then_some((position, name))
+            })
+            .collect();
+    }
+}
+impl MapLayer for Layer {
+    fn name(&self) -> &str {
+        "Grids"
+    }
+
+    fn is_visible(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.enabled = visible;
+    }
+
+    fn z_order(&self) -> i32 {
+        Self::Z_ORDER
+    }
+
+    fn draw(&mut self, image: &mut OverlayImage, transform: &MapTransform, config: &mut GuiConfig) {
+        if self.records_version != Some(config.alerts.records_version()) {
+            self.update_worked(config);
+        }
+        self.draw_worked(image, transform);
+
+        // Draw the squares, and the fields over them
+        if transform.pixels_per_degree() >= Self::SQUARE_SCALE {
+            Self::draw_lines(image, transform, 2.0, 1.0, Self::SQUARE_COLOR);
+        }
+        Self::draw_lines(image, transform, 20.0, 10.0, Self::FIELD_COLOR);
+
+        self.update_labels(transform);
+    }
+
+    fn ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) -> bool {
+        let mut changed = false;
+
+        // The band of the worked squares
+        ui.horizontal(|ui| {
+            ui.label("Worked grids on");
+            egui::ComboBox::from_id_source(ui.make_persistent_id("grids_band"))
+            .selected_text(self.band.as_str())
+            .show_ui(ui, |ui| {
+                for band in Band::iter() {
+                    changed |= ui.selectable_value(&mut self.band, band, band.as_str()).changed();
+                }
+            });
+        });
+        if changed {
+            self.records_version = None;
+        }
+        ui.label(format!("Worked: {} squares", self.worked.len()));
+
+        // The progress towards VUCC
+        if let Some(required) = vucc_requirement(self.band) {
+            let text = format!("VUCC: {}/{required}", self.worked.len());
+            match self.worked.len() >= required {
+                true => ui.colored_label(Color32::from_rgb(40, 200, 80), text),
+                false => ui.label(text)
+            };
+        }
+
+        // The progress towards the FFMA, which is only for 6M
+        if self.band == Band::B6m {
+            let worked = self.worked.iter().filter(|s| FFMA_SQUARES.contains(s)).count();
+            ui.label(format!("FFMA: {worked}/{}", FFMA_SQUARES.len()))
+            .on_hover_text("The squares that are still needed are shaded orange");
+        }
+
+        // Redraw the layer if the log changed
+        changed || self.records_version != Some(config.alerts.records_version())
+    }
+
+    fn paint(&mut self, painter: &egui::Painter, map_rect: Rect, _config: &mut GuiConfig) {
+        let font = egui::FontId::monospace(11.0);
+        for (position, name) in &self.labels {
+            painter.text(map_rect.min + position.to_vec2(), egui::Align2::CENTER_CENTER, name, font.clone(), Color32::from_white_alpha(170));
+        }
+    }
+}
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            band: Band::All,
+            opacity: 90,
+            worked: Default::default(),
+            records_version: None,
+            labels: Default::default()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Mode;
+
+    /// Creates a worked contact
+    fn record(grid: &str, frequency: u64) -> WorkedRecord {
+        WorkedRecord { callsign: "W1AW".to_string(), grid: grid.to_string(), frequency, mode: Mode::FT8 }
+    }
+
+    /// Gets the names of the worked squares, sorted
+    fn names(squares: &HashSet<u16>) -> Vec<String> {
+        let mut names: Vec<String> = squares.iter().map(|s| square_name(*s)).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn squares() {
+        let fn31 = parse_square("FN31").unwrap();
+        assert_eq!(square_name(fn31), "FN31");
+        assert_eq!(square_index(&geo::coord! { x: -72.7, y: 41.7 }), Some(fn31));
+        assert_eq!(square_rect(fn31), geo::Rect::new(geo::coord! { x: -74.0, y: 41.0 }, geo::coord! { x: -72.0, y: 42.0 }));
+
+        // The corners of the world
+        assert_eq!(square_name(square_index(&geo::coord! { x: -180.0, y: -90.0 }).unwrap()), "AA00");
+        assert_eq!(square_name(square_index(&geo::coord! { x: 180.0, y: 90.0 }).unwrap()), "RR99");
+        assert_eq!(square_index(&geo::coord! { x: 181.0, y: 0.0 }), None);
+        assert_eq!(FFMA_SQUARES.len(), 488);
+    }
+
+    #[test]
+    fn parse_squares() {
+        let fn31 = parse_square("FN31");
+
+        // Longer grid squares are truncated to their square, and case doesn't matter
+        assert_eq!(parse_square("FN31pr"), fn31);
+        assert_eq!(parse_square("fn31PR"), fn31);
+        assert_eq!(parse_square("fn31pr63"), fn31);
+        assert_eq!(parse_square(" fn31 "), fn31);
+
+        // Empty, incomplete, and invalid grid squares don't have a square
+        assert_eq!(parse_square(""), None);
+        assert_eq!(parse_square("FN"), None);
+        assert_eq!(parse_square("FN3"), None);
+        assert_eq!(parse_square("FN31p"), None);
+        assert_eq!(parse_square("ZZ99"), None);
+    }
+
+    #[test]
+    fn worked() {
+        let records = [
+            record("FN31pr", 50_313_000),
+            record("fn31", 50_125_000),
+            record("fn42AB", 144_174_000),
+            record("", 50_313_000),
+            record("EM", 50_313_000),
+            record("DM79", 14_074_000),
+            record("CN87", 12_000_000)
+        ];
+
+        // Every band
+        assert_eq!(names(&worked_squares(&records, Band::All)), vec!["CN87", "DM79", "FN31", "FN42"]);
+
+        // Only the contacts on the band are counted, and each square is only counted once
+        assert_eq!(names(&worked_squares(&records, Band::B6m)), vec!["FN31"]);
+        assert_eq!(names(&worked_squares(&records, Band::B2m)), vec!["FN42"]);
+        assert_eq!(names(&worked_squares(&records, Band::B20m)), vec!["DM79"]);
+        assert!(worked_squares(&records, Band::B40m).is_empty());
+        assert!(worked_squares(&[], Band::All).is_empty());
+    }
+}
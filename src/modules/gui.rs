@@ -16,7 +16,14 @@ use super::tabs::contact_logger::ContactLoggerTab;
 use super::tabs::contacts::ContactTableTab;
 use super::tabs::pskreporter::PSKReporterTab;
 use super::tabs::settings::SettingsTab;
-use super::types;
+use super::tabs::wsjtx::WsjtxTab;
+use super::tabs::js8call::Js8CallTab;
+use super::tabs::dxcluster::DxClusterTab;
+use super::tabs::rbn::RbnTab;
+use super::tabs::solar::SolarConditionsTab;
+use super::tabs::muf::MufMapTab;
+use super::tabs::contacts_map::ContactsMapTab;
+use super::{geodesy, types};
 
 
 /// The tab trait. This should be implemented for each tab variant
@@ -69,7 +76,21 @@ pub enum TabVariant {
     /// A settings tab
     Settings(Box<SettingsTab>),
     /// A tab for viewing band allocations
-    BandAllocations(Box<BandAllocationsTab>)
+    BandAllocations(Box<BandAllocationsTab>),
+    /// A tab that shows messages decoded by WSJT-X
+    Wsjtx(Box<WsjtxTab>),
+    /// A tab that shows the stations heard by JS8Call
+    Js8Call(Box<Js8CallTab>),
+    /// A tab that shows the spots received from the DX cluster
+    DxCluster(Box<DxClusterTab>),
+    /// A tab that shows the RBN skimmers that heard a callsign
+    Rbn(Box<RbnTab>),
+    /// A tab that shows solar and space weather information
+    SolarConditions(Box<SolarConditionsTab>),
+    /// A tab that shows a MUF or foF2 map
+    MufMap(Box<MufMapTab>),
+    /// A tab that shows the logged contacts on a map
+    ContactsMap(Box<ContactsMapTab>)
 }
 impl Tab for TabVariant {
 
@@ -82,6 +103,13 @@ impl Tab for TabVariant {
             TabVariant::PSKReporter(data) => data.id(),
             TabVariant::Settings(data) => data.id(),
             TabVariant::BandAllocations(data) => data.id(),
+            TabVariant::Wsjtx(data) => data.id(),
+            TabVariant::Js8Call(data) => data.id(),
+            TabVariant::DxCluster(data) => data.id(),
+            TabVariant::Rbn(data) => data.id(),
+            TabVariant::SolarConditions(data) => data.id(),
+            TabVariant::MufMap(data) => data.id(),
+            TabVariant::ContactsMap(data) => data.id(),
         }
     }
 
@@ -94,6 +122,13 @@ impl Tab for TabVariant {
             TabVariant::PSKReporter(data) => data.scroll_bars(),
             TabVariant::Settings(data) => data.scroll_bars(),
             TabVariant::BandAllocations(data) => data.scroll_bars(),
+            TabVariant::Wsjtx(data) => data.scroll_bars(),
+            TabVariant::Js8Call(data) => data.scroll_bars(),
+            TabVariant::DxCluster(data) => data.scroll_bars(),
+            TabVariant::Rbn(data) => data.scroll_bars(),
+            TabVariant::SolarConditions(data) => data.scroll_bars(),
+            TabVariant::MufMap(data) => data.scroll_bars(),
+            TabVariant::ContactsMap(data) => data.scroll_bars(),
         }
     }
 
@@ -106,6 +141,13 @@ impl Tab for TabVariant {
             TabVariant::PSKReporter(data) => data.title(),
             TabVariant::Settings(data) => data.title(),
             TabVariant::BandAllocations(data) => data.title(),
+            TabVariant::Wsjtx(data) => data.title(),
+            TabVariant::Js8Call(data) => data.title(),
+            TabVariant::DxCluster(data) => data.title(),
+            TabVariant::Rbn(data) => data.title(),
+            TabVariant::SolarConditions(data) => data.title(),
+            TabVariant::MufMap(data) => data.title(),
+            TabVariant::ContactsMap(data) => data.title(),
         }
     }
 
@@ -118,6 +160,13 @@ impl Tab for TabVariant {
             TabVariant::PSKReporter(data) => data.init(config),
             TabVariant::Settings(data) => data.init(config),
             TabVariant::BandAllocations(data) => data.init(config),
+            TabVariant::Wsjtx(data) => data.init(config),
+            TabVariant::Js8Call(data) => data.init(config),
+            TabVariant::DxCluster(data) => data.init(config),
+            TabVariant::Rbn(data) => data.init(config),
+            TabVariant::SolarConditions(data) => data.init(config),
+            TabVariant::MufMap(data) => data.init(config),
+            TabVariant::ContactsMap(data) => data.init(config),
         }
     }
 
@@ -130,6 +179,13 @@ impl Tab for TabVariant {
             TabVariant::PSKReporter(data) => data.process_event(config, event),
             TabVariant::Settings(data) => data.process_event(config, event),
             TabVariant::BandAllocations(data) => data.process_event(config, event),
+            TabVariant::Wsjtx(data) => data.process_event(config, event),
+            TabVariant::Js8Call(data) => data.process_event(config, event),
+            TabVariant::DxCluster(data) => data.process_event(config, event),
+            TabVariant::Rbn(data) => data.process_event(config, event),
+            TabVariant::SolarConditions(data) => data.process_event(config, event),
+            TabVariant::MufMap(data) => data.process_event(config, event),
+            TabVariant::ContactsMap(data) => data.process_event(config, event),
         }
     }
 
@@ -142,6 +198,13 @@ impl Tab for TabVariant {
             TabVariant::PSKReporter(data) => data.ui(config, ui),
             TabVariant::Settings(data) => data.ui(config, ui),
             TabVariant::BandAllocations(data) => data.ui(config, ui),
+            TabVariant::Wsjtx(data) => data.ui(config, ui),
+            TabVariant::Js8Call(data) => data.ui(config, ui),
+            TabVariant::DxCluster(data) => data.ui(config, ui),
+            TabVariant::Rbn(data) => data.ui(config, ui),
+            TabVariant::SolarConditions(data) => data.ui(config, ui),
+            TabVariant::MufMap(data) => data.ui(config, ui),
+            TabVariant::ContactsMap(data) => data.ui(config, ui),
         }
     }
     
@@ -316,6 +379,19 @@ pub fn duration_parser(input: &str) -> Option<u64> {
     Some(duration_secs)
 }
 
+/// Shows the distance and bearing of the short and long paths between two stations.
+/// 
+/// `bearing_label` describes the direction of the bearing (e.g. `Bearing from TX to RX`)
+pub fn path_ui(ui: &mut Ui, path: &geodesy::Path, unit: &types::DistanceUnit, bearing_label: &str) {
+    let abbreviation = unit.abbreviation();
+    ui.label(format!(
+        "Distance: {:.0} {abbreviation} (long path {:.0} {abbreviation})",
+        unit.to_unit_from_meters(path.distance),
+        unit.to_unit_from_meters(path.long_distance)
+    ));
+    ui.label(format!("{bearing_label}: {:.0}\u{00B0} (long path {:.0}\u{00B0})", path.bearing, path.long_bearing));
+}
+
 /// Generates a random [egui::Id]
 /// 
 /// This is typically used to differentiate between different tabs
@@ -0,0 +1,532 @@
+//
+// A client for the JS8Call TCP API.
+//
+// JS8Call sends and receives newline-delimited JSON messages formatted as `{"type": "RIG.FREQ", "value": "", "params": {...}}`.
+// The TCP API must be enabled in JS8Call (Settings -> Reporting -> API).
+//
+
+use std::time::Duration;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{debug, error, info, trace, warn};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::TcpStream, sync::mpsc, task::JoinHandle};
+use crate::{GuiConfig, RT};
+use super::types;
+
+
+/// A message received from JS8Call
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// The frequency of the radio changed
+    Frequency {
+        /// The dial frequency, in Hz
+        dial: u64,
+        /// The audio offset, in Hz
+        offset: u64
+    },
+    /// The callsign of the station that JS8Call is configured with
+    StationCallsign(String),
+    /// JS8Call heard one or more stations
+    Heard(Vec<HeardStation>),
+    /// A message was received (e.g. a directed message or band activity)
+    Activity {
+        /// The frequency of the message, in Hz (dial + offset)
+        frequency: u64,
+        /// The signal to noise ratio, in dB
+        snr: i32,
+        /// The text of the message
+        text: String
+    },
+    /// The operator logged a QSO in JS8Call
+    QsoLogged(types::Contact),
+    /// Any other message that we don't need to handle. This contains the message type.
+    Other(String)
+}
+impl Message {
+    /// Parses a line of JSON sent by JS8Call into a [Message]
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        Self::parse_at(line, Utc::now().naive_utc())
+    }
+
+    /// Parses a line of JSON that was received at the provided time (in UTC). The receive time is used for logged QSOs that are missing their start time.
+    fn parse_at(line: &str, received: NaiveDateTime) -> Result<Self, Error> {
+        let raw: RawMessage = serde_json::from_str(line).map_err(Error::Deserialize)?;
+        let params = &raw.params;
+
+        let message = match raw.message_type.as_str() {
+            "RIG.FREQ" => Self::Frequency {
+                dial: get_u64(params, "DIAL"),
+                offset: get_u64(params, "OFFSET")
+            },
+            "STATION.CALLSIGN" => Self::StationCallsign(raw.value),
+            "RX.SPOT" => Self::Heard(vec![HeardStation::new(
+                &get_str(params, "CALL"),
+                &get_str(params, "GRID"),
+                get_i64(params, "SNR") as i32,
+                get_u64(params, "FREQ"),
+                get_u64(params, "UTC")
+            )]),
+            "RX.DIRECTED" => Self::Heard(vec![HeardStation::new(
+                &get_str(params, "FROM"),
+                &get_str(params, "GRID"),
+                get_i64(params, "SNR") as i32,
+                get_u64(params, "FREQ"),
+                get_u64(params, "UTC")
+            )]),
+            // The call activity response contains an object for each callsign that was heard recently
+            "RX.CALL_ACTIVITY" => Self::Heard(params.as_object().map(|o| {
+                o.iter()
+                .filter(|(k, _)| !k.starts_with('_'))
+                .map(|(callsign, v)| HeardStation::new(
+                    callsign,
+                    &get_str(v, "GRID"),
+                    get_i64(v, "SNR") as i32,
+                    0,
+                    get_u64(v, "UTC")
+                ))
+                .collect()
+            }).unwrap_or_default()),
+            "RX.ACTIVITY" => Self::Activity {
+                frequency: get_u64(params, "FREQ"),
+                snr: get_i64(params, "SNR") as i32,
+                text: raw.value
+            },
+            "LOG.QSO" => {
+                let time_on = get_time(params, "UTC.ON").unwrap_or_else(|| {
+                    warn!("JS8Call logged a QSO without a valid start time, using the time that it was received");
+                    received
+                });
+                let time_off = get_time(params, "UTC.OFF").unwrap_or(time_on);
+
+                // Use the submode (e.g. JS8) if it exists, since the mode is usually just MFSK
+                let mode = match get_str(params, "SUBMODE") {
+                    s if s.is_empty() => get_str(params, "MODE"),
+                    s => s
+                };
+
+                // Combine the operator's name and comments into the note
+                let note = [get_str(params, "NAME"), get_str(params, "COMMENTS")].into_iter()
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" - ");
+
+                Self::QsoLogged(types::Contact {
+                    callsign: get_str(params, "CALL").to_ascii_uppercase(),
+                    grid: get_str(params, "GRID"),
+                    date: time_on.date(),
+                    time: time_on.time(),
+                    duration: time_off.signed_duration_since(time_on).num_seconds().max(0) as u64,
+                    frequency: get_u64(params, "FREQ"),
+                    mode: types::Mode::from_name(&mode),
+                    tx_rst: get_str(params, "RPT.SENT"),
+                    rx_rst: get_str(params, "RPT.RECV"),
+                    note,
+                    ..Default::default()
+                })
+            },
+            other => Self::Other(other.to_string())
+        };
+
+        Ok(message)
+    }
+}
+
+/// The raw JSON message sent by JS8Call
+#[derive(Debug, Deserialize)]
+struct RawMessage {
+    #[serde(rename = "type")]
+    message_type: String,
+    #[serde(default)]
+    value: String,
+    #[serde(default)]
+    params: Value
+}
+
+/// A station that was heard by JS8Call
+#[derive(Debug, Clone)]
+pub struct HeardStation {
+    /// The callsign of the station
+    pub callsign: String,
+    /// The grid square of the station. This may be empty.
+    pub grid: String,
+    /// The signal to noise ratio, in dB
+    pub snr: i32,
+    /// The frequency that the station was heard on, in Hz. This is 0 if it's unknown.
+    pub frequency: u64,
+    /// The time that the station was heard, in seconds since the UNIX epoch
+    pub time: u64
+}
+impl HeardStation {
+    fn new(callsign: &str, grid: &str, snr: i32, frequency: u64, utc_ms: u64) -> Self {
+        Self {
+            callsign: callsign.trim().to_ascii_uppercase(),
+            grid: grid.trim().to_string(),
+            snr,
+            frequency,
+            time: utc_ms / 1000
+        }
+    }
+}
+
+/// Gets a string from a JSON object, returning an empty string if it doesn't exist
+fn get_str(value: &Value, key: &str) -> String {
+    match value.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        _ => String::new()
+    }
+}
+
+/// Gets a number from a JSON object, returning 0 if it doesn't exist. JS8Call sometimes sends numbers as strings, so those are parsed too.
+fn get_i64(value: &Value, key: &str) -> i64 {
+    match value.get(key) {
+        Some(Value::Number(n)) => n.as_i64().or(n.as_f64().map(|f| f as i64)).unwrap_or_default(),
+        Some(Value::String(s)) => s.trim().parse().unwrap_or_default(),
+        _ => 0
+    }
+}
+
+/// Gets an unsigned number from a JSON object, returning 0 if it doesn't exist or is negative
+fn get_u64(value: &Value, key: &str) -> u64 {
+    get_i64(value, key).max(0) as u64
+}
+
+/// Gets a time in milliseconds since the UNIX epoch from a JSON object, returning `None` if it doesn't exist or isn't valid
+fn get_time(value: &Value, key: &str) -> Option<NaiveDateTime> {
+    match get_i64(value, key) {
+        ms if ms > 0 => DateTime::from_timestamp_millis(ms).map(|t| t.naive_utc()),
+        _ => None
+    }
+}
+
+
+/// A connection to the JS8Call API. This reconnects automatically if the connection is lost.
+pub struct Client {
+    /// The address of the JS8Call API
+    address: String,
+    /// The receiving end of the messages that were received from JS8Call
+    rx: mpsc::UnboundedReceiver<Message>,
+    /// The sending end of the requests that should be sent to JS8Call
+    tx: mpsc::UnboundedSender<Value>,
+    /// The connection task
+    task: JoinHandle<()>,
+    /// The tasks that are currently inserting logged QSOs into the database
+    insert_tasks: Vec<Promise<Result<types::Contact>>>,
+    /// The last frequency reported by JS8Call (dial + offset), in Hz
+    frequency: u64,
+    /// The callsign that JS8Call is configured with
+    station_callsign: String
+}
+impl Client {
+    /// How long to wait before reconnecting if the connection failed
+    const RETRY_DELAY: Duration = Duration::from_secs(5);
+    /// How often to ask JS8Call for the recent call activity
+    const CALL_ACTIVITY_INTERVAL: Duration = Duration::from_secs(15);
+
+    /// Connects to the JS8Call API at the provided address (e.g. `127.0.0.1:2442`)
+    pub fn new(address: String) -> Self {
+        let (message_tx, message_rx) = mpsc::unbounded_channel();
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+
+        let task = RT.spawn(Self::run(address.clone(), message_tx, request_rx));
+
+        Self {
+            address,
+            rx: message_rx,
+            tx: request_tx,
+            task,
+            insert_tasks: Default::default(),
+            frequency: 0,
+            station_callsign: Default::default()
+        }
+    }
+
+    /// Sends a request to JS8Call (e.g. `RIG.GET_FREQ`)
+    pub fn send(&self, message_type: &str, value: &str) {
+        let _ = self.tx.send(json!({ "type": message_type, "value": value, "params": {} }));
+    }
+
+    /// The last frequency reported by JS8Call (dial + offset), in Hz
+    pub fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    /// The callsign that JS8Call is configured with
+    pub fn station_callsign(&self) -> &str {
+        &self.station_callsign
+    }
+
+    /// The connection task. This connects to JS8Call and forwards messages in both directions until the client is dropped.
+    async fn run(address: String, tx: mpsc::UnboundedSender<Message>, mut rx: mpsc::UnboundedReceiver<Value>) {
+        loop {
+            match Self::connection(&address, &tx, &mut rx).await {
+                Ok(()) => return,
+                Err(err) => {
+                    debug!("JS8Call connection to '{address}' failed: {err}");
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                }
+            }
+
+            if tx.is_closed() {
+                return;
+            }
+        }
+    }
+
+    /// A single connection to JS8Call. This returns `Ok(())` if the client was dropped, or an error if the connection failed.
+    async fn connection(address: &str, tx: &mpsc::UnboundedSender<Message>, rx: &mut mpsc::UnboundedReceiver<Value>) -> Result<()> {
+        let stream = TcpStream::connect(address).await.map_err(Error::Io)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        info!("Connected to JS8Call at '{address}'");
+
+        // Ask JS8Call for its current state
+        for request in ["STATION.GET_CALLSIGN", "RIG.GET_FREQ", "RX.GET_CALL_ACTIVITY"] {
+            let line = json!({ "type": request, "value": "", "params": {} }).to_string() + "\n";
+            writer.write_all(line.as_bytes()).await.map_err(Error::Io)?;
+        }
+
+        let mut interval = tokio::time::interval(Self::CALL_ACTIVITY_INTERVAL);
+
+        loop {
+            tokio::select! {
+                // A message was received from JS8Call
+                line = lines.next_line() => {
+                    let Some(line) = line.map_err(Error::Io)? else {
+                        return Err(Error::Disconnected)?;
+                    };
+
+                    match Message::parse(&line) {
+                        Ok(message) => if tx.send(message).is_err() {
+                            return Ok(());
+                        },
+                        Err(err) => warn!("Failed to parse JS8Call message: {err}")
+                    }
+                },
+                // A request should be sent to JS8Call
+                request = rx.recv() => {
+                    let Some(request) = request else {
+                        return Ok(());
+                    };
+                    writer.write_all((request.to_string() + "\n").as_bytes()).await.map_err(Error::Io)?;
+                },
+                // Periodically ask for the recent call activity so the map stays up to date
+                _ = interval.tick() => {
+                    let line = json!({ "type": "RX.GET_CALL_ACTIVITY", "value": "", "params": {} }).to_string() + "\n";
+                    writer.write_all(line.as_bytes()).await.map_err(Error::Io)?;
+                }
+            }
+        }
+    }
+}
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+        .field("address", &self.address)
+        .field("frequency", &self.frequency)
+        .field("station_callsign", &self.station_callsign)
+        .finish()
+    }
+}
+
+/// Processes any messages received from JS8Call, connecting or disconnecting depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the client out of the config so we can mutate the rest of the config while processing messages
+    let mut client = config.js8call.take();
+
+    // Connect, disconnect, or reconnect if the config changed
+    if !config.js8call_config.enabled {
+        client = None;
+    } else if client.as_ref().map_or(true, |c| c.address != config.js8call_config.address) {
+        client = Some(Client::new(config.js8call_config.address.clone()));
+    }
+
+    let Some(mut client) = client else {
+        return;
+    };
+
+    // Process the received messages
+    while let Ok(message) = client.rx.try_recv() {
+        match message {
+            Message::Frequency { dial, offset } => {
+                let frequency = dial + offset;
+
+                // Push the frequency into the contact logger if it changed
+                if config.js8call_config.follow_frequency && frequency != client.frequency {
+                    config.events.push_back((None, types::Event::PrefillLogger(types::LoggerPrefill {
+                        frequency: Some(frequency),
+                        mode: Some(types::Mode::JS8CALL),
+                        ..Default::default()
+                    })));
+                }
+
+                client.frequency = frequency;
+            },
+            Message::StationCallsign(callsign) => client.station_callsign = callsign,
+            Message::Heard(stations) => {
+                for station in stations.into_iter().filter(|s| !s.callsign.is_empty()) {
+                    config.events.push_back((None, types::Event::Js8CallHeard(station)));
+                }
+            },
+            Message::Activity { frequency, snr, text } => trace!("JS8Call activity on {frequency} Hz ({snr} dB): {text}"),
+            Message::QsoLogged(contact) => {
+                debug!("JS8Call logged a QSO with {}", contact.callsign);
+
+                // Insert the contact into the database if automatic logging is enabled
+                if config.js8call_config.auto_log {
+                    client.insert_tasks.push(config.db_api.insert_contact_promise(contact));
+                }
+            },
+            Message::Other(message_type) => trace!("Ignoring JS8Call message type {message_type}")
+        }
+    }
+
+    // Process any finished insert tasks
+    for task in client.insert_tasks.extract_if(|t| t.ready().is_some()) {
+        match task.block_and_take() {
+            Ok(contact) => {
+                config.events.push_back((None, types::Event::RefreshContacts));
+                config.events.push_back((None, types::Event::ContactLogged(contact.clone())));
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Info(format!("Logged {} from JS8Call", contact.callsign)));
+            },
+            Err(err) => {
+                error!("Failed to insert contact from JS8Call: {err}");
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to log contact from JS8Call: {err}")));
+            }
+        }
+    }
+
+    // Put the client back into the config
+    config.js8call = Some(client);
+
+}
+
+
+/// The JS8Call module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should we connect to JS8Call?
+    pub enabled: bool,
+    /// The address of the JS8Call TCP API
+    pub address: String,
+    /// Should QSOs logged in JS8Call automatically be inserted into the database?
+    pub auto_log: bool,
+    /// Should frequency changes in JS8Call be pushed into the contact logger?
+    pub follow_frequency: bool,
+    /// The color of the heard station markers
+    pub marker_color: [u8; 4]
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:2442".into(),
+            auto_log: true,
+            follow_frequency: true,
+            marker_color: [0, 160, 255, 255]
+        }
+    }
+}
+
+/// Errors regarding the JS8Call module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Connection error: {0}")]
+    Io(std::io::Error),
+    #[error("JS8Call closed the connection")]
+    Disconnected,
+    #[error("Failed to deserialize message: {0}")]
+    Deserialize(serde_json::Error)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+    use super::*;
+
+    /// A `LOG.QSO` message sent by JS8Call when the operator logs a QSO
+    const LOG_QSO: &str = r#"{"params":{"CALL":"kn4crd","COMMENTS":"FB QSO","EXTRA":{},"FREQ":7079150,"GRID":"EM73","MODE":"MFSK","NAME":"Jordan","RPT.RECV":"-10","RPT.SENT":"+05","STATION.CALL":"W1AW","STATION.GRID":"FN31","STATION.OP":"","SUBMODE":"JS8","UTC.OFF":1589563030000,"UTC.ON":1589562900000,"_ID":-1},"type":"LOG.QSO","value":"<call:6>KN4CRD <gridsquare:4>EM73 <mode:4>MFSK <submode:3>JS8 <eor>"}"#;
+
+    /// A `RX.DIRECTED` message sent by JS8Call when a directed message is decoded
+    const RX_DIRECTED: &str = r#"{"params":{"CMD":" SNR","DIAL":7078000,"EXTRA":"","FREQ":7079150,"FROM":"KN4CRD","GRID":" EM73","OFFSET":1150,"SNR":-12,"SPEED":0,"TDRIFT":0.5,"TEXT":"KN4CRD: W1AW SNR -12 ♢ ","TO":"W1AW","UTC":1589562901234,"_ID":-1},"type":"RX.DIRECTED","value":"KN4CRD: W1AW SNR -12 ♢ "}"#;
+
+    fn received() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2020, 5, 15).unwrap().and_hms_opt(18, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn logged_qso_is_parsed() {
+        let Message::QsoLogged(contact) = Message::parse_at(LOG_QSO, received()).unwrap() else {
+            panic!("LOG.QSO should be parsed as a logged QSO");
+        };
+        assert_eq!(contact.callsign, "KN4CRD");
+        assert_eq!(contact.grid, "EM73");
+        assert_eq!(contact.date, NaiveDate::from_ymd_opt(2020, 5, 15).unwrap());
+        assert_eq!(contact.time, NaiveTime::from_hms_opt(17, 15, 0).unwrap());
+        assert_eq!(contact.duration, 130);
+        assert_eq!(contact.frequency, 7_079_150);
+        assert_eq!(contact.mode, types::Mode::JS8CALL);
+        assert_eq!(contact.tx_rst, "+05");
+        assert_eq!(contact.rx_rst, "-10");
+        assert_eq!(contact.note, "Jordan - FB QSO");
+    }
+
+    #[test]
+    fn logged_qso_without_start_time_uses_receive_time() {
+        for utc_on in [r#""UTC.ON":1589562900000"#, r#""UTC.ON":0"#, r#""UTC.ON":"""#, r#""UTC.ON":"soon""#] {
+            let line = LOG_QSO.replace(r#""UTC.ON":1589562900000"#, utc_on).replace(r#""UTC.OFF":1589563030000,"#, "");
+            let Message::QsoLogged(contact) = Message::parse_at(&line, received()).unwrap() else {
+                panic!("LOG.QSO should be parsed as a logged QSO");
+            };
+
+            if utc_on.ends_with("1589562900000") {
+                assert_eq!(contact.time, NaiveTime::from_hms_opt(17, 15, 0).unwrap());
+            } else {
+                assert_eq!(contact.date.and_time(contact.time), received(), "{utc_on} should fall back to the receive time");
+            }
+            // Without an end time, the contact has no duration
+            assert_eq!(contact.duration, 0);
+        }
+    }
+
+    #[test]
+    fn directed_message_is_parsed() {
+        let Message::Heard(stations) = Message::parse(RX_DIRECTED).unwrap() else {
+            panic!("RX.DIRECTED should be parsed as a heard station");
+        };
+        assert_eq!(stations.len(), 1);
+        assert_eq!(stations[0].callsign, "KN4CRD");
+        assert_eq!(stations[0].grid, "EM73");
+        assert_eq!(stations[0].snr, -12);
+        assert_eq!(stations[0].frequency, 7_079_150);
+        assert_eq!(stations[0].time, 1_589_562_901);
+    }
+
+    #[test]
+    fn other_messages_are_parsed() {
+        assert!(matches!(Message::parse(r#"{"type":"RIG.FREQ","value":"","params":{"DIAL":7078000,"FREQ":7079150,"OFFSET":"1150","_ID":-1}}"#),
+            Ok(Message::Frequency { dial: 7_078_000, offset: 1150 })));
+        assert!(matches!(Message::parse(r#"{"type":"STATION.CALLSIGN","value":"W1AW","params":{"_ID":-1}}"#),
+            Ok(Message::StationCallsign(callsign)) if callsign == "W1AW"));
+        assert!(matches!(Message::parse(r#"{"type":"PING","value":"","params":{"NAME":"JS8Call","UTC":1589562901234}}"#),
+            Ok(Message::Other(kind)) if kind == "PING"));
+        assert!(matches!(Message::parse("not json"), Err(Error::Deserialize(_))));
+    }
+}
@@ -1,124 +1,330 @@
 //
 // This file contains functions that convert to/from maidenhead locators (Grid Squares) and longitude/latitude
 //
+// A locator is made of up to 5 pairs of characters, each pair dividing the previous one into smaller areas:
+// the field (`DM`, 20x10 degrees), square (`DM79`, 2x1 degrees), subsquare (`DM79mr`, 5x2.5 minutes),
+// extended square (`DM79mr45`, 30x15 seconds), and extended subsquare (`DM79mr45ab`, 1.25x0.625 seconds).
+//
 
+use std::{fmt, str::FromStr};
+use arrayvec::ArrayString;
 use geo::Coord;
+use thiserror::Error;
+use super::geodesy;
 
-/// Converts a Latitude and Longitude to a 6-character grid square (e.g. "DM79mr");
-pub fn lat_lon_to_grid(location: &Coord) -> String {
-
-    // Allocate a string with 6 characters (4 bytes each)
-    let mut grid = String::with_capacity(4*6);
 
-    // Get the lon and lat of the input location and add an offset to keep the value positive
-    let mut lon = location.x + 180.0;
-    let mut lat = location.y + 90.0;
+/// The number of divisions of each pair of characters, from the field to the extended subsquare
+const DIVISIONS: [u32; 5] = [18, 10, 24, 10, 24];
 
-    // 1st character; Longitude with 20 degrees of precision
-    let c1 = (lon / 20.0) as u8;
-    grid.push((c1  + 65) as char);
+/// The supported number of characters in a locator
+pub const PRECISIONS: [usize; 5] = [2, 4, 6, 8, 10];
 
-    // 2nd character; Latitude with 10 degrees of precision
-    let c2 = (lat / 10.0) as u8;
-    grid.push((c2  + 65) as char);
 
-    // 3rd character; Longitude with 2 degrees of precision
-    let c3 = ((lon - (c1 as f64 * 20.0)) / 2.0) as u8;
-    grid.push((c3 + 48) as char);
+/// A maidenhead locator (grid square), and the area that it covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locator {
+    /// The locator in its usual form, with uppercase fields and lowercase subsquares (e.g. `DM79mr`)
+    name: ArrayString<10>,
+    /// The south west corner of the locator
+    corner: Coord,
+    /// The width and height of the locator, in degrees
+    size: Coord
+}
+impl Locator {
+    /// Parses a 2, 4, 6, 8, or 10 character locator. Letters can be in either case, and surrounding whitespace is ignored.
+    pub fn parse(grid: &str) -> Result<Self, Error> {
+        let characters: Vec<char> = grid.trim().chars().collect();
+        if !PRECISIONS.contains(&characters.len()) {
+            return Err(Error::InvalidLength(characters.len()));
+        }
 
-    // 4th character; Latitude with 1 degree of precision
-    let c4 = ((lat - (c2 as f64 * 10.0)) / 1.0) as u8;
-    grid.push((c4 + 48) as char);
+        let mut name = ArrayString::new();
+        let mut corner = Coord::zero();
+        let mut size = geo::coord! { x: 360.0, y: 180.0 };
 
-    // 5th character; Longitude with 1/24th of a degree of precision
-    let c5 = ((lon - (c1 as f64 * 20.0)) % 1.0 * 12.0) as u8;
-    grid.push((c5 + 97) as char);
-        
-    // 6th character; Latitude with 1/12th of a degree of precision
-    let c6 = ((lat - (c2 as f64 * 10.0)) % 1.0 * 24.0) as u8;
-    grid.push((c6 + 97) as char);
+        for (pair, (chars, divisions)) in characters.chunks(2).zip(DIVISIONS).enumerate() {
+            size = size / divisions as f64;
 
-    grid
-}
+            // The first character of each pair is the longitude, and the second is the latitude
+            let x = index(chars[0], pair, divisions).ok_or(Error::InvalidCharacter { character: chars[0], position: pair * 2 + 1 })?;
+            let y = index(chars[1], pair, divisions).ok_or(Error::InvalidCharacter { character: chars[1], position: pair * 2 + 2 })?;
+            corner.x += x as f64 * size.x;
+            corner.y += y as f64 * size.y;
 
-/// Converts a 6-character grid square into a Latitude and Longitude
-/// 
-/// WARNING: For performance reasons, this function does not *currently* provide input validation. In other words,
-/// giving this function a string with an invalid grid square (random characters, characters that are out of range, etc) will provide an unusual output,
-/// and possibly cause a panic.
-/// 
-/// NOTE: This function only supports up to 6 characters. Anything more will provide invalid results.
-pub fn grid_to_lat_lon(grid: &str) -> Coord {
-
-    // Create the latitude and longitude values
-    let mut lat = 0.0;
-    let mut lon = 0.0;
-
-    // Used to efficiently count the number of characters in the string
-    let mut length = 0u8;
-
-    // Iterate through the characters
-    for (idx, mut character) in grid.char_indices() {
-        // Convert character to uppercase
-        character = character.to_ascii_uppercase();
-
-        // Increment the total character count
-        length += 1;
-
-        let num = if character.is_ascii_digit() {
-            // Convert the character into its decimal value and subtract 48 to apply an offset. This gives us the number that the digit represents.
-            (character as u32).saturating_sub(48)
-        } else {
-            // Convert the unicode character into its decimal value and subtract 65 to apply an offset. This gives us the alphabet index for each character.
-            (character as u32).saturating_sub(65)
-        };
-
-        // 1st character; Longitude with 20 degrees of precision
-        if idx == 0 {
-            lon += num as f64 * 20.0;
-        }
-        // 2nd character; Latitude with 10 degrees of precision
-        else if idx == 1 {
-            lat += num as f64 * 10.0;
+            name.push(character(x, pair));
+            name.push(character(y, pair));
         }
-        // 3rd character; Longitude with 2 degrees of precision
-        else if idx == 2 {
-            lon += num as f64 * 2.0;
+
+        Ok(Self { name, corner: corner - geo::coord! { x: 180.0, y: 90.0 }, size })
+    }
+
+    /// Gets the locator that contains a location, with 2, 4, 6, 8, or 10 characters.
+    ///
+    /// Longitudes outside of -180 to 180 degrees are wrapped around, and latitudes outside of -90 to 90 degrees are clamped.
+    pub fn from_location(location: &Coord, precision: usize) -> Result<Self, Error> {
+        if !PRECISIONS.contains(&precision) {
+            return Err(Error::InvalidPrecision(precision));
         }
-        // 4th character; Latitude with 1 degree of precision
-        else if idx == 3 {
-            lat += num as f64;
+        if !location.x.is_finite() || !location.y.is_finite() {
+            return Err(Error::InvalidLocation);
         }
-        // 5th character; Longitude with 2/24th of a degree of precision
-        else if idx == 4 {
-            lon += num as f64 * (2.0 / 24.0);
+
+        // Add an offset to keep the values positive
+        let mut lon = (location.x + 180.0).rem_euclid(360.0);
+        let mut lat = (location.y + 90.0).clamp(0.0, 180.0);
+
+        let mut name = ArrayString::new();
+        let mut corner = Coord::zero();
+        let mut size = geo::coord! { x: 360.0, y: 180.0 };
+
+        for (pair, divisions) in DIVISIONS.into_iter().take(precision / 2).enumerate() {
+            size = size / divisions as f64;
+
+            // The edges (e.g. the north pole) belong to the last division
+            let x = ((lon / size.x) as u32).min(divisions - 1);
+            let y = ((lat / size.y) as u32).min(divisions - 1);
+            lon = (lon - x as f64 * size.x).max(0.0);
+            lat = (lat - y as f64 * size.y).max(0.0);
+            corner.x += x as f64 * size.x;
+            corner.y += y as f64 * size.y;
+
+            name.push(character(x, pair));
+            name.push(character(y, pair));
         }
-        // 6th character; Latitude with 1/24th of a degree of precision
-        else if idx == 5 {
-            lat += num as f64 * (1.0 / 24.0);
+
+        Ok(Self { name, corner: corner - geo::coord! { x: 180.0, y: 90.0 }, size })
+    }
+
+    /// The locator as a string (e.g. `DM79mr`)
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of characters in the locator
+    pub fn precision(&self) -> usize {
+        self.name.len()
+    }
+
+    /// The width and height of the locator, in degrees
+    pub fn size(&self) -> Coord {
+        self.size
+    }
+
+    /// The center of the locator
+    pub fn center(&self) -> Coord {
+        self.corner + self.size / 2.0
+    }
+
+    /// The area covered by the locator
+    pub fn bounds(&self) -> geo::Rect {
+        geo::Rect::new(self.corner, self.corner + self.size)
+    }
+
+    /// The outline of the locator, as a polygon
+    pub fn polygon(&self) -> geo::Polygon {
+        self.bounds().to_polygon()
+    }
+
+    /// Does the locator contain a location? Locations on the south and west edges are inside, and locations on the north and east edges aren't.
+    ///
+    /// Like `from_location`, longitudes wrap around (so 180 degrees is inside the locators along -180 degrees), and the north pole is inside the northernmost locators.
+    pub fn contains(&self, location: &Coord) -> bool {
+        let max = self.corner + self.size;
+        let x = (location.x + 180.0).rem_euclid(360.0) - 180.0;
+        let north_pole = location.y == 90.0 && max.y > 90.0 - self.size.y / 2.0;
+        (self.corner.x..max.x).contains(&x) && ((self.corner.y..max.y).contains(&location.y) || north_pole)
+    }
+
+    /// The locators with the same precision that surround this one, clockwise from the north.
+    ///
+    /// Neighbors wrap around the antimeridian, but there are no neighbors past the poles, so locators along the poles have 5 neighbors instead of 8.
+    pub fn neighbors(&self) -> Vec<Self> {
+        let center = self.center();
+        [(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (1.0, -1.0), (0.0, -1.0), (-1.0, -1.0), (-1.0, 0.0), (-1.0, 1.0)].into_iter()
+            .map(|(x, y)| geo::coord! { x: center.x + x * self.size.x, y: center.y + y * self.size.y })
+            .filter(|location| (-90.0..=90.0).contains(&location.y))
+            .filter_map(|location| Self::from_location(&location, self.precision()).ok())
+            .collect()
+    }
+
+    /// The short and long paths from the center of this locator to the center of another
+    pub fn path_to(&self, other: &Self) -> geodesy::Path {
+        geodesy::Path::new(&self.center(), &other.center())
+    }
+
+    /// The distance from the center of this locator to the center of another, in meters
+    pub fn distance_to(&self, other: &Self) -> f64 {
+        geodesy::distance(&self.center(), &other.center())
+    }
+
+    /// The initial bearing from the center of this locator to the center of another, from 0 to 360 degrees
+    pub fn bearing_to(&self, other: &Self) -> f64 {
+        geodesy::bearing(&self.center(), &other.center())
+    }
+}
+impl FromStr for Locator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+impl fmt::Display for Locator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Gets the index of a character within a pair, or None if the character isn't valid for the pair
+fn index(character: char, pair: usize, divisions: u32) -> Option<u32> {
+    let index = match pair % 2 {
+        // Letters
+        0 => character.is_ascii_alphabetic().then(|| character.to_ascii_uppercase() as u32 - 'A' as u32),
+        // Digits
+        _ => character.to_digit(10)
+    };
+    index.filter(|i| *i < divisions)
+}
+
+/// Gets the character of an index within a pair. Fields are uppercase, and subsquares are lowercase.
+fn character(index: u32, pair: usize) -> char {
+    let offset = match pair {
+        0 => b'A',
+        p if p % 2 == 1 => b'0',
+        _ => b'a'
+    };
+    (offset + index as u8) as char
+}
+
+
+/// Converts a Latitude and Longitude to a 6-character grid square (e.g. "DM79mr").
+///
+/// Returns an empty string if the location isn't finite.
+pub fn lat_lon_to_grid(location: &Coord) -> String {
+    Locator::from_location(location, 6).map(|l| l.to_string()).unwrap_or_default()
+}
+
+/// Converts a 2, 4, 6, 8, or 10 character grid square into the Latitude and Longitude of its center
+pub fn grid_to_lat_lon(grid: &str) -> Result<Coord, Error> {
+    Locator::parse(grid).map(|l| l.center())
+}
+
+/// Is the string a valid 2, 4, 6, 8, or 10 character grid square?
+pub fn is_valid(grid: &str) -> bool {
+    Locator::parse(grid).is_ok()
+}
+
+
+/// Errors regarding maidenhead locators
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    #[error("A grid square must have 2, 4, 6, 8, or 10 characters, not {0}")]
+    InvalidLength(usize),
+    #[error("Invalid character '{character}' at position {position} of the grid square")]
+    InvalidCharacter { character: char, position: usize },
+    #[error("A grid square can't have a precision of {0} characters")]
+    InvalidPrecision(usize),
+    #[error("The location isn't a valid latitude and longitude")]
+    InvalidLocation
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Locations spread over the world, including the edges
+    const LOCATIONS: &[(f64, f64)] = &[
+        (0.0, 0.0), (-111.866, 40.73), (151.21, -33.87), (-0.001, -0.001), (12.3456789, 45.6789012),
+        (-180.0, 0.0), (180.0, 0.0), (179.9999999, 89.9999999), (-179.9999999, -89.9999999),
+        (0.0, 90.0), (0.0, -90.0), (-180.0, -90.0), (180.0, 90.0)
+    ];
+
+    #[test]
+    fn locations_round_trip() {
+        // The edge cases, and a sweep over the whole world
+        let sweep = (0..=100).flat_map(|i| (0..50).map(move |j| (-180.0 + i as f64 * 3.6037, -90.0 + j as f64 * 3.6011)));
+        for (x, y) in LOCATIONS.iter().copied().chain(sweep) {
+            let location = geo::coord! { x: x, y: y };
+            for precision in PRECISIONS {
+                let locator = Locator::from_location(&location, precision).unwrap();
+                assert_eq!(locator.precision(), precision);
+                assert!(locator.contains(&location), "{locator} doesn't contain {location:?}");
+                assert_eq!(Locator::parse(locator.as_str()), Ok(locator), "{locator} doesn't round trip");
+            }
         }
+    }
+
+    #[test]
+    fn known_locators() {
+        let location = geo::coord! { x: -111.866, y: 40.73 };
+        assert_eq!(Locator::from_location(&location, 2).unwrap().as_str(), "DN");
+        assert_eq!(Locator::from_location(&location, 4).unwrap().as_str(), "DN40");
+        assert_eq!(Locator::from_location(&location, 6).unwrap().as_str(), "DN40br");
+        assert_eq!(lat_lon_to_grid(&location), "DN40br");
 
+        // The edges of the world belong to the first and last fields
+        assert_eq!(Locator::from_location(&geo::coord! { x: -180.0, y: -90.0 }, 4).unwrap().as_str(), "AA00");
+        assert_eq!(Locator::from_location(&geo::coord! { x: 179.99, y: 90.0 }, 4).unwrap().as_str(), "RR99");
+        assert_eq!(Locator::from_location(&geo::coord! { x: 0.0, y: 90.0 }, 6).unwrap().as_str(), "JR09ax");
     }
 
-    // Apply an offset to the location so we're centered in the middle of the grid square
-    // The offset value varies depending on how many characters are in our grid square (i.e. the precision)
-    if length == 2 {
-        lat += 5.0;
-        lon += 10.0;
+    #[test]
+    fn longitude_wraps_around() {
+        let east = Locator::from_location(&geo::coord! { x: 180.0, y: 10.0 }, 6).unwrap();
+        let west = Locator::from_location(&geo::coord! { x: -180.0, y: 10.0 }, 6).unwrap();
+        assert_eq!(east, west);
+        assert_eq!(Locator::from_location(&geo::coord! { x: 190.0, y: 10.0 }, 4).unwrap().as_str(), "AK50");
     }
-    else if length == 4 {
-        lat += 0.5;
-        lon += 1.0;
+
+    #[test]
+    fn invalid_locations() {
+        assert_eq!(Locator::from_location(&geo::coord! { x: f64::NAN, y: 0.0 }, 4), Err(Error::InvalidLocation));
+        assert_eq!(Locator::from_location(&geo::coord! { x: 0.0, y: f64::INFINITY }, 4), Err(Error::InvalidLocation));
+        assert_eq!(Locator::from_location(&geo::coord! { x: 0.0, y: 0.0 }, 5), Err(Error::InvalidPrecision(5)));
+        assert_eq!(Locator::from_location(&geo::coord! { x: 0.0, y: 0.0 }, 12), Err(Error::InvalidPrecision(12)));
+    }
+
+    #[test]
+    fn parses_mixed_case() {
+        let locator = Locator::parse(" dM79Mr45Ab ").unwrap();
+        assert_eq!(locator.as_str(), "DM79mr45ab");
+        assert_eq!("DM79MR".parse::<Locator>().unwrap().as_str(), "DM79mr");
+        assert_eq!(grid_to_lat_lon("dm79mr"), grid_to_lat_lon("DM79MR"));
     }
-    else if length == 6 {
-        lat += (1.0 / 24.0) * 0.5;
-        lon += (2.0 / 24.0) * 0.5;
+
+    #[test]
+    fn rejects_invalid_locators() {
+        for (grid, length) in [("", 0), ("D", 1), ("DM7", 3), ("DM79m", 5), ("DM79mr4", 7), ("DM79mr45ab1", 11)] {
+            assert_eq!(Locator::parse(grid), Err(Error::InvalidLength(length)), "{grid}");
+        }
+        assert_eq!(Locator::parse("SM79"), Err(Error::InvalidCharacter { character: 'S', position: 1 }));
+        assert_eq!(Locator::parse("DS79"), Err(Error::InvalidCharacter { character: 'S', position: 2 }));
+        assert_eq!(Locator::parse("DMA9"), Err(Error::InvalidCharacter { character: 'A', position: 3 }));
+        assert_eq!(Locator::parse("DM79yr"), Err(Error::InvalidCharacter { character: 'y', position: 5 }));
+        assert_eq!(Locator::parse("DM79mr4a"), Err(Error::InvalidCharacter { character: 'a', position: 8 }));
+        assert_eq!(Locator::parse("DM79mr45xz"), Err(Error::InvalidCharacter { character: 'z', position: 10 }));
+        assert_eq!(Locator::parse("D!79"), Err(Error::InvalidCharacter { character: '!', position: 2 }));
+        assert!(!is_valid("DM 79"));
     }
 
-    // Subtract 90.0 and 180.0 degrees from the latitude and longitude to make them normal again
-    lat -= 90.0;
-    lon -= 180.0;
-    
-    geo::coord! { x: lon, y: lat }
+    #[test]
+    fn neighbors_wrap_around_the_antimeridian() {
+        let neighbors = Locator::parse("RK90").unwrap().neighbors();
+        let names: Vec<&str> = neighbors.iter().map(Locator::as_str).collect();
+        assert_eq!(names, ["RK91", "AK01", "AK00", "AJ09", "RJ99", "RJ89", "RK80", "RK81"]);
 
+        let neighbors = Locator::parse("AK00").unwrap().neighbors();
+        assert!(neighbors.iter().any(|n| n.as_str() == "RK90"));
+    }
+
+    #[test]
+    fn neighbors_stop_at_the_poles() {
+        let north: Vec<String> = Locator::parse("JR09").unwrap().neighbors().iter().map(Locator::to_string).collect();
+        assert_eq!(north, ["JR19", "JR18", "JR08", "IR98", "IR99"]);
+
+        let south: Vec<String> = Locator::parse("AA00").unwrap().neighbors().iter().map(Locator::to_string).collect();
+        assert_eq!(south, ["AA01", "AA11", "AA10", "RA90", "RA91"]);
+    }
 }
@@ -2,51 +2,76 @@
 // The map widget. This is intended to be used as a base widget for other things such as pskreporter maps, callsign maps, etc
 //
 
-use std::{collections::HashMap, f64::consts::PI, io::Cursor, time::Instant};
+use std::{cell::RefCell, collections::{BTreeMap, HashMap, HashSet}, f64::consts::PI, hash::{DefaultHasher, Hash, Hasher}, path::PathBuf, time::Instant};
 use anyhow::Result;
 use egui::{Color32, Context, Rect, TextureHandle, TextureId, Ui, Vec2};
 use geo::{Coord, Intersects};
-use image::{ImageBuffer, ImageDecoder};
+use image::ImageBuffer;
 use lazy_static::lazy_static;
-use log::error;
+use log::{debug, error};
 use poll_promise::Promise;
-use reqwest::Response;
+use reqwest::RequestBuilder;
+use rstar::{primitives::GeomWithData, PointDistance, RTree};
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use thiserror::Error;
 use crate::{GuiConfig, RT};
-use super::{gui::generate_random_id, types::convert_range};
+use super::{geodesy, gui::generate_random_id, maidenhead, map_files, tile_cache, types::{self, convert_range}};
 
 
 /// The maximum number of visible tiles. This is used to initialize hashmaps and vecs to improve frame time consistency (this is very overkill, lol)
 const MAX_TILES: usize = 128;
 /// The color of debug text
 const DEBUG_COLOR: egui::Color32 = Color32::from_rgb(219, 65, 5);
+/// The maximum latitude of the Web Mercator projection
+pub const MAX_LATITUDE: f64 = 85.0511;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 lazy_static! {
     // We use a custom useragent to identify our application
     /// The client used to sent requests to the tile APIs
-    static ref CLIENT: reqwest::Client = reqwest::Client::builder().user_agent(format!("{NAME}/{VERSION} OSS for Amateur Radio Operators")).build().unwrap();
+    /// The connect timeout is short so the map quickly falls back to the tile cache when we're offline
+    static ref CLIENT: reqwest::Client = reqwest::Client::builder()
+        .user_agent(format!("{NAME}/{VERSION} OSS for Amateur Radio Operators"))
+        .connect_timeout(std::time::Duration::from_secs(5))
+        .build()
+        .unwrap();
+}
+/// Looks up the location of a callsign that was searched for on the map, or returns None if the callsign doesn't have a known location.
+/// The map doesn't know how to look up callsigns itself, so the tab that owns the map provides this with [`MapWidget::set_callsign_locator`].
+pub type CallsignLocator = fn(String, &GuiConfig) -> Promise<Result<Option<Coord>>>;
+
+thread_local! {
+    /// The open MBTiles files, so they aren't opened again for every tile.
+    /// Local tiles are read on the blocking threads of the runtime, and each thread has its own connections so they don't wait on each other.
+    static MBTILES: RefCell<HashMap<PathBuf, rusqlite::Connection>> = RefCell::new(HashMap::new());
 }
 
 #[derive(Debug)]
 struct FocusedMarker {
+    /// The index of the marker's layer, in the layers passed to the map
+    layer: usize,
     id: u64,
     hovered: bool,
     clicked: bool,
     selected: bool
 }
 impl FocusedMarker {
-    fn new(id: u64) -> Self {
+    fn new(layer: usize, id: u64) -> Self {
         Self {
+            layer,
             id,
             hovered: Default::default(),
             clicked: Default::default(),
             selected: Default::default()
         }
     }
+
+    /// Is this the marker with the ID in the layer?
+    fn is_marker(&self, layer: usize, id: u64) -> bool {
+        self.layer == layer && self.id == id
+    }
 }
 /// A map widget. This aims to be a high-performance zoomable map with support for multiple different tile providers.
 /// 
@@ -54,7 +79,7 @@ impl FocusedMarker {
 ///       which means it can't be initialized with [Default::default()] like most widgets.
 ///       This typically requires you to wrap the map widget into an `Option<Self>` and initialize it as soon as a frame is rendered
 ///       so we can get access to the egui context and the tokio runtime.
-pub struct MapWidget<T: MapMarkerTrait> {
+pub struct MapWidget {
     map_rect_id: egui::Id,
     /// The tile in the center of the map
     center_tile: TileId,
@@ -65,19 +90,67 @@ pub struct MapWidget<T: MapMarkerTrait> {
     /// The tilemanager system is responsible for caching and fetching any tiles that the map widget requires
     tile_manager: TileManager,
     /// The overlay manager is responsible for lazily computing an overlay for the map. This is used to draw objects on the map with good performance
-    overlay_manager: MapOverlayManager<T>,
+    overlay_manager: MapOverlayManager,
     /// The center of the map. `center_tile` is still used for movement since it's cheaper and simpler, but it isn't very precise,
     /// so we store the center location here and re-center the map on zoom events.
     center_loc: Coord<f64>,
 
+    /// The projection of the map
+    projection: Projection,
+    /// The zoom of the azimuthal projection, where 1 fits the whole world in the map
+    azimuthal_zoom: f32,
+    /// The offset of the center of the azimuthal projection from the center of the map, in pixels
+    azimuthal_offset: Vec2,
+
     /// Should the overlay be updated on the next frame?
     update_overlay: bool,
-    /// The geo rect from last frame. This is used to determine if the map changed in any way (zoomed, moved, resized, etc)
-    last_geo_rect: geo::Rect<f64>,
+    /// Should the markers be updated on the next frame? Unlike the overlay, this is cheap, so it's done whenever the focused marker changes
+    update_markers: bool,
+    /// Should nearby markers be grouped into clusters when the map is zoomed out?
+    clustering: bool,
+    /// The transform from last frame. This is used to determine if the map changed in any way (zoomed, moved, resized, etc)
+    last_transform: Option<MapTransform>,
+    /// The tile meshes of the azimuthal projection. They're only projected again when the view changes.
+    azimuthal_meshes: Option<AzimuthalMeshes>,
+    /// A hash of the names, visibility, z-order, and number of markers of the layers from last frame. This is used to determine if the layers changed.
+    last_layers: u64,
     /// The currently focused marker, if any
-    focused_marker: Option<FocusedMarker>
+    focused_marker: Option<FocusedMarker>,
+
+    /// The text in the search box
+    search: String,
+    /// Looks up the location of searched callsigns. Callsigns can't be searched for if this isn't set.
+    callsign_locator: Option<CallsignLocator>,
+    /// The callsign being searched for, and the task that looks up its location
+    search_task: Option<(String, Promise<Result<Option<Coord>>>)>,
+    /// The view the map is moving from and to, and when the movement started
+    animation: Option<(MapView, MapView, Instant)>,
+
+    /// The path of the file that the map is exported to or imported from
+    file_path: String,
+    /// The task that saves the map as a PNG image, which returns the path of the image
+    export_task: Option<Promise<Result<PathBuf>>>,
+    /// The path that the markers are being saved to, and the task that saves them, which returns the number of markers that were saved
+    export_markers_task: Option<(PathBuf, Promise<Result<usize>>)>,
+    /// The task that reads a KML or GeoJSON file as an overlay layer
+    import_task: Option<Promise<Result<map_files::OverlayLayer>>>,
+    /// The layers imported from KML and GeoJSON files, which are shown over the layers of the map
+    imported: Vec<map_files::OverlayLayer>,
+    /// The index of an imported layer that should be removed after this frame
+    remove_imported: Option<usize>
 }
-impl<T: MapMarkerTrait> MapWidget<T> {
+impl MapWidget {
+    /// The maximum zoom of the azimuthal projection
+    const MAX_AZIMUTHAL_ZOOM: f32 = 32.0;
+    /// How long it takes to move to a searched location, in seconds
+    const ANIMATION_DURATION: f32 = 0.6;
+    /// How far the arrow keys move the map, in pixels
+    const KEY_PAN_STEP: f32 = 64.0;
+    /// The Web Mercator zoom used for each precision of grid square searches, so the grid square roughly fills the map
+    const GRID_ZOOMS: [f32; 5] = [4.0, 7.0, 12.0, 15.0, 18.0];
+    /// The Web Mercator zoom used for lat/lon and callsign searches
+    const LOCATION_ZOOM: f32 = 8.0;
+
     pub fn new(ctx: &Context) -> Self {
         Self {
             map_rect_id: generate_random_id(),
@@ -87,9 +160,26 @@ impl<T: MapMarkerTrait> MapWidget<T> {
             tile_manager: TileManager::new(ctx),
             overlay_manager: MapOverlayManager::new(ctx),
             center_loc: Coord::zero(),
+            projection: Default::default(),
+            azimuthal_zoom: 1.0,
+            azimuthal_offset: Vec2::ZERO,
             update_overlay: Default::default(),
-            last_geo_rect: geo::Rect::new(Coord::zero(), Coord::zero()),
-            focused_marker: None
+            update_markers: Default::default(),
+            clustering: true,
+            last_transform: None,
+            azimuthal_meshes: None,
+            last_layers: 0,
+            focused_marker: None,
+            search: String::new(),
+            callsign_locator: None,
+            search_task: None,
+            animation: None,
+            file_path: String::new(),
+            export_task: None,
+            export_markers_task: None,
+            import_task: None,
+            imported: Vec::new(),
+            remove_imported: None
         }
     }
 
@@ -276,17 +366,8 @@ impl<T: MapMarkerTrait> MapWidget<T> {
         geo::Rect::new(min, max)
     }
 
-    /// Render the UI layout. This doesn't implement `egui::Widget` because we also need mutable access to the `GuiConfig`
-    pub fn ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) -> egui::Response {
-
-        // Allocate the ract for the entire map and add senses to it
-        let (id, map_rect) = ui.allocate_space(ui.available_size());
-        let response = ui.interact(map_rect, id, egui::Sense::click_and_drag());
-
-        // Allocate a painter that only clips anything outside the map rect
-        let map_painter = ui.painter_at(map_rect);
-
-        // ===== MAP TILES ===== //
+    /// Draws the visible Web Mercator tiles
+    fn draw_mercator_tiles(&mut self, painter: &egui::Painter, map_rect: Rect, config: &GuiConfig) {
 
         // Calculate the tile size at the current zoom level
         let scale_zoom = (self.zoom % 1.0) + 1.0;
@@ -305,17 +386,14 @@ impl<T: MapMarkerTrait> MapWidget<T> {
         // Find visible tiles using the breadth/4-way flood fill algorithm
         fill_tiles_breadth(map_rect, (self.center_tile, center_tile_rect), &mut tiles);
 
-        // Tick the tile manager (i.e. load tiles and cleanup the cache)
-        self.tile_manager.tick();
-
         // Iterate through each visible tile and render it
         for (tile_id, tile_rect) in tiles {
 
             // Get the texture id of the tile image
-            let tile_tex = self.tile_manager.get_tile(&tile_id, &config.map_config.tile_provider);
+            let tile_tex = self.tile_manager.get_tile(&tile_id, &config.map_config);
 
             // Draw the tile
-            map_painter.image(
+            painter.image(
                 tile_tex,
                 tile_rect,
                 Rect::from_min_max(egui::Pos2::new(0.0, 0.0), egui::Pos2::new(1.0, 1.0)),
@@ -323,17 +401,489 @@ impl<T: MapMarkerTrait> MapWidget<T> {
             );
 
         }
+    }
+
+    /// Creates the transform of the azimuthal projection, which is centered on the grid square in the station settings (or 0, 0 if it isn't valid)
+    fn azimuthal_transform(&self, map_rect: Rect, config: &GuiConfig) -> MapTransform {
+        let center = maidenhead::grid_to_lat_lon(&config.station.grid).unwrap_or(Coord::zero());
+
+        // At a zoom of 1, the antipode (the edge of the projection) fits inside the map
+        let radius = (map_rect.width().min(map_rect.height()) / 2.0 - 8.0).max(16.0) * self.azimuthal_zoom;
+        let origin = (map_rect.size() / 2.0) + self.azimuthal_offset;
+
+        MapTransform::azimuthal(map_rect, center, radius as f64, (origin.x as f64, origin.y as f64))
+    }
+
+    /// Draws the tiles reprojected into the azimuthal projection.
+    /// 
+    /// Each tile is split into quads, which are drawn as a textured mesh, so the tiles are reprojected on the GPU.
+    /// The meshes are cached, and only projected again when the transform or tile zoom changes.
+    fn draw_azimuthal_tiles(&mut self, painter: &egui::Painter, map_rect: Rect, transform: &MapTransform, config: &GuiConfig) {
+
+        // Pick the tile zoom level that roughly matches the scale at the center of the projection
+        let zoom = azimuthal_tile_zoom(transform);
+        let offset = map_rect.left_top().to_vec2();
+
+        // Project the tiles again if the view changed
+        let cached = self.azimuthal_meshes.as_ref().is_some_and(|m| m.transform == *transform && m.zoom == zoom && m.offset == offset);
+        if !cached {
+            self.azimuthal_meshes = Some(AzimuthalMeshes {
+                transform: *transform,
+                zoom,
+                offset,
+                meshes: project_azimuthal_tiles(map_rect, transform, zoom)
+            });
+        }
+
+        // Draw the meshes with the current textures of their tiles, since they change as the tiles load
+        let Some(azimuthal_meshes) = &self.azimuthal_meshes else { return };
+        for (tile_id, mesh) in &azimuthal_meshes.meshes {
+            let mut mesh = mesh.clone();
+            mesh.texture_id = self.tile_manager.get_tile(tile_id, &config.map_config);
+            painter.add(egui::Shape::mesh(mesh));
+        }
+    }
+
+    /// Changes the projection of the map
+    pub fn set_projection(&mut self, projection: Projection) {
+        if self.projection != projection {
+            self.projection = projection;
+            self.update_overlay = true;
+        }
+    }
+
+    /// Sets the function that looks up the location of callsigns searched for on the map
+    pub fn set_callsign_locator(&mut self, locator: CallsignLocator) {
+        self.callsign_locator = Some(locator);
+    }
+
+    /// Enables or disables grouping nearby markers into clusters when the map is zoomed out. Clustering is enabled by default.
+    pub fn set_clustering(&mut self, clustering: bool) {
+        if self.clustering != clustering {
+            self.clustering = clustering;
+            self.update_markers = true;
+        }
+    }
+
+    /// Zooms in on a cluster of markers, centering the map on the cluster
+    fn zoom_to_cluster(&mut self, cluster: &DrawnMarker, transform: &MapTransform) {
+        match self.projection {
+            Projection::Mercator => {
+                let location = transform.to_geo(cluster.position[0] as f64, cluster.position[1] as f64);
+                self.zoom = (self.zoom + 2.0).clamp(0.0, 20.0);
+                self.center_tile.zoom = self.zoom as u8;
+                self.set_center_location(location);
+            },
+            Projection::AzimuthalEquidistant => {
+                // The offset of the cluster from the center of the projection is scaled by the zoom, so move the projection by the scaled offset to center the cluster
+                let origin = transform.azimuthal_origin().unwrap_or_default();
+                let cluster_offset = Vec2::from(cluster.position) - Vec2::new(origin.0 as f32, origin.1 as f32);
+                let zoom = (self.azimuthal_zoom * 4.0).min(Self::MAX_AZIMUTHAL_ZOOM);
+                self.azimuthal_offset = -cluster_offset * (zoom / self.azimuthal_zoom);
+                self.azimuthal_zoom = zoom;
+            }
+        }
+        self.animation = None;
+    }
+
+    /// Gets the position and zoom of the map, so it can be restored later with [Self::set_view()]
+    pub fn view(&self) -> MapView {
+        MapView {
+            center: [self.center_loc.x, self.center_loc.y],
+            zoom: self.zoom,
+            azimuthal_zoom: self.azimuthal_zoom,
+            azimuthal_offset: self.azimuthal_offset.into()
+        }
+    }
+
+    /// Moves and zooms the map to a view from [Self::view()]
+    pub fn set_view(&mut self, view: MapView) {
+        self.zoom = view.zoom.clamp(0.0, 20.0);
+        self.center_tile.zoom = self.zoom as u8;
+
+        // Keep the center inside the Web Mercator projection, since the tiles can't be found outside of it
+        let [longitude, latitude] = view.center;
+        self.set_center_location(geo::coord! {
+            x: (longitude + 180.0).rem_euclid(360.0) - 180.0,
+            y: latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE)
+        });
+
+        self.azimuthal_zoom = view.azimuthal_zoom.clamp(1.0, Self::MAX_AZIMUTHAL_ZOOM);
+        self.azimuthal_offset = view.azimuthal_offset.into();
+    }
+
+    /// Smoothly moves the map to a location. The map is zoomed in to at least the Web Mercator zoom level,
+    /// and the azimuthal projection is zoomed in by a similar amount.
+    pub fn fly_to(&mut self, location: Coord, zoom: f32) {
+        let from = self.view();
+        let azimuthal_zoom = self.azimuthal_zoom.max(2.0_f32.powf(zoom / 2.0 - 2.0)).clamp(1.0, Self::MAX_AZIMUTHAL_ZOOM);
+
+        // The offset of the location from the center of the azimuthal projection is scaled by the zoom,
+        // so move the projection by the scaled offset to center the location
+        let azimuthal_offset = self.last_transform
+            .filter(|t| t.projection() == Projection::AzimuthalEquidistant)
+            .and_then(|t| {
+                let origin = t.azimuthal_origin()?;
+                let (x, y) = t.to_screen(&location);
+                Some(Vec2::new((x - origin.0) as f32, (y - origin.1) as f32))
+            })
+            .filter(|offset| offset.is_finite())
+            .map(|offset| -offset * (azimuthal_zoom / self.azimuthal_zoom))
+            .unwrap_or(Vec2::ZERO);
+
+        let to = MapView {
+            center: [location.x, location.y],
+            zoom: self.zoom.max(zoom),
+            azimuthal_zoom,
+            azimuthal_offset: azimuthal_offset.into()
+        };
+        self.animation = Some((from, to, Instant::now()));
+    }
+
+    /// Moves the map by a number of pixels, like it was dragged
+    fn pan(&mut self, delta: Vec2) {
+        self.animation = None;
+
+        // The azimuthal map is moved by its offset
+        if self.projection == Projection::AzimuthalEquidistant {
+            self.azimuthal_offset += delta;
+            return;
+        }
+
+        // Calculate the tile size at the current zoom level
+        let corrected_tile_size = 256.0 * ((self.zoom % 1.0) + 1.0);
+
+        // Update the tile offset
+        self.relative_offset -= delta;
+
+        // Get the half tile size
+        let half_tile_size = corrected_tile_size / 2.0;
+
+        // Move north
+        if self.relative_offset.y < -half_tile_size {
+            if let Some(new_tile) = self.center_tile.north() {
+                self.center_tile = new_tile;
+                self.relative_offset.y = half_tile_size;
+            } else {
+                self.relative_offset.y = -half_tile_size;
+            }
+        }
+        // Move east
+        if self.relative_offset.x > half_tile_size {
+            if let Some(new_tile) = self.center_tile.east() {
+                self.center_tile = new_tile;
+                self.relative_offset.x = -half_tile_size;
+            } else {
+                self.relative_offset.x = half_tile_size;
+            }
+        }
+        // Move south
+        if self.relative_offset.y > half_tile_size {
+            if let Some(new_tile) = self.center_tile.south() {
+                self.center_tile = new_tile;
+                self.relative_offset.y = -half_tile_size;
+            } else {
+                self.relative_offset.y = half_tile_size;
+            }
+        }
+        // Move west
+        if self.relative_offset.x < -half_tile_size {
+            if let Some(new_tile) = self.center_tile.west() {
+                self.center_tile = new_tile;
+                self.relative_offset.x = half_tile_size;
+            } else {
+                self.relative_offset.x = -half_tile_size;
+            }
+        }
+
+        // Update the center location
+        self.center_loc = self.get_center_location();
+    }
+
+    /// Zooms the map in or out around its center. A zoom delta above 1 zooms in, and below 1 zooms out, like [egui::InputState::zoom_delta()].
+    fn zoom_by(&mut self, zoom_delta: f32) {
+        self.animation = None;
+
+        match self.projection {
+            // The offset is scaled too, so the center of the view stays in place
+            Projection::AzimuthalEquidistant => {
+                let zoom = (self.azimuthal_zoom * zoom_delta).clamp(1.0, Self::MAX_AZIMUTHAL_ZOOM);
+                self.azimuthal_offset *= zoom / self.azimuthal_zoom;
+                self.azimuthal_zoom = zoom;
+            },
+            Projection::Mercator => {
+                // Add the zoom delta to the zoom value
+                self.zoom += (zoom_delta - 1.0) * 0.5;
+                // Clamp the zoom to the 0-20 tile zoom range
+                self.zoom = self.zoom.clamp(0.0, 20.0);
+
+                // Update the tile zoom level
+                // NOTE: The type conversion to u8 automatically floors the value so we don't have to do it manually
+                self.center_tile.zoom = self.zoom as u8;
+
+                // Set the center location again
+                self.set_center_location(self.center_loc);
+            }
+        }
+    }
+
+    /// Moves the map to the grid square, lat/lon, or callsign in the search box.
+    ///
+    /// Callsigns are looked up in the background with the callsign locator, and the map is moved when the lookup finishes.
+    fn search(&mut self, config: &mut GuiConfig) {
+        let query = self.search.trim();
+        if query.is_empty() {
+            return;
+        }
+
+        // Grid squares are checked first, since they can't be confused with a callsign or a lat/lon
+        if let Ok(locator) = maidenhead::Locator::parse(query) {
+            let zoom = Self::GRID_ZOOMS[locator.precision() / 2 - 1];
+            self.fly_to(locator.center(), zoom);
+        }
+        else if let Some(location) = parse_location(query) {
+            self.fly_to(location, Self::LOCATION_ZOOM);
+        }
+        else if let Some(locator) = self.callsign_locator {
+            let callsign = query.to_ascii_uppercase();
+            let task = locator(callsign.clone(), config);
+            self.search_task = Some((callsign, task));
+        }
+        else {
+            config.notification_read = false;
+            config.notifications.push(types::Notification::Error(format!("Failed to find {query}: it isn't a grid square or lat/lon")));
+        }
+    }
+
+    /// Moves the map to the searched callsign when its lookup finishes
+    fn process_search(&mut self, config: &mut GuiConfig) {
+        let Some((callsign, task)) = self.search_task.take_if(|(_, t)| t.ready().is_some()) else { return };
+
+        let location = match task.block_and_take() {
+            Ok(location) => location.ok_or_else(|| "no location was found".to_string()),
+            Err(err) => Err(err.to_string())
+        };
+
+        match location {
+            Ok(location) => self.fly_to(location, Self::LOCATION_ZOOM),
+            Err(err) => {
+                error!("Failed to find '{callsign}' on the map: {err}");
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to find {callsign}: {err}")));
+            }
+        }
+    }
+
+    /// Moves the map along its animation, if it's moving to a searched location
+    fn animate(&mut self, ctx: &Context) {
+        let Some((from, to, start)) = self.animation else { return };
+
+        let t = (start.elapsed().as_secs_f32() / Self::ANIMATION_DURATION).min(1.0);
+        // Ease in and out
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.set_view(from.lerp(&to, eased));
+
+        if t < 1.0 {
+            ctx.request_repaint();
+        } else {
+            self.animation = None;
+        }
+    }
+
+    /// Shows the menu that exports the map to files, and imports files as overlay layers
+    fn files_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig, layers: &[&mut dyn MapLayer], imported_start: usize, transform: MapTransform) {
+        ui.label("File path");
+        ui.add(egui::TextEdit::singleline(&mut self.file_path).hint_text("e.g. /home/pi/propagation.png").desired_width(240.0));
+        let path = PathBuf::from(self.file_path.trim());
+        let has_path = !self.file_path.trim().is_empty();
+        ui.separator();
+
+        // Export the view or the markers
+        if ui.add_enabled(has_path && self.export_task.is_none(), egui::Button::new("Save the map as a PNG image")).clicked() {
+            self.export_png(transform, config);
+            ui.close_menu();
+        }
+        for format in [map_files::FileFormat::Kml, map_files::FileFormat::GeoJson] {
+            if ui.add_enabled(has_path && self.export_markers_task.is_none(), egui::Button::new(format!("Save the markers as {}", format.name()))).clicked() {
+                let task = map_files::export_markers(path.clone(), format, layers, config);
+                self.export_markers_task = Some((path.clone(), task));
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+
+        // Import an overlay
+        if ui.add_enabled(has_path && self.import_task.is_none(), egui::Button::new("Import a KML or GeoJSON overlay")).clicked() {
+            self.import_task = Some(map_files::import_overlay(path));
+            ui.close_menu();
+        }
+
+        // The imported overlays, which can be removed
+        for (index, layer) in layers.iter().enumerate().skip(imported_start) {
+            ui.horizontal(|ui| {
+                ui.label(layer.name());
+                if ui.small_button("Remove").clicked() {
+                    self.remove_imported = Some(index - imported_start);
+                }
+            });
+        }
+    }
+
+    /// Starts saving the current view of the map as a PNG image, which is rendered in the background
+    fn export_png(&mut self, transform: MapTransform, config: &GuiConfig) {
+        let snapshot = MapSnapshot {
+            transform,
+            tile_zoom: match self.projection {
+                Projection::Mercator => self.center_tile.zoom,
+                Projection::AzimuthalEquidistant => azimuthal_tile_zoom(&transform)
+            },
+            overlay: self.overlay_manager.cached_color_image.clone(),
+            markers: self.overlay_manager.drawn.clone(),
+            config: config.map_config.clone()
+        };
+
+        // Enter the async runtime
+        let _enter_guard = RT.enter();
+        self.export_task = Some(Promise::spawn_async(snapshot.save_png(PathBuf::from(self.file_path.trim()))));
+    }
+
+    /// Render the UI layout. This doesn't implement `egui::Widget` because we also need mutable access to the `GuiConfig`
+    pub fn ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) -> egui::Response {
+        self.ui_with_layers(ui, config, &mut [])
+    }
+
+    /// Render the UI layout with the provided layers, which are drawn in their z-order.
+    /// 
+    /// The layers are owned by the caller so they can be updated directly. Call [Self::update_overlay()] when a layer changes outside of its own UI.
+    /// The map is updated automatically when a layer is added, removed, shown, or hidden, or its number of markers changes.
+    /// 
+    /// The layers should be passed in the same order every frame, since the focused marker is tracked by the index of its layer.
+    /// Layers imported by the user from KML and GeoJSON files are owned by the map, and are shown after the provided layers.
+    pub fn ui_with_layers(&mut self, ui: &mut Ui, config: &mut GuiConfig, layers: &mut [&mut dyn MapLayer]) -> egui::Response {
+
+        // The imported layers are shown after the provided layers. They're taken out of the map while it's shown so both can be borrowed at once.
+        let mut imported = std::mem::take(&mut self.imported);
+        let imported_start = layers.len();
+        let mut all_layers: Vec<&mut dyn MapLayer> = layers.iter_mut()
+            .map(|layer| &mut **layer as &mut dyn MapLayer)
+            .chain(imported.iter_mut().map(|layer| layer as &mut dyn MapLayer))
+            .collect();
+
+        let response = self.show(ui, config, &mut all_layers, imported_start);
+
+        // Keep any layers that were imported this frame, and remove the layer that was removed this frame
+        imported.append(&mut self.imported);
+        if let Some(index) = self.remove_imported.take().filter(|i| *i < imported.len()) {
+            imported.remove(index);
+            self.focused_marker = None;
+        }
+        self.imported = imported;
+
+        response
+    }
+
+    /// Shows the map with all of its layers. The layers from `imported_start` onwards are the imported layers.
+    fn show(&mut self, ui: &mut Ui, config: &mut GuiConfig, layers: &mut [&mut dyn MapLayer], imported_start: usize) -> egui::Response {
+
+        // Allocate the ract for the entire map and add senses to it
+        let (id, map_rect) = ui.allocate_space(ui.available_size());
+        let response = ui.interact(map_rect, id, egui::Sense::click_and_drag());
+
+        // Allocate a painter that only clips anything outside the map rect
+        let map_painter = ui.painter_at(map_rect);
+
+        // Move towards the searched location
+        self.process_search(config);
+        self.animate(ui.ctx());
+
+        // The map image was saved
+        if let Some(task) = self.export_task.take_if(|t| t.ready().is_some()) {
+            config.notification_read = false;
+            match task.block_and_take() {
+                Ok(path) => config.notifications.push(types::Notification::Info(format!("Saved the map to {}", path.display()))),
+                Err(err) => {
+                    error!("Failed to save the map image: {err}");
+                    config.notifications.push(types::Notification::Error(format!("Failed to save the map image: {err}")));
+                }
+            }
+        }
+
+        // The markers were saved
+        if let Some((path, task)) = self.export_markers_task.take_if(|(_, t)| t.ready().is_some()) {
+            config.notification_read = false;
+            match task.block_and_take() {
+                Ok(n_markers) => config.notifications.push(types::Notification::Info(format!("Saved {n_markers} markers to {}", path.display()))),
+                Err(err) => {
+                    error!("Failed to save the markers: {err}");
+                    config.notifications.push(types::Notification::Error(format!("Failed to save the markers: {err}")));
+                }
+            }
+        }
+
+        // An overlay was imported
+        if let Some(task) = self.import_task.take_if(|t| t.ready().is_some()) {
+            match task.block_and_take() {
+                Ok(layer) => self.imported.push(layer),
+                Err(err) => {
+                    error!("Failed to import the overlay: {err}");
+                    config.notification_read = false;
+                    config.notifications.push(types::Notification::Error(format!("Failed to import the overlay: {err}")));
+                }
+            }
+        }
+
+        // ===== MAP TILES ===== //
+
+        // Tick the tile manager (i.e. load tiles and cleanup the cache)
+        self.tile_manager.tick(&config.map_config.cache);
+
+        // Draw the tiles and create the transform used to draw the overlay
+        let transform = match self.projection {
+            Projection::Mercator => {
+                self.draw_mercator_tiles(&map_painter, map_rect, config);
+                MapTransform::mercator(map_rect, self.get_visible_geo_rect(&map_rect))
+            },
+            Projection::AzimuthalEquidistant => {
+                let transform = self.azimuthal_transform(map_rect, config);
+                self.draw_azimuthal_tiles(&map_painter, map_rect, &transform, config);
+                draw_range_rings(&map_painter, map_rect, &transform, &config.distance_unit);
+                transform
+            }
+        };
 
         // ===== MAP OVERLAY ===== //
 
-        // Get the visible geo rect
-        let geo_rect = self.get_visible_geo_rect(&map_rect);
-        // Update the map overlay if asked or if the geo_rect changed
-        if self.update_overlay || geo_rect != self.last_geo_rect {
-            self.overlay_manager.update_overlay(map_rect, geo_rect, self.focused_marker.as_ref(), config);
+        // The visible layers, from the bottom to the top
+        let order = layer_order(layers);
+
+        // Update everything if the layers changed
+        let layers_hash = hash_layers(layers);
+        if self.last_layers != layers_hash {
+            self.update_overlay();
+            self.last_layers = layers_hash;
+        }
+
+        // Update the map overlay and markers if asked or if the transform changed (i.e. the map was moved, zoomed, or resized)
+        let transform_changed = self.last_transform != Some(transform);
+        if self.update_overlay || transform_changed {
+            self.overlay_manager.update_overlay(map_rect, &transform, layers, &order, config);
             self.update_overlay = false;
-            self.last_geo_rect = geo_rect;
         }
+        if self.update_markers || transform_changed {
+            // Reset the focused marker if it doesn't exist anymore
+            if let Some(focused_marker) = self.focused_marker.as_ref() {
+                let exists = layers.get(focused_marker.layer)
+                    .filter(|layer| layer.is_visible())
+                    .is_some_and(|layer| find_marker(&**layer, focused_marker.id).is_some());
+                if !exists {
+                    self.focused_marker = None;
+                }
+            }
+
+            self.overlay_manager.update_markers(&transform, layers, &order, self.focused_marker.as_ref(), self.clustering, config);
+            self.update_markers = false;
+        }
+        self.last_transform = Some(transform);
 
         // Draw the map overlay
         map_painter.image(
@@ -343,6 +893,14 @@ impl<T: MapMarkerTrait> MapWidget<T> {
             Color32::WHITE
         );
 
+        // Paint anything the layers draw on top of the overlay
+        for &index in order.iter() {
+            layers[index].paint(&map_painter, map_rect, config);
+        }
+
+        // Paint the markers on top of the layers
+        self.overlay_manager.paint_markers(&map_painter, map_rect);
+
         // ===== INTERACTION ===== //
 
         // Display some text when the user hovers over a marker
@@ -352,30 +910,52 @@ impl<T: MapMarkerTrait> MapWidget<T> {
             let hover_pos = ui.ctx().input(|i| i.pointer.hover_pos()).unwrap_or_default();
             let clicked = ui.ctx().input(|i| i.pointer.primary_clicked());
 
-            // Get the first hovered marker (if there is one)
-            if let Some(marker) = self.overlay_manager.hovered_markers_iter_mut(geo_rect, map_rect, hover_pos).next() {
+            // Get the hovered marker or cluster (if there is one). The hover position is made relative to the map rect, like the overlay
+            let hovered = self.overlay_manager.hovered_marker(hover_pos - map_rect.left_top());
+
+            // Show the number of markers in a hovered cluster, and zoom in on the cluster when it's clicked
+            if let Some(cluster @ DrawnMarker { kind: DrawnMarkerKind::Cluster(count), .. }) = &hovered {
+                egui::containers::show_tooltip_at_pointer(ui.ctx(), self.map_rect_id.with("_tooltip"), |ui| {
+                    ui.label(format!("{count} markers"));
+                    ui.weak("Click to zoom in");
+                });
+                if clicked {
+                    self.zoom_to_cluster(cluster, &transform);
+                }
+            }
+
+            // Get the hovered marker and its layer, if a single marker is hovered
+            let hovered_marker = hovered
+                .and_then(|h| match h.kind {
+                    DrawnMarkerKind::Marker(index) => Some((h.layer, index)),
+                    DrawnMarkerKind::Cluster(_) => None
+                })
+                .and_then(|(layer, index)| Some(layer).zip(layers.get_mut(layer)?.marker_mut(index)));
+
+            if let Some((layer, marker)) = hovered_marker {
+                let id = marker.id();
 
                 // The focused marker was clicked; toggle the selected state
-                if let Some(focused_marker) = self.focused_marker.as_mut().filter(|m| m.id == marker.id() && clicked) {
+                if let Some(focused_marker) = self.focused_marker.as_mut().filter(|m| m.is_marker(layer, id) && clicked) {
                     focused_marker.selected = !focused_marker.selected;
                 }
                 // The hovered marker is not the same as the focused marker AND the focused marker is not selected,
                 // OR there is no focused marker; update the focused marker
-                else if self.focused_marker.as_mut().filter(|m| m.id != marker.id() && (!m.selected || clicked)).is_some() || self.focused_marker.is_none() {
+                else if self.focused_marker.as_mut().filter(|m| !m.is_marker(layer, id) && (!m.selected || clicked)).is_some() || self.focused_marker.is_none() {
                     // Create a new focused marker
-                    let mut focused_marker = FocusedMarker::new(marker.id());
+                    let mut focused_marker = FocusedMarker::new(layer, id);
                     focused_marker.selected = clicked;
                     self.focused_marker = Some(focused_marker);
                 }
 
                 // Update the hovered and clicked state for the focused marker.
-                // This updates the map overlay if the marker was just hovered (i.e. only when neccessary)
-                if let Some(focused_marker) = self.focused_marker.as_mut().filter(|m| m.id == marker.id()) {
+                // This updates the markers if the marker was just hovered (i.e. only when neccessary)
+                if let Some(focused_marker) = self.focused_marker.as_mut().filter(|m| m.is_marker(layer, id)) {
                     // Update the clicked state
                     focused_marker.clicked = clicked;
-                    // Update the map overlay if the marker was just hovered
+                    // Update the markers if the marker was just hovered
                     if !focused_marker.hovered {
-                        self.update_overlay = true;
+                        self.update_markers = true;
                     }
                     // Update the hovered state
                     focused_marker.hovered = true;
@@ -390,108 +970,112 @@ impl<T: MapMarkerTrait> MapWidget<T> {
                 // Update the hovered state
                 focused_marker.hovered = false;
             }
-            // No marker is hovered, but there is a focused (NOT selected) marker, so update the focused marker and map overlay
+            // No marker is hovered, but there is a focused (NOT selected) marker, so update the focused marker and markers
             else if self.focused_marker.is_some() {
-                // Set the focused marker to None and update the map overlay
+                // Set the focused marker to None and update the markers
                 self.focused_marker = None;
-                self.update_overlay = true;
+                self.update_markers = true;
             }
         }
 
-        // The map was dragged so update the center position
-        if response.dragged() {
-
-            // Update the tile offset
-            self.relative_offset -= response.drag_delta();
-
-            // Get the half tile size
-            let half_tile_size = corrected_tile_size / 2.0;
-
-            // Move north
-            if self.relative_offset.y < -half_tile_size {
-                if let Some(new_tile) = self.center_tile.north() {
-                    self.center_tile = new_tile;
-                    self.relative_offset.y = half_tile_size;
-                } else {
-                    self.relative_offset.y = -half_tile_size;
-                }
-            }
-            // Move east
-            if self.relative_offset.x > half_tile_size {
-                if let Some(new_tile) = self.center_tile.east() {
-                    self.center_tile = new_tile;
-                    self.relative_offset.x = -half_tile_size;
-                } else {
-                    self.relative_offset.x = half_tile_size;
-                }
+        // Two-finger touch gestures pan the map. The first finger also drags the map, so it's ignored while there are multiple touches.
+        let multi_touch = ui.ctx().input(|i| i.multi_touch());
+        if let Some(touch) = multi_touch.filter(|_| response.contains_pointer()) {
+            if touch.translation_delta != Vec2::ZERO {
+                self.pan(touch.translation_delta);
             }
-            // Move south
-            if self.relative_offset.y > half_tile_size {
-                if let Some(new_tile) = self.center_tile.south() {
-                    self.center_tile = new_tile;
-                    self.relative_offset.y = -half_tile_size;
-                } else {
-                    self.relative_offset.y = half_tile_size;
-                }
-            }
-            // Move west
-            if self.relative_offset.x < -half_tile_size {
-                if let Some(new_tile) = self.center_tile.west() {
-                    self.center_tile = new_tile;
-                    self.relative_offset.x = half_tile_size;
-                } else {
-                    self.relative_offset.x = -half_tile_size;
-                }
-            }
-
-            // Update the center location
-            self.center_loc = self.get_center_location();
-
+        }
+        // The map was dragged, so move the map
+        else if response.dragged() {
+            self.pan(response.drag_delta());
         }
 
-        // Reset the map zoom and center when double clicked
+        // Reset the map zoom and position when double clicked
         if response.double_clicked() {
-            // Reset the tile offset and zoom
-            self.relative_offset = Vec2::new(0.0, 0.0);
-            self.center_tile.zoom = 0;
-            self.zoom = 0.0;
-            // Center the map at 0, 0
-            self.set_center_location(Coord::zero());
+            self.animation = None;
+            match self.projection {
+                Projection::AzimuthalEquidistant => {
+                    self.azimuthal_offset = Vec2::ZERO;
+                    self.azimuthal_zoom = 1.0;
+                },
+                Projection::Mercator => {
+                    // Reset the tile offset and zoom
+                    self.relative_offset = Vec2::new(0.0, 0.0);
+                    self.center_tile.zoom = 0;
+                    self.zoom = 0.0;
+                    // Center the map at 0, 0
+                    self.set_center_location(Coord::zero());
+                }
+            }
         }
 
         // Hover and Zoom logic
-        if let Some(_hover_pos) = response.hover_pos() {
+        if let Some(hover_pos) = response.hover_pos() {
 
-            // Get the zoom delta (how much the user zoomed)
+            // Get the zoom delta (how much the user zoomed). This includes pinch zooming on touchscreens.
             let zoom_delta = ui.ctx().input(|i| i.zoom_delta());
-            
-            // The user zoomed in/out
             if zoom_delta != 1.0 {
+                self.zoom_by(zoom_delta);
+            }
 
-                // Add the zoom delta to the zoom value
-                self.zoom += (zoom_delta - 1.0) * 0.5;
-                // Clamp the zoom to the 0-20 tile zoom range
-                self.zoom = self.zoom.clamp(0.0, 20.0);
+            // Pan with the arrow keys and zoom with +/-, unless a text box has the keyboard (e.g. the search box)
+            if !ui.ctx().wants_keyboard_input() {
+                let (pan, zoom) = ui.ctx().input(|i| {
+                    let mut pan = Vec2::ZERO;
+                    for (key, direction) in [(egui::Key::ArrowLeft, Vec2::RIGHT), (egui::Key::ArrowRight, Vec2::LEFT), (egui::Key::ArrowUp, Vec2::DOWN), (egui::Key::ArrowDown, Vec2::UP)] {
+                        if i.key_pressed(key) {
+                            pan += direction * Self::KEY_PAN_STEP;
+                        }
+                    }
 
-                // Update the tile zoom level
-                // NOTE: The type conversion to u8 automatically floors the value so we don't have to do it manually
-                self.center_tile.zoom = self.zoom as u8;
+                    let zoom = if i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals) {
+                        2.0
+                    } else if i.key_pressed(egui::Key::Minus) {
+                        0.5
+                    } else {
+                        1.0
+                    };
+                    (pan, zoom)
+                });
 
-                // Set the center location again
-                self.set_center_location(self.center_loc);
+                if pan != Vec2::ZERO {
+                    self.pan(pan);
+                }
+                if zoom != 1.0 {
+                    self.zoom_by(zoom);
+                }
+            }
 
+            // Show the location under the cursor at the bottom of the map
+            let relative_pos = hover_pos - map_rect.left_top();
+            let location = transform.to_geo(relative_pos.x as f64, relative_pos.y as f64);
+            if location.x.is_finite() && location.y.is_finite() {
+                let location = geo::coord! { x: (location.x + 180.0).rem_euclid(360.0) - 180.0, y: location.y };
+                let text = format!("{:.4}, {:.4}  {}", location.y, location.x, maidenhead::lat_lon_to_grid(&location));
+                let galley = map_painter.layout_no_wrap(text, egui::FontId::monospace(12.0), Color32::WHITE);
+                let rect = egui::Align2::CENTER_BOTTOM.anchor_rect(Rect::from_min_size(map_rect.center_bottom() - Vec2::new(0.0, 32.0), galley.size())).expand(2.0);
+                map_painter.rect_filled(rect, 2.0, Color32::from_black_alpha(160));
+                map_painter.galley(rect.shrink(2.0).min, galley, Color32::WHITE);
             }
 
         }
 
+        // The scale bar at the bottom of the map
+        draw_scale_bar(&map_painter, map_rect, &transform, &config.distance_unit);
+
         // Allocate a overlay UI for the map. This is useful showing text on top of the map
         ui.allocate_ui_at_rect(map_rect.shrink(4.0), |ui| {
 
             // Render the selected marker's UI in the top right corner of the map
             if let Some(focused_marker) = self.focused_marker.as_mut().filter(|m| m.selected) {
 
-                // Find the focused marker in the markers vec
-                if let Some(marker) = self.overlay_manager.markers.iter_mut().find(|m| m.id() == focused_marker.id) {
+                // Find the focused marker in its layer
+                let marker = layers.get_mut(focused_marker.layer)
+                    .and_then(|layer| {
+                        let index = find_marker(&**layer, focused_marker.id)?;
+                        layer.marker_mut(index)
+                    });
+                if let Some(marker) = marker {
 
                     // Get the rounding, margin, and fill color of the UI
                     let (rounding, margin, fill_color) = {
@@ -510,14 +1094,17 @@ impl<T: MapMarkerTrait> MapWidget<T> {
                 }
             }
 
-            // If we are using the OpenStreetMap tile provider, add license attribution in the bottom-right of the map
-            if let TileProvider::OpenStreetMap = config.map_config.tile_provider {
+            // If the tile provider needs attribution, add it in the bottom-right of the map
+            if let Some((text, url)) = config.map_config.tile_provider.attribution() {
 
-                // License attribution for OpenStreetMap in the bottom right corner of the map
+                // License attribution in the bottom right corner of the map
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
         
-                    // Create the openstreetmap hyperlink (Thanks OSM for being awesome :) )
-                    ui.hyperlink_to("OpenStreetMap", "https://www.openstreetmap.org");
+                    // Create the hyperlink to the tile provider (Thanks OSM for being awesome :) ), or just the text for custom tile providers
+                    match url {
+                        Some(url) => ui.hyperlink_to(text, url),
+                        None => ui.label(text)
+                    };
         
                     // Paint a background behind the hyperlink
                     map_painter.rect_filled(
@@ -530,6 +1117,83 @@ impl<T: MapMarkerTrait> MapWidget<T> {
             }
         });
 
+        // Show the visibility toggles, legends, and controls of the layers in the bottom-left corner of the map
+        if !layers.is_empty() {
+            ui.allocate_ui_at_rect(map_rect.shrink(4.0), |ui| {
+
+                // Get the rounding, margin, and fill color of the UI
+                let (rounding, margin, fill_color) = {
+                    let style = ui.style();
+                    (style.visuals.menu_rounding, style.spacing.menu_margin, style.visuals.panel_fill)
+                };
+                ui.with_layout(egui::Layout::bottom_up(egui::Align::Min), |ui| {
+                    for layer in layers.iter_mut() {
+                        let changed = egui::containers::Frame::none()
+                        .fill(fill_color)
+                        .inner_margin(margin)
+                        .rounding(rounding)
+                        .show(ui, |ui| {
+                            // The controls are shown under the visibility toggle, so they're laid out top down
+                            ui.with_layout(egui::Layout::top_down(egui::Align::Min), |ui| {
+                                let mut visible = layer.is_visible();
+                                if ui.checkbox(&mut visible, layer.name()).changed() {
+                                    layer.set_visible(visible);
+                                }
+                                visible && layer.ui(ui, config)
+                            }).inner
+                        })
+                        .inner;
+
+                        // Redraw the overlay if the layer changed. Visibility changes are picked up on the next frame.
+                        if changed {
+                            self.update_overlay = true;
+                        }
+                    }
+                });
+            });
+        }
+
+        // The search box at the top of the map
+        ui.allocate_ui_at_rect(map_rect.shrink(4.0), |ui| {
+
+            // Get the rounding, margin, and fill color of the UI
+            let (rounding, margin, fill_color) = {
+                let style = ui.style();
+                (style.visuals.menu_rounding, style.spacing.menu_margin, style.visuals.panel_fill)
+            };
+            ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                egui::containers::Frame::none()
+                .fill(fill_color)
+                .inner_margin(margin)
+                .rounding(rounding)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let hint = match self.callsign_locator {
+                            Some(_) => "Grid, lat/lon, or callsign",
+                            None => "Grid or lat/lon"
+                        };
+                        let text_edit = ui.add(egui::TextEdit::singleline(&mut self.search)
+                            .hint_text(hint)
+                            .desired_width(180.0));
+                        let entered = text_edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                        if self.search_task.is_some() {
+                            ui.spinner();
+                        } else if ui.button("Search").clicked() || entered {
+                            self.search(config);
+                        }
+
+                        // Export the map, and import overlays
+                        ui.separator();
+                        ui.menu_button("Files", |ui| self.files_ui(ui, config, layers, imported_start, transform));
+                        if self.export_task.is_some() || self.export_markers_task.is_some() || self.import_task.is_some() {
+                            ui.spinner();
+                        }
+                    });
+                });
+            });
+        });
+
         // Debug info
         #[cfg(debug_assertions)]
         ui.allocate_ui_at_rect(map_rect.shrink(4.0), |ui| {
@@ -541,6 +1205,7 @@ impl<T: MapMarkerTrait> MapWidget<T> {
             // ui.colored_label(debug_color, format!("Relative offset: {:?}", self.relative_offset));
             // ui.colored_label(debug_color, format!("Corrected tile size: {:?}", corrected_tile_size));
             ui.colored_label(DEBUG_COLOR, format!("Marker is focused: {}", self.focused_marker.is_some()));
+            ui.colored_label(DEBUG_COLOR, format!("Drawn markers and clusters: {}", self.overlay_manager.drawn.len()));
 
             let crosshair_rect = Rect::from_center_size(map_rect.center(), Vec2::new(5.0, 5.0));
             map_painter.rect_filled(crosshair_rect, 0.0, Color32::RED);
@@ -552,60 +1217,307 @@ impl<T: MapMarkerTrait> MapWidget<T> {
         response
     }
 
-    pub fn markers_mut(&mut self) -> &mut Vec<T> {
-        &mut self.overlay_manager.markers
-    }
-
-    /// Updates the map overlay. This is usually called when markers are added/remove from the map.
+    /// Updates the map overlay and markers on the next frame. This is usually called when markers are added/remove from a layer.
     /// 
-    /// NOTE: When this function is called, the focused marker state is retained if a marker with the same ID still exists in the overlay, otherwise the focused marker is reset to None.
+    /// NOTE: The focused marker state is retained if a marker with the same ID still exists in its layer, otherwise the focused marker is reset to None.
     pub fn update_overlay(&mut self) {
-        // Sets the update_overlay flag to true so the overlay is updated on the next frame
         self.update_overlay = true;
+        self.update_markers = true;
+    }
 
-        // If there is a focused marker but it doesn't exist in the overlay, reset the focused marker to None
-        if let Some(focused_marker_id) = self.focused_marker.as_ref().map(|m| m.id) {
-            // Search for any marker with a matching ID in the overlay
-            if !self.overlay_manager.markers.iter().any(|m| m.id() == focused_marker_id) {
-                // Couldn't find a matching marker, so reset the focused marker to None
-                self.focused_marker = None;
-            }
-        }
+    /// Repaints the markers on the next frame without redrawing the overlay. This is cheaper than [Self::update_overlay], and is used when the colors of markers change over time.
+    pub fn update_markers(&mut self) {
+        self.update_markers = true;
     }
 }
-impl<T: MapMarkerTrait> std::fmt::Debug for MapWidget<T> {
+impl std::fmt::Debug for MapWidget {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("MapWidget").field("Position", &self.relative_offset).field("Zoom", &self.zoom).finish()
     }
 }
 
+/// The position and zoom of a [MapWidget]. This is serialized by the tabs so their maps are restored where they were left.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapView {
+    /// The longitude and latitude of the center of the Web Mercator map
+    center: [f64; 2],
+    /// The zoom of the Web Mercator map
+    zoom: f32,
+    /// The zoom of the azimuthal projection
+    azimuthal_zoom: f32,
+    /// The offset of the center of the azimuthal projection from the center of the map, in pixels
+    azimuthal_offset: [f32; 2]
+}
+impl MapView {
+    /// Interpolates between two views. The longitude goes the short way around the world, across the antimeridian if needed.
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let longitude_delta = (other.center[0] - self.center[0] + 180.0).rem_euclid(360.0) - 180.0;
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
 
-/// The configuration for the map widget
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Config {
-    /// The tile provider that should be used
-    pub tile_provider: TileProvider
+        Self {
+            center: [
+                self.center[0] + longitude_delta * t as f64,
+                self.center[1] + (other.center[1] - self.center[1]) * t as f64
+            ],
+            zoom: lerp(self.zoom, other.zoom),
+            azimuthal_zoom: lerp(self.azimuthal_zoom, other.azimuthal_zoom),
+            azimuthal_offset: [lerp(self.azimuthal_offset[0], other.azimuthal_offset[0]), lerp(self.azimuthal_offset[1], other.azimuthal_offset[1])]
+        }
+    }
 }
-impl Default for Config {
+impl Default for MapView {
     fn default() -> Self {
         Self {
-            tile_provider: TileProvider::OpenStreetMap
+            center: [0.0, 0.0],
+            zoom: 0.0,
+            azimuthal_zoom: 1.0,
+            azimuthal_offset: [0.0, 0.0]
         }
     }
 }
 
+/// The tile meshes of the azimuthal projection, with the view that they were projected for
+struct AzimuthalMeshes {
+    transform: MapTransform,
+    /// The zoom level of the tiles
+    zoom: u8,
+    /// The offset of the map from the top left of the window
+    offset: Vec2,
+    /// The visible tiles and their meshes. The texture of each mesh is set when it's drawn.
+    meshes: Vec<(TileId, egui::Mesh)>
+}
 
-/// A struct that manages the map overlay. When given points on the map, this lazily draws the objects onto a transparent overlay, which is later drawn over the map itself.
-/// 
-/// This was created so we don't re-draw every point on the map every frame. This way, the points are only redrawn when the map changes
-struct MapOverlayManager<T: MapMarkerTrait> {
-    /// Markers that should be drawn on the map
-    markers: Vec<T>,
+/// Splits each visible tile of the azimuthal projection into quads, and projects them into meshes
+fn project_azimuthal_tiles(map_rect: Rect, transform: &MapTransform, zoom: u8) -> Vec<(TileId, egui::Mesh)> {
+    const SUBDIVISIONS: usize = 8;
+
+    let tiles_per_axis = max_tiles(zoom as u32);
+    let offset = map_rect.left_top().to_vec2();
+
+    // Quads that are stretched more than this are near the antipode, where the projection tears, so they're skipped
+    let max_edge = (transform.azimuthal_radius().unwrap_or_default() * 0.25) as f32;
+
+    let mut meshes = Vec::new();
+    for y in 0..tiles_per_axis {
+        for x in 0..tiles_per_axis {
+
+            // Project the vertices of the tile
+            let vertices: Vec<Option<egui::Pos2>> = (0..=SUBDIVISIONS).flat_map(|j| (0..=SUBDIVISIONS).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let tile_x = x as f64 + i as f64 / SUBDIVISIONS as f64;
+                    let tile_y = y as f64 + j as f64 / SUBDIVISIONS as f64;
+                    let location = geo::coord! {
+                        x: tile_x / tiles_per_axis as f64 * 360.0 - 180.0,
+                        y: gudermannian(PI * (1.0 - 2.0 * tile_y / tiles_per_axis as f64))
+                    };
+                    let (px, py) = transform.to_screen(&location);
+                    (px.is_finite() && py.is_finite()).then(|| egui::Pos2::new(px as f32, py as f32) + offset)
+                })
+                .collect();
+
+            // Skip tiles that aren't visible
+            if !vertices.iter().flatten().any(|v| map_rect.contains(*v)) {
+                continue;
+            }
+
+            let mut mesh = egui::Mesh::default();
+            for (index, vertex) in vertices.iter().enumerate() {
+                let (i, j) = (index % (SUBDIVISIONS + 1), index / (SUBDIVISIONS + 1));
+                mesh.vertices.push(egui::epaint::Vertex {
+                    pos: vertex.unwrap_or_default(),
+                    uv: egui::Pos2::new(i as f32 / SUBDIVISIONS as f32, j as f32 / SUBDIVISIONS as f32),
+                    color: Color32::WHITE
+                });
+            }
+
+            // Add the two triangles of each quad
+            for j in 0..SUBDIVISIONS {
+                for i in 0..SUBDIVISIONS {
+                    let corners = [
+                        j * (SUBDIVISIONS + 1) + i,
+                        j * (SUBDIVISIONS + 1) + i + 1,
+                        (j + 1) * (SUBDIVISIONS + 1) + i + 1,
+                        (j + 1) * (SUBDIVISIONS + 1) + i
+                    ];
+                    let Some(points) = corners.iter().map(|c| vertices[*c]).collect::<Option<Vec<_>>>() else { continue };
+                    let stretched = (0..4).any(|k| points[k].distance(points[(k + 1) % 4]) > max_edge);
+                    if stretched {
+                        continue;
+                    }
+
+                    let [a, b, c, d] = corners.map(|c| c as u32);
+                    mesh.add_triangle(a, b, c);
+                    mesh.add_triangle(a, c, d);
+                }
+            }
+
+            meshes.push((TileId { x, y, zoom }, mesh));
+        }
+    }
+
+    meshes
+}
+
+/// A copy of everything needed to render the map into an image, so the image can be rendered in the background
+struct MapSnapshot {
+    /// The transform of the map. The image is the same size as the map.
+    transform: MapTransform,
+    /// The zoom level of the tiles
+    tile_zoom: u8,
+    /// The overlay image, which uses premultiplied alpha
+    overlay: egui::ColorImage,
+    /// The visible markers and clusters
+    markers: Vec<DrawnMarker>,
+    /// The tile provider and tile cache
+    config: Config
+}
+impl MapSnapshot {
+    /// The color of the areas without tiles, such as past the antipode of the azimuthal projection
+    const BACKGROUND: image::Rgba<u8> = image::Rgba([24, 24, 24, 255]);
+
+    /// How many tiles are fetched at once while rendering the image
+    const MAX_CONCURRENT_TILES: usize = 8;
+
+    /// Renders the map into a PNG file, and returns the path of the file.
+    ///
+    /// The tiles are sampled at each pixel, so they're reprojected like the map, and the overlay and markers are blended on top.
+    /// Text (labels, cluster counts, and range ring distances) isn't rendered.
+    /// The tiles are fetched concurrently, and the image is rasterized and encoded on a blocking thread.
+    async fn save_png(self, path: PathBuf) -> Result<PathBuf> {
+
+        // Find the visible tiles. Every pixel is reprojected, so this is done on a blocking thread.
+        let (snapshot, tile_ids) = tokio::task::spawn_blocking(move || {
+            let tile_ids = self.visible_tiles();
+            (self, tile_ids)
+        }).await?;
+
+        // Load the visible tiles. Tiles that fail to load are left blank.
+        let mut tiles = HashMap::with_capacity(tile_ids.len());
+        let mut pending = tile_ids.into_iter();
+        let mut tasks = tokio::task::JoinSet::new();
+        loop {
+            while tasks.len() < Self::MAX_CONCURRENT_TILES {
+                let Some(tile_id) = pending.next() else { break };
+                let config = snapshot.config.clone();
+                tasks.spawn(async move { (tile_id, TileManager::fetch_tile(&tile_id, &config).await) });
+            }
+
+            match tasks.join_next().await {
+                Some(Ok((tile_id, Ok(tile)))) => {
+                    tiles.insert(tile_id, tile);
+                },
+                Some(Ok((tile_id, Err(err)))) => error!("Failed to load tile {tile_id:?} for the map image: {err}"),
+                Some(Err(err)) => error!("Failed to load a tile for the map image: {err}"),
+                None => break
+            }
+        }
+
+        // Rasterize and encode the image
+        tokio::task::spawn_blocking(move || snapshot.render(&tiles, path)).await?
+    }
+
+    /// Finds the tile under a pixel, and the position of the pixel in the tile from 0 to 1
+    fn sample(&self, x: u32, y: u32) -> Option<(TileId, f64, f64)> {
+        let tiles_per_axis = max_tiles(self.tile_zoom as u32) as f64;
+        let location = self.transform.to_geo(x as f64 + 0.5, y as f64 + 0.5);
+        if !location.x.is_finite() || !location.y.is_finite() {
+            return None;
+        }
+
+        let tile_x = ((location.x + 180.0).rem_euclid(360.0) / 360.0 * tiles_per_axis).min(tiles_per_axis - 1e-9);
+        let tile_y = ((1.0 - inverse_gudermannian(location.y.clamp(-MAX_LATITUDE, MAX_LATITUDE)) / PI) / 2.0 * tiles_per_axis).clamp(0.0, tiles_per_axis - 1e-9);
+        Some((TileId { x: tile_x as u32, y: tile_y as u32, zoom: self.tile_zoom }, tile_x.fract(), tile_y.fract()))
+    }
+
+    /// Gets the tiles that are under any pixel of the image
+    fn visible_tiles(&self) -> HashSet<TileId> {
+        let (width, height) = (self.transform.width(), self.transform.height());
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.sample(x, y).map(|(tile_id, _, _)| tile_id))
+            .collect()
+    }
+
+    /// Draws the tiles, overlay, and markers into an image, and saves it as a PNG file
+    fn render(self, tiles: &HashMap<TileId, image::RgbaImage>, path: PathBuf) -> Result<PathBuf> {
+        let (width, height) = (self.transform.width(), self.transform.height());
+
+        // Sample the tiles
+        let mut image = image::RgbaImage::from_pixel(width, height, Self::BACKGROUND);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let Some((tile_id, tile_x, tile_y)) = self.sample(x, y) else { continue };
+            let Some(tile) = tiles.get(&tile_id) else { continue };
+            let px = ((tile_x * tile.width() as f64) as u32).min(tile.width() - 1);
+            let py = ((tile_y * tile.height() as f64) as u32).min(tile.height() - 1);
+            *pixel = *tile.get_pixel(px, py);
+            pixel.0[3] = 255;
+        }
+
+        // Blend the overlay over the tiles
+        if self.overlay.size == [width as usize, height as usize] {
+            for (pixel, overlay) in image.pixels_mut().zip(self.overlay.pixels.iter()) {
+                let alpha = 255 - overlay.a() as u16;
+                for (channel, color) in pixel.0.iter_mut().zip([overlay.r(), overlay.g(), overlay.b()]) {
+                    *channel = (color as u16 + *channel as u16 * alpha / 255).min(255) as u8;
+                }
+            }
+        }
+
+        // Draw the markers over the overlay
+        for marker in &self.markers {
+            marker.rasterize(&mut image);
+        }
+
+        image.save_with_format(&path, image::ImageFormat::Png).map_err(Error::ImageEncoding)?;
+        Ok(path)
+    }
+}
+
+
+/// The configuration for the map widget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The tile provider that should be used
+    pub tile_provider: TileProvider,
+    /// The on-disk tile cache
+    pub cache: tile_cache::Config
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tile_provider: TileProvider::OpenStreetMap,
+            cache: Default::default()
+        }
+    }
+}
+
+
+/// A struct that manages the map overlay and markers. When given layers, this lazily draws them onto a transparent overlay, which is later drawn over the map itself.
+/// 
+/// This was created so we don't re-draw every layer every frame. This way, the layers are only redrawn when the map changes.
+/// Markers are painted with egui shapes every frame instead, but their positions and clusters are only computed when the map changes.
+struct MapOverlayManager {
     /// A handle to the overlay image texture
     overlay: TextureHandle,
-    cached_color_image: egui::ColorImage
+    cached_color_image: egui::ColorImage,
+    /// The visible markers and clusters, in the order they're painted
+    drawn: Vec<DrawnMarker>,
+    /// The positions of the visible markers and clusters, with their index in `drawn`. This is used for hover and hit tests.
+    index: RTree<GeomWithData<[f32; 2], usize>>,
+    /// The great circle path of the focused marker, relative to the overlay
+    path: Vec<egui::Shape>
 }
-impl<T: MapMarkerTrait> MapOverlayManager<T> {
+impl MapOverlayManager {
+    /// The distance between each point of a great circle path, in meters
+    const PATH_STEP: f64 = 100_000.0;
+    /// The size of the cells that nearby markers are grouped into, in pixels
+    const CLUSTER_CELL_SIZE: f32 = 48.0;
+    /// Markers are only clustered when the map is zoomed out further than this scale (about zoom level 6)
+    const CLUSTER_MAX_PIXELS_PER_DEGREE: f64 = 64.0;
+    /// Labels are only shown when there are at most this many markers and clusters on the map, so they don't cover it
+    const MAX_LABELS: usize = 200;
 
     /// Creates a new MapOverlayManager.
     /// 
@@ -621,58 +1533,31 @@ impl<T: MapMarkerTrait> MapOverlayManager<T> {
             egui::TextureOptions::LINEAR
         );
 
-        // let mut markers: Vec<T> = Vec::with_capacity(500);
-        // let mut rng = rand::thread_rng();
-        // for _ in 0..500 {
-        //     // objects.push((geo::coord! { x: rng.gen_range(-100.0..-90.0), y: rng.gen_range(-40.0..-30.0) }, true));
-        //     let m = Box::new(DummyMapMarker {
-        //         location: geo::coord! { x: rng.gen_range(-180.0..180.0), y: rng.gen_range(-85.0..85.0) },
-        //         callsign: arrayvec::ArrayString::from("K6JTH").unwrap()
-        //     });
-        //     markers.push(m);
-        //     // markers.push((geo::coord! { x: rng.gen_range(-180.0..180.0), y: rng.gen_range(-85.0..85.0) }, true));
-        // }
-
         Self {
-            markers: Default::default(),
             overlay: overlay_texture,
-            cached_color_image
+            cached_color_image,
+            drawn: Default::default(),
+            index: Default::default(),
+            path: Default::default()
         }
     }
 
-    /// When provided with a geo rect, map rect, and a cursor hover position,
-    /// this will return a iterator over the marker(s) that the cursor is hovering over.
-    fn hovered_markers_iter_mut(&mut self, geo_rect: geo::Rect<f64>, map_rect: egui::Rect, mut hover_pos: egui::Pos2) -> impl Iterator<Item = &mut T> {
-        
-        // Make the hover pos relative to the map rect instead of the whole window (i.e. 0px/0px is the top left of the map rect)
-        hover_pos -= map_rect.left_top().to_vec2();
-
-        // Get the width and height of the map rect
-        let width = map_rect.width() as usize;
-        let height = map_rect.height() as usize;
-
-        // Get the min and max lon/lat values of the geo rect
-        let (geo_min_x, geo_max_x) = (geo_rect.min().x, geo_rect.max().x);
-        let (geo_min_y, geo_max_y) = (inverse_gudermannian(geo_rect.min().y), inverse_gudermannian(geo_rect.max().y));
-
-        self.markers.iter_mut()
-        .filter(move |marker| geo_rect.intersects(marker.location()))
-        .filter(move |marker| {
-            // Calculate the x and y coordinates for the marker
-            let location = marker.location();
-            let x = convert_range(location.x, [geo_min_x, geo_max_x], [0.0, width as f64]) as f32;
-            let y = convert_range(inverse_gudermannian(location.y), [geo_min_y, geo_max_y], [height as f64, 0.0]) as f32;
-
-            // Create the marker rect
-            let point_rect = egui::Rect::from_center_size(egui::Pos2 { x, y }, Vec2 { x: 8.0, y: 8.0 });
-
-            // Check if the cursor is hovering over the marker rect
-            point_rect.contains(hover_pos)
-        })
-        
+    /// Returns the marker or cluster that the cursor is hovering over, if any.
+    /// 
+    /// The hover position is relative to the overlay (i.e. 0px/0px is the top left of the map rect).
+    fn hovered_marker(&self, hover_pos: Vec2) -> Option<DrawnMarker> {
+        let point = [hover_pos.x, hover_pos.y];
+
+        // Check the closest markers first, up to the size of the largest marker or cluster
+        self.index.nearest_neighbor_iter(&point)
+            .take_while(|p| p.distance_2(&point) <= DrawnMarker::MAX_RADIUS.powi(2))
+            .map(|p| &self.drawn[p.data])
+            .find(|drawn| drawn.contains(point))
+            .cloned()
     }
 
-    fn update_overlay(&mut self, map_rect: egui::Rect, geo_rect: geo::Rect<f64>, focused_marker: Option<&FocusedMarker>, config: &mut GuiConfig) {
+    /// Draws the layers onto the overlay image in the provided order, and uploads it to the GPU
+    fn update_overlay(&mut self, map_rect: egui::Rect, transform: &MapTransform, layers: &mut [&mut dyn MapLayer], order: &[usize], config: &mut GuiConfig) {
 
         // Get the width and height of the map rect
         let width = map_rect.width() as usize;
@@ -690,55 +1575,137 @@ impl<T: MapMarkerTrait> MapOverlayManager<T> {
         // We call unwrap here because the only way this should fail is if the pixel buffer isn't big enough, but we resize it every time, so it's guaranteed to be the right size
         let mut image_buf: ImageBuffer<image::Rgba<u8>, &mut [u8]> = ImageBuffer::from_raw(width as u32, height as u32, self.cached_color_image.as_raw_mut()).unwrap();
 
-        // Get the min and max lon/lat values of the geo rect
-        let (geo_min_x, geo_max_x) = (geo_rect.min().x, geo_rect.max().x);
-        let (geo_min_y, geo_max_y) = (inverse_gudermannian(geo_rect.min().y), inverse_gudermannian(geo_rect.max().y));
-
-        // Iterate through the visible markers
-        for marker in self.markers.iter().filter(|c| geo_rect.intersects(c.location())) {
-
-            // Calculate the x and y coordinates for the marker
-            let location = marker.location();
-            let x = convert_range(location.x, [geo_min_x, geo_max_x], [0.0, width as f64]) as i32;
-            let y = convert_range(inverse_gudermannian(location.y), [geo_min_y, geo_max_y], [height as f64, 0.0]) as i32;
-
-            // Draw a line to another point if the marker is focused and hovered
-            if focused_marker.as_ref().filter(|m| marker.id() == m.id && (m.hovered || m.selected)).is_some() {
-                if let Some(destination) = marker.draw_line_hovered() {
-                    // Calculate the destination x and y coordinates for the line
-                    let dest_x = convert_range(destination.x, [geo_min_x, geo_max_x], [0.0, width as f64]) as i32;
-                    let dest_y = convert_range(inverse_gudermannian(destination.y), [geo_min_y, geo_max_y], [height as f64, 0.0]) as i32;
-    
-                    // Draw a line from the marker to the destination
-                    imageproc::drawing::draw_antialiased_line_segment_mut(
-                        &mut image_buf,
-                        (x, y),
-                        (dest_x, dest_y),
-                        marker.color(config),
-                        imageproc::pixelops::interpolate
-                    );
+        // Draw the layers from the bottom to the top
+        for &index in order {
+            layers[index].draw(&mut image_buf, transform, config);
+        }
+
+        // Update the map overlay with our new image
+        self.overlay.set(
+            self.cached_color_image.clone(),
+            egui::TextureOptions::LINEAR
+        );
+
+    }
+
+    /// Calculates the positions of the visible markers in the provided layer order, groups nearby markers of each layer into clusters,
+    /// and rebuilds the index used for hover and hit tests
+    fn update_markers(&mut self, transform: &MapTransform, layers: &[&mut dyn MapLayer], order: &[usize], focused_marker: Option<&FocusedMarker>, clustering: bool, config: &mut GuiConfig) {
+        self.place_markers(transform, layers, order, focused_marker, clustering, |marker| to_color32(marker.color(config)));
+    }
+
+    /// Does the work of [Self::update_markers()], getting the color of each marker with `color`
+    fn place_markers(&mut self, transform: &MapTransform, layers: &[&mut dyn MapLayer], order: &[usize], focused_marker: Option<&FocusedMarker>, clustering: bool, mut color: impl FnMut(&dyn MapMarkerTrait) -> Color32) {
+        self.drawn.clear();
+        self.path.clear();
+
+        // Markers are only clustered when the map is zoomed out
+        let clustering = clustering && transform.pixels_per_degree() < Self::CLUSTER_MAX_PIXELS_PER_DEGREE;
+
+        for &layer_index in order {
+            let layer = &layers[layer_index];
+
+            // The markers in each cell of the clustering grid, with their index, position, and color.
+            // The cells are keyed by row and then column, so the clusters are always drawn in the same order.
+            let mut cells: BTreeMap<(i32, i32), Vec<(usize, [f32; 2], Color32)>> = BTreeMap::new();
+
+            // Iterate through the visible markers
+            for index in 0..layer.marker_count() {
+                let Some(marker) = layer.marker(index).filter(|m| transform.is_visible(m.location())) else { continue };
+
+                // Calculate the x and y coordinates for the marker
+                let location = marker.location();
+                let (x, y) = transform.to_screen(location);
+                let position = [x as f32, y as f32];
+                let color = color(marker);
+
+                // Draw a line to another point if the marker is focused and hovered
+                let focused = focused_marker.filter(|m| m.is_marker(layer_index, marker.id()));
+                if let Some(destination) = marker.draw_line_hovered().filter(|_| focused.is_some_and(|m| m.hovered || m.selected)) {
+
+                    // Draw the great circle path from the marker to the destination, which is split into separate lines where it crosses the antimeridian
+                    for line in geodesy::great_circle(location, destination, Self::PATH_STEP) {
+                        let points = line.iter()
+                            .map(|point| {
+                                let (x, y) = transform.to_screen(point);
+                                egui::Pos2::new(x as f32, y as f32)
+                            })
+                            .collect();
+                        self.path.push(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+                    }
+                }
+
+                // The focused marker is never clustered, so it stays visible while it's selected
+                if clustering && focused.is_none() {
+                    let cell = ((position[1] / Self::CLUSTER_CELL_SIZE).floor() as i32, (position[0] / Self::CLUSTER_CELL_SIZE).floor() as i32);
+                    cells.entry(cell).or_default().push((index, position, color));
+                    continue;
                 }
+
+                self.drawn.push(DrawnMarker::marker(layer_index, index, marker, position, color));
             }
 
-            // Create the marker rect
-            let point_rect = imageproc::rect::Rect::at(x - 4, y - 4)
-                .of_size(8, 8);
+            // Cells with a single marker are drawn as a marker, and cells with more are drawn as a cluster in the middle of its markers, with their average color
+            for members in cells.into_values() {
+                if let [(index, position, color)] = members[..] {
+                    if let Some(marker) = layer.marker(index) {
+                        self.drawn.push(DrawnMarker::marker(layer_index, index, marker, position, color));
+                    }
+                    continue;
+                }
 
-            // Draw the hollow rect
-            imageproc::drawing::draw_hollow_rect_mut(
-                &mut image_buf,
-                point_rect,
-                marker.color(config)
-            );
+                let count = members.len();
+                let mut position = [0.0; 2];
+                let mut color = [0_usize; 3];
+                for (_, p, c) in members.iter() {
+                    position[0] += p[0] / count as f32;
+                    position[1] += p[1] / count as f32;
+                    color[0] += c.r() as usize;
+                    color[1] += c.g() as usize;
+                    color[2] += c.b() as usize;
+                }
+                let color = Color32::from_rgb((color[0] / count) as u8, (color[1] / count) as u8, (color[2] / count) as u8);
+
+                self.drawn.push(DrawnMarker {
+                    layer: layer_index,
+                    position,
+                    color,
+                    shape: MarkerShape::Circle,
+                    size: DrawnMarker::cluster_radius(count) * 2.0,
+                    label: None,
+                    kind: DrawnMarkerKind::Cluster(count)
+                });
+            }
+        }
 
+        // Label the markers if the map isn't crowded
+        if self.drawn.len() <= Self::MAX_LABELS {
+            for drawn in self.drawn.iter_mut() {
+                let DrawnMarkerKind::Marker(index) = drawn.kind else { continue };
+                drawn.label = layers[drawn.layer].marker(index).and_then(|m| m.label()).map(str::to_string);
+            }
         }
 
-        // Update the map overlay with our new image
-        self.overlay.set(
-            self.cached_color_image.clone(),
-            egui::TextureOptions::LINEAR
+        self.index = RTree::bulk_load(
+            self.drawn.iter()
+            .enumerate()
+            .map(|(i, drawn)| GeomWithData::new(drawn.position, i))
+            .collect()
         );
+    }
+
+    /// Paints the great circle path of the focused marker, and the visible markers and clusters
+    fn paint_markers(&self, painter: &egui::Painter, map_rect: Rect) {
+        let offset = map_rect.left_top().to_vec2();
 
+        painter.extend(self.path.iter().cloned().map(|mut shape| {
+            shape.translate(offset);
+            shape
+        }));
+
+        for drawn in self.drawn.iter() {
+            drawn.paint(painter, offset);
+        }
     }
 
     fn get_overlay(&self) -> TextureId {
@@ -747,6 +1714,183 @@ impl<T: MapMarkerTrait> MapOverlayManager<T> {
 
 }
 
+/// A marker or a cluster of markers, at its position on the map overlay
+#[derive(Debug, Clone)]
+struct DrawnMarker {
+    /// The index of the layer of the marker or cluster
+    layer: usize,
+    /// The position on the overlay, in pixels
+    position: [f32; 2],
+    /// The color of the marker, or the average color of the markers in the cluster
+    color: Color32,
+    /// The shape of the marker. Clusters are circles.
+    shape: MarkerShape,
+    /// The width and height of the marker or cluster, in pixels
+    size: f32,
+    /// The label shown next to the marker, if any
+    label: Option<String>,
+    kind: DrawnMarkerKind
+}
+impl DrawnMarker {
+    /// The smallest width and height of a marker, in pixels
+    const MIN_SIZE: f32 = 4.0;
+    /// The largest radius of a cluster, which is also the largest width and height of a marker, in pixels
+    const MAX_RADIUS: f32 = 20.0;
+
+    /// Creates a single marker, with its index in its layer
+    fn marker(layer: usize, index: usize, marker: &dyn MapMarkerTrait, position: [f32; 2], color: Color32) -> Self {
+        Self {
+            layer,
+            position,
+            color,
+            shape: marker.shape(),
+            size: marker.size().clamp(Self::MIN_SIZE, Self::MAX_RADIUS),
+            label: None,
+            kind: DrawnMarkerKind::Marker(index)
+        }
+    }
+
+    /// The radius of a cluster, which grows with the number of markers in it
+    fn cluster_radius(count: usize) -> f32 {
+        (8.0 + 3.0 * (count as f32).log10()).min(Self::MAX_RADIUS)
+    }
+
+    /// Is the point (relative to the overlay) over the marker or cluster?
+    fn contains(&self, point: [f32; 2]) -> bool {
+        let (dx, dy) = (point[0] - self.position[0], point[1] - self.position[1]);
+        match self.kind {
+            DrawnMarkerKind::Marker(_) => dx.abs() <= self.size / 2.0 && dy.abs() <= self.size / 2.0,
+            DrawnMarkerKind::Cluster(_) => dx.hypot(dy) <= self.size / 2.0
+        }
+    }
+
+    /// Paints the marker in its shape with its label, or the cluster as a circle with the number of markers in it
+    fn paint(&self, painter: &egui::Painter, offset: Vec2) {
+        let center = egui::Pos2::from(self.position) + offset;
+        let radius = self.size / 2.0;
+        let stroke = egui::Stroke::new(1.0, self.color);
+
+        if let DrawnMarkerKind::Cluster(count) = self.kind {
+            let label = match count {
+                0..=999 => count.to_string(),
+                _ => format!("{}k", count / 1000)
+            };
+            painter.circle(center, radius, self.color.gamma_multiply(0.8), egui::Stroke::new(1.0, Color32::WHITE));
+            painter.text(center, egui::Align2::CENTER_CENTER, label, egui::FontId::proportional(11.0), Color32::WHITE);
+            return;
+        }
+
+        match self.shape {
+            MarkerShape::Square => {
+                painter.rect_stroke(Rect::from_center_size(center, Vec2::splat(self.size)), 0.0, stroke);
+            },
+            MarkerShape::Circle => {
+                painter.circle_stroke(center, radius, stroke);
+            },
+            MarkerShape::Triangle => {
+                let points = vec![center + Vec2::new(0.0, -radius), center + Vec2::new(radius, radius), center + Vec2::new(-radius, radius)];
+                painter.add(egui::Shape::convex_polygon(points, Color32::TRANSPARENT, stroke));
+            },
+            MarkerShape::Diamond => {
+                let points = vec![center + Vec2::new(0.0, -radius), center + Vec2::new(radius, 0.0), center + Vec2::new(0.0, radius), center + Vec2::new(-radius, 0.0)];
+                painter.add(egui::Shape::convex_polygon(points, Color32::TRANSPARENT, stroke));
+            },
+            MarkerShape::Icon(icon) => {
+                painter.text(center, egui::Align2::CENTER_CENTER, icon, egui::FontId::proportional(self.size * 1.5), self.color);
+            }
+        }
+
+        // Paint the label to the right of the marker, over a dark background so it's readable on any tiles
+        if let Some(label) = &self.label {
+            let galley = painter.layout_no_wrap(label.clone(), egui::FontId::proportional(11.0), Color32::WHITE);
+            let rect = egui::Align2::LEFT_CENTER.anchor_size(center + Vec2::new(radius + 3.0, 0.0), galley.size());
+            painter.rect_filled(rect.expand(1.0), 2.0, Color32::from_black_alpha(150));
+            painter.galley(rect.min, galley, Color32::WHITE);
+        }
+    }
+
+    /// Draws the marker or cluster onto an image, like [Self::paint()]. Text isn't drawn, so icons are drawn as circles.
+    fn rasterize(&self, image: &mut image::RgbaImage) {
+        use imageproc::{drawing, point::Point};
+
+        let (x, y) = (self.position[0], self.position[1]);
+        let center = (x as i32, y as i32);
+        let radius = self.size / 2.0;
+        let color = image::Rgba(self.color.to_srgba_unmultiplied());
+
+        if let DrawnMarkerKind::Cluster(_) = self.kind {
+            drawing::draw_filled_circle_mut(image, center, radius as i32, color);
+            drawing::draw_hollow_circle_mut(image, center, radius as i32, image::Rgba([255, 255, 255, 255]));
+            return;
+        }
+
+        match self.shape {
+            MarkerShape::Square => {
+                let rect = imageproc::rect::Rect::at((x - radius) as i32, (y - radius) as i32).of_size(self.size.max(1.0) as u32, self.size.max(1.0) as u32);
+                drawing::draw_hollow_rect_mut(image, rect, color);
+            },
+            MarkerShape::Circle | MarkerShape::Icon(_) => {
+                drawing::draw_hollow_circle_mut(image, center, radius as i32, color);
+            },
+            MarkerShape::Triangle => {
+                let points = [Point::new(x, y - radius), Point::new(x + radius, y + radius), Point::new(x - radius, y + radius)];
+                drawing::draw_hollow_polygon_mut(image, &points, color);
+            },
+            MarkerShape::Diamond => {
+                let points = [Point::new(x, y - radius), Point::new(x + radius, y), Point::new(x, y + radius), Point::new(x - radius, y)];
+                drawing::draw_hollow_polygon_mut(image, &points, color);
+            }
+        }
+    }
+}
+
+/// Is a [DrawnMarker] a single marker or a cluster?
+#[derive(Debug, Clone, Copy)]
+enum DrawnMarkerKind {
+    /// A single marker, with its index in its layer
+    Marker(usize),
+    /// A cluster of nearby markers, with the number of markers in it
+    Cluster(usize)
+}
+
+/// Gets the indices of the visible layers, sorted from the bottom to the top. Layers with the same z-order keep their order.
+fn layer_order(layers: &[&mut dyn MapLayer]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..layers.len()).filter(|&i| layers[i].is_visible()).collect();
+    order.sort_by_key(|&i| layers[i].z_order());
+    order
+}
+
+/// Hashes the names, visibility, z-order, number of markers, and generation of the layers, so changes to them can be detected
+fn hash_layers(layers: &[&mut dyn MapLayer]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for layer in layers {
+        (layer.name(), layer.is_visible(), layer.z_order(), layer.marker_count(), layer.generation()).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Finds the index of the marker with an ID in a layer
+fn find_marker(layer: &dyn MapLayer, id: u64) -> Option<usize> {
+    (0..layer.marker_count()).find(|&i| layer.marker(i).is_some_and(|m| m.id() == id))
+}
+
+/// Parses a latitude and longitude separated by a comma and/or spaces (e.g. `33.6, -117.9`), in decimal degrees
+fn parse_location(text: &str) -> Option<Coord> {
+    let mut parts = text.split(|c: char| c == ',' || c.is_whitespace()).filter(|p| !p.is_empty());
+    let latitude: f64 = parts.next()?.parse().ok()?;
+    let longitude: f64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then(|| geo::coord! { x: longitude, y: latitude })
+}
+
+/// Converts the color of a marker into an egui color
+fn to_color32(color: image::Rgba<u8>) -> Color32 {
+    let [r, g, b, a] = color.0;
+    Color32::from_rgba_unmultiplied(r, g, b, a)
+}
 
 /// Breadth flood fill tiling algorithm.
 /// 
@@ -866,7 +2010,7 @@ impl TileManager {
     /// Checks if any tiles have finished loading and removes expired tiles from the cache.
     /// 
     /// Call this each frame.
-    fn tick(&mut self) {
+    fn tick(&mut self, cache_config: &tile_cache::Config) {
 
         // Get the current instant
         let now = Instant::now();
@@ -893,15 +2037,24 @@ impl TileManager {
                 },
                 // The tile failed to load; put the fail into the cache. This is done to add a retry cooldown
                 Err(err) => {
-                    error!("Failed to load tile: {err}");
+                    // Tiles that aren't cached while offline are expected, so they aren't worth logging as an error
+                    match err.downcast_ref::<tile_cache::Error>() {
+                        Some(tile_cache::Error::NotCached) => debug!("Failed to load tile: {err}"),
+                        _ => error!("Failed to load tile: {err}")
+                    }
                     self.tile_cache.insert(tile_id, CachedTile::Failed { failed_at: now });
                 }
             }
         }
 
+        // Keep the on-disk cache under its size limit
+        if cache_config.enabled {
+            tile_cache::prune_if_due(cache_config);
+        }
+
     }
 
-    fn get_tile(&mut self, tile_id: &TileId, tile_provider: &TileProvider) -> TextureId {
+    fn get_tile(&mut self, tile_id: &TileId, config: &Config) -> TextureId {
 
         // Get the current instant
         let now = Instant::now();
@@ -936,7 +2089,7 @@ impl TileManager {
             let _enter_guard = RT.enter();
 
             // Spawn a task to load the tile
-            let promise = Promise::spawn_async(Self::get_tile_image_from_server(self.ctx.clone(), *tile_id, tile_provider.clone()));
+            let promise = Promise::spawn_async(Self::load_tile(self.ctx.clone(), *tile_id, config.clone()));
             self.tasks.insert(*tile_id, promise);
 
             // Return the loading texture
@@ -946,40 +2099,33 @@ impl TileManager {
 
     }
 
-    async fn get_tile_image_from_server(ctx: Context, tile_id: TileId, tile_provider: TileProvider) -> Result<TextureHandle> {
-
-        // Query the tile server using the provided tile provider
-        // TODO: Continue + License attribution
-        let response = tile_provider.get_tile(&tile_id).await?;
-
-        // If the API gave us an error, return it
-        if response.status().is_client_error() || response.status().is_server_error() {
-            // Format the error into a tile provider error with the response code and response text
-            let err = Error::TileProvider(response.status(), response.text().await.map_err(Error::Request)?);
-            return Err(err)?;
-        }
-
-        let response = response.bytes().await
-            .map_err(Error::Request)?;
+    /// Loads a tile from the on-disk cache or the tile provider, and uploads it to the GPU
+    async fn load_tile(ctx: Context, tile_id: TileId, config: Config) -> Result<TextureHandle> {
 
-        // Create the image decoder
-        let img = image::codecs::png::PngDecoder::new(Cursor::new(response))
-            .map_err(Error::ImageDecoding)?;
-
-        // Decode and read the image pixels into a 256x256x3 byte vector
-        let mut pixel_data = vec![0; img.total_bytes() as usize];
-        img.read_image(&mut pixel_data)
-            .map_err(Error::ImageDecoding)?;
+        // Get the tile image from the cache, or query the tile server using the provided tile provider
+        let img = Self::fetch_tile(&tile_id, &config).await?;
         
         // Upload the tile image to the GPU
         let tile_texture = ctx.load_texture(
             format!("TileManager_z{}_x{}_y{}", tile_id.zoom, tile_id.x, tile_id.y),
-            egui::ColorImage::from_rgb([256, 256], &pixel_data),
+            egui::ColorImage::from_rgba_unmultiplied([img.width() as usize, img.height() as usize], img.as_raw()),
             egui::TextureOptions::LINEAR
         );
 
         Ok(tile_texture)
     }
+
+    /// Gets a tile from the on-disk cache or the tile provider, and decodes it
+    async fn fetch_tile(tile_id: &TileId, config: &Config) -> Result<image::RgbaImage> {
+        let image_data = tile_cache::get_tile(tile_id, &config.tile_provider, &config.cache).await?;
+
+        // Decode the image, guessing its format (PNG, JPEG, or WebP) from its contents
+        let img = image::load_from_memory(&image_data)
+            .map_err(Error::ImageDecoding)?
+            .to_rgba8();
+
+        Ok(img)
+    }
 }
 impl std::fmt::Debug for TileManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -989,16 +2135,29 @@ impl std::fmt::Debug for TileManager {
 
 /// The ID of a map tile
 #[derive(Debug, Default, PartialEq, Clone, Copy, Eq, Hash)]
-struct TileId {
+pub struct TileId {
     /// The X/Longitude coordinate of the tile
-    x: u32,
+    pub x: u32,
     /// The Y/Latitude coordinate of the tile
-    y: u32,
+    pub y: u32,
     /// The zoom level of the tile
-    zoom: u8
+    pub zoom: u8
 }
 impl TileId {
 
+    /// Returns the tile that contains a location at a zoom level. Latitudes past the top and bottom of the map are clamped.
+    pub fn containing(location: &Coord, zoom: u8) -> Self {
+        let n_tiles = max_tiles(zoom as u32);
+        let x = (location.x + 180.0) / 360.0;
+        let y = (1.0 - inverse_gudermannian(location.y.clamp(-MAX_LATITUDE, MAX_LATITUDE)) / PI) / 2.0;
+
+        Self {
+            x: ((x * n_tiles as f64) as u32).min(n_tiles - 1),
+            y: ((y * n_tiles as f64) as u32).min(n_tiles - 1),
+            zoom
+        }
+    }
+
     /// Does this TileID correspond to an actual map tile? (i.e. is this tile in bounds of earth)
     /// 
     /// Returns false if the tile is *outside of the range of the world*
@@ -1060,16 +2219,26 @@ impl TileId {
 /// A map error
 #[derive(Debug, Error)]
 enum Error {
-    #[error("Failed execute request: {0}")]
-    Request(reqwest::Error),
-    #[error("Failed to get tile from the tile provider ({0}): {1}")]
-    TileProvider(reqwest::StatusCode, String),
     #[error("Failed to decode the tile image: {0}")]
     ImageDecoding(image::ImageError),
+    #[error("Failed to save the map image: {0}")]
+    ImageEncoding(image::ImageError),
     #[error("No auth token was provided")]
     NoAuthToken,
     #[error("No style was provided")]
-    NoStyle
+    NoStyle,
+    #[error("No URL template was provided")]
+    NoUrl,
+    #[error("Failed to read the tile: {0}")]
+    Io(std::io::Error),
+    #[error("Failed to read the tile from the MBTiles file: {0}")]
+    MBTiles(rusqlite::Error),
+    #[error("The tile isn't in the MBTiles file")]
+    MissingTile,
+    #[error("The tile provider doesn't read tiles from disk")]
+    NotLocal,
+    #[error("The tile provider reads tiles from disk, so there's nothing to request")]
+    Local
 }
 
 /// The supported tile providers. These are APIs that can be used to fetch tiles.
@@ -1111,14 +2280,54 @@ pub enum TileProvider {
         /// The basemap style to use
         #[serde(default)]
         style: CartoCDNStyle
+    },
+    /// A custom tile server (e.g. a local tile server or a topographic map), configured with a URL template.
+    /// 
+    /// In the template, `{z}`, `{x}`, and `{y}` are replaced with the tile coordinates, `{-y}` with the flipped Y coordinate used by TMS servers,
+    /// `{s}` with one of the subdomains, and `{key}` with the API key. A `file://` template reads the tiles from a directory instead.
+    /// 
+    /// Some examples:
+    /// - https://{s}.tile.opentopomap.org/{z}/{x}/{y}.png
+    /// - http://192.168.1.10:8080/tile/{z}/{x}/{y}.png
+    /// - file:///home/user/tiles/{z}/{x}/{y}.png
+    Custom {
+        /// The URL template
+        url: String,
+        /// The subdomains used for `{s}`, separated by commas (e.g. `a,b,c`)
+        #[serde(default)]
+        subdomains: String,
+        /// The API key, if the server needs one
+        #[serde(default)]
+        api_key: String,
+        /// Where the API key is placed in the request
+        #[serde(default)]
+        api_key_placement: ApiKeyPlacement,
+        /// The name of the query parameter or header that contains the API key
+        #[serde(default)]
+        api_key_name: String,
+        /// Extra headers sent with each request, one `Name: value` per line
+        #[serde(default)]
+        headers: String,
+        /// The attribution text shown in the corner of the map
+        #[serde(default)]
+        attribution: String
+    },
+    /// An MBTiles file, which is a SQLite database of tiles
+    MBTiles {
+        /// The path of the MBTiles file
+        path: String,
+        /// The attribution text shown in the corner of the map
+        #[serde(default)]
+        attribution: String
     }
 }
 impl TileProvider {
-    async fn get_tile(&self, tile_id: &TileId) -> Result<Response> {
-        let response = match self {
+    /// Creates the request for a tile, which the tile cache sends
+    pub fn request(&self, tile_id: &TileId) -> Result<RequestBuilder> {
+        let request = match self {
             TileProvider::OpenStreetMap => {
                 let url = format!("https://tile.openstreetmap.org/{}/{}/{}.png", tile_id.zoom, tile_id.x, tile_id.y);
-                CLIENT.get(url).send().await.map_err(Error::Request)?
+                CLIENT.get(url)
             },
             TileProvider::MapBox { access_token, style_owner, style } => {
 
@@ -1133,7 +2342,7 @@ impl TileProvider {
                 }
 
                 let url = format!("https://api.mapbox.com/styles/v1/{style_owner}/{style}/tiles/256/{}/{}/{}", tile_id.zoom, tile_id.x, tile_id.y);
-                CLIENT.get(url).query(&[("access_token", &access_token)]).send().await.map_err(Error::Request)?
+                CLIENT.get(url).query(&[("access_token", &access_token)])
             },
             TileProvider::CartoCDN { access_token, style } => {
 
@@ -1143,16 +2352,122 @@ impl TileProvider {
                 }
 
                 let url = format!("https://basemaps.cartocdn.com/{}/{}/{}/{}.png", style.as_str(), tile_id.zoom, tile_id.x, tile_id.y);
-                CLIENT.get(url).bearer_auth(access_token).send().await.map_err(Error::Request)?
-            }
+                CLIENT.get(url).bearer_auth(access_token)
+            },
+            TileProvider::Custom { url, subdomains, api_key, api_key_placement, api_key_name, headers, attribution: _ } => {
+
+                // Ensure we have a URL template, and that it isn't for local tiles
+                if url.trim().is_empty() {
+                    Err(Error::NoUrl)?;
+                }
+                if self.is_local() {
+                    Err(Error::Local)?;
+                }
+
+                let mut request = CLIENT.get(tile_url(url.trim(), tile_id, subdomains, api_key));
+
+                // Add the API key
+                if !api_key.is_empty() {
+                    request = match api_key_placement {
+                        ApiKeyPlacement::Url => request,
+                        ApiKeyPlacement::Query => request.query(&[(api_key_name.trim(), api_key)]),
+                        ApiKeyPlacement::Header => request.header(api_key_name.trim(), api_key),
+                        ApiKeyPlacement::Bearer => request.bearer_auth(api_key)
+                    };
+                }
+
+                // Add the extra headers
+                for (name, value) in headers.lines().filter_map(|l| l.split_once(':')) {
+                    request = request.header(name.trim(), value.trim());
+                }
+
+                request
+            },
+            TileProvider::MBTiles { .. } => Err(Error::Local)?
         };
 
-        Ok(response)
+        Ok(request)
+    }
+
+    /// Does this provider read tiles from disk? Local tiles aren't cached, and can't be downloaded into offline packs.
+    pub fn is_local(&self) -> bool {
+        match self {
+            TileProvider::Custom { url, .. } => url.trim().starts_with("file://"),
+            TileProvider::MBTiles { .. } => true,
+            _ => false
+        }
+    }
+
+    /// Reads a tile from a directory or MBTiles file. This blocks, so it should be called on a blocking thread.
+    pub fn read_local(&self, tile_id: &TileId) -> Result<Vec<u8>> {
+        match self {
+            TileProvider::Custom { url, subdomains, api_key, .. } if self.is_local() => {
+                let path = tile_url(url.trim().trim_start_matches("file://"), tile_id, subdomains, api_key);
+                Ok(std::fs::read(path).map_err(Error::Io)?)
+            },
+            TileProvider::MBTiles { path, .. } => {
+                let path = PathBuf::from(path.trim());
+                MBTILES.with_borrow_mut(|files| {
+
+                    // Open the file the first time it's used on this thread
+                    let connection = match files.entry(path) {
+                        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                        std::collections::hash_map::Entry::Vacant(entry) => {
+                            let connection = rusqlite::Connection::open_with_flags(entry.key(), rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+                                .map_err(Error::MBTiles)?;
+                            entry.insert(connection)
+                        }
+                    };
+
+                    // MBTiles uses TMS rows, which count up from the bottom of the map
+                    let row = max_tiles(tile_id.zoom as u32) - 1 - tile_id.y;
+                    let tile = connection.query_row(
+                        "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                        (tile_id.zoom, tile_id.x, row),
+                        |r| r.get::<_, Vec<u8>>(0)
+                    );
+                    match tile {
+                        Ok(tile) => Ok(tile),
+                        Err(rusqlite::Error::QueryReturnedNoRows) => Err(Error::MissingTile)?,
+                        Err(err) => Err(Error::MBTiles(err))?
+                    }
+                })
+            },
+            _ => Err(Error::NotLocal)?
+        }
+    }
+
+    /// Returns the attribution text of the tile provider, and a link to the provider if there is one
+    pub fn attribution(&self) -> Option<(&str, Option<&'static str>)> {
+        match self {
+            TileProvider::OpenStreetMap => Some(("OpenStreetMap", Some("https://www.openstreetmap.org"))),
+            TileProvider::Custom { attribution, .. } | TileProvider::MBTiles { attribution, .. } if !attribution.trim().is_empty() => Some((attribution.trim(), None)),
+            _ => None
+        }
+    }
+
+    /// Returns a name for the tiles of this provider and style, which keeps them apart in the tile cache
+    pub fn cache_key(&self) -> String {
+        // Only keep characters that are safe in a path
+        let sanitize = |s: &str| s.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_', "_");
+
+        match self {
+            TileProvider::OpenStreetMap => "openstreetmap".to_string(),
+            TileProvider::MapBox { style_owner, style, .. } => format!("mapbox/{}/{}", sanitize(style_owner), sanitize(style)),
+            TileProvider::CartoCDN { style, .. } => format!("carto/{}", sanitize(style.as_str())),
+            // The URL template can contain anything, so it's hashed instead
+            TileProvider::Custom { url, .. } => {
+                let mut hasher = DefaultHasher::new();
+                url.trim().hash(&mut hasher);
+                format!("custom/{:016x}", hasher.finish())
+            },
+            TileProvider::MBTiles { path, .. } => format!("mbtiles/{}", sanitize(path))
+        }
     }
 
     /// Returns the name of the tile providers. This is used to display the supported tile providers in the settings tab
-    pub fn tile_providers() -> [&'static str; 3] {
-        ["OpenStreetMap", "MapBox", "Carto"]
+    pub fn tile_providers() -> [&'static str; 5] {
+        ["OpenStreetMap", "MapBox", "Carto", "Custom", "MBTiles"]
     }
 
     /// Returns the name of the tile provider as a string. This is used to display the supported tile providers in the settings tab
@@ -1160,11 +2475,56 @@ impl TileProvider {
         match self {
             TileProvider::OpenStreetMap => "OpenStreetMap",
             TileProvider::MapBox { .. } => "MapBox",
-            TileProvider::CartoCDN { .. } => "Carto"
+            TileProvider::CartoCDN { .. } => "Carto",
+            TileProvider::Custom { .. } => "Custom",
+            TileProvider::MBTiles { .. } => "MBTiles"
         }
     }
 }
 
+/// Where the API key of a custom tile provider is placed in the request
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
+pub enum ApiKeyPlacement {
+    /// In the URL template, replacing `{key}`
+    #[default]
+    Url,
+    /// In a query parameter
+    Query,
+    /// In a header
+    Header,
+    /// In the `Authorization` header, as a bearer token
+    Bearer
+}
+impl ApiKeyPlacement {
+    /// Returns the name of the placement as a string
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiKeyPlacement::Url => "URL template ({key})",
+            ApiKeyPlacement::Query => "Query parameter",
+            ApiKeyPlacement::Header => "Header",
+            ApiKeyPlacement::Bearer => "Bearer token"
+        }
+    }
+}
+
+/// Fills in a URL template of a custom tile provider. The tiles are spread over the subdomains, like most web maps do.
+fn tile_url(template: &str, tile_id: &TileId, subdomains: &str, api_key: &str) -> String {
+    let subdomains: Vec<&str> = subdomains.split(',').map(str::trim).filter(|s| !s.is_empty()).collect();
+    let subdomain = match subdomains.is_empty() {
+        true => "",
+        false => subdomains[(tile_id.x + tile_id.y) as usize % subdomains.len()]
+    };
+    let flipped_y = max_tiles(tile_id.zoom as u32) - 1 - tile_id.y;
+
+    template
+        .replace("{z}", &tile_id.zoom.to_string())
+        .replace("{x}", &tile_id.x.to_string())
+        .replace("{-y}", &flipped_y.to_string())
+        .replace("{y}", &tile_id.y.to_string())
+        .replace("{s}", subdomain)
+        .replace("{key}", api_key)
+}
+
 /// The supported CartoCDN map styles
 #[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
 pub enum CartoCDNStyle {
@@ -1227,9 +2587,10 @@ enum CachedTile {
 #[allow(unused_variables)]
 /// Must be implemented for a marker that should be visible on the map.
 /// 
-/// This exists so you can easily create custom markers for different purposes.
-pub trait MapMarkerTrait: Copy {
-    /// Should return an ID that's unique to the marker. This is required so we can track marker interaction events (clicks, hovers, etc)
+/// This exists so you can easily create custom markers for different purposes. Markers are stored in a layer, such as a [MarkerLayer], and can own their data (strings, vectors, etc).
+/// A layer can mix different kinds of markers by storing an enum or `Box<dyn MapMarkerTrait>`.
+pub trait MapMarkerTrait {
+    /// Should return an ID that's unique to the marker within its layer. This is required so we can track marker interaction events (clicks, hovers, etc)
     /// 
     /// NOTE: This ID has to be unique, not necessarily random. It may be useful to have the ID as a hash of the marker data. This allows the UI to persist the markers states across overlay updates.
     /// However, this is not required.
@@ -1255,6 +2616,710 @@ pub trait MapMarkerTrait: Copy {
 
     /// Implement this if you want the map widget to draw a line from this marker to another coordinate (possibly another marker) on hover
     fn draw_line_hovered(&self) -> Option<&Coord<f64>> { None }
+
+    /// The shape of the marker. Markers are hollow squares by default.
+    fn shape(&self) -> MarkerShape { MarkerShape::Square }
+
+    /// The width and height of the marker, in pixels. Use [snr_size()] to size the marker by a signal report.
+    fn size(&self) -> f32 { 8.0 }
+
+    /// A text label shown next to the marker, such as a callsign. Labels are only shown when the map isn't crowded.
+    fn label(&self) -> Option<&str> { None }
+
+    /// The names and values of the marker's data, such as the callsign, SNR, frequency, and time. These are saved when the markers are exported to a KML or GeoJSON file.
+    fn properties(&self) -> Vec<(&str, String)> { Vec::new() }
+}
+impl<M: MapMarkerTrait + ?Sized> MapMarkerTrait for Box<M> {
+    fn id(&self) -> u64 {
+        (**self).id()
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        (**self).location()
+    }
+
+    fn hovered_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        (**self).hovered_ui(ui, config)
+    }
+
+    fn selected_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        (**self).selected_ui(ui, config)
+    }
+
+    fn color(&self, config: &mut GuiConfig) -> image::Rgba<u8> {
+        (**self).color(config)
+    }
+
+    fn draw_line_hovered(&self) -> Option<&Coord<f64>> {
+        (**self).draw_line_hovered()
+    }
+
+    fn shape(&self) -> MarkerShape {
+        (**self).shape()
+    }
+
+    fn size(&self) -> f32 {
+        (**self).size()
+    }
+
+    fn label(&self) -> Option<&str> {
+        (**self).label()
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        (**self).properties()
+    }
+}
+
+/// The shape of a marker
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MarkerShape {
+    /// A hollow square
+    #[default]
+    Square,
+    /// A hollow circle
+    Circle,
+    /// A hollow triangle, pointing up
+    Triangle,
+    /// A hollow diamond
+    Diamond,
+    /// A character, such as an emoji, drawn in the color of the marker
+    Icon(char)
+}
+
+/// Gets the size of a marker from an SNR in dB, so stronger signals have bigger markers.
+/// 
+/// The size goes from 4 pixels at -30 dB to 16 pixels at +20 dB.
+pub fn snr_size(snr: f32) -> f32 {
+    convert_range(snr.clamp(-30.0, 20.0) as f64, [-30.0, 20.0], [4.0, 16.0]) as f32
+}
+
+/// The image that layers are drawn onto
+pub type OverlayImage<'a> = ImageBuffer<image::Rgba<u8>, &'a mut [u8]>;
+
+#[allow(unused_variables)]
+/// Must be implemented for a layer that should be shown on the map.
+/// 
+/// A layer can draw itself onto the map overlay, which is useful for things that cover an area (contours, gradients, etc), and it can have markers.
+/// The overlay is drawn in the z-order of the layers, and only when the overlay is updated. The markers are painted over the overlay, also in the z-order of their layers.
+pub trait MapLayer {
+    /// The name of the layer, which is shown next to its visibility toggle
+    fn name(&self) -> &str;
+
+    /// Is the layer shown on the map?
+    fn is_visible(&self) -> bool;
+
+    /// Shows or hides the layer
+    fn set_visible(&mut self, visible: bool);
+
+    /// The z-order of the layer. Layers with a higher z-order are drawn over layers with a lower one, and layers with the same z-order are drawn in the order they're passed to the map.
+    fn z_order(&self) -> i32 { 0 }
+
+    /// Draws the layer onto the overlay image. The transform converts geographic coordinates into pixels on the image.
+    fn draw(&mut self, image: &mut OverlayImage, transform: &MapTransform, config: &mut GuiConfig) {}
+
+    /// Shows the legend and controls of the layer in the bottom-left corner of the map, under its visibility toggle. This is only called when the layer is visible.
+    /// 
+    /// Return true if the layer changed and should be redrawn.
+    fn ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) -> bool { false }
+
+    /// Paints on top of the overlay every frame. This is useful for things that can't be drawn onto the overlay image, such as text.
+    /// 
+    /// `map_rect` is the rect of the map on the screen, so positions from the transform should be offset by its top left corner.
+    fn paint(&mut self, painter: &egui::Painter, map_rect: Rect, config: &mut GuiConfig) {}
+
+    /// The number of markers in the layer
+    fn marker_count(&self) -> usize { 0 }
+
+    /// Gets a marker of the layer by its index
+    fn marker(&self, index: usize) -> Option<&dyn MapMarkerTrait> { None }
+
+    /// Gets a mutable marker of the layer by its index
+    fn marker_mut(&mut self, index: usize) -> Option<&mut dyn MapMarkerTrait> { None }
+
+    /// A counter that the layer increments each time its markers change, so the map only updates them when they changed.
+    /// 
+    /// This is compared instead of the markers themselves, because going through every marker each frame is too slow with many markers.
+    fn generation(&self) -> u64 { 0 }
+}
+
+/// A named layer of markers. The markers can be of any type, including an enum or `Box<dyn MapMarkerTrait>` to mix different kinds of markers.
+#[derive(Debug)]
+pub struct MarkerLayer<T: MapMarkerTrait> {
+    /// The name of the layer
+    name: String,
+    /// Is the layer shown on the map?
+    pub visible: bool,
+    /// The z-order of the layer
+    pub z_order: i32,
+    /// The markers of the layer
+    markers: Vec<T>,
+    /// The legend of the layer, as the color and description of each kind of marker
+    pub legend: Vec<(Color32, String)>,
+    /// Incremented each time the markers are borrowed mutably
+    generation: u64
+}
+impl<T: MapMarkerTrait> MarkerLayer<T> {
+    /// Creates a visible layer without any markers
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            z_order: 0,
+            markers: Vec::new(),
+            legend: Vec::new(),
+            generation: 0
+        }
+    }
+
+    /// The markers of the layer
+    pub fn markers(&self) -> &[T] {
+        &self.markers
+    }
+
+    /// The markers of the layer, so they can be changed. The map updates the markers of the layer the next time it's shown.
+    pub fn markers_mut(&mut self) -> &mut Vec<T> {
+        self.generation += 1;
+        &mut self.markers
+    }
+}
+impl<T: MapMarkerTrait> MapLayer for MarkerLayer<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn z_order(&self) -> i32 {
+        self.z_order
+    }
+
+    fn ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) -> bool {
+        for (color, text) in &self.legend {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("\u{25A0}").color(*color));
+                ui.label(text);
+            });
+        }
+        false
+    }
+
+    fn marker_count(&self) -> usize {
+        self.markers.len()
+    }
+
+    fn marker(&self, index: usize) -> Option<&dyn MapMarkerTrait> {
+        self.markers.get(index).map(|m| m as &dyn MapMarkerTrait)
+    }
+
+    fn marker_mut(&mut self, index: usize) -> Option<&mut dyn MapMarkerTrait> {
+        self.markers.get_mut(index).map(|m| m as &mut dyn MapMarkerTrait)
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// A named layer of lines and polygons, such as paths and areas. They're drawn onto the map overlay.
+#[derive(Debug)]
+pub struct FeatureLayer {
+    /// The name of the layer
+    name: String,
+    /// Is the layer shown on the map?
+    pub visible: bool,
+    /// The z-order of the layer
+    pub z_order: i32,
+    /// The lines of the layer
+    pub lines: Vec<MapLine>,
+    /// The polygons of the layer, which are drawn under the lines
+    pub polygons: Vec<MapPolygon>
+}
+impl FeatureLayer {
+    /// Creates a visible layer without any lines or polygons
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            z_order: 0,
+            lines: Vec::new(),
+            polygons: Vec::new()
+        }
+    }
+}
+impl MapLayer for FeatureLayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    fn z_order(&self) -> i32 {
+        self.z_order
+    }
+
+    fn draw(&mut self, image: &mut OverlayImage, transform: &MapTransform, _config: &mut GuiConfig) {
+        for polygon in &self.polygons {
+            let rings: Vec<Vec<(f64, f64)>> = std::iter::once(polygon.polygon.exterior())
+                .chain(polygon.polygon.interiors())
+                .map(|ring| ring.coords().map(|c| transform.to_screen(c)).collect())
+                .collect();
+
+            fill_polygon(image, &rings, polygon.fill);
+            if let Some(outline) = polygon.outline {
+                for ring in &rings {
+                    for segment in ring.windows(2) {
+                        draw_line(image, segment[0], segment[1], outline);
+                    }
+                }
+            }
+        }
+
+        for line in &self.lines {
+            for segment in line.points.windows(2) {
+                // Skip segments that wrap around the antimeridian
+                if (segment[0].x - segment[1].x).abs() > 180.0 {
+                    continue;
+                }
+                draw_line(image, transform.to_screen(&segment[0]), transform.to_screen(&segment[1]), line.color);
+            }
+        }
+    }
+}
+
+/// A line on the map, made of straight segments between its points. Use [geodesy::great_circle()] for a line that follows the great circle path.
+#[derive(Debug, Clone)]
+pub struct MapLine {
+    /// The points of the line
+    pub points: Vec<Coord<f64>>,
+    /// The color of the line, which is premultiplied like [draw_line()]
+    pub color: image::Rgba<u8>
+}
+
+/// A filled area on the map
+#[derive(Debug, Clone)]
+pub struct MapPolygon {
+    /// The area, whose holes are left unfilled
+    pub polygon: geo::Polygon<f64>,
+    /// The color of the area, which is blended like [blend_rect()]
+    pub fill: image::Rgba<u8>,
+    /// The color of the outline, if it has one
+    pub outline: Option<image::Rgba<u8>>
+}
+
+/// Converts between geographic coordinates and pixels on the map overlay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapTransform {
+    /// The width of the overlay, in pixels
+    width: f64,
+    /// The height of the overlay, in pixels
+    height: f64,
+    /// The projection specific values
+    kind: TransformKind
+}
+impl MapTransform {
+    /// Creates the transform for the Web Mercator projection
+    fn mercator(map_rect: Rect, geo_rect: geo::Rect<f64>) -> Self {
+        Self {
+            width: map_rect.width() as usize as f64,
+            height: map_rect.height() as usize as f64,
+            kind: TransformKind::Mercator {
+                geo_rect,
+                x_range: [geo_rect.min().x, geo_rect.max().x],
+                y_range: [inverse_gudermannian(geo_rect.min().y), inverse_gudermannian(geo_rect.max().y)]
+            }
+        }
+    }
+
+    /// Creates the transform for the azimuthal equidistant projection
+    fn azimuthal(map_rect: Rect, center: Coord<f64>, radius: f64, origin: (f64, f64)) -> Self {
+        Self {
+            width: map_rect.width() as usize as f64,
+            height: map_rect.height() as usize as f64,
+            kind: TransformKind::Azimuthal { center, radius, origin }
+        }
+    }
+
+    /// The width of the overlay, in pixels
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    /// The height of the overlay, in pixels
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+
+    /// The projection of the transform
+    pub fn projection(&self) -> Projection {
+        match self.kind {
+            TransformKind::Mercator { .. } => Projection::Mercator,
+            TransformKind::Azimuthal { .. } => Projection::AzimuthalEquidistant
+        }
+    }
+
+    /// The visible area of the map. The longitude may be outside of -180..180 when the map is zoomed out.
+    /// 
+    /// The whole world is treated as visible in the azimuthal projection.
+    pub fn geo_rect(&self) -> geo::Rect<f64> {
+        match self.kind {
+            TransformKind::Mercator { geo_rect, .. } => geo_rect,
+            TransformKind::Azimuthal { .. } => geo::Rect::new(geo::coord! { x: -180.0, y: -90.0 }, geo::coord! { x: 180.0, y: 90.0 })
+        }
+    }
+
+    /// Could the location be visible on the map? This is a cheap check used to cull markers.
+    pub fn is_visible(&self, location: &Coord<f64>) -> bool {
+        match self.kind {
+            TransformKind::Mercator { geo_rect, .. } => geo_rect.intersects(location),
+            TransformKind::Azimuthal { .. } => true
+        }
+    }
+
+    /// The radius of the azimuthal projection (i.e. the distance to the antipode), in pixels
+    pub fn azimuthal_radius(&self) -> Option<f64> {
+        match self.kind {
+            TransformKind::Mercator { .. } => None,
+            TransformKind::Azimuthal { radius, .. } => Some(radius)
+        }
+    }
+
+    /// The pixel position of the center of the azimuthal projection
+    pub fn azimuthal_origin(&self) -> Option<(f64, f64)> {
+        match self.kind {
+            TransformKind::Mercator { .. } => None,
+            TransformKind::Azimuthal { origin, .. } => Some(origin)
+        }
+    }
+
+    /// The scale of the map in pixels per degree of longitude. In the azimuthal projection, this is the scale at the center.
+    pub fn pixels_per_degree(&self) -> f64 {
+        match self.kind {
+            TransformKind::Mercator { x_range, .. } => self.width / (x_range[1] - x_range[0]),
+            TransformKind::Azimuthal { radius, .. } => radius / 180.0
+        }
+    }
+
+    /// Converts a location into a pixel position on the overlay
+    pub fn to_screen(&self, location: &Coord<f64>) -> (f64, f64) {
+        match self.kind {
+            TransformKind::Mercator { x_range, y_range, .. } => {
+                let x = convert_range(location.x, x_range, [0.0, self.width]);
+                let y = convert_range(inverse_gudermannian(location.y), y_range, [self.height, 0.0]);
+                (x, y)
+            },
+            TransformKind::Azimuthal { center, radius, origin } => {
+                // The distance from the center is proportional to the great circle distance, in the direction of the bearing
+                let distance = geodesy::haversine_distance(&center, location) / (PI * geodesy::EARTH_RADIUS) * radius;
+                let bearing = geodesy::bearing(&center, location).to_radians();
+                (origin.0 + distance * bearing.sin(), origin.1 - distance * bearing.cos())
+            }
+        }
+    }
+
+    /// Converts a pixel position on the overlay into a location.
+    /// 
+    /// In the azimuthal projection, positions past the antipode return NaN.
+    pub fn to_geo(&self, x: f64, y: f64) -> Coord<f64> {
+        match self.kind {
+            TransformKind::Mercator { x_range, y_range, .. } => {
+                let longitude = convert_range(x, [0.0, self.width], x_range);
+                let latitude = gudermannian(convert_range(y, [self.height, 0.0], y_range));
+                geo::coord! { x: longitude, y: latitude }
+            },
+            TransformKind::Azimuthal { center, radius, origin } => {
+                let (dx, dy) = (x - origin.0, origin.1 - y);
+                let distance = dx.hypot(dy);
+                if distance > radius {
+                    return geo::coord! { x: f64::NAN, y: f64::NAN };
+                }
+
+                let bearing = dx.atan2(dy).to_degrees();
+                geodesy::destination(&center, bearing, distance / radius * PI * geodesy::EARTH_RADIUS)
+            }
+        }
+    }
+}
+
+/// The projection specific values of a [MapTransform]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransformKind {
+    /// The Web Mercator projection used by the tiles
+    Mercator {
+        /// The visible area of the map
+        geo_rect: geo::Rect<f64>,
+        /// The min and max longitude
+        x_range: [f64; 2],
+        /// The min and max latitude, projected using the inverse gudermannian function
+        y_range: [f64; 2]
+    },
+    /// The azimuthal equidistant projection, where distances and bearings from the center are true
+    Azimuthal {
+        /// The location at the center of the projection
+        center: Coord<f64>,
+        /// The distance from the center to the antipode, in pixels
+        radius: f64,
+        /// The pixel position of the center
+        origin: (f64, f64)
+    }
+}
+
+/// The projection of a map
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum_macros::EnumIter, strum_macros::Display)]
+pub enum Projection {
+    /// The Web Mercator projection used by the tiles
+    #[default]
+    Mercator,
+    /// The azimuthal equidistant projection, centered on the grid square in the station settings
+    #[strum(to_string = "Azimuthal")]
+    AzimuthalEquidistant
+}
+impl Projection {
+    /// Shows a combobox to pick the projection. Returns true if it changed.
+    pub fn combo_box(&mut self, ui: &mut Ui, id: egui::Id) -> bool {
+        let mut changed = false;
+        egui::ComboBox::from_id_source(id)
+        .selected_text(self.to_string())
+        .show_ui(ui, |ui| {
+            for projection in Self::iter() {
+                changed |= ui.selectable_value(self, projection, projection.to_string()).changed();
+            }
+        })
+        .response
+        .on_hover_text("The azimuthal projection shows the true bearing and distance from your grid square");
+        changed
+    }
+}
+
+/// Draws the range rings and bearing spokes of the azimuthal projection
+fn draw_range_rings(painter: &egui::Painter, map_rect: Rect, transform: &MapTransform, unit: &types::DistanceUnit) {
+    let (Some(radius), Some(origin)) = (transform.azimuthal_radius(), transform.azimuthal_origin()) else { return };
+    let center = map_rect.left_top() + Vec2::new(origin.0 as f32, origin.1 as f32);
+    let stroke = egui::Stroke::new(1.0, Color32::from_white_alpha(90));
+    let text_color = Color32::from_white_alpha(200);
+    let font = egui::FontId::proportional(11.0);
+
+    // Pick the smallest ring spacing that keeps the rings apart
+    let max_distance = unit.to_unit_from_meters(PI * geodesy::EARTH_RADIUS);
+    let pixels_per_unit = radius / max_distance;
+    let step = [100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0].into_iter()
+        .find(|s| s * pixels_per_unit >= 60.0)
+        .unwrap_or(5000.0);
+
+    // The range rings
+    let mut distance = step;
+    while distance < max_distance {
+        let ring_radius = (distance * pixels_per_unit) as f32;
+        painter.circle_stroke(center, ring_radius, stroke);
+        painter.text(center + Vec2::new(3.0, -ring_radius), egui::Align2::LEFT_BOTTOM, format!("{distance:.0} {}", unit.abbreviation()), font.clone(), text_color);
+        distance += step;
+    }
+
+    // The antipode
+    painter.circle_stroke(center, radius as f32, egui::Stroke::new(1.5, Color32::from_white_alpha(140)));
+
+    // The bearing spokes, every 30 degrees
+    for bearing in (0..360).step_by(30) {
+        let direction = Vec2::angled((bearing as f32 - 90.0).to_radians());
+        painter.line_segment([center, center + direction * radius as f32], stroke);
+
+        let label = match bearing {
+            0 => "N".to_string(),
+            90 => "E".to_string(),
+            180 => "S".to_string(),
+            270 => "W".to_string(),
+            b => format!("{b}\u{00B0}")
+        };
+        painter.text(center + direction * (radius as f32 + 8.0), egui::Align2::CENTER_CENTER, label, font.clone(), text_color);
+    }
+}
+
+/// Draws a scale bar at the bottom of the map, in the distance unit of the GUI.
+///
+/// The scale is measured at the center of the map, since the scale of both projections changes across the map.
+fn draw_scale_bar(painter: &egui::Painter, map_rect: Rect, transform: &MapTransform, unit: &types::DistanceUnit) {
+    const MAX_WIDTH: f64 = 120.0;
+
+    // The length of a degree of longitude at the equator, scaled to the latitude of the center of the Mercator map
+    let meters_per_degree = 2.0 * PI * geodesy::EARTH_RADIUS / 360.0;
+    let scale = match transform.projection() {
+        Projection::Mercator => {
+            let center = transform.to_geo(transform.width() as f64 / 2.0, transform.height() as f64 / 2.0);
+            meters_per_degree * center.y.to_radians().cos()
+        },
+        Projection::AzimuthalEquidistant => meters_per_degree
+    };
+    let units_per_pixel = unit.to_unit_from_meters(scale / transform.pixels_per_degree());
+    if !units_per_pixel.is_finite() || units_per_pixel <= 0.0 {
+        return;
+    }
+
+    // Pick the longest 1, 2, or 5 times a power of 10 that fits
+    let max_distance = units_per_pixel * MAX_WIDTH;
+    let magnitude = 10_f64.powf(max_distance.log10().floor());
+    let distance = [5.0, 2.0, 1.0].into_iter()
+        .map(|m| m * magnitude)
+        .find(|d| *d <= max_distance)
+        .unwrap_or(magnitude);
+    let width = (distance / units_per_pixel) as f32;
+
+    let right = map_rect.center_bottom() + Vec2::new(width / 2.0, -8.0);
+    let left = right - Vec2::new(width, 0.0);
+    let stroke = egui::Stroke::new(2.0, Color32::WHITE);
+    let shadow = egui::Stroke::new(4.0, Color32::from_black_alpha(160));
+
+    // The bar, with ticks at each end
+    let lines = [[left, right], [left, left - Vec2::new(0.0, 6.0)], [right, right - Vec2::new(0.0, 6.0)]];
+    for s in [shadow, stroke] {
+        for line in lines {
+            painter.line_segment(line, s);
+        }
+    }
+
+    // The length of the bar, with enough decimals for short distances
+    let decimals = (-magnitude.log10()).round().max(0.0) as usize;
+    let text = format!("{distance:.decimals$} {}", unit.abbreviation());
+    let galley = painter.layout_no_wrap(text, egui::FontId::proportional(11.0), Color32::WHITE);
+    let rect = egui::Align2::CENTER_BOTTOM.anchor_rect(Rect::from_min_size(map_rect.center_bottom() - Vec2::new(0.0, 12.0), galley.size()));
+    painter.rect_filled(rect.expand(1.0), 2.0, Color32::from_black_alpha(160));
+    painter.galley(rect.min, galley, Color32::WHITE);
+}
+
+/// Blends a color over a rect of the overlay image, clipping anything outside of the image.
+/// 
+/// NOTE: The overlay is uploaded as an [egui::ColorImage], which uses premultiplied alpha, so translucent colors are premultiplied here.
+/// Drawing them with `imageproc` directly would make them too bright.
+pub fn blend_rect(image: &mut OverlayImage, rect: imageproc::rect::Rect, color: image::Rgba<u8>) {
+
+    // Premultiply the color
+    let [r, g, b, a] = color.0;
+    let premultiply = |c: u8| ((c as u16 * a as u16) / 255) as u8;
+    let (r, g, b) = (premultiply(r), premultiply(g), premultiply(b));
+    let inverse_alpha = 255 - a as u16;
+
+    // Clip the rect to the image
+    let (min_x, min_y) = (rect.left().max(0) as u32, rect.top().max(0) as u32);
+    let max_x = (rect.right() + 1).clamp(0, image.width() as i32) as u32;
+    let max_y = (rect.bottom() + 1).clamp(0, image.height() as i32) as u32;
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let pixel = image.get_pixel_mut(x, y);
+            let [dr, dg, db, da] = pixel.0;
+            let over = |src: u8, dst: u8| src.saturating_add(((dst as u16 * inverse_alpha) / 255) as u8);
+            *pixel = image::Rgba([over(r, dr), over(g, dg), over(b, db), over(a, da)]);
+        }
+    }
+}
+
+/// Iterates over the square cells of the overlay image whose centers are inside of the world, with the location of each center.
+///
+/// This is used by the layers that shade the map one cell at a time. Cells past the top and bottom of the Web Mercator map are skipped.
+pub fn world_cells(transform: &MapTransform, cell_size: u32) -> impl Iterator<Item = (imageproc::rect::Rect, Coord<f64>)> {
+    let transform = *transform;
+    let half_cell = cell_size as f64 / 2.0;
+
+    (0..transform.height()).step_by(cell_size as usize)
+        .flat_map(move |y| (0..transform.width()).step_by(cell_size as usize).map(move |x| (x, y)))
+        .filter_map(move |(x, y)| {
+            let location = transform.to_geo(x as f64 + half_cell, y as f64 + half_cell);
+            let outside = !(-180.0..=180.0).contains(&location.x) || (transform.projection() == Projection::Mercator && location.y.abs() > MAX_LATITUDE);
+            (!outside).then(|| (imageproc::rect::Rect::at(x as i32, y as i32).of_size(cell_size, cell_size), location))
+        })
+}
+
+/// Fills a polygon on the overlay image, using the even-odd rule so any extra rings are holes. The color is blended like [blend_rect()].
+/// 
+/// The polygon is skipped if any point isn't finite.
+pub fn fill_polygon(image: &mut OverlayImage, rings: &[Vec<(f64, f64)>], color: image::Rgba<u8>) {
+    let points = || rings.iter().flatten();
+    if !points().all(|p| p.0.is_finite() && p.1.is_finite()) {
+        return;
+    }
+
+    // Only scan the rows that the polygon covers
+    let min_y = points().map(|p| p.1).fold(f64::INFINITY, f64::min).max(0.0);
+    let max_y = points().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max).min(image.height() as f64 - 1.0);
+    if min_y > max_y {
+        return;
+    }
+
+    let mut crossings = Vec::new();
+    for y in min_y as u32..=max_y as u32 {
+        // Find where the edges cross the middle of the row
+        let scan_y = y as f64 + 0.5;
+        crossings.clear();
+        for ring in rings {
+            for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+                if (a.1 <= scan_y) != (b.1 <= scan_y) {
+                    crossings.push(a.0 + (scan_y - a.1) / (b.1 - a.1) * (b.0 - a.0));
+                }
+            }
+        }
+        crossings.sort_by(f64::total_cmp);
+
+        // Fill between each pair of crossings
+        for pair in crossings.chunks_exact(2) {
+            let start = pair[0].round().max(0.0) as i32;
+            let end = pair[1].round().min(image.width() as f64) as i32;
+            if end > start {
+                blend_rect(image, imageproc::rect::Rect::at(start, y as i32).of_size((end - start) as u32, 1), color);
+            }
+        }
+    }
+}
+
+/// Draws an antialiased line onto the overlay image, clipping the parts that are outside of the image.
+/// 
+/// The line is skipped if either end isn't finite (e.g. a location past the antipode in the azimuthal projection).
+/// The color should be premultiplied, like [blend_rect()] does.
+pub fn draw_line(image: &mut OverlayImage, from: (f64, f64), to: (f64, f64), color: image::Rgba<u8>) {
+    if !(from.0.is_finite() && from.1.is_finite() && to.0.is_finite() && to.1.is_finite()) {
+        return;
+    }
+
+    // Clip the line to the image using the Liang-Barsky algorithm, so lines that go far off screen don't take forever to draw
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [(-dx, from.0), (dx, width - 1.0 - from.0), (-dy, from.1), (dy, height - 1.0 - from.1)] {
+        if p == 0.0 {
+            // The line is parallel to this edge and outside of it
+            if q < 0.0 {
+                return;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return;
+    }
+
+    imageproc::drawing::draw_antialiased_line_segment_mut(
+        image,
+        ((from.0 + t0 * dx) as i32, (from.1 + t0 * dy) as i32),
+        ((from.0 + t1 * dx) as i32, (from.1 + t1 * dy) as i32),
+        color,
+        imageproc::pixelops::interpolate
+    );
 }
 
 /// A dummy map marker used for debugging and development.
@@ -1292,6 +3357,11 @@ impl MapMarkerTrait for DummyMapMarker {
 }
 
 
+/// The tile zoom level that roughly matches the scale at the center of the azimuthal projection
+fn azimuthal_tile_zoom(transform: &MapTransform) -> u8 {
+    (transform.azimuthal_radius().unwrap_or_default() / 128.0).log2().floor().clamp(1.0, 4.0) as u8
+}
+
 /// Returns the maximum number of tiles in either the X or Y axis on the map at the provided zoom level.
 /// 
 /// NOTE: Because the map is square, the X and Y axis share the same max value, so all you have to do it provide a zoom value.
@@ -1311,3 +3381,179 @@ fn inverse_gudermannian(value: f64) -> f64 {
     let sin = f64::sin(value * (PI / 180.0) * sign);
     sign * (f64::ln((1.0 + sin) / (1.0 - sin)) / 2.0)
 }
+
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+    use super::*;
+
+    /// The number of markers used by the benchmarks
+    const N_MARKERS: u64 = 50_000;
+
+    /// A 1024x512 pixel transform of most of the world
+    fn world_transform() -> MapTransform {
+        MapTransform::mercator(
+            Rect::from_min_size(egui::Pos2::ZERO, Vec2::new(1024.0, 512.0)),
+            geo::Rect::new(geo::coord! { x: -180.0, y: -85.0 }, geo::coord! { x: 180.0, y: 85.0 })
+        )
+    }
+
+    /// A transform of a small area around Europe
+    fn europe_transform() -> MapTransform {
+        MapTransform::mercator(
+            Rect::from_min_size(egui::Pos2::ZERO, Vec2::new(1024.0, 512.0)),
+            geo::Rect::new(geo::coord! { x: -10.0, y: 35.0 }, geo::coord! { x: 30.0, y: 60.0 })
+        )
+    }
+
+    /// A layer of randomly placed markers, which are the same for the same seed
+    fn random_layer(n_markers: u64, seed: u64) -> MarkerLayer<DummyMapMarker> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut layer = MarkerLayer::new("Random");
+        layer.markers = (0..n_markers)
+            .map(|id| DummyMapMarker { id, location: geo::coord! { x: rng.gen_range(-180.0..180.0), y: rng.gen_range(-85.0..85.0) } })
+            .collect();
+        layer
+    }
+
+    /// Places the markers of the layer with the transform, and returns the overlay manager
+    fn place(layer: &mut MarkerLayer<DummyMapMarker>, transform: &MapTransform, clustering: bool) -> MapOverlayManager {
+        let mut overlay_manager = MapOverlayManager::new(&Context::default());
+        let layers: [&mut dyn MapLayer; 1] = [layer];
+        overlay_manager.place_markers(transform, &layers, &[0], None, clustering, |_| Color32::RED);
+        overlay_manager
+    }
+
+    /// The number of markers that are drawn, counting the markers in the clusters
+    fn drawn_count(overlay_manager: &MapOverlayManager) -> usize {
+        overlay_manager.drawn.iter()
+            .map(|drawn| match drawn.kind {
+                DrawnMarkerKind::Marker(_) => 1,
+                DrawnMarkerKind::Cluster(count) => count
+            })
+            .sum()
+    }
+
+    #[test]
+    fn markers_outside_of_view_are_culled() {
+        let transform = europe_transform();
+        let mut layer = random_layer(5_000, 1);
+        let visible: Vec<u64> = layer.markers.iter().filter(|m| transform.is_visible(&m.location)).map(|m| m.id).collect();
+        assert!(!visible.is_empty() && visible.len() < layer.markers.len());
+
+        let overlay_manager = place(&mut layer, &transform, false);
+        let drawn: Vec<u64> = overlay_manager.drawn.iter()
+            .map(|drawn| match drawn.kind {
+                DrawnMarkerKind::Marker(index) => layer.markers[index].id,
+                DrawnMarkerKind::Cluster(_) => panic!("Markers shouldn't be clustered")
+            })
+            .collect();
+        assert_eq!(drawn, visible);
+        assert_eq!(overlay_manager.index.size(), visible.len());
+    }
+
+    #[test]
+    fn hovered_markers_are_found_in_index() {
+        let transform = world_transform();
+        let mut layer = MarkerLayer::new("Markers");
+        layer.markers = vec![
+            DummyMapMarker { id: 0, location: geo::coord! { x: -111.866, y: 40.73 } },
+            DummyMapMarker { id: 1, location: geo::coord! { x: 151.21, y: -33.87 } }
+        ];
+        let overlay_manager = place(&mut layer, &transform, false);
+
+        for (index, marker) in layer.markers.iter().enumerate() {
+            let (x, y) = transform.to_screen(&marker.location);
+            let hovered = overlay_manager.hovered_marker(Vec2::new(x as f32, y as f32)).expect("The marker should be hovered");
+            assert!(matches!(hovered.kind, DrawnMarkerKind::Marker(i) if i == index));
+        }
+        assert!(overlay_manager.hovered_marker(Vec2::new(512.0, 256.0)).is_none());
+    }
+
+    #[test]
+    fn clusters_contain_every_visible_marker() {
+        let transform = world_transform();
+        let mut layer = random_layer(5_000, 2);
+        let visible = layer.markers.iter().filter(|m| transform.is_visible(&m.location)).count();
+
+        let clustered = place(&mut layer, &transform, true);
+        assert!(clustered.drawn.iter().any(|drawn| matches!(drawn.kind, DrawnMarkerKind::Cluster(_))));
+        assert!(clustered.drawn.len() < visible);
+        assert_eq!(drawn_count(&clustered), visible);
+        assert_eq!(clustered.index.size(), clustered.drawn.len());
+
+        let unclustered = place(&mut layer, &transform, false);
+        assert_eq!(unclustered.drawn.len(), visible);
+        assert_eq!(drawn_count(&unclustered), visible);
+    }
+
+    #[test]
+    fn clusters_are_drawn_in_order() {
+        let transform = world_transform();
+        let mut layer = random_layer(5_000, 3);
+        let positions = |overlay_manager: &MapOverlayManager| overlay_manager.drawn.iter().map(|drawn| drawn.position).collect::<Vec<_>>();
+
+        let first = positions(&place(&mut layer, &transform, true));
+        let second = positions(&place(&mut layer, &transform, true));
+        assert_eq!(first, second);
+
+        // The cells are sorted by row, and then column
+        let cells: Vec<(i32, i32)> = first.iter()
+            .map(|p| ((p[1] / MapOverlayManager::CLUSTER_CELL_SIZE).floor() as i32, (p[0] / MapOverlayManager::CLUSTER_CELL_SIZE).floor() as i32))
+            .collect();
+        assert!(cells.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn layer_hash_changes_with_markers() {
+        let mut layer = random_layer(100, 4);
+        let hash = |layer: &mut MarkerLayer<DummyMapMarker>| hash_layers(&[layer]);
+        let original = hash(&mut layer);
+        assert_eq!(hash(&mut layer), original);
+
+        // Reading the markers doesn't change the hash
+        assert_eq!(layer.markers().len(), 100);
+        assert_eq!(hash(&mut layer), original);
+
+        // Moving a marker changes the hash
+        layer.markers_mut()[10].location.x += 1.0;
+        let moved = hash(&mut layer);
+        assert_ne!(moved, original);
+
+        // Replacing a marker with a different one with the same count changes the hash
+        layer.markers_mut()[20].id = 1_000;
+        assert_ne!(hash(&mut layer), moved);
+    }
+
+    #[bench]
+    fn bench_update_markers_clustered(b: &mut test::Bencher) {
+        let transform = world_transform();
+        let mut layer = random_layer(N_MARKERS, 5);
+        let mut overlay_manager = MapOverlayManager::new(&Context::default());
+        let layers: [&mut dyn MapLayer; 1] = [&mut layer];
+        b.iter(|| overlay_manager.place_markers(&transform, &layers, &[0], None, true, |_| Color32::RED));
+    }
+
+    #[bench]
+    fn bench_update_markers_unclustered(b: &mut test::Bencher) {
+        let transform = world_transform();
+        let mut layer = random_layer(N_MARKERS, 5);
+        let mut overlay_manager = MapOverlayManager::new(&Context::default());
+        let layers: [&mut dyn MapLayer; 1] = [&mut layer];
+        b.iter(|| overlay_manager.place_markers(&transform, &layers, &[0], None, false, |_| Color32::RED));
+    }
+
+    #[bench]
+    fn bench_hovered_marker(b: &mut test::Bencher) {
+        let transform = world_transform();
+        let mut layer = random_layer(N_MARKERS, 5);
+        let overlay_manager = place(&mut layer, &transform, false);
+        let mut rng = SmallRng::seed_from_u64(6);
+        b.iter(|| {
+            let hover_pos = Vec2::new(rng.gen_range(0.0..1024.0), rng.gen_range(0.0..512.0));
+            overlay_manager.hovered_marker(hover_pos).is_some()
+        });
+    }
+}
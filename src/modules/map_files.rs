@@ -0,0 +1,705 @@
+//
+// Exports the markers of a map to KML and GeoJSON files, and imports KML and GeoJSON files as overlay layers
+//
+
+use std::{fmt::Write, fs, hash::{DefaultHasher, Hash, Hasher}, path::{Path, PathBuf}};
+use anyhow::Result;
+use egui::Ui;
+use geo::Coord;
+use poll_promise::Promise;
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+use crate::GuiConfig;
+use super::map::{FeatureLayer, MapLayer, MapLine, MapMarkerTrait, MapPolygon, MapTransform, MarkerShape, OverlayImage};
+
+
+/// The color of imported markers and lines that don't have a color of their own
+const DEFAULT_COLOR: [u8; 3] = [255, 140, 0];
+/// The opacity of imported polygons that don't have an opacity of their own
+const DEFAULT_FILL_OPACITY: f64 = 0.25;
+/// The z-order of imported layers, so they're drawn over the greyline and grids
+const OVERLAY_Z_ORDER: i32 = 30;
+
+
+/// The format of an exported or imported file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Kml,
+    GeoJson
+}
+impl FileFormat {
+    /// The name of the format
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kml => "KML",
+            Self::GeoJson => "GeoJSON"
+        }
+    }
+
+    /// Guesses the format of a file from its extension (`.kml`, `.geojson`, or `.json`)
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+        match extension.as_str() {
+            "kml" => Ok(Self::Kml),
+            "geojson" | "json" => Ok(Self::GeoJson),
+            _ => Err(Error::UnknownFormat(extension))
+        }
+    }
+}
+
+
+/// A marker that was exported from a layer
+struct ExportedMarker {
+    /// The label of the marker, or its ID if it doesn't have one
+    name: String,
+    location: Coord,
+    /// The RGB color of the marker
+    color: [u8; 3],
+    properties: Vec<(String, String)>
+}
+
+/// Saves the markers of the visible layers to a KML or GeoJSON file, with the properties of each marker. Each layer is saved as a folder in KML files.
+///
+/// The markers are copied from the layers, and the file is written on a background thread. The promise returns the number of markers that were saved.
+pub fn export_markers(path: PathBuf, format: FileFormat, layers: &[&mut dyn MapLayer], config: &mut GuiConfig) -> Promise<Result<usize>> {
+
+    // Get the markers of each visible layer
+    let layers: Vec<(String, Vec<ExportedMarker>)> = layers.iter()
+        .filter(|layer| layer.is_visible())
+        .map(|layer| {
+            let markers = (0..layer.marker_count())
+                .filter_map(|i| layer.marker(i))
+                .map(|marker| {
+                    let [r, g, b, _] = marker.color(config).0;
+                    ExportedMarker {
+                        name: marker.label().map(str::to_string).unwrap_or_else(|| marker.id().to_string()),
+                        location: *marker.location(),
+                        color: [r, g, b],
+                        properties: marker.properties().into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+                    }
+                })
+                .collect();
+            (layer.name().to_string(), markers)
+        })
+        .collect();
+
+    Promise::spawn_thread("map_export_markers", move || {
+        let n_markers = layers.iter().map(|(_, markers)| markers.len()).sum();
+        let text = match format {
+            FileFormat::Kml => markers_to_kml(&layers),
+            FileFormat::GeoJson => markers_to_geojson(&layers)
+        };
+        fs::write(path, text).map_err(Error::Io)?;
+
+        Ok(n_markers)
+    })
+}
+
+/// Creates a GeoJSON feature collection of the markers. The name of each marker's layer is stored in its `layer` property.
+fn markers_to_geojson(layers: &[(String, Vec<ExportedMarker>)]) -> String {
+    let features: Vec<Value> = layers.iter()
+        .flat_map(|(layer, markers)| markers.iter().map(move |marker| (layer, marker)))
+        .map(|(layer, marker)| {
+            let mut properties = Map::new();
+            properties.insert("name".to_string(), json!(marker.name));
+            properties.insert("layer".to_string(), json!(layer));
+            // The color of the marker, from the simplestyle spec
+            properties.insert("marker-color".to_string(), json!(format!("#{:02x}{:02x}{:02x}", marker.color[0], marker.color[1], marker.color[2])));
+            for (key, value) in &marker.properties {
+                properties.insert(key.clone(), json!(value));
+            }
+
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [marker.location.x, marker.location.y]
+                },
+                "properties": properties
+            })
+        })
+        .collect();
+
+    let collection = json!({
+        "type": "FeatureCollection",
+        "features": features
+    });
+    serde_json::to_string_pretty(&collection).unwrap_or_default()
+}
+
+/// Creates a KML document of the markers, with a folder for each layer. The properties are stored in the extended data of each placemark.
+fn markers_to_kml(layers: &[(String, Vec<ExportedMarker>)]) -> String {
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+
+    for (layer, markers) in layers {
+        let _ = writeln!(kml, "<Folder>\n<name>{}</name>", escape_xml(layer));
+        for marker in markers {
+            // KML colors are in the order alpha, blue, green, red
+            let [r, g, b] = marker.color;
+            let _ = writeln!(kml, "<Placemark>\n<name>{}</name>", escape_xml(&marker.name));
+            let _ = writeln!(kml, "<Style><IconStyle><color>ff{b:02x}{g:02x}{r:02x}</color></IconStyle></Style>");
+
+            if !marker.properties.is_empty() {
+                kml.push_str("<ExtendedData>\n");
+                for (key, value) in &marker.properties {
+                    let _ = writeln!(kml, "<Data name=\"{}\"><value>{}</value></Data>", escape_xml(key), escape_xml(value));
+                }
+                kml.push_str("</ExtendedData>\n");
+            }
+
+            let _ = writeln!(kml, "<Point><coordinates>{},{}</coordinates></Point>\n</Placemark>", marker.location.x, marker.location.y);
+        }
+        kml.push_str("</Folder>\n");
+    }
+
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+/// Escapes the characters that can't be used in XML text and attributes
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+
+/// Loads a KML or GeoJSON file as an overlay layer, which is named after the file.
+///
+/// Points are shown as markers, and lines and polygons are drawn onto the map. GeoJSON features can be styled with the
+/// `marker-color`, `stroke`, `fill`, and `fill-opacity` properties from the simplestyle spec.
+/// The file is read and parsed on a background thread.
+pub fn import_overlay(path: PathBuf) -> Promise<Result<OverlayLayer>> {
+    Promise::spawn_thread("map_import_overlay", move || read_overlay(&path))
+}
+
+/// Reads and parses a KML or GeoJSON file as an overlay layer
+fn read_overlay(path: &Path) -> Result<OverlayLayer> {
+    let format = FileFormat::from_path(path)?;
+    let text = fs::read_to_string(path).map_err(Error::Io)?;
+
+    let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "Overlay".to_string());
+    let mut layer = OverlayLayer::new(name);
+    match format {
+        FileFormat::Kml => read_kml(&text, &mut layer)?,
+        FileFormat::GeoJson => {
+            let value: Value = serde_json::from_str(&text).map_err(Error::InvalidGeoJson)?;
+            read_geojson(&value, &Map::new(), &mut layer)?;
+        }
+    }
+
+    if layer.markers.is_empty() && layer.features.lines.is_empty() && layer.features.polygons.is_empty() {
+        return Err(Error::Empty.into());
+    }
+    Ok(layer)
+}
+
+/// Adds the features of a GeoJSON object to the layer. The properties are the properties of the feature that the object belongs to.
+fn read_geojson(value: &Value, properties: &Map<String, Value>, layer: &mut OverlayLayer) -> Result<(), Error> {
+    match value["type"].as_str() {
+        Some("FeatureCollection") => {
+            for feature in value["features"].as_array().ok_or(Error::InvalidGeometry)? {
+                read_geojson(feature, properties, layer)?;
+            }
+        },
+        Some("Feature") => {
+            // Features without a geometry are allowed, but there's nothing to show
+            let properties = value["properties"].as_object().cloned().unwrap_or_default();
+            if !value["geometry"].is_null() {
+                read_geojson(&value["geometry"], &properties, layer)?;
+            }
+        },
+        Some("GeometryCollection") => {
+            for geometry in value["geometries"].as_array().ok_or(Error::InvalidGeometry)? {
+                read_geojson(geometry, properties, layer)?;
+            }
+        },
+        Some(kind) => {
+            let style = Style::from_properties(properties);
+            let coordinates = &value["coordinates"];
+            match kind {
+                "Point" => layer.add_marker(position(coordinates)?, &style),
+                "MultiPoint" => {
+                    for point in positions(coordinates)? {
+                        layer.add_marker(point, &style);
+                    }
+                },
+                "LineString" => layer.add_line(positions(coordinates)?, &style),
+                "MultiLineString" => {
+                    for line in coordinates.as_array().ok_or(Error::InvalidGeometry)? {
+                        layer.add_line(positions(line)?, &style);
+                    }
+                },
+                "Polygon" => layer.add_polygon(rings(coordinates)?, &style),
+                "MultiPolygon" => {
+                    for polygon in coordinates.as_array().ok_or(Error::InvalidGeometry)? {
+                        layer.add_polygon(rings(polygon)?, &style);
+                    }
+                },
+                _ => return Err(Error::UnsupportedGeometry(kind.to_string()))
+            }
+        },
+        None => return Err(Error::InvalidGeometry)
+    }
+    Ok(())
+}
+
+/// Reads a GeoJSON position, which is a longitude, latitude, and optional altitude
+fn position(value: &Value) -> Result<Coord, Error> {
+    match value.as_array().map(|v| v.as_slice()) {
+        Some([x, y, ..]) => Ok(geo::coord! {
+            x: x.as_f64().ok_or(Error::InvalidGeometry)?,
+            y: y.as_f64().ok_or(Error::InvalidGeometry)?
+        }),
+        _ => Err(Error::InvalidGeometry)
+    }
+}
+
+/// Reads an array of GeoJSON positions
+fn positions(value: &Value) -> Result<Vec<Coord>, Error> {
+    value.as_array().ok_or(Error::InvalidGeometry)?.iter().map(position).collect()
+}
+
+/// Reads the rings of a GeoJSON polygon. The first ring is the exterior, and the rest are holes.
+fn rings(value: &Value) -> Result<Vec<Vec<Coord>>, Error> {
+    value.as_array().ok_or(Error::InvalidGeometry)?.iter().map(positions).collect()
+}
+
+/// Adds the placemarks of a KML document to the layer. Points, lines, and polygons are read, including those inside of multi-geometries.
+fn read_kml(text: &str, layer: &mut OverlayLayer) -> Result<(), Error> {
+    let document = roxmltree::Document::parse(text).map_err(Error::InvalidKml)?;
+    let style = Style::default();
+
+    for placemark in document.descendants().filter(|n| n.has_tag_name("Placemark")) {
+
+        // The name, description, and extended data of the placemark
+        let mut properties = Vec::new();
+        if let Some(name) = child_text(placemark, "name") {
+            properties.push(("name".to_string(), name));
+        }
+        if let Some(description) = child_text(placemark, "description") {
+            properties.push(("description".to_string(), description));
+        }
+        for data in placemark.descendants().filter(|n| n.has_tag_name("Data") || n.has_tag_name("SimpleData")) {
+            let Some(name) = data.attribute("name") else { continue };
+            // <Data> has a <value> child, and <SimpleData> has the value as its text
+            let value = child_text(data, "value").or_else(|| data.text().map(|t| t.trim().to_string())).unwrap_or_default();
+            properties.push((name.to_string(), value));
+        }
+
+        for geometry in placemark.descendants() {
+            match geometry.tag_name().name() {
+                "Point" => {
+                    let point = kml_coordinates(geometry)?.first().copied().ok_or(Error::InvalidGeometry)?;
+                    layer.add_kml_marker(point, &properties);
+                },
+                "LineString" => layer.add_line(kml_coordinates(geometry)?, &style),
+                "Polygon" => {
+                    // The outer boundary is the exterior, and the inner boundaries are holes
+                    let mut rings = Vec::new();
+                    for boundary in ["outerBoundaryIs", "innerBoundaryIs"] {
+                        for ring in geometry.children().filter(|c| c.has_tag_name(boundary)) {
+                            rings.push(kml_coordinates(ring)?);
+                        }
+                    }
+                    layer.add_polygon(rings, &style);
+                },
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Gets the trimmed text of the first child element with the tag name
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|c| c.has_tag_name(name))
+        .and_then(|c| c.text())
+        .map(|t| t.trim().to_string())
+}
+
+/// Reads the coordinates of a KML geometry, which are tuples of a longitude, latitude, and optional altitude separated by whitespace
+/// (e.g. `-117.9,33.6,0 -118.0,33.7,0`)
+fn kml_coordinates(node: roxmltree::Node) -> Result<Vec<Coord>, Error> {
+    let text = node.descendants()
+        .find(|c| c.has_tag_name("coordinates"))
+        .and_then(|c| c.text())
+        .ok_or(Error::InvalidGeometry)?;
+
+    text.split_whitespace()
+        .map(|tuple| {
+            let mut values = tuple.split(',').map(|v| v.trim().parse::<f64>());
+            match (values.next(), values.next()) {
+                (Some(Ok(longitude)), Some(Ok(latitude))) => Ok(geo::coord! { x: longitude, y: latitude }),
+                _ => Err(Error::InvalidGeometry)
+            }
+        })
+        .collect()
+}
+
+
+/// The colors of an imported feature
+struct Style {
+    /// The name of the feature, if it has one
+    name: Option<String>,
+    /// The properties of the feature, which are shown when its marker is hovered
+    properties: Vec<(String, String)>,
+    marker: [u8; 3],
+    stroke: [u8; 3],
+    fill: [u8; 3],
+    /// The opacity of polygons, from 0 to 1
+    fill_opacity: f64
+}
+impl Style {
+    /// Reads the name and simplestyle properties of a GeoJSON feature
+    fn from_properties(properties: &Map<String, Value>) -> Self {
+        let color = |key: &str| properties.get(key).and_then(|v| v.as_str()).and_then(parse_hex_color);
+        let name = ["name", "title", "callsign"].into_iter()
+            .find_map(|key| properties.get(key).and_then(|v| v.as_str()))
+            .map(str::to_string);
+
+        Self {
+            name,
+            properties: properties.iter()
+                .map(|(key, value)| (key.clone(), value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string())))
+                .collect(),
+            marker: color("marker-color").unwrap_or(DEFAULT_COLOR),
+            stroke: color("stroke").unwrap_or(DEFAULT_COLOR),
+            fill: color("fill").unwrap_or(DEFAULT_COLOR),
+            fill_opacity: properties.get("fill-opacity").and_then(|v| v.as_f64()).unwrap_or(DEFAULT_FILL_OPACITY).clamp(0.0, 1.0)
+        }
+    }
+}
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            name: None,
+            properties: Vec::new(),
+            marker: DEFAULT_COLOR,
+            stroke: DEFAULT_COLOR,
+            fill: DEFAULT_COLOR,
+            fill_opacity: DEFAULT_FILL_OPACITY
+        }
+    }
+}
+
+/// Parses a hex color like `#ff8c00`, `ff8c00`, or `#f80`
+fn parse_hex_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.trim().trim_start_matches('#');
+    let digit = |i: usize, len: usize| u8::from_str_radix(hex.get(i..i + len)?, 16).ok();
+    match hex.len() {
+        6 => Some([digit(0, 2)?, digit(2, 2)?, digit(4, 2)?]),
+        3 => Some([digit(0, 1)? * 17, digit(1, 1)? * 17, digit(2, 1)? * 17]),
+        _ => None
+    }
+}
+
+
+/// A layer of points, lines, and polygons imported from a KML or GeoJSON file, such as club member locations or a planned rover route
+#[derive(Debug)]
+pub struct OverlayLayer {
+    /// The lines and polygons, which are drawn onto the map overlay
+    features: FeatureLayer,
+    /// The points, which are shown as markers
+    markers: Vec<OverlayMarker>
+}
+impl OverlayLayer {
+    /// Creates a visible layer without any features
+    fn new(name: String) -> Self {
+        let mut features = FeatureLayer::new(name);
+        features.z_order = OVERLAY_Z_ORDER;
+        Self {
+            features,
+            markers: Vec::new()
+        }
+    }
+
+    /// Adds a point from a GeoJSON file
+    fn add_marker(&mut self, location: Coord, style: &Style) {
+        self.markers.push(OverlayMarker::new(self.markers.len(), location, style.name.clone(), style.properties.clone(), style.marker));
+    }
+
+    /// Adds a point from a KML file. The name of the placemark is the name of the marker.
+    fn add_kml_marker(&mut self, location: Coord, properties: &[(String, String)]) {
+        let name = properties.iter().find(|(k, _)| k == "name").map(|(_, v)| v.clone());
+        self.markers.push(OverlayMarker::new(self.markers.len(), location, name, properties.to_vec(), DEFAULT_COLOR));
+    }
+
+    fn add_line(&mut self, points: Vec<Coord>, style: &Style) {
+        let [r, g, b] = style.stroke;
+        self.features.lines.push(MapLine { points, color: image::Rgba([r, g, b, 255]) });
+    }
+
+    /// Adds a polygon from its rings. The first ring is the exterior, and the rest are holes.
+    fn add_polygon(&mut self, mut rings: Vec<Vec<Coord>>, style: &Style) {
+        if rings.is_empty() {
+            return;
+        }
+        let exterior = geo::LineString::new(rings.remove(0));
+        let interiors = rings.into_iter().map(geo::LineString::new).collect();
+
+        let [r, g, b] = style.fill;
+        let [sr, sg, sb] = style.stroke;
+        self.features.polygons.push(MapPolygon {
+            polygon: geo::Polygon::new(exterior, interiors),
+            fill: image::Rgba([r, g, b, (style.fill_opacity * 255.0) as u8]),
+            outline: Some(image::Rgba([sr, sg, sb, 255]))
+        });
+    }
+}
+impl MapLayer for OverlayLayer {
+    fn name(&self) -> &str {
+        self.features.name()
+    }
+
+    fn is_visible(&self) -> bool {
+        self.features.visible
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.features.visible = visible;
+    }
+
+    fn z_order(&self) -> i32 {
+        self.features.z_order
+    }
+
+    fn draw(&mut self, image: &mut OverlayImage, transform: &MapTransform, config: &mut GuiConfig) {
+        self.features.draw(image, transform, config);
+    }
+
+    fn ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) -> bool {
+        ui.weak(format!("{} points, {} lines, {} polygons", self.markers.len(), self.features.lines.len(), self.features.polygons.len()));
+        false
+    }
+
+    fn marker_count(&self) -> usize {
+        self.markers.len()
+    }
+
+    fn marker(&self, index: usize) -> Option<&dyn MapMarkerTrait> {
+        self.markers.get(index).map(|m| m as &dyn MapMarkerTrait)
+    }
+
+    fn marker_mut(&mut self, index: usize) -> Option<&mut dyn MapMarkerTrait> {
+        self.markers.get_mut(index).map(|m| m as &mut dyn MapMarkerTrait)
+    }
+}
+
+/// A point imported from a KML or GeoJSON file
+#[derive(Debug, Clone)]
+struct OverlayMarker {
+    /// The ID of the map marker. This is a hash of the index and location of the point.
+    id: u64,
+    location: Coord,
+    /// The name of the point, which is shown as its label
+    name: Option<String>,
+    /// The properties of the point from the file
+    properties: Vec<(String, String)>,
+    /// The RGB color of the marker
+    color: [u8; 3]
+}
+impl OverlayMarker {
+    fn new(index: usize, location: Coord, name: Option<String>, properties: Vec<(String, String)>, color: [u8; 3]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        index.hash(&mut hasher);
+        location.x.to_bits().hash(&mut hasher);
+        location.y.to_bits().hash(&mut hasher);
+
+        Self {
+            id: hasher.finish(),
+            location,
+            name,
+            properties,
+            color
+        }
+    }
+}
+impl MapMarkerTrait for OverlayMarker {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        &self.location
+    }
+
+    fn hovered_ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) {
+        if let Some(name) = &self.name {
+            ui.heading(name);
+        }
+        for (key, value) in self.properties.iter().filter(|(k, _)| k != "name") {
+            ui.label(format!("{key}: {value}"));
+        }
+        ui.label(format!("Location: {:.4}, {:.4}", self.location.y, self.location.x));
+    }
+
+    fn selected_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        self.hovered_ui(ui, config);
+    }
+
+    fn color(&self, _config: &mut GuiConfig) -> image::Rgba<u8> {
+        let [r, g, b] = self.color;
+        image::Rgba([r, g, b, 255])
+    }
+
+    fn shape(&self) -> MarkerShape {
+        MarkerShape::Circle
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        self.properties.iter().map(|(k, v)| (k.as_str(), v.clone())).collect()
+    }
+}
+
+
+/// Errors regarding exporting and importing map files
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read or write the file: {0}")]
+    Io(std::io::Error),
+    #[error("Unknown file type '.{0}', expected .kml, .geojson, or .json")]
+    UnknownFormat(String),
+    #[error("Invalid GeoJSON file: {0}")]
+    InvalidGeoJson(serde_json::Error),
+    #[error("Invalid KML file: {0}")]
+    InvalidKml(roxmltree::Error),
+    #[error("The file has a geometry with invalid coordinates")]
+    InvalidGeometry,
+    #[error("Unsupported GeoJSON geometry '{0}'")]
+    UnsupportedGeometry(String),
+    #[error("The file doesn't have any points, lines, or polygons")]
+    Empty
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two layers of markers, with names and properties that need to be escaped
+    fn layers() -> Vec<(String, Vec<ExportedMarker>)> {
+        vec![
+            ("Contacts".to_string(), vec![
+                ExportedMarker {
+                    name: "W1AW".to_string(),
+                    location: geo::coord! { x: -72.7275, y: 41.7148 },
+                    color: [0, 200, 83],
+                    properties: vec![("Band".to_string(), "20m".to_string()), ("Note".to_string(), "Bob & <Alice> \"QRP\"".to_string())]
+                },
+                ExportedMarker {
+                    name: "VK2ABC".to_string(),
+                    location: geo::coord! { x: 151.2093, y: -33.8688 },
+                    color: [255, 0, 0],
+                    properties: Vec::new()
+                }
+            ]),
+            ("Spots & Skimmers".to_string(), vec![
+                ExportedMarker {
+                    name: "JA1XYZ".to_string(),
+                    location: geo::coord! { x: 139.6917, y: 35.6895 },
+                    color: [16, 32, 48],
+                    properties: vec![("SNR".to_string(), "-12 dB".to_string())]
+                }
+            ])
+        ]
+    }
+
+    /// Gets the value of a property of an imported marker
+    fn property<'a>(marker: &'a OverlayMarker, key: &str) -> Option<&'a str> {
+        marker.properties.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn kml_round_trips() {
+        let kml = markers_to_kml(&layers());
+        let mut layer = OverlayLayer::new("Markers".to_string());
+        read_kml(&kml, &mut layer).unwrap();
+
+        let exported: Vec<ExportedMarker> = layers().into_iter().flat_map(|(_, markers)| markers).collect();
+        assert_eq!(layer.markers.len(), exported.len());
+        for (imported, exported) in layer.markers.iter().zip(&exported) {
+            assert_eq!(imported.name.as_deref(), Some(exported.name.as_str()));
+            assert_eq!(imported.location, exported.location);
+            for (key, value) in &exported.properties {
+                assert_eq!(property(imported, key), Some(value.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn geojson_round_trips() {
+        let geojson = markers_to_geojson(&layers());
+        let value: Value = serde_json::from_str(&geojson).unwrap();
+        let mut layer = OverlayLayer::new("Markers".to_string());
+        read_geojson(&value, &Map::new(), &mut layer).unwrap();
+
+        let exported: Vec<(String, ExportedMarker)> = layers().into_iter()
+            .flat_map(|(layer, markers)| markers.into_iter().map(move |marker| (layer.clone(), marker)))
+            .collect();
+        assert_eq!(layer.markers.len(), exported.len());
+        for (imported, (layer, exported)) in layer.markers.iter().zip(&exported) {
+            assert_eq!(imported.name.as_deref(), Some(exported.name.as_str()));
+            assert_eq!(imported.location, exported.location);
+            assert_eq!(imported.color, exported.color);
+            assert_eq!(property(imported, "layer"), Some(layer.as_str()));
+            for (key, value) in &exported.properties {
+                assert_eq!(property(imported, key), Some(value.as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn geojson_lines_and_polygons_are_imported() {
+        let value = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "LineString", "coordinates": [[-118.0, 33.7], [-117.9, 33.6, 12.0]] },
+                    "properties": { "stroke": "#f80" }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]] },
+                    "properties": { "fill": "#0000ff", "fill-opacity": 0.5 }
+                },
+                { "type": "Feature", "geometry": null, "properties": {} }
+            ]
+        });
+        let mut layer = OverlayLayer::new("Features".to_string());
+        read_geojson(&value, &Map::new(), &mut layer).unwrap();
+
+        assert!(layer.markers.is_empty());
+        assert_eq!(layer.features.lines.len(), 1);
+        assert_eq!(layer.features.lines[0].points.len(), 2);
+        assert_eq!(layer.features.lines[0].color, image::Rgba([255, 136, 0, 255]));
+        assert_eq!(layer.features.polygons.len(), 1);
+        assert_eq!(layer.features.polygons[0].fill, image::Rgba([0, 0, 255, 127]));
+
+        let invalid = json!({ "type": "Point", "coordinates": ["north", 1.0] });
+        assert!(matches!(read_geojson(&invalid, &Map::new(), &mut layer), Err(Error::InvalidGeometry)));
+    }
+
+    #[test]
+    fn formats_and_colors_are_parsed() {
+        assert_eq!(FileFormat::from_path(Path::new("members.KML")).unwrap(), FileFormat::Kml);
+        assert_eq!(FileFormat::from_path(Path::new("route.geojson")).unwrap(), FileFormat::GeoJson);
+        assert_eq!(FileFormat::from_path(Path::new("route.json")).unwrap(), FileFormat::GeoJson);
+        assert!(matches!(FileFormat::from_path(Path::new("map.png")), Err(Error::UnknownFormat(e)) if e == "png"));
+
+        assert_eq!(parse_hex_color("#ff8c00"), Some([255, 140, 0]));
+        assert_eq!(parse_hex_color("f80"), Some([255, 136, 0]));
+        assert_eq!(parse_hex_color("#ff8c0"), None);
+        assert_eq!(parse_hex_color("orange"), None);
+    }
+}
@@ -2,5 +2,25 @@ pub mod types;
 pub mod gui;
 pub mod database;
 pub mod map;
+pub mod tile_cache;
+pub mod map_files;
+pub mod muf;
+pub mod greyline;
+pub mod grids;
 pub mod maidenhead;
+pub mod geodesy;
+pub mod adif;
+pub mod dxcc;
 pub mod tabs;
+pub mod wsjtx;
+pub mod js8call;
+pub mod fldigi;
+pub mod n1mm;
+pub mod rigctld;
+pub mod cw;
+pub mod dxcluster;
+pub mod rbn;
+pub mod pskreporter;
+pub mod pskreporter_mqtt;
+pub mod alerts;
+pub mod solar;
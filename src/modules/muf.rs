@@ -0,0 +1,603 @@
+//
+// Loads ionosonde-derived MUF and foF2 data in the prop.kc2g.com formats, and draws it as a map layer.
+//
+// Two formats are supported, and the format is detected from the contents:
+// - The station list (https://prop.kc2g.com/api/stations.json), which is interpolated into a color gradient
+// - Rendered GeoJSON contours (e.g. https://prop.kc2g.com/renders/current/mufd-normal-now.geojson), which are drawn as lines or filled areas
+//
+
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+use egui::{Color32, RichText, Ui};
+use geo::{Contains, Coord, LineString, MultiLineString, MultiPolygon, Polygon};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use thiserror::Error;
+use crate::GuiConfig;
+use super::{geodesy, map::{self, MapLayer, MapTransform, OverlayImage}, types::Band};
+
+
+/// The station list of prop.kc2g.com
+pub const STATIONS_URL: &str = "https://prop.kc2g.com/api/stations.json";
+
+
+/// The value shown by the layer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum Quantity {
+    /// The maximum usable frequency for a 3000km path
+    #[default]
+    #[strum(to_string = "MUF (3000km)")]
+    Muf,
+    /// The critical frequency of the F2 layer, which is roughly the highest frequency usable for NVIS
+    #[strum(to_string = "foF2")]
+    Fof2
+}
+impl Quantity {
+    /// The range of the color scale, in MHz
+    fn scale(&self) -> [f64; 2] {
+        match self {
+            Self::Muf => [5.0, 35.0],
+            Self::Fof2 => [2.0, 12.0]
+        }
+    }
+}
+
+/// An ionosonde station
+#[derive(Debug, Clone)]
+pub struct Station {
+    /// The name of the station (e.g. `Boulder`)
+    pub name: String,
+    /// The URSI code of the station (e.g. `BC840`)
+    pub code: String,
+    /// The location of the station
+    pub location: Coord<f64>,
+    /// When the station was measured, in seconds since the UNIX epoch
+    pub time: i64,
+    /// How confident the automatic scaling was, from 0 to 1
+    pub confidence: f64,
+    /// The critical frequency of the F2 layer, in MHz
+    pub fof2: Option<f64>,
+    /// The maximum usable frequency for a 3000km path, in MHz
+    pub mufd: Option<f64>
+}
+impl Station {
+    /// Gets the value of a quantity, in MHz
+    pub fn value(&self, quantity: Quantity) -> Option<f64> {
+        match quantity {
+            Quantity::Muf => self.mufd,
+            Quantity::Fof2 => self.fof2
+        }
+    }
+}
+
+/// The shape of a contour
+#[derive(Debug, Clone)]
+pub enum ContourGeometry {
+    /// A contour line
+    Lines(MultiLineString<f64>),
+    /// An area at or above the value of the contour
+    Area(MultiPolygon<f64>)
+}
+
+/// A contour of a GeoJSON file
+#[derive(Debug, Clone)]
+pub struct Contour {
+    /// The value of the contour, in MHz
+    pub value: f64,
+    /// The shape of the contour
+    pub geometry: ContourGeometry
+}
+
+/// MUF or foF2 data
+#[derive(Debug, Clone)]
+pub enum Data {
+    /// Station measurements, which are interpolated
+    Stations(Vec<Station>),
+    /// Contours, sorted by value
+    Contours(Vec<Contour>)
+}
+impl Data {
+    /// Parses a station list or a GeoJSON file
+    pub fn parse(text: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(text).map_err(Error::Json)?;
+
+        match value {
+            Value::Array(stations) => {
+                let stations: Vec<Station> = stations.iter().filter_map(parse_station).collect();
+                if stations.is_empty() {
+                    return Err(Error::Empty)?;
+                }
+                Ok(Self::Stations(stations))
+            },
+            Value::Object(object) if object.contains_key("features") => {
+                let mut contours: Vec<Contour> = object["features"].as_array()
+                    .map(|f| f.iter().filter_map(parse_feature).collect())
+                    .unwrap_or_default();
+                if contours.is_empty() {
+                    return Err(Error::Empty)?;
+                }
+                contours.sort_by(|a, b| a.value.total_cmp(&b.value));
+                Ok(Self::Contours(contours))
+            },
+            _ => Err(Error::UnknownFormat)?
+        }
+    }
+
+    /// Loads data from a URL or a local file
+    pub async fn load(source: String) -> Result<Self> {
+        let text = match source.starts_with("http://") || source.starts_with("https://") {
+            true => reqwest::get(source).await.map_err(Error::Request)?
+                .error_for_status().map_err(Error::Request)?
+                .text().await.map_err(Error::Request)?,
+            false => tokio::fs::read_to_string(source).await.map_err(Error::Io)?
+        };
+
+        Self::parse(&text)
+    }
+
+    /// The stations, if the data is a station list
+    pub fn stations(&self) -> &[Station] {
+        match self {
+            Self::Stations(stations) => stations,
+            Self::Contours(_) => &[]
+        }
+    }
+}
+
+/// Parses a station of the station list. Numbers may be strings, and longitudes may be 0-360.
+fn parse_station(value: &Value) -> Option<Station> {
+    let station = value.get("station")?;
+
+    let latitude = as_number(station.get("latitude")?)?;
+    let mut longitude = as_number(station.get("longitude")?)?;
+    if longitude > 180.0 {
+        longitude -= 360.0;
+    }
+
+    // Manually scaled measurements have a confidence of -1, and are trusted
+    let confidence = value.get("cs").and_then(as_number).map_or(1.0, |cs| match cs < 0.0 {
+        true => 1.0,
+        false => (cs / 100.0).clamp(0.0, 1.0)
+    });
+
+    Some(Station {
+        name: station.get("name").and_then(Value::as_str).unwrap_or_default().to_string(),
+        code: station.get("code").and_then(Value::as_str).unwrap_or_default().to_string(),
+        location: Coord { x: longitude, y: latitude },
+        time: value.get("time").and_then(Value::as_str).and_then(parse_time).unwrap_or_default(),
+        confidence,
+        fof2: value.get("fof2").and_then(as_number),
+        mufd: value.get("mufd").and_then(as_number)
+    })
+}
+
+/// Parses a GeoJSON feature into a contour. The value is read from the first known property.
+fn parse_feature(feature: &Value) -> Option<Contour> {
+    const VALUE_KEYS: [&str; 5] = ["level-value", "value", "mufd", "fof2", "title"];
+
+    let properties = feature.get("properties")?;
+    let value = VALUE_KEYS.iter().find_map(|k| properties.get(*k).and_then(as_number))?;
+
+    let geometry = feature.get("geometry")?;
+    let coordinates = geometry.get("coordinates")?;
+    let geometry = match geometry.get("type")?.as_str()? {
+        "LineString" => ContourGeometry::Lines(MultiLineString::new(vec![parse_line(coordinates)?])),
+        "MultiLineString" => ContourGeometry::Lines(MultiLineString::new(coordinates.as_array()?.iter().filter_map(parse_line).collect())),
+        "Polygon" => ContourGeometry::Area(MultiPolygon::new(vec![parse_polygon(coordinates)?])),
+        "MultiPolygon" => ContourGeometry::Area(MultiPolygon::new(coordinates.as_array()?.iter().filter_map(parse_polygon).collect())),
+        _ => return None
+    };
+
+    Some(Contour { value, geometry })
+}
+
+/// Parses a GeoJSON line (an array of `[longitude, latitude]` positions)
+fn parse_line(value: &Value) -> Option<LineString<f64>> {
+    value.as_array()?.iter()
+        .map(|p| Some(Coord { x: p.get(0)?.as_f64()?, y: p.get(1)?.as_f64()? }))
+        .collect::<Option<Vec<_>>>()
+        .map(LineString::new)
+}
+
+/// Parses a GeoJSON polygon (an exterior ring followed by any holes)
+fn parse_polygon(value: &Value) -> Option<Polygon<f64>> {
+    let mut rings = value.as_array()?.iter().filter_map(parse_line);
+    Some(Polygon::new(rings.next()?, rings.collect()))
+}
+
+/// Reads a number that may be stored as a string (e.g. `"14"` or `"14 MHz"`)
+fn as_number(value: &Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str()?.split_whitespace().next()?.parse().ok())
+}
+
+/// Parses a station time (e.g. `2026-10-18 12:00:00` or `2026-10-18T12:00:00Z`) into seconds since the UNIX epoch
+fn parse_time(s: &str) -> Option<i64> {
+    let s = s.trim().trim_end_matches('Z').replace('T', " ");
+    NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| t.and_utc().timestamp())
+}
+
+
+/// A map layer that shows MUF or foF2 data as a color gradient, with a frequency slider that shows where a band is likely open
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layer {
+    /// Should the layer be shown?
+    pub enabled: bool,
+    /// The data to draw
+    #[serde(skip)]
+    data: Option<Data>,
+    /// The value shown by the layer
+    pub quantity: Quantity,
+    /// The opacity of the gradient
+    pub opacity: u8,
+    /// Stations measured longer ago than this are ignored, in hours
+    pub max_age: u32,
+    /// Should the layer only show where the frequency is open, instead of the gradient?
+    pub highlight: bool,
+    /// The frequency used by the highlight, in MHz
+    pub frequency: f64
+}
+impl Layer {
+    /// The size of each interpolated cell, in pixels
+    const CELL_SIZE: u32 = 8;
+    /// The distance from the nearest station at which the gradient starts fading out, in km
+    const FADE_START: f64 = 1000.0;
+    /// The distance from the nearest station at which the gradient is fully faded out, in km
+    const FADE_END: f64 = 3500.0;
+    /// The color used where the frequency is open
+    const OPEN_COLOR: [u8; 3] = [40, 220, 80];
+    /// The color used where the frequency is closed
+    const CLOSED_COLOR: [u8; 3] = [20, 20, 20];
+    /// The width and height of the legend gradient
+    const LEGEND_SIZE: egui::Vec2 = egui::vec2(180.0, 10.0);
+
+    /// The data drawn by the layer
+    pub fn data(&self) -> Option<&Data> {
+        self.data.as_ref()
+    }
+
+    /// Replaces the data drawn by the layer
+    pub fn set_data(&mut self, data: Option<Data>) {
+        self.data = data;
+    }
+
+    /// Gets the color of a value, in MHz
+    fn color(&self, value: f64) -> [u8; 3] {
+        match self.highlight {
+            true if value >= self.frequency => Self::OPEN_COLOR,
+            true => Self::CLOSED_COLOR,
+            false => {
+                let [min, max] = self.quantity.scale();
+                gradient((value - min) / (max - min))
+            }
+        }
+    }
+
+    /// Interpolates the stations into a grid of cells, fading out cells that are far away from every station
+    fn draw_stations(&self, stations: &[Station], image: &mut OverlayImage, transform: &MapTransform) {
+        let oldest = Utc::now().timestamp() - self.max_age as i64 * 3600;
+
+        // Convert the stations into unit vectors so the distance to each cell is cheap to calculate
+        let points: Vec<([f64; 3], f64, f64)> = stations.iter()
+            .filter(|s| s.time >= oldest && s.confidence > 0.0)
+            .filter_map(|s| Some((geodesy::unit_vector(&s.location), s.value(self.quantity)?, s.confidence)))
+            .collect();
+        if points.is_empty() {
+            return;
+        }
+
+        for (cell, location) in map::world_cells(transform, Self::CELL_SIZE) {
+            let Some((value, nearest)) = interpolate(&points, geodesy::unit_vector(&location)) else { continue };
+
+            // Fade out the cell based on the distance to the nearest station
+            let fade = 1.0 - ((nearest - Self::FADE_START) / (Self::FADE_END - Self::FADE_START)).clamp(0.0, 1.0);
+            let alpha = (self.opacity as f64 * fade) as u8;
+            if alpha == 0 {
+                continue;
+            }
+
+            let [r, g, b] = self.color(value);
+            map::blend_rect(image, cell, image::Rgba([r, g, b, alpha]));
+        }
+    }
+
+    /// Draws contour lines, and fills contour areas with the value of the highest area that contains each cell
+    fn draw_contours(&self, contours: &[Contour], image: &mut OverlayImage, transform: &MapTransform) {
+
+        // Fill the areas
+        let areas: Vec<(&MultiPolygon<f64>, f64)> = contours.iter().rev()
+            .filter_map(|c| match &c.geometry {
+                ContourGeometry::Area(area) => Some((area, c.value)),
+                ContourGeometry::Lines(_) => None
+            })
+            .collect();
+        if !areas.is_empty() {
+            for (cell, location) in map::world_cells(transform, Self::CELL_SIZE) {
+                // The contours are sorted from highest to lowest, so the first match is the highest
+                let Some((_, value)) = areas.iter().find(|(area, _)| area.contains(&location)) else { continue };
+
+                let [r, g, b] = self.color(*value);
+                map::blend_rect(image, cell, image::Rgba([r, g, b, self.opacity]));
+            }
+        }
+
+        // Draw the lines
+        for contour in contours {
+            let ContourGeometry::Lines(lines) = &contour.geometry else { continue };
+            let [r, g, b] = self.color(contour.value);
+
+            for line in lines {
+                for segment in line.lines() {
+                    // Skip segments that wrap around the antimeridian
+                    if (segment.start.x - segment.end.x).abs() > 180.0 {
+                        continue;
+                    }
+
+                    let (start_x, start_y) = transform.to_screen(&segment.start);
+                    let (end_x, end_y) = transform.to_screen(&segment.end);
+                    imageproc::drawing::draw_antialiased_line_segment_mut(
+                        image,
+                        (start_x as i32, start_y as i32),
+                        (end_x as i32, end_y as i32),
+                        image::Rgba([r, g, b, 255]),
+                        imageproc::pixelops::interpolate
+                    );
+                }
+            }
+        }
+    }
+}
+impl MapLayer for Layer {
+    fn name(&self) -> &str {
+        "MUF"
+    }
+
+    fn is_visible(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_visible(&mut self, visible: bool) {
+        self.enabled = visible;
+    }
+
+    fn draw(&mut self, image: &mut OverlayImage, transform: &MapTransform, _config: &mut GuiConfig) {
+        match &self.data {
+            Some(Data::Stations(stations)) => self.draw_stations(stations, image, transform),
+            Some(Data::Contours(contours)) => self.draw_contours(contours, image, transform),
+            None => {}
+        }
+    }
+
+    fn ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) -> bool {
+        let mut changed = false;
+
+        // The quantity selector
+        egui::ComboBox::from_id_source(ui.id().with("muf_quantity"))
+        .selected_text(self.quantity.to_string())
+        .show_ui(ui, |ui| {
+            for quantity in Quantity::iter() {
+                changed |= ui.selectable_value(&mut self.quantity, quantity, quantity.to_string()).changed();
+            }
+        });
+
+        // The legend
+        if self.highlight {
+            ui.horizontal(|ui| {
+                let [r, g, b] = Self::OPEN_COLOR;
+                ui.label(RichText::new("■").color(Color32::from_rgb(r, g, b)));
+                ui.label(format!("Open at {:.1} MHz", self.frequency));
+            });
+        } else {
+            let [min, max] = self.quantity.scale();
+            let (rect, _) = ui.allocate_exact_size(Self::LEGEND_SIZE, egui::Sense::hover());
+            let steps = 32;
+            for i in 0..steps {
+                let [r, g, b] = gradient(i as f64 / (steps - 1) as f64);
+                let step_width = rect.width() / steps as f32;
+                let step_rect = egui::Rect::from_min_size(
+                    rect.min + egui::vec2(step_width * i as f32, 0.0),
+                    egui::vec2(step_width + 0.5, rect.height())
+                );
+                ui.painter().rect_filled(step_rect, 0.0, Color32::from_rgb(r, g, b));
+            }
+            ui.horizontal(|ui| {
+                ui.set_width(Self::LEGEND_SIZE.x);
+                ui.label(format!("{min:.0}"));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| ui.label(format!("{max:.0}+ MHz")));
+            });
+        }
+
+        // The frequency slider
+        changed |= ui.checkbox(&mut self.highlight, "Show where a frequency is open").changed();
+        ui.add_enabled_ui(self.highlight, |ui| {
+            changed |= ui.add(egui::Slider::new(&mut self.frequency, 1.8..=54.0)
+                .logarithmic(true)
+                .suffix(" MHz")
+                .custom_formatter(|v, _| format!("{v:.1}"))
+            ).changed();
+
+            // Show the band of the frequency
+            let band = Band::from_frequency((self.frequency * 1_000_000.0) as u64);
+            if band != Band::All {
+                ui.label(band.as_str());
+            }
+        });
+
+        changed
+    }
+}
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            data: None,
+            quantity: Default::default(),
+            opacity: 120,
+            max_age: 2,
+            highlight: false,
+            frequency: 14.1
+        }
+    }
+}
+
+
+/// Interpolates the value at a location using inverse distance weighting, weighted by the confidence of each point.
+///
+/// Returns the value and the distance to the nearest point in km.
+fn interpolate(points: &[([f64; 3], f64, f64)], location: [f64; 3]) -> Option<(f64, f64)> {
+    let mut sum = 0.0;
+    let mut weights = 0.0;
+    let mut nearest = f64::MAX;
+
+    for (point, value, confidence) in points {
+        // The squared chord length is cheaper than the great-circle distance, and increases with it
+        let chord = (point[0] - location[0]).powi(2) + (point[1] - location[1]).powi(2) + (point[2] - location[2]).powi(2);
+        if chord < 1e-12 {
+            return Some((*value, 0.0));
+        }
+
+        nearest = nearest.min(chord);
+        let weight = confidence / chord;
+        sum += value * weight;
+        weights += weight;
+    }
+
+    // Convert the nearest chord length back into a great-circle distance
+    let nearest = 2.0 * (nearest.sqrt() / 2.0).min(1.0).asin() * geodesy::EARTH_RADIUS / 1000.0;
+    (weights > 0.0).then(|| (sum / weights, nearest))
+}
+
+/// Gets the color of a value on a blue-cyan-green-yellow-red-magenta scale, from 0 to 1
+fn gradient(value: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 6] = [
+        [40.0, 40.0, 220.0],
+        [0.0, 200.0, 230.0],
+        [40.0, 210.0, 60.0],
+        [240.0, 220.0, 40.0],
+        [230.0, 50.0, 40.0],
+        [220.0, 40.0, 220.0]
+    ];
+
+    let position = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let t = position - index as f64;
+    let (a, b) = (STOPS[index], STOPS[index + 1]);
+    [0, 1, 2].map(|i| (a[i] + (b[i] - a[i]) * t) as u8)
+}
+
+
+/// Errors regarding the MUF module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to download the MUF data: {0}")]
+    Request(reqwest::Error),
+    #[error("Failed to read the MUF data: {0}")]
+    Io(std::io::Error),
+    #[error("Failed to parse the MUF data: {0}")]
+    Json(serde_json::Error),
+    #[error("The MUF data isn't a station list or a GeoJSON file")]
+    UnknownFormat,
+    #[error("The MUF data doesn't contain any stations or contours")]
+    Empty
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A few stations from the station list of prop.kc2g.com. The coordinates are strings, and the longitudes are 0-360.
+    const STATIONS: &str = r#"[
+        {"cs": 75, "fof2": 6.175, "fof1": null, "foe": 3.01, "hmf2": 250.2, "md": 3.4, "mufd": 21.0, "source": "giro", "time": "2024-03-16T12:00:00",
+         "station": {"code": "BC840", "id": 5, "latitude": "40.0", "longitude": "254.7", "name": "Boulder, CO", "use_for_essn": 1, "use_for_map": 1}},
+        {"cs": -1, "fof2": "4.2", "fof1": null, "foe": null, "hmf2": null, "md": null, "mufd": "12.6", "source": "giro", "time": "2024-03-16 11:45:00.000",
+         "station": {"code": "JR055", "id": 27, "latitude": "54.6", "longitude": "13.4", "name": "Juliusruh", "use_for_essn": 1, "use_for_map": 1}},
+        {"cs": 100, "fof2": 9.1, "fof1": null, "foe": null, "hmf2": 301.0, "md": 2.9, "mufd": null, "source": "giro", "time": "2024-03-16T12:05:00Z",
+         "station": {"code": "LM42B", "id": 81, "latitude": "-21.8", "longitude": "114.1", "name": "Learmonth", "use_for_essn": 1, "use_for_map": 1}},
+        {"cs": 80, "fof2": 5.0, "mufd": 15.0, "time": "2024-03-16T12:00:00"}
+    ]"#;
+
+    /// Two contours from a rendered GeoJSON file of prop.kc2g.com, out of order
+    const CONTOURS: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "properties": {"level-index": 3, "level-value": 21, "stroke": "#ffa500", "title": "21"},
+             "geometry": {"type": "MultiLineString", "coordinates": [[[-120.0, 30.0], [-100.0, 32.5], [-80.0, 31.0]]]}},
+            {"type": "Feature", "properties": {"level-index": 1, "level-value": 14, "stroke": "#00ff00", "title": "14"},
+             "geometry": {"type": "LineString", "coordinates": [[-120.0, 45.0], [-100.0, 47.5]]}},
+            {"type": "Feature", "properties": {"level-index": 2}, "geometry": {"type": "Point", "coordinates": [0.0, 0.0]}}
+        ]
+    }"#;
+
+    #[test]
+    fn stations_are_parsed() {
+        let Data::Stations(stations) = Data::parse(STATIONS).unwrap() else {
+            panic!("the station list should be parsed as stations");
+        };
+        // The entry without a station is skipped
+        assert_eq!(stations.len(), 3);
+
+        let boulder = &stations[0];
+        assert_eq!((boulder.name.as_str(), boulder.code.as_str()), ("Boulder, CO", "BC840"));
+        assert!((boulder.location.x - -105.3).abs() < 1e-9);
+        assert_eq!(boulder.location.y, 40.0);
+        assert_eq!(boulder.time, 1_710_590_400);
+        assert_eq!(boulder.confidence, 0.75);
+        assert_eq!(boulder.value(Quantity::Muf), Some(21.0));
+        assert_eq!(boulder.value(Quantity::Fof2), Some(6.175));
+
+        // Manually scaled measurements are trusted, and numbers may be strings
+        let juliusruh = &stations[1];
+        assert_eq!(juliusruh.confidence, 1.0);
+        assert_eq!(juliusruh.time, 1_710_590_400 - 15 * 60);
+        assert_eq!(juliusruh.value(Quantity::Muf), Some(12.6));
+        assert_eq!(juliusruh.value(Quantity::Fof2), Some(4.2));
+
+        let learmonth = &stations[2];
+        assert_eq!(learmonth.time, 1_710_590_400 + 5 * 60);
+        assert_eq!(learmonth.value(Quantity::Muf), None);
+    }
+
+    #[test]
+    fn contours_are_parsed_and_sorted() {
+        let data = Data::parse(CONTOURS).unwrap();
+        assert!(data.stations().is_empty());
+        let Data::Contours(contours) = data else {
+            panic!("the GeoJSON file should be parsed as contours");
+        };
+
+        // The point doesn't have a value or a supported geometry, so it's skipped
+        assert_eq!(contours.iter().map(|c| c.value).collect::<Vec<_>>(), [14.0, 21.0]);
+        assert!(matches!(&contours[1].geometry, ContourGeometry::Lines(lines) if lines.0.len() == 1 && lines.0[0].0.len() == 3));
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        let error = |text: &str| Data::parse(text).unwrap_err().downcast::<Error>().unwrap();
+        assert!(matches!(error("[]"), Error::Empty));
+        assert!(matches!(error(r#"[{"mufd": 10.0}]"#), Error::Empty));
+        assert!(matches!(error(r#"{"type": "FeatureCollection", "features": []}"#), Error::Empty));
+        assert!(matches!(error(r#"{"stations": []}"#), Error::UnknownFormat));
+        assert!(matches!(error("<html>"), Error::Json(_)));
+    }
+
+    #[test]
+    fn stations_are_interpolated() {
+        let points = [
+            (geodesy::unit_vector(&Coord { x: 0.0, y: 0.0 }), 10.0, 1.0),
+            (geodesy::unit_vector(&Coord { x: 90.0, y: 0.0 }), 20.0, 1.0)
+        ];
+
+        // At a station, its value is used
+        assert_eq!(interpolate(&points, points[0].0), Some((10.0, 0.0)));
+
+        // Halfway between equally confident stations, the value is the average, and the nearest station is an eighth of the way around the world
+        let (value, nearest) = interpolate(&points, geodesy::unit_vector(&Coord { x: 45.0, y: 0.0 })).unwrap();
+        assert!((value - 15.0).abs() < 1e-9);
+        assert!((nearest - 0.125 * 2.0 * std::f64::consts::PI * geodesy::EARTH_RADIUS / 1000.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,626 @@
+//
+// A listener and sender for N1MM Logger+ style UDP contact broadcasts.
+//
+// Each contact is broadcast as a UDP datagram containing an XML document. The root element is `contactinfo` for a new contact,
+// `contactreplace` for an edited contact, or `contactdelete` for a deleted contact. Each contact has a unique `ID`, which is used as the database record ID.
+// N1MM Logger+ broadcasts on port 12060 by default.
+//
+
+use std::time::Duration;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use log::{debug, error, info, trace, warn};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use surrealdb::sql;
+use thiserror::Error;
+use tokio::{net::UdpSocket, sync::mpsc, task::JoinHandle};
+use crate::{GuiConfig, RT};
+use super::{database::{self, TABLE_CONTACT}, types::{self, Band}};
+
+
+/// The largest datagram that we expect to receive
+const MAX_DATAGRAM_SIZE: usize = 8192;
+/// The name of this application, as sent in the `app` element
+const APP_NAME: &str = "QLog";
+
+
+/// The type of a contact broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    /// A new contact was logged
+    ContactInfo,
+    /// An existing contact was edited
+    ContactReplace,
+    /// A contact was deleted
+    ContactDelete
+}
+
+/// A contact broadcast received from another logger
+#[derive(Debug, Clone)]
+pub struct Packet {
+    /// The type of the broadcast
+    pub kind: PacketKind,
+    /// The application that sent the broadcast (e.g. `N1MM`)
+    pub app: String,
+    /// The name of the station (computer) that sent the broadcast
+    pub station_name: String,
+    /// The contact. The ID is set if the broadcast included one.
+    pub contact: types::Contact
+}
+impl Packet {
+    /// Parses an XML contact broadcast into a [Packet]
+    pub fn parse(xml: &str) -> Result<Self, Error> {
+        let kind = match root_element(xml).ok_or(Error::InvalidXml)? {
+            "contactinfo" => PacketKind::ContactInfo,
+            "contactreplace" => PacketKind::ContactReplace,
+            "contactdelete" => PacketKind::ContactDelete,
+            other => return Err(Error::UnknownPacket(other.to_string()))
+        };
+
+        let raw: RawContact = serde_xml_rs::from_str(xml).map_err(Error::Deserialize)?;
+
+        Ok(Self {
+            kind,
+            app: raw.app.clone(),
+            station_name: raw.station_name.clone(),
+            contact: raw.to_contact()?
+        })
+    }
+}
+
+/// Gets the name of the root element of an XML document, skipping the declaration and any comments
+fn root_element(xml: &str) -> Option<&str> {
+    let mut rest = xml;
+    loop {
+        let start = rest.find('<')?;
+        rest = &rest[start + 1..];
+        if !rest.starts_with('?') && !rest.starts_with('!') {
+            let end = rest.find(|c: char| c == '>' || c == '/' || c.is_whitespace())?;
+            return Some(&rest[..end]);
+        }
+    }
+}
+
+/// The raw contact broadcast. Only the elements that we use are included.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawContact {
+    app: String,
+    /// The time of the contact in UTC, formatted as `YYYY-MM-DD HH:MM:SS`
+    timestamp: String,
+    /// The receive frequency, in tens of Hz
+    rxfreq: String,
+    mode: String,
+    call: String,
+    /// The signal report that was sent
+    snt: String,
+    /// The serial number that was sent
+    sntnr: String,
+    /// The signal report that was received
+    rcv: String,
+    /// The serial number that was received
+    rcvnr: String,
+    gridsquare: String,
+    exchange1: String,
+    section: String,
+    name: String,
+    comment: String,
+    #[serde(rename = "StationName")]
+    station_name: String,
+    #[serde(rename = "ID")]
+    id: String
+}
+impl RawContact {
+    /// Converts the broadcast into a [types::Contact]. This fails if the timestamp is missing or invalid, rather than logging the contact at the wrong time.
+    fn to_contact(&self) -> Result<types::Contact, Error> {
+        let timestamp = NaiveDateTime::parse_from_str(self.timestamp.trim(), "%Y-%m-%d %H:%M:%S")
+            .map_err(|_| Error::InvalidTimestamp(self.timestamp.clone()))?;
+
+        // Serial numbers of 0 mean that the contest doesn't use them
+        let serial = |s: &str| match s.trim() {
+            "" | "0" => String::new(),
+            s => s.to_string()
+        };
+
+        // Combine the exchange and comment into the note
+        let note = [self.name.trim(), self.exchange1.trim(), self.section.trim(), self.comment.trim()].into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" - ");
+
+        Ok(types::Contact {
+            id: record_id(&self.id),
+            callsign: self.call.trim().to_ascii_uppercase(),
+            grid: self.gridsquare.trim().to_string(),
+            date: timestamp.date(),
+            time: timestamp.time(),
+            frequency: self.rxfreq.trim().parse::<u64>().unwrap_or_default() * 10,
+            mode: types::Mode::from_name(&self.mode),
+            tx_rst: format!("{} {}", self.snt.trim(), serial(&self.sntnr)).trim().to_string(),
+            rx_rst: format!("{} {}", self.rcv.trim(), serial(&self.rcvnr)).trim().to_string(),
+            note,
+            ..Default::default()
+        })
+    }
+}
+
+/// Converts an N1MM contact ID into a database record ID, returning `None` if the ID is empty
+fn record_id(id: &str) -> Option<sql::Thing> {
+    match id.trim() {
+        "" => None,
+        id => Some(sql::Thing { tb: TABLE_CONTACT.into(), id: sql::Id::String(id.to_string()) })
+    }
+}
+
+/// Builds a `contactinfo` broadcast for a contact that was logged by QLog
+fn contact_info(contact: &types::Contact, station: &types::StationProfile, config: &Config) -> String {
+    let id = contact.id.as_ref().map(|t| t.id.to_raw()).unwrap_or_default();
+    let timestamp = contact.date.and_time(contact.time).format("%Y-%m-%d %H:%M:%S");
+    let frequency = contact.frequency / 10;
+
+    let elements = [
+        ("app", APP_NAME.to_string()),
+        ("contestname", "DX".to_string()),
+        ("contestnr", "0".to_string()),
+        ("timestamp", timestamp.to_string()),
+        ("mycall", station.callsign.clone()),
+        ("band", band(contact.frequency).to_string()),
+        ("rxfreq", frequency.to_string()),
+        ("txfreq", frequency.to_string()),
+        ("operator", station.callsign.clone()),
+        ("mode", contact.mode.to_string()),
+        ("call", contact.callsign.clone()),
+        ("snt", contact.tx_rst.clone()),
+        ("sntnr", "0".to_string()),
+        ("rcv", contact.rx_rst.clone()),
+        ("rcvnr", "0".to_string()),
+        ("gridsquare", contact.grid.clone()),
+        ("comment", contact.note.clone()),
+        ("radionr", "1".to_string()),
+        ("IsOriginal", "True".to_string()),
+        ("StationName", config.station_name.clone()),
+        ("ID", id),
+        ("IsClaimedQso", "1".to_string())
+    ];
+
+    let elements: String = elements.iter()
+        .map(|(name, value)| format!("<{name}>{}</{name}>", types::escape_xml(value)))
+        .collect();
+
+    format!("<?xml version=\"1.0\" encoding=\"utf-8\"?><contactinfo>{elements}</contactinfo>")
+}
+
+/// Gets the N1MM band (the lower edge of the band in MHz) for a frequency in Hz
+fn band(frequency: u64) -> &'static str {
+    match Band::from_frequency(frequency) {
+        Band::B2200m => "0.136",
+        Band::B630m => "0.472",
+        Band::B160m => "1.8",
+        Band::B80m => "3.5",
+        Band::B60m => "5",
+        Band::B40m => "7",
+        Band::B30m => "10",
+        Band::B20m => "14",
+        Band::B17m => "18",
+        Band::B15m => "21",
+        Band::B12m => "24",
+        Band::B10m => "28",
+        Band::B6m => "50",
+        Band::B2m => "144",
+        Band::B1_25M => "222",
+        Band::B70CM => "420",
+        Band::B33CM => "902",
+        Band::B23CM => "1240",
+        Band::F2_4GHZ => "2300",
+        Band::F3_4GHZ => "3300",
+        Band::F5_8GHZ => "5650",
+        Band::F10GHZ => "10000",
+        Band::F24GHZ => "24000",
+        Band::F47GHZ => "47000",
+        Band::F76GHZ => "76000",
+        Band::All => "0"
+    }
+}
+
+
+/// A task that was started in response to a broadcast
+enum Task {
+    Insert(Promise<Result<types::Contact>>),
+    Update(Promise<Result<types::Contact>>),
+    Delete(Promise<Result<types::Contact>>)
+}
+impl Task {
+    /// Is the task finished?
+    fn ready(&self) -> bool {
+        match self {
+            Self::Insert(p) | Self::Update(p) | Self::Delete(p) => p.ready().is_some()
+        }
+    }
+}
+
+/// A node in a multi-station network. This listens for contact broadcasts from other loggers, and broadcasts the contacts logged by QLog.
+pub struct Node {
+    /// The address that we listen on, if the listener is enabled
+    listen_address: Option<String>,
+    /// The address that we broadcast to, if the sender is enabled
+    send_address: Option<String>,
+    /// The receiving end of the broadcasts received from other loggers
+    rx: mpsc::UnboundedReceiver<Packet>,
+    /// The sending end of the broadcasts that should be sent to other loggers
+    tx: mpsc::UnboundedSender<String>,
+    /// The listener task
+    listen_task: Option<JoinHandle<()>>,
+    /// The sender task
+    send_task: Option<JoinHandle<()>>,
+    /// The database tasks that were started in response to broadcasts
+    tasks: Vec<Task>
+}
+impl Node {
+    /// How long to wait before retrying if we failed to bind to a socket
+    const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+    /// Starts listening on `listen_address` (e.g. `0.0.0.0:12060`) and broadcasting to `send_address` (e.g. `255.255.255.255:12060`), if they're provided
+    pub fn new(listen_address: Option<String>, send_address: Option<String>) -> Self {
+        let (packet_tx, packet_rx) = mpsc::unbounded_channel();
+        let (send_tx, send_rx) = mpsc::unbounded_channel();
+
+        let listen_task = listen_address.clone().map(|address| RT.spawn(Self::listen(address, packet_tx)));
+        let send_task = send_address.clone().map(|address| RT.spawn(Self::send(address, send_rx)));
+
+        Self {
+            listen_address,
+            send_address,
+            rx: packet_rx,
+            tx: send_tx,
+            listen_task,
+            send_task,
+            tasks: Default::default()
+        }
+    }
+
+    /// The listener task. This binds to the socket and forwards every broadcast it receives to `tx` until the receiver is dropped.
+    async fn listen(address: String, tx: mpsc::UnboundedSender<Packet>) {
+
+        // Try to bind to the socket, retrying if we fail (e.g. the port is in use)
+        let socket = loop {
+            match UdpSocket::bind(&address).await {
+                Ok(s) => break s,
+                Err(err) => {
+                    error!("Failed to bind contact broadcast listener to '{address}': {err}");
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                    if tx.is_closed() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        info!("Listening for contact broadcasts on '{address}'");
+
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf).await {
+                Ok(r) => r,
+                Err(err) => {
+                    warn!("Failed to receive contact broadcast: {err}");
+                    continue;
+                }
+            };
+
+            match Packet::parse(&String::from_utf8_lossy(&buf[..len])) {
+                Ok(packet) => {
+                    // The GUI stopped listening, so stop the task
+                    if tx.send(packet).is_err() {
+                        return;
+                    }
+                },
+                // Other broadcasts (e.g. radio info or spots) are sent on the same port, so they're ignored
+                Err(Error::UnknownPacket(name)) => trace!("Ignoring {name} broadcast from {from}"),
+                Err(err) => warn!("Failed to parse contact broadcast from {from}: {err}")
+            }
+        }
+
+    }
+
+    /// The sender task. This broadcasts every message it receives from `rx` until the sender is dropped.
+    async fn send(address: String, mut rx: mpsc::UnboundedReceiver<String>) {
+
+        // Bind to any port and allow broadcasts
+        let socket = match UdpSocket::bind("0.0.0.0:0").await.and_then(|s| s.set_broadcast(true).map(|_| s)) {
+            Ok(s) => s,
+            Err(err) => {
+                error!("Failed to create contact broadcast socket: {err}");
+                return;
+            }
+        };
+
+        while let Some(message) = rx.recv().await {
+            if let Err(err) = socket.send_to(message.as_bytes(), &address).await {
+                warn!("Failed to send contact broadcast to '{address}': {err}");
+            }
+        }
+
+    }
+}
+impl Drop for Node {
+    fn drop(&mut self) {
+        if let Some(task) = &self.listen_task {
+            task.abort();
+        }
+        if let Some(task) = &self.send_task {
+            task.abort();
+        }
+    }
+}
+impl std::fmt::Debug for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Node")
+        .field("listen_address", &self.listen_address)
+        .field("send_address", &self.send_address)
+        .field("tasks", &self.tasks.len())
+        .finish()
+    }
+}
+
+/// Broadcasts a contact that was logged by QLog to the other loggers on the network, if the sender is enabled
+pub fn broadcast(config: &GuiConfig, contact: &types::Contact) {
+    if let Some(node) = config.n1mm.as_ref().filter(|n| n.send_address.is_some()) {
+        debug!("Broadcasting contact with {}", contact.callsign);
+        let _ = node.tx.send(contact_info(contact, &config.station, &config.n1mm_config));
+    }
+}
+
+/// Processes any contact broadcasts that were received, starting or stopping the listener and sender depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the node out of the config so we can mutate the rest of the config while processing broadcasts
+    let mut node = config.n1mm.take();
+
+    // Start, stop, or restart the node if the config changed
+    let listen_address = config.n1mm_config.listen.then(|| config.n1mm_config.listen_address.clone());
+    let send_address = config.n1mm_config.send.then(|| config.n1mm_config.send_address.clone());
+    if listen_address.is_none() && send_address.is_none() {
+        node = None;
+    } else if node.as_ref().map_or(true, |n| n.listen_address != listen_address || n.send_address != send_address) {
+        node = Some(Node::new(listen_address, send_address));
+    }
+
+    let Some(mut node) = node else {
+        return;
+    };
+
+    // Process the received broadcasts
+    while let Ok(packet) = node.rx.try_recv() {
+
+        // Ignore our own broadcasts, which we receive if we're listening on the port we broadcast to
+        if packet.app == APP_NAME && packet.station_name == config.n1mm_config.station_name {
+            continue;
+        }
+
+        debug!("Received {:?} for {} from {}", packet.kind, packet.contact.callsign, packet.station_name);
+
+        match (packet.kind, &packet.contact.id) {
+            // Contacts can be broadcast more than once (e.g. when a logger resends its log), so contacts with an ID replace any existing contact
+            (PacketKind::ContactInfo, None) => node.tasks.push(Task::Insert(config.db_api.insert_contact_promise(packet.contact))),
+            (PacketKind::ContactInfo, Some(_)) => node.tasks.push(Task::Insert(config.db_api.upsert_contact_promise(packet.contact))),
+            (PacketKind::ContactReplace, Some(_)) => node.tasks.push(Task::Update(config.db_api.upsert_contact_promise(packet.contact))),
+            (PacketKind::ContactDelete, Some(thing)) => node.tasks.push(Task::Delete(config.db_api.delete_contact_promise(thing.id.clone()))),
+            // Edits and deletes can't be matched to a contact without an ID
+            (kind, None) => warn!("Ignoring {kind:?} for {} without an ID", packet.contact.callsign)
+        }
+    }
+
+    // Process any finished database tasks
+    for task in node.tasks.extract_if(|t| t.ready()) {
+        let (action, promise) = match task {
+            Task::Insert(p) => ("insert", p),
+            Task::Update(p) => ("update", p),
+            Task::Delete(p) => ("delete", p)
+        };
+
+        match promise.block_and_take() {
+            Ok(_) => config.events.push_back((None, types::Event::RefreshContacts)),
+            // The contact may have been logged before we started listening, so there's nothing to delete
+            Err(err) if matches!(err.downcast_ref(), Some(database::Error::DoesNotExist)) => debug!("Ignoring {action} of an unknown contact from broadcast"),
+            Err(err) => {
+                error!("Failed to {action} contact from broadcast: {err}");
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to {action} contact from the network: {err}")));
+            }
+        }
+    }
+
+    // Put the node back into the config
+    config.n1mm = Some(node);
+
+}
+
+
+/// The contact broadcast module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should we listen for contact broadcasts?
+    pub listen: bool,
+    /// The address to listen for contact broadcasts on
+    pub listen_address: String,
+    /// Should we broadcast the contacts logged by QLog?
+    pub send: bool,
+    /// The address to broadcast contacts to
+    pub send_address: String,
+    /// The name of this station on the network. This must be unique for each logger on the network.
+    pub station_name: String
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: false,
+            listen_address: "0.0.0.0:12060".into(),
+            send: false,
+            send_address: "255.255.255.255:12060".into(),
+            station_name: "QLOG".into()
+        }
+    }
+}
+
+/// Errors regarding the contact broadcast module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid XML document")]
+    InvalidXml,
+    #[error("Unknown broadcast type: {0}")]
+    UnknownPacket(String),
+    #[error("Failed to deserialize broadcast: {0}")]
+    Deserialize(serde_xml_rs::Error),
+    #[error("Invalid contact timestamp '{0}'")]
+    InvalidTimestamp(String)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+    use super::*;
+
+    /// A `contactinfo` broadcast from N1MM Logger+ for a CW contest QSO
+    const CONTACT_INFO: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<contactinfo>
+	<app>N1MM</app>
+	<contestname>CWOPS</contestname>
+	<contestnr>73</contestnr>
+	<timestamp>2020-01-17 16:43:38</timestamp>
+	<mycall>W2XYZ</mycall>
+	<band>3.5</band>
+	<rxfreq>352519</rxfreq>
+	<txfreq>352519</txfreq>
+	<operator></operator>
+	<mode>CW</mode>
+	<call>w1aw</call>
+	<countryprefix>K</countryprefix>
+	<wpxprefix>W1</wpxprefix>
+	<stationprefix>W2XYZ</stationprefix>
+	<continent>NA</continent>
+	<snt>599</snt>
+	<sntnr>5</sntnr>
+	<rcv>599</rcv>
+	<rcvnr>0</rcvnr>
+	<gridsquare>FN31</gridsquare>
+	<exchange1>HIRAM</exchange1>
+	<section></section>
+	<comment>CT</comment>
+	<qth></qth>
+	<name></name>
+	<power></power>
+	<misctext></misctext>
+	<zone>0</zone>
+	<prec></prec>
+	<ck>0</ck>
+	<ismultiplier1>0</ismultiplier1>
+	<ismultiplier2>0</ismultiplier2>
+	<ismultiplier3>1</ismultiplier3>
+	<points>1</points>
+	<radionr>1</radionr>
+	<run1run2>1</run1run2>
+	<RoverLocation></RoverLocation>
+	<RadioInterfaced>1</RadioInterfaced>
+	<NetworkedCompNr>0</NetworkedCompNr>
+	<IsOriginal>False</IsOriginal>
+	<NetBiosName></NetBiosName>
+	<IsRunQSO>0</IsRunQSO>
+	<StationName>CONTEST-PC</StationName>
+	<ID>f9ffac4fcd3e479ca86e137df1338531</ID>
+	<IsClaimedQso>1</IsClaimedQso>
+</contactinfo>"#;
+
+    /// A `contactdelete` broadcast from N1MM Logger+
+    const CONTACT_DELETE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<contactdelete>
+	<app>N1MM</app>
+	<timestamp>2020-01-17 16:43:38</timestamp>
+	<call>W1AW</call>
+	<contestnr>73</contestnr>
+	<StationName>CONTEST-PC</StationName>
+	<ID>f9ffac4fcd3e479ca86e137df1338531</ID>
+</contactdelete>"#;
+
+    #[test]
+    fn contact_info_is_parsed() {
+        let packet = Packet::parse(CONTACT_INFO).unwrap();
+        assert_eq!(packet.kind, PacketKind::ContactInfo);
+        assert_eq!(packet.app, "N1MM");
+        assert_eq!(packet.station_name, "CONTEST-PC");
+
+        let contact = packet.contact;
+        assert_eq!(contact.id.map(|id| id.id.to_raw()).as_deref(), Some("f9ffac4fcd3e479ca86e137df1338531"));
+        assert_eq!(contact.callsign, "W1AW");
+        assert_eq!(contact.grid, "FN31");
+        assert_eq!(contact.date, NaiveDate::from_ymd_opt(2020, 1, 17).unwrap());
+        assert_eq!(contact.time, NaiveTime::from_hms_opt(16, 43, 38).unwrap());
+        assert_eq!(contact.frequency, 3_525_190);
+        assert_eq!(contact.mode, types::Mode::CW);
+        assert_eq!(contact.tx_rst, "599 5");
+        assert_eq!(contact.rx_rst, "599");
+        assert_eq!(contact.note, "HIRAM - CT");
+    }
+
+    #[test]
+    fn contact_delete_is_parsed() {
+        let packet = Packet::parse(CONTACT_DELETE).unwrap();
+        assert_eq!(packet.kind, PacketKind::ContactDelete);
+        assert_eq!(packet.contact.callsign, "W1AW");
+        assert!(packet.contact.id.is_some());
+    }
+
+    #[test]
+    fn invalid_broadcasts_are_rejected() {
+        // A missing or invalid timestamp would log the contact at the wrong time
+        for timestamp in ["<timestamp></timestamp>", "<timestamp>17/01/2020 16:43</timestamp>", ""] {
+            let xml = CONTACT_INFO.replace("<timestamp>2020-01-17 16:43:38</timestamp>", timestamp);
+            assert!(matches!(Packet::parse(&xml), Err(Error::InvalidTimestamp(_))), "{timestamp} should be rejected");
+        }
+
+        assert!(matches!(Packet::parse(r#"<?xml version="1.0"?><RadioInfo><app>N1MM</app></RadioInfo>"#), Err(Error::UnknownPacket(name)) if name == "RadioInfo"));
+        assert!(matches!(Packet::parse("not xml"), Err(Error::InvalidXml)));
+    }
+
+    #[test]
+    fn sent_contact_info_round_trips() {
+        let contact = types::Contact {
+            callsign: "K1ABC".into(),
+            grid: "FN42".into(),
+            date: NaiveDate::from_ymd_opt(2024, 3, 16).unwrap(),
+            time: NaiveTime::from_hms_opt(12, 34, 56).unwrap(),
+            frequency: 14_074_000,
+            mode: types::Mode::FT8,
+            tx_rst: "-10".into(),
+            rx_rst: "-12".into(),
+            note: "Bob & <Alice>".into(),
+            ..Default::default()
+        };
+        let station = types::StationProfile { callsign: "W1AW".into(), ..Default::default() };
+        let xml = contact_info(&contact, &station, &Config::default());
+        assert!(xml.contains("<band>14</band>"));
+
+        let packet = Packet::parse(&xml).unwrap();
+        assert_eq!((packet.kind, packet.app.as_str(), packet.station_name.as_str()), (PacketKind::ContactInfo, APP_NAME, "QLOG"));
+        assert_eq!(packet.contact.callsign, contact.callsign);
+        assert_eq!(packet.contact.grid, contact.grid);
+        assert_eq!((packet.contact.date, packet.contact.time), (contact.date, contact.time));
+        assert_eq!(packet.contact.frequency, contact.frequency);
+        assert_eq!(packet.contact.mode, contact.mode);
+        assert_eq!(packet.contact.note, contact.note);
+    }
+
+    #[test]
+    fn bands_use_n1mm_names() {
+        assert_eq!(band(1_840_000), "1.8");
+        assert_eq!(band(3_525_190), "3.5");
+        assert_eq!(band(5_357_000), "5");
+        assert_eq!(band(10_136_000), "10");
+        assert_eq!(band(144_174_000), "144");
+        assert_eq!(band(15_000_000), "0");
+    }
+}
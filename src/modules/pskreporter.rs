@@ -0,0 +1,110 @@
+//
+// PSKReporter reception reports, which are shared by the PSKReporter tab, the live feed, and the database
+//
+
+use std::hash::{Hash, Hasher};
+use serde::{Deserialize, Serialize};
+use super::{alerts, pskreporter_mqtt, types};
+
+
+/// A callsign in a reception report
+pub type CallsignString = arrayvec::ArrayString<20>;
+/// A grid square in a reception report
+pub type GridString = arrayvec::ArrayString<10>;
+/// A mode in a reception report
+pub type ModeString = arrayvec::ArrayString<16>;
+
+
+/// A reception report from the PSKReporter API
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReceptionReport {
+    /// The callsign of the receiving station
+    #[serde(alias = "receiverCallsign")]
+    pub rx_callsign: CallsignString,
+    /// The grid square of the receiving station
+    #[serde(alias = "receiverLocator")]
+    pub rx_grid: GridString,
+    /// The callsign of the transmitting station
+    #[serde(alias = "senderCallsign")]
+    pub tx_callsign: CallsignString,
+    /// The grid square of the transmitting station
+    #[serde(alias = "senderLocator")]
+    pub tx_grid: GridString,
+    /// The frequency that the station was heard on
+    pub frequency: u64,
+    /// The time the report was generated
+    #[serde(alias = "flowStartSeconds")]
+    pub time: u64,
+    /// The mode that the transmitting station used
+    pub mode: ModeString,
+    /// The signal to noise ratio of the transmitting station
+    #[serde(alias = "sNR")]
+    pub snr: i8
+}
+impl ReceptionReport {
+    /// The data of the report that's saved when the markers are exported
+    pub fn properties(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("tx_callsign", self.tx_callsign.to_string()),
+            ("tx_grid", self.tx_grid.to_string()),
+            ("rx_callsign", self.rx_callsign.to_string()),
+            ("rx_grid", self.rx_grid.to_string()),
+            ("frequency", self.frequency.to_string()),
+            ("mode", self.mode.to_string()),
+            ("snr", self.snr.to_string()),
+            ("time", chrono::DateTime::from_timestamp(self.time as i64, 0).map(|t| t.to_rfc3339()).unwrap_or_default())
+        ]
+    }
+
+    /// Converts the transmitting station into a spot that can be checked for alerts
+    pub fn alert_candidate(&self) -> alerts::Candidate<'_> {
+        alerts::Candidate {
+            source: alerts::Source::PskReporter,
+            callsign: &self.tx_callsign,
+            frequency: Some(self.frequency),
+            mode: Some(types::Mode::from_name(&self.mode)),
+            grid: Some(&self.tx_grid).filter(|g| !g.is_empty()).map(|g| g.as_str())
+        }
+    }
+}
+
+impl From<&pskreporter_mqtt::Report> for ReceptionReport {
+    fn from(report: &pskreporter_mqtt::Report) -> Self {
+        Self {
+            rx_callsign: truncated(&report.rx_callsign),
+            rx_grid: truncated(&report.rx_grid),
+            tx_callsign: truncated(&report.tx_callsign),
+            tx_grid: truncated(&report.tx_grid),
+            frequency: report.frequency,
+            time: report.time,
+            mode: truncated(&report.mode),
+            snr: report.snr.clamp(i8::MIN as i32, i8::MAX as i32) as i8
+        }
+    }
+}
+
+/// Hashes a reception report into a u64. This is used to generate a unique but repeatable ID for each reception report.
+/// 
+/// This is useful for persisting the markers across overlay updates and API queries.
+/// If we update the map overlay and a marker exists with the same ID, we can persist the marker state across the update.
+pub fn hash_reception_report(report: &ReceptionReport) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    report.tx_callsign.hash(&mut hasher);
+    report.rx_callsign.hash(&mut hasher);
+    report.frequency.hash(&mut hasher);
+    report.snr.hash(&mut hasher);
+    report.time.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Copies as much of the string as fits into a fixed capacity string
+fn truncated<const CAP: usize>(string: &str) -> arrayvec::ArrayString<CAP> {
+    let mut truncated = arrayvec::ArrayString::new();
+    for c in string.chars() {
+        if truncated.try_push(c).is_err() {
+            break;
+        }
+    }
+    truncated
+}
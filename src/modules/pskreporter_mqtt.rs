@@ -0,0 +1,449 @@
+//
+// A client for the PSKReporter MQTT feed, which streams reception reports as they're uploaded.
+//
+// The feed publishes every report to a topic formatted like:
+// `pskr/filter/v2/{band}/{mode}/{sender callsign}/{receiver callsign}/{sender locator}/{receiver locator}/{sender country}/{receiver country}`
+// so the reports can be filtered by subscribing with `+` wildcards in the levels we don't care about.
+// The payload is a JSON object with short field names, e.g. `{"sq":1,"f":14074000,"md":"FT8","rp":-12,"t":1662407712,"sc":"W1AW","sl":"FN31pr","rc":"K1ABC","rl":"FN42","b":"20m"}`.
+// Any MQTT broker can be used, including a local test broker (e.g. `mosquitto`, with reports published by `mosquitto_pub`).
+//
+
+use std::time::Duration;
+use anyhow::Result;
+use log::{debug, info, trace};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::{sync::{broadcast, watch}, task::JoinHandle};
+use crate::RT;
+use super::types::Band;
+
+
+/// A reception report received from the feed
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Report {
+    /// The sequence number of the report
+    #[serde(rename = "sq")]
+    pub sequence: u64,
+    /// The frequency that the station was heard on, in Hz
+    #[serde(rename = "f")]
+    pub frequency: u64,
+    /// The mode that the transmitting station used
+    #[serde(rename = "md")]
+    pub mode: String,
+    /// The signal to noise ratio of the transmitting station, in dB
+    #[serde(rename = "rp")]
+    pub snr: i32,
+    /// The time the report was generated, in seconds since the epoch
+    #[serde(rename = "t")]
+    pub time: u64,
+    /// The callsign of the transmitting station
+    #[serde(rename = "sc")]
+    pub tx_callsign: String,
+    /// The grid square of the transmitting station
+    #[serde(rename = "sl")]
+    pub tx_grid: String,
+    /// The callsign of the receiving station
+    #[serde(rename = "rc")]
+    pub rx_callsign: String,
+    /// The grid square of the receiving station
+    #[serde(rename = "rl")]
+    pub rx_grid: String
+}
+
+/// The reports that should be streamed from the feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    /// Only stream reports on this band
+    pub band: Band,
+    /// Only stream reports in this mode (e.g. `FT8`)
+    pub mode: Option<String>,
+    /// Only stream reports of signals sent by this callsign
+    pub tx_callsign: Option<String>,
+    /// Only stream reports of signals received by this callsign
+    pub rx_callsign: Option<String>,
+    /// Only stream reports where either station is in a grid square starting with this prefix (e.g. `FN` or `FN31`)
+    pub grid: Option<String>
+}
+impl Filter {
+    /// The topic to subscribe to for this filter.
+    ///
+    /// This fails if a level contains characters that can't be used in a topic, or if nothing is filtered by the topic,
+    /// since that would stream every report uploaded to PSKReporter (many thousands a minute).
+    pub fn topic(&self) -> Result<String, Error> {
+        let band = feed_band(self.band).map(str::to_string);
+        let mode = topic_level(self.mode.as_deref())?;
+        let tx_callsign = topic_level(self.tx_callsign.as_deref())?;
+        let rx_callsign = topic_level(self.rx_callsign.as_deref())?;
+
+        if band.is_none() && mode.is_none() && tx_callsign.is_none() && rx_callsign.is_none() {
+            return Err(Error::Unfiltered);
+        }
+
+        let level = |value: Option<String>| value.unwrap_or_else(|| "+".to_string());
+        Ok(format!("pskr/filter/v2/{}/{}/{}/{}/+/+/+/+", level(band), level(mode), level(tx_callsign), level(rx_callsign)))
+    }
+
+    /// Does the report match the filter?
+    ///
+    /// The topic levels only match whole values, so the grid prefix is checked here instead of in the topic.
+    pub fn matches(&self, report: &Report) -> bool {
+        let Some(grid) = self.grid.as_deref().filter(|g| !g.is_empty()) else {
+            return true;
+        };
+        let prefix = |locator: &str| locator.get(..grid.len()).is_some_and(|p| p.eq_ignore_ascii_case(grid));
+        prefix(&report.tx_grid) || prefix(&report.rx_grid)
+    }
+}
+
+/// Converts a callsign or mode into a topic level, or `None` if it's empty.
+///
+/// The feed replaces the `/` in portable callsigns (e.g. `EA8/DL1ABC`) with `_`, since `/` separates the topic levels.
+/// Anything else that isn't a letter, digit, or `-` (including the `+` and `#` wildcards) is rejected.
+fn topic_level(value: Option<&str>) -> Result<Option<String>, Error> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+
+    if !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '/' || c == '-') {
+        return Err(Error::InvalidLevel(value.to_string()));
+    }
+
+    Ok(Some(value.to_ascii_uppercase().replace('/', "_")))
+}
+
+/// The name of a band in the feed (e.g. `20m`, `70cm`, or `13cm`), which uses the wavelength even for the microwave bands.
+///
+/// Returns `None` for [Band::All].
+fn feed_band(band: Band) -> Option<&'static str> {
+    Some(match band {
+        Band::All => return None,
+        Band::B2200m => "2200m",
+        Band::B630m => "630m",
+        Band::B160m => "160m",
+        Band::B80m => "80m",
+        Band::B60m => "60m",
+        Band::B40m => "40m",
+        Band::B30m => "30m",
+        Band::B20m => "20m",
+        Band::B17m => "17m",
+        Band::B15m => "15m",
+        Band::B12m => "12m",
+        Band::B10m => "10m",
+        Band::B6m => "6m",
+        Band::B2m => "2m",
+        Band::B1_25M => "1.25m",
+        Band::B70CM => "70cm",
+        Band::B33CM => "33cm",
+        Band::B23CM => "23cm",
+        Band::F2_4GHZ => "13cm",
+        Band::F3_4GHZ => "9cm",
+        Band::F5_8GHZ => "6cm",
+        Band::F10GHZ => "3cm",
+        Band::F24GHZ => "1.25cm",
+        Band::F47GHZ => "6mm",
+        Band::F76GHZ => "4mm"
+    })
+}
+
+
+/// The state of the connection to the broker
+#[derive(Debug, Clone, Default)]
+enum Status {
+    /// We're connecting for the first time
+    #[default]
+    Connecting,
+    /// The connection was established
+    Connected,
+    /// The connection was lost, and we're reconnecting
+    Disconnected(String)
+}
+
+/// A connection to a PSKReporter MQTT broker. This reconnects automatically if the connection is lost.
+pub struct Client {
+    /// The address of the broker
+    address: String,
+    /// The reports that are streamed
+    filter: Filter,
+    /// The receiving end of the reports that were received from the broker.
+    /// 
+    /// This is bounded, and the oldest reports are dropped if it isn't emptied (e.g. while the tab is hidden).
+    rx: broadcast::Receiver<Report>,
+    /// The state of the connection
+    status: watch::Receiver<Status>,
+    /// The connection task
+    task: JoinHandle<()>
+}
+impl Client {
+    /// How long to wait before reconnecting if the connection failed
+    const RETRY_DELAY: Duration = Duration::from_secs(10);
+    /// How often to ping the broker to keep the connection alive
+    const KEEP_ALIVE: Duration = Duration::from_secs(30);
+    /// The port that's used if the address doesn't have one
+    const DEFAULT_PORT: u16 = 1883;
+    /// The maximum number of reports that are waiting to be processed. The oldest reports are dropped first.
+    const MAX_QUEUED_REPORTS: usize = 10_000;
+
+    /// Connects to the broker at the provided address (e.g. `mqtt.pskreporter.info:1883`), streaming the reports that match the filter.
+    ///
+    /// This fails if the filter can't be turned into a topic. See [Filter::topic()].
+    pub fn new(address: String, filter: Filter) -> Result<Self, Error> {
+        let topic = filter.topic()?;
+        let (tx, rx) = broadcast::channel(Self::MAX_QUEUED_REPORTS);
+        let (status_tx, status) = watch::channel(Status::default());
+
+        let task = RT.spawn(Self::run(address.clone(), topic, tx, status_tx));
+
+        Ok(Self {
+            address,
+            filter,
+            rx,
+            status,
+            task
+        })
+    }
+
+    /// The address of the broker
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Are we currently connected?
+    pub fn connected(&self) -> bool {
+        matches!(*self.status.borrow(), Status::Connected)
+    }
+
+    /// The reason the last connection failed, if we aren't connected
+    pub fn last_error(&self) -> Option<String> {
+        match &*self.status.borrow() {
+            Status::Disconnected(reason) => Some(reason.clone()),
+            _ => None
+        }
+    }
+
+    /// Starts, stops, or restarts a client so that it matches the provided settings
+    pub fn reconcile(client: Option<Self>, enabled: bool, address: &str, filter: &Filter) -> Result<Option<Self>, Error> {
+        if !enabled {
+            Ok(None)
+        } else if client.as_ref().map_or(true, |c| c.address != address || c.filter != *filter) {
+            Self::new(address.to_string(), filter.clone()).map(Some)
+        } else {
+            Ok(client)
+        }
+    }
+
+    /// Processes the reports received from the broker, returning the ones that match the filter
+    pub fn process(&mut self) -> Vec<Report> {
+        let mut reports = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(report) => {
+                    if self.filter.matches(&report) {
+                        reports.push(report);
+                    }
+                },
+                Err(broadcast::error::TryRecvError::Lagged(dropped)) => debug!("Dropped {dropped} PSKReporter MQTT reports that weren't processed in time"),
+                Err(_) => break
+            }
+        }
+        reports
+    }
+
+    /// The connection task. This connects to the broker and forwards the reports until the client is dropped.
+    async fn run(address: String, topic: String, tx: broadcast::Sender<Report>, status: watch::Sender<Status>) {
+        loop {
+            match Self::connection(&address, &topic, &tx, &status).await {
+                Ok(()) => return,
+                Err(err) => {
+                    debug!("PSKReporter MQTT connection to '{address}' failed: {err}");
+                    if status.send(Status::Disconnected(err.to_string())).is_err() {
+                        return;
+                    }
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// A single connection to the broker. This returns `Ok(())` if the client was dropped, or an error if the connection failed.
+    async fn connection(address: &str, topic: &str, tx: &broadcast::Sender<Report>, status: &watch::Sender<Status>) -> Result<()> {
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().map_err(|_| Error::InvalidAddress(address.to_string()))?),
+            None => (address, Self::DEFAULT_PORT)
+        };
+
+        // Each connection needs a unique client ID, or the broker drops the older connection
+        let mut options = MqttOptions::new(format!("qlog-{:08x}", rand::random::<u32>()), host, port);
+        options.set_keep_alive(Self::KEEP_ALIVE);
+        let (client, mut event_loop) = AsyncClient::new(options, 16);
+
+        loop {
+            match event_loop.poll().await.map_err(Error::Connection)? {
+                // The broker accepted the connection. The session isn't persisted, so we subscribe on every connection.
+                Event::Incoming(Packet::ConnAck(_)) => {
+                    info!("Connected to PSKReporter MQTT broker at '{address}', subscribing to '{topic}'");
+                    client.try_subscribe(topic, QoS::AtMostOnce).map_err(Error::Subscribe)?;
+                    if status.send(Status::Connected).is_err() {
+                        return Ok(());
+                    }
+                },
+                // A report was published
+                Event::Incoming(Packet::Publish(publish)) => {
+                    match serde_json::from_slice::<Report>(&publish.payload) {
+                        Ok(report) => {
+                            if tx.send(report).is_err() {
+                                return Ok(());
+                            }
+                        },
+                        Err(err) => trace!("Ignoring invalid PSKReporter MQTT payload on '{}': {err}", publish.topic)
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+        .field("address", &self.address)
+        .field("filter", &self.filter)
+        .field("status", &*self.status.borrow())
+        .finish()
+    }
+}
+
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid broker address '{0}', expected host:port")]
+    InvalidAddress(String),
+    #[error("Connection error: {0}")]
+    Connection(rumqttc::ConnectionError),
+    #[error("Failed to subscribe: {0}")]
+    Subscribe(rumqttc::ClientError),
+    #[error("Enter a callsign, band, or mode to filter the live feed")]
+    Unfiltered,
+    #[error("'{0}' can't be used in the live feed filter")]
+    InvalidLevel(String)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A report published by the feed, including the fields that aren't decoded
+    const PAYLOAD: &str = r#"{"sq":30142870791,"f":14075836,"md":"FT8","rp":-12,"t":1662407712,"sc":"EA8/DL1ABC","sl":"IL18DG","rc":"K1ABC","rl":"FN42ET","sa":29,"ra":291,"b":"20m"}"#;
+
+    /// The address of the local broker used by the integration test
+    const LOCAL_BROKER: &str = "127.0.0.1:1883";
+
+    /// A filter for the callsign and nothing else
+    fn callsign_filter(callsign: &str) -> Filter {
+        Filter { band: Band::All, mode: None, tx_callsign: Some(callsign.into()), rx_callsign: None, grid: None }
+    }
+
+    #[test]
+    fn report_is_decoded() {
+        let report: Report = serde_json::from_str(PAYLOAD).unwrap();
+        assert_eq!(report.sequence, 30142870791);
+        assert_eq!(report.frequency, 14_075_836);
+        assert_eq!(report.mode, "FT8");
+        assert_eq!(report.snr, -12);
+        assert_eq!(report.time, 1662407712);
+        assert_eq!((report.tx_callsign.as_str(), report.tx_grid.as_str()), ("EA8/DL1ABC", "IL18DG"));
+        assert_eq!((report.rx_callsign.as_str(), report.rx_grid.as_str()), ("K1ABC", "FN42ET"));
+
+        // Missing fields use the defaults, and invalid payloads are rejected
+        let report: Report = serde_json::from_str(r#"{"sc":"W1AW","f":7074000}"#).unwrap();
+        assert_eq!((report.tx_callsign.as_str(), report.frequency, report.rx_callsign.as_str()), ("W1AW", 7_074_000, ""));
+        assert!(serde_json::from_str::<Report>(r#"{"f":"14074000"}"#).is_err());
+    }
+
+    #[test]
+    fn topic_filters_levels() {
+        let filter = Filter {
+            band: Band::B20m,
+            mode: Some("FT8".into()),
+            tx_callsign: None,
+            rx_callsign: Some("k1abc".into()),
+            grid: Some("FN".into())
+        };
+        assert_eq!(filter.topic().unwrap(), "pskr/filter/v2/20m/FT8/+/K1ABC/+/+/+/+");
+
+        // Portable callsigns stay in a single level
+        assert_eq!(callsign_filter("EA8/DL1ABC").topic().unwrap(), "pskr/filter/v2/+/+/EA8_DL1ABC/+/+/+/+/+");
+    }
+
+    #[test]
+    fn topic_uses_feed_band_names() {
+        let topic = |band| Filter { band, mode: None, tx_callsign: None, rx_callsign: None, grid: None }.topic().unwrap();
+        assert_eq!(topic(Band::B160m), "pskr/filter/v2/160m/+/+/+/+/+/+/+");
+        assert_eq!(topic(Band::B1_25M), "pskr/filter/v2/1.25m/+/+/+/+/+/+/+");
+        assert_eq!(topic(Band::B70CM), "pskr/filter/v2/70cm/+/+/+/+/+/+/+");
+        assert_eq!(topic(Band::F2_4GHZ), "pskr/filter/v2/13cm/+/+/+/+/+/+/+");
+        assert_eq!(topic(Band::F10GHZ), "pskr/filter/v2/3cm/+/+/+/+/+/+/+");
+    }
+
+    #[test]
+    fn topic_rejects_invalid_filters() {
+        // Nothing is filtered, so the whole feed would be streamed. The grid isn't part of the topic.
+        let unfiltered = Filter { band: Band::All, mode: Some(" ".into()), tx_callsign: Some(String::new()), rx_callsign: None, grid: Some("FN".into()) };
+        assert!(matches!(unfiltered.topic(), Err(Error::Unfiltered)));
+
+        // Wildcards and other characters can't be used in a level
+        for callsign in ["K1+", "#", "K1ABC/+", "K1 ABC", "pskr/#"] {
+            assert!(matches!(callsign_filter(callsign).topic(), Err(Error::InvalidLevel(_))), "{callsign} should be rejected");
+        }
+    }
+
+    #[test]
+    fn matches_grid_prefix() {
+        let report: Report = serde_json::from_str(PAYLOAD).unwrap();
+        let filter = |grid: Option<&str>| Filter { grid: grid.map(str::to_string), ..callsign_filter("K1ABC") };
+
+        assert!(filter(None).matches(&report));
+        assert!(filter(Some("")).matches(&report));
+        assert!(filter(Some("FN42")).matches(&report));
+        assert!(filter(Some("il18dg")).matches(&report));
+        assert!(!filter(Some("FN31")).matches(&report));
+        assert!(!filter(Some("FN42ETXX")).matches(&report));
+    }
+
+    #[test]
+    #[ignore = "needs an MQTT broker listening on 127.0.0.1:1883, e.g. `mosquitto`"]
+    fn reports_are_streamed_from_local_broker() {
+        let filter = Filter { band: Band::B20m, mode: Some("FT8".into()), ..callsign_filter("EA8/DL1ABC") };
+        let mut client = Client::new(LOCAL_BROKER.to_string(), filter).unwrap();
+
+        // Publish a report that doesn't match the filter, and one that does, like the feed would
+        let (publisher, mut event_loop) = AsyncClient::new(MqttOptions::new("qlog-test-publisher", "127.0.0.1", 1883), 16);
+        RT.spawn(async move { while event_loop.poll().await.is_ok() {} });
+
+        // The client might not have subscribed yet, so keep publishing until the report arrives
+        let mut reports = Vec::new();
+        for _ in 0..50 {
+            RT.block_on(async {
+                publisher.publish("pskr/filter/v2/40m/FT8/EA8_DL1ABC/K1ABC/IL18/FN42/29/291", QoS::AtLeastOnce, false, PAYLOAD.replace("14075836", "7074000")).await.unwrap();
+                publisher.publish("pskr/filter/v2/20m/FT8/EA8_DL1ABC/K1ABC/IL18/FN42/29/291", QoS::AtLeastOnce, false, PAYLOAD).await.unwrap();
+            });
+            std::thread::sleep(Duration::from_millis(200));
+
+            reports = client.process();
+            if !reports.is_empty() {
+                break;
+            }
+        }
+
+        assert!(client.connected());
+        assert!(!reports.is_empty(), "No reports were received from the local broker");
+        assert!(reports.iter().all(|r| r.frequency == 14_075_836 && r.tx_callsign == "EA8/DL1ABC"));
+    }
+}
@@ -0,0 +1,195 @@
+//
+// A client for the Reverse Beacon Network (RBN) telnet feed.
+//
+// The RBN is a network of CW/RTTY skimmers (SDR receivers that decode every signal on a band) that post spots to a telnet server.
+// The feed uses the DX cluster spot format, with the signal report in the comment:
+// `DX de KM3T-#:     14025.0  W1AW           CW    18 dB  25 WPM  CQ      1234Z`
+// Port 7000 carries CW and RTTY spots, and port 7001 carries FT8 and FT4 spots.
+//
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::GuiConfig;
+use super::{dxcluster, types};
+
+
+/// A spot posted by an RBN skimmer
+#[derive(Debug, Clone)]
+pub struct Spot {
+    /// The skimmer that heard the station, without the `-#` suffix (e.g. `DK9IP-2`)
+    pub skimmer: String,
+    /// The callsign of the station that was heard
+    pub callsign: String,
+    /// The frequency of the station, in Hz
+    pub frequency: u64,
+    /// The mode of the station
+    pub mode: types::Mode,
+    /// The signal to noise ratio, in dB
+    pub snr: i32,
+    /// The speed of the station, in WPM for CW or baud for RTTY
+    pub speed: Option<u32>,
+    /// What the station was sending (e.g. `CQ`, `BEACON`, or `NCDXF B`)
+    pub kind: String,
+    /// The time of the spot, in UTC
+    pub time: NaiveDateTime
+}
+impl Spot {
+    /// Converts a DX cluster spot into an RBN spot, returning `None` if the comment doesn't contain a signal report
+    pub fn from_cluster_spot(spot: &dxcluster::Spot) -> Option<Self> {
+        let tokens: Vec<&str> = spot.comment.split_whitespace().collect();
+
+        // The mode is always first, and the SNR is followed by `dB`
+        let mode = types::Mode::from_name(tokens.first()?);
+        let db = tokens.iter().position(|t| t.eq_ignore_ascii_case("dB"))?;
+        let snr = tokens.get(db.checked_sub(1)?)?.parse::<i32>().ok()?;
+
+        // The speed is followed by `WPM` or `BPS`, and isn't sent for digital modes
+        let unit = tokens.iter().position(|t| t.eq_ignore_ascii_case("WPM") || t.eq_ignore_ascii_case("BPS"));
+        let speed = unit.and_then(|idx| tokens.get(idx.checked_sub(1)?)?.parse::<u32>().ok());
+
+        // Anything after the report is the type of transmission
+        let kind = tokens[unit.unwrap_or(db).max(db) + 1..].join(" ");
+
+        Some(Self {
+            skimmer: spot.spotter.trim_end_matches("-#").to_string(),
+            callsign: spot.callsign.clone(),
+            frequency: spot.frequency,
+            mode,
+            snr,
+            speed,
+            kind,
+            time: spot.time
+        })
+    }
+
+    /// The callsign of the skimmer's operator, without the skimmer number (e.g. `DK9IP` for `DK9IP-2`)
+    pub fn skimmer_callsign(&self) -> &str {
+        self.skimmer.split('-').next().unwrap_or(&self.skimmer)
+    }
+
+    /// The band of the spot
+    pub fn band(&self) -> types::Band {
+        types::Band::from_frequency(self.frequency)
+    }
+}
+
+/// Processes any spots received from the RBN, connecting or disconnecting depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the client out of the config so we can mutate the rest of the config while processing messages, connecting, disconnecting, or reconnecting if the config changed
+    let callsign = config.station.callsign.trim().to_ascii_uppercase();
+    let client = dxcluster::Client::reconcile(config.rbn.take(), config.rbn_config.enabled, &config.rbn_config.address, &callsign);
+
+    let Some(mut client) = client else {
+        return;
+    };
+
+    // Send the spots to the tabs
+    for spot in client.process().iter().filter_map(Spot::from_cluster_spot) {
+        config.events.push_back((None, types::Event::RbnSpot(spot)));
+    }
+
+    // Put the client back into the config
+    config.rbn = Some(client);
+
+}
+
+
+/// The RBN module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should we connect to the RBN?
+    pub enabled: bool,
+    /// The address of the RBN telnet server
+    pub address: String
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "telnet.reversebeacon.net:7000".into()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use super::*;
+
+    /// Parses a skimmer line from the RBN telnet server
+    fn parse(line: &str) -> Option<Spot> {
+        Spot::from_cluster_spot(&dxcluster::Spot::parse(line).unwrap())
+    }
+
+    /// An RBN spot with the provided comment
+    fn comment(comment: &str) -> Option<Spot> {
+        Spot::from_cluster_spot(&dxcluster::Spot {
+            spotter: "DK9IP-#".into(),
+            frequency: 14_025_000,
+            callsign: "JA1XYZ".into(),
+            comment: comment.into(),
+            time: NaiveDate::from_ymd_opt(2026, 10, 17).unwrap().and_hms_opt(12, 34, 0).unwrap()
+        })
+    }
+
+    #[test]
+    fn cw_spot_is_parsed() {
+        let spot = parse("DX de DK9IP-2-#:  14025.0  JA1XYZ         CW    12 dB  22 WPM  CQ      1234Z").unwrap();
+        assert_eq!(spot.skimmer, "DK9IP-2");
+        assert_eq!(spot.skimmer_callsign(), "DK9IP");
+        assert_eq!(spot.callsign, "JA1XYZ");
+        assert_eq!(spot.frequency, 14_025_000);
+        assert_eq!(spot.band(), types::Band::B20m);
+        assert_eq!(spot.mode, types::Mode::CW);
+        assert_eq!(spot.snr, 12);
+        assert_eq!(spot.speed, Some(22));
+        assert_eq!(spot.kind, "CQ");
+
+        // Beacons have a two word type
+        let spot = parse("DX de VE7CC-#:    14100.0  4X6TU          CW    -3 dB  22 WPM  NCDXF B 2359Z").unwrap();
+        assert_eq!((spot.snr, spot.speed, spot.kind.as_str()), (-3, Some(22), "NCDXF B"));
+    }
+
+    #[test]
+    fn rtty_spot_is_parsed() {
+        let spot = parse("DX de W3OA-#:     14080.9  K1ABC          RTTY  15 dB  45 BPS  CQ      0102Z").unwrap();
+        assert_eq!(spot.mode, types::Mode::RTTY);
+        assert_eq!((spot.snr, spot.speed, spot.kind.as_str()), (15, Some(45), "CQ"));
+    }
+
+    #[test]
+    fn digital_spots_have_no_speed() {
+        let spot = parse("DX de KM3T-#:     14074.0  EA8XYZ         FT8   -9 dB         CQ      1234Z").unwrap();
+        assert_eq!(spot.mode, types::Mode::FT8);
+        assert_eq!((spot.snr, spot.speed, spot.kind.as_str()), (-9, None, "CQ"));
+
+        let spot = parse("DX de KM3T-#:      7047.5  EA8XYZ         FT4   -15 dB        CQ      1234Z").unwrap();
+        assert_eq!(spot.mode, types::Mode::FT4);
+        assert_eq!((spot.snr, spot.speed), (-15, None));
+
+        // Modes that aren't known are kept by name
+        let spot = comment("PSK63 7 dB CQ").unwrap();
+        assert_eq!(spot.mode, types::Mode::OTHER("PSK63".into()));
+    }
+
+    #[test]
+    fn malformed_comments_are_ignored() {
+        // Human spots and comments without a signal report
+        for text in ["", "   ", "CQ up 2", "CW 12 22 WPM CQ", "dB", "CW dB", "CW twelve dB 22 WPM CQ", "CW 12.5 dB"] {
+            assert!(comment(text).is_none(), "{text:?}");
+        }
+
+        // A report without a speed or type is still a spot
+        let spot = comment("CW 12 dB").unwrap();
+        assert_eq!((spot.snr, spot.speed, spot.kind.as_str()), (12, None, ""));
+        let spot = comment("CW 12 dB WPM").unwrap();
+        assert_eq!((spot.snr, spot.speed, spot.kind.as_str()), (12, None, ""));
+        let spot = comment("CW 12 dB fast WPM CQ").unwrap();
+        assert_eq!((spot.speed, spot.kind.as_str()), (None, "CQ"));
+    }
+}
@@ -0,0 +1,163 @@
+//
+// A minimal client for the hamlib rigctld TCP protocol.
+//
+// Commands are sent as lines of text (e.g. `F 14074000`), and set commands are answered with `RPRT <code>`, where a code of 0 means success.
+// rigctld listens on port 4532 by default.
+//
+
+use std::time::Duration;
+use anyhow::Result;
+use log::{error, trace};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::TcpStream};
+use crate::{GuiConfig, RT};
+use super::types;
+
+
+/// A connection to rigctld
+#[derive(Debug)]
+pub struct Connection {
+    /// The TCP stream
+    stream: BufReader<TcpStream>
+}
+impl Connection {
+    /// How long to wait for a response from rigctld
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Connects to rigctld at the provided address (e.g. `127.0.0.1:4532`)
+    pub async fn connect(address: &str) -> Result<Self> {
+        let stream = tokio::time::timeout(Self::TIMEOUT, TcpStream::connect(address)).await
+            .map_err(|_| Error::Timeout)?
+            .map_err(Error::Io)?;
+
+        Ok(Self { stream: BufReader::new(stream) })
+    }
+
+    /// Sends a set command (e.g. `F 14074000`), returning an error if rigctld rejected it
+    pub async fn set(&mut self, command: &str) -> Result<()> {
+        trace!("Sending rigctld command: {command}");
+        self.stream.write_all(format!("{command}\n").as_bytes()).await.map_err(Error::Io)?;
+
+        // Read lines until we get the response code
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = tokio::time::timeout(Self::TIMEOUT, self.stream.read_line(&mut line)).await
+                .map_err(|_| Error::Timeout)?
+                .map_err(Error::Io)?;
+            if read == 0 {
+                return Err(Error::Disconnected)?;
+            }
+
+            if let Some(code) = line.trim().strip_prefix("RPRT") {
+                return match code.trim().parse::<i32>() {
+                    Ok(0) => Ok(()),
+                    Ok(code) => Err(Error::Rejected(code))?,
+                    Err(_) => Err(Error::InvalidResponse(line.trim().to_string()))?
+                };
+            }
+        }
+    }
+
+    /// Sets the frequency of the radio, in Hz
+    pub async fn set_frequency(&mut self, frequency: u64) -> Result<()> {
+        self.set(&format!("F {frequency}")).await
+    }
+
+    /// Sends text as morse code using the radio's internal keyer
+    pub async fn send_morse(&mut self, text: &str) -> Result<()> {
+        self.set(&format!("b {text}")).await
+    }
+
+    /// Stops sending morse code
+    pub async fn stop_morse(&mut self) -> Result<()> {
+        self.set("\\stop_morse").await
+    }
+
+    /// Sets the speed of the radio's internal keyer, in words per minute
+    pub async fn set_keyer_speed(&mut self, wpm: u8) -> Result<()> {
+        self.set(&format!("L KEYSPD {wpm}")).await
+    }
+}
+
+
+/// Sends one-off commands to the radio (e.g. tuning to a spot), reporting any errors to the GUI
+#[derive(Default)]
+pub struct Rig {
+    /// The commands that are currently running
+    tasks: Vec<Promise<Result<()>>>
+}
+impl Rig {
+    /// Tunes the radio to a frequency, in Hz
+    pub fn tune(&mut self, address: &str, frequency: u64) {
+        let address = address.to_string();
+        let _eg = RT.enter();
+        self.tasks.push(Promise::spawn_async(async move {
+            let mut connection = Connection::connect(&address).await?;
+            connection.set_frequency(frequency).await
+        }));
+    }
+}
+impl std::fmt::Debug for Rig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rig")
+        .field("tasks", &self.tasks.len())
+        .finish()
+    }
+}
+
+/// Handles a tune event, if rig control is enabled
+pub fn tune(config: &mut GuiConfig, frequency: u64) {
+    if config.rig_config.enabled {
+        config.rig.tune(&config.rig_config.address, frequency);
+    }
+}
+
+/// Processes any finished rig commands, showing a notification if they failed.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+    for task in config.rig.tasks.extract_if(|t| t.ready().is_some()) {
+        if let Err(err) = task.block_and_take() {
+            error!("Rig command failed: {err}");
+            config.notification_read = false;
+            config.notifications.push(types::Notification::Error(format!("Failed to control the radio: {err}")));
+        }
+    }
+}
+
+
+/// The rig control config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should the radio be tuned when a spot is clicked?
+    pub enabled: bool,
+    /// The address of rigctld. This is also used by the CW keyer.
+    pub address: String
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:4532".into()
+        }
+    }
+}
+
+/// Errors regarding the rigctld client
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Connection error: {0}")]
+    Io(std::io::Error),
+    #[error("Timed out waiting for rigctld")]
+    Timeout,
+    #[error("rigctld closed the connection")]
+    Disconnected,
+    #[error("rigctld rejected the command (code {0})")]
+    Rejected(i32),
+    #[error("Invalid response from rigctld: {0}")]
+    InvalidResponse(String)
+}
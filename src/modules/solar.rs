@@ -0,0 +1,678 @@
+//
+// Fetches solar and space weather information from the hamqsl solar XML feed (https://www.hamqsl.com/solar.html)
+// and the NOAA Space Weather Prediction Center JSON products (https://services.swpc.noaa.gov).
+//
+// The URLs are configurable, so a local stand-in can serve fixtures (e.g. `python -m http.server` in a folder that mirrors the paths below).
+// The last successful response is cached next to the exe file, so the tab has something to show when starting offline.
+//
+
+use std::{env::current_exe, path::PathBuf, time::{Duration, Instant}};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{debug, error, warn};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use crate::{GuiConfig, RT};
+use super::types;
+
+
+/// The name of the cache file, relative to the exe file
+const CACHE_FILE: &str = "solar_cache.json";
+
+/// The planetary K index for the last 7 days, in 3 hour intervals
+const PATH_KP: &str = "/products/noaa-planetary-k-index.json";
+/// The GOES X-ray flux for the last 7 days
+const PATH_XRAY: &str = "/json/goes/primary/xrays-7-day.json";
+/// The solar wind density and speed for the last 7 days
+const PATH_PLASMA: &str = "/products/solar-wind/plasma-7-day.json";
+/// The interplanetary magnetic field for the last 7 days
+const PATH_MAG: &str = "/products/solar-wind/mag-7-day.json";
+/// The current NOAA space weather scales
+const PATH_SCALES: &str = "/products/noaa-scales.json";
+
+
+/// A single value of a time series
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Sample {
+    /// The time of the sample, in seconds since the UNIX epoch
+    pub time: i64,
+    /// The value of the sample
+    pub value: f64
+}
+
+/// The estimated conditions of a group of bands, from the hamqsl feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandCondition {
+    /// The bands (e.g. `80m-40m`)
+    pub bands: String,
+    /// The condition during the day
+    pub day: String,
+    /// The condition during the night
+    pub night: String
+}
+
+/// The current solar conditions, from the hamqsl feed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Summary {
+    /// When the feed was updated (e.g. `18 Oct 2026 1200 GMT`)
+    pub updated: String,
+    /// The solar flux index
+    pub sfi: Option<f64>,
+    /// The sunspot number
+    pub ssn: Option<f64>,
+    /// The A index
+    pub a_index: Option<f64>,
+    /// The K index
+    pub k_index: Option<f64>,
+    /// The X-ray flux class (e.g. `B5.2`)
+    pub xray: String,
+    /// The solar wind speed, in km/s
+    pub solar_wind: Option<f64>,
+    /// The Bz component of the interplanetary magnetic field, in nT
+    pub bz: Option<f64>,
+    /// The state of the geomagnetic field (e.g. `QUIET`)
+    pub geomagnetic_field: String,
+    /// The estimated noise level (e.g. `S0-S1`)
+    pub signal_noise: String,
+    /// The estimated conditions of each group of bands
+    pub bands: Vec<BandCondition>
+}
+
+/// The current NOAA space weather scales, from 0 (none) to 5 (extreme)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Scales {
+    /// The radio blackout scale
+    pub r: u8,
+    /// The solar radiation storm scale
+    pub s: u8,
+    /// The geomagnetic storm scale
+    pub g: u8
+}
+
+/// Solar and space weather information. Each source is optional, since one may fail while the others succeed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conditions {
+    /// When the information was fetched
+    pub fetched: Option<DateTime<Utc>>,
+    /// The current conditions from the hamqsl feed
+    pub summary: Option<Summary>,
+    /// The planetary K index
+    pub kp: Vec<Sample>,
+    /// The long wavelength (0.1-0.8nm) X-ray flux, in W/m²
+    pub xray: Vec<Sample>,
+    /// The solar wind speed, in km/s
+    pub wind_speed: Vec<Sample>,
+    /// The Bz component of the interplanetary magnetic field, in nT
+    pub bz: Vec<Sample>,
+    /// The current NOAA space weather scales
+    pub scales: Option<Scales>
+}
+impl Conditions {
+    /// Fetches every source, returning an error only if all of them failed
+    async fn fetch(hamqsl_url: String, swpc_url: String) -> Result<Self> {
+        let swpc_url = swpc_url.trim_end_matches('/');
+
+        let (summary, kp, xray, plasma, mag, scales) = tokio::join!(
+            fetch_summary(&hamqsl_url),
+            fetch_json(format!("{swpc_url}{PATH_KP}")),
+            fetch_json(format!("{swpc_url}{PATH_XRAY}")),
+            fetch_json(format!("{swpc_url}{PATH_PLASMA}")),
+            fetch_json(format!("{swpc_url}{PATH_MAG}")),
+            fetch_json(format!("{swpc_url}{PATH_SCALES}"))
+        );
+
+        // Keep the sources that succeeded, logging the ones that didn't
+        let mut errors = Vec::new();
+        let summary = keep("the hamqsl solar feed", summary, &mut errors);
+        let kp = keep("the K index", kp, &mut errors);
+        let xray = keep("the X-ray flux", xray, &mut errors);
+        let plasma = keep("the solar wind plasma", plasma, &mut errors);
+        let mag = keep("the solar wind magnetic field", mag, &mut errors);
+        let scales = keep("the NOAA scales", scales, &mut errors);
+
+        // Every source failed
+        if errors.len() == 6 {
+            return Err(errors.remove(0));
+        }
+
+        Ok(Self {
+            fetched: Some(Utc::now()),
+            summary,
+            kp: kp.map(|v| series(&v, "time_tag", "kp", None)).unwrap_or_default(),
+            xray: xray.map(|v| xray_series(&v)).unwrap_or_default(),
+            wind_speed: plasma.map(|v| series(&v, "time_tag", "speed", None)).unwrap_or_default(),
+            bz: mag.map(|v| series(&v, "time_tag", "bz_gsm", None)).unwrap_or_default(),
+            scales: scales.as_ref().and_then(parse_scales)
+        })
+    }
+
+    /// The current geomagnetic storm scale, using the K index if the NOAA scales aren't available
+    pub fn geomagnetic_scale(&self) -> Option<u8> {
+        if let Some(scales) = self.scales {
+            return Some(scales.g);
+        }
+        let kp = self.kp.last().map(|s| s.value).or_else(|| self.summary.as_ref()?.k_index)?;
+        Some((kp.floor() as i64 - 4).clamp(0, 5) as u8)
+    }
+
+    /// The current radio blackout scale, using the X-ray flux if the NOAA scales aren't available
+    pub fn radio_blackout_scale(&self) -> Option<u8> {
+        if let Some(scales) = self.scales {
+            return Some(scales.r);
+        }
+        let flux = self.xray.last()?.value;
+        Some(match flux {
+            f if f >= 2e-3 => 5,
+            f if f >= 1e-3 => 4,
+            f if f >= 1e-4 => 3,
+            f if f >= 5e-5 => 2,
+            f if f >= 1e-5 => 1,
+            _ => 0
+        })
+    }
+
+    /// Loads the cached conditions
+    fn load_cache() -> Result<Self> {
+        let data = std::fs::read(cache_path()).map_err(Error::Io)?;
+        Ok(serde_json::from_slice(&data).map_err(Error::Json)?)
+    }
+
+    /// Saves the conditions to the cache
+    async fn save_cache(&self) -> Result<()> {
+        let data = serde_json::to_vec(self).map_err(Error::Json)?;
+        tokio::fs::write(cache_path(), data).await.map_err(Error::Io)?;
+        Ok(())
+    }
+}
+
+/// The path of the cache file, which is next to the exe file
+fn cache_path() -> PathBuf {
+    let exe_path = current_exe().expect("Failed to get path of exe file");
+    let exe_dir = exe_path.parent().expect("Failed to get parent directory of exe file");
+    exe_dir.join(CACHE_FILE)
+}
+
+/// Returns the value if the request succeeded, otherwise logs the error and adds it to `errors`
+fn keep<T>(name: &str, result: Result<T>, errors: &mut Vec<anyhow::Error>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Failed to fetch {name}: {err}");
+            errors.push(err);
+            None
+        }
+    }
+}
+
+/// Fetches a JSON product
+async fn fetch_json(url: String) -> Result<Value> {
+    debug!("Fetching {url}");
+    let response = reqwest::get(url).await.map_err(Error::Request)?
+        .error_for_status().map_err(Error::Request)?
+        .json::<Value>().await.map_err(Error::Request)?;
+    Ok(response)
+}
+
+/// Fetches and parses the hamqsl solar XML feed
+async fn fetch_summary(url: &str) -> Result<Summary> {
+    debug!("Fetching {url}");
+    let text = reqwest::get(url).await.map_err(Error::Request)?
+        .error_for_status().map_err(Error::Request)?
+        .text().await.map_err(Error::Request)?;
+
+    let raw: RawSolar = serde_xml_rs::from_str(&text).map_err(Error::Deserialize)?;
+    Ok(raw.solardata.into())
+}
+
+/// Parses a time series from a SWPC product.
+///
+/// Products are either a list of objects (e.g. `[{"time_tag": "...", "flux": 1e-6}]`),
+/// or a table where the first row contains the column names (e.g. `[["time_tag", "speed"], ["...", "400.1"]]`).
+/// Values may be numbers or strings. If a filter is provided, only the rows where that column has that value are used.
+fn series(value: &Value, time_key: &str, value_key: &str, filter: Option<(&str, &str)>) -> Vec<Sample> {
+    let Some(rows) = value.as_array() else {
+        return Vec::new();
+    };
+
+    // Gets a column from a row, using the header to find the index of the column if the rows are arrays
+    let header: Option<Vec<String>> = rows.first()
+        .and_then(Value::as_array)
+        .map(|h| h.iter().map(|c| c.as_str().unwrap_or_default().to_ascii_lowercase()).collect());
+    let column = |row: &Value, key: &str| -> Option<Value> {
+        match &header {
+            Some(header) => row.get(header.iter().position(|c| c == key)?).cloned(),
+            None => row.as_object()?.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.clone())
+        }
+    };
+
+    let mut samples: Vec<Sample> = rows.iter()
+        .skip(header.is_some() as usize)
+        .filter(|row| filter.map_or(true, |(key, expected)| column(row, key).is_some_and(|v| v.as_str() == Some(expected))))
+        .filter_map(|row| Some(Sample {
+            time: parse_time(column(row, time_key)?.as_str()?)?,
+            value: as_number(&column(row, value_key)?)?
+        }))
+        .collect();
+
+    samples.sort_by_key(|s| s.time);
+    samples
+}
+
+/// Parses the long wavelength X-ray flux. Missing measurements are reported as zero (or less), so they're removed.
+fn xray_series(value: &Value) -> Vec<Sample> {
+    let mut samples = series(value, "time_tag", "flux", Some(("energy", "0.1-0.8nm")));
+    samples.retain(|s| s.value > 0.0);
+    samples
+}
+
+/// Parses the current NOAA scales. The current values are under the `0` key, and the others are forecasts.
+fn parse_scales(value: &Value) -> Option<Scales> {
+    let current = value.get("0")?;
+    let scale = |key: &str| current.get(key).and_then(|s| s.get("Scale")).and_then(as_number).unwrap_or_default() as u8;
+    Some(Scales {
+        r: scale("R"),
+        s: scale("S"),
+        g: scale("G")
+    })
+}
+
+/// Gets a number from a JSON value that may be a number or a string
+fn as_number(value: &Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str()?.trim().parse().ok())
+}
+
+/// Parses a SWPC time (e.g. `2026-10-18 12:00:00.000` or `2026-10-18T12:00:00Z`) into seconds since the UNIX epoch
+fn parse_time(s: &str) -> Option<i64> {
+    let s = s.trim().trim_end_matches('Z').replace('T', " ");
+    NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M"))
+        .ok()
+        .map(|t| t.and_utc().timestamp())
+}
+
+
+/// Periodically fetches the solar conditions, raising notifications for geomagnetic storms and radio blackouts
+#[derive(Default)]
+pub struct Monitor {
+    /// The most recent conditions, which may have come from the cache
+    conditions: Option<Conditions>,
+    /// Did the conditions come from the cache?
+    cached: bool,
+    /// The task that is fetching the conditions
+    task: Option<Promise<Result<Conditions>>>,
+    /// When the conditions were last fetched. This is updated when the fetch finishes, not when it starts.
+    last_fetch: Option<Instant>,
+    /// The task that is loading the cache
+    cache_task: Option<Promise<Result<Conditions>>>,
+    /// Has the cache been loaded?
+    loaded: bool,
+    /// The geomagnetic storm and radio blackout scales that the user was last notified about
+    notified: (u8, u8)
+}
+impl Monitor {
+    /// The most recent conditions, if any
+    pub fn conditions(&self) -> Option<&Conditions> {
+        self.conditions.as_ref()
+    }
+
+    /// Did the conditions come from the cache?
+    pub fn cached(&self) -> bool {
+        self.cached
+    }
+
+    /// Are the conditions currently being fetched?
+    pub fn fetching(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Fetches the conditions now, unless they're already being fetched
+    pub fn refresh(&mut self, config: &Config) {
+        if self.task.is_none() {
+            let _eg = RT.enter();
+            let (hamqsl_url, swpc_url) = (config.hamqsl_url.clone(), config.swpc_url.clone());
+            self.task = Some(Promise::spawn_async(async move {
+                let conditions = Conditions::fetch(hamqsl_url, swpc_url).await?;
+
+                // The cache is saved here so the file isn't written on the GUI thread
+                if let Err(err) = conditions.save_cache().await {
+                    warn!("Failed to save the solar conditions cache: {err}");
+                }
+                Ok(conditions)
+            }));
+        }
+    }
+}
+impl std::fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Monitor")
+        .field("fetched", &self.conditions.as_ref().and_then(|c| c.fetched))
+        .field("cached", &self.cached)
+        .field("fetching", &self.task.is_some())
+        .finish()
+    }
+}
+
+/// Loads the cached conditions, fetches new conditions on a schedule, and raises notifications.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+    let monitor = &mut config.solar;
+
+    // Load the cache the first time this is called, so there's something to show before the first fetch finishes
+    if !monitor.loaded {
+        monitor.loaded = true;
+        let _eg = RT.enter();
+        monitor.cache_task = Some(Promise::spawn_blocking(Conditions::load_cache));
+    }
+    if let Some(task) = monitor.cache_task.take_if(|t| t.ready().is_some()) {
+        match task.block_and_take() {
+            // The first fetch may have finished before the cache was loaded
+            Ok(conditions) if monitor.conditions.is_none() => {
+                monitor.conditions = Some(conditions);
+                monitor.cached = true;
+            },
+            Ok(_) => {},
+            Err(err) => debug!("Failed to load the solar conditions cache: {err}")
+        }
+    }
+
+    if !config.solar_config.enabled {
+        return;
+    }
+
+    // Fetch the conditions if the refresh interval has elapsed
+    let refresh = Duration::from_secs(config.solar_config.refresh_rate.max(Config::MIN_REFRESH_RATE) * 60);
+    if monitor.task.is_none() && monitor.last_fetch.map_or(true, |t| t.elapsed() >= refresh) {
+        monitor.refresh(&config.solar_config);
+    }
+
+    // Process the finished fetch
+    let Some(task) = monitor.task.take_if(|t| t.ready().is_some()) else {
+        return;
+    };
+    monitor.last_fetch = Some(Instant::now());
+
+    let conditions = match task.block_and_take() {
+        Ok(conditions) => conditions,
+        Err(err) => {
+            error!("Failed to fetch the solar conditions: {err}");
+            return;
+        }
+    };
+
+    // Notify the user when a storm or blackout starts or gets worse
+    let storm = conditions.geomagnetic_scale().unwrap_or_default();
+    let blackout = conditions.radio_blackout_scale().unwrap_or_default();
+    if config.solar_config.notify && storm >= config.solar_config.storm_threshold.max(1) && storm > monitor.notified.0 {
+        config.notification_read = false;
+        config.notifications.push(types::Notification::Warning(format!("Geomagnetic storm: G{storm} ({})", scale_name(storm))));
+    }
+    if config.solar_config.notify && blackout >= config.solar_config.blackout_threshold.max(1) && blackout > monitor.notified.1 {
+        config.notification_read = false;
+        config.notifications.push(types::Notification::Warning(format!("HF radio blackout: R{blackout} ({})", scale_name(blackout))));
+    }
+    monitor.notified = (storm, blackout);
+
+    monitor.conditions = Some(conditions);
+    monitor.cached = false;
+}
+
+/// The name of a NOAA scale level
+pub fn scale_name(level: u8) -> &'static str {
+    match level {
+        0 => "none",
+        1 => "minor",
+        2 => "moderate",
+        3 => "strong",
+        4 => "severe",
+        _ => "extreme"
+    }
+}
+
+
+/// The root of the hamqsl solar XML feed
+#[derive(Debug, Deserialize)]
+struct RawSolar {
+    solardata: RawSolarData
+}
+
+/// The solar data in the hamqsl feed. Every value is a string, since missing values are reported as text (e.g. `No Report`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawSolarData {
+    updated: String,
+    solarflux: String,
+    aindex: String,
+    kindex: String,
+    xray: String,
+    sunspots: String,
+    solarwind: String,
+    magneticfield: String,
+    geomagfield: String,
+    signalnoise: String,
+    calculatedconditions: RawConditions
+}
+impl From<RawSolarData> for Summary {
+    fn from(raw: RawSolarData) -> Self {
+        let number = |s: &str| s.trim().parse::<f64>().ok();
+
+        // Each group of bands is listed twice, once for the day and once for the night
+        let mut bands: Vec<BandCondition> = Vec::new();
+        for band in raw.calculatedconditions.band {
+            let index = match bands.iter().position(|b| b.bands == band.name) {
+                Some(index) => index,
+                None => {
+                    bands.push(BandCondition { bands: band.name.clone(), day: String::new(), night: String::new() });
+                    bands.len() - 1
+                }
+            };
+            match band.time.as_str() {
+                "day" => bands[index].day = band.condition.trim().to_string(),
+                _ => bands[index].night = band.condition.trim().to_string()
+            }
+        }
+
+        Self {
+            updated: raw.updated.trim().to_string(),
+            sfi: number(&raw.solarflux),
+            ssn: number(&raw.sunspots),
+            a_index: number(&raw.aindex),
+            k_index: number(&raw.kindex),
+            xray: raw.xray.trim().to_string(),
+            solar_wind: number(&raw.solarwind),
+            bz: number(&raw.magneticfield),
+            geomagnetic_field: raw.geomagfield.trim().to_string(),
+            signal_noise: raw.signalnoise.trim().to_string(),
+            bands
+        }
+    }
+}
+
+/// The estimated band conditions in the hamqsl feed
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawConditions {
+    band: Vec<RawBand>
+}
+
+/// The estimated condition of a group of bands (e.g. `<band name="80m-40m" time="day">Poor</band>`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawBand {
+    name: String,
+    time: String,
+    #[serde(rename = "$value")]
+    condition: String
+}
+
+
+/// The solar conditions module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should the solar conditions be fetched?
+    pub enabled: bool,
+    /// The refresh interval, in minutes
+    pub refresh_rate: u64,
+    /// The URL of the hamqsl solar XML feed
+    pub hamqsl_url: String,
+    /// The base URL of the NOAA SWPC services
+    pub swpc_url: String,
+    /// Should notifications be shown for geomagnetic storms and radio blackouts?
+    pub notify: bool,
+    /// The minimum geomagnetic storm scale (G1-G5) to notify for
+    pub storm_threshold: u8,
+    /// The minimum radio blackout scale (R1-R5) to notify for
+    pub blackout_threshold: u8
+}
+impl Config {
+    /// The minimum refresh interval, in minutes. The sources don't update more often than this, so there's no point in asking.
+    pub const MIN_REFRESH_RATE: u64 = 5;
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            refresh_rate: 15,
+            hamqsl_url: "https://www.hamqsl.com/solarxml.php".into(),
+            swpc_url: "https://services.swpc.noaa.gov".into(),
+            notify: true,
+            storm_threshold: 1,
+            blackout_threshold: 1
+        }
+    }
+}
+
+/// Errors regarding the solar conditions module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("The request failed: {0}")]
+    Request(reqwest::Error),
+    #[error("Failed to parse the solar XML feed: {0}")]
+    Deserialize(serde_xml_rs::Error),
+    #[error("Failed to read or write the cache: {0}")]
+    Io(std::io::Error),
+    #[error("Invalid cache: {0}")]
+    Json(serde_json::Error)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn series_objects() {
+        // The newer format of the K index product
+        let value = json!([
+            { "time_tag": "2024-05-10T12:00:00", "Kp": 8.67, "a_running": 207, "station_count": 8 },
+            { "time_tag": "2024-05-10T09:00:00", "Kp": 6.33, "a_running": 80, "station_count": 8 },
+            { "time_tag": "2024-05-10T15:00:00", "Kp": "9.00", "a_running": 400, "station_count": 8 }
+        ]);
+        let samples = series(&value, "time_tag", "kp", None);
+
+        // The samples are sorted by time, and string values are parsed
+        let times: Vec<i64> = samples.iter().map(|s| s.time).collect();
+        let values: Vec<f64> = samples.iter().map(|s| s.value).collect();
+        assert_eq!(times, vec![1_715_331_600, 1_715_342_400, 1_715_353_200]);
+        assert_eq!(values, vec![6.33, 8.67, 9.0]);
+    }
+
+    #[test]
+    fn series_table() {
+        // The solar wind products are tables, where the first row contains the column names
+        let value = json!([
+            ["time_tag", "density", "speed", "temperature"],
+            ["2024-05-10 12:00:00.000", "5.10", "401.2", "81234"],
+            ["2024-05-10 12:01:00.000", null, null, null],
+            ["2024-05-10 12:02:00.000", "5.32", "412.9", "80512"]
+        ]);
+        let samples = series(&value, "time_tag", "speed", None);
+
+        // The row with missing values is skipped
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].time, 1_715_342_400);
+        assert_eq!(samples[0].value, 401.2);
+        assert_eq!(samples[1].time, 1_715_342_520);
+        assert_eq!(samples[1].value, 412.9);
+
+        // Unknown columns don't have any samples
+        assert!(series(&value, "time_tag", "bz_gsm", None).is_empty());
+    }
+
+    #[test]
+    fn series_invalid() {
+        assert!(series(&json!({ "time_tag": "2024-05-10 12:00:00.000" }), "time_tag", "kp", None).is_empty());
+        assert!(series(&json!([]), "time_tag", "kp", None).is_empty());
+        assert!(series(&json!([{ "time_tag": "yesterday", "kp": 3 }, { "kp": 3 }]), "time_tag", "kp", None).is_empty());
+    }
+
+    #[test]
+    fn xray_flux() {
+        let value = json!([
+            { "time_tag": "2024-05-10T06:54:00Z", "satellite": 16, "flux": 1.2e-4, "energy": "0.1-0.8nm" },
+            { "time_tag": "2024-05-10T06:54:00Z", "satellite": 16, "flux": 3.4e-5, "energy": "0.05-0.4nm" },
+            { "time_tag": "2024-05-10T06:55:00Z", "satellite": 16, "flux": 0.0, "energy": "0.1-0.8nm" },
+            { "time_tag": "2024-05-10T06:56:00Z", "satellite": 16, "flux": -1e5, "energy": "0.1-0.8nm" },
+            { "time_tag": "2024-05-10T06:57:00Z", "satellite": 16, "flux": null, "energy": "0.1-0.8nm" },
+            { "time_tag": "2024-05-10T06:58:00Z", "satellite": 16, "flux": 9.8e-5, "energy": "0.1-0.8nm" }
+        ]);
+        let samples = xray_series(&value);
+
+        // Only the long wavelength flux is used, and missing measurements are removed
+        let values: Vec<f64> = samples.iter().map(|s| s.value).collect();
+        assert_eq!(values, vec![1.2e-4, 9.8e-5]);
+        assert!(samples.iter().all(|s| s.value.log10().is_finite()));
+        assert_eq!(samples[0].time, 1_715_324_040);
+    }
+
+    #[test]
+    fn scales() {
+        // The scales are strings, and may be missing
+        let value = json!({
+            "-1": { "R": { "Scale": "1" }, "S": { "Scale": "0" }, "G": { "Scale": "5" } },
+            "0": { "DateStamp": "2024-05-10", "TimeStamp": "18:00:00", "R": { "Scale": "3", "Text": "strong" }, "S": { "Scale": null }, "G": { "Scale": "4", "Text": "severe" } },
+            "1": { "R": { "Scale": null }, "S": { "Scale": null }, "G": { "Scale": "3" } }
+        });
+        let scales = parse_scales(&value).unwrap();
+        assert_eq!((scales.r, scales.s, scales.g), (3, 0, 4));
+
+        assert!(parse_scales(&json!({ "1": {} })).is_none());
+    }
+
+    #[test]
+    fn numbers_and_times() {
+        assert_eq!(as_number(&json!(4.5)), Some(4.5));
+        assert_eq!(as_number(&json!(" 4.5 ")), Some(4.5));
+        assert_eq!(as_number(&json!("No Report")), None);
+        assert_eq!(as_number(&json!(null)), None);
+
+        assert_eq!(parse_time("2024-05-10 12:00:00.000"), Some(1_715_342_400));
+        assert_eq!(parse_time("2024-05-10T12:00:00Z"), Some(1_715_342_400));
+        assert_eq!(parse_time("2024-05-10T12:00:00"), Some(1_715_342_400));
+        assert_eq!(parse_time("2024-05-10 12:00"), Some(1_715_342_400));
+        assert_eq!(parse_time("10/05/2024"), None);
+    }
+
+    #[test]
+    fn scales_fallback() {
+        // The K index and X-ray flux are used if the NOAA scales aren't available
+        let mut conditions = Conditions {
+            kp: vec![Sample { time: 0, value: 6.33 }],
+            xray: vec![Sample { time: 0, value: 1.2e-4 }],
+            ..Default::default()
+        };
+        assert_eq!(conditions.geomagnetic_scale(), Some(2));
+        assert_eq!(conditions.radio_blackout_scale(), Some(3));
+
+        conditions.scales = Some(Scales { r: 1, s: 0, g: 4 });
+        assert_eq!(conditions.geomagnetic_scale(), Some(4));
+        assert_eq!(conditions.radio_blackout_scale(), Some(1));
+
+        assert_eq!(Conditions::default().geomagnetic_scale(), None);
+        assert_eq!(Conditions::default().radio_blackout_scale(), None);
+    }
+}
@@ -2,7 +2,7 @@
 // Contains code belonging to the callsign lookup tab
 //
 
-use std::{sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use std::{future::Future, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
 use anyhow::{Context, Result};
 use arrayvec::ArrayString;
 use chrono::NaiveDate;
@@ -13,7 +13,7 @@ use serde::{Deserialize, Serialize};
 use egui::{widgets, Align, Id, Layout, Ui, Widget, WidgetText};
 use thiserror::Error;
 use tokio::sync::{Mutex, RwLock};
-use crate::{modules::gui::{generate_random_id, Tab}, types, GuiConfig, RT};
+use crate::{modules::{geodesy, gui::{self, generate_random_id, Tab}, maidenhead}, types, GuiConfig, RT};
 use crate::types::arc_rwlock_serde;
 
 
@@ -109,41 +109,9 @@ impl CallsignLookupTab {
 
     }
 
-    /// Queries the HamDB/HamQTH API about the provided callsign
-    fn lookup_callsign_promise(&self, config: &mut Config) -> Promise<Result<CallsignInformation>> {
-
-        // Clone the callsign so we can move it into the task
-        let callsign = self.callsign.clone();
-
-        // Create a new task to get the HamQTH session ID
-        let hamqth_id = Self::get_hamqth_session_id(
-            config.username.clone(),
-            config.password.clone(),
-            config.hamqth_session_id.clone()
-        );
-
-        let _eg = RT.enter();
-        Promise::spawn_async(async move {
-
-            // Try the query the HamDB API first
-            let hamdb_error = match Self::query_hamdb(callsign.clone()).await {
-                Ok(callsign_info) => return Ok(callsign_info),
-                Err(e) => e
-            };
-
-            // Try to query the HamQTH API with the session ID
-            if let Ok(hamqth_id) = hamqth_id.await {
-                // Query the HamQTH API with the session ID
-                let callsign_info = Self::query_hamqth(callsign, hamqth_id).await?;
-
-                // Return the callsign information
-                return Ok(callsign_info);
-            }
-
-            // We couldn't find the callsign, so return an error
-            Err(Error::CallsignNotFound)?
-
-        })
+    /// Queries the HamDB/HamQTH API about the callsign in the search box
+    fn lookup_callsign_promise(&self, config: &Config) -> Promise<Result<CallsignInformation>> {
+        lookup_promise(self.callsign.clone(), config)
     }
 }
 impl Tab for CallsignLookupTab {
@@ -160,7 +128,7 @@ impl Tab for CallsignLookupTab {
         if let types::Event::LookupCallsign(callsign) = event {
             // Only want to start a new lookup task if we don't already have one running
             if self.task.is_none() {
-                self.task = Some(self.lookup_callsign_promise(&mut config.callsign_lookup_config));
+                self.task = Some(self.lookup_callsign_promise(&config.callsign_lookup_config));
             }
         }
     }
@@ -199,6 +167,12 @@ impl Tab for CallsignLookupTab {
                 widgets::Label::new(format!("License Class:   {}", info.class)).ui(ui);
                 widgets::Label::new(format!("License Expires:   {}", info.expires)).ui(ui);
 
+                // The distance and bearing from our station, which comes from the station settings
+                if let Ok(station) = maidenhead::grid_to_lat_lon(&config.station.grid) {
+                    let path = geodesy::Path::new(&station, &info.location);
+                    gui::path_ui(ui, &path, &config.distance_unit, "Bearing");
+                }
+
             });
         }
         // No callsign has been searched yet
@@ -214,7 +188,7 @@ impl Tab for CallsignLookupTab {
             // Show a button to search for the callsign. The button is disabled if a lookup task is already running
             let response = ui.add_enabled(self.task.is_none(), widgets::Button::new("\u{1F50D}"));
             if response.clicked() {
-                self.task = Some(self.lookup_callsign_promise(&mut config.callsign_lookup_config));
+                self.task = Some(self.lookup_callsign_promise(&config.callsign_lookup_config));
             }
 
             // Show a textedit box for the callsign
@@ -558,6 +532,59 @@ struct HamQTHAuthResponse {
 }
 
 
+/// Queries the HamDB/HamQTH API about the provided callsign.
+///
+/// This is used by other tabs that need information about a station (e.g. the location of an RBN skimmer).
+pub fn lookup_promise(callsign: String, config: &Config) -> Promise<Result<CallsignInformation>> {
+    let lookup = lookup(callsign, config);
+
+    let _eg = RT.enter();
+    Promise::spawn_async(lookup)
+}
+
+/// Looks up the best known location of a callsign. This is the callsign locator of the maps, which is used to search for callsigns.
+pub fn location_promise(callsign: String, config: &GuiConfig) -> Promise<Result<Option<Coord>>> {
+    let lookup = lookup(callsign, &config.callsign_lookup_config);
+
+    let _eg = RT.enter();
+    Promise::spawn_async(async move {
+        Ok(lookup.await?.best_location())
+    })
+}
+
+/// Queries the HamDB API about the provided callsign, falling back to the HamQTH API
+fn lookup(callsign: String, config: &Config) -> impl Future<Output = Result<CallsignInformation>> {
+
+    // Create a new task to get the HamQTH session ID
+    let hamqth_id = CallsignLookupTab::get_hamqth_session_id(
+        config.username.clone(),
+        config.password.clone(),
+        config.hamqth_session_id.clone()
+    );
+
+    async move {
+
+        // Try the query the HamDB API first
+        let hamdb_error = match CallsignLookupTab::query_hamdb(callsign.clone()).await {
+            Ok(callsign_info) => return Ok(callsign_info),
+            Err(e) => e
+        };
+
+        // Try to query the HamQTH API with the session ID
+        if let Ok(hamqth_id) = hamqth_id.await {
+            // Query the HamQTH API with the session ID
+            let callsign_info = CallsignLookupTab::query_hamqth(callsign, hamqth_id).await?;
+
+            // Return the callsign information
+            return Ok(callsign_info);
+        }
+
+        // We couldn't find the callsign, so return an error
+        Err(Error::CallsignNotFound)?
+
+    }
+}
+
 /// Information about a callsign
 #[derive(Debug, Clone)]
 pub struct CallsignInformation {
@@ -580,6 +607,16 @@ pub struct CallsignInformation {
     /// The expiration date of the operator's license
     pub expires: String,
 }
+impl CallsignInformation {
+    /// Gets the exact location of the station if it's known, otherwise the center of its grid square
+    pub fn best_location(&self) -> Option<Coord> {
+        if self.location.x != 0.0 || self.location.y != 0.0 {
+            Some(self.location)
+        } else {
+            maidenhead::grid_to_lat_lon(&self.grid).ok()
+        }
+    }
+}
 
 /// A trait to convert a HamQTH or HamDB response into the `CallsignInformation` type
 trait ToCallsignInformation {
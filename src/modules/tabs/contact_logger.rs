@@ -7,9 +7,9 @@ use chrono::{NaiveDate, NaiveTime, Utc};
 use log::{error, warn};
 use poll_promise::Promise;
 use serde::{Deserialize, Serialize};
-use egui::{widgets, Id, Ui, Vec2, Widget, WidgetText};
+use egui::{widgets, Id, Key, Ui, Vec2, Widget, WidgetText};
 use strum::IntoEnumIterator;
-use crate::{modules::{gui::{frequency_formatter, frequency_parser, generate_random_id, power_formatter, power_parser}, types}, GuiConfig, Tab};
+use crate::{modules::{cw, gui::{frequency_formatter, frequency_parser, generate_random_id, power_formatter, power_parser}, maidenhead, types}, GuiConfig, Tab};
 
 /// The contact logger tab
 #[derive(Serialize, Deserialize)]
@@ -36,6 +36,9 @@ pub struct ContactLoggerTab {
     task: Option<Promise<Result<types::Contact>>>
 }
 impl ContactLoggerTab {
+    /// The keys used to send the CW macros, in order
+    const MACRO_KEYS: [Key; 12] = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12];
+
     /// Updates the start date and time of the contact to 'now'
     fn update_start_date_time(&mut self) {
         // Get the current date and time
@@ -64,6 +67,75 @@ impl ContactLoggerTab {
         self.end_time_str = format!("{}", self.end_time.format("%H:%M:%S"));
     }
 }
+impl ContactLoggerTab {
+    /// Shows the CW macro buttons, speed control, and abort button, and handles the macro keys
+    fn cw_macros_ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+
+        // The macro that should be sent, if any
+        let mut send = None;
+
+        ui.horizontal_wrapped(|ui| {
+
+            // A button for each macro
+            for (idx, m) in config.cw_config.macros.iter().enumerate().take(Self::MACRO_KEYS.len()) {
+                let response = ui.button(format!("F{} {}", idx + 1, m.label)).on_hover_text(&m.text);
+                if response.clicked() {
+                    send = Some(idx);
+                }
+            }
+
+            ui.separator();
+
+            // The speed drag value
+            widgets::DragValue::new(&mut config.cw_config.speed)
+            .clamp_range(cw::Config::MIN_SPEED..=cw::Config::MAX_SPEED)
+            .suffix(" WPM")
+            .ui(ui)
+            .on_hover_text("Page Up/Page Down to change the speed");
+
+            // The abort button
+            if ui.button("Abort").on_hover_text("Stop sending (Esc)").clicked() {
+                if let Some(keyer) = &config.cw {
+                    keyer.abort();
+                }
+            }
+
+            // The serial number of the next contact
+            ui.label(format!("NR: {:03}", config.cw_config.serial));
+
+        });
+
+        // Handle the macro, speed, and abort keys. The keys are global, so only the focused logger handles them.
+        let focused = config.focused_tab == Some(self.id);
+        ui.input(|i| {
+            if !focused {
+                return;
+            }
+            for (idx, key) in Self::MACRO_KEYS.iter().enumerate() {
+                if i.key_pressed(*key) {
+                    send = Some(idx);
+                }
+            }
+            if i.key_pressed(Key::PageUp) {
+                config.cw_config.speed = (config.cw_config.speed + 2).min(cw::Config::MAX_SPEED);
+            }
+            if i.key_pressed(Key::PageDown) {
+                config.cw_config.speed = config.cw_config.speed.saturating_sub(2).max(cw::Config::MIN_SPEED);
+            }
+            if i.key_pressed(Key::Escape) {
+                if let Some(keyer) = &config.cw {
+                    keyer.abort();
+                }
+            }
+        });
+
+        // Send the macro
+        if let Some(m) = send.and_then(|idx| config.cw_config.macros.get(idx)) {
+            cw::send_macro(config, &m.text, &self.input);
+        }
+
+    }
+}
 impl Tab for ContactLoggerTab {
 
     fn id(&self) -> Id {
@@ -73,14 +145,48 @@ impl Tab for ContactLoggerTab {
     fn title(&mut self) -> WidgetText {
         "Contact Logger".into()
     }
-    
+
+    fn process_event(&mut self, _config: &mut GuiConfig, event: &types::Event) {
+        // Fill in any values that were provided by another tab or program (e.g. the frequency of the radio)
+        if let types::Event::PrefillLogger(prefill) = event {
+            if let Some(callsign) = &prefill.callsign {
+                self.input.callsign.clone_from(callsign);
+                // A new callsign means a new contact, so reset the start time
+                self.update_start_date_time();
+            }
+            if let Some(grid) = &prefill.grid {
+                self.input.grid.clone_from(grid);
+            }
+            if let Some(frequency) = prefill.frequency {
+                self.input.frequency = frequency;
+            }
+            if let Some(mode) = &prefill.mode {
+                self.input.mode = mode.clone();
+            }
+            if let Some(tx_rst) = &prefill.tx_rst {
+                self.input.tx_rst.clone_from(tx_rst);
+            }
+            if let Some(rx_rst) = &prefill.rx_rst {
+                self.input.rx_rst.clone_from(rx_rst);
+            }
+            if let Some(note) = &prefill.note {
+                self.input.note.clone_from(note);
+            }
+        }
+    }
+
     fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
 
         // Process any pending tasks
         if let Some(task) = self.task.take_if(|t| t.ready().is_some()) {
             // If the contact was added successfully, send a refresh contacts event, otherwise print the error
             match task.block_and_take() {
-                Ok(_contact) => config.events.push((None, types::Event::RefreshContacts)),
+                Ok(contact) => {
+                    config.events.push_back((None, types::Event::RefreshContacts));
+                    config.events.push_back((None, types::Event::ContactLogged(contact)));
+                    // The contact was logged, so the next contact gets the next serial number
+                    config.cw_config.serial += 1;
+                },
                 Err(err) => error!("Failed to insert contact: {err}")
             }
         }
@@ -96,18 +202,50 @@ impl Tab for ContactLoggerTab {
             // Subtract the spacing and button width from the available width
             let available_width = available_width - spacing - 28.0;
 
-            // Callsign textbox (50% width)
+            // Callsign textbox (35% width)
             ui.vertical(|ui| {
                 ui.add(widgets::Label::new("Callsign").wrap(false));
                 
                 widgets::TextEdit::singleline(&mut self.input.callsign)
                 .hint_text("Callsign")
                 .clip_text(true)
-                .min_size(Vec2::new(available_width * 0.5, 0.0))
+                .min_size(Vec2::new(available_width * 0.35 - spacing, 0.0))
                 .desired_width(0.0)
                 .show(ui);
             });
 
+            // Grid square textbox (15% width)
+            ui.vertical(|ui| {
+                ui.add(widgets::Label::new("Grid").wrap(false));
+
+                // Show the grid square in red if it isn't valid
+                let error = match self.input.grid.trim().is_empty() {
+                    true => None,
+                    false => maidenhead::Locator::parse(&self.input.grid).err()
+                };
+                let mut text_edit = widgets::TextEdit::singleline(&mut self.input.grid)
+                .hint_text("Grid")
+                .clip_text(true)
+                .min_size(Vec2::new(available_width * 0.15, 0.0))
+                .desired_width(0.0);
+                if error.is_some() {
+                    text_edit = text_edit.text_color(ui.visuals().error_fg_color);
+                }
+                let response = text_edit.show(ui).response;
+
+                // The widget lost focus, so tidy up the grid square (e.g. "dm79MR" becomes "DM79mr")
+                if response.lost_focus() {
+                    if let Ok(locator) = maidenhead::Locator::parse(&self.input.grid) {
+                        self.input.grid = locator.to_string();
+                    }
+                }
+
+                // Explain why the grid square isn't valid
+                if let Some(err) = error {
+                    response.on_hover_text(err.to_string());
+                }
+            });
+
             // The start date textbox (25% width)
             ui.vertical(|ui| {
                 ui.add(widgets::Label::new("Start date").wrap(false));
@@ -372,6 +510,14 @@ impl Tab for ContactLoggerTab {
                     config.notifications.push(types::Notification::Error("The end time must be after the start time".to_string()));
                     return;
                 }
+                // Ensure the grid square is valid, if there is one
+                if !self.input.grid.trim().is_empty() {
+                    if let Err(err) = maidenhead::Locator::parse(&self.input.grid) {
+                        config.notification_read = false;
+                        config.notifications.push(types::Notification::Error(err.to_string()));
+                        return;
+                    }
+                }
                 // Update the duration of the contact
                 self.input.duration = elapsed as u64;
 
@@ -380,6 +526,12 @@ impl Tab for ContactLoggerTab {
 
             };
         });
+
+        // The CW macro bar, if the keyer is enabled
+        if config.cw.is_some() {
+            ui.add_space(8.0);
+            self.cw_macros_ui(config, ui);
+        }
     }
     
 }
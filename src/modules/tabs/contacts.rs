@@ -53,7 +53,16 @@ pub struct ContactTableTab {
     /// A flag to indicate if we should query the database again.
     /// This is used instead of a queue so we only query the database once at a time, but we can still ensure we have the latest data.
     #[serde(skip)]
-    should_query: bool
+    should_query: bool,
+    /// The contact to scroll to, and the task that is querying the database for its row
+    #[serde(skip)]
+    show_task: Option<(ShowContact, Promise<Result<Option<usize>>>)>,
+    /// The row that the table should scroll to on the next frame
+    #[serde(skip)]
+    scroll_to_row: Option<usize>,
+    /// The row that is highlighted, after scrolling to a contact
+    #[serde(skip)]
+    highlighted_row: Option<usize>
 }
 impl Tab for ContactTableTab {
 
@@ -70,10 +79,20 @@ impl Tab for ContactTableTab {
     }
 
     fn process_event(&mut self, config: &mut GuiConfig, event: &types::Event) {
-        // Refresh the contacts table if the event is a refresh contacts event
-        if let types::Event::RefreshContacts = event {
-            self.should_query = true;
-        };
+        match event {
+            // Refresh the contacts table if the event is a refresh contacts event
+            types::Event::RefreshContacts => self.should_query = true,
+            // Find the row of the contact. The contacts are queried in the default order, so the table is reset to it.
+            types::Event::ShowContact { callsign, date, time } => {
+                self.sort_column = None;
+                self.sort_dir = database::ColumnSortDirection::Ascending;
+                self.should_query = true;
+
+                let task = config.db_api.get_contact_row_promise(callsign.clone(), *date, *time);
+                self.show_task = Some((ShowContact { callsign: callsign.clone(), date: *date, time: *time }, task));
+            },
+            _ => {}
+        }
     }
 
     fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
@@ -85,6 +104,11 @@ impl Tab for ContactTableTab {
 
             // Since we deleted the contact, we should query the database again
             self.should_query = true;
+
+            // Let the other tabs know that the log changed
+            if contact.is_ok() {
+                config.events.push_back((None, types::Event::RefreshContacts));
+            }
         }
 
         // Process any pending update task
@@ -93,6 +117,11 @@ impl Tab for ContactTableTab {
 
             // Since we updated the contact, we should query the database again
             self.should_query = true;
+
+            // Let the other tabs know that the log changed
+            if contact.is_ok() {
+                config.events.push_back((None, types::Event::RefreshContacts));
+            }
         }
 
         // If we finished querying the database, process the response
@@ -109,6 +138,20 @@ impl Tab for ContactTableTab {
             }
         }
 
+        // If we found the row of the contact to show, scroll to it and highlight it
+        if let Some((target, promise)) = self.show_task.take_if(|(_, t)| t.ready().is_some()) {
+            match promise.block_and_take() {
+                Ok(row) => {
+                    if row.is_none() {
+                        debug!("Couldn't find the contact to show: {target:?}");
+                    }
+                    self.scroll_to_row = row;
+                    self.highlighted_row = row;
+                },
+                Err(err) => error!("Failed to query the database for the contact to show: {err}")
+            }
+        }
+
         // Enforce a minimum width for the tab. The tab will automatically add horizontal scrollbars if the window is too small.
         // This stops us from making the table unreasonably small.
         ui.set_min_width(300.0);
@@ -120,8 +163,9 @@ impl Tab for ContactTableTab {
         let mut first_row_idx = None;
         let mut last_row_idx = 0;
 
-        egui_extras::TableBuilder::new(ui)
+        let mut table = egui_extras::TableBuilder::new(ui)
         .columns(Column::initial(50.0).at_least(50.0), 1) // Callsign
+        .columns(Column::initial(50.0).at_least(50.0), 1) // Grid
         .columns(Column::initial(70.0).at_least(70.0), 1) // Frequency
         .columns(Column::initial(35.0).at_least(35.0), 1) // Mode
         .columns(Column::initial(40.0).at_least(40.0), 2) // TX and RX RST
@@ -134,8 +178,14 @@ impl Tab for ContactTableTab {
         .resizable(true)
         .striped(true)
         .min_scrolled_height(20.0)
-        .sense(egui::Sense::click())
-        .header(20.0, |mut header| {
+        .sense(egui::Sense::click());
+
+        // Scroll to the contact that should be shown
+        if let Some(row) = self.scroll_to_row.take() {
+            table = table.scroll_to_row(row, Some(Align::Center));
+        }
+
+        table.header(20.0, |mut header| {
 
             // Iterate through each viewable column and render it
             for column in database::ContactTableColumn::iter() {
@@ -182,8 +232,9 @@ impl Tab for ContactTableTab {
                         self.sort_dir = database::ColumnSortDirection::Ascending;
                     }
     
-                    // Update the table now that our sort state changed
+                    // Update the table now that our sort state changed, which also moves the highlighted contact
                     self.should_query = true;
+                    self.highlighted_row = None;
 
                 }
 
@@ -199,6 +250,9 @@ impl Tab for ContactTableTab {
                 // Get the first and last row index
                 let row_index = row.index();
 
+                // Highlight the contact that was shown
+                row.set_selected(self.highlighted_row == Some(row_index));
+
                 // Update the first and last row index
                 if first_row_idx.is_none() {
                     first_row_idx = Some(row_index);
@@ -218,7 +272,7 @@ impl Tab for ContactTableTab {
                         });
 
                         // Show nothing for the remaining columns. We still call row.col() so you can still scroll with your mouse anywhere in the table.
-                        for _ in 0..9 {
+                        for _ in 0..10 {
                             row.col(|ui| {});
                         }
 
@@ -265,6 +319,45 @@ impl Tab for ContactTableTab {
                     self.editing_column = Some((row_index, database::ContactTableColumn::Callsign));
                 }
 
+                // ===== GRID COLUMN ===== //
+                let (_rect, response) = row.col(|ui| {
+
+                    // This column is currently being edited, show a textedit
+                    if self.editing_column.is_some_and(|(idx, c)| idx == row_index && c.is_grid()) {
+                        // Show a textedit widget
+                        let w = widgets::TextEdit::singleline(&mut contact.grid)
+                        .horizontal_align(Align::Center)
+                        .desired_width(f32::INFINITY)
+                        .margin(egui::Margin::same(2.0))
+                        .show(ui);
+
+                        // The textedit lost focus, implying that the user wants to save the changes
+                        if w.response.lost_focus() {
+                            // Stop editing the column
+                            self.editing_column = None;
+
+                            // Update the contact
+                            should_update_row = Some(contact.clone());
+                        };
+
+                        // Focuses the textedit when a column is being edited
+                        w.response.request_focus();
+                    }
+                    // This column isn't being edited, show a label
+                    else {
+                        // Show a label widget
+                        widgets::Label::new(&contact.grid)
+                        .truncate(true)
+                        .selectable(false)
+                        .ui(ui);
+                    }
+
+                });
+                // The grid column was double clicked; start editing the column
+                if response.double_clicked() {
+                    self.editing_column = Some((row_index, database::ContactTableColumn::Grid));
+                }
+
                 // ===== FREQUENCY COLUMN ===== //
                 let (_rect, response) = row.col(|ui| {
 
@@ -756,7 +849,7 @@ impl Tab for ContactTableTab {
                     if ui.button("Lookup callsign").on_hover_text("You must have a callsign lookup tab open to see the result").clicked() {
 
                         // Lookup the contact
-                        config.events.push((None, types::Event::LookupCallsign(contact.callsign.clone())));
+                        config.events.push_back((None, types::Event::LookupCallsign(contact.callsign.clone())));
 
                         // Close the menu after the button was clicked
                         ui.close_menu();
@@ -829,7 +922,10 @@ impl Default for ContactTableTab {
             query_task: Default::default(),
             update_task: Default::default(),
             delete_task: Default::default(),
-            should_query: true
+            should_query: true,
+            show_task: Default::default(),
+            scroll_to_row: Default::default(),
+            highlighted_row: Default::default()
         }
     }
 }
@@ -847,3 +943,15 @@ impl std::fmt::Debug for ContactTableTab {
         .finish()
     }
 }
+
+
+/// The contact that the table should scroll to
+#[derive(Debug)]
+struct ShowContact {
+    /// The callsign of the contact
+    callsign: String,
+    /// The date of the contact
+    date: NaiveDate,
+    /// The time of the contact
+    time: NaiveTime
+}
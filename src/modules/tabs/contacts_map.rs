@@ -0,0 +1,598 @@
+//
+// Contains the code for the contacts map tab
+//
+
+use std::{collections::{HashMap, HashSet, VecDeque}, hash::{Hash, Hasher}, time::{Duration, Instant}};
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveTime};
+use egui::{Color32, Id, RichText, Ui, Widget, WidgetText};
+use geo::Coord;
+use log::{debug, error};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use crate::modules::{geodesy, greyline, grids, gui::{self, generate_random_id, Tab}, maidenhead, map::{self, MapLayer, MapMarkerTrait}};
+use crate::{types, GuiConfig};
+use super::callsign_lookup::{self, CallsignInformation};
+
+
+type CallsignString = arrayvec::ArrayString<20>;
+type GridString = arrayvec::ArrayString<10>;
+type ModeString = arrayvec::ArrayString<16>;
+type RstString = arrayvec::ArrayString<8>;
+
+
+/// A tab that shows the logged contacts on a map
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContactsMapTab {
+    /// The egui ID
+    id: Id,
+    #[serde(skip)]
+    map: Option<map::MapWidget>,
+    /// The position and zoom of the map, so it's restored when the tab is reopened
+    view: map::MapView,
+    /// The layer of contact markers
+    #[serde(skip)]
+    contact_markers: map::MarkerLayer<ContactMarker>,
+    /// The first date to show contacts from (`YYYY-MM-DD`). If this is empty, there's no limit.
+    from_date: String,
+    /// The last date to show contacts from (`YYYY-MM-DD`). If this is empty, there's no limit.
+    to_date: String,
+    /// The band to filter for
+    band: types::Band,
+    /// The mode to filter for. If this is `None`, every mode is shown.
+    mode: Option<types::Mode>,
+    /// What the markers are colored by
+    color_by: ColorBy,
+    /// Should the callsigns of contacts without a grid square be looked up?
+    /// This is off by default, since every lookup counts towards the quota of the lookup service.
+    lookup_callsigns: bool,
+    /// The projection of the map
+    projection: map::Projection,
+    /// The greyline layer
+    greyline: greyline::Layer,
+    /// The Maidenhead grid layer
+    grids: grids::Layer,
+    /// Every logged contact
+    #[serde(skip)]
+    contacts: Vec<types::Contact>,
+    /// The task that is querying the database for the contacts
+    #[serde(skip)]
+    task: Option<Promise<Result<Vec<types::Contact>>>>,
+    /// Should the markers be recreated on the next frame? This is set when the contacts, filters, or locations change.
+    #[serde(skip)]
+    markers_changed: bool,
+    /// The entries of the legend, with their color. This is recreated with the markers.
+    #[serde(skip)]
+    legend: Vec<(String, Color32)>,
+    /// The locations of the callsigns that were looked up. This is `None` if the station couldn't be located.
+    #[serde(skip)]
+    locations: HashMap<String, Option<Coord>>,
+    /// The callsigns that should be looked up
+    #[serde(skip)]
+    lookup_queue: VecDeque<String>,
+    /// Every callsign that was queued for a lookup, so each callsign is only looked up once
+    #[serde(skip)]
+    queued: HashSet<String>,
+    /// The callsign lookups that are running
+    #[serde(skip)]
+    lookups: Vec<(String, Promise<Result<CallsignInformation>>)>,
+    /// The times that the lookups in the last minute were started, oldest first
+    #[serde(skip)]
+    lookup_times: VecDeque<Instant>,
+    /// The number of lookups that were started since the tab was opened
+    #[serde(skip)]
+    n_lookups: usize
+}
+impl ContactsMapTab {
+    /// The maximum number of callsign lookups that can run at once
+    const MAX_LOOKUPS: usize = 4;
+    /// The maximum number of callsign lookups that can be started each minute
+    const MAX_LOOKUPS_PER_MINUTE: usize = 20;
+    /// The maximum number of callsign lookups that can be started while the tab is open, so a large log doesn't use up the lookup quota
+    const MAX_TOTAL_LOOKUPS: usize = 250;
+
+    /// Starts querying the database for every contact
+    fn load(&mut self, config: &mut GuiConfig) {
+        let n_contacts = config.db_api.get_contacts_metadata().map(|m| m.n_contacts).unwrap_or_default();
+        self.task = Some(config.db_api.get_contacts_promise(0, Some(n_contacts.max(1)), None, None));
+    }
+
+    /// Gets the contacts that match the filters
+    fn visible_contacts(&self) -> Vec<&types::Contact> {
+        let from_date = NaiveDate::parse_from_str(self.from_date.trim(), "%Y-%m-%d").ok();
+        let to_date = NaiveDate::parse_from_str(self.to_date.trim(), "%Y-%m-%d").ok();
+
+        self.contacts.iter()
+            .filter(|c| from_date.map_or(true, |d| c.date >= d))
+            .filter(|c| to_date.map_or(true, |d| c.date <= d))
+            .filter(|c| self.band == types::Band::All || types::Band::from_frequency(c.frequency) == self.band)
+            .filter(|c| self.mode.as_ref().map_or(true, |m| std::mem::discriminant(m) == std::mem::discriminant(&c.mode)))
+            .collect()
+    }
+
+    /// Queues lookups of the locations of the contacts without a grid square, unless they were already queued.
+    ///
+    /// This is called when the contacts are loaded or the lookups are enabled, instead of on every frame.
+    fn queue_lookups(&mut self) {
+        if !self.lookup_callsigns {
+            return;
+        }
+
+        for contact in self.contacts.iter().filter(|c| !maidenhead::is_valid(&c.grid)) {
+            let callsign = contact.callsign.trim().to_ascii_uppercase();
+            if !callsign.is_empty() && self.queued.insert(callsign.clone()) {
+                self.lookup_queue.push_back(callsign);
+            }
+        }
+    }
+
+    /// Starts any queued callsign lookups and processes the finished ones
+    fn process_lookups(&mut self, config: &GuiConfig) {
+
+        // Store the locations of the finished lookups
+        for (callsign, task) in self.lookups.extract_if(|(_, t)| t.ready().is_some()) {
+            let location = match task.block_and_take() {
+                Ok(info) => info.best_location(),
+                Err(err) => {
+                    debug!("Failed to locate '{callsign}': {err}");
+                    None
+                }
+            };
+            self.locations.insert(callsign, location);
+            self.markers_changed = true;
+        }
+
+        if !self.lookup_callsigns {
+            return;
+        }
+
+        // Forget the lookups that were started over a minute ago
+        while self.lookup_times.front().is_some_and(|t| t.elapsed() > Duration::from_secs(60)) {
+            self.lookup_times.pop_front();
+        }
+
+        // Start the queued lookups, without going over the rate limit or the total limit
+        while self.lookups.len() < Self::MAX_LOOKUPS && self.lookup_times.len() < Self::MAX_LOOKUPS_PER_MINUTE && self.n_lookups < Self::MAX_TOTAL_LOOKUPS {
+            let Some(callsign) = self.lookup_queue.pop_front() else {
+                break;
+            };
+            let task = callsign_lookup::lookup_promise(callsign.clone(), &config.callsign_lookup_config);
+            self.lookups.push((callsign, task));
+            self.lookup_times.push_back(Instant::now());
+            self.n_lookups += 1;
+        }
+
+    }
+
+    /// Creates the map markers for the contacts.
+    ///
+    /// Contacts are located by their grid square, then by a callsign lookup, then by their DXCC entity. Contacts that can't be located are skipped.
+    fn markers(&self, contacts: &[&types::Contact], config: &GuiConfig) -> Vec<ContactMarker> {
+        let station_location = maidenhead::grid_to_lat_lon(&config.station.grid).ok();
+
+        // The range of dates, used to color the markers by age
+        let newest = contacts.iter().map(|c| c.date).max().unwrap_or_default();
+        let oldest = contacts.iter().map(|c| c.date).min().unwrap_or_default();
+        let days = newest.signed_duration_since(oldest).num_days().max(1) as f32;
+
+        contacts.iter().filter_map(|contact| {
+            let callsign = contact.callsign.trim().to_ascii_uppercase();
+            let (location, located_by) = if let Ok(location) = maidenhead::grid_to_lat_lon(&contact.grid) {
+                (location, LocatedBy::Grid)
+            } else if let Some(location) = self.locations.get(&callsign).copied().flatten() {
+                (location, LocatedBy::Lookup)
+            } else {
+                let entity = config.alerts.dxcc().and_then(|d| d.lookup(&callsign))?;
+                (entity.location, LocatedBy::Dxcc)
+            };
+
+            let band = types::Band::from_frequency(contact.frequency);
+            let mode = contact.mode.to_string();
+            let color = match self.color_by {
+                ColorBy::Band => band_color(band),
+                ColorBy::Mode => mode_color(&mode),
+                ColorBy::Age => age_color(newest.signed_duration_since(contact.date).num_days() as f32 / days)
+            };
+
+            Some(ContactMarker {
+                id: hash_contact(contact),
+                location,
+                station_location,
+                located_by,
+                callsign: CallsignString::from(&callsign).unwrap_or_default(),
+                grid: GridString::from(contact.grid.trim()).unwrap_or_default(),
+                date: contact.date,
+                time: contact.time,
+                frequency: contact.frequency,
+                band,
+                mode: ModeString::from(&mode).unwrap_or_default(),
+                tx_rst: RstString::from(contact.tx_rst.trim()).unwrap_or_default(),
+                rx_rst: RstString::from(contact.rx_rst.trim()).unwrap_or_default(),
+                color: color.to_array()
+            })
+        }).collect()
+    }
+
+    /// Gets the colors of the bands or modes of the visible contacts, or the dates of the age gradient
+    fn legend(&self, contacts: &[&types::Contact]) -> Vec<(String, Color32)> {
+        match self.color_by {
+            ColorBy::Band => {
+                let bands: HashSet<types::Band> = contacts.iter().map(|c| types::Band::from_frequency(c.frequency)).collect();
+                types::Band::iter()
+                    .filter(|b| bands.contains(b))
+                    .map(|b| (b.as_str().to_string(), band_color(b)))
+                    .collect()
+            },
+            ColorBy::Mode => {
+                let mut modes: Vec<String> = contacts.iter().map(|c| c.mode.to_string()).collect();
+                modes.sort();
+                modes.dedup();
+                modes.into_iter().map(|m| {
+                    let color = mode_color(&m);
+                    (m, color)
+                }).collect()
+            },
+            ColorBy::Age => match (contacts.iter().map(|c| c.date).max(), contacts.iter().map(|c| c.date).min()) {
+                (Some(newest), Some(oldest)) => vec![(format!("Newest {newest}"), age_color(0.0)), (format!("Oldest {oldest}"), age_color(1.0))],
+                _ => Vec::new()
+            }
+        }
+    }
+
+    /// Shows the legend
+    fn legend_ui(&self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Legend:");
+            for (text, color) in &self.legend {
+                ui.label(RichText::new(text).strong().color(*color));
+            }
+        });
+    }
+}
+impl Tab for ContactsMapTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "Contacts Map".into()
+    }
+
+    fn init(&mut self, config: &mut GuiConfig) {
+        self.load(config);
+    }
+
+    fn process_event(&mut self, config: &mut GuiConfig, event: &types::Event) {
+        // The contacts changed, so query them again
+        if let types::Event::RefreshContacts = event {
+            if self.task.is_none() {
+                self.load(config);
+            }
+        }
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+
+        // The contacts finished loading
+        if let Some(task) = self.task.take_if(|t| t.ready().is_some()) {
+            match task.block_and_take() {
+                Ok(contacts) => {
+                    self.contacts = contacts;
+                    self.markers_changed = true;
+                    self.queue_lookups();
+                },
+                Err(err) => error!("Failed to query the database for contacts: {err}")
+            }
+        }
+
+        self.process_lookups(config);
+
+        // Show the filters above the map
+        ui.horizontal_wrapped(|ui| {
+
+            // The date range. Invalid dates are shown in red and ignored.
+            for (date, hint) in [(&mut self.from_date, "From (YYYY-MM-DD)"), (&mut self.to_date, "To (YYYY-MM-DD)")] {
+                let invalid = !date.trim().is_empty() && NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").is_err();
+                let mut text_edit = egui::TextEdit::singleline(date).hint_text(hint).desired_width(110.0);
+                if invalid {
+                    text_edit = text_edit.text_color(ui.visuals().error_fg_color);
+                }
+                self.markers_changed |= text_edit.ui(ui).changed();
+            }
+
+            // The band combobox
+            egui::ComboBox::from_id_source(self.id.with("band"))
+            .selected_text(self.band.as_str())
+            .show_ui(ui, |ui| {
+                for band in types::Band::iter() {
+                    self.markers_changed |= ui.selectable_value(&mut self.band, band, band.as_str()).changed();
+                }
+            });
+
+            // The mode combobox
+            egui::ComboBox::from_id_source(self.id.with("mode"))
+            .selected_text(self.mode.as_ref().map_or("All".to_string(), |m| m.to_string()))
+            .show_ui(ui, |ui| {
+                self.markers_changed |= ui.selectable_value(&mut self.mode, None, "All").changed();
+                for mode in types::Mode::iter() {
+                    let text = mode.to_string();
+                    self.markers_changed |= ui.selectable_value(&mut self.mode, Some(mode), text).changed();
+                }
+            });
+
+            // The color combobox
+            ui.label("Color by");
+            egui::ComboBox::from_id_source(self.id.with("color_by"))
+            .selected_text(self.color_by.to_string())
+            .show_ui(ui, |ui| {
+                for color_by in ColorBy::iter() {
+                    self.markers_changed |= ui.selectable_value(&mut self.color_by, color_by, color_by.to_string()).changed();
+                }
+            });
+
+            // The callsign lookup checkbox
+            if ui.checkbox(&mut self.lookup_callsigns, "Look up callsigns")
+            .on_hover_text(format!(
+                "Locate contacts without a grid square with the callsign lookup. Otherwise, they're shown at their DXCC entity.\n\
+                Each lookup counts towards the quota of the lookup service, so at most {} callsigns are looked up each minute, and {} in total.",
+                Self::MAX_LOOKUPS_PER_MINUTE, Self::MAX_TOTAL_LOOKUPS
+            ))
+            .changed() {
+                self.queue_lookups();
+            }
+
+            // The projection combobox
+            self.projection.combo_box(ui, self.id.with("projection"));
+
+            if self.task.is_some() || (self.lookup_callsigns && !self.lookups.is_empty()) {
+                ui.spinner();
+            }
+            if self.lookup_callsigns && !self.lookup_queue.is_empty() {
+                match self.n_lookups < Self::MAX_TOTAL_LOOKUPS {
+                    true => ui.label(format!("{} lookups queued", self.lookup_queue.len())),
+                    false => ui.label(format!("Lookup limit reached, {} callsigns weren't looked up", self.lookup_queue.len()))
+                };
+            }
+        });
+
+        // Recreate the markers and the legend if the contacts, filters, or locations changed
+        let markers = self.markers_changed.then(|| {
+            let contacts = self.visible_contacts();
+            (self.markers(&contacts, config), self.legend(&contacts))
+        })
+        .map(|(markers, legend)| {
+            self.legend = legend;
+            markers
+        });
+        self.markers_changed = false;
+        self.legend_ui(ui);
+
+        // Update the map if the markers changed
+        let map = self.map.get_or_insert_with(|| {
+            let mut map = map::MapWidget::new(ui.ctx());
+            map.set_view(self.view);
+            map.set_callsign_locator(callsign_lookup::location_promise);
+            map
+        });
+        if let Some(markers) = markers {
+            *self.contact_markers.markers_mut() = markers;
+            map.update_overlay();
+        }
+        ui.label(format!("Showing {} of {} contacts", self.contact_markers.markers().len(), self.contacts.len()));
+        ui.separator();
+
+        // Show the map widget in the selected projection, with the greyline, grids, and contacts layers
+        map.set_projection(self.projection);
+        let mut layers: [&mut dyn MapLayer; 3] = [&mut self.greyline, &mut self.grids, &mut self.contact_markers];
+        map.ui_with_layers(ui, config, &mut layers);
+        self.view = map.view();
+
+    }
+}
+impl Default for ContactsMapTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            map: Default::default(),
+            view: Default::default(),
+            contact_markers: map::MarkerLayer::new("Contacts"),
+            from_date: Default::default(),
+            to_date: Default::default(),
+            band: types::Band::All,
+            mode: None,
+            color_by: Default::default(),
+            lookup_callsigns: false,
+            projection: Default::default(),
+            greyline: Default::default(),
+            grids: Default::default(),
+            contacts: Default::default(),
+            task: None,
+            markers_changed: false,
+            legend: Default::default(),
+            locations: Default::default(),
+            lookup_queue: Default::default(),
+            queued: Default::default(),
+            lookups: Default::default(),
+            lookup_times: Default::default(),
+            n_lookups: 0
+        }
+    }
+}
+impl std::fmt::Debug for ContactsMapTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContactsMapTab")
+        .field("id", &self.id)
+        .field("map", &self.map)
+        .field("from_date", &self.from_date)
+        .field("to_date", &self.to_date)
+        .field("band", &self.band)
+        .field("mode", &self.mode)
+        .field("color_by", &self.color_by)
+        .field("contacts", &self.contacts.len())
+        .field("locations", &self.locations.len())
+        .finish()
+    }
+}
+
+
+/// What the markers are colored by
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+enum ColorBy {
+    #[default]
+    Band,
+    Mode,
+    Age
+}
+
+/// How a contact was located
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocatedBy {
+    /// The grid square of the contact
+    Grid,
+    /// A callsign lookup
+    Lookup,
+    /// The DXCC entity of the callsign, which is only a rough location
+    Dxcc
+}
+
+/// A logged contact, shown as a marker on the map
+#[derive(Debug, Clone, Copy)]
+struct ContactMarker {
+    /// The ID of the map marker. This is a hash of the contact.
+    id: u64,
+    /// The location of the other station
+    location: Coord<f64>,
+    /// The location of our station, if the station grid square is set
+    station_location: Option<Coord<f64>>,
+    /// How the other station was located
+    located_by: LocatedBy,
+    /// The callsign of the other station
+    callsign: CallsignString,
+    /// The grid square of the other station, if it was logged
+    grid: GridString,
+    /// The date of the contact, in UTC
+    date: NaiveDate,
+    /// The time of the contact, in UTC
+    time: NaiveTime,
+    /// The frequency of the contact, in Hz
+    frequency: u64,
+    /// The band of the contact
+    band: types::Band,
+    /// The mode of the contact
+    mode: ModeString,
+    /// The signal report sent to the other station
+    tx_rst: RstString,
+    /// The signal report received from the other station
+    rx_rst: RstString,
+    /// The RGBA color of the marker
+    color: [u8; 4]
+}
+impl MapMarkerTrait for ContactMarker {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        &self.location
+    }
+
+    fn hovered_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        ui.heading(self.callsign.as_str());
+        ui.label(format!("Date (UTC): {} {}", self.date.format("%Y-%m-%d"), self.time.format("%H:%M")));
+
+        let freq = gui::frequency_formatter(self.frequency as f64, 0..=0);
+        ui.label(format!("Frequency: {freq} ({})", self.band.as_str()));
+        ui.label(format!("Mode: {}", self.mode));
+        if !self.tx_rst.is_empty() || !self.rx_rst.is_empty() {
+            ui.label(format!("RST: {} sent, {} received", self.tx_rst, self.rx_rst));
+        }
+
+        // Where the location came from
+        match self.located_by {
+            LocatedBy::Grid => ui.label(format!("Grid: {}", self.grid)),
+            LocatedBy::Lookup => ui.label(format!("Grid: {} (callsign lookup)", maidenhead::lat_lon_to_grid(&self.location))),
+            LocatedBy::Dxcc => ui.label("Location: DXCC entity (approximate)")
+        };
+
+        // The distance and bearing from our station
+        if let Some(station_location) = self.station_location {
+            let path = geodesy::Path::new(&station_location, &self.location);
+            gui::path_ui(ui, &path, &config.distance_unit, "Bearing");
+        }
+    }
+
+    fn selected_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        self.hovered_ui(ui, config);
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            // Scroll the contacts table to the contact
+            if ui.button("Show in table").on_hover_text("You must have a contacts tab open to see the contact").clicked() {
+                config.events.push_back((None, types::Event::ShowContact {
+                    callsign: self.callsign.to_string(),
+                    date: self.date,
+                    time: self.time
+                }));
+            }
+
+            // Look up the callsign
+            if ui.button("Lookup callsign").on_hover_text("You must have a callsign lookup tab open to see the result").clicked() {
+                config.events.push_back((None, types::Event::LookupCallsign(self.callsign.to_string())));
+            }
+        });
+    }
+
+    fn color(&self, _config: &mut GuiConfig) -> image::Rgba<u8> {
+        image::Rgba(self.color)
+    }
+
+    fn draw_line_hovered(&self) -> Option<&Coord<f64>> {
+        self.station_location.as_ref()
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        vec![
+            ("callsign", self.callsign.to_string()),
+            ("grid", self.grid.to_string()),
+            ("time", self.date.and_time(self.time).and_utc().to_rfc3339()),
+            ("frequency", self.frequency.to_string()),
+            ("band", self.band.as_str().to_string()),
+            ("mode", self.mode.to_string()),
+            ("tx_rst", self.tx_rst.to_string()),
+            ("rx_rst", self.rx_rst.to_string())
+        ]
+    }
+}
+
+/// The color of a band. The hue goes from red on the low bands to violet on the high bands.
+fn band_color(band: types::Band) -> Color32 {
+    let n_bands = types::Band::iter().count() - 1;
+    let index = types::Band::iter().position(|b| b == band).unwrap_or_default().saturating_sub(1);
+    egui::ecolor::Hsva::new(index as f32 / n_bands as f32 * 0.85, 0.85, 1.0, 1.0).into()
+}
+
+/// The color of a mode, which is picked from the hash of its name so it's the same each time
+fn mode_color(mode: &str) -> Color32 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    mode.hash(&mut hasher);
+    egui::ecolor::Hsva::new((hasher.finish() % 360) as f32 / 360.0, 0.8, 1.0, 1.0).into()
+}
+
+/// The color of a contact by its age, from 0 (the newest contact) to 1 (the oldest contact)
+fn age_color(age: f32) -> Color32 {
+    let age = age.clamp(0.0, 1.0);
+    Color32::from_rgb((255.0 * (1.0 - age)) as u8, (220.0 * (1.0 - age) + 60.0 * age) as u8, (60.0 + 195.0 * age) as u8)
+}
+
+/// Hashes a contact into a u64. This is used to generate a unique but repeatable ID for each map marker.
+fn hash_contact(contact: &types::Contact) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    contact.callsign.hash(&mut hasher);
+    contact.date.hash(&mut hasher);
+    contact.time.hash(&mut hasher);
+    contact.frequency.hash(&mut hasher);
+    hasher.finish()
+}
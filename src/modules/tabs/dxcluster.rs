@@ -0,0 +1,437 @@
+//
+// Contains the code for the DX cluster tab
+//
+
+use chrono::Utc;
+use egui::{Align, CursorIcon, Id, Layout, RichText, Ui, Widget, WidgetText};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use crate::modules::{alerts, database::ColumnSortDirection, dxcluster, gui::{self, generate_random_id, Tab}};
+use crate::{types, GuiConfig};
+
+
+/// A tab that shows the spots received from the DX cluster
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct DxClusterTab {
+    /// The egui ID
+    id: Id,
+    /// The spots that have been received, with only the newest spot for each callsign and band, and the alert each spot matched
+    #[serde(skip)]
+    spots: Vec<(dxcluster::Spot, Option<alerts::Alert>)>,
+    /// The generation of the alert engine that the alerts were checked with
+    #[serde(skip)]
+    alerts_generation: u64,
+    /// The column to sort the spots by
+    sort_column: Option<SpotColumn>,
+    /// The direction to sort the spots in
+    sort_dir: ColumnSortDirection,
+    /// The band to filter for
+    band: types::Band,
+    /// The mode to filter for
+    mode: ModeFilter,
+    /// How old can the spots be before they're hidden?
+    max_age: MaxAge,
+    /// The command that should be sent to the node
+    #[serde(skip)]
+    command: String,
+    /// The frequency of the spot that should be posted, in kHz
+    #[serde(skip)]
+    spot_frequency: String,
+    /// The callsign of the spot that should be posted
+    #[serde(skip)]
+    spot_callsign: String,
+    /// The comment of the spot that should be posted
+    #[serde(skip)]
+    spot_comment: String,
+    /// Should the raw output of the node be shown?
+    show_console: bool
+}
+impl DxClusterTab {
+    /// The maximum number of spots to keep
+    const MAX_SPOTS: usize = 1000;
+
+    /// Adds a spot, replacing any older spot of the same callsign on the same band
+    fn add_spot(&mut self, config: &GuiConfig, spot: &dxcluster::Spot) {
+        let band = spot.band();
+        self.spots.retain(|(s, _)| s.callsign != spot.callsign || s.band() != band);
+        let alert = config.alerts.alert(&config.alerts_config, &alerts::Candidate::from_cluster_spot(spot));
+        self.spots.push((spot.clone(), alert));
+
+        // Remove the oldest spots if there are too many
+        if self.spots.len() > Self::MAX_SPOTS {
+            self.spots.sort_by_key(|(s, _)| std::cmp::Reverse(s.time));
+            self.spots.truncate(Self::MAX_SPOTS);
+        }
+    }
+
+    /// Shows the command and spot controls
+    fn controls_ui(&mut self, client: &dxcluster::Client, ui: &mut Ui) {
+
+        // The command textbox and buttons
+        ui.horizontal(|ui| {
+            let response = egui::TextEdit::singleline(&mut self.command)
+            .hint_text("Command (e.g. SH/DX 30)")
+            .desired_width(200.0)
+            .ui(ui);
+
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (ui.button("Send").clicked() || enter) && !self.command.trim().is_empty() {
+                client.send(&self.command);
+                self.command.clear();
+            }
+
+            if ui.button("SH/DX").on_hover_text("Ask the node for the 30 most recent spots").clicked() {
+                client.send("SH/DX 30");
+            }
+        });
+
+        // The spot form
+        ui.horizontal(|ui| {
+            egui::TextEdit::singleline(&mut self.spot_frequency)
+            .hint_text("kHz")
+            .desired_width(70.0)
+            .ui(ui);
+            egui::TextEdit::singleline(&mut self.spot_callsign)
+            .hint_text("Callsign")
+            .desired_width(90.0)
+            .ui(ui);
+            egui::TextEdit::singleline(&mut self.spot_comment)
+            .hint_text("Comment")
+            .desired_width(160.0)
+            .ui(ui);
+
+            let valid = self.spot_frequency.trim().parse::<f64>().is_ok() && !self.spot_callsign.trim().is_empty();
+            if ui.add_enabled(valid, egui::Button::new("Spot")).on_hover_text("Post a spot to the cluster").clicked() {
+                client.send(&format!("DX {} {} {}", self.spot_frequency.trim(), self.spot_callsign.trim().to_ascii_uppercase(), self.spot_comment.trim()));
+                self.spot_callsign.clear();
+                self.spot_comment.clear();
+            }
+        });
+
+    }
+
+    /// Shows the filter options
+    fn filters_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+
+            // The band filter
+            egui::ComboBox::from_id_source(self.id.with("band"))
+            .selected_text(format!("Band: {}", self.band.as_str()))
+            .show_ui(ui, |ui| {
+                for band in types::Band::iter() {
+                    ui.selectable_value(&mut self.band, band, band.as_str());
+                }
+            });
+
+            // The mode filter
+            egui::ComboBox::from_id_source(self.id.with("mode"))
+            .selected_text(format!("Mode: {}", self.mode))
+            .show_ui(ui, |ui| {
+                for mode in ModeFilter::iter() {
+                    ui.selectable_value(&mut self.mode, mode, mode.to_string());
+                }
+            });
+
+            // The age filter
+            egui::ComboBox::from_id_source(self.id.with("age"))
+            .selected_text(format!("Age: {}", self.max_age))
+            .show_ui(ui, |ui| {
+                for age in MaxAge::iter() {
+                    ui.selectable_value(&mut self.max_age, age, age.to_string());
+                }
+            });
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                if ui.button("Clear").clicked() {
+                    self.spots.clear();
+                }
+                ui.checkbox(&mut self.show_console, "Console");
+            });
+
+        });
+    }
+
+    /// Checks the spots for alerts again if the log or the alert rules changed since they were checked
+    fn refresh_alerts(&mut self, config: &GuiConfig) {
+        if self.alerts_generation == config.alerts.generation() {
+            return;
+        }
+        for (spot, alert) in &mut self.spots {
+            *alert = config.alerts.alert(&config.alerts_config, &alerts::Candidate::from_cluster_spot(spot));
+        }
+        self.alerts_generation = config.alerts.generation();
+    }
+
+    /// Gets the spots that should be shown, filtered and sorted
+    fn visible_spots(&self) -> Vec<&(dxcluster::Spot, Option<alerts::Alert>)> {
+        let now = Utc::now().naive_utc();
+
+        let mut spots: Vec<&(dxcluster::Spot, Option<alerts::Alert>)> = self.spots.iter()
+            .filter(|(s, _)| self.band == types::Band::All || s.band() == self.band)
+            .filter(|(s, _)| self.mode.matches(&s.mode()))
+            .filter(|(s, _)| self.max_age.duration().map_or(true, |age| now.signed_duration_since(s.time) <= age))
+            .collect();
+
+        // Sort the spots, showing the newest first by default
+        match self.sort_column {
+            Some(SpotColumn::Time) | None => spots.sort_by_key(|(s, _)| std::cmp::Reverse(s.time)),
+            Some(SpotColumn::Frequency) => spots.sort_by_key(|(s, _)| s.frequency),
+            Some(SpotColumn::Callsign) => spots.sort_by(|(a, _), (b, _)| a.callsign.cmp(&b.callsign)),
+            Some(SpotColumn::Band) => spots.sort_by_key(|(s, _)| s.frequency),
+            Some(SpotColumn::Mode) => spots.sort_by_key(|(s, _)| s.mode().to_string()),
+            Some(SpotColumn::Spotter) => spots.sort_by(|(a, _), (b, _)| a.spotter.cmp(&b.spotter)),
+            Some(SpotColumn::Comment) => spots.sort_by(|(a, _), (b, _)| a.comment.cmp(&b.comment))
+        }
+        if self.sort_column.is_some() && self.sort_dir == ColumnSortDirection::Descending {
+            spots.reverse();
+        }
+
+        spots
+    }
+}
+impl Tab for DxClusterTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "DX Cluster".into()
+    }
+
+    fn scroll_bars(&self) -> [bool; 2] {
+        [true, false]
+    }
+
+    fn process_event(&mut self, config: &mut GuiConfig, event: &types::Event) {
+        if let types::Event::DxSpot(spot) = event {
+            self.add_spot(config, spot);
+        }
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+        use egui_extras::Column;
+
+        // Show the status of the connection and the controls
+        match &config.dxcluster {
+            Some(client) => {
+                match client.connected() {
+                    true => ui.label(format!("Connected to {}", client.address())),
+                    false => ui.colored_label(ui.style().visuals.warn_fg_color, format!("Connecting to {}", client.address()))
+                };
+                self.controls_ui(client, ui);
+            },
+            None if config.station.callsign.trim().is_empty() => { ui.label("Set your callsign in the station settings to log in to the DX cluster"); },
+            None => { ui.label("The DX cluster client is disabled, enable it in the settings tab"); }
+        }
+
+        self.filters_ui(ui);
+
+        // Show the raw output of the node
+        if self.show_console {
+            if let Some(client) = &config.dxcluster {
+                egui::ScrollArea::vertical()
+                .id_source(self.id.with("console"))
+                .max_height(150.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in client.console() {
+                        ui.monospace(line);
+                    }
+                });
+            }
+        }
+
+        ui.separator();
+
+        self.refresh_alerts(config);
+        let spots = self.visible_spots();
+        let mut clicked = None;
+        let mut sort_clicked = None;
+
+        egui_extras::TableBuilder::new(ui)
+        .column(Column::initial(45.0).at_least(45.0)) // Time
+        .column(Column::initial(80.0).at_least(80.0)) // Frequency
+        .column(Column::initial(80.0).at_least(60.0)) // Callsign
+        .column(Column::initial(45.0).at_least(45.0)) // Band
+        .column(Column::initial(45.0).at_least(45.0)) // Mode
+        .column(Column::initial(80.0).at_least(60.0)) // Spotter
+        .column(Column::remainder().at_least(100.0).clip(true)) // Comment
+        .cell_layout(Layout::left_to_right(Align::Center))
+        .resizable(true)
+        .striped(true)
+        .sense(egui::Sense::click())
+        .header(20.0, |mut header| {
+            for column in SpotColumn::iter() {
+
+                // Highlight this column if it's selected
+                header.set_selected(self.sort_column == Some(column));
+
+                let response = header.col(|ui| {
+                    let text = RichText::new(column.to_string()).strong();
+                    egui::Label::new(text).selectable(false).ui(ui);
+                }).1;
+
+                if response.on_hover_cursor(CursorIcon::PointingHand).clicked() {
+                    sort_clicked = Some(column);
+                }
+            }
+        })
+        .body(|body| {
+            body.rows(18.0, spots.len(), |mut row| {
+                let (spot, alert) = spots[row.index()];
+
+                // Highlight the spots that match an alert rule
+                let highlight = |ui: &mut Ui| if let Some(alert) = alert { alerts::highlight(ui, alert) };
+
+                row.col(|ui| { highlight(ui); ui.label(spot.time.format("%H%MZ").to_string()); });
+                row.col(|ui| { highlight(ui); ui.label(gui::frequency_formatter(spot.frequency as f64, 0..=0)); });
+                row.col(|ui| { highlight(ui); ui.strong(&spot.callsign); });
+                row.col(|ui| { highlight(ui); ui.label(spot.band().as_str()); });
+                row.col(|ui| { highlight(ui); ui.label(spot.mode().to_string()); });
+                row.col(|ui| { highlight(ui); ui.label(&spot.spotter); });
+                row.col(|ui| { highlight(ui); ui.label(&spot.comment); });
+
+                let hover_text = match alert {
+                    Some(alert) => format!("{}, click to tune the radio and fill the contact logger", alert.kind),
+                    None => "Click to tune the radio and fill the contact logger".into()
+                };
+                let response = row.response().on_hover_text(hover_text);
+                if response.clicked() {
+                    clicked = Some(spot.clone());
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Lookup callsign").on_hover_text("You must have a callsign lookup tab open to see the result").clicked() {
+                        config.events.push_back((None, types::Event::LookupCallsign(spot.callsign.clone())));
+                        ui.close_menu();
+                    }
+                });
+            });
+        });
+
+        // Tune the radio and fill the logger with the clicked spot
+        if let Some(spot) = clicked {
+            config.events.push_back((None, types::Event::Tune(spot.frequency)));
+            config.events.push_back((None, types::Event::PrefillLogger(types::LoggerPrefill {
+                callsign: Some(spot.callsign.clone()),
+                frequency: Some(spot.frequency),
+                mode: Some(spot.mode()),
+                ..Default::default()
+            })));
+        }
+
+        // Go through the sorting logic (ascending, descending, none)
+        if let Some(column) = sort_clicked {
+            match (self.sort_column == Some(column), self.sort_dir) {
+                (false, _) => {
+                    self.sort_column = Some(column);
+                    self.sort_dir = ColumnSortDirection::Ascending;
+                },
+                (true, ColumnSortDirection::Ascending) => self.sort_dir = ColumnSortDirection::Descending,
+                (true, ColumnSortDirection::Descending) => {
+                    self.sort_column = None;
+                    self.sort_dir = ColumnSortDirection::Ascending;
+                }
+            }
+        }
+
+    }
+}
+impl Default for DxClusterTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            spots: Default::default(),
+            alerts_generation: Default::default(),
+            sort_column: Default::default(),
+            sort_dir: Default::default(),
+            band: types::Band::All,
+            mode: Default::default(),
+            max_age: Default::default(),
+            command: Default::default(),
+            spot_frequency: Default::default(),
+            spot_callsign: Default::default(),
+            spot_comment: Default::default(),
+            show_console: Default::default()
+        }
+    }
+}
+impl std::fmt::Debug for DxClusterTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DxClusterTab")
+        .field("id", &self.id)
+        .field("spots", &self.spots.len())
+        .field("sort_column", &self.sort_column)
+        .field("sort_dir", &self.sort_dir)
+        .field("band", &self.band)
+        .field("mode", &self.mode)
+        .field("max_age", &self.max_age)
+        .finish()
+    }
+}
+
+
+/// A column in the spot table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+enum SpotColumn {
+    #[strum(to_string = "UTC")]
+    Time,
+    #[strum(to_string = "Freq")]
+    Frequency,
+    #[strum(to_string = "DX")]
+    Callsign,
+    Band,
+    Mode,
+    Spotter,
+    Comment
+}
+
+/// A mode filter for the spot table
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+enum ModeFilter {
+    #[default]
+    All,
+    #[strum(to_string = "CW")]
+    Cw,
+    Phone,
+    Digital
+}
+impl ModeFilter {
+    /// Does the mode match this filter?
+    fn matches(&self, mode: &types::Mode) -> bool {
+        match self {
+            Self::All => true,
+            Self::Cw => mode.is_cw(),
+            Self::Phone => mode.is_ssb() || mode.is_am() || mode.is_fm(),
+            Self::Digital => !(mode.is_cw() || mode.is_ssb() || mode.is_am() || mode.is_fm())
+        }
+    }
+}
+
+/// An age filter for the spot table. This is also used by the RBN tab.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+pub enum MaxAge {
+    #[strum(to_string = "15 minutes")]
+    Minutes15,
+    #[default]
+    #[strum(to_string = "30 minutes")]
+    Minutes30,
+    #[strum(to_string = "1 hour")]
+    Hour1,
+    #[strum(to_string = "3 hours")]
+    Hours3,
+    All
+}
+impl MaxAge {
+    /// The maximum age of a spot, or `None` if every spot should be shown
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        match self {
+            Self::Minutes15 => Some(chrono::Duration::minutes(15)),
+            Self::Minutes30 => Some(chrono::Duration::minutes(30)),
+            Self::Hour1 => Some(chrono::Duration::hours(1)),
+            Self::Hours3 => Some(chrono::Duration::hours(3)),
+            Self::All => None
+        }
+    }
+}
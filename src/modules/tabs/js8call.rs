@@ -0,0 +1,228 @@
+//
+// Contains the code for the JS8Call tab
+//
+
+use std::{collections::HashMap, hash::{Hash, Hasher}};
+use egui::{Id, Ui, WidgetText};
+use geo::Coord;
+use serde::{Deserialize, Serialize};
+use crate::modules::{gui::{self, generate_random_id, Tab}, js8call, maidenhead, map::{self, MapLayer, MapMarkerTrait}};
+use crate::{types, GuiConfig};
+use super::callsign_lookup;
+
+
+type CallsignString = arrayvec::ArrayString<20>;
+type GridString = arrayvec::ArrayString<10>;
+
+
+/// A tab that shows the stations heard by JS8Call on a map
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Js8CallTab {
+    /// The egui ID
+    id: Id,
+    #[serde(skip)]
+    map: Option<map::MapWidget>,
+    /// The position and zoom of the map, so it's restored when the tab is reopened
+    view: map::MapView,
+    /// The layer of heard station markers
+    #[serde(skip)]
+    stations: map::MarkerLayer<HeardStationMarker>,
+    /// The stations that have been heard, indexed by callsign
+    #[serde(skip)]
+    heard: HashMap<CallsignString, HeardStationMarker>,
+    /// Should the map markers be rebuilt on the next frame?
+    #[serde(skip)]
+    update_markers: bool
+}
+impl Tab for Js8CallTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "JS8Call".into()
+    }
+
+    fn process_event(&mut self, _config: &mut GuiConfig, event: &types::Event) {
+        if let types::Event::Js8CallHeard(station) = event {
+
+            // We can't show stations without a grid square on the map
+            let Ok(grid) = GridString::from(&station.grid) else { return };
+            let Ok(location) = maidenhead::grid_to_lat_lon(&grid) else { return };
+            let Ok(callsign) = CallsignString::from(&station.callsign) else { return };
+
+            // Only replace the station if this report is newer than the one we already have
+            let heard = self.heard.get(&callsign);
+            if heard.is_some_and(|h| h.time > station.time) {
+                return;
+            }
+
+            self.heard.insert(callsign, HeardStationMarker {
+                id: hash_callsign(&callsign),
+                location,
+                callsign,
+                grid,
+                snr: station.snr,
+                frequency: station.frequency,
+                time: station.time
+            });
+            self.update_markers = true;
+        }
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+
+        // Get the map widget, initializing it if it doesn't exist
+        let map = self.map.get_or_insert_with(|| {
+            let mut map = map::MapWidget::new(ui.ctx());
+            map.set_view(self.view);
+            map.set_callsign_locator(callsign_lookup::location_promise);
+            map
+        });
+
+        // Rebuild the map markers if a station was heard
+        if self.update_markers {
+            *self.stations.markers_mut() = self.heard.values().copied().collect();
+            map.update_overlay();
+            self.update_markers = false;
+        }
+
+        // Show the status of JS8Call above the map
+        ui.horizontal(|ui| {
+            match &config.js8call {
+                Some(client) => {
+                    ui.label(format!("{} - {}", client.station_callsign(), gui::frequency_formatter(client.frequency() as f64, 0..=0)));
+                    if ui.button("Refresh").on_hover_text("Ask JS8Call for the recent call activity").clicked() {
+                        client.send("RX.GET_CALL_ACTIVITY", "");
+                    }
+                },
+                None => { ui.label("The JS8Call client is disabled, enable it in the settings tab"); }
+            }
+
+            ui.label(format!("Heard stations: {}", self.heard.len()));
+
+            if ui.button("Clear").clicked() {
+                self.heard.clear();
+                self.update_markers = true;
+            }
+        });
+
+        // Show the map widget
+        let mut layers: [&mut dyn MapLayer; 1] = [&mut self.stations];
+        map.ui_with_layers(ui, config, &mut layers);
+        self.view = map.view();
+
+    }
+}
+impl Default for Js8CallTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            map: Default::default(),
+            view: Default::default(),
+            stations: map::MarkerLayer::new("Heard stations"),
+            heard: Default::default(),
+            update_markers: Default::default()
+        }
+    }
+}
+impl std::fmt::Debug for Js8CallTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Js8CallTab")
+        .field("id", &self.id)
+        .field("map", &self.map)
+        .field("heard", &self.heard.len())
+        .finish()
+    }
+}
+
+
+/// A station heard by JS8Call, shown as a marker on the map
+#[derive(Debug, Clone, Copy)]
+struct HeardStationMarker {
+    /// The ID of the map marker. This is a hash of the callsign.
+    id: u64,
+    /// The location of the station
+    location: Coord<f64>,
+    /// The callsign of the station
+    callsign: CallsignString,
+    /// The grid square of the station
+    grid: GridString,
+    /// The signal to noise ratio, in dB
+    snr: i32,
+    /// The frequency that the station was heard on, in Hz
+    frequency: u64,
+    /// The time that the station was heard, in seconds since the UNIX epoch
+    time: u64
+}
+impl MapMarkerTrait for HeardStationMarker {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        &self.location
+    }
+
+    fn hovered_ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) {
+        ui.heading(self.callsign.as_str());
+        ui.label(format!("Grid: {}", self.grid));
+        ui.label(format!("SNR: {}dB", self.snr));
+        if self.frequency != 0 {
+            ui.label(format!("Frequency: {}", gui::frequency_formatter(self.frequency as f64, 0..=0)));
+        }
+        if let Some(time) = chrono::DateTime::from_timestamp(self.time as i64, 0) {
+            ui.label(format!("Last heard (UTC): {}", time.format("%H:%M:%S")));
+        }
+    }
+
+    fn selected_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        self.hovered_ui(ui, config);
+
+        // Fill the logger with this station
+        if ui.button("Log this station").clicked() {
+            config.events.push_back((None, types::Event::PrefillLogger(types::LoggerPrefill {
+                callsign: Some(self.callsign.to_string()),
+                grid: Some(self.grid.to_string()),
+                rx_rst: Some(self.snr.to_string()),
+                mode: Some(types::Mode::JS8CALL),
+                ..Default::default()
+            })));
+        }
+
+        // Look up the callsign
+        if ui.button("Lookup callsign").clicked() {
+            config.events.push_back((None, types::Event::LookupCallsign(self.callsign.to_string())));
+        }
+    }
+
+    fn color(&self, config: &mut GuiConfig) -> image::Rgba<u8> {
+        image::Rgba(config.js8call_config.marker_color)
+    }
+
+    fn size(&self) -> f32 {
+        map::snr_size(self.snr as f32)
+    }
+
+    fn label(&self) -> Option<&str> {
+        Some(self.callsign.as_str())
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        vec![
+            ("callsign", self.callsign.to_string()),
+            ("grid", self.grid.to_string()),
+            ("snr", self.snr.to_string()),
+            ("frequency", self.frequency.to_string()),
+            ("time", chrono::DateTime::from_timestamp(self.time as i64, 0).map(|t| t.to_rfc3339()).unwrap_or_default())
+        ]
+    }
+}
+
+/// Hashes a callsign into a u64. This is used so each station keeps the same marker ID when it's heard again.
+fn hash_callsign(callsign: &CallsignString) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    callsign.hash(&mut hasher);
+    hasher.finish()
+}
@@ -4,3 +4,10 @@ pub mod pskreporter;
 pub mod contact_logger;
 pub mod settings;
 pub mod band_allocations;
+pub mod wsjtx;
+pub mod js8call;
+pub mod dxcluster;
+pub mod rbn;
+pub mod solar;
+pub mod muf;
+pub mod contacts_map;
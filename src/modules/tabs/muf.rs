@@ -0,0 +1,276 @@
+//
+// Contains the code for the MUF map tab
+//
+
+use std::{hash::{Hash, Hasher}, time::Instant};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use egui::{Id, Ui, Widget, WidgetText};
+use geo::Coord;
+use log::error;
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use crate::modules::{greyline, gui::{generate_random_id, Tab}, map::{self, MapLayer, MapMarkerTrait}, muf};
+use crate::{types, GuiConfig, RT};
+use super::callsign_lookup;
+
+
+type CodeString = arrayvec::ArrayString<16>;
+type NameString = arrayvec::ArrayString<48>;
+
+
+/// A tab that shows a MUF or foF2 map, loaded from a URL or a local file
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct MufMapTab {
+    /// The egui ID
+    id: Id,
+    #[serde(skip)]
+    map: Option<map::MapWidget>,
+    /// The position and zoom of the map, so it's restored when the tab is reopened
+    view: map::MapView,
+    /// The MUF layer drawn on the map
+    layer: muf::Layer,
+    /// The layer of ionosonde station markers
+    #[serde(skip)]
+    stations: map::MarkerLayer<StationMarker>,
+    /// The greyline layer, drawn over the MUF layer
+    greyline: greyline::Layer,
+    /// The projection of the map
+    projection: map::Projection,
+    /// The URL or path of the station list or GeoJSON file
+    source: String,
+    /// How often the data is reloaded, in minutes. Zero disables reloading.
+    refresh_rate: u64,
+    /// The task that is loading the data
+    #[serde(skip)]
+    task: Option<Promise<Result<muf::Data>>>,
+    /// When the data was last loaded. This is updated when the task is finished, not started.
+    #[serde(skip)]
+    last_load: Option<Instant>,
+    /// When the data was last loaded, shown to the user
+    #[serde(skip)]
+    loaded_at: Option<DateTime<Utc>>
+}
+impl MufMapTab {
+    /// Starts loading the data from a URL or a local file
+    fn load(source: &str) -> Promise<Result<muf::Data>> {
+        let _eg = RT.enter();
+        Promise::spawn_async(muf::Data::load(source.trim().to_string()))
+    }
+}
+impl Tab for MufMapTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "MUF Map".into()
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+
+        // Get the map widget, initializing it if it doesn't exist
+        let map = self.map.get_or_insert_with(|| {
+            let mut map = map::MapWidget::new(ui.ctx());
+            map.set_view(self.view);
+            map.set_callsign_locator(callsign_lookup::location_promise);
+            map
+        });
+
+        // The data finished loading; update the layer and the station markers
+        if let Some(task) = self.task.take_if(|t| t.poll().is_ready()) {
+            self.last_load = Some(Instant::now());
+
+            match task.block_and_take() {
+                Ok(data) => {
+                    *self.stations.markers_mut() = data.stations().iter().map(StationMarker::from_station).collect();
+                    self.layer.set_data(Some(data));
+                    self.loaded_at = Some(Utc::now());
+                },
+                Err(err) => {
+                    error!("Failed to load the MUF data: {err}");
+                    config.notification_read = false;
+                    config.notifications.push(types::Notification::Error(format!("Failed to load the MUF data: {err}")));
+                }
+            }
+            map.update_overlay();
+        }
+
+        // Load the data on the first frame, and again when the refresh rate has elapsed
+        let refresh_due = self.last_load.map_or(true, |t| self.refresh_rate > 0 && t.elapsed().as_secs() >= self.refresh_rate * 60);
+        if self.task.is_none() && refresh_due && !self.source.trim().is_empty() {
+            self.task = Some(Self::load(&self.source));
+        }
+
+        // Show the source and controls above the map
+        ui.horizontal(|ui| {
+
+            // The source textbox
+            let response = egui::widgets::TextEdit::singleline(&mut self.source)
+            .hint_text(muf::STATIONS_URL)
+            .desired_width(320.0)
+            .ui(ui)
+            .on_hover_text("A prop.kc2g.com station list or GeoJSON contour file, as a URL or a local path");
+
+            // Load the data when enter is pressed or the button is clicked
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if self.task.is_some() {
+                ui.spinner();
+            } else if (ui.button("Load").clicked() || submitted) && !self.source.trim().is_empty() {
+                self.task = Some(Self::load(&self.source));
+            }
+
+            // The refresh rate
+            ui.label("Reload every");
+            egui::widgets::DragValue::new(&mut self.refresh_rate)
+            .clamp_range(0..=1440)
+            .suffix(" min")
+            .ui(ui)
+            .on_hover_text("Set to zero to disable reloading");
+
+            // Station settings, which only apply to station lists
+            if let Some(muf::Data::Stations(stations)) = self.layer.data() {
+                ui.separator();
+                ui.label(format!("Stations: {}", stations.len()));
+                ui.label("Max age");
+                if egui::widgets::DragValue::new(&mut self.layer.max_age).clamp_range(1..=48).suffix(" h").ui(ui).changed() {
+                    map.update_overlay();
+                }
+            }
+
+            // The opacity of the layer
+            ui.separator();
+            ui.label("Opacity");
+            if egui::widgets::Slider::new(&mut self.layer.opacity, 0..=255).show_value(false).ui(ui).changed() {
+                map.update_overlay();
+            }
+
+            // The projection combobox
+            self.projection.combo_box(ui, self.id.with("projection"));
+
+            if let Some(loaded_at) = self.loaded_at {
+                ui.label(format!("Loaded {}", loaded_at.format("%H:%M UTC")));
+            }
+        });
+
+        // Show the map widget in the selected projection with the MUF, greyline, and station layers
+        map.set_projection(self.projection);
+        let mut layers: [&mut dyn MapLayer; 3] = [&mut self.layer, &mut self.greyline, &mut self.stations];
+        map.ui_with_layers(ui, config, &mut layers);
+        self.view = map.view();
+
+    }
+}
+impl Default for MufMapTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            map: Default::default(),
+            view: Default::default(),
+            layer: Default::default(),
+            stations: map::MarkerLayer::new("Stations"),
+            greyline: Default::default(),
+            projection: Default::default(),
+            source: muf::STATIONS_URL.to_string(),
+            refresh_rate: 15,
+            task: None,
+            last_load: None,
+            loaded_at: None
+        }
+    }
+}
+impl std::fmt::Debug for MufMapTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MufMapTab")
+        .field("id", &self.id)
+        .field("map", &self.map)
+        .field("source", &self.source)
+        .field("refresh_rate", &self.refresh_rate)
+        .finish()
+    }
+}
+
+
+/// An ionosonde station, shown as a marker on the map
+#[derive(Debug, Clone, Copy)]
+struct StationMarker {
+    /// The ID of the map marker. This is a hash of the station code.
+    id: u64,
+    /// The location of the station
+    location: Coord<f64>,
+    /// The URSI code of the station
+    code: CodeString,
+    /// The name of the station
+    name: NameString,
+    /// When the station was measured, in seconds since the UNIX epoch
+    time: i64,
+    /// How confident the automatic scaling was, from 0 to 1
+    confidence: f64,
+    /// The critical frequency of the F2 layer, in MHz
+    fof2: Option<f64>,
+    /// The maximum usable frequency for a 3000km path, in MHz
+    mufd: Option<f64>
+}
+impl StationMarker {
+    fn from_station(station: &muf::Station) -> Self {
+        let mut hasher = std::hash::DefaultHasher::new();
+        station.code.hash(&mut hasher);
+
+        // Truncate anything that doesn't fit
+        let truncate = |s: &str, len: usize| s.char_indices().take_while(|(i, c)| i + c.len_utf8() <= len).map(|(_, c)| c).collect::<String>();
+
+        Self {
+            id: hasher.finish(),
+            location: station.location,
+            code: CodeString::from(&truncate(&station.code, 16)).unwrap_or_default(),
+            name: NameString::from(&truncate(&station.name, 48)).unwrap_or_default(),
+            time: station.time,
+            confidence: station.confidence,
+            fof2: station.fof2,
+            mufd: station.mufd
+        }
+    }
+}
+impl MapMarkerTrait for StationMarker {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        &self.location
+    }
+
+    fn hovered_ui(&mut self, ui: &mut Ui, _config: &mut GuiConfig) {
+        ui.heading(format!("{} ({})", self.name, self.code));
+        if let Some(mufd) = self.mufd {
+            ui.label(format!("MUF (3000km): {mufd:.1} MHz"));
+        }
+        if let Some(fof2) = self.fof2 {
+            ui.label(format!("foF2: {fof2:.1} MHz"));
+        }
+        ui.label(format!("Confidence: {:.0}%", self.confidence * 100.0));
+        if let Some(time) = DateTime::from_timestamp(self.time, 0) {
+            ui.label(format!("Measured (UTC): {}", time.format("%d/%m %H:%M")));
+        }
+    }
+
+    fn color(&self, _config: &mut GuiConfig) -> image::Rgba<u8> {
+        image::Rgba([255, 255, 255, 255])
+    }
+
+    fn label(&self) -> Option<&str> {
+        Some(self.code.as_str())
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        vec![
+            ("code", self.code.to_string()),
+            ("name", self.name.to_string()),
+            ("time", DateTime::from_timestamp(self.time, 0).map(|t| t.to_rfc3339()).unwrap_or_default()),
+            ("confidence", self.confidence.to_string()),
+            ("fof2", self.fof2.map(|f| f.to_string()).unwrap_or_default()),
+            ("mufd", self.mufd.map(|m| m.to_string()).unwrap_or_default())
+        ]
+    }
+}
@@ -2,12 +2,14 @@
 // A PSKReporter abstraction interface
 //
 
-use std::{collections::HashMap, hash::{Hash, Hasher}, str::FromStr, time::{Duration, Instant}};
+use std::{collections::{HashMap, HashSet}, str::FromStr, time::{Duration, Instant}};
 use crate::{GuiConfig, ACCENT_COLOR, RT};
-use super::super::{gui::{self, Tab}, maidenhead, map::{self, MapMarkerTrait}};
+use super::super::{alerts, dxcc, geodesy, greyline, grids, gui::{self, Tab}, maidenhead, map::{self, MapLayer, MapMarkerTrait}, pskreporter::{hash_reception_report, CallsignString, GridString, ModeString, ReceptionReport}, pskreporter_mqtt, types::{self, Band}};
+use super::callsign_lookup;
 use anyhow::Result;
 use egui::{Id, Widget};
-use geo::{point, Coord, GeodesicBearing};
+use egui_plot::{BoxElem, BoxPlot, BoxSpread, GridMark, Plot};
+use geo::Coord;
 use log::{debug, error, warn};
 use poll_promise::Promise;
 use rand::{RngCore, SeedableRng};
@@ -16,18 +18,18 @@ use strum::IntoEnumIterator;
 use thiserror::Error;
 
 
-type CallsignString = arrayvec::ArrayString<20>;
-type GridString = arrayvec::ArrayString<10>;
-type ModeString = arrayvec::ArrayString<16>;
-
-
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
 pub struct PSKReporterTab {
     /// The ID of the tab
     id: Id,
     #[serde(skip)]
-    map: Option<map::MapWidget<MapMarker>>,
+    map: Option<map::MapWidget>,
+    /// The position and zoom of the map, so it's restored when the tab is reopened
+    view: map::MapView,
+    /// The layer of station and reception report markers
+    #[serde(skip)]
+    reports: map::MarkerLayer<MapMarker>,
     #[serde(skip)]
     /// The async task that queries the API and returns our map markers
     api_task: Option<Promise<Result<Vec<MapMarker>>>>,
@@ -43,10 +45,179 @@ pub struct PSKReporterTab {
     /// 
     /// This is used to automatically query the API every once in a while.
     last_query_options: Option<QueryOptions>,
+    /// Where the reports on the map come from
+    source: Source,
+    /// The live feed client. This is `None` if the live feed is off, or if nothing has been searched for yet.
+    #[serde(skip)]
+    stream: Option<pskreporter_mqtt::Client>,
+    /// The reason the live feed couldn't be started with the last query options (e.g. nothing is filtered)
+    #[serde(skip)]
+    stream_error: Option<String>,
+    /// The last time the live reports were faded and expired
+    #[serde(skip)]
+    last_fade: Option<Instant>,
+    /// The reports that haven't been saved to the database yet
+    #[serde(skip)]
+    unsaved: Vec<ReceptionReport>,
+    /// The last time the reports were saved to the database
+    #[serde(skip)]
+    last_save: Option<Instant>,
+    /// The async tasks that save the reports to the database
+    #[serde(skip)]
+    save_tasks: Vec<Promise<Result<()>>>,
+    /// The playback of the saved reports
+    history: History,
+    /// The layer of reports from 24 hours before the playback time
+    #[serde(skip)]
+    yesterday: map::MarkerLayer<ComparisonMarker>,
+    /// The reach statistics and A/B comparison panel
+    analytics: Analytics,
+    /// The greyline layer
+    greyline: greyline::Layer,
+    /// The Maidenhead grid layer
+    grids: grids::Layer,
+    /// The projection of the map
+    projection: map::Projection,
+    /// The generation of the alert engine that the markers were checked with
+    #[serde(skip)]
+    alerts_generation: u64
 }
 impl PSKReporterTab {
     /// The height of the progress bar slider
     const SLIDER_HEIGHT: f32 = 8.0;
+    /// How often the live reports are faded and expired
+    const FADE_INTERVAL: Duration = Duration::from_secs(1);
+    /// The maximum number of live reports shown on the map. The oldest reports are removed first.
+    const MAX_LIVE_REPORTS: usize = 5000;
+    /// How often the received reports are saved to the database
+    const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Saves the received reports to the database every once in a while, and checks the results of the previous saves
+    fn save_reports(&mut self, config: &mut GuiConfig) {
+
+        // Check the results of the previous saves
+        for task in self.save_tasks.extract_if(|t| t.ready().is_some()) {
+            if let Err(err) = task.block_and_take() {
+                error!("Failed to save PSKReporter reports: {err}");
+            }
+        }
+
+        // Save the reports in batches, because the live feed can receive many reports a second
+        if self.unsaved.is_empty() || self.last_save.is_some_and(|t| t.elapsed() < Self::SAVE_INTERVAL) {
+            return;
+        }
+
+        // Delete the reports that are older than the history retention period while we're at it
+        let cutoff = (chrono::Utc::now().timestamp() as u64).saturating_sub(config.pskreporter_config.history_days * 86_400);
+        self.save_tasks.push(config.db_api.insert_reception_reports_promise(std::mem::take(&mut self.unsaved), cutoff));
+        self.last_save = Some(Instant::now());
+    }
+
+    /// Replaces the report markers with the reports at the playback time. Returns true if the markers changed.
+    fn process_history(&mut self, config: &mut GuiConfig, ctx: &egui::Context) -> bool {
+        let Some(options) = self.last_query_options.as_ref() else {
+            return false;
+        };
+        self.history.process(config, options);
+        self.history.advance(ctx);
+
+        // Only rebuild the markers if the reports, the playback second, the window, the band, or the yesterday toggle changed
+        let window = options.last.as_duration().as_secs();
+        let shown = (self.history.generation, self.history.time as u64, window, self.history.band, self.history.compare_yesterday);
+        if self.history.shown == Some(shown) {
+            return false;
+        }
+        self.history.shown = Some(shown);
+
+        // The reports at the playback time, and 24 hours earlier if they're being compared
+        let mut markers = self.history.markers(options, 0, window);
+        let station = markers.iter().find_map(MapMarker::report).and_then(|r| station_marker(r, options, History::STATION_MARKER_ID));
+        markers.extend(station);
+        let yesterday: Vec<ComparisonMarker> = match self.history.compare_yesterday {
+            true => self.history.markers(options, 86_400, window).into_iter().map(ComparisonMarker).collect(),
+            false => Vec::new()
+        };
+
+        // Only update the map if the markers changed
+        let mut changed = false;
+        if self.reports.markers().iter().map(|m| m.id()).ne(markers.iter().map(|m| m.id())) {
+            // The saved reports were already alerted for when they were received, so they're only checked for the highlight color
+            for marker in &mut markers {
+                marker.check_alert(config);
+            }
+            *self.reports.markers_mut() = markers;
+            changed = true;
+        }
+        if self.yesterday.markers().iter().map(|m| m.id()).ne(yesterday.iter().map(|m| m.id())) {
+            *self.yesterday.markers_mut() = yesterday;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Streams reports from the live feed into the reports layer, then fades and expires the old ones. Returns true if the markers changed.
+    fn process_stream(&mut self, config: &mut GuiConfig, ctx: &egui::Context) -> bool {
+
+        // The live feed is off, or there isn't anything to stream yet
+        let Some(options) = self.last_query_options.clone().filter(|_| self.source == Source::Live) else {
+            self.stream = None;
+            self.stream_error = None;
+            return false;
+        };
+
+        // Connect, or reconnect if the search or the broker address changed
+        let client = pskreporter_mqtt::Client::reconcile(self.stream.take(), true, &config.pskreporter_config.mqtt_address, &options.live_filter());
+        self.stream_error = client.as_ref().err().map(ToString::to_string);
+        let Ok(Some(mut client)) = client else {
+            return false;
+        };
+        let reports = client.process();
+        self.stream = Some(client);
+
+        let mut changed = !reports.is_empty();
+        let lifetime = Some(options.last.as_duration().as_secs());
+
+        for report in reports.iter().map(ReceptionReport::from) {
+            // Skip the reports with an invalid grid square
+            let Some(mut marker) = report_marker(&report, &options, lifetime) else { continue };
+
+            // Add the station that was searched for from the first report
+            if !self.reports.markers().iter().any(|m| m.report().is_none()) {
+                let id = rand::rngs::SmallRng::from_entropy().next_u64();
+                self.reports.markers_mut().extend(station_marker(&report, &options, id));
+            }
+
+            // Only keep the newest report of each path, so repeated transmissions don't pile up on top of each other
+            self.reports.markers_mut().retain(|m| !m.report().is_some_and(|r| r.tx_callsign == report.tx_callsign && r.rx_callsign == report.rx_callsign));
+
+            // Check the transmitting stations for alerts
+            if let MapMarker::ReceptionReportTransmitter { alert, .. } = &mut marker {
+                *alert = alerts::raise(config, &report.alert_candidate());
+            }
+            self.reports.markers_mut().push(marker);
+            self.unsaved.push(report);
+        }
+
+        // Remove the oldest reports if there are too many
+        let mut excess = self.reports.markers().iter().filter(|m| m.report().is_some()).count().saturating_sub(Self::MAX_LIVE_REPORTS);
+        self.reports.markers_mut().retain(|m| {
+            let remove = excess > 0 && m.report().is_some();
+            excess -= remove as usize;
+            !remove
+        });
+
+        // Every once in a while, remove the reports that are older than the selected duration, and repaint the others so they fade
+        if self.last_fade.map_or(true, |t| t.elapsed() >= Self::FADE_INTERVAL) {
+            let now = chrono::Utc::now().timestamp();
+            let max_age = options.last.as_duration().as_secs() as i64;
+            self.reports.markers_mut().retain(|m| m.report().map_or(true, |r| now - (r.time as i64) < max_age));
+            self.last_fade = Some(Instant::now());
+            changed = true;
+        }
+        ctx.request_repaint_after(Self::FADE_INTERVAL);
+
+        changed
+    }
 }
 impl Tab for PSKReporterTab {
     fn id(&self) -> Id {
@@ -58,11 +229,38 @@ impl Tab for PSKReporterTab {
     }
 
     fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // Stream any new reports from the live feed
+        let stream_changed = self.process_stream(config, ui.ctx());
+
+        // Play back the saved reports
+        let history_changed = self.source == Source::History && self.process_history(config, ui.ctx());
+
+        // Save the reports that were received
+        self.save_reports(config);
         
         // Get the map widget, initializing it if it doesn't exist
         // NOTE: We use get_or_insert_with here instead of get_or_insert because it lazily initializes the map widget.
         // Using get_or_insert caused a huge performance hit, presumably because the value wasn't being lazily initialized.
-        let map = self.map.get_or_insert_with(|| map::MapWidget::new(ui.ctx()));
+        let map = self.map.get_or_insert_with(|| {
+            let mut map = map::MapWidget::new(ui.ctx());
+            map.set_view(self.view);
+            map.set_callsign_locator(callsign_lookup::location_promise);
+            map
+        });
+
+        // The log or the alert rules changed, so check the reports again
+        let alerts_changed = self.alerts_generation != config.alerts.generation();
+        if alerts_changed {
+            for marker in self.reports.markers_mut() {
+                marker.check_alert(config);
+            }
+            self.alerts_generation = config.alerts.generation();
+        }
+
+        if stream_changed || history_changed || alerts_changed {
+            map.update_markers();
+        }
 
         // The pending task finished; process the result
         while self.api_task.as_ref().is_some_and(|p| p.poll().is_ready()) {
@@ -73,7 +271,7 @@ impl Tab for PSKReporterTab {
             self.last_api_query = Some(Instant::now());
 
             // Parse the result, breaking out early if the result was an error
-            let response = match response {
+            let mut response = match response {
                 Ok(r) => r,
                 Err(err) => {
                     error!("Failed to query PSKReporter API: {err}");
@@ -81,18 +279,30 @@ impl Tab for PSKReporterTab {
                 }
             };
 
-            // Get the map markers vec
-            let markers = map.markers_mut();
+            // The reports are saved even if the source changed while the API was being queried
+            self.unsaved.extend(response.iter().filter_map(MapMarker::report));
+
+            // The source was changed while the API was being queried, so the result is stale
+            if self.source != Source::Search {
+                break;
+            }
+
+            // Check the transmitting stations for alerts
+            for marker in &mut response {
+                if let MapMarker::ReceptionReportTransmitter { inner, alert, .. } = marker {
+                    *alert = alerts::raise(config, &inner.alert_candidate());
+                }
+            }
 
             // Replace the old markers with the new ones
-            *markers = response;
+            *self.reports.markers_mut() = response;
 
             // Update the map overlay now that the markers have been updated
             map.update_overlay();
         }
 
         // If auto refresh is enabled, no task is pending, and the API query refresh rate has elapsed, query the API again
-        if self.auto_refresh && self.api_task.is_none() && !self.last_api_query.is_some_and(|t| t.elapsed().as_secs() < config.pskreporter_config.refresh_rate) {
+        if self.auto_refresh && self.source == Source::Search && self.api_task.is_none() && !self.last_api_query.is_some_and(|t| t.elapsed().as_secs() < config.pskreporter_config.refresh_rate) {
 
             // Only query the API if we have query options to use. The query options are only updated when the user clicks the search button.
             if let Some(query_options) = self.last_query_options.as_ref() {
@@ -191,8 +401,23 @@ impl Tab for PSKReporterTab {
                 }
             });
 
+            // In live mode, reports can also be filtered by grid square
+            if self.source == Source::Live {
+                egui::widgets::TextEdit::singleline(&mut self.query_options.grid)
+                .hint_text("Grid")
+                .desired_width(60.0)
+                .ui(ui)
+                .on_hover_text("Only show reports where either station is in a grid square starting with this prefix (e.g. FN or FN31)");
+            }
+
             // The search button to query the API. This is disabled if the API task is already running
-            if ui.add_enabled(self.api_task.is_none(), egui::widgets::Button::new("Search")).clicked() {
+            // In live and history mode, the button restarts the live feed or reloads the history instead
+            let enabled = match self.source {
+                Source::Search => self.api_task.is_none(),
+                Source::Live => true,
+                Source::History => self.history.task.is_none()
+            };
+            if ui.add_enabled(enabled, egui::widgets::Button::new("Search")).clicked() {
 
                 // Enter the tokio runtime
                 let _eg = RT.enter();
@@ -200,8 +425,17 @@ impl Tab for PSKReporterTab {
                 // Update the last query options with the current query options
                 self.last_query_options = Some(self.query_options.clone());
 
+                // The live feed is restarted with the new query options, so clear the reports of the last search
+                if self.source == Source::Live {
+                    self.reports.markers_mut().clear();
+                    map.update_overlay();
+                }
+                // Load the saved reports of the new search
+                else if self.source == Source::History {
+                    self.history.load(config, &self.query_options);
+                }
                 // We are filtering for signals sent by the callsign
-                if self.query_options.sent_by {
+                else if self.query_options.sent_by {
                     // Spawn a task to query the API for signals sent by the callsign
                     self.api_task = Some(Promise::spawn_async(
                         ApiQueryBuilder::sent_by(
@@ -227,11 +461,63 @@ impl Tab for PSKReporterTab {
                 
             };
 
-            // The auto refresh checkbox
-            ui.checkbox(&mut self.auto_refresh, "Auto Refresh");
+            // The source combobox. The reports from different sources aren't mixed, so the reports are cleared when it's changed.
+            let source = self.source;
+            egui::ComboBox::from_id_source(self.id.with("source"))
+            .selected_text(self.source.as_str())
+            .show_ui(ui, |ui| {
+                for opt in Source::iter() {
+                    ui.selectable_value(&mut self.source, opt, opt.as_str())
+                    .on_hover_text(opt.description());
+                }
+            });
+            if self.source != source {
+                self.api_task = None;
+                self.reports.markers_mut().clear();
+                self.yesterday.markers_mut().clear();
+                self.history.shown = None;
+                map.update_overlay();
+
+                // Load the saved reports of the last search
+                if let (Source::History, Some(options)) = (self.source, self.last_query_options.as_ref()) {
+                    self.history.load(config, options);
+                }
+            }
+
+            // The auto refresh checkbox. The live feed is always up to date, so it's only shown when searching the API.
+            if self.source == Source::Search {
+                ui.checkbox(&mut self.auto_refresh, "Auto Refresh");
+            }
 
+            // The projection combobox
+            self.projection.combo_box(ui, self.id.with("projection"));
+
+            // The analytics panel toggle
+            ui.toggle_value(&mut self.analytics.open, "Stats")
+            .on_hover_text("Show the reach statistics of the reports, and compare two callsigns or antennas");
+
+            // In live mode, show the status of the live feed
+            if self.source == Source::Live {
+                match &self.stream {
+                    Some(stream) if stream.connected() => {
+                        ui.label(format!("{} live reports", self.reports.markers().iter().filter(|m| m.report().is_some()).count()))
+                        .on_hover_text(format!("Connected to {}", stream.address()));
+                    },
+                    Some(stream) => {
+                        let status = match stream.last_error() {
+                            Some(err) => format!("Reconnecting to {}: {err}", stream.address()),
+                            None => format!("Connecting to {}", stream.address())
+                        };
+                        ui.spinner().on_hover_text(status);
+                    },
+                    None => match &self.stream_error {
+                        Some(err) => { ui.colored_label(ui.style().visuals.warn_fg_color, err); },
+                        None => { ui.label("Search to start streaming"); }
+                    }
+                }
+            }
             // If auto refresh is enabled, show a progress bar indicating how long until the next API query
-            if self.auto_refresh {
+            else if self.source == Source::Search && self.auto_refresh {
 
                 // Get a value between 0.0 and 1.0 indicating how much time has passed since the last API query divided by the refresh rate
                 let completeness = self.last_api_query.as_ref().map(
@@ -249,8 +535,25 @@ impl Tab for PSKReporterTab {
 
         });
 
-        // Show the map widget
-        map.ui(ui, config);
+        // In history mode, show the timeline above the map, reloading the reports if the timeline changed
+        if self.source == Source::History && self.history.ui(ui, self.last_query_options.is_some()) {
+            if let Some(options) = self.last_query_options.as_ref() {
+                self.history.load(config, options);
+            }
+        }
+
+        // Show the reach statistics of the reports next to the map
+        if self.analytics.open {
+            egui::SidePanel::right(self.id.with("analytics"))
+            .default_width(Analytics::PANEL_WIDTH)
+            .show_inside(ui, |ui| self.analytics.ui(ui, config, &self.reports, self.last_query_options.as_ref()));
+        }
+
+        // Show the map widget in the selected projection, with the greyline, grids, and reports layers
+        map.set_projection(self.projection);
+        let mut layers: [&mut dyn MapLayer; 4] = [&mut self.greyline, &mut self.grids, &mut self.yesterday, &mut self.reports];
+        map.ui_with_layers(ui, config, &mut layers);
+        self.view = map.view();
 
     }
 }
@@ -259,11 +562,27 @@ impl Default for PSKReporterTab {
         Self {
             id: gui::generate_random_id(),
             map: Default::default(),
+            view: Default::default(),
+            reports: map::MarkerLayer::new("Reports"),
             api_task: Default::default(),
             last_api_query: Default::default(),
             auto_refresh: Default::default(),
             query_options: Default::default(),
-            last_query_options: Default::default()
+            last_query_options: Default::default(),
+            source: Default::default(),
+            stream: Default::default(),
+            stream_error: Default::default(),
+            last_fade: Default::default(),
+            unsaved: Default::default(),
+            last_save: Default::default(),
+            save_tasks: Default::default(),
+            history: Default::default(),
+            yesterday: map::MarkerLayer::new("Yesterday"),
+            analytics: Default::default(),
+            greyline: Default::default(),
+            grids: Default::default(),
+            projection: Default::default(),
+            alerts_generation: Default::default()
         }
     }
 }
@@ -288,7 +607,23 @@ struct QueryOptions {
     /// The mode to filter for.
     mode: Mode,
     /// How old can the reports be before we consider them stale?
-    last: Last
+    last: Last,
+    /// Only show live reports where either station is in a grid square starting with this prefix
+    #[serde(default)]
+    grid: String
+}
+impl QueryOptions {
+    /// The live feed filter for the query options
+    fn live_filter(&self) -> pskreporter_mqtt::Filter {
+        let callsign = Some(self.callsign.trim().to_ascii_uppercase()).filter(|c| !c.is_empty());
+        pskreporter_mqtt::Filter {
+            band: self.band,
+            mode: self.mode.mode_string().map(str::to_string),
+            tx_callsign: callsign.clone().filter(|_| self.sent_by),
+            rx_callsign: callsign.filter(|_| !self.sent_by),
+            grid: Some(self.grid.trim().to_ascii_uppercase()).filter(|g| !g.is_empty())
+        }
+    }
 }
 impl Default for QueryOptions {
     fn default() -> Self {
@@ -297,7 +632,341 @@ impl Default for QueryOptions {
             sent_by: Default::default(),
             band: Band::All,
             mode: Mode::All,
-            last: Last::Minutes15
+            last: Last::Minutes15,
+            grid: Default::default()
+        }
+    }
+}
+
+/// Where the reports on the map come from
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
+enum Source {
+    /// The reports are queried from the API
+    #[default]
+    Search,
+    /// The reports are streamed from the live MQTT feed
+    Live,
+    /// The reports that were saved to the database are played back
+    History
+}
+impl Source {
+    /// Return the name of the source as a string
+    fn as_str(&self) -> &'static str {
+        match self {
+            Source::Search => "Search",
+            Source::Live => "Live",
+            Source::History => "History"
+        }
+    }
+
+    /// Return a description of the source, which is shown when it's hovered
+    fn description(&self) -> &'static str {
+        match self {
+            Source::Search => "Query the PSKReporter API, which can only be queried once a minute",
+            Source::Live => "Stream reports from the PSKReporter MQTT feed as they're uploaded",
+            Source::History => "Play back the reports that were received by previous searches"
+        }
+    }
+}
+
+/// The playback of the reports that were saved to the database
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct History {
+    /// How far back the reports are loaded
+    span: HistorySpan,
+    /// How many minutes of reports are played back each second
+    speed: u32,
+    /// Should the reports from 24 hours before the playback time be shown?
+    compare_yesterday: bool,
+    /// The band that's played back, so each band can be watched on its own
+    band: Band,
+    /// The task that loads the reports from the database
+    #[serde(skip)]
+    task: Option<Promise<Result<Vec<ReceptionReport>>>>,
+    /// The loaded reports, oldest first
+    #[serde(skip)]
+    reports: Vec<ReceptionReport>,
+    /// The number of reports on each band over the timeline, split into [Self::ACTIVITY_BUCKETS] buckets
+    #[serde(skip)]
+    activity: Vec<(Band, Vec<u32>)>,
+    /// The start and end of the timeline, in seconds since the epoch
+    #[serde(skip)]
+    range: (u64, u64),
+    /// The playback time, in seconds since the epoch
+    #[serde(skip)]
+    time: f64,
+    /// Is the playback running?
+    #[serde(skip)]
+    playing: bool,
+    /// Incremented each time the reports are loaded, so the markers are only rebuilt when something changed
+    #[serde(skip)]
+    generation: u64,
+    /// What the markers were last built for, as the generation of the reports, the playback second, the window, the band, and the yesterday toggle
+    #[serde(skip)]
+    shown: Option<(u64, u64, u64, Band, bool)>
+}
+impl History {
+    /// The playback speeds, in minutes per second
+    const SPEEDS: [u32; 5] = [1, 5, 15, 60, 240];
+    /// The number of buckets the timeline is split into when counting the reports on each band
+    const ACTIVITY_BUCKETS: usize = 96;
+    /// The height of each band's activity row
+    const ROW_HEIGHT: f32 = 12.0;
+    /// The width of the band labels next to the activity rows
+    const LABEL_WIDTH: f32 = 48.0;
+    /// The ID of the marker of the station that was searched for. This is constant so the marker stays selected during the playback.
+    const STATION_MARKER_ID: u64 = 0;
+
+    /// Loads the saved reports of the search from the database
+    fn load(&mut self, config: &GuiConfig, options: &QueryOptions) {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.range = (now.saturating_sub(self.span.as_duration().as_secs()), now);
+        self.playing = false;
+        self.shown = None;
+
+        // Load another day of reports so the start of the timeline can be compared with the day before
+        let since = match self.compare_yesterday {
+            true => self.range.0.saturating_sub(86_400),
+            false => self.range.0
+        };
+
+        let callsign = Some(options.callsign.trim().to_ascii_uppercase()).filter(|c| !c.is_empty());
+        let task = match options.sent_by {
+            true => config.db_api.get_reception_reports_promise(callsign, None, since),
+            false => config.db_api.get_reception_reports_promise(None, callsign, since)
+        };
+        self.task = Some(task);
+    }
+
+    /// Processes the loaded reports, keeping the ones on the band and in the mode of the search
+    fn process(&mut self, config: &mut GuiConfig, options: &QueryOptions) {
+        let Some(task) = self.task.take_if(|t| t.ready().is_some()) else {
+            return;
+        };
+
+        let mut reports = match task.block_and_take() {
+            Ok(reports) => reports,
+            Err(err) => {
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to load the PSKReporter history: {err}")));
+                return;
+            }
+        };
+
+        // The reports are searched by callsign, so filter them by band and mode
+        reports.retain(|r| {
+            options.band.freq_range().map_or(true, |(min, max)| (min..=max).contains(&r.frequency))
+            && options.mode.mode_string().map_or(true, |m| r.mode.eq_ignore_ascii_case(m))
+        });
+
+        // Count the reports on each band over the timeline, so we can see when each band opened
+        let (start, end) = self.range;
+        let span = (end - start).max(1);
+        let mut activity: Vec<(Band, Vec<u32>)> = Vec::new();
+        for report in reports.iter().filter(|r| (start..=end).contains(&r.time)) {
+            let band = Band::from_frequency(report.frequency);
+            let bucket = (((report.time - start) * Self::ACTIVITY_BUCKETS as u64 / span) as usize).min(Self::ACTIVITY_BUCKETS - 1);
+            match activity.iter_mut().find(|(b, _)| *b == band) {
+                Some((_, counts)) => counts[bucket] += 1,
+                None => {
+                    let mut counts = vec![0; Self::ACTIVITY_BUCKETS];
+                    counts[bucket] = 1;
+                    activity.push((band, counts));
+                }
+            }
+        }
+        activity.sort_by_key(|(band, _)| Band::iter().position(|b| b == *band));
+
+        self.reports = reports;
+        self.activity = activity;
+        self.generation += 1;
+
+        // Start at the end of the timeline, unless the playback time is still on it
+        if !(start as f64..=end as f64).contains(&self.time) {
+            self.time = end as f64;
+        }
+    }
+
+    /// Moves the playback time forward if the playback is running
+    fn advance(&mut self, ctx: &egui::Context) {
+        if !self.playing {
+            return;
+        }
+
+        self.time += ctx.input(|i| i.stable_dt) as f64 * self.speed as f64 * 60.0;
+        if self.time >= self.range.1 as f64 {
+            self.time = self.range.1 as f64;
+            self.playing = false;
+        }
+        ctx.request_repaint();
+    }
+
+    /// The markers of the reports in the `window` seconds before the playback time, minus `offset` seconds.
+    ///
+    /// Only the newest report of each path is shown, so repeated transmissions don't pile up on top of each other.
+    fn markers(&self, options: &QueryOptions, offset: u64, window: u64) -> Vec<MapMarker> {
+        let end = (self.time as u64).saturating_sub(offset);
+        let start = end.saturating_sub(window);
+
+        // The reports are sorted by time, so find the reports in the window with a binary search
+        let first = self.reports.partition_point(|r| r.time <= start);
+        let last = self.reports.partition_point(|r| r.time <= end);
+
+        let mut paths: HashMap<(CallsignString, CallsignString), &ReceptionReport> = HashMap::new();
+        for report in self.reports[first..last].iter().filter(|r| self.band == Band::All || Band::from_frequency(r.frequency) == self.band) {
+            paths.insert((report.tx_callsign, report.rx_callsign), report);
+        }
+
+        // Sort the markers so they're only updated when the reports change
+        let mut markers: Vec<MapMarker> = paths.values().filter_map(|r| report_marker(r, options, None)).collect();
+        markers.sort_by_key(|m| m.id());
+        markers
+    }
+
+    /// Shows the timeline controls and the activity of each band. Returns true if the reports need to be reloaded.
+    fn ui(&mut self, ui: &mut egui::Ui, searched: bool) -> bool {
+        if !searched {
+            ui.label("Search to play back the saved reports");
+            return false;
+        }
+
+        let mut reload = false;
+        let (start, end) = self.range;
+
+        ui.horizontal(|ui| {
+
+            // The play/pause button. The playback starts over if it's at the end of the timeline.
+            let text = if self.playing { "⏸" } else { "▶" };
+            if ui.button(text).on_hover_text(if self.playing { "Pause" } else { "Play" }).clicked() {
+                if !self.playing && self.time >= end as f64 {
+                    self.time = start as f64;
+                }
+                self.playing = !self.playing;
+            }
+
+            // The playback speed combobox
+            egui::ComboBox::from_id_source("history_speed_combobox")
+            .selected_text(format!("{} min/s", self.speed))
+            .show_ui(ui, |ui| {
+                for speed in Self::SPEEDS {
+                    ui.selectable_value(&mut self.speed, speed, format!("{speed} min/s"));
+                }
+            });
+
+            // The span combobox
+            egui::ComboBox::from_id_source("history_span_combobox")
+            .selected_text(self.span.as_str())
+            .show_ui(ui, |ui| {
+                for opt in HistorySpan::iter() {
+                    reload |= ui.selectable_value(&mut self.span, opt, opt.as_str()).changed();
+                }
+            });
+
+            // The yesterday checkbox. The reports from the day before the timeline may not be loaded yet.
+            reload |= ui.checkbox(&mut self.compare_yesterday, "Compare yesterday")
+            .on_hover_text("Show the reports from 24 hours before the playback time, so you can see if the band opened earlier or later than yesterday")
+            .changed() && self.compare_yesterday;
+
+            // The playback time
+            let time = chrono::DateTime::from_timestamp(self.time as i64, 0).unwrap_or_default();
+            ui.label(format!("{} UTC", time.format("%d/%m/%Y %H:%M")));
+
+            if self.task.is_some() {
+                ui.spinner();
+            }
+
+            // The timeline scrubber, which fills the rest of the row
+            ui.spacing_mut().slider_width = ui.available_width();
+            if egui::Slider::new(&mut self.time, start as f64..=end as f64).show_value(false).ui(ui).dragged() {
+                self.playing = false;
+            }
+
+        });
+
+        // The activity of each band over the timeline. Clicking a band only plays back that band, and clicking a row moves the playback time.
+        let max = self.activity.iter().flat_map(|(_, counts)| counts.iter()).copied().max().unwrap_or_default().max(1);
+        for (band, counts) in &self.activity {
+            ui.horizontal(|ui| {
+                let selected = self.band == *band;
+                if ui.add_sized([Self::LABEL_WIDTH, Self::ROW_HEIGHT], egui::SelectableLabel::new(selected, band.as_str())).clicked() {
+                    self.band = if selected { Band::All } else { *band };
+                }
+
+                let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), Self::ROW_HEIGHT), egui::Sense::click_and_drag());
+                let painter = ui.painter_at(rect);
+
+                // Draw a bucket for each period of the timeline that the band had reports in, brighter if it had more reports
+                let width = rect.width() / counts.len() as f32;
+                for (i, count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+                    let bucket = egui::Rect::from_min_size(egui::pos2(rect.left() + i as f32 * width, rect.top()), egui::vec2(width, rect.height()));
+                    painter.rect_filled(bucket, 0.0, ACCENT_COLOR.gamma_multiply(0.2 + 0.8 * *count as f32 / max as f32));
+                }
+
+                // Draw the playback time
+                let progress = ((self.time - start as f64) / (end - start).max(1) as f64) as f32;
+                painter.vline(rect.left() + rect.width() * progress, rect.y_range(), egui::Stroke::new(1.0, ui.visuals().strong_text_color()));
+
+                // Move the playback time to the cursor
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let progress = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64;
+                    self.time = start as f64 + progress * (end - start) as f64;
+                    self.playing = false;
+                }
+            });
+        }
+        if self.activity.is_empty() && self.task.is_none() {
+            ui.label("No reports were saved for this search. Reports are saved when they're received by the Search and Live sources.");
+        }
+
+        reload
+    }
+}
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            span: HistorySpan::Hours24,
+            speed: 15,
+            compare_yesterday: false,
+            band: Band::All,
+            task: None,
+            reports: Vec::new(),
+            activity: Vec::new(),
+            range: (0, 0),
+            time: 0.0,
+            playing: false,
+            generation: 0,
+            shown: None
+        }
+    }
+}
+
+/// How far back the saved reports are played back
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
+enum HistorySpan {
+    Hours6,
+    Hours24,
+    Days3,
+    Days7
+}
+impl HistorySpan {
+    /// Return the duration of self
+    fn as_duration(&self) -> Duration {
+        match self {
+            HistorySpan::Hours6 => Duration::from_secs(21_600),
+            HistorySpan::Hours24 => Duration::from_secs(86_400),
+            HistorySpan::Days3 => Duration::from_secs(259_200),
+            HistorySpan::Days7 => Duration::from_secs(604_800)
+        }
+    }
+
+    /// Return the name of the span as a string
+    fn as_str(&self) -> &'static str {
+        match self {
+            HistorySpan::Hours6 => "6 Hours",
+            HistorySpan::Hours24 => "24 Hours",
+            HistorySpan::Days3 => "3 Days",
+            HistorySpan::Days7 => "7 Days"
         }
     }
 }
@@ -340,7 +1009,11 @@ enum MapMarker {
         /// The location of the receiver
         rx_location: Coord<f64>,
         /// The inner data about the reception report
-        inner: ReceptionReport
+        inner: ReceptionReport,
+        /// How long the report is shown for, in seconds. Live reports fade as they get older, and reports from the API don't have a lifetime.
+        lifetime: Option<u64>,
+        /// The alert that the transmitting station matched, checked when the report is received
+        alert: Option<alerts::Alert>
     },
     /// A reception report regarding a receiver on the pskreporter map
     ReceptionReportReceiver {
@@ -351,7 +1024,25 @@ enum MapMarker {
         /// The location of the transmitter
         tx_location: Coord<f64>,
         /// The inner data about the reception report
-        inner: ReceptionReport
+        inner: ReceptionReport,
+        /// How long the report is shown for, in seconds. Live reports fade as they get older, and reports from the API don't have a lifetime.
+        lifetime: Option<u64>
+    }
+}
+impl MapMarker {
+    /// The reception report of the marker, or `None` if the marker is the station that was searched for
+    fn report(&self) -> Option<&ReceptionReport> {
+        match self {
+            MapMarker::Transmitter { .. } | MapMarker::Receiver { .. } => None,
+            MapMarker::ReceptionReportTransmitter { inner, .. } | MapMarker::ReceptionReportReceiver { inner, .. } => Some(inner)
+        }
+    }
+
+    /// Checks the transmitting station of the report for alerts, without raising one
+    fn check_alert(&mut self, config: &GuiConfig) {
+        if let MapMarker::ReceptionReportTransmitter { inner, alert, .. } = self {
+            *alert = config.alerts.alert(&config.alerts_config, &inner.alert_candidate());
+        }
     }
 }
 impl MapMarkerTrait for MapMarker {
@@ -413,15 +1104,9 @@ impl MapMarkerTrait for MapMarker {
                 ui.label(format!("Time (UTC): {}", time.format("%H:%M:%S")));
                 ui.label(format!("Date (DMY): {}", time.format("%d/%m/%Y")));
 
-                // The distance and bearing to the receiver
-                // TODO: Add a measurement field to the config and support KM, not just miles
-                let (mut bearing, mut distance) = point!(*rx_location).geodesic_bearing_distance(point!(*location));
-                // Convert the distance to the preferred unit and convert the final bearing to an initial bearing
-                distance = config.distance_unit.to_unit_from_meters(distance);
-                bearing = (bearing + 360.0) % 360.0;
-
-                ui.label(format!("Distance: {distance:.2} mi"));
-                ui.label(format!("Bearing from RX to TX: {bearing:.0}\u{00B0}"));
+                // The distance and bearing from the receiver
+                let path = geodesy::Path::new(rx_location, location);
+                gui::path_ui(ui, &path, &config.distance_unit, "Bearing from RX to TX");
 
             },
             MapMarker::ReceptionReportReceiver { location, tx_location, inner, .. } => {
@@ -446,15 +1131,9 @@ impl MapMarkerTrait for MapMarker {
                 ui.label(format!("Time (UTC): {}", time.format("%H:%M:%S")));
                 ui.label(format!("Date (DMY): {}", time.format("%d/%m/%Y")));
 
-                // The distance and bearing to the transmitter
-                // TODO: Add a measurement field to the config and support KM, not just miles
-                let (mut bearing, mut distance) = point!(*tx_location).geodesic_bearing_distance(point!(*location));
-                // Convert the distance to the preferred unit and convert the final bearing to an initial bearing
-                distance = config.distance_unit.to_unit_from_meters(distance);
-                bearing = (bearing + 360.0) % 360.0;
-
-                ui.label(format!("Distance: {distance:.2} mi"));
-                ui.label(format!("Bearing from TX to RX: {bearing:.0}\u{00B0}"));
+                // The distance and bearing from the transmitter
+                let path = geodesy::Path::new(tx_location, location);
+                gui::path_ui(ui, &path, &config.distance_unit, "Bearing from TX to RX");
 
             }
         }
@@ -468,8 +1147,12 @@ impl MapMarkerTrait for MapMarker {
         match self {
             MapMarker::Transmitter { .. } => image::Rgba(config.pskreporter_config.tx_color),
             MapMarker::Receiver { .. } => image::Rgba(config.pskreporter_config.rx_color),
-            MapMarker::ReceptionReportTransmitter { .. } => image::Rgba(config.pskreporter_config.tx_reception_report_color),
-            MapMarker::ReceptionReportReceiver { .. } => image::Rgba(config.pskreporter_config.rx_reception_report_color),
+            // Transmitting stations that match an alert rule use the color of the rule
+            MapMarker::ReceptionReportTransmitter { inner, lifetime, alert, .. } => match alert {
+                Some(alert) => fade(alert.color, inner.time, *lifetime),
+                None => fade(config.pskreporter_config.tx_reception_report_color, inner.time, *lifetime)
+            },
+            MapMarker::ReceptionReportReceiver { inner, lifetime, .. } => fade(config.pskreporter_config.rx_reception_report_color, inner.time, *lifetime),
         }
     }
 
@@ -481,6 +1164,670 @@ impl MapMarkerTrait for MapMarker {
             MapMarker::ReceptionReportReceiver { tx_location, .. } => Some(tx_location)
         }
     }
+
+    fn shape(&self) -> map::MarkerShape {
+        match self {
+            // The station that was searched for stands out from the reports
+            MapMarker::Transmitter { .. } | MapMarker::Receiver { .. } => map::MarkerShape::Diamond,
+            MapMarker::ReceptionReportTransmitter { .. } | MapMarker::ReceptionReportReceiver { .. } => map::MarkerShape::Square
+        }
+    }
+
+    fn size(&self) -> f32 {
+        match self {
+            MapMarker::Transmitter { .. } | MapMarker::Receiver { .. } => 12.0,
+            MapMarker::ReceptionReportTransmitter { inner, .. } | MapMarker::ReceptionReportReceiver { inner, .. } => map::snr_size(inner.snr as f32)
+        }
+    }
+
+    fn label(&self) -> Option<&str> {
+        match self {
+            MapMarker::Transmitter { callsign, .. } | MapMarker::Receiver { callsign, .. } => Some(callsign.as_str()),
+            MapMarker::ReceptionReportTransmitter { inner, .. } => Some(inner.tx_callsign.as_str()),
+            MapMarker::ReceptionReportReceiver { inner, .. } => Some(inner.rx_callsign.as_str())
+        }
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        match self {
+            MapMarker::Transmitter { grid, callsign, mode, .. } | MapMarker::Receiver { grid, callsign, mode, .. } => vec![
+                ("callsign", callsign.to_string()),
+                ("grid", grid.to_string()),
+                ("mode", mode.to_string())
+            ],
+            MapMarker::ReceptionReportTransmitter { inner, .. } | MapMarker::ReceptionReportReceiver { inner, .. } => inner.properties()
+        }
+    }
+}
+
+/// The reach statistics of the reports on the map, and the A/B comparison of two callsigns or antennas
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct Analytics {
+    /// Is the panel shown?
+    open: bool,
+    /// What's being compared
+    comparison: Comparison,
+    /// The callsigns that are compared
+    callsigns: [String; 2],
+    /// How far back the reports of the callsigns are compared
+    span: HistorySpan,
+    /// The periods that each antenna was used for, oldest first
+    periods: Vec<AntennaPeriod>,
+    /// The statistics of the reports on the map, with the generation of the reports layer and the search direction they were computed from
+    #[serde(skip)]
+    stats: Option<((u64, bool), ReachStats)>,
+    /// The tasks that load the reports of the A and B sides from the database
+    #[serde(skip)]
+    tasks: [Option<Promise<Result<Vec<ReceptionReport>>>>; 2],
+    /// Were the reports of the comparison searched for by the transmitting station?
+    #[serde(skip)]
+    sent_by: bool,
+    /// The result of the last comparison
+    #[serde(skip)]
+    results: Option<ComparisonResults>
+}
+impl Analytics {
+    /// The default width of the panel
+    const PANEL_WIDTH: f32 = 320.0;
+    /// The width of the callsign textboxes
+    const CALLSIGN_WIDTH: f32 = 90.0;
+
+    /// Shows the statistics of the reports in the layer, and the A/B comparison
+    fn ui(&mut self, ui: &mut egui::Ui, config: &mut GuiConfig, layer: &map::MarkerLayer<MapMarker>, options: Option<&QueryOptions>) {
+        self.process(config);
+
+        // Only compute the statistics again if the markers of the layer changed
+        let sent_by = options.map_or(true, |o| o.sent_by);
+        let key = (layer.generation(), sent_by);
+        if self.stats.as_ref().map_or(true, |(k, _)| *k != key) {
+            let reports: Vec<ReceptionReport> = layer.markers().iter().filter_map(MapMarker::report).copied().collect();
+            self.stats = Some((key, ReachStats::new(&reports, sent_by, config.alerts.dxcc())));
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.heading("Reach");
+            if let Some((_, stats)) = self.stats.as_ref() {
+                stats.ui(ui, config, sent_by);
+            }
+
+            ui.separator();
+
+            ui.heading("A/B Comparison");
+            self.comparison_ui(ui, config, options);
+        });
+    }
+
+    /// Shows the settings and the results of the A/B comparison
+    fn comparison_ui(&mut self, ui: &mut egui::Ui, config: &mut GuiConfig, options: Option<&QueryOptions>) {
+        ui.horizontal(|ui| {
+            for opt in Comparison::iter() {
+                ui.selectable_value(&mut self.comparison, opt, opt.as_str())
+                .on_hover_text(opt.description());
+            }
+        });
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let ready = match self.comparison {
+            Comparison::Callsigns => {
+                ui.horizontal(|ui| {
+                    for (callsign, hint) in self.callsigns.iter_mut().zip(["Callsign A", "Callsign B"]) {
+                        egui::TextEdit::singleline(callsign)
+                        .hint_text(hint)
+                        .desired_width(Self::CALLSIGN_WIDTH)
+                        .ui(ui);
+                    }
+
+                    egui::ComboBox::from_id_source("analytics_span_combobox")
+                    .selected_text(self.span.as_str())
+                    .show_ui(ui, |ui| {
+                        for opt in HistorySpan::iter() {
+                            ui.selectable_value(&mut self.span, opt, opt.as_str());
+                        }
+                    });
+                });
+
+                self.callsigns.iter().all(|c| !c.trim().is_empty())
+            },
+            Comparison::Antennas => {
+                // Tag the time that each antenna is used from, ending the period of the last antenna
+                ui.horizontal(|ui| {
+                    let current = self.current_antenna();
+                    for antenna in [Antenna::A, Antenna::B] {
+                        if ui.selectable_label(current == Some(antenna), antenna.as_str())
+                        .on_hover_text(format!("Start using {}. Switch antennas every few minutes for a fair comparison.", antenna.as_str()))
+                        .clicked() && current != Some(antenna) {
+                            self.tag(Some(antenna), now);
+                        }
+                    }
+                    if ui.add_enabled(current.is_some(), egui::widgets::Button::new("Stop")).clicked() {
+                        self.tag(None, now);
+                    }
+                    if ui.add_enabled(!self.periods.is_empty(), egui::widgets::Button::new("Clear")).clicked() {
+                        self.periods.clear();
+                    }
+                });
+
+                let minutes = |antenna: Antenna| self.periods.iter()
+                    .filter(|p| p.antenna == antenna)
+                    .map(|p| p.end.unwrap_or(now).saturating_sub(p.start))
+                    .sum::<u64>() / 60;
+                ui.label(format!("Antenna A: {} min, Antenna B: {} min", minutes(Antenna::A), minutes(Antenna::B)));
+                ui.weak("The reports of the searched callsign that were received while each antenna was in use are compared. Keep the Live source or Auto Refresh running while testing.");
+
+                options.is_some_and(|o| !o.callsign.trim().is_empty())
+                && [Antenna::A, Antenna::B].iter().all(|a| self.periods.iter().any(|p| p.antenna == *a))
+            }
+        };
+
+        ui.horizontal(|ui| {
+            let loading = self.tasks.iter().any(Option::is_some);
+            if ui.add_enabled(ready && !loading, egui::widgets::Button::new("Compare")).clicked() {
+                self.load(config, options, now);
+            }
+            if loading {
+                ui.spinner();
+            }
+        });
+
+        if let Some(results) = self.results.as_ref() {
+            results.ui(ui, config);
+        }
+    }
+
+    /// The antenna that's currently in use, if any
+    fn current_antenna(&self) -> Option<Antenna> {
+        self.periods.last().filter(|p| p.end.is_none()).map(|p| p.antenna)
+    }
+
+    /// Ends the period of the current antenna, and starts a period for the new antenna if one is provided
+    fn tag(&mut self, antenna: Option<Antenna>, now: u64) {
+        if let Some(period) = self.periods.last_mut().filter(|p| p.end.is_none()) {
+            period.end = Some(now);
+        }
+        if let Some(antenna) = antenna {
+            self.periods.push(AntennaPeriod { antenna, start: now, end: None });
+        }
+    }
+
+    /// The antenna that was in use at the time, if any
+    fn antenna_at(&self, time: u64, now: u64) -> Option<Antenna> {
+        self.periods.iter()
+        .find(|p| p.start <= time && time <= p.end.unwrap_or(now))
+        .map(|p| p.antenna)
+    }
+
+    /// Loads the saved reports of the A and B sides from the database
+    fn load(&mut self, config: &GuiConfig, options: Option<&QueryOptions>, now: u64) {
+        self.sent_by = options.map_or(true, |o| o.sent_by);
+        self.results = None;
+
+        // Both antennas are used by the searched callsign, so both sides load the same reports and are split by antenna when they're loaded
+        let (callsigns, since) = match self.comparison {
+            Comparison::Callsigns => (self.callsigns.clone(), now.saturating_sub(self.span.as_duration().as_secs())),
+            Comparison::Antennas => {
+                let callsign = options.map(|o| o.callsign.clone()).unwrap_or_default();
+                ([callsign.clone(), callsign], self.periods.iter().map(|p| p.start).min().unwrap_or(now))
+            }
+        };
+
+        for (task, callsign) in self.tasks.iter_mut().zip(callsigns) {
+            let callsign = Some(callsign.trim().to_ascii_uppercase());
+            *task = Some(match self.sent_by {
+                true => config.db_api.get_reception_reports_promise(callsign, None, since),
+                false => config.db_api.get_reception_reports_promise(None, callsign, since)
+            });
+        }
+    }
+
+    /// Compares the A and B sides once both of them are loaded
+    fn process(&mut self, config: &mut GuiConfig) {
+        if !self.tasks.iter().all(|t| t.as_ref().is_some_and(|t| t.ready().is_some())) {
+            return;
+        }
+
+        let [a, b] = std::mem::take(&mut self.tasks).map(|t| t.unwrap().block_and_take());
+        let (mut a, mut b) = match (a, b) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(err), _) | (_, Err(err)) => {
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to load the reports to compare: {err}")));
+                return;
+            }
+        };
+
+        // Split the reports by the antenna that was in use when they were received
+        let labels = match self.comparison {
+            Comparison::Callsigns => self.callsigns.clone().map(|c| c.trim().to_ascii_uppercase()),
+            Comparison::Antennas => {
+                let now = chrono::Utc::now().timestamp() as u64;
+                a.retain(|r| self.antenna_at(r.time, now) == Some(Antenna::A));
+                b.retain(|r| self.antenna_at(r.time, now) == Some(Antenna::B));
+                [Antenna::A.as_str().to_string(), Antenna::B.as_str().to_string()]
+            }
+        };
+
+        let dxcc = config.alerts.dxcc();
+        self.results = Some(ComparisonResults {
+            labels,
+            sent_by: self.sent_by,
+            stats: [ReachStats::new(&a, self.sent_by, dxcc), ReachStats::new(&b, self.sent_by, dxcc)],
+            snr: SnrComparison::new(&a, &b, self.sent_by)
+        });
+    }
+}
+impl Default for Analytics {
+    fn default() -> Self {
+        Self {
+            open: false,
+            comparison: Comparison::Antennas,
+            callsigns: Default::default(),
+            span: HistorySpan::Hours24,
+            periods: Vec::new(),
+            stats: None,
+            tasks: Default::default(),
+            sent_by: true,
+            results: None
+        }
+    }
+}
+
+/// What the A/B comparison compares
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
+enum Comparison {
+    /// Two stations over the same period
+    Callsigns,
+    /// One station over the periods that each antenna was used
+    Antennas
+}
+impl Comparison {
+    /// Return the name of the comparison as a string
+    fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Callsigns => "Callsigns",
+            Comparison::Antennas => "Antennas"
+        }
+    }
+
+    /// Return a description of the comparison, which is shown when it's hovered
+    fn description(&self) -> &'static str {
+        match self {
+            Comparison::Callsigns => "Compare the saved reports of two callsigns",
+            Comparison::Antennas => "Compare the saved reports of the searched callsign while antenna A and antenna B were in use"
+        }
+    }
+}
+
+/// An antenna being compared
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+enum Antenna {
+    A,
+    B
+}
+impl Antenna {
+    /// Return the name of the antenna as a string
+    fn as_str(&self) -> &'static str {
+        match self {
+            Antenna::A => "Antenna A",
+            Antenna::B => "Antenna B"
+        }
+    }
+}
+
+/// A period that an antenna was used for
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+struct AntennaPeriod {
+    /// The antenna that was used
+    antenna: Antenna,
+    /// The start of the period, in seconds since the epoch
+    start: u64,
+    /// The end of the period, in seconds since the epoch. This is `None` if the antenna is still in use.
+    end: Option<u64>
+}
+
+/// The results of an A/B comparison
+struct ComparisonResults {
+    /// The names of the A and B sides
+    labels: [String; 2],
+    /// Were the reports searched for by the transmitting station?
+    sent_by: bool,
+    /// The statistics of the A and B sides
+    stats: [ReachStats; 2],
+    /// The SNR of B compared to A
+    snr: SnrComparison
+}
+impl ComparisonResults {
+    /// Shows the statistics of both sides next to each other, and the SNR comparison
+    fn ui(&self, ui: &mut egui::Ui, config: &GuiConfig) {
+        let [a, b] = &self.stats;
+        let unit = &config.distance_unit;
+        let distance = |meters: f64| format!("{:.0} {}", unit.to_unit_from_meters(meters), unit.abbreviation());
+
+        egui::Grid::new("analytics_comparison_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            ui.strong(&self.labels[0]);
+            ui.strong(&self.labels[1]);
+            ui.end_row();
+
+            let rows = [
+                ("Reports", a.reports.to_string(), b.reports.to_string()),
+                (if self.sent_by { "Receivers" } else { "Transmitters" }, a.stations.to_string(), b.stations.to_string()),
+                ("Max distance", distance(a.max_distance), distance(b.max_distance)),
+                ("Median distance", distance(a.median_distance), distance(b.median_distance)),
+                ("Median SNR", format!("{:.1} dB", a.median_snr), format!("{:.1} dB", b.median_snr))
+            ];
+            for (name, a, b) in rows {
+                ui.label(name);
+                ui.label(a);
+                ui.label(b);
+                ui.end_row();
+            }
+        });
+
+        // The SNR is only compared over the stations that heard both sides, so the result isn't skewed by where each side was heard
+        let snr = &self.snr;
+        if snr.common == 0 {
+            ui.label("No station heard both on the same band, so the SNR can't be compared");
+            return;
+        }
+        ui.label(format!("{} stations heard both on the same band", snr.common));
+        ui.strong(format!("{} vs {}: {:+.1} dB mean, {:+.1} dB median", self.labels[1], self.labels[0], snr.mean_difference, snr.median_difference));
+        ui.label(format!("{} was heard better by {} of {} stations", self.labels[1], snr.b_better, snr.common));
+    }
+}
+
+/// Statistics about how far a station was heard, or how far it could hear, computed from its reception reports
+#[derive(Debug, Default)]
+struct ReachStats {
+    /// The number of reports
+    reports: usize,
+    /// The number of unique stations at the other end of the reports
+    stations: usize,
+    /// The longest distance between the stations, in meters
+    max_distance: f64,
+    /// The median distance between the stations, in meters
+    median_distance: f64,
+    /// The median SNR of the reports, in dB
+    median_snr: f64,
+    /// The sorted SNRs of the reports on each band
+    snr: Vec<(Band, Vec<f64>)>,
+    /// The number of unique stations on each continent, most first
+    continents: Vec<(String, usize)>,
+    /// The number of unique stations in each DXCC entity, most first
+    entities: Vec<(String, usize)>
+}
+impl ReachStats {
+    /// The height of the SNR plot
+    const PLOT_HEIGHT: f32 = 160.0;
+    /// The number of DXCC entities that are shown
+    const TOP_ENTITIES: usize = 10;
+
+    /// Computes the statistics of the reports. `sent_by` is true if the reports were searched for by the transmitting station.
+    fn new(reports: &[ReceptionReport], sent_by: bool, dxcc: Option<&dxcc::Database>) -> Self {
+
+        // The distance of each report
+        let mut distances: Vec<f64> = reports.iter().filter_map(|r| {
+            let tx = maidenhead::grid_to_lat_lon(&r.tx_grid).ok()?;
+            let rx = maidenhead::grid_to_lat_lon(&r.rx_grid).ok()?;
+            Some(geodesy::distance(&tx, &rx))
+        }).collect();
+        distances.sort_by(f64::total_cmp);
+
+        // The SNRs of the reports on each band
+        let mut snr: Vec<(Band, Vec<f64>)> = Vec::new();
+        for report in reports {
+            let band = Band::from_frequency(report.frequency);
+            match snr.iter_mut().find(|(b, _)| *b == band) {
+                Some((_, values)) => values.push(report.snr as f64),
+                None => snr.push((band, vec![report.snr as f64]))
+            }
+        }
+        snr.iter_mut().for_each(|(_, values)| values.sort_by(f64::total_cmp));
+        snr.sort_by_key(|(band, _)| Band::iter().position(|b| b == *band));
+        let mut all_snr: Vec<f64> = reports.iter().map(|r| r.snr as f64).collect();
+        all_snr.sort_by(f64::total_cmp);
+
+        // Count the unique stations on each continent and in each DXCC entity
+        let stations: HashSet<&str> = reports.iter().map(|r| other_station(r, sent_by).as_str()).collect();
+        let mut continents: HashMap<String, usize> = HashMap::new();
+        let mut entities: HashMap<String, usize> = HashMap::new();
+        for entity in stations.iter().filter_map(|c| dxcc?.lookup(c)) {
+            *continents.entry(entity.continent.clone()).or_default() += 1;
+            *entities.entry(entity.name.clone()).or_default() += 1;
+        }
+        let sorted = |counts: HashMap<String, usize>| {
+            let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            counts
+        };
+
+        Self {
+            reports: reports.len(),
+            stations: stations.len(),
+            max_distance: distances.last().copied().unwrap_or_default(),
+            median_distance: quantile(&distances, 0.5),
+            median_snr: quantile(&all_snr, 0.5),
+            snr,
+            continents: sorted(continents),
+            entities: sorted(entities)
+        }
+    }
+
+    /// Shows the statistics
+    fn ui(&self, ui: &mut egui::Ui, config: &GuiConfig, sent_by: bool) {
+        if self.reports == 0 {
+            ui.label("There aren't any reports on the map");
+            return;
+        }
+
+        let unit = &config.distance_unit;
+        let distance = |meters: f64| format!("{:.0} {}", unit.to_unit_from_meters(meters), unit.abbreviation());
+
+        // The summary of the reports
+        egui::Grid::new("analytics_summary_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            let rows = [
+                ("Reports", self.reports.to_string()),
+                (if sent_by { "Unique receivers" } else { "Unique transmitters" }, self.stations.to_string()),
+                ("Max distance", distance(self.max_distance)),
+                ("Median distance", distance(self.median_distance)),
+                ("Median SNR", format!("{:.1} dB", self.median_snr))
+            ];
+            for (name, value) in rows {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+
+        // The distribution of the SNR on each band, as the minimum, quartiles, and maximum
+        ui.strong("SNR by band");
+        let boxes: Vec<BoxElem> = self.snr.iter().enumerate().map(|(i, (band, values))| {
+            let spread = BoxSpread::new(values[0], quantile(values, 0.25), quantile(values, 0.5), quantile(values, 0.75), values[values.len() - 1]);
+            BoxElem::new(i as f64, spread).name(format!("{} ({} reports)", band.as_str(), values.len()))
+        }).collect();
+        let names: Vec<&'static str> = self.snr.iter().map(|(band, _)| band.as_str()).collect();
+        Plot::new("analytics_snr_plot")
+        .height(Self::PLOT_HEIGHT)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .x_axis_formatter(move |mark: GridMark, _chars, _range| match mark.value.fract() == 0.0 {
+            true => names.get(mark.value as usize).copied().unwrap_or_default().to_string(),
+            false => String::new()
+        })
+        .y_axis_formatter(|mark: GridMark, _chars, _range| format!("{} dB", mark.value))
+        .show(ui, |plot_ui| plot_ui.box_plot(BoxPlot::new(boxes).name("SNR")));
+
+        // The continents and DXCC entities of the stations
+        if self.continents.is_empty() {
+            ui.weak("Load the DXCC entity list in the alerts settings to see the continents and entities of the stations");
+            return;
+        }
+        ui.strong(if sent_by { "Receivers by continent" } else { "Transmitters by continent" });
+        egui::Grid::new("analytics_continent_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            for (continent, count) in &self.continents {
+                ui.label(continent);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+        ui.strong(format!("Top {} DXCC entities", Self::TOP_ENTITIES));
+        egui::Grid::new("analytics_entity_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            for (entity, count) in self.entities.iter().take(Self::TOP_ENTITIES) {
+                ui.label(entity);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+    }
+}
+
+/// The SNR of the B side compared to the A side, normalized over the stations that heard both
+#[derive(Debug, Default)]
+struct SnrComparison {
+    /// The number of stations that heard both sides on the same band
+    common: usize,
+    /// The mean of the differences between B and A, in dB
+    mean_difference: f64,
+    /// The median of the differences between B and A, in dB
+    median_difference: f64,
+    /// The number of stations that heard B better than A
+    b_better: usize
+}
+impl SnrComparison {
+    /// Compares the mean SNR of each station that heard both sides on the same band. Bands are compared separately because the SNR varies a lot between bands.
+    fn new(a: &[ReceptionReport], b: &[ReceptionReport], sent_by: bool) -> Self {
+        let means = |reports: &[ReceptionReport]| {
+            let mut sums: HashMap<(CallsignString, Band), (f64, usize)> = HashMap::new();
+            for report in reports {
+                let sum = sums.entry((*other_station(report, sent_by), Band::from_frequency(report.frequency))).or_default();
+                sum.0 += report.snr as f64;
+                sum.1 += 1;
+            }
+            sums.into_iter().map(|(key, (sum, count))| (key, sum / count as f64)).collect::<HashMap<_, _>>()
+        };
+        let (a, b) = (means(a), means(b));
+
+        let mut differences: Vec<f64> = a.iter().filter_map(|(key, a)| Some(b.get(key)? - a)).collect();
+        differences.sort_by(f64::total_cmp);
+
+        Self {
+            common: differences.len(),
+            mean_difference: differences.iter().sum::<f64>() / differences.len().max(1) as f64,
+            median_difference: quantile(&differences, 0.5),
+            b_better: differences.iter().filter(|d| **d > 0.0).count()
+        }
+    }
+}
+
+/// The callsign of the station at the other end of the report. `sent_by` is true if the report was searched for by the transmitting station.
+fn other_station(report: &ReceptionReport, sent_by: bool) -> &CallsignString {
+    match sent_by {
+        true => &report.rx_callsign,
+        false => &report.tx_callsign
+    }
+}
+
+/// Linearly interpolates the `q` quantile (between 0 and 1) of the sorted values, returning 0 if there aren't any values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let position = q * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * position.fract()
+}
+
+/// A report from 24 hours before the playback time. These are shown in their own color, so the band conditions can be compared with the day before.
+#[derive(Debug, Clone, Copy)]
+struct ComparisonMarker(MapMarker);
+impl MapMarkerTrait for ComparisonMarker {
+    fn id(&self) -> u64 {
+        self.0.id()
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        self.0.location()
+    }
+
+    fn hovered_ui(&mut self, ui: &mut egui::Ui, config: &mut GuiConfig) {
+        ui.weak("24 hours earlier");
+        self.0.hovered_ui(ui, config);
+    }
+
+    fn selected_ui(&mut self, ui: &mut egui::Ui, config: &mut GuiConfig) {
+        self.hovered_ui(ui, config);
+    }
+
+    fn color(&self, config: &mut GuiConfig) -> image::Rgba<u8> {
+        image::Rgba(config.pskreporter_config.yesterday_color)
+    }
+
+    fn draw_line_hovered(&self) -> Option<&Coord<f64>> {
+        self.0.draw_line_hovered()
+    }
+
+    fn shape(&self) -> map::MarkerShape {
+        map::MarkerShape::Circle
+    }
+
+    fn size(&self) -> f32 {
+        self.0.size()
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.0.label()
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        self.0.properties()
+    }
+}
+
+/// Converts a report into a marker, returning `None` if either grid square is invalid.
+///
+/// Reports of signals sent by the searched callsign are placed at the receiver, and everything else is placed at the transmitter.
+fn report_marker(report: &ReceptionReport, options: &QueryOptions, lifetime: Option<u64>) -> Option<MapMarker> {
+    let tx_location = maidenhead::grid_to_lat_lon(&report.tx_grid).ok()?;
+    let rx_location = maidenhead::grid_to_lat_lon(&report.rx_grid).ok()?;
+    let id = hash_reception_report(report);
+
+    Some(match options.sent_by && !options.callsign.trim().is_empty() {
+        true => MapMarker::ReceptionReportReceiver { id, location: rx_location, tx_location, inner: *report, lifetime },
+        false => MapMarker::ReceptionReportTransmitter { id, location: tx_location, rx_location, inner: *report, lifetime, alert: None }
+    })
+}
+
+/// Converts one of the reports of the searched callsign into a marker for the callsign's station, returning `None` if every station was searched for
+fn station_marker(report: &ReceptionReport, options: &QueryOptions, id: u64) -> Option<MapMarker> {
+    if options.callsign.trim().is_empty() {
+        return None;
+    }
+
+    Some(match options.sent_by {
+        true => MapMarker::Transmitter {
+            id,
+            location: maidenhead::grid_to_lat_lon(&report.tx_grid).ok()?,
+            grid: report.tx_grid,
+            callsign: report.tx_callsign,
+            mode: report.mode
+        },
+        false => MapMarker::Receiver {
+            id,
+            location: maidenhead::grid_to_lat_lon(&report.rx_grid).ok()?,
+            grid: report.rx_grid,
+            callsign: report.rx_callsign,
+            mode: report.mode
+        }
+    })
 }
 
 
@@ -548,7 +1895,7 @@ impl ApiQueryBuilder {
             // Convert the reception report into a receiver marker and return it
             MapMarker::Receiver {
                 id: rx_marker_id,
-                location: maidenhead::grid_to_lat_lon(&report.rx_grid),
+                location: maidenhead::grid_to_lat_lon(&report.rx_grid)?,
                 grid: report.rx_grid,
                 callsign: report.rx_callsign,
                 mode: report.mode
@@ -561,12 +1908,17 @@ impl ApiQueryBuilder {
 
         // Iterate through the reception reports, convert them to map markers, and add them to the markers vec
         for report in response.reports {
+            // Skip the reports with an invalid grid square
+            let Ok(location) = maidenhead::grid_to_lat_lon(&report.tx_grid) else { continue };
+
             // Convert the reception report into a transmitter marker and push it into the markers vec
             markers.push(MapMarker::ReceptionReportTransmitter {
                 id: hash_reception_report(&report),
-                location: maidenhead::grid_to_lat_lon(&report.tx_grid),
+                location,
                 rx_location: *rx_marker.location(),
-                inner: report
+                inner: report,
+                lifetime: None,
+                alert: None
             });
         }
 
@@ -631,7 +1983,7 @@ impl ApiQueryBuilder {
             // Convert the reception report into a transmitter marker and return it
             MapMarker::Transmitter {
                 id: tx_marker_id,
-                location: maidenhead::grid_to_lat_lon(&report.tx_grid),
+                location: maidenhead::grid_to_lat_lon(&report.tx_grid)?,
                 grid: report.tx_grid,
                 callsign: report.tx_callsign,
                 mode: report.mode
@@ -641,11 +1993,15 @@ impl ApiQueryBuilder {
         };
 
         for report in response.reports {
+            // Skip the reports with an invalid grid square
+            let Ok(location) = maidenhead::grid_to_lat_lon(&report.rx_grid) else { continue };
+
             markers.push(MapMarker::ReceptionReportReceiver {
                 id: hash_reception_report(&report),
-                location: maidenhead::grid_to_lat_lon(&report.rx_grid),
+                location,
                 tx_location: *tx_marker.location(),
-                inner: report
+                inner: report,
+                lifetime: None
             });
         }
 
@@ -702,128 +2058,6 @@ impl ApiQueryBuilder {
 
 }
 
-/// A band filter for the PSKReporter API
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
-enum Band {
-    /// All bands
-    All,
-    /// 2200M Band 135KHz
-    B2200m,
-    /// 630M Band 472KHz
-    B630m,
-    /// 160M Band 1.8MHz
-    B160m,
-    /// 80M Band 3.5MHz
-    B80m,
-    /// 60M Band 5.3MHz
-    B60m,
-    /// 40M Band 7MHz
-    B40m,
-    /// 30M Band 10.1MHz
-    B30m,
-    /// 20M Band 14MHz
-    B20m,
-    /// 17M Band 18MHz
-    B17m,
-    /// 15M Band 21MHz
-    B15m,
-    /// 12M Band 24MHz
-    B12m,
-    /// 10M Band 28MHz
-    B10m,
-    /// 6M Band 50MHz
-    B6m,
-    /// 2M Band 144MHz
-    B2m,
-    /// 1.25M Band 222MHz
-    B1_25M,
-    /// 70CM Band 420MHz
-    B70CM,
-    /// 33CM Band 902MHz
-    B33CM,
-    /// 23CM Band 1.24GHz
-    B23CM,
-    /// 2.4GHZ Band 2.4GHz
-    F2_4GHZ,
-    /// 3.4GHZ Band 3.4GHz
-    F3_4GHZ,
-    /// 5.8GHZ Band 5.8GHz
-    F5_8GHZ,
-    /// 10GHZ Band 10GHz
-    F10GHZ,
-    /// 24GHZ Band 24GHz
-    F24GHZ,
-    /// 47GHZ Band 47GHz
-    F47GHZ,
-    /// 76GHZ Band 76GHz
-    F76GHZ
-}
-impl Band {
-    /// Return the frequency range of the band, or None if the band is All
-    fn freq_range(&self) -> Option<(u64, u64)> {
-        match self {
-            Band::All => None,
-            Band::B2200m => Some((135_700, 137_800)),
-            Band::B630m => Some((472_000, 479_000)),
-            Band::B160m => Some((1_800_000, 2_000_000)),
-            Band::B80m => Some((3_500_000, 4_000_000)),
-            Band::B60m => Some((5_330_500, 5_407_800)),
-            Band::B40m => Some((7_000_000, 7_300_000)), 
-            Band::B30m => Some((10_100_000, 10_150_000)),
-            Band::B20m => Some((14_000_000, 14_350_000)),
-            Band::B17m => Some((18_068_000, 18_168_000)),
-            Band::B15m => Some((21_000_000, 21_450_000)), 
-            Band::B12m => Some((24_890_000, 24_990_000)), 
-            Band::B10m => Some((28_000_000, 29_700_000)),
-            Band::B6m => Some((50_000_000, 54_000_000)),
-            Band::B2m => Some((144_000_000, 148_000_000)),
-            Band::B1_25M => Some((219_000_000, 225_000_000)), 
-            Band::B70CM => Some((420_000_000, 450_000_000)),
-            Band::B33CM => Some((902_000_000, 928_000_000)), 
-            Band::B23CM => Some((1_240_000_000, 1_300_000_000)),
-            Band::F2_4GHZ => Some((2_300_000_000, 2_450_000_000)), 
-            Band::F3_4GHZ => Some((3_300_000_000, 3_500_000_000)), 
-            Band::F5_8GHZ => Some((5_650_000_000, 5_925_000_000)), 
-            Band::F10GHZ => Some((10_000_000_000, 10_500_000_000)),
-            Band::F24GHZ => Some((24_000_000_000, 24_250_000_000)), 
-            Band::F47GHZ => Some((47_000_000_000, 47_200_000_000)), 
-            Band::F76GHZ => Some((76_000_000_000, 81_000_000_000)), 
-        }
-    }
-
-    /// Return the name of the band as a string
-    fn as_str(&self) -> &'static str {
-        match self {
-            Band::All => "All",
-            Band::B2200m => "2200M",
-            Band::B630m => "630M",
-            Band::B160m => "160M",
-            Band::B80m => "80M",
-            Band::B60m => "60M",
-            Band::B40m => "40M",
-            Band::B30m => "30M",
-            Band::B20m => "20M",
-            Band::B17m => "17M",
-            Band::B15m => "15M",
-            Band::B12m => "12M",
-            Band::B10m => "10M",
-            Band::B6m => "6M",
-            Band::B2m => "2M",
-            Band::B1_25M => "1.25M",
-            Band::B70CM => "70CM",
-            Band::B33CM => "33CM",
-            Band::B23CM => "23CM",
-            Band::F2_4GHZ => "2.4GHZ",
-            Band::F3_4GHZ => "3.4GHZ",
-            Band::F5_8GHZ => "5.8GHZ",
-            Band::F10GHZ => "10GHZ",
-            Band::F24GHZ => "24GHZ",
-            Band::F47GHZ => "47GHZ",
-            Band::F76GHZ => "76GHZ",
-        }
-    }
-}
-
 /// A mode filter for the PSKReporter API
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, strum_macros::EnumIter)]
 enum Mode {
@@ -947,34 +2181,6 @@ struct ApiResponseFailed {
     message: String
 }
 
-/// A reception report from the PSKReporter API
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
-#[serde(default)]
-struct ReceptionReport {
-    /// The callsign of the receiving station
-    #[serde(alias = "receiverCallsign")]
-    rx_callsign: CallsignString,
-    /// The grid square of the receiving station
-    #[serde(alias = "receiverLocator")]
-    rx_grid: GridString,
-    /// The callsign of the transmitting station
-    #[serde(alias = "senderCallsign")]
-    tx_callsign: CallsignString,
-    /// The grid square of the transmitting station
-    #[serde(alias = "senderLocator")]
-    tx_grid: GridString,
-    /// The frequency that the station was heard on
-    frequency: u64,
-    /// The time the report was generated
-    #[serde(alias = "flowStartSeconds")]
-    time: u64,
-    /// The mode that the transmitting station used
-    mode: ModeString,
-    /// The signal to noise ratio of the transmitting station
-    #[serde(alias = "sNR")]
-    snr: i8
-}
-
 /// The global config for the PSKReporter module
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(default)]
@@ -988,7 +2194,13 @@ pub struct Config {
     /// The color of the transmitting station reception report markers
     pub tx_reception_report_color: [u8; 4],
     /// The color of the receiving station reception report markers
-    pub rx_reception_report_color: [u8; 4]
+    pub rx_reception_report_color: [u8; 4],
+    /// The address of the MQTT broker that streams the live reports
+    pub mqtt_address: String,
+    /// How many days the received reports are saved for
+    pub history_days: u64,
+    /// The color of the reports from 24 hours before the playback time
+    pub yesterday_color: [u8; 4]
 }
 impl Default for Config {
     fn default() -> Self {
@@ -997,21 +2209,165 @@ impl Default for Config {
             tx_color: [0, 0, 255, 255],
             rx_color: [0, 0, 255, 255],
             tx_reception_report_color: [255, 0, 0, 255],
-            rx_reception_report_color: [255, 0, 0, 255]
+            rx_reception_report_color: [255, 0, 0, 255],
+            mqtt_address: "mqtt.pskreporter.info:1883".into(),
+            history_days: 7,
+            yesterday_color: [128, 128, 128, 255]
         }
     }
 }
 
-/// Hashes a reception report into a u64. This is used to generate a unique but repeatable ID for each reception report.
-/// 
-/// This is useful for persisting the markers across overlay updates and API queries.
-/// If we update the map overlay and a marker exists with the same ID, we can persist the marker state across the update.
-fn hash_reception_report(report: &ReceptionReport) -> u64 {
-    let mut hasher = std::hash::DefaultHasher::new();
-    report.tx_callsign.hash(&mut hasher);
-    report.rx_callsign.hash(&mut hasher);
-    report.frequency.hash(&mut hasher);
-    report.snr.hash(&mut hasher);
-    report.time.hash(&mut hasher);
-    hasher.finish()
+/// Fades the color of a live report as it gets older, so the newest reports stand out. Reports without a lifetime don't fade.
+fn fade(color: [u8; 4], time: u64, lifetime: Option<u64>) -> image::Rgba<u8> {
+    let Some(lifetime) = lifetime.filter(|l| *l > 0) else {
+        return image::Rgba(color);
+    };
+    let age = (chrono::Utc::now().timestamp() - time as i64).max(0) as f32;
+    let opacity = (1.0 - age / lifetime as f32).clamp(0.2, 1.0);
+    let [r, g, b, a] = color;
+    image::Rgba([r, g, b, (a as f32 * opacity) as u8])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A report of `tx` being heard by `rx` with the SNR, on the frequency in Hz
+    fn report(tx: &str, tx_grid: &str, rx: &str, rx_grid: &str, frequency: u64, snr: i8) -> ReceptionReport {
+        ReceptionReport {
+            tx_callsign: CallsignString::from(tx).unwrap(),
+            tx_grid: GridString::from(tx_grid).unwrap(),
+            rx_callsign: CallsignString::from(rx).unwrap(),
+            rx_grid: GridString::from(rx_grid).unwrap(),
+            frequency,
+            snr,
+            ..Default::default()
+        }
+    }
+
+    /// A report of W1AW on 20m or 40m being heard by `rx`
+    fn heard_by(rx: &str, frequency: u64, snr: i8) -> ReceptionReport {
+        report("W1AW", "FN31", rx, "FN42", frequency, snr)
+    }
+
+    #[test]
+    fn quantile_interpolates() {
+        assert_eq!(quantile(&[], 0.5), 0.0);
+        assert_eq!(quantile(&[4.0], 0.5), 4.0);
+        assert_eq!(quantile(&[1.0, 2.0, 3.0], 0.5), 2.0);
+        assert_eq!(quantile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.5);
+        assert_eq!(quantile(&[0.0, 10.0, 20.0, 30.0, 40.0], 0.25), 10.0);
+        assert_eq!(quantile(&[0.0, 10.0, 20.0, 30.0], 0.25), 7.5);
+        assert_eq!(quantile(&[0.0, 10.0, 20.0, 30.0], 0.0), 0.0);
+        assert_eq!(quantile(&[0.0, 10.0, 20.0, 30.0], 1.0), 30.0);
+    }
+
+    #[test]
+    fn snr_comparison_only_uses_common_receivers() {
+        let a = [
+            // K1ABC heard A twice on 20m, so its mean SNR is used
+            heard_by("K1ABC", 14_074_000, -10),
+            heard_by("K1ABC", 14_074_000, -14),
+            heard_by("N2XYZ", 14_074_000, -5),
+            heard_by("G4XYZ", 7_074_000, -8)
+        ];
+        let b = [
+            heard_by("K1ABC", 14_075_000, -6),
+            // N2XYZ only heard B on another band, so it isn't compared
+            heard_by("N2XYZ", 7_074_000, -1),
+            heard_by("G4XYZ", 7_074_000, -10),
+            // JA1AAA didn't hear A
+            heard_by("JA1AAA", 14_074_000, 0)
+        ];
+
+        // The differences are +6 dB (K1ABC) and -2 dB (G4XYZ), so the even median is between them
+        let comparison = SnrComparison::new(&a, &b, true);
+        assert_eq!(comparison.common, 2);
+        assert_eq!(comparison.mean_difference, 2.0);
+        assert_eq!(comparison.median_difference, 2.0);
+        assert_eq!(comparison.b_better, 1);
+
+        // Swapping the sides negates the differences
+        let comparison = SnrComparison::new(&b, &a, true);
+        assert_eq!(comparison.common, 2);
+        assert_eq!(comparison.mean_difference, -2.0);
+        assert_eq!(comparison.b_better, 1);
+    }
+
+    #[test]
+    fn snr_comparison_odd_median() {
+        let a = [heard_by("K1ABC", 14_074_000, -10), heard_by("N2XYZ", 14_074_000, -10), heard_by("G4XYZ", 14_074_000, -10)];
+        let b = [heard_by("K1ABC", 14_074_000, -13), heard_by("N2XYZ", 14_074_000, -9), heard_by("G4XYZ", 14_074_000, 2)];
+
+        // The differences are -3, +1, and +12 dB
+        let comparison = SnrComparison::new(&a, &b, true);
+        assert_eq!(comparison.common, 3);
+        assert_eq!(comparison.mean_difference, 10.0 / 3.0);
+        assert_eq!(comparison.median_difference, 1.0);
+        assert_eq!(comparison.b_better, 2);
+    }
+
+    #[test]
+    fn snr_comparison_by_transmitter() {
+        // When searching by the receiving station, the transmitting stations are compared
+        let a = [report("K1ABC", "FN42", "W1AW", "FN31", 14_074_000, -12), report("N2XYZ", "FN20", "W1AW", "FN31", 14_074_000, -3)];
+        let b = [report("K1ABC", "FN42", "W1AW", "FN31", 14_074_000, -7)];
+        let comparison = SnrComparison::new(&a, &b, false);
+        assert_eq!(comparison.common, 1);
+        assert_eq!(comparison.mean_difference, 5.0);
+
+        // When searching by the transmitting station, the receivers are compared instead
+        let comparison = SnrComparison::new(&a, &b, true);
+        assert_eq!(comparison.common, 1);
+        assert_eq!(comparison.mean_difference, 0.5);
+        let b = [report("K1ABC", "FN42", "W2AW", "FN20", 14_074_000, -7)];
+        assert_eq!(SnrComparison::new(&a, &b, true).common, 0);
+    }
+
+    #[test]
+    fn snr_comparison_with_an_empty_side() {
+        let a = [heard_by("K1ABC", 14_074_000, -10)];
+        for (a, b) in [(&a[..], &[][..]), (&[][..], &a[..]), (&[][..], &[][..])] {
+            let comparison = SnrComparison::new(a, b, true);
+            assert_eq!(comparison.common, 0);
+            assert_eq!(comparison.mean_difference, 0.0);
+            assert_eq!(comparison.median_difference, 0.0);
+            assert_eq!(comparison.b_better, 0);
+        }
+    }
+
+    #[test]
+    fn reach_stats() {
+        let reports = [
+            report("W1AW", "FN31", "K1ABC", "FN42", 14_074_000, -10),
+            report("W1AW", "FN31", "G4XYZ", "IO91", 14_074_000, -20),
+            report("W1AW", "FN31", "K1ABC", "FN42", 7_074_000, 0),
+            // The receiver's grid is unknown, so the report doesn't have a distance
+            report("W1AW", "FN31", "JA1AAA", "", 7_074_000, -5)
+        ];
+        let stats = ReachStats::new(&reports, true, None);
+
+        assert_eq!(stats.reports, 4);
+        assert_eq!(stats.stations, 3);
+        assert_eq!(stats.median_snr, -7.5);
+
+        // The bands are in band order, with their SNRs sorted
+        assert_eq!(stats.snr, vec![(Band::B40m, vec![-5.0, 0.0]), (Band::B20m, vec![-20.0, -10.0])]);
+
+        // The distances of the three reports with both grids
+        let location = |grid| maidenhead::grid_to_lat_lon(grid).unwrap();
+        assert_eq!(stats.max_distance, geodesy::distance(&location("FN31"), &location("IO91")));
+        assert_eq!(stats.median_distance, geodesy::distance(&location("FN31"), &location("FN42")));
+
+        // Without the DXCC entity list, the stations can't be grouped
+        assert!(stats.continents.is_empty() && stats.entities.is_empty());
+
+        // Searching by the receiving station counts the transmitters instead
+        assert_eq!(ReachStats::new(&reports, false, None).stations, 1);
+
+        let empty = ReachStats::new(&[], true, None);
+        assert_eq!((empty.reports, empty.stations, empty.max_distance, empty.median_snr), (0, 0, 0.0, 0.0));
+        assert!(empty.snr.is_empty());
+    }
 }
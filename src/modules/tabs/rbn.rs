@@ -0,0 +1,557 @@
+//
+// Contains the code for the Reverse Beacon Network tab, which shows the skimmers that heard a callsign
+//
+
+use std::{collections::{HashMap, HashSet, VecDeque}, hash::{Hash, Hasher}, time::{Duration, Instant}};
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+use egui::{Align, Id, Layout, Ui, Widget, WidgetText};
+use geo::Coord;
+use log::debug;
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use crate::modules::{geodesy, gui::{self, generate_random_id, Tab}, maidenhead, map::{self, MapLayer, MapMarkerTrait}, rbn};
+use crate::{types, GuiConfig};
+use super::{callsign_lookup::{self, CallsignInformation}, dxcluster::MaxAge};
+
+
+type CallsignString = arrayvec::ArrayString<20>;
+type ModeString = arrayvec::ArrayString<16>;
+
+
+/// A tab that shows the RBN skimmers that heard a callsign on a map, with a summary of each band
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct RbnTab {
+    /// The egui ID
+    id: Id,
+    #[serde(skip)]
+    map: Option<map::MapWidget>,
+    /// The position and zoom of the map, so it's restored when the tab is reopened
+    view: map::MapView,
+    /// The layer of the station and skimmer markers
+    #[serde(skip)]
+    skimmers: map::MarkerLayer<MapMarker>,
+    /// The callsign to show the spots of. If this is empty, the station callsign is used.
+    callsign: String,
+    /// The callsign that was submitted, which is used instead of `callsign` so the station isn't looked up while its callsign is being typed.
+    /// This is set to `callsign` on the first frame.
+    #[serde(skip)]
+    submitted_callsign: Option<String>,
+    /// The band to filter for
+    band: types::Band,
+    /// How old can the spots be before they're hidden?
+    max_age: MaxAge,
+    /// Should the band summary be shown?
+    show_summary: bool,
+    /// The spots that have been received, with only the newest spot for each skimmer, station, band, and time bucket
+    #[serde(skip)]
+    spots: Vec<rbn::Spot>,
+    /// The locations of the stations that were looked up, keyed by callsign. This is `None` if the station couldn't be located.
+    #[serde(skip)]
+    locations: HashMap<String, Option<Coord>>,
+    /// The callsigns that should be looked up
+    #[serde(skip)]
+    lookup_queue: VecDeque<String>,
+    /// The callsign lookups that are running
+    #[serde(skip)]
+    lookups: Vec<(String, Promise<Result<CallsignInformation>>)>,
+    /// The times that the lookups in the last minute were started, oldest first
+    #[serde(skip)]
+    lookup_times: VecDeque<Instant>,
+    /// The number of lookups that were started since the tab was opened
+    #[serde(skip)]
+    n_lookups: usize
+}
+impl RbnTab {
+    /// The maximum number of spots to keep
+    const MAX_SPOTS: usize = 2000;
+    /// The length of the time buckets in seconds. A skimmer spotting the same station on the same band more than once in a bucket only counts as one spot.
+    const SPOT_BUCKET: i64 = 10 * 60;
+    /// The maximum number of callsign lookups that can run at once
+    const MAX_LOOKUPS: usize = 4;
+    /// The maximum number of callsign lookups that can be started each minute
+    const MAX_LOOKUPS_PER_MINUTE: usize = 20;
+    /// The maximum number of callsign lookups that can be started while the tab is open, so busy bands don't use up the lookup quota
+    const MAX_TOTAL_LOOKUPS: usize = 500;
+    /// The color of the marker of the station that was heard
+    const STATION_COLOR: [u8; 4] = [0, 0, 255, 255];
+
+    /// The callsign that we're showing the spots of
+    fn target(&self, config: &GuiConfig) -> String {
+        match self.submitted_callsign.as_deref().unwrap_or(&self.callsign).trim() {
+            "" => config.station.callsign.trim().to_ascii_uppercase(),
+            callsign => callsign.to_ascii_uppercase()
+        }
+    }
+
+    /// The key that identifies duplicate spots: the skimmer, the station that was heard, the band, and the time bucket
+    fn spot_key(spot: &rbn::Spot) -> (&str, &str, types::Band, i64) {
+        (&spot.skimmer, &spot.callsign, spot.band(), spot.time.and_utc().timestamp().div_euclid(Self::SPOT_BUCKET))
+    }
+
+    /// Adds a spot, replacing any older spot from the same skimmer for the same station, band, and time bucket
+    fn add_spot(&mut self, spot: &rbn::Spot) {
+        let key = Self::spot_key(spot);
+        self.spots.retain(|s| Self::spot_key(s) != key);
+        self.spots.push(spot.clone());
+
+        // Remove the oldest spots if there are too many
+        if self.spots.len() > Self::MAX_SPOTS {
+            self.spots.sort_by_key(|s| std::cmp::Reverse(s.time));
+            self.spots.truncate(Self::MAX_SPOTS);
+        }
+
+        self.locate(spot.skimmer_callsign());
+    }
+
+    /// Queues a lookup of the location of a callsign, unless it was already looked up
+    fn locate(&mut self, callsign: &str) {
+        if !callsign.is_empty() && !self.locations.contains_key(callsign) && !self.lookup_queue.iter().any(|c| c == callsign) && !self.lookups.iter().any(|(c, _)| c == callsign) {
+            self.lookup_queue.push_back(callsign.to_string());
+        }
+    }
+
+    /// Starts any queued callsign lookups and processes the finished ones
+    fn process_lookups(&mut self, config: &GuiConfig) {
+
+        // Store the locations of the finished lookups
+        for (callsign, task) in self.lookups.extract_if(|(_, t)| t.ready().is_some()) {
+            let location = match task.block_and_take() {
+                Ok(info) => info.best_location(),
+                Err(err) => {
+                    debug!("Failed to locate '{callsign}': {err}");
+                    None
+                }
+            };
+            self.locations.insert(callsign, location);
+        }
+
+        // Forget the lookups that were started over a minute ago
+        while self.lookup_times.front().is_some_and(|t| t.elapsed() > Duration::from_secs(60)) {
+            self.lookup_times.pop_front();
+        }
+
+        // Start the queued lookups, without going over the rate limit or the total limit
+        while self.lookups.len() < Self::MAX_LOOKUPS && self.lookup_times.len() < Self::MAX_LOOKUPS_PER_MINUTE && self.n_lookups < Self::MAX_TOTAL_LOOKUPS {
+            let Some(callsign) = self.lookup_queue.pop_front() else {
+                break;
+            };
+            let task = callsign_lookup::lookup_promise(callsign.clone(), &config.callsign_lookup_config);
+            self.lookups.push((callsign, task));
+            self.lookup_times.push_back(Instant::now());
+            self.n_lookups += 1;
+        }
+
+    }
+
+    /// Shows the spots of the callsign in the text box, and looks up the location of the station
+    fn submit_callsign(&mut self, config: &GuiConfig) {
+        self.submitted_callsign = Some(self.callsign.trim().to_ascii_uppercase());
+
+        // Our own location comes from the station settings
+        let target = self.target(config);
+        if target != config.station.callsign.trim().to_ascii_uppercase() || maidenhead::grid_to_lat_lon(&config.station.grid).is_err() {
+            self.locate(&target);
+        }
+    }
+
+    /// The location of the station that was heard. Our own location comes from the station settings.
+    fn station_location(&self, config: &GuiConfig, target: &str) -> Option<Coord> {
+        if target == config.station.callsign.trim().to_ascii_uppercase() {
+            if let Ok(location) = maidenhead::grid_to_lat_lon(&config.station.grid) {
+                return Some(location);
+            }
+        }
+
+        self.locations.get(target).copied().flatten()
+    }
+
+    /// Gets the spots that should be shown, newest first
+    fn visible_spots(&self, target: &str) -> Vec<&rbn::Spot> {
+        let now = Utc::now().naive_utc();
+
+        let mut spots: Vec<&rbn::Spot> = self.spots.iter()
+            .filter(|s| s.callsign == target)
+            .filter(|s| self.band == types::Band::All || s.band() == self.band)
+            .filter(|s| self.max_age.duration().map_or(true, |age| now.signed_duration_since(s.time) <= age))
+            .collect();
+        spots.sort_by_key(|s| std::cmp::Reverse(s.time));
+
+        spots
+    }
+
+    /// Creates the map markers for the spots
+    fn markers(&self, spots: &[&rbn::Spot], target: &str, station_location: Option<Coord>) -> Vec<MapMarker> {
+        // Only show the newest spot from each skimmer on each band, the spots are sorted newest first
+        let mut shown = HashSet::new();
+        let mut markers: Vec<MapMarker> = spots.iter().filter(|spot| shown.insert((spot.skimmer.as_str(), spot.band()))).filter_map(|spot| {
+            let location = self.locations.get(spot.skimmer_callsign()).copied().flatten()?;
+            Some(MapMarker::Skimmer {
+                id: hash_spot(spot),
+                location,
+                station_location,
+                skimmer: CallsignString::from(&spot.skimmer).unwrap_or_default(),
+                frequency: spot.frequency,
+                mode: ModeString::from(&spot.mode.to_string()).unwrap_or_default(),
+                snr: spot.snr,
+                speed: spot.speed,
+                time: spot.time
+            })
+        }).collect();
+
+        // The station is added last so it's drawn on top of the skimmers
+        if let Some(location) = station_location {
+            let mut hasher = std::hash::DefaultHasher::new();
+            target.hash(&mut hasher);
+            markers.push(MapMarker::Station {
+                id: hasher.finish(),
+                location,
+                callsign: CallsignString::from(target).unwrap_or_default()
+            });
+        }
+
+        markers
+    }
+
+    /// Shows the number of spots, skimmers, and the signal reports for each band
+    fn summary_ui(&self, spots: &[&rbn::Spot], ui: &mut Ui) {
+        egui::Grid::new(self.id.with("summary"))
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Band");
+            ui.strong("Spots");
+            ui.strong("Skimmers");
+            ui.strong("Best SNR");
+            ui.strong("Avg SNR");
+            ui.strong("Last Heard");
+            ui.end_row();
+
+            for summary in BandSummary::from_spots(spots) {
+                ui.label(summary.band.as_str());
+                ui.label(summary.spots.to_string());
+                ui.label(summary.skimmers.to_string());
+                ui.label(format!("{} dB", summary.best_snr));
+                ui.label(format!("{:.1} dB", summary.average_snr));
+                ui.label(summary.last_heard.format("%H%MZ").to_string());
+                ui.end_row();
+            }
+        });
+    }
+}
+impl Tab for RbnTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "Reverse Beacon".into()
+    }
+
+    fn process_event(&mut self, config: &mut GuiConfig, event: &types::Event) {
+        if let types::Event::RbnSpot(spot) = event {
+            if spot.callsign == self.target(config) {
+                self.add_spot(spot);
+            }
+        }
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+
+        // Show the callsign that was restored with the tab
+        if self.submitted_callsign.is_none() {
+            self.submit_callsign(config);
+        }
+        self.process_lookups(config);
+
+        // Show the status of the connection
+        match &config.rbn {
+            Some(client) if client.connected() => { ui.label(format!("Connected to {}", client.address())); },
+            Some(client) => { ui.colored_label(ui.style().visuals.warn_fg_color, format!("Connecting to {}", client.address())); },
+            None if config.station.callsign.trim().is_empty() => { ui.label("Set your callsign in the station settings to log in to the RBN"); },
+            None => { ui.label("The RBN client is disabled, enable it in the settings tab"); }
+        }
+
+        // Show the filters
+        ui.horizontal(|ui| {
+            // The callsign is submitted when the text box loses focus or enter is pressed
+            let response = egui::TextEdit::singleline(&mut self.callsign)
+            .hint_text(format!("Callsign ({})", config.station.callsign.trim().to_ascii_uppercase()))
+            .desired_width(110.0)
+            .ui(ui);
+            if response.lost_focus() {
+                self.submit_callsign(config);
+            }
+
+            egui::ComboBox::from_id_source(self.id.with("band"))
+            .selected_text(format!("Band: {}", self.band.as_str()))
+            .show_ui(ui, |ui| {
+                for band in types::Band::iter() {
+                    ui.selectable_value(&mut self.band, band, band.as_str());
+                }
+            });
+
+            egui::ComboBox::from_id_source(self.id.with("age"))
+            .selected_text(format!("Age: {}", self.max_age))
+            .show_ui(ui, |ui| {
+                for age in MaxAge::iter() {
+                    ui.selectable_value(&mut self.max_age, age, age.to_string());
+                }
+            });
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                if ui.button("Clear").clicked() {
+                    self.spots.clear();
+                }
+                ui.checkbox(&mut self.show_summary, "Summary");
+            });
+        });
+
+        let target = self.target(config);
+        let station_location = self.station_location(config, &target);
+        let spots = self.visible_spots(&target);
+
+        // Show how many skimmers heard the station, and how many of them couldn't be located
+        let skimmers: HashSet<&str> = spots.iter().map(|s| s.skimmer_callsign()).collect();
+        let unlocated = skimmers.iter().filter(|c| self.locations.get(**c).is_some_and(|l| l.is_none())).count();
+        ui.horizontal(|ui| {
+            ui.label(format!("{} spots from {} skimmers", spots.len(), skimmers.len()));
+            if unlocated > 0 {
+                ui.label(format!("({unlocated} couldn't be located)"))
+                .on_hover_text(format!(
+                    "Skimmers are located with the callsign lookup, add a HamQTH account in the settings to locate stations outside of the US.\n\
+                    Each lookup counts towards the quota of the lookup service, so at most {} callsigns are looked up each minute, and {} in total.",
+                    Self::MAX_LOOKUPS_PER_MINUTE, Self::MAX_TOTAL_LOOKUPS
+                ));
+            }
+            if !self.lookups.is_empty() || (!self.lookup_queue.is_empty() && self.n_lookups < Self::MAX_TOTAL_LOOKUPS) {
+                ui.spinner();
+            }
+        });
+
+        if self.show_summary {
+            self.summary_ui(&spots, ui);
+        }
+
+        ui.separator();
+
+        // Update the map if the markers changed
+        let markers = self.markers(&spots, &target, station_location);
+        let map = self.map.get_or_insert_with(|| {
+            let mut map = map::MapWidget::new(ui.ctx());
+            map.set_view(self.view);
+            map.set_callsign_locator(callsign_lookup::location_promise);
+            map
+        });
+        if self.skimmers.markers().iter().map(|m| m.id()).ne(markers.iter().map(|m| m.id())) {
+            *self.skimmers.markers_mut() = markers;
+            map.update_overlay();
+        }
+
+        let mut layers: [&mut dyn MapLayer; 1] = [&mut self.skimmers];
+        map.ui_with_layers(ui, config, &mut layers);
+        self.view = map.view();
+
+    }
+}
+impl Default for RbnTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            map: Default::default(),
+            view: Default::default(),
+            skimmers: map::MarkerLayer::new("Skimmers"),
+            callsign: Default::default(),
+            submitted_callsign: None,
+            band: types::Band::All,
+            max_age: Default::default(),
+            show_summary: true,
+            spots: Default::default(),
+            locations: Default::default(),
+            lookup_queue: Default::default(),
+            lookups: Default::default(),
+            lookup_times: Default::default(),
+            n_lookups: 0
+        }
+    }
+}
+impl std::fmt::Debug for RbnTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RbnTab")
+        .field("id", &self.id)
+        .field("map", &self.map)
+        .field("callsign", &self.callsign)
+        .field("band", &self.band)
+        .field("max_age", &self.max_age)
+        .field("spots", &self.spots.len())
+        .field("locations", &self.locations.len())
+        .finish()
+    }
+}
+
+
+/// The spots received on a single band
+struct BandSummary {
+    /// The band
+    band: types::Band,
+    /// The number of spots
+    spots: usize,
+    /// The number of skimmers that heard the station
+    skimmers: usize,
+    /// The strongest signal report, in dB
+    best_snr: i32,
+    /// The average signal report, in dB
+    average_snr: f32,
+    /// The time of the newest spot
+    last_heard: NaiveDateTime
+}
+impl BandSummary {
+    /// Summarizes the spots of each band, skipping the bands without any spots
+    fn from_spots(spots: &[&rbn::Spot]) -> Vec<Self> {
+        types::Band::iter().filter(|b| *b != types::Band::All).filter_map(|band| {
+            let spots: Vec<&&rbn::Spot> = spots.iter().filter(|s| s.band() == band).collect();
+            let skimmers: HashSet<&str> = spots.iter().map(|s| s.skimmer_callsign()).collect();
+
+            Some(Self {
+                band,
+                spots: spots.len(),
+                skimmers: skimmers.len(),
+                best_snr: spots.iter().map(|s| s.snr).max()?,
+                average_snr: spots.iter().map(|s| s.snr as f32).sum::<f32>() / spots.len() as f32,
+                last_heard: spots.iter().map(|s| s.time).max()?
+            })
+        }).collect()
+    }
+}
+
+/// A marker that's visible on the map
+#[derive(Debug, Clone, Copy)]
+enum MapMarker {
+    /// The station that was heard
+    Station {
+        /// The ID of the map marker. This is a hash of the callsign.
+        id: u64,
+        /// The location of the station
+        location: Coord<f64>,
+        /// The callsign of the station
+        callsign: CallsignString
+    },
+    /// A skimmer that heard the station
+    Skimmer {
+        /// The ID of the map marker. This is a hash of the spot.
+        id: u64,
+        /// The location of the skimmer
+        location: Coord<f64>,
+        /// The location of the station that was heard, if it's known
+        station_location: Option<Coord<f64>>,
+        /// The callsign of the skimmer
+        skimmer: CallsignString,
+        /// The frequency of the station, in Hz
+        frequency: u64,
+        /// The mode of the station
+        mode: ModeString,
+        /// The signal to noise ratio, in dB
+        snr: i32,
+        /// The speed of the station, in WPM or baud
+        speed: Option<u32>,
+        /// The time of the spot, in UTC
+        time: NaiveDateTime
+    }
+}
+impl MapMarkerTrait for MapMarker {
+    fn id(&self) -> u64 {
+        *match self {
+            MapMarker::Station { id, .. } => id,
+            MapMarker::Skimmer { id, .. } => id
+        }
+    }
+
+    fn location(&self) -> &Coord<f64> {
+        match self {
+            MapMarker::Station { location, .. } => location,
+            MapMarker::Skimmer { location, .. } => location
+        }
+    }
+
+    fn hovered_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        match self {
+            MapMarker::Station { location, callsign, .. } => {
+
+                ui.heading("Station");
+                ui.label(format!("Callsign: {callsign}"));
+                ui.label(format!("Grid: {}", maidenhead::lat_lon_to_grid(location)));
+
+            },
+            MapMarker::Skimmer { location, station_location, skimmer, frequency, mode, snr, speed, time, .. } => {
+
+                ui.heading("Skimmer");
+                ui.label(format!("Callsign: {skimmer}"));
+                ui.label(format!("Grid: {}", maidenhead::lat_lon_to_grid(location)));
+
+                // The signal report
+                let freq = gui::frequency_formatter(*frequency as f64, 0..=0);
+                ui.label(format!("Frequency: {freq}"));
+                ui.label(format!("Mode: {mode}"));
+                ui.label(format!("SNR: {snr}dB"));
+                if let Some(speed) = speed {
+                    let unit = if mode.as_str() == "RTTY" { "baud" } else { "WPM" };
+                    ui.label(format!("Speed: {speed} {unit}"));
+                }
+                ui.label(format!("Time (UTC): {}", time.format("%H:%M")));
+
+                // The distance and bearing from the station to the skimmer
+                if let Some(station_location) = station_location {
+                    let path = geodesy::Path::new(station_location, location);
+                    gui::path_ui(ui, &path, &config.distance_unit, "Bearing");
+                }
+
+            }
+        }
+    }
+
+    fn selected_ui(&mut self, ui: &mut Ui, config: &mut GuiConfig) {
+        self.hovered_ui(ui, config);
+    }
+
+    fn color(&self, _config: &mut GuiConfig) -> image::Rgba<u8> {
+        match self {
+            MapMarker::Station { .. } => image::Rgba(RbnTab::STATION_COLOR),
+            // Fade from red for weak signals to green for strong signals
+            MapMarker::Skimmer { snr, .. } => {
+                let strength = (*snr as f32 / 30.0).clamp(0.0, 1.0);
+                image::Rgba([(255.0 * (1.0 - strength)) as u8, (200.0 * strength) as u8, 0, 255])
+            }
+        }
+    }
+
+    fn draw_line_hovered(&self) -> Option<&Coord<f64>> {
+        match self {
+            MapMarker::Station { .. } => None,
+            MapMarker::Skimmer { station_location, .. } => station_location.as_ref()
+        }
+    }
+
+    fn properties(&self) -> Vec<(&str, String)> {
+        match self {
+            MapMarker::Station { callsign, .. } => vec![("callsign", callsign.to_string())],
+            MapMarker::Skimmer { skimmer, frequency, mode, snr, speed, time, .. } => vec![
+                ("skimmer", skimmer.to_string()),
+                ("frequency", frequency.to_string()),
+                ("mode", mode.to_string()),
+                ("snr", snr.to_string()),
+                ("speed", speed.map(|s| s.to_string()).unwrap_or_default()),
+                ("time", time.and_utc().to_rfc3339())
+            ]
+        }
+    }
+}
+
+/// Hashes a spot into a u64. This is used to generate a unique but repeatable ID for each map marker.
+fn hash_spot(spot: &rbn::Spot) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    spot.skimmer.hash(&mut hasher);
+    spot.frequency.hash(&mut hasher);
+    spot.time.hash(&mut hasher);
+    hasher.finish()
+}
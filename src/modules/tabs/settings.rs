@@ -4,10 +4,11 @@
 
 use std::{fmt::Debug, ops::RangeInclusive};
 use egui::{Id, Widget};
+use poll_promise::Promise;
 use egui_dock::{DockState, TabViewer};
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
-use crate::modules::{gui, map};
+use crate::modules::{alerts, cw, dxcc, gui, maidenhead, map, solar, tile_cache, types};
 
 /// The settings tab for the GUI
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,9 +42,20 @@ impl Default for SettingsTab {
         Self {
             id: gui::generate_random_id(),
             tabs: DockState::new(vec![
+                Box::new(StationSettingsTab),
                 Box::new(PSKReporterSettingsTab),
-                Box::new(MapSettingsTab),
-                Box::new(CallsignLookupSettingsTab)
+                Box::new(MapSettingsTab::default()),
+                Box::new(CallsignLookupSettingsTab),
+                Box::new(WsjtxSettingsTab),
+                Box::new(Js8CallSettingsTab),
+                Box::new(FldigiSettingsTab),
+                Box::new(N1mmSettingsTab),
+                Box::new(RigSettingsTab),
+                Box::new(CwSettingsTab),
+                Box::new(DxClusterSettingsTab),
+                Box::new(RbnSettingsTab),
+                Box::new(AlertsSettingsTab),
+                Box::new(SolarSettingsTab)
             ])
         }
     }
@@ -84,6 +96,8 @@ struct PSKReporterSettingsTab;
 impl PSKReporterSettingsTab {
     /// The minimum and maximum refresh rate allowed. The range is inclusive, in seconds, and from 1 to 30 minutes.
     const REFRESH_RATE_RANGE: RangeInclusive<u16> = 60..=1800;
+    /// The minimum and maximum number of days that reports are saved for. The range is inclusive.
+    const HISTORY_DAYS_RANGE: RangeInclusive<u16> = 1..=90;
 }
 impl SettingsTabTrait for PSKReporterSettingsTab {
     fn title(&mut self) -> egui::WidgetText {
@@ -118,6 +132,31 @@ impl SettingsTabTrait for PSKReporterSettingsTab {
             });
         });
 
+        // The live feed setting
+        ui.group(|ui| {
+
+            // A label to describe the broker address option
+            ui.label("Address of the MQTT broker for the live feed");
+            // The broker address textbox
+            egui::widgets::TextEdit::singleline(&mut config.pskreporter_config.mqtt_address)
+            .hint_text("mqtt.pskreporter.info:1883")
+            .ui(ui)
+            .on_hover_text("Any MQTT broker that publishes PSKReporter reports can be used, e.g. a local broker for testing");
+
+        });
+
+        // The history setting
+        ui.group(|ui| {
+
+            // A label to describe the history option
+            ui.label("How many days to save received reports for (Used by the history playback)");
+            // A drag value to set the number of days
+            egui::widgets::DragValue::new(&mut config.pskreporter_config.history_days)
+            .clamp_range(Self::HISTORY_DAYS_RANGE)
+            .ui(ui);
+
+        });
+
         // The marker color settings
         ui.group(|ui| {
 
@@ -137,14 +176,234 @@ impl SettingsTabTrait for PSKReporterSettingsTab {
             ui.label("RX reception report color");
             ui.color_edit_button_srgba_unmultiplied(&mut config.pskreporter_config.rx_reception_report_color);
 
+            // A label and color picker to set the color of the reports from yesterday
+            ui.label("Yesterday's reports color (History playback)");
+            ui.color_edit_button_srgba_unmultiplied(&mut config.pskreporter_config.yesterday_color);
+
         });
 
     }
 }
 
 /// The map settings tab
-#[derive(Debug)]
-struct MapSettingsTab;
+struct MapSettingsTab {
+    /// The northern edge of the offline pack area, in degrees
+    pack_north: f64,
+    /// The southern edge of the offline pack area, in degrees
+    pack_south: f64,
+    /// The western edge of the offline pack area, in degrees
+    pack_west: f64,
+    /// The eastern edge of the offline pack area, in degrees
+    pack_east: f64,
+    /// The grid square that can be used as the offline pack area
+    pack_grid: String,
+    /// The lowest zoom level of the offline pack
+    pack_min_zoom: u8,
+    /// The highest zoom level of the offline pack
+    pack_max_zoom: u8,
+    /// The offline pack that is downloading, or the last one that was downloaded
+    pack: Option<tile_cache::PackDownload>,
+    /// The task that is finding the size of the cache and offline packs
+    usage_task: Option<Promise<anyhow::Result<(u64, u64)>>>,
+    /// The size of the cache and offline packs, in bytes
+    usage: Option<(u64, u64)>
+}
+impl MapSettingsTab {
+    /// The highest zoom level of the map tiles
+    const MAX_ZOOM: u8 = 19;
+
+    /// Shows the tile cache settings
+    fn cache_ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // Process the finished usage task
+        if let Some(task) = self.usage_task.take_if(|t| t.ready().is_some()) {
+            match task.block_and_take() {
+                Ok(usage) => self.usage = Some(usage),
+                Err(err) => {
+                    config.notification_read = false;
+                    config.notifications.push(types::Notification::Error(format!("Failed to get the size of the tile cache: {err}")));
+                }
+            }
+        }
+
+        let cache = &mut config.map_config.cache;
+
+        // Checkboxes to enable the cache and offline mode
+        ui.checkbox(&mut cache.enabled, "Save map tiles to disk");
+        ui.checkbox(&mut cache.offline, "Offline")
+        .on_hover_text("Only use saved map tiles and offline packs, without contacting the map provider");
+
+        // The size limit of the cache
+        ui.horizontal(|ui| {
+            ui.label("Size limit");
+            egui::widgets::DragValue::new(&mut cache.size_limit)
+            .clamp_range(10..=100_000)
+            .suffix(" MB")
+            .ui(ui)
+            .on_hover_text("Offline packs don't count towards this");
+        });
+
+        // The directory of the cache
+        ui.label("Directory of the saved map tiles");
+        let default_path = cache.path().display().to_string();
+        egui::widgets::TextEdit::singleline(&mut cache.path)
+        .hint_text(default_path)
+        .ui(ui);
+
+        // The size of the cache and offline packs, and a button to clear the cache
+        ui.horizontal(|ui| {
+            match (&self.usage_task, self.usage) {
+                (Some(_), _) => { ui.spinner(); },
+                (None, Some((cache_size, packs_size))) => {
+                    ui.label(format!("Saved tiles: {:.1} MB, offline packs: {:.1} MB", cache_size as f64 / 1e6, packs_size as f64 / 1e6));
+                },
+                (None, None) => {}
+            }
+
+            if ui.button("Refresh").clicked() {
+                self.usage_task = Some(tile_cache::usage_promise(cache));
+            }
+            if ui.button("Clear saved tiles").clicked() {
+                if let Err(err) = tile_cache::clear(cache, false) {
+                    config.notification_read = false;
+                    config.notifications.push(types::Notification::Error(format!("Failed to clear the tile cache: {err}")));
+                }
+                self.usage_task = Some(tile_cache::usage_promise(cache));
+            }
+        });
+    }
+
+    /// Shows the offline pack area, zoom levels, and download progress
+    fn pack_ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        ui.label("Offline map pack (Download the map tiles of an area ahead of time, so the map works without an internet connection)");
+
+        // The edges of the area
+        egui::Grid::new("map_pack_area_grid")
+        .num_columns(4)
+        .show(ui, |ui| {
+            ui.label("North");
+            egui::widgets::DragValue::new(&mut self.pack_north).clamp_range(-85.0..=85.0).speed(0.1).suffix("°").ui(ui);
+            ui.label("South");
+            egui::widgets::DragValue::new(&mut self.pack_south).clamp_range(-85.0..=85.0).speed(0.1).suffix("°").ui(ui);
+            ui.end_row();
+
+            ui.label("West");
+            egui::widgets::DragValue::new(&mut self.pack_west).clamp_range(-180.0..=180.0).speed(0.1).suffix("°").ui(ui);
+            ui.label("East");
+            egui::widgets::DragValue::new(&mut self.pack_east).clamp_range(-180.0..=180.0).speed(0.1).suffix("°").ui(ui);
+            ui.end_row();
+        });
+
+        // Use the area of a grid square
+        ui.horizontal(|ui| {
+            egui::widgets::TextEdit::singleline(&mut self.pack_grid)
+            .hint_text("Grid square")
+            .desired_width(80.0)
+            .ui(ui);
+
+            if ui.button("Use grid square").clicked() {
+                match maidenhead::Locator::parse(&self.pack_grid) {
+                    Ok(locator) => {
+                        let bounds = locator.bounds();
+                        (self.pack_west, self.pack_south) = bounds.min().x_y();
+                        (self.pack_east, self.pack_north) = bounds.max().x_y();
+                    },
+                    Err(err) => {
+                        config.notification_read = false;
+                        config.notifications.push(types::Notification::Error(err.to_string()));
+                    }
+                }
+            }
+        });
+
+        // The range of zoom levels
+        ui.horizontal(|ui| {
+            ui.label("Zoom levels");
+            egui::widgets::DragValue::new(&mut self.pack_min_zoom).clamp_range(0..=self.pack_max_zoom).ui(ui);
+            ui.label("to");
+            egui::widgets::DragValue::new(&mut self.pack_max_zoom).clamp_range(self.pack_min_zoom..=Self::MAX_ZOOM).ui(ui);
+        });
+
+        // The area crosses the antimeridian if the western edge is east of the eastern edge
+        let area = tile_cache::PackArea {
+            north: self.pack_north.max(self.pack_south),
+            south: self.pack_north.min(self.pack_south),
+            west: self.pack_west,
+            east: self.pack_east
+        };
+        let zooms = self.pack_min_zoom..=self.pack_max_zoom;
+        ui.label(format!("{} tiles", tile_cache::pack_size(&area, zooms.clone())));
+
+        match &self.pack {
+            // Show the progress of the download
+            Some(pack) if !pack.is_finished() => {
+                let (done, failed, total) = pack.progress();
+                ui.horizontal(|ui| {
+                    egui::widgets::ProgressBar::new((done + failed) as f32 / total.max(1) as f32)
+                    .text(format!("{} of {total} tiles", done + failed))
+                    .desired_width(240.0)
+                    .ui(ui);
+
+                    if ui.button("Cancel").clicked() {
+                        pack.cancel();
+                    }
+                });
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(250));
+            },
+            // Show a button to download the pack, and the result of the last download
+            _ => {
+                if let Some((done, failed, total)) = self.pack.as_ref().map(|p| p.progress()) {
+                    ui.label(format!("Downloaded {done} of {total} tiles ({failed} failed)"));
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Download").clicked() {
+                        match tile_cache::PackDownload::start(area, zooms, config.map_config.tile_provider.clone(), &config.map_config.cache) {
+                            Ok(pack) => self.pack = Some(pack),
+                            Err(err) => {
+                                config.notification_read = false;
+                                config.notifications.push(types::Notification::Error(err.to_string()));
+                            }
+                        }
+                    }
+
+                    if ui.button("Delete offline packs").clicked() {
+                        if let Err(err) = tile_cache::clear(&config.map_config.cache, true) {
+                            config.notification_read = false;
+                            config.notifications.push(types::Notification::Error(format!("Failed to delete the offline packs: {err}")));
+                        }
+                        self.usage_task = Some(tile_cache::usage_promise(&config.map_config.cache));
+                    }
+                });
+            }
+        }
+    }
+}
+impl Default for MapSettingsTab {
+    fn default() -> Self {
+        Self {
+            pack_north: 85.0,
+            pack_south: -85.0,
+            pack_west: -180.0,
+            pack_east: 180.0,
+            pack_grid: String::new(),
+            pack_min_zoom: 0,
+            pack_max_zoom: 5,
+            pack: None,
+            usage_task: None,
+            usage: None
+        }
+    }
+}
+impl std::fmt::Debug for MapSettingsTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapSettingsTab")
+        .field("pack", &self.pack)
+        .field("usage", &self.usage)
+        .finish()
+    }
+}
 impl SettingsTabTrait for MapSettingsTab {
     fn title(&mut self) -> egui::WidgetText {
         "Map".into()
@@ -221,11 +480,95 @@ impl SettingsTabTrait for MapSettingsTab {
                     .password(true)
                     .ui(ui);
 
+                },
+                // A custom provider requires a URL template, and optionally an API key, extra headers, and attribution
+                map::TileProvider::Custom { url, subdomains, api_key, api_key_placement, api_key_name, headers, attribution } => {
+
+                    // A label to describe the URL template option
+                    ui.label("URL template")
+                    .on_hover_text("{z}, {x}, and {y} are replaced with the tile coordinates, {-y} with the flipped Y coordinate used by TMS servers, \
+                    {s} with one of the subdomains, and {key} with the API key. Use a file:// URL to read tiles from a directory.");
+                    // The URL template textbox
+                    egui::widgets::TextEdit::singleline(url)
+                    .hint_text("https://{s}.tile.opentopomap.org/{z}/{x}/{y}.png")
+                    .ui(ui);
+
+                    // A label to describe the subdomains option
+                    ui.label("Subdomains");
+                    // The subdomains textbox
+                    egui::widgets::TextEdit::singleline(subdomains)
+                    .hint_text("The subdomains for {s}, separated by commas (e.g. a,b,c)")
+                    .ui(ui);
+
+                    // A label to describe the API key option
+                    ui.label("API key");
+                    // The API key textbox
+                    egui::widgets::TextEdit::singleline(api_key)
+                    .hint_text("Your API key, if the tile server needs one")
+                    .password(true)
+                    .ui(ui);
+
+                    // The API key placement combobox, and the name of the query parameter or header
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("map_api_key_placement_combobox")
+                        .selected_text(api_key_placement.name())
+                        .show_ui(ui, |ui| {
+                            for placement in map::ApiKeyPlacement::iter() {
+                                ui.selectable_value(api_key_placement, placement, placement.name());
+                            }
+                        });
+
+                        if matches!(api_key_placement, map::ApiKeyPlacement::Query | map::ApiKeyPlacement::Header) {
+                            egui::widgets::TextEdit::singleline(api_key_name)
+                            .hint_text("Parameter or header name")
+                            .ui(ui);
+                        }
+                    });
+
+                    // A label to describe the headers option
+                    ui.label("Extra headers");
+                    // The headers textbox
+                    egui::widgets::TextEdit::multiline(headers)
+                    .hint_text("One 'Name: value' per line")
+                    .desired_rows(2)
+                    .ui(ui);
+
+                    // A label to describe the attribution option
+                    ui.label("Attribution");
+                    // The attribution textbox
+                    egui::widgets::TextEdit::singleline(attribution)
+                    .hint_text("The attribution shown in the corner of the map (e.g. © OpenTopoMap)")
+                    .ui(ui);
+
+                },
+                // MBTiles requires the path of the file
+                map::TileProvider::MBTiles { path, attribution } => {
+
+                    // A label to describe the path option
+                    ui.label("Path");
+                    // The path textbox
+                    egui::widgets::TextEdit::singleline(path)
+                    .hint_text("The path of the .mbtiles file")
+                    .ui(ui);
+
+                    // A label to describe the attribution option
+                    ui.label("Attribution");
+                    // The attribution textbox
+                    egui::widgets::TextEdit::singleline(attribution)
+                    .hint_text("The attribution shown in the corner of the map")
+                    .ui(ui);
+
                 }
             }
 
         });
 
+        // The tile cache
+        ui.group(|ui| self.cache_ui(config, ui));
+
+        // The offline packs
+        ui.group(|ui| self.pack_ui(config, ui));
+
     }
 }
 
@@ -258,3 +601,657 @@ impl SettingsTabTrait for CallsignLookupSettingsTab {
 
     }
 }
+
+/// The WSJT-X settings tab
+#[derive(Debug)]
+struct WsjtxSettingsTab;
+impl SettingsTabTrait for WsjtxSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "WSJT-X".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The WSJT-X listener settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the listener
+            ui.checkbox(&mut config.wsjtx_config.enabled, "Listen for WSJT-X messages")
+            .on_hover_text("JTDX and MSHV use the same protocol, so they are supported too");
+
+            // A label to describe the address option
+            ui.label("UDP address (This should match the UDP Server setting in WSJT-X)");
+            // The address textbox
+            egui::widgets::TextEdit::singleline(&mut config.wsjtx_config.address)
+            .hint_text("127.0.0.1:2237")
+            .ui(ui);
+
+            // A checkbox to enable automatic logging
+            ui.checkbox(&mut config.wsjtx_config.auto_log, "Automatically log QSOs logged in WSJT-X");
+
+        });
+
+    }
+}
+
+/// The JS8Call settings tab
+#[derive(Debug)]
+struct Js8CallSettingsTab;
+impl SettingsTabTrait for Js8CallSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "JS8Call".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The JS8Call API settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the client
+            ui.checkbox(&mut config.js8call_config.enabled, "Connect to JS8Call")
+            .on_hover_text("The TCP API must be enabled in JS8Call (Settings -> Reporting -> API)");
+
+            // A label to describe the address option
+            ui.label("TCP address of the JS8Call API");
+            // The address textbox
+            egui::widgets::TextEdit::singleline(&mut config.js8call_config.address)
+            .hint_text("127.0.0.1:2442")
+            .ui(ui);
+
+            // A checkbox to enable automatic logging
+            ui.checkbox(&mut config.js8call_config.auto_log, "Automatically log QSOs logged in JS8Call");
+
+            // A checkbox to push frequency changes into the contact logger
+            ui.checkbox(&mut config.js8call_config.follow_frequency, "Update the contact logger when the frequency changes");
+
+        });
+
+        // The marker color settings
+        ui.group(|ui| {
+
+            // A label and color picker to set the heard station color
+            ui.label("Heard station color");
+            ui.color_edit_button_srgba_unmultiplied(&mut config.js8call_config.marker_color);
+
+        });
+
+    }
+}
+
+/// The fldigi settings tab
+#[derive(Debug)]
+struct FldigiSettingsTab;
+impl SettingsTabTrait for FldigiSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "fldigi".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The fldigi XML-RPC settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the client
+            ui.checkbox(&mut config.fldigi_config.enabled, "Connect to fldigi");
+
+            // A label to describe the URL option
+            ui.label("URL of the fldigi XML-RPC server");
+            // The URL textbox
+            egui::widgets::TextEdit::singleline(&mut config.fldigi_config.url)
+            .hint_text("http://127.0.0.1:7362/RPC2")
+            .ui(ui);
+
+            // A checkbox to push frequency and mode changes into the contact logger
+            ui.checkbox(&mut config.fldigi_config.follow_radio, "Update the contact logger when the frequency or modem changes");
+
+            // A checkbox to push the log fields into the contact logger
+            ui.checkbox(&mut config.fldigi_config.prefill_fields, "Fill the contact logger with the call, name, QTH, and RST entered in fldigi");
+
+            // Show whether fldigi is reachable
+            match &config.fldigi {
+                Some(client) if client.connected() => { ui.label(format!("Connected - {}", client.state().modem)); },
+                Some(_) => { ui.colored_label(ui.style().visuals.warn_fg_color, "Waiting for fldigi"); },
+                None => {}
+            }
+
+        });
+
+        // The logbook server settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the logbook server
+            ui.checkbox(&mut config.fldigi_config.log_server, "Receive QSOs saved in fldigi")
+            .on_hover_text("fldigi must be configured to use a logbook server (Configure -> Logging -> Logbook -> Connect to server)");
+
+            // A label to describe the address option
+            ui.label("Address of the logbook server");
+            // The address textbox
+            egui::widgets::TextEdit::singleline(&mut config.fldigi_config.log_address)
+            .hint_text("127.0.0.1:8421")
+            .ui(ui);
+
+            // A checkbox to enable automatic logging
+            ui.checkbox(&mut config.fldigi_config.auto_log, "Automatically log QSOs saved in fldigi");
+
+        });
+
+    }
+}
+
+/// The contact broadcast (N1MM Logger+) settings tab
+#[derive(Debug)]
+struct N1mmSettingsTab;
+impl SettingsTabTrait for N1mmSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "Network".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The station settings
+        ui.group(|ui| {
+
+            // A label to describe the station name option
+            ui.label("Station name")
+            .on_hover_text("The name of this logger on the network. Each logger on the network must have a different name.");
+            // The station name textbox
+            egui::widgets::TextEdit::singleline(&mut config.n1mm_config.station_name)
+            .hint_text("QLOG")
+            .ui(ui);
+
+        });
+
+        // The listener settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the listener
+            ui.checkbox(&mut config.n1mm_config.listen, "Receive contacts from other loggers")
+            .on_hover_text("Contacts broadcast by other loggers (e.g. N1MM Logger+) are added to, updated in, or deleted from the log");
+
+            // A label to describe the listen address option
+            ui.label("UDP address to listen on");
+            // The listen address textbox
+            egui::widgets::TextEdit::singleline(&mut config.n1mm_config.listen_address)
+            .hint_text("0.0.0.0:12060")
+            .ui(ui);
+
+        });
+
+        // The sender settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the sender
+            ui.checkbox(&mut config.n1mm_config.send, "Broadcast contacts logged in QLog");
+
+            // A label to describe the send address option
+            ui.label("UDP address to broadcast to");
+            // The send address textbox
+            egui::widgets::TextEdit::singleline(&mut config.n1mm_config.send_address)
+            .hint_text("255.255.255.255:12060")
+            .ui(ui);
+
+        });
+
+    }
+}
+
+/// The station profile settings tab
+#[derive(Debug)]
+struct StationSettingsTab;
+impl SettingsTabTrait for StationSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "Station".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The station profile
+        ui.group(|ui| {
+
+            // A label to describe the callsign option
+            ui.label("Callsign");
+            // The callsign textbox
+            egui::widgets::TextEdit::singleline(&mut config.station.callsign)
+            .hint_text("Your callsign")
+            .ui(ui);
+
+            // A label to describe the name option
+            ui.label("Name");
+            // The name textbox
+            egui::widgets::TextEdit::singleline(&mut config.station.name)
+            .hint_text("Your name")
+            .ui(ui);
+
+            // A label to describe the grid option
+            ui.label("Grid square");
+            // The grid textbox
+            egui::widgets::TextEdit::singleline(&mut config.station.grid)
+            .hint_text("Your grid square")
+            .ui(ui);
+
+        });
+
+    }
+}
+
+/// The rig control settings tab
+#[derive(Debug)]
+struct RigSettingsTab;
+impl SettingsTabTrait for RigSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "Rig Control".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The rigctld settings
+        ui.group(|ui| {
+
+            // A checkbox to enable rig control
+            ui.checkbox(&mut config.rig_config.enabled, "Tune the radio when a spot is clicked")
+            .on_hover_text("Requires hamlib's rigctld to be running (e.g. rigctld -m <model> -r <port>)");
+
+            // A label to describe the address option
+            ui.label("TCP address of rigctld");
+            // The address textbox
+            egui::widgets::TextEdit::singleline(&mut config.rig_config.address)
+            .hint_text("127.0.0.1:4532")
+            .ui(ui);
+
+        });
+
+    }
+}
+
+/// The CW keyer settings tab
+#[derive(Debug)]
+struct CwSettingsTab;
+impl SettingsTabTrait for CwSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "CW Keyer".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The keyer output settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the keyer
+            ui.checkbox(&mut config.cw_config.enabled, "Enable the CW keyer")
+            .on_hover_text("Macros are shown in the contact logger, and can be sent with the F-keys");
+
+            // A label to describe the output option
+            ui.label("Output");
+            // A combobox to select the output
+            egui::ComboBox::from_id_source("cw_output_combobox")
+            .selected_text(config.cw_config.output.to_string())
+            .show_ui(ui, |ui| {
+                for output in cw::Output::iter() {
+                    let text = output.to_string();
+                    ui.selectable_value(&mut config.cw_config.output, output, text);
+                }
+            });
+
+            // Show the options for the selected output
+            match config.cw_config.output {
+                cw::Output::Rigctld => {
+                    ui.label(format!("Sending through rigctld at '{}' (see the Rig Control settings)", config.rig_config.address));
+                },
+                cw::Output::WinKeyer => {
+                    // A label to describe the serial port option
+                    ui.label("Serial port of the WinKeyer");
+                    // The serial port textbox
+                    egui::widgets::TextEdit::singleline(&mut config.cw_config.winkeyer_port)
+                    .hint_text("/dev/ttyUSB0 or COM3")
+                    .ui(ui);
+                },
+                cw::Output::Test => {
+                    ui.label("The keyed text is shown below with its timing instead of being sent to a radio");
+                }
+            }
+
+            // A slider to set the speed
+            ui.label("Speed");
+            egui::Slider::new(&mut config.cw_config.speed, cw::Config::MIN_SPEED..=cw::Config::MAX_SPEED)
+            .suffix(" WPM")
+            .ui(ui);
+
+        });
+
+        // The serial number settings
+        ui.group(|ui| {
+
+            // A label and drag value to set the next serial number
+            ui.label("Next serial number ({NR})");
+            egui::DragValue::new(&mut config.cw_config.serial)
+            .clamp_range(1..=9999)
+            .ui(ui);
+
+            // A checkbox to enable cut numbers
+            ui.checkbox(&mut config.cw_config.cut_numbers, "Send cut numbers (T for 0, N for 9)");
+
+        });
+
+        // The macros
+        ui.group(|ui| {
+
+            ui.label("Macros")
+            .on_hover_text("Variables: {MYCALL}, {MYNAME}, {MYGRID}, {CALL}, {GRID}, {RST}, {NR}");
+
+            let mut remove = None;
+            egui::Grid::new("cw_macros_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                for (idx, m) in config.cw_config.macros.iter_mut().enumerate() {
+                    ui.label(format!("F{}", idx + 1));
+                    egui::widgets::TextEdit::singleline(&mut m.label)
+                    .hint_text("Label")
+                    .desired_width(80.0)
+                    .ui(ui);
+                    egui::widgets::TextEdit::singleline(&mut m.text)
+                    .hint_text("Text")
+                    .desired_width(240.0)
+                    .ui(ui);
+                    if ui.button("\u{1F5D1}").on_hover_text("Remove this macro").clicked() {
+                        remove = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some(idx) = remove {
+                config.cw_config.macros.remove(idx);
+            }
+
+            // There are only 12 F-keys
+            if ui.add_enabled(config.cw_config.macros.len() < 12, egui::Button::new("Add macro")).clicked() {
+                config.cw_config.macros.push(cw::Macro { label: String::new(), text: String::new() });
+            }
+
+        });
+
+        // The keyer log
+        if let Some(keyer) = &config.cw {
+            ui.group(|ui| {
+                ui.label("Keyer log");
+                egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in keyer.log() {
+                        ui.monospace(line);
+                    }
+                });
+            });
+        }
+
+    }
+}
+
+/// The DX cluster settings tab
+#[derive(Debug)]
+struct DxClusterSettingsTab;
+impl SettingsTabTrait for DxClusterSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "DX Cluster".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The DX cluster settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the client
+            ui.checkbox(&mut config.dxcluster_config.enabled, "Connect to the DX cluster")
+            .on_hover_text("The callsign in the station settings is used to log in");
+
+            // A label to describe the address option
+            ui.label("Address of the DX cluster node");
+            // The address textbox
+            egui::widgets::TextEdit::singleline(&mut config.dxcluster_config.address)
+            .hint_text("dxc.ve7cc.net:23")
+            .ui(ui);
+
+        });
+
+    }
+}
+
+/// The Reverse Beacon Network settings tab
+#[derive(Debug)]
+struct RbnSettingsTab;
+impl SettingsTabTrait for RbnSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "RBN".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The RBN settings
+        ui.group(|ui| {
+
+            // A checkbox to enable the client
+            ui.checkbox(&mut config.rbn_config.enabled, "Connect to the Reverse Beacon Network")
+            .on_hover_text("The callsign in the station settings is used to log in");
+
+            // A label to describe the address option
+            ui.label("Address of the RBN telnet server");
+            // The address textbox
+            egui::widgets::TextEdit::singleline(&mut config.rbn_config.address)
+            .hint_text("telnet.reversebeacon.net:7000")
+            .ui(ui)
+            .on_hover_text("Port 7000 carries CW and RTTY spots, and port 7001 carries FT8 and FT4 spots");
+
+        });
+
+    }
+}
+
+/// The alerts settings tab
+#[derive(Debug)]
+struct AlertsSettingsTab;
+impl SettingsTabTrait for AlertsSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "Alerts".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The general alert settings
+        ui.group(|ui| {
+
+            // A checkbox to enable alerts
+            ui.checkbox(&mut config.alerts_config.enabled, "Check spots against the log");
+
+            // The cooldown setting
+            ui.horizontal(|ui| {
+                ui.label("Minutes before alerting for the same station on the same band again");
+                egui::widgets::DragValue::new(&mut config.alerts_config.cooldown)
+                .clamp_range(1..=1440)
+                .ui(ui);
+            });
+
+            // A label to describe the sound option
+            ui.label("Sound file to play (a beep is played if this is empty)");
+            // The sound path textbox
+            egui::widgets::TextEdit::singleline(&mut config.alerts_config.sound_path)
+            .hint_text("alert.wav")
+            .ui(ui);
+
+        });
+
+        // The country file settings
+        ui.group(|ui| {
+
+            // Show the status of the country file
+            match config.alerts.dxcc() {
+                _ if config.alerts.dxcc_loading() => { ui.label("Loading the country file..."); },
+                Some(dxcc) => { ui.label(format!("Loaded {} DXCC entities", dxcc.entity_count())); },
+                None => { ui.colored_label(ui.style().visuals.warn_fg_color, "No country file is loaded, so DXCC and band slot alerts are disabled"); }
+            }
+
+            // A label to describe the path option
+            ui.label("Path of the country file (cty.dat)");
+            // The path textbox, which uses the file next to the exe by default
+            egui::widgets::TextEdit::singleline(&mut config.alerts_config.cty_path)
+            .hint_text(dxcc::default_path().display().to_string())
+            .ui(ui);
+
+            // A label to describe the URL option
+            ui.label("URL to download the country file from");
+            // The URL textbox
+            egui::widgets::TextEdit::singleline(&mut config.alerts_config.cty_url)
+            .hint_text(dxcc::CTY_URL)
+            .ui(ui);
+
+            // Buttons to reload or download the country file
+            ui.add_enabled_ui(!config.alerts.dxcc_loading(), |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Reload").clicked() {
+                        config.alerts.load_dxcc(config.alerts_config.cty_path());
+                    }
+                    if ui.button("Download").clicked() {
+                        config.alerts.download_dxcc(config.alerts_config.cty_url.trim().to_string(), config.alerts_config.cty_path());
+                    }
+                });
+            });
+
+        });
+
+        // The rules
+        ui.group(|ui| {
+
+            ui.label("Rules")
+            .on_hover_text("Rules are checked from top to bottom, and the first matching rule decides how a spot is highlighted");
+
+            let mut remove = None;
+            let mut move_up = None;
+            egui::Grid::new("alert_rules_grid")
+            .num_columns(12)
+            .striped(true)
+            .show(ui, |ui| {
+                for (idx, rule) in config.alerts_config.rules.iter_mut().enumerate() {
+                    ui.checkbox(&mut rule.enabled, "");
+
+                    // The type of alert
+                    egui::ComboBox::from_id_source(("alert_kind", idx))
+                    .selected_text(rule.kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in alerts::AlertKind::iter() {
+                            ui.selectable_value(&mut rule.kind, kind, kind.to_string());
+                        }
+                    });
+
+                    // The band filter
+                    egui::ComboBox::from_id_source(("alert_band", idx))
+                    .selected_text(rule.band.as_str())
+                    .show_ui(ui, |ui| {
+                        for band in types::Band::iter() {
+                            ui.selectable_value(&mut rule.band, band, band.as_str());
+                        }
+                    });
+
+                    // The sources
+                    ui.checkbox(&mut rule.cluster, "Cluster");
+                    ui.checkbox(&mut rule.rbn, "RBN");
+                    ui.checkbox(&mut rule.pskreporter, "PSKReporter");
+                    ui.checkbox(&mut rule.wsjtx, "WSJT-X");
+
+                    // The actions
+                    ui.checkbox(&mut rule.notify, "Notify");
+                    ui.checkbox(&mut rule.sound, "Sound");
+                    ui.color_edit_button_srgba_unmultiplied(&mut rule.color);
+
+                    if ui.add_enabled(idx > 0, egui::Button::new("\u{2B06}")).on_hover_text("Move this rule up").clicked() {
+                        move_up = Some(idx);
+                    }
+                    if ui.button("\u{1F5D1}").on_hover_text("Remove this rule").clicked() {
+                        remove = Some(idx);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if let Some(idx) = move_up {
+                config.alerts_config.rules.swap(idx, idx - 1);
+            }
+            if let Some(idx) = remove {
+                config.alerts_config.rules.remove(idx);
+            }
+
+            if ui.button("Add rule").clicked() {
+                config.alerts_config.rules.push(Default::default());
+            }
+
+        });
+
+    }
+}
+
+/// The solar conditions settings tab
+#[derive(Debug)]
+struct SolarSettingsTab;
+impl SettingsTabTrait for SolarSettingsTab {
+    fn title(&mut self) -> egui::WidgetText {
+        "Solar".into()
+    }
+
+    fn ui(&mut self, config: &mut crate::GuiConfig, ui: &mut egui::Ui) {
+
+        // The fetching settings
+        ui.group(|ui| {
+
+            // A checkbox to enable fetching
+            ui.checkbox(&mut config.solar_config.enabled, "Fetch solar and space weather information");
+
+            // The refresh rate setting
+            ui.horizontal(|ui| {
+                ui.label("Minutes between refreshes");
+                egui::widgets::DragValue::new(&mut config.solar_config.refresh_rate)
+                .clamp_range(solar::Config::MIN_REFRESH_RATE..=1440)
+                .ui(ui);
+            });
+
+            // A label to describe the hamqsl option
+            ui.label("URL of the hamqsl solar XML feed");
+            // The hamqsl URL textbox
+            egui::widgets::TextEdit::singleline(&mut config.solar_config.hamqsl_url)
+            .hint_text("https://www.hamqsl.com/solarxml.php")
+            .ui(ui);
+
+            // A label to describe the SWPC option
+            ui.label("Base URL of the NOAA SWPC services");
+            // The SWPC URL textbox
+            egui::widgets::TextEdit::singleline(&mut config.solar_config.swpc_url)
+            .hint_text("https://services.swpc.noaa.gov")
+            .ui(ui);
+
+        });
+
+        // The notification settings
+        ui.group(|ui| {
+
+            // A checkbox to enable notifications
+            ui.checkbox(&mut config.solar_config.notify, "Notify for geomagnetic storms and radio blackouts");
+
+            // The storm threshold setting
+            ui.horizontal(|ui| {
+                ui.label("Minimum geomagnetic storm scale (G)");
+                egui::widgets::DragValue::new(&mut config.solar_config.storm_threshold)
+                .clamp_range(1..=5)
+                .ui(ui);
+            });
+
+            // The blackout threshold setting
+            ui.horizontal(|ui| {
+                ui.label("Minimum radio blackout scale (R)");
+                egui::widgets::DragValue::new(&mut config.solar_config.blackout_threshold)
+                .clamp_range(1..=5)
+                .ui(ui);
+            });
+
+        });
+
+    }
+}
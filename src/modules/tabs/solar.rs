@@ -0,0 +1,303 @@
+//
+// Contains the code for the solar conditions tab
+//
+
+use chrono::{DateTime, Utc};
+use egui::{Color32, Id, RichText, Ui, WidgetText};
+use egui_plot::{Bar, BarChart, GridMark, HLine, Line, Plot, PlotPoint, PlotPoints};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use crate::modules::{gui::{generate_random_id, Tab}, solar};
+use crate::GuiConfig;
+
+
+/// A tab that shows solar and space weather information, with charts of the last day or week
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SolarConditionsTab {
+    /// The egui ID
+    id: Id,
+    /// How much history the charts should show
+    range: ChartRange
+}
+impl SolarConditionsTab {
+    /// The height of each chart
+    const CHART_HEIGHT: f32 = 140.0;
+    /// The color used for good conditions
+    const GOOD_COLOR: Color32 = Color32::from_rgb(80, 200, 120);
+    /// The color used for fair conditions
+    const FAIR_COLOR: Color32 = Color32::from_rgb(230, 200, 60);
+    /// The color used for poor conditions
+    const POOR_COLOR: Color32 = Color32::from_rgb(230, 80, 80);
+
+    /// Shows the current values
+    fn current_ui(&self, conditions: &solar::Conditions, ui: &mut Ui) {
+        let summary = conditions.summary.clone().unwrap_or_default();
+        let number = |value: Option<f64>, precision: usize| value.map_or("-".to_string(), |v| format!("{v:.precision$}"));
+
+        // Fall back to the SWPC values if the hamqsl feed is unavailable
+        let kp = conditions.kp.last().map(|s| s.value);
+        let wind = summary.solar_wind.or(conditions.wind_speed.last().map(|s| s.value));
+        let bz = summary.bz.or(conditions.bz.last().map(|s| s.value));
+        let xray = match summary.xray.is_empty() {
+            true => conditions.xray.last().map_or("-".to_string(), |s| xray_class(s.value)),
+            false => summary.xray.clone()
+        };
+
+        egui::Grid::new(self.id.with("current"))
+        .num_columns(6)
+        .spacing([20.0, 4.0])
+        .show(ui, |ui| {
+            ui.label("Solar flux");
+            ui.strong(number(summary.sfi, 0));
+            ui.label("Sunspots");
+            ui.strong(number(summary.ssn, 0));
+            ui.label("X-ray");
+            ui.strong(xray);
+            ui.end_row();
+
+            ui.label("A index");
+            ui.strong(number(summary.a_index, 0));
+            ui.label("K index");
+            ui.strong(number(summary.k_index.or(kp), 1));
+            ui.label("Geomagnetic field");
+            ui.strong(if summary.geomagnetic_field.is_empty() { "-" } else { summary.geomagnetic_field.as_str() });
+            ui.end_row();
+
+            ui.label("Solar wind");
+            ui.strong(format!("{} km/s", number(wind, 0)));
+            ui.label("Bz");
+            ui.strong(format!("{} nT", number(bz, 1)));
+            ui.label("Noise");
+            ui.strong(if summary.signal_noise.is_empty() { "-" } else { summary.signal_noise.as_str() });
+            ui.end_row();
+        });
+
+        // The NOAA scales
+        if let Some(scales) = conditions.scales {
+            ui.horizontal(|ui| {
+                ui.label("NOAA scales:");
+                for (letter, level, name) in [("R", scales.r, "Radio blackouts"), ("S", scales.s, "Solar radiation storms"), ("G", scales.g, "Geomagnetic storms")] {
+                    let color = match level {
+                        0 => Self::GOOD_COLOR,
+                        1..=2 => Self::FAIR_COLOR,
+                        _ => Self::POOR_COLOR
+                    };
+                    ui.label(RichText::new(format!("{letter}{level}")).color(color).strong())
+                    .on_hover_text(format!("{name}: {}", solar::scale_name(level)));
+                }
+            });
+        }
+    }
+
+    /// Shows the estimated band conditions
+    fn bands_ui(&self, summary: &solar::Summary, ui: &mut Ui) {
+        let color = |condition: &str| match condition {
+            "Good" => Self::GOOD_COLOR,
+            "Fair" => Self::FAIR_COLOR,
+            _ => Self::POOR_COLOR
+        };
+
+        egui::Grid::new(self.id.with("bands"))
+        .num_columns(3)
+        .striped(true)
+        .spacing([20.0, 4.0])
+        .show(ui, |ui| {
+            ui.strong("Bands");
+            ui.strong("Day");
+            ui.strong("Night");
+            ui.end_row();
+
+            for band in &summary.bands {
+                ui.label(&band.bands);
+                ui.label(RichText::new(&band.day).color(color(&band.day)));
+                ui.label(RichText::new(&band.night).color(color(&band.night)));
+                ui.end_row();
+            }
+        });
+    }
+
+    /// Creates a chart that shares its time axis with the other charts
+    fn chart(&self, name: &str, start: i64, end: i64) -> Plot {
+        let format = self.range.time_format();
+        Plot::new(self.id.with(name))
+        .height(Self::CHART_HEIGHT)
+        .link_axis(self.id.with("charts"), true, false)
+        .include_x(start as f64)
+        .include_x(end as f64)
+        .allow_scroll(false)
+        .x_axis_formatter(move |mark: GridMark, _chars, _range| format_time(mark.value, format))
+        .label_formatter(move |_name, point: &PlotPoint| format!("{}\n{:.2}", format_time(point.x, "%d/%m %H:%M"), point.y))
+    }
+
+    /// Shows the charts of the planetary K index, X-ray flux, and solar wind
+    fn charts_ui(&self, conditions: &solar::Conditions, end: i64, ui: &mut Ui) {
+        let start = end - self.range.seconds();
+        // Values that can't be plotted (e.g. the log of a missing X-ray flux) are skipped
+        let points = |samples: &[solar::Sample], map: fn(f64) -> f64| -> PlotPoints {
+            samples.iter().filter(|s| s.time >= start).map(|s| [s.time as f64, map(s.value)]).filter(|[_, y]| y.is_finite()).collect()
+        };
+
+        // The K index is measured every 3 hours, so it's shown as a bar chart
+        ui.label("Planetary K index");
+        let bars: Vec<Bar> = conditions.kp.iter().filter(|s| s.time >= start).map(|s| {
+            let color = match s.value {
+                kp if kp >= 5.0 => Self::POOR_COLOR,
+                kp if kp >= 4.0 => Self::FAIR_COLOR,
+                _ => Self::GOOD_COLOR
+            };
+            // The time is the start of the 3 hour period
+            Bar::new(s.time as f64 + 5400.0, s.value).width(10800.0 * 0.9).fill(color)
+        }).collect();
+        self.chart("kp", start, end)
+        .include_y(0.0)
+        .include_y(9.0)
+        .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new(bars).name("Kp")));
+
+        // The X-ray flux spans several orders of magnitude, so it's shown on a log scale with the flare classes
+        ui.label("X-ray flux (0.1-0.8nm)");
+        self.chart("xray", start, end)
+        .include_y(-8.0)
+        .include_y(-4.0)
+        .y_axis_formatter(|mark: GridMark, _chars, _range| match mark.value.round() as i64 {
+            -8 => "A",
+            -7 => "B",
+            -6 => "C",
+            -5 => "M",
+            -4 => "X",
+            _ => ""
+        }.to_string())
+        .show(ui, |plot_ui| plot_ui.line(Line::new(points(&conditions.xray, f64::log10)).name("log10(W/m²)")));
+
+        ui.label("Solar wind speed (km/s)");
+        self.chart("wind", start, end)
+        .show(ui, |plot_ui| plot_ui.line(Line::new(points(&conditions.wind_speed, |v| v)).name("km/s")));
+
+        // A southward (negative) Bz lets the solar wind into the magnetosphere, so zero is marked
+        ui.label("Bz (nT)");
+        self.chart("bz", start, end)
+        .show(ui, |plot_ui| {
+            plot_ui.hline(HLine::new(0.0).color(Color32::GRAY));
+            plot_ui.line(Line::new(points(&conditions.bz, |v| v)).name("nT"));
+        });
+    }
+}
+impl Tab for SolarConditionsTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "Solar Conditions".into()
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+
+        // Show the status and controls
+        ui.horizontal(|ui| {
+            let monitor = &mut config.solar;
+
+            match monitor.conditions().and_then(|c| c.fetched) {
+                Some(fetched) if monitor.cached() => { ui.colored_label(ui.style().visuals.warn_fg_color, format!("Cached from {}", fetched.format("%d/%m/%Y %H:%M UTC"))); },
+                Some(fetched) => { ui.label(format!("Updated {}", fetched.format("%H:%M UTC"))); },
+                None => {}
+            }
+
+            if !config.solar_config.enabled {
+                ui.label("Fetching is disabled, enable it in the settings tab");
+            } else if monitor.fetching() {
+                ui.spinner();
+            } else if ui.button("Refresh").clicked() {
+                monitor.refresh(&config.solar_config);
+            }
+
+            egui::ComboBox::from_id_source(self.id.with("range"))
+            .selected_text(self.range.to_string())
+            .show_ui(ui, |ui| {
+                for range in ChartRange::iter() {
+                    ui.selectable_value(&mut self.range, range, range.to_string());
+                }
+            });
+        });
+
+        let Some(conditions) = config.solar.conditions() else {
+            ui.label("No solar information has been fetched yet");
+            return;
+        };
+
+        ui.separator();
+        self.current_ui(conditions, ui);
+
+        if let Some(summary) = conditions.summary.as_ref().filter(|s| !s.bands.is_empty()) {
+            ui.separator();
+            self.bands_ui(summary, ui);
+        }
+
+        ui.separator();
+        let end = conditions.fetched.map_or_else(|| Utc::now().timestamp(), |t| t.timestamp());
+        self.charts_ui(conditions, end, ui);
+
+    }
+}
+impl Default for SolarConditionsTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            range: Default::default()
+        }
+    }
+}
+impl std::fmt::Debug for SolarConditionsTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SolarConditionsTab")
+        .field("id", &self.id)
+        .field("range", &self.range)
+        .finish()
+    }
+}
+
+
+/// How much history the charts should show
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, Display)]
+enum ChartRange {
+    #[default]
+    #[strum(to_string = "24 hours")]
+    Day,
+    #[strum(to_string = "7 days")]
+    Week
+}
+impl ChartRange {
+    /// The length of the range, in seconds
+    fn seconds(&self) -> i64 {
+        match self {
+            Self::Day => 86_400,
+            Self::Week => 604_800
+        }
+    }
+
+    /// The format of the labels on the time axis
+    fn time_format(&self) -> &'static str {
+        match self {
+            Self::Day => "%H:%M",
+            Self::Week => "%d/%m"
+        }
+    }
+}
+
+/// Formats a chart time, in seconds since the UNIX epoch
+fn format_time(time: f64, format: &str) -> String {
+    DateTime::from_timestamp(time as i64, 0).map_or(String::new(), |t| t.format(format).to_string())
+}
+
+/// Converts an X-ray flux in W/m² into a flare class (e.g. `1.2e-6` -> `C1.2`)
+fn xray_class(flux: f64) -> String {
+    let (class, base) = match flux {
+        f if f >= 1e-4 => ("X", 1e-4),
+        f if f >= 1e-5 => ("M", 1e-5),
+        f if f >= 1e-6 => ("C", 1e-6),
+        f if f >= 1e-7 => ("B", 1e-7),
+        _ => ("A", 1e-8)
+    };
+    format!("{class}{:.1}", flux / base)
+}
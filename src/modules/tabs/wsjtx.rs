@@ -0,0 +1,238 @@
+//
+// Contains the code for the WSJT-X decodes tab
+//
+
+use std::collections::{HashSet, VecDeque};
+use anyhow::Result;
+use egui::{Align, Color32, Id, Layout, RichText, Ui, Widget, WidgetText};
+use log::error;
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use crate::modules::{alerts, gui::{self, generate_random_id, Tab}, wsjtx};
+use crate::{types, GuiConfig};
+
+
+/// A tab that shows the messages decoded by WSJT-X, highlighting stations that haven't been worked before
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct WsjtxTab {
+    /// The egui ID
+    id: Id,
+    /// The most recent decodes, oldest first, and the alert each decode matched
+    #[serde(skip)]
+    decodes: VecDeque<(wsjtx::Decode, Option<alerts::Alert>)>,
+    /// The generation of the alert engine that the alerts were checked with
+    #[serde(skip)]
+    alerts_generation: u64,
+    /// The most recent status message
+    #[serde(skip)]
+    status: Option<wsjtx::Status>,
+    /// Every callsign that exists in the contacts table
+    #[serde(skip)]
+    worked_callsigns: HashSet<String>,
+    /// The task that is currently querying the database for worked callsigns
+    #[serde(skip)]
+    worked_task: Option<Promise<Result<HashSet<String>>>>,
+    /// Should we query the worked callsigns again? The contacts can change while a query is running.
+    #[serde(skip)]
+    should_query_worked: bool,
+    /// Should we only show CQ messages?
+    only_cq: bool
+}
+impl WsjtxTab {
+    /// The maximum number of decodes to keep
+    const MAX_DECODES: usize = 500;
+    /// The color used to highlight stations that haven't been worked before
+    const NEW_COLOR: Color32 = Color32::from_rgb(80, 200, 120);
+
+    /// Checks the station that sent a decode for alerts
+    fn alert(config: &GuiConfig, status: Option<&wsjtx::Status>, decode: &wsjtx::Decode) -> Option<alerts::Alert> {
+        let callsign = decode.de_callsign()?;
+        let status = status.filter(|s| s.id == decode.id);
+        config.alerts.alert(&config.alerts_config, &alerts::Candidate {
+            source: alerts::Source::Wsjtx,
+            callsign,
+            frequency: status.map(|s| s.dial_frequency + decode.delta_frequency as u64),
+            mode: status.map(|s| types::Mode::from_name(&s.mode)),
+            grid: decode.grid()
+        })
+    }
+
+    /// Checks the decodes for alerts again if the log or the alert rules changed since they were checked
+    fn refresh_alerts(&mut self, config: &GuiConfig) {
+        if self.alerts_generation == config.alerts.generation() {
+            return;
+        }
+        for (decode, alert) in &mut self.decodes {
+            *alert = Self::alert(config, self.status.as_ref(), decode);
+        }
+        self.alerts_generation = config.alerts.generation();
+    }
+}
+impl Tab for WsjtxTab {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn title(&mut self) -> WidgetText {
+        "WSJT-X".into()
+    }
+
+    fn scroll_bars(&self) -> [bool; 2] {
+        [true, false]
+    }
+
+    fn init(&mut self, config: &mut GuiConfig) {
+        self.worked_task = Some(config.db_api.get_worked_callsigns_promise());
+    }
+
+    fn process_event(&mut self, config: &mut GuiConfig, event: &types::Event) {
+        match event {
+            types::Event::WsjtxDecode(decode) => {
+                let alert = Self::alert(config, self.status.as_ref(), decode);
+                self.decodes.push_back((decode.clone(), alert));
+                while self.decodes.len() > Self::MAX_DECODES {
+                    self.decodes.pop_front();
+                }
+            },
+            types::Event::WsjtxStatus(status) => self.status = Some(status.clone()),
+            // The contacts changed, so the worked callsigns may have changed too
+            types::Event::RefreshContacts => self.should_query_worked = true,
+            _ => {}
+        }
+    }
+
+    fn ui(&mut self, config: &mut GuiConfig, ui: &mut Ui) {
+        use egui_extras::Column;
+
+        // Process the worked callsigns task
+        if let Some(task) = self.worked_task.take_if(|t| t.ready().is_some()) {
+            match task.block_and_take() {
+                Ok(callsigns) => self.worked_callsigns = callsigns,
+                Err(err) => error!("Failed to get worked callsigns: {err}")
+            }
+        }
+
+        // Query the worked callsigns again if the contacts changed, once the previous query finished
+        if self.should_query_worked && self.worked_task.is_none() {
+            self.should_query_worked = false;
+            self.worked_task = Some(config.db_api.get_worked_callsigns_promise());
+        }
+
+        // Show the status of WSJT-X and the filter options
+        ui.horizontal(|ui| {
+
+            match &self.status {
+                Some(status) => {
+                    ui.label(format!("{} - {} {}", status.id, gui::frequency_formatter(status.dial_frequency as f64, 0..=0), status.mode));
+                    if status.transmitting {
+                        ui.colored_label(ui.style().visuals.warn_fg_color, "TX");
+                    }
+                },
+                None if !config.wsjtx_config.enabled => { ui.label("The WSJT-X listener is disabled, enable it in the settings tab"); },
+                None => { ui.label(format!("Waiting for WSJT-X on {}", config.wsjtx_config.address)); }
+            }
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                if ui.button("Clear").clicked() {
+                    self.decodes.clear();
+                }
+                ui.checkbox(&mut self.only_cq, "Only CQ");
+            });
+
+        });
+
+        ui.separator();
+
+        // Get the decodes that should be shown, newest first
+        self.refresh_alerts(config);
+        let decodes: Vec<&(wsjtx::Decode, Option<alerts::Alert>)> = self.decodes.iter().rev()
+            .filter(|(d, _)| !self.only_cq || d.is_cq())
+            .collect();
+
+        egui_extras::TableBuilder::new(ui)
+        .column(Column::initial(60.0).at_least(60.0)) // Time
+        .column(Column::initial(35.0).at_least(35.0)) // SNR
+        .column(Column::initial(35.0).at_least(35.0)) // DT
+        .column(Column::initial(45.0).at_least(45.0)) // Frequency
+        .column(Column::remainder().at_least(100.0).clip(true)) // Message
+        .cell_layout(Layout::left_to_right(Align::Center))
+        .striped(true)
+        .sense(egui::Sense::click())
+        .header(20.0, |mut header| {
+            for name in ["UTC", "dB", "DT", "Freq", "Message"] {
+                header.col(|ui| {
+                    ui.strong(name);
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(18.0, decodes.len(), |mut row| {
+                let (decode, alert) = decodes[row.index()];
+                let callsign = decode.de_callsign();
+
+                // Is the station new (i.e. not in the log)?
+                let new = callsign.is_some_and(|c| !self.worked_callsigns.contains(&c.to_ascii_uppercase()));
+
+                // Highlight the stations that match an alert rule
+                let highlight = |ui: &mut Ui| if let Some(alert) = alert { alerts::highlight(ui, alert) };
+
+                row.col(|ui| { highlight(ui); ui.label(decode.time.format("%H:%M:%S").to_string()); });
+                row.col(|ui| { highlight(ui); ui.label(decode.snr.to_string()); });
+                row.col(|ui| { highlight(ui); ui.label(format!("{:.1}", decode.delta_time)); });
+                row.col(|ui| { highlight(ui); ui.label(decode.delta_frequency.to_string()); });
+                row.col(|ui| {
+                    highlight(ui);
+                    // Highlight new stations, and dim stations that have been worked before
+                    let text = match (callsign, new) {
+                        (Some(_), true) => RichText::new(&decode.message).color(Self::NEW_COLOR).strong(),
+                        (Some(_), false) => RichText::new(&decode.message).weak(),
+                        _ => RichText::new(&decode.message)
+                    };
+                    egui::Label::new(text).selectable(false).ui(ui);
+                });
+
+                // Show a context menu to look up the station
+                if let Some(callsign) = callsign {
+                    let hover_text = match (alert, new) {
+                        (Some(alert), _) => alert.kind.to_string(),
+                        (None, true) => "New station".into(),
+                        (None, false) => "Worked before".into()
+                    };
+                    let response = row.response().on_hover_text(hover_text);
+                    response.context_menu(|ui| {
+                        if ui.button("Lookup callsign").on_hover_text("You must have a callsign lookup tab open to see the result").clicked() {
+                            config.events.push_back((None, types::Event::LookupCallsign(callsign.to_string())));
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+        });
+
+    }
+}
+impl Default for WsjtxTab {
+    fn default() -> Self {
+        Self {
+            id: generate_random_id(),
+            decodes: Default::default(),
+            alerts_generation: Default::default(),
+            status: Default::default(),
+            worked_callsigns: Default::default(),
+            worked_task: Default::default(),
+            should_query_worked: Default::default(),
+            only_cq: Default::default()
+        }
+    }
+}
+impl std::fmt::Debug for WsjtxTab {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsjtxTab")
+        .field("id", &self.id)
+        .field("decodes", &self.decodes.len())
+        .field("status", &self.status)
+        .field("only_cq", &self.only_cq)
+        .finish()
+    }
+}
@@ -0,0 +1,686 @@
+//
+// An on-disk cache of map tiles, so the map keeps working without an internet connection (e.g. during portable operation).
+//
+// Tiles are stored in a directory tree (`<provider>/<zoom>/<x>/<y>.<png|jpg|webp>`), with a small JSON file next to each tile that records
+// when it expires (from the `Cache-Control` and `Expires` headers) and its `ETag`/`Last-Modified` validators.
+// Expired tiles are revalidated with the tile provider, and are used as-is when the tile provider can't be reached.
+//
+// Offline packs are prefetched areas that are stored in their own directory tree. They don't count towards the size limit of the cache,
+// so they're never removed to make room for other tiles.
+//
+
+use std::{env::current_exe, fs, io, ops::RangeInclusive, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst}, Arc, Mutex}, time::{Duration, Instant, SystemTime}};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use geo::Coord;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use poll_promise::Promise;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::RT;
+use super::map::{TileId, TileProvider};
+
+/// The name of the directory that contains the cache, which is next to the exe file
+const TILES_DIR: &str = "tiles";
+/// The name of the directory that contains the cached tiles
+const CACHE_DIR: &str = "cache";
+/// The name of the directory that contains the offline packs
+const PACKS_DIR: &str = "packs";
+/// How long a tile is fresh for if the tile provider doesn't say, in seconds
+const DEFAULT_LIFETIME: i64 = 7 * 24 * 60 * 60;
+/// How often the cache is checked against its size limit
+const PRUNE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// The file extensions of the supported tile formats
+const TILE_EXTENSIONS: [&str; 3] = ["png", "jpg", "webp"];
+
+lazy_static! {
+    /// The last time the cache was checked against its size limit. Every map widget shares the cache, so this is shared too.
+    static ref LAST_PRUNE: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+
+/// The configuration of the tile cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should downloaded tiles be saved to disk?
+    pub enabled: bool,
+    /// Should only cached tiles and offline packs be used, without contacting the tile provider?
+    pub offline: bool,
+    /// The maximum size of the cache, in megabytes. Offline packs don't count towards this.
+    pub size_limit: u64,
+    /// The directory of the cache. The `tiles` directory next to the exe is used if this is empty.
+    pub path: String
+}
+impl Config {
+    /// The directory of the cache
+    pub fn path(&self) -> PathBuf {
+        match self.path.trim() {
+            "" => {
+                let exe_path = current_exe().expect("Failed to get path of exe file");
+                let exe_dir = exe_path.parent().expect("Failed to get parent directory of exe file");
+                exe_dir.join(TILES_DIR)
+            },
+            path => PathBuf::from(path)
+        }
+    }
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            offline: false,
+            size_limit: 500,
+            path: String::new()
+        }
+    }
+}
+
+
+/// The cache information of a tile, which is stored next to the tile
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Metadata {
+    /// When the tile expires, as a UNIX timestamp
+    expires: i64,
+    /// The `ETag` header of the tile, used to revalidate it
+    etag: Option<String>,
+    /// The `Last-Modified` header of the tile, used to revalidate it
+    last_modified: Option<String>
+}
+impl Metadata {
+    /// Reads the cache information from the headers of a tile response. Returns None if the tile shouldn't be stored.
+    fn from_headers(headers: &header::HeaderMap) -> Option<Self> {
+        let now = Utc::now().timestamp();
+        let get = |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.trim().to_string());
+
+        // Prefer the max age, then the expiry date, then the default lifetime
+        let cache_control = get(header::CACHE_CONTROL).unwrap_or_default().to_ascii_lowercase();
+        let mut lifetime = None;
+        for directive in cache_control.split(',').map(str::trim) {
+            match directive.split_once('=') {
+                _ if directive == "no-store" => return None,
+                _ if directive == "no-cache" => lifetime = Some(0),
+                Some(("max-age", age)) => lifetime = lifetime.or(age.trim_matches('"').parse::<i64>().ok()),
+                _ => {}
+            }
+        }
+        let expires = match lifetime {
+            Some(lifetime) => now + lifetime,
+            None => get(header::EXPIRES)
+                .and_then(|e| DateTime::parse_from_rfc2822(&e).ok())
+                .map_or(now + DEFAULT_LIFETIME, |e| e.timestamp())
+        };
+
+        Some(Self {
+            expires,
+            etag: get(header::ETAG),
+            last_modified: get(header::LAST_MODIFIED)
+        })
+    }
+
+    /// Is the tile still fresh, so it doesn't need to be revalidated?
+    fn is_fresh(&self) -> bool {
+        Utc::now().timestamp() < self.expires
+    }
+}
+
+/// The result of downloading a tile
+enum Download {
+    /// The tile changed (or wasn't cached), so this is the new tile and its cache information
+    Modified(Vec<u8>, Option<Metadata>),
+    /// The cached tile is still valid, so this is its new cache information
+    NotModified(Option<Metadata>)
+}
+
+
+/// Gets the image of a tile.
+///
+/// Fresh tiles are read from the cache (or an offline pack). Otherwise, the tile is downloaded and cached, falling back to the stale tile if the download fails.
+pub async fn get_tile(tile_id: &TileId, provider: &TileProvider, config: &Config) -> Result<Vec<u8>> {
+
+    // Local tiles are already on disk, so they aren't cached. They're read on a blocking thread, since reading them can take a while.
+    if provider.is_local() {
+        let (provider, tile_id) = (provider.clone(), *tile_id);
+        return tokio::task::spawn_blocking(move || provider.read_local(&tile_id)).await?;
+    }
+
+    let root = config.path();
+    let cache_path = tile_path(&root.join(CACHE_DIR), provider, tile_id);
+    let pack_path = tile_path(&root.join(PACKS_DIR), provider, tile_id);
+
+    // Prefer the cache, since it's revalidated more often than the offline packs. The tiles are read on a blocking thread, so they don't block the runtime.
+    let cached = {
+        let (cache_path, enabled) = (cache_path.clone(), config.enabled);
+        tokio::task::spawn_blocking(move || match enabled {
+            true => read(&cache_path).or_else(|| read(&pack_path)),
+            false => read(&pack_path)
+        }).await?
+    };
+    match &cached {
+        Some((data, metadata)) if config.offline || metadata.is_fresh() => return Ok(data.clone()),
+        None if config.offline => Err(Error::NotCached)?,
+        _ => {}
+    }
+
+    // Revalidate the stale tile, or download the tile if it isn't cached
+    let result = download(tile_id, provider, cached.as_ref().map(|(_, m)| m)).await;
+    let (data, metadata) = match (result, cached) {
+        (Ok(Download::Modified(data, metadata)), _) => (data, metadata),
+        (Ok(Download::NotModified(metadata)), Some((data, old))) => {
+            // The validators aren't always sent again, so keep the old ones
+            let metadata = metadata.map(|m| Metadata {
+                etag: m.etag.or(old.etag),
+                last_modified: m.last_modified.or(old.last_modified),
+                ..m
+            });
+            (data, metadata)
+        },
+        (Ok(Download::NotModified(_)), None) => Err(Error::TileProvider(StatusCode::NOT_MODIFIED, "The tile isn't cached".to_string()))?,
+        // The tile provider couldn't be reached, so use the stale tile
+        (Err(err), Some((data, _))) => {
+            debug!("Using a stale tile because the tile provider couldn't be reached: {err}");
+            return Ok(data);
+        },
+        (Err(err), None) => return Err(err)
+    };
+
+    // Save the tile to the cache on a blocking thread
+    let data = match metadata.filter(|_| config.enabled) {
+        Some(metadata) => tokio::task::spawn_blocking(move || {
+            if let Err(err) = save(&cache_path, &data, &metadata) {
+                warn!("Failed to save tile to the cache: {err}");
+            }
+            data
+        }).await?,
+        None => data
+    };
+
+    Ok(data)
+}
+
+/// Downloads a tile from the tile provider. If the stale tile's cache information is provided, it's used to revalidate the tile.
+async fn download(tile_id: &TileId, provider: &TileProvider, stale: Option<&Metadata>) -> Result<Download> {
+    let mut request = provider.request(tile_id)?;
+    if let Some(etag) = stale.and_then(|m| m.etag.as_ref()) {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = stale.and_then(|m| m.last_modified.as_ref()) {
+        request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await.map_err(Error::Request)?;
+    let metadata = Metadata::from_headers(response.headers());
+
+    // The stale tile is still valid
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Download::NotModified(metadata));
+    }
+
+    // If the API gave us an error, return it
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(Error::TileProvider(status, response.text().await.map_err(Error::Request)?).into());
+    }
+
+    let data = response.bytes().await.map_err(Error::Request)?;
+    Ok(Download::Modified(data.to_vec(), metadata))
+}
+
+/// The path of a tile in a directory tree, without the file extension. The extension depends on the format of the tile.
+fn tile_path(root: &Path, provider: &TileProvider, tile_id: &TileId) -> PathBuf {
+    root.join(provider.cache_key())
+        .join(tile_id.zoom.to_string())
+        .join(tile_id.x.to_string())
+        .join(tile_id.y.to_string())
+}
+
+/// The file extension of a tile, from the format of its data. Unknown formats are saved as PNG, like the tiles of most providers.
+fn tile_extension(data: &[u8]) -> &'static str {
+    match image::guess_format(data) {
+        Ok(image::ImageFormat::Jpeg) => "jpg",
+        Ok(image::ImageFormat::WebP) => "webp",
+        _ => "png"
+    }
+}
+
+/// Reads a tile in any of the supported formats, and its cache information. Tiles without cache information are treated as expired.
+fn read(path: &Path) -> Option<(Vec<u8>, Metadata)> {
+    let data = TILE_EXTENSIONS.iter().find_map(|extension| fs::read(path.with_extension(extension)).ok())?;
+    let metadata = fs::read(path.with_extension("json")).ok()
+        .and_then(|m| serde_json::from_slice(&m).ok())
+        .unwrap_or_default();
+    Some((data, metadata))
+}
+
+/// Saves a tile and its cache information, with the file extension of its format
+fn save(path: &Path, data: &[u8], metadata: &Metadata) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(Error::Io)?;
+    }
+    let extension = tile_extension(data);
+    fs::write(path.with_extension(extension), data).map_err(Error::Io)?;
+    fs::write(path.with_extension("json"), serde_json::to_vec(metadata).map_err(Error::Json)?).map_err(Error::Io)?;
+
+    // Remove the tile if it was saved in another format before, so the old one isn't read instead
+    for other in TILE_EXTENSIONS.iter().filter(|e| **e != extension) {
+        let _ = fs::remove_file(path.with_extension(other));
+    }
+    Ok(())
+}
+
+
+/// Removes the least recently downloaded tiles if the cache is over its size limit.
+///
+/// This runs in the background, at most once every few minutes. Call it whenever tiles are loaded.
+pub fn prune_if_due(config: &Config) {
+    let mut last_prune = LAST_PRUNE.lock().expect("The tile cache prune lock was poisoned");
+    if last_prune.is_some_and(|p| p.elapsed() < PRUNE_INTERVAL) {
+        return;
+    }
+    *last_prune = Some(Instant::now());
+
+    let root = config.path().join(CACHE_DIR);
+    let limit = config.size_limit * 1_000_000;
+    std::thread::spawn(move || {
+        if let Err(err) = prune(&root, limit) {
+            warn!("Failed to prune the tile cache: {err}");
+        }
+    });
+}
+
+/// Removes the least recently downloaded tiles until the cache is under the size limit, in bytes
+fn prune(root: &Path, limit: u64) -> Result<()> {
+    let mut tiles = Vec::new();
+    find_tiles(root, &mut tiles)?;
+
+    let mut size: u64 = tiles.iter().map(|t| t.1).sum();
+    if size <= limit {
+        return Ok(());
+    }
+
+    // Remove the oldest tiles first
+    tiles.sort_unstable_by_key(|t| t.2);
+    let mut removed = 0;
+    for (path, tile_size, _) in tiles {
+        if size <= limit {
+            break;
+        }
+        fs::remove_file(&path).map_err(Error::Io)?;
+        let _ = fs::remove_file(path.with_extension("json"));
+        size = size.saturating_sub(tile_size);
+        removed += 1;
+    }
+
+    debug!("Removed {removed} tiles from the tile cache");
+    Ok(())
+}
+
+/// Finds the tiles in a directory tree, with their size (including their cache information) and when they were saved
+fn find_tiles(dir: &Path, tiles: &mut Vec<(PathBuf, u64, SystemTime)>) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => Err(Error::Io(err))?
+    };
+
+    for entry in entries {
+        let path = entry.map_err(Error::Io)?.path();
+        if path.is_dir() {
+            find_tiles(&path, tiles)?;
+        } else if path.extension().is_some_and(|e| TILE_EXTENSIONS.iter().any(|t| e == *t)) {
+            let metadata = fs::metadata(&path).map_err(Error::Io)?;
+            let info_size = fs::metadata(path.with_extension("json")).map_or(0, |m| m.len());
+            tiles.push((path, metadata.len() + info_size, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the size of the cache and the offline packs, in bytes
+pub fn usage_promise(config: &Config) -> Promise<Result<(u64, u64)>> {
+    let root = config.path();
+    Promise::spawn_thread("tile_cache_usage", move || {
+        let size = |dir: &str| -> Result<u64> {
+            let mut tiles = Vec::new();
+            find_tiles(&root.join(dir), &mut tiles)?;
+            Ok(tiles.iter().map(|t| t.1).sum())
+        };
+        Ok((size(CACHE_DIR)?, size(PACKS_DIR)?))
+    })
+}
+
+/// Removes every cached tile, or every offline pack
+pub fn clear(config: &Config, packs: bool) -> Result<()> {
+    let dir = config.path().join(if packs { PACKS_DIR } else { CACHE_DIR });
+    match fs::remove_dir_all(dir) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Error::Io(err))?,
+        _ => Ok(())
+    }
+}
+
+
+/// The area of an offline pack, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackArea {
+    /// The northern edge
+    pub north: f64,
+    /// The southern edge
+    pub south: f64,
+    /// The western edge. If this is east of the eastern edge, the area crosses the antimeridian.
+    pub west: f64,
+    /// The eastern edge
+    pub east: f64
+}
+impl PackArea {
+    /// The parts of the area, as rectangles that don't cross the antimeridian
+    fn rects(&self) -> Vec<geo::Rect> {
+        let rect = |west: f64, east: f64| geo::Rect::new(Coord { x: west, y: self.south }, Coord { x: east, y: self.north });
+        match self.west <= self.east {
+            true => vec![rect(self.west, self.east)],
+            false => vec![rect(self.west, 180.0), rect(-180.0, self.east)]
+        }
+    }
+}
+
+/// Calculates the tiles that cover an area at each zoom level. Areas that cross the antimeridian are covered by two ranges of tiles at each zoom level.
+fn pack_tiles(area: &PackArea, zooms: RangeInclusive<u8>) -> impl Iterator<Item = (u8, RangeInclusive<u32>, RangeInclusive<u32>)> {
+    let rects = area.rects();
+    zooms.flat_map(move |zoom| {
+        rects.clone().into_iter().map(move |rect| {
+            // The top left and bottom right tiles
+            let min = TileId::containing(&Coord { x: rect.min().x, y: rect.max().y }, zoom);
+            let max = TileId::containing(&Coord { x: rect.max().x, y: rect.min().y }, zoom);
+            (zoom, min.x..=max.x, min.y..=max.y)
+        })
+    })
+}
+
+/// The number of tiles that cover an area over a range of zoom levels
+pub fn pack_size(area: &PackArea, zooms: RangeInclusive<u8>) -> usize {
+    pack_tiles(area, zooms).map(|(_, x, y)| x.count() * y.count()).sum()
+}
+
+/// A download of an offline pack, which prefetches the tiles that cover an area over a range of zoom levels.
+///
+/// Tiles are downloaded one at a time with a delay between them, so we don't put a load on the tile provider.
+/// This is especially important for OpenStreetMap, whose tile usage policy forbids bulk downloading,
+/// so packs from OpenStreetMap can only have a few detailed tiles (the policy allows up to 250 tiles at zoom level 13 and above).
+pub struct PackDownload {
+    /// The number of tiles in the pack
+    total: usize,
+    /// The number of tiles that were downloaded (or were already in the pack)
+    done: Arc<AtomicUsize>,
+    /// The number of tiles that failed to download
+    failed: Arc<AtomicUsize>,
+    /// Set to cancel the download
+    cancelled: Arc<AtomicBool>,
+    /// The task that is downloading the tiles
+    task: Promise<()>
+}
+impl PackDownload {
+    /// The maximum number of tiles in a pack
+    pub const MAX_TILES: usize = 50_000;
+    /// The maximum number of tiles in a pack from OpenStreetMap
+    pub const MAX_OSM_TILES: usize = 2_000;
+    /// The lowest zoom level that counts as detailed for OpenStreetMap's tile usage policy
+    pub const OSM_DETAIL_ZOOM: u8 = 13;
+    /// The maximum number of detailed tiles in a pack from OpenStreetMap
+    pub const MAX_OSM_DETAIL_TILES: usize = 250;
+    /// The highest zoom level of a pack from OpenStreetMap
+    pub const MAX_OSM_ZOOM: u8 = 16;
+
+    /// Starts downloading the tiles that cover an area over a range of zoom levels
+    pub fn start(area: PackArea, zooms: RangeInclusive<u8>, provider: TileProvider, config: &Config) -> Result<Self, Error> {
+        if provider.is_local() {
+            return Err(Error::LocalProvider);
+        }
+        let total = pack_size(&area, zooms.clone());
+
+        // Keep OpenStreetMap packs small, and rate limit them to 2 tiles per second
+        let (max_tiles, delay) = match provider {
+            TileProvider::OpenStreetMap if *zooms.end() > Self::MAX_OSM_ZOOM => return Err(Error::ZoomTooHigh(Self::MAX_OSM_ZOOM)),
+            TileProvider::OpenStreetMap => {
+                let detailed = pack_size(&area, Self::OSM_DETAIL_ZOOM.max(*zooms.start())..=*zooms.end());
+                if detailed > Self::MAX_OSM_DETAIL_TILES {
+                    return Err(Error::TooManyDetailedTiles(detailed, Self::MAX_OSM_DETAIL_TILES, Self::OSM_DETAIL_ZOOM));
+                }
+                (Self::MAX_OSM_TILES, Duration::from_millis(500))
+            },
+            _ => (Self::MAX_TILES, Duration::from_millis(100))
+        };
+        if total > max_tiles {
+            return Err(Error::TooManyTiles(total, max_tiles));
+        }
+
+        let done = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicUsize::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let root = config.path().join(PACKS_DIR);
+
+        let task = {
+            let (done, failed, cancelled) = (done.clone(), failed.clone(), cancelled.clone());
+            let _eg = RT.enter();
+            Promise::spawn_async(async move {
+                let tiles: Vec<TileId> = pack_tiles(&area, zooms)
+                    .flat_map(|(zoom, xs, ys)| xs.flat_map(move |x| ys.clone().map(move |y| TileId { x, y, zoom })))
+                    .collect();
+
+                for tile_id in tiles {
+                    if cancelled.load(SeqCst) {
+                        break;
+                    }
+
+                    // Skip the tiles that are already in the pack
+                    let path = tile_path(&root, &provider, &tile_id);
+                    let fresh = {
+                        let path = path.clone();
+                        tokio::task::spawn_blocking(move || read(&path).is_some_and(|(_, m)| m.is_fresh())).await.unwrap_or_default()
+                    };
+                    if fresh {
+                        done.fetch_add(1, SeqCst);
+                        continue;
+                    }
+
+                    // Tiles in a pack are always saved, since they're needed offline
+                    let result = match download(&tile_id, &provider, None).await {
+                        Ok(Download::Modified(data, metadata)) => tokio::task::spawn_blocking(move || save(&path, &data, &metadata.unwrap_or_default())).await
+                            .unwrap_or_else(|err| Err(err.into())),
+                        Ok(Download::NotModified(_)) => Ok(()),
+                        Err(err) => Err(err)
+                    };
+                    match result {
+                        Ok(()) => done.fetch_add(1, SeqCst),
+                        Err(err) => {
+                            warn!("Failed to download tile for the offline pack: {err}");
+                            failed.fetch_add(1, SeqCst)
+                        }
+                    };
+
+                    tokio::time::sleep(delay).await;
+                }
+            })
+        };
+
+        Ok(Self { total, done, failed, cancelled, task })
+    }
+
+    /// The number of tiles that were downloaded, the number that failed, and the number in the pack
+    pub fn progress(&self) -> (usize, usize, usize) {
+        (self.done.load(SeqCst), self.failed.load(SeqCst), self.total)
+    }
+
+    /// Has the download finished or been cancelled?
+    pub fn is_finished(&self) -> bool {
+        self.task.ready().is_some()
+    }
+
+    /// Stops the download after the current tile
+    pub fn cancel(&self) {
+        self.cancelled.store(true, SeqCst);
+    }
+}
+impl std::fmt::Debug for PackDownload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PackDownload").field("progress", &self.progress()).finish()
+    }
+}
+
+
+/// Errors regarding the tile cache
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read or write the tile cache: {0}")]
+    Io(std::io::Error),
+    #[error("Failed to parse the cache information of a tile: {0}")]
+    Json(serde_json::Error),
+    #[error("Failed execute request: {0}")]
+    Request(reqwest::Error),
+    #[error("Failed to get tile from the tile provider ({0}): {1}")]
+    TileProvider(StatusCode, String),
+    #[error("The tile isn't cached, and the map is offline")]
+    NotCached,
+    #[error("The pack has {0} tiles, but can only have up to {1}. Try a smaller area or fewer zoom levels.")]
+    TooManyTiles(usize, usize),
+    #[error("The pack has {0} tiles at zoom level {2} and above, but this tile provider only allows up to {1}. Try a smaller area or lower zoom levels.")]
+    TooManyDetailedTiles(usize, usize, u8),
+    #[error("Packs from this tile provider can only go up to zoom level {0}")]
+    ZoomTooHigh(u8),
+    #[error("The map tiles are already on disk, so they don't need an offline pack")]
+    LocalProvider
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    /// Builds the headers of a tile response
+    fn headers(values: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        values.iter().map(|(name, value)| (name.clone(), HeaderValue::from_static(value))).collect()
+    }
+
+    /// Checks that the tile expires after the provided lifetime, allowing for the clock to tick during the test
+    fn assert_lifetime(metadata: &Metadata, now: i64, lifetime: i64) {
+        assert!((now + lifetime..=now + lifetime + 5).contains(&metadata.expires), "expires {} is not {lifetime} seconds after {now}", metadata.expires);
+    }
+
+    #[test]
+    fn metadata_default_lifetime() {
+        let now = Utc::now().timestamp();
+        let metadata = Metadata::from_headers(&HeaderMap::new()).unwrap();
+        assert_lifetime(&metadata, now, DEFAULT_LIFETIME);
+        assert!(metadata.is_fresh());
+        assert_eq!(metadata.etag, None);
+        assert_eq!(metadata.last_modified, None);
+    }
+
+    #[test]
+    fn metadata_validators() {
+        let metadata = Metadata::from_headers(&headers(&[
+            (header::ETAG, "\"4a3b-5f1e\""),
+            (header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+        ])).unwrap();
+        assert_eq!(metadata.etag.as_deref(), Some("\"4a3b-5f1e\""));
+        assert_eq!(metadata.last_modified.as_deref(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+    }
+
+    #[test]
+    fn metadata_no_store() {
+        assert!(Metadata::from_headers(&headers(&[(header::CACHE_CONTROL, "no-store")])).is_none());
+        // No-store wins over everything else
+        assert!(Metadata::from_headers(&headers(&[(header::CACHE_CONTROL, "max-age=3600, No-Store")])).is_none());
+        assert!(Metadata::from_headers(&headers(&[
+            (header::CACHE_CONTROL, "public, no-store"),
+            (header::EXPIRES, "Wed, 21 Oct 2099 07:28:00 GMT")
+        ])).is_none());
+    }
+
+    #[test]
+    fn metadata_max_age() {
+        let now = Utc::now().timestamp();
+        let metadata = Metadata::from_headers(&headers(&[(header::CACHE_CONTROL, "public, max-age=3600")])).unwrap();
+        assert_lifetime(&metadata, now, 3600);
+
+        let metadata = Metadata::from_headers(&headers(&[(header::CACHE_CONTROL, "Max-Age=\"60\"")])).unwrap();
+        assert_lifetime(&metadata, now, 60);
+
+        // The max age wins over the expiry date
+        let metadata = Metadata::from_headers(&headers(&[
+            (header::CACHE_CONTROL, "max-age=600"),
+            (header::EXPIRES, "Wed, 21 Oct 2099 07:28:00 GMT")
+        ])).unwrap();
+        assert_lifetime(&metadata, now, 600);
+
+        // An invalid max age is ignored
+        let metadata = Metadata::from_headers(&headers(&[(header::CACHE_CONTROL, "max-age=soon")])).unwrap();
+        assert_lifetime(&metadata, now, DEFAULT_LIFETIME);
+    }
+
+    #[test]
+    fn metadata_no_cache() {
+        // No-cache wins over the max age, wherever it is
+        let now = Utc::now().timestamp();
+        for cache_control in ["no-cache", "no-cache, max-age=3600", "max-age=3600, no-cache"] {
+            let metadata = Metadata::from_headers(&headers(&[
+                (header::CACHE_CONTROL, cache_control),
+                (header::EXPIRES, "Wed, 21 Oct 2099 07:28:00 GMT")
+            ])).unwrap();
+            assert_lifetime(&metadata, now, 0);
+        }
+    }
+
+    #[test]
+    fn metadata_expires() {
+        let metadata = Metadata::from_headers(&headers(&[(header::EXPIRES, "Wed, 21 Oct 2015 07:28:00 GMT")])).unwrap();
+        assert_eq!(metadata.expires, 1_445_412_480);
+        assert!(!metadata.is_fresh());
+
+        let metadata = Metadata::from_headers(&headers(&[(header::EXPIRES, "Thu, 01 Jan 2099 00:00:00 +0000")])).unwrap();
+        assert_eq!(metadata.expires, 4_070_908_800);
+        assert!(metadata.is_fresh());
+
+        // An invalid expiry date (e.g. `0`, which means already expired) falls back to the default lifetime
+        let now = Utc::now().timestamp();
+        let metadata = Metadata::from_headers(&headers(&[(header::EXPIRES, "0")])).unwrap();
+        assert_lifetime(&metadata, now, DEFAULT_LIFETIME);
+    }
+
+    #[test]
+    fn pack_whole_world() {
+        let world = PackArea { north: 85.0, south: -85.0, west: -180.0, east: 180.0 };
+        assert_eq!(pack_tiles(&world, 0..=1).collect::<Vec<_>>(), vec![(0, 0..=0, 0..=0), (1, 0..=1, 0..=1)]);
+        assert_eq!(pack_size(&world, 0..=0), 1);
+        assert_eq!(pack_size(&world, 0..=2), 1 + 4 + 16);
+        assert_eq!(pack_size(&world, 3..=3), 64);
+    }
+
+    #[test]
+    fn pack_area() {
+        let area = PackArea { north: 10.0, south: -10.0, west: -10.0, east: 10.0 };
+        assert_eq!(pack_tiles(&area, 2..=2).collect::<Vec<_>>(), vec![(2, 1..=2, 1..=2)]);
+        assert_eq!(pack_size(&area, 2..=2), 4);
+
+        // A single point is still covered by a tile
+        let point = PackArea { north: 51.5, south: 51.5, west: -0.1, east: -0.1 };
+        assert_eq!(pack_size(&point, 0..=10), 11);
+
+        // An empty range of zoom levels doesn't have any tiles
+        assert_eq!(pack_size(&area, RangeInclusive::new(3, 2)), 0);
+    }
+
+    #[test]
+    fn pack_antimeridian() {
+        // The area crosses the antimeridian, so it's covered by the tiles on both edges of the map, not every tile in between
+        let area = PackArea { north: 10.0, south: -10.0, west: 170.0, east: -170.0 };
+        assert_eq!(pack_tiles(&area, 2..=2).collect::<Vec<_>>(), vec![(2, 3..=3, 1..=2), (2, 0..=0, 1..=2)]);
+        assert_eq!(pack_size(&area, 2..=2), 4);
+
+        // An area that only touches the antimeridian doesn't cross it
+        let area = PackArea { north: 10.0, south: -10.0, west: 170.0, east: 180.0 };
+        assert_eq!(pack_tiles(&area, 2..=2).collect::<Vec<_>>(), vec![(2, 3..=3, 1..=2)]);
+    }
+}
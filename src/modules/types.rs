@@ -8,8 +8,10 @@ use std::ops::{Add, Div, Mul, Sub};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use chrono::{NaiveDate, NaiveTime};
+use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 use tokio::task::JoinHandle;
+use super::{dxcluster, js8call, rbn, wsjtx};
 
 
 /// A radio contact
@@ -20,6 +22,8 @@ pub struct Contact {
     pub id: Option<surrealdb::sql::Thing>,
     /// The callsign of the receiving station during the contact
     pub callsign: String,
+    /// The grid square of the receiving station during the contact, if known
+    pub grid: String,
     /// The date that the contact began, in UTC
     pub date: NaiveDate,
     /// The time that the contact began, in UTC
@@ -62,6 +66,8 @@ pub enum Mode {
     RTTY,
     /// A form of 8FSK, optimized for the HF bands and only allows for exchanging signal reports
     FT8,
+    /// A faster variant of FT8, designed for contesting
+    FT4,
     /// Inspired by FT8, with the ability to exchange messages
     JS8CALL,
     /// A form of MFSK, optimized for the HF bands, and typically occupies a wide bandwidth
@@ -72,6 +78,27 @@ pub enum Mode {
     #[strum(to_string = "Other")]
     OTHER(String)
 }
+impl Mode {
+    /// Converts the name of a mode (e.g. `"FT8"` or `"USB"`) into a [Mode], case-insensitively.
+    /// 
+    /// This is used when importing contacts from other programs. Unknown modes are stored as [Mode::OTHER].
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_ascii_uppercase().as_str() {
+            "SSB" | "USB" | "LSB" => Self::SSB,
+            "CW" => Self::CW,
+            "AM" => Self::AM,
+            "FM" => Self::FM,
+            "PSK31" | "BPSK31" => Self::PSK31,
+            "RTTY" => Self::RTTY,
+            "FT8" => Self::FT8,
+            "FT4" => Self::FT4,
+            "JS8" | "JS8CALL" => Self::JS8CALL,
+            "OLIVIA" => Self::OLIVIA,
+            "DOMINOEX" | "DOMINO" => Self::DOMINOEX,
+            _ => Self::OTHER(name.trim().to_string())
+        }
+    }
+}
 
 /// Notifications that should be shown to the user through the GUI.
 /// 
@@ -93,6 +120,188 @@ pub enum Event {
     RefreshContacts,
     /// Search for a callsign
     LookupCallsign(String),
+    /// A message was decoded by WSJT-X (or a compatible program such as JTDX or MSHV)
+    WsjtxDecode(wsjtx::Decode),
+    /// WSJT-X (or a compatible program) reported a change in its status
+    WsjtxStatus(wsjtx::Status),
+    /// JS8Call heard a station
+    Js8CallHeard(js8call::HeardStation),
+    /// Prefill the contact logger with information from another source (e.g. the frequency of the radio)
+    PrefillLogger(LoggerPrefill),
+    /// A contact was logged by QLog (i.e. not received from another logger on the network)
+    ContactLogged(Contact),
+    /// A spot was received from the DX cluster
+    DxSpot(dxcluster::Spot),
+    /// A spot was received from the Reverse Beacon Network
+    RbnSpot(rbn::Spot),
+    /// Tune the radio to a frequency, in Hz
+    Tune(u64),
+    /// Scroll the contacts table to a contact, which is found by its callsign, date, and time
+    ShowContact {
+        callsign: String,
+        date: NaiveDate,
+        time: NaiveTime
+    },
+}
+
+/// Information that should be filled into the contact logger. Only the fields that are `Some` are updated.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoggerPrefill {
+    /// The callsign of the other station
+    pub callsign: Option<String>,
+    /// The grid square of the other station
+    pub grid: Option<String>,
+    /// The frequency, in Hz
+    pub frequency: Option<u64>,
+    /// The mode
+    pub mode: Option<Mode>,
+    /// The signal report sent to the other station
+    pub tx_rst: Option<String>,
+    /// The signal report received from the other station
+    pub rx_rst: Option<String>,
+    /// A note about the contact
+    pub note: Option<String>
+}
+
+/// An amateur radio band. `All` is used when filtering by band, and when a frequency is outside of every band.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+pub enum Band {
+    /// All bands
+    All,
+    /// 2200M Band 135KHz
+    B2200m,
+    /// 630M Band 472KHz
+    B630m,
+    /// 160M Band 1.8MHz
+    B160m,
+    /// 80M Band 3.5MHz
+    B80m,
+    /// 60M Band 5.3MHz
+    B60m,
+    /// 40M Band 7MHz
+    B40m,
+    /// 30M Band 10.1MHz
+    B30m,
+    /// 20M Band 14MHz
+    B20m,
+    /// 17M Band 18MHz
+    B17m,
+    /// 15M Band 21MHz
+    B15m,
+    /// 12M Band 24MHz
+    B12m,
+    /// 10M Band 28MHz
+    B10m,
+    /// 6M Band 50MHz
+    B6m,
+    /// 2M Band 144MHz
+    B2m,
+    /// 1.25M Band 222MHz
+    B1_25M,
+    /// 70CM Band 420MHz
+    B70CM,
+    /// 33CM Band 902MHz
+    B33CM,
+    /// 23CM Band 1.24GHz
+    B23CM,
+    /// 2.4GHZ Band 2.4GHz
+    F2_4GHZ,
+    /// 3.4GHZ Band 3.4GHz
+    F3_4GHZ,
+    /// 5.8GHZ Band 5.8GHz
+    F5_8GHZ,
+    /// 10GHZ Band 10GHz
+    F10GHZ,
+    /// 24GHZ Band 24GHz
+    F24GHZ,
+    /// 47GHZ Band 47GHz
+    F47GHZ,
+    /// 76GHZ Band 76GHz
+    F76GHZ
+}
+impl Band {
+    /// Gets the band that contains a frequency (in Hz), or `All` if the frequency isn't in an amateur radio band
+    pub fn from_frequency(frequency: u64) -> Self {
+        Self::iter()
+            .find(|b| b.freq_range().is_some_and(|(min, max)| (min..=max).contains(&frequency)))
+            .unwrap_or(Self::All)
+    }
+
+    /// Return the frequency range of the band, or None if the band is All
+    pub fn freq_range(&self) -> Option<(u64, u64)> {
+        match self {
+            Band::All => None,
+            Band::B2200m => Some((135_700, 137_800)),
+            Band::B630m => Some((472_000, 479_000)),
+            Band::B160m => Some((1_800_000, 2_000_000)),
+            Band::B80m => Some((3_500_000, 4_000_000)),
+            Band::B60m => Some((5_330_500, 5_407_800)),
+            Band::B40m => Some((7_000_000, 7_300_000)), 
+            Band::B30m => Some((10_100_000, 10_150_000)),
+            Band::B20m => Some((14_000_000, 14_350_000)),
+            Band::B17m => Some((18_068_000, 18_168_000)),
+            Band::B15m => Some((21_000_000, 21_450_000)), 
+            Band::B12m => Some((24_890_000, 24_990_000)), 
+            Band::B10m => Some((28_000_000, 29_700_000)),
+            Band::B6m => Some((50_000_000, 54_000_000)),
+            Band::B2m => Some((144_000_000, 148_000_000)),
+            Band::B1_25M => Some((219_000_000, 225_000_000)), 
+            Band::B70CM => Some((420_000_000, 450_000_000)),
+            Band::B33CM => Some((902_000_000, 928_000_000)), 
+            Band::B23CM => Some((1_240_000_000, 1_300_000_000)),
+            Band::F2_4GHZ => Some((2_300_000_000, 2_450_000_000)), 
+            Band::F3_4GHZ => Some((3_300_000_000, 3_500_000_000)), 
+            Band::F5_8GHZ => Some((5_650_000_000, 5_925_000_000)), 
+            Band::F10GHZ => Some((10_000_000_000, 10_500_000_000)),
+            Band::F24GHZ => Some((24_000_000_000, 24_250_000_000)), 
+            Band::F47GHZ => Some((47_000_000_000, 47_200_000_000)), 
+            Band::F76GHZ => Some((76_000_000_000, 81_000_000_000)), 
+        }
+    }
+
+    /// Return the name of the band as a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Band::All => "All",
+            Band::B2200m => "2200M",
+            Band::B630m => "630M",
+            Band::B160m => "160M",
+            Band::B80m => "80M",
+            Band::B60m => "60M",
+            Band::B40m => "40M",
+            Band::B30m => "30M",
+            Band::B20m => "20M",
+            Band::B17m => "17M",
+            Band::B15m => "15M",
+            Band::B12m => "12M",
+            Band::B10m => "10M",
+            Band::B6m => "6M",
+            Band::B2m => "2M",
+            Band::B1_25M => "1.25M",
+            Band::B70CM => "70CM",
+            Band::B33CM => "33CM",
+            Band::B23CM => "23CM",
+            Band::F2_4GHZ => "2.4GHZ",
+            Band::F3_4GHZ => "3.4GHZ",
+            Band::F5_8GHZ => "5.8GHZ",
+            Band::F10GHZ => "10GHZ",
+            Band::F24GHZ => "24GHZ",
+            Band::F47GHZ => "47GHZ",
+            Band::F76GHZ => "76GHZ",
+        }
+    }
+}
+
+/// Information about the operator's own station, used to fill macros and outgoing messages
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StationProfile {
+    /// The callsign of the station
+    pub callsign: String,
+    /// The name of the operator
+    pub name: String,
+    /// The grid square of the station
+    pub grid: String
 }
 
 /// The distance unit used by the GUI
@@ -109,6 +318,19 @@ impl DistanceUnit {
             Self::Miles => meters * 0.0006213712
         }
     }
+
+    /// The abbreviation of the unit (e.g. `km`)
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            Self::Kilometers => "km",
+            Self::Miles => "mi"
+        }
+    }
+}
+
+/// Escapes the characters that can't be used in XML text
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
 }
 
 /// Converts a value from one range into a value in another range
@@ -0,0 +1,757 @@
+//
+// A listener for the WSJT-X UDP message protocol. JTDX and MSHV speak the same protocol, so they are supported too.
+//
+// The protocol is documented in the WSJT-X source tree (Network/NetworkMessage.hpp). Every message is serialized using Qt's QDataStream,
+// which means integers are big-endian, strings are prefixed with a u32 length, and dates/times use the Julian day number.
+//
+
+use std::time::Duration;
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use log::{debug, error, info, trace, warn};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{net::UdpSocket, sync::mpsc, task::JoinHandle};
+use crate::{GuiConfig, RT};
+use super::{gui, types};
+
+
+/// The magic number at the start of every WSJT-X message
+const MAGIC: u32 = 0xADBC_CBDA;
+/// The largest UDP datagram we're willing to receive. WSJT-X messages are usually a few hundred bytes.
+const MAX_DATAGRAM_SIZE: usize = 8192;
+/// The Julian day number of 0001-01-01 (minus 1), used to convert a Julian day into a chrono date
+const JULIAN_DAY_OFFSET: i64 = 1_721_425;
+
+
+/// A message sent by WSJT-X
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// Sent periodically by WSJT-X so we know it's alive
+    Heartbeat(Heartbeat),
+    /// Sent when the state of WSJT-X changes (frequency, mode, DX call, etc)
+    Status(Status),
+    /// A new (or replayed) decoded message
+    Decode(Decode),
+    /// The operator logged a QSO
+    QsoLogged(QsoLogged),
+    /// The operator logged a QSO, formatted as an ADIF record
+    LoggedAdif {
+        /// The ID of the client that sent the message
+        id: String,
+        /// The ADIF text
+        adif: String
+    },
+    /// Any other message that we don't need to handle. This contains the message type.
+    Other(u32)
+}
+impl Message {
+    /// The message type of a heartbeat message
+    const TYPE_HEARTBEAT: u32 = 0;
+    /// The message type of a status message
+    const TYPE_STATUS: u32 = 1;
+    /// The message type of a decode message
+    const TYPE_DECODE: u32 = 2;
+    /// The message type of a QSO logged message
+    const TYPE_QSO_LOGGED: u32 = 5;
+    /// The message type of a logged ADIF message
+    const TYPE_LOGGED_ADIF: u32 = 12;
+
+    /// Parses a UDP datagram into a [Message]
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(data);
+
+        // Ensure the datagram is actually a WSJT-X message
+        let magic = reader.u32()?;
+        if magic != MAGIC {
+            return Err(Error::InvalidMagic(magic));
+        }
+
+        // Read the schema version, message type, and client ID
+        let _schema = reader.u32()?;
+        let message_type = reader.u32()?;
+        let id = reader.utf8()?;
+
+        let message = match message_type {
+            Self::TYPE_HEARTBEAT => Self::Heartbeat(Heartbeat {
+                id,
+                max_schema: reader.u32()?,
+                version: reader.utf8().unwrap_or_default(),
+                revision: reader.utf8().unwrap_or_default()
+            }),
+            Self::TYPE_STATUS => {
+                // The fields up to the DX grid have existed since the first version of the protocol, anything after that is optional
+                let mut status = Status {
+                    id,
+                    dial_frequency: reader.u64()?,
+                    mode: reader.utf8()?,
+                    dx_call: reader.utf8()?,
+                    report: reader.utf8()?,
+                    tx_mode: reader.utf8()?,
+                    tx_enabled: reader.bool()?,
+                    transmitting: reader.bool()?,
+                    decoding: reader.bool()?,
+                    rx_df: reader.u32()?,
+                    tx_df: reader.u32()?,
+                    de_call: reader.utf8()?,
+                    de_grid: reader.utf8()?,
+                    dx_grid: reader.utf8()?,
+                    ..Default::default()
+                };
+                status.tx_watchdog = reader.bool().unwrap_or_default();
+                status.sub_mode = reader.utf8().unwrap_or_default();
+                status.fast_mode = reader.bool().unwrap_or_default();
+                Self::Status(status)
+            },
+            Self::TYPE_DECODE => {
+                let mut decode = Decode {
+                    id,
+                    new: reader.bool()?,
+                    time: reader.time()?,
+                    snr: reader.i32()?,
+                    delta_time: reader.f64()?,
+                    delta_frequency: reader.u32()?,
+                    mode: reader.utf8()?,
+                    message: reader.utf8()?,
+                    ..Default::default()
+                };
+                decode.low_confidence = reader.bool().unwrap_or_default();
+                decode.off_air = reader.bool().unwrap_or_default();
+                Self::Decode(decode)
+            },
+            Self::TYPE_QSO_LOGGED => {
+                let mut qso = QsoLogged {
+                    id,
+                    time_off: reader.date_time()?,
+                    dx_call: reader.utf8()?,
+                    dx_grid: reader.utf8()?,
+                    tx_frequency: reader.u64()?,
+                    mode: reader.utf8()?,
+                    report_sent: reader.utf8()?,
+                    report_received: reader.utf8()?,
+                    tx_power: reader.utf8()?,
+                    comments: reader.utf8()?,
+                    name: reader.utf8()?,
+                    time_on: reader.date_time()?,
+                    ..Default::default()
+                };
+                qso.operator_call = reader.utf8().unwrap_or_default();
+                qso.my_call = reader.utf8().unwrap_or_default();
+                qso.my_grid = reader.utf8().unwrap_or_default();
+                qso.exchange_sent = reader.utf8().unwrap_or_default();
+                qso.exchange_received = reader.utf8().unwrap_or_default();
+                Self::QsoLogged(qso)
+            },
+            Self::TYPE_LOGGED_ADIF => Self::LoggedAdif {
+                id,
+                adif: reader.utf8()?
+            },
+            other => Self::Other(other)
+        };
+
+        Ok(message)
+    }
+}
+
+/// A heartbeat message
+#[derive(Debug, Default, Clone)]
+pub struct Heartbeat {
+    /// The ID of the client that sent the message
+    pub id: String,
+    /// The highest schema version supported by the client
+    pub max_schema: u32,
+    /// The version of the client
+    pub version: String,
+    /// The revision of the client
+    pub revision: String
+}
+
+/// A status message
+#[derive(Debug, Default, Clone)]
+pub struct Status {
+    /// The ID of the client that sent the message
+    pub id: String,
+    /// The dial frequency of the radio, in Hz
+    pub dial_frequency: u64,
+    /// The current mode (e.g. `FT8`)
+    pub mode: String,
+    /// The callsign of the station we're working
+    pub dx_call: String,
+    /// The signal report for the DX station
+    pub report: String,
+    /// The transmit mode
+    pub tx_mode: String,
+    /// Is transmitting enabled?
+    pub tx_enabled: bool,
+    /// Is the station currently transmitting?
+    pub transmitting: bool,
+    /// Is the client currently decoding?
+    pub decoding: bool,
+    /// The receive offset, in Hz
+    pub rx_df: u32,
+    /// The transmit offset, in Hz
+    pub tx_df: u32,
+    /// Our callsign
+    pub de_call: String,
+    /// Our grid square
+    pub de_grid: String,
+    /// The grid square of the station we're working
+    pub dx_grid: String,
+    /// Has the TX watchdog timed out?
+    pub tx_watchdog: bool,
+    /// The sub mode, if any
+    pub sub_mode: String,
+    /// Is fast mode enabled?
+    pub fast_mode: bool
+}
+
+/// A decoded message
+#[derive(Debug, Default, Clone)]
+pub struct Decode {
+    /// The ID of the client that sent the message
+    pub id: String,
+    /// Is this a new decode? This is false for decodes that are replayed when we ask the client for them.
+    pub new: bool,
+    /// The time of the decode, in UTC
+    pub time: NaiveTime,
+    /// The signal to noise ratio, in dB
+    pub snr: i32,
+    /// The time offset of the decode, in seconds
+    pub delta_time: f64,
+    /// The audio frequency offset of the decode, in Hz
+    pub delta_frequency: u32,
+    /// The mode of the decode, as a single character (e.g. `~` for FT8)
+    pub mode: String,
+    /// The decoded text
+    pub message: String,
+    /// Was the decode low confidence?
+    pub low_confidence: bool,
+    /// Was the decode from a recording instead of the radio?
+    pub off_air: bool
+}
+impl Decode {
+    /// Returns the callsign of the station that sent the decoded message, if it could be determined.
+    ///
+    /// Examples:
+    /// - `CQ K1ABC FN42` -> `K1ABC`
+    /// - `CQ DX K1ABC FN42` -> `K1ABC`
+    /// - `W9XYZ K1ABC -10` -> `K1ABC`
+    pub fn de_callsign(&self) -> Option<&str> {
+        let mut words = self.message.split_whitespace();
+
+        let callsign = match words.next()? {
+            // A CQ can have a modifier before the callsign (e.g. `CQ DX`, `CQ POTA`, or `CQ 123`)
+            "CQ" | "QRZ" => {
+                let word = words.next()?;
+                match words.clone().next() {
+                    // If there are at least two more words, and this word doesn't look like a callsign, it's a modifier
+                    Some(next) if !looks_like_callsign(word) && looks_like_callsign(next) => words.next()?,
+                    _ => word
+                }
+            },
+            // A directed message is formatted as `TO FROM ...`
+            _ => words.next()?
+        };
+
+        // Strip the angle brackets that are used for hashed callsigns
+        let callsign = callsign.trim_start_matches('<').trim_end_matches('>');
+
+        looks_like_callsign(callsign).then_some(callsign)
+    }
+
+    /// Returns true if the decoded message is a CQ
+    pub fn is_cq(&self) -> bool {
+        self.message.starts_with("CQ ")
+    }
+
+    /// Returns the grid square at the end of the decoded message, if there is one (e.g. `CQ K1ABC FN42` -> `FN42`)
+    pub fn grid(&self) -> Option<&str> {
+        let word = self.message.split_whitespace().last()?;
+        let bytes = word.as_bytes();
+
+        // A grid is two letters from A to R followed by two digits. `RR73` looks like a grid, but it isn't one.
+        let valid = bytes.len() == 4 && word != "RR73"
+            && bytes[..2].iter().all(|b| (b'A'..=b'R').contains(b))
+            && bytes[2..].iter().all(u8::is_ascii_digit);
+
+        valid.then_some(word)
+    }
+}
+
+/// A QSO logged message
+#[derive(Debug, Default, Clone)]
+pub struct QsoLogged {
+    /// The ID of the client that sent the message
+    pub id: String,
+    /// The date and time that the QSO ended, in UTC
+    pub time_off: NaiveDateTime,
+    /// The callsign of the station we worked
+    pub dx_call: String,
+    /// The grid square of the station we worked
+    pub dx_grid: String,
+    /// The transmit frequency, in Hz
+    pub tx_frequency: u64,
+    /// The mode used during the QSO
+    pub mode: String,
+    /// The signal report we sent
+    pub report_sent: String,
+    /// The signal report we received
+    pub report_received: String,
+    /// The transmit power, as entered by the operator
+    pub tx_power: String,
+    /// Any comments entered by the operator
+    pub comments: String,
+    /// The name of the DX operator
+    pub name: String,
+    /// The date and time that the QSO started, in UTC
+    pub time_on: NaiveDateTime,
+    /// The callsign of the operator
+    pub operator_call: String,
+    /// Our callsign
+    pub my_call: String,
+    /// Our grid square
+    pub my_grid: String,
+    /// The contest exchange we sent
+    pub exchange_sent: String,
+    /// The contest exchange we received
+    pub exchange_received: String
+}
+impl QsoLogged {
+    /// Converts the logged QSO into a [types::Contact] that can be inserted into the database
+    pub fn to_contact(&self) -> types::Contact {
+
+        // Combine the operator's name and comments into the note
+        let note = match (self.name.is_empty(), self.comments.is_empty()) {
+            (false, false) => format!("{} - {}", self.name, self.comments),
+            (false, true) => self.name.clone(),
+            _ => self.comments.clone()
+        };
+
+        types::Contact {
+            id: None,
+            callsign: self.dx_call.to_ascii_uppercase(),
+            grid: self.dx_grid.clone(),
+            date: self.time_on.date(),
+            time: self.time_on.time(),
+            duration: self.time_off.signed_duration_since(self.time_on).num_seconds().max(0) as u64,
+            frequency: self.tx_frequency,
+            mode: types::Mode::from_name(&self.mode),
+            // WSJT-X doesn't specify a unit for the power, so we parse it like any other power input (watts by default)
+            tx_power: gui::power_parser(&self.tx_power).unwrap_or_default() as u64,
+            rx_power: 0,
+            tx_rst: self.report_sent.clone(),
+            rx_rst: self.report_received.clone(),
+            note
+        }
+    }
+}
+
+
+/// A simple QDataStream reader
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    /// Reads `N` bytes from the datagram
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let end = self.position + N;
+        let bytes = self.data.get(self.position..end).ok_or(Error::Truncated)?;
+        self.position = end;
+        // This can't fail since the slice is exactly N bytes long
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.bytes()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_be_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.bytes()?))
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_be_bytes(self.bytes()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_be_bytes(self.bytes()?))
+    }
+
+    /// Reads a UTF-8 string. A null string (length `0xFFFFFFFF`) is returned as an empty string.
+    fn utf8(&mut self) -> Result<String, Error> {
+        let length = self.u32()?;
+
+        // This is a null string
+        if length == u32::MAX {
+            return Ok(String::new());
+        }
+
+        let end = self.position + length as usize;
+        let bytes = self.data.get(self.position..end).ok_or(Error::Truncated)?;
+        self.position = end;
+
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Reads a QTime (milliseconds since midnight)
+    fn time(&mut self) -> Result<NaiveTime, Error> {
+        let ms = self.u32()?;
+        NaiveTime::from_num_seconds_from_midnight_opt(ms / 1000, (ms % 1000) * 1_000_000).ok_or(Error::InvalidDateTime)
+    }
+
+    /// Reads a QDateTime (Julian day, milliseconds since midnight, and a timespec)
+    fn date_time(&mut self) -> Result<NaiveDateTime, Error> {
+        let julian_day = self.i64()?;
+        let time = self.time()?;
+        let timespec = self.u8()?;
+
+        // A timespec of 2 means the date time has an offset from UTC, which is followed by the offset in seconds.
+        // WSJT-X always uses UTC, but we still have to read the offset to keep the reader aligned.
+        if timespec == 2 {
+            let _offset = self.i32()?;
+        }
+
+        let date = NaiveDate::from_num_days_from_ce_opt((julian_day - JULIAN_DAY_OFFSET) as i32).ok_or(Error::InvalidDateTime)?;
+
+        Ok(date.and_time(time))
+    }
+}
+
+
+/// Listens for WSJT-X messages on a UDP socket in the background
+pub struct Listener {
+    /// The address that the listener is bound to
+    address: String,
+    /// The receiving end of the messages that were received by the listener task
+    rx: mpsc::UnboundedReceiver<Message>,
+    /// The listener task
+    task: JoinHandle<()>,
+    /// The tasks that are currently inserting logged QSOs into the database
+    insert_tasks: Vec<Promise<Result<types::Contact>>>
+}
+impl Listener {
+    /// How long to wait before retrying if we failed to bind to the socket
+    const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+    /// Starts listening for WSJT-X messages on the provided address (e.g. `127.0.0.1:2237`)
+    pub fn new(address: String) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let task = RT.spawn(Self::listen(address.clone(), tx));
+
+        Self {
+            address,
+            rx,
+            task,
+            insert_tasks: Default::default()
+        }
+    }
+
+    /// The listener task. This binds to the socket and forwards every message it receives to `tx` until the receiver is dropped.
+    async fn listen(address: String, tx: mpsc::UnboundedSender<Message>) {
+
+        // Try to bind to the socket, retrying if we fail (e.g. the port is in use)
+        let socket = loop {
+            match UdpSocket::bind(&address).await {
+                Ok(s) => break s,
+                Err(err) => {
+                    error!("Failed to bind WSJT-X listener to '{address}': {err}");
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                    if tx.is_closed() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        info!("Listening for WSJT-X messages on '{address}'");
+
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf).await {
+                Ok(r) => r,
+                Err(err) => {
+                    // Back off so a persistent error doesn't spin the task
+                    warn!("Failed to receive WSJT-X message: {err}");
+                    tokio::time::sleep(Self::RETRY_DELAY).await;
+                    if tx.is_closed() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            match Message::parse(&buf[..len]) {
+                Ok(message) => {
+                    // The GUI stopped listening, so stop the task
+                    if tx.send(message).is_err() {
+                        return;
+                    }
+                },
+                Err(err) => warn!("Failed to parse WSJT-X message from {from}: {err}")
+            }
+        }
+
+    }
+}
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+impl std::fmt::Debug for Listener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Listener")
+        .field("address", &self.address)
+        .field("insert_tasks", &self.insert_tasks.len())
+        .finish()
+    }
+}
+
+/// Processes any messages received by the WSJT-X listener, starting or stopping the listener depending on the config.
+///
+/// Call this each frame.
+pub fn tick(config: &mut GuiConfig) {
+
+    // Take the listener out of the config so we can mutate the rest of the config while processing messages
+    let mut listener = config.wsjtx.take();
+
+    // Start, stop, or restart the listener if the config changed
+    if !config.wsjtx_config.enabled {
+        listener = None;
+    } else if listener.as_ref().map_or(true, |l| l.address != config.wsjtx_config.address) {
+        listener = Some(Listener::new(config.wsjtx_config.address.clone()));
+    }
+
+    let Some(mut listener) = listener else {
+        return;
+    };
+
+    // Process the received messages
+    while let Ok(message) = listener.rx.try_recv() {
+        match message {
+            Message::Heartbeat(heartbeat) => trace!("Received WSJT-X heartbeat from '{}' ({} {})", heartbeat.id, heartbeat.version, heartbeat.revision),
+            Message::Status(status) => config.events.push_back((None, types::Event::WsjtxStatus(status))),
+            Message::Decode(decode) => config.events.push_back((None, types::Event::WsjtxDecode(decode))),
+            Message::QsoLogged(qso) => {
+                debug!("WSJT-X logged a QSO with {}", qso.dx_call);
+
+                // Insert the contact into the database if automatic logging is enabled
+                if config.wsjtx_config.auto_log {
+                    listener.insert_tasks.push(config.db_api.insert_contact_promise(qso.to_contact()));
+                }
+            },
+            // The QSO logged message contains the same information, so we only use the ADIF message for debugging
+            Message::LoggedAdif { id, adif } => trace!("Received ADIF record from '{id}': {adif}"),
+            Message::Other(message_type) => trace!("Ignoring WSJT-X message type {message_type}")
+        }
+    }
+
+    // Process any finished insert tasks
+    for task in listener.insert_tasks.extract_if(|t| t.ready().is_some()) {
+        match task.block_and_take() {
+            Ok(contact) => {
+                config.events.push_back((None, types::Event::RefreshContacts));
+                config.events.push_back((None, types::Event::ContactLogged(contact.clone())));
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Info(format!("Logged {} from WSJT-X", contact.callsign)));
+            },
+            Err(err) => {
+                error!("Failed to insert contact from WSJT-X: {err}");
+                config.notification_read = false;
+                config.notifications.push(types::Notification::Error(format!("Failed to log contact from WSJT-X: {err}")));
+            }
+        }
+    }
+
+    // Put the listener back into the config
+    config.wsjtx = Some(listener);
+
+}
+
+/// Returns true if the string looks like a callsign (i.e. it contains at least one letter and one digit)
+fn looks_like_callsign(s: &str) -> bool {
+    let s = s.trim_start_matches('<').trim_end_matches('>');
+    s.len() >= 3
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '/')
+        && s.chars().any(|c| c.is_ascii_digit())
+        && s.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+
+/// The WSJT-X module config
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Should we listen for WSJT-X messages?
+    pub enabled: bool,
+    /// The address to listen on. This should match the "UDP Server" setting in WSJT-X.
+    pub address: String,
+    /// Should QSOs logged in WSJT-X automatically be inserted into the database?
+    pub auto_log: bool
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:2237".into(),
+            auto_log: true
+        }
+    }
+}
+
+/// Errors regarding the WSJT-X module
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("The message doesn't start with the WSJT-X magic number (got {0:#X})")]
+    InvalidMagic(u32),
+    #[error("The message ended unexpectedly")]
+    Truncated,
+    #[error("The message contains an invalid date or time")]
+    InvalidDateTime
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A heartbeat from WSJT-X 2.6.1
+    const HEARTBEAT: &[u8] = &[
+        0xad, 0xbc, 0xcb, 0xda, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06,
+        0x57, 0x53, 0x4a, 0x54, 0x2d, 0x58, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x05, 0x32, 0x2e,
+        0x36, 0x2e, 0x31, 0x00, 0x00, 0x00, 0x06, 0x65, 0x34, 0x61, 0x36, 0x62, 0x37,
+    ];
+
+    /// A status message while working K1ABC on 20m FT8, with a null sub mode
+    const STATUS: &[u8] = &[
+        0xad, 0xbc, 0xcb, 0xda, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06,
+        0x57, 0x53, 0x4a, 0x54, 0x2d, 0x58, 0x00, 0x00, 0x00, 0x00, 0x00, 0xd6, 0xc0, 0x90, 0x00, 0x00,
+        0x00, 0x03, 0x46, 0x54, 0x38, 0x00, 0x00, 0x00, 0x05, 0x4b, 0x31, 0x41, 0x42, 0x43, 0x00, 0x00,
+        0x00, 0x03, 0x2d, 0x31, 0x30, 0x00, 0x00, 0x00, 0x03, 0x46, 0x54, 0x38, 0x01, 0x00, 0x01, 0x00,
+        0x00, 0x05, 0xdc, 0x00, 0x00, 0x04, 0xb0, 0x00, 0x00, 0x00, 0x05, 0x57, 0x39, 0x58, 0x59, 0x5a,
+        0x00, 0x00, 0x00, 0x04, 0x45, 0x4e, 0x35, 0x32, 0x00, 0x00, 0x00, 0x04, 0x46, 0x4e, 0x34, 0x32,
+        0x00, 0xff, 0xff, 0xff, 0xff, 0x00,
+    ];
+
+    /// A decode of `CQ K1ABC FN42` at 12:34:45
+    const DECODE: &[u8] = &[
+        0xad, 0xbc, 0xcb, 0xda, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x06,
+        0x57, 0x53, 0x4a, 0x54, 0x2d, 0x58, 0x01, 0x02, 0xb2, 0xfe, 0x88, 0xff, 0xff, 0xff, 0xf4, 0x3f,
+        0xc9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a, 0x00, 0x00, 0x04, 0xd2, 0x00, 0x00, 0x00, 0x01, 0x7e,
+        0x00, 0x00, 0x00, 0x0d, 0x43, 0x51, 0x20, 0x4b, 0x31, 0x41, 0x42, 0x43, 0x20, 0x46, 0x4e, 0x34,
+        0x32, 0x00, 0x00,
+    ];
+
+    /// A QSO with K1ABC in FT4, logged on 2023-02-24 from 12:34:00 to 12:35:30
+    const QSO_LOGGED: &[u8] = &[
+        0xad, 0xbc, 0xcb, 0xda, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x06,
+        0x57, 0x53, 0x4a, 0x54, 0x2d, 0x58, 0x00, 0x00, 0x00, 0x00, 0x00, 0x25, 0x89, 0x60, 0x02, 0xb3,
+        0xae, 0x50, 0x01, 0x00, 0x00, 0x00, 0x05, 0x4b, 0x31, 0x41, 0x42, 0x43, 0x00, 0x00, 0x00, 0x04,
+        0x46, 0x4e, 0x34, 0x32, 0x00, 0x00, 0x00, 0x00, 0x00, 0xd6, 0xc5, 0x62, 0x00, 0x00, 0x00, 0x03,
+        0x46, 0x54, 0x34, 0x00, 0x00, 0x00, 0x03, 0x2d, 0x31, 0x30, 0x00, 0x00, 0x00, 0x03, 0x2d, 0x31,
+        0x32, 0x00, 0x00, 0x00, 0x03, 0x31, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03,
+        0x42, 0x6f, 0x62, 0x00, 0x00, 0x00, 0x00, 0x00, 0x25, 0x89, 0x60, 0x02, 0xb2, 0x4e, 0xc0, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x57, 0x39, 0x58, 0x59, 0x5a, 0x00, 0x00, 0x00,
+        0x04, 0x45, 0x4e, 0x35, 0x32, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn parses_heartbeat() {
+        let Message::Heartbeat(heartbeat) = Message::parse(HEARTBEAT).unwrap() else {
+            panic!("expected a heartbeat");
+        };
+        assert_eq!(heartbeat.id, "WSJT-X");
+        assert_eq!(heartbeat.max_schema, 3);
+        assert_eq!(heartbeat.version, "2.6.1");
+        assert_eq!(heartbeat.revision, "e4a6b7");
+    }
+
+    #[test]
+    fn parses_status() {
+        let Message::Status(status) = Message::parse(STATUS).unwrap() else {
+            panic!("expected a status");
+        };
+        assert_eq!(status.dial_frequency, 14_074_000);
+        assert_eq!(status.mode, "FT8");
+        assert_eq!(status.dx_call, "K1ABC");
+        assert_eq!(status.report, "-10");
+        assert!(status.tx_enabled && !status.transmitting && status.decoding);
+        assert_eq!((status.rx_df, status.tx_df), (1500, 1200));
+        assert_eq!(status.de_call, "W9XYZ");
+        assert_eq!(status.de_grid, "EN52");
+        assert_eq!(status.dx_grid, "FN42");
+        assert_eq!(status.sub_mode, "");
+        assert!(!status.fast_mode);
+    }
+
+    #[test]
+    fn parses_decode() {
+        let Message::Decode(decode) = Message::parse(DECODE).unwrap() else {
+            panic!("expected a decode");
+        };
+        assert!(decode.new);
+        assert_eq!(decode.time, NaiveTime::from_hms_opt(12, 34, 45).unwrap());
+        assert_eq!(decode.snr, -12);
+        assert!((decode.delta_time - 0.2).abs() < 1e-9);
+        assert_eq!(decode.delta_frequency, 1234);
+        assert_eq!(decode.mode, "~");
+        assert_eq!(decode.message, "CQ K1ABC FN42");
+        assert!(decode.is_cq());
+        assert_eq!(decode.de_callsign(), Some("K1ABC"));
+        assert_eq!(decode.grid(), Some("FN42"));
+    }
+
+    #[test]
+    fn parses_qso_logged() {
+        let Message::QsoLogged(qso) = Message::parse(QSO_LOGGED).unwrap() else {
+            panic!("expected a logged QSO");
+        };
+        let date = NaiveDate::from_ymd_opt(2023, 2, 24).unwrap();
+        assert_eq!(qso.time_on, date.and_hms_opt(12, 34, 0).unwrap());
+        assert_eq!(qso.time_off, date.and_hms_opt(12, 35, 30).unwrap());
+        assert_eq!(qso.dx_call, "K1ABC");
+        assert_eq!(qso.tx_frequency, 14_075_234);
+        assert_eq!(qso.my_call, "W9XYZ");
+
+        let contact = qso.to_contact();
+        assert_eq!(contact.mode, types::Mode::FT4);
+        assert_eq!(contact.duration, 90);
+        assert_eq!(contact.tx_rst, "-10");
+        assert_eq!(contact.rx_rst, "-12");
+        assert_eq!(contact.tx_power, 100_000); // milliwatts
+        assert_eq!(contact.note, "Bob");
+    }
+
+    #[test]
+    fn rejects_truncated_datagram() {
+        // Cut the decode off in the middle of the message text
+        assert!(matches!(Message::parse(&DECODE[..DECODE.len() - 8]), Err(Error::Truncated)));
+        assert!(matches!(Message::parse(&HEARTBEAT[..3]), Err(Error::Truncated)));
+    }
+
+    #[test]
+    fn rejects_invalid_magic() {
+        let mut data = HEARTBEAT.to_vec();
+        data[0] = 0;
+        assert!(matches!(Message::parse(&data), Err(Error::InvalidMagic(0x00BC_CBDA))));
+    }
+}